@@ -252,6 +252,7 @@ pub struct Data {
     market: market_worker::Data,
     assets: BTreeMap<AssetId, api::Asset>,
     amp_assets: BTreeSet<AssetId>,
+    assets_cache: Arc<assets_registry::AssetsCache>,
     msg_sender: mpsc::Sender<Message>,
     ws_sender: UnboundedSender<ws::WrappedRequest>,
     ws_hint: UnboundedSender<()>,
@@ -278,6 +279,7 @@ pub enum Message {
     WalletEvent(Account, wallet::Event),
     WalletNotif(Account, WalletNotif),
     BackgroundMessage(String, mpsc::Sender<()>),
+    AssetsRefreshed,
     Quit,
 }
 
@@ -325,7 +327,14 @@ impl Data {
         asset_ids: impl Iterator<Item = &'a AssetId>,
     ) -> Result<Vec<api::Asset>, anyhow::Error> {
         let asset_ids = asset_ids.copied().collect::<Vec<_>>();
-        assets_registry::get_assets(self.env, self.master_xpub(), asset_ids, self.proxy())
+        let master_xpub = self.master_xpub();
+        let loaded = self
+            .assets_cache
+            .get_assets(master_xpub, asset_ids, self.proxy())?;
+        if !loaded.unknown.is_empty() {
+            debug!("unknown GDK registry assets: {:?}", loaded.unknown);
+        }
+        Ok(loaded.assets)
     }
 
     fn merge_txs(txs: BTreeMap<Account, TransactionList>) -> TransactionList {
@@ -2116,8 +2125,53 @@ impl Data {
             self.recreate_wallets();
         }
 
+        self.refresh_gdk_assets(self.proxy().clone());
+    }
+
+    /// Sends `Message::AssetsRefreshed` once the downloaded registry updates are cached
+    fn refresh_gdk_assets(&mut self, proxy: Option<ProxyAddress>) {
         let master_xpub = self.master_xpub();
-        assets_registry::refresh(self.env, master_xpub, self.proxy().clone());
+        self.assets_cache.refresh(master_xpub, proxy);
+        let refreshed = self.assets_cache.wait_refreshed();
+        let msg_sender = self.msg_sender.clone();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .expect("must not fail");
+            runtime.block_on(refreshed);
+            let _ = msg_sender.send(Message::AssetsRefreshed);
+        });
+    }
+
+    /// Updates the assets that were registered before their icons were downloaded
+    fn process_assets_refreshed(&mut self) {
+        let asset_ids = self
+            .assets
+            .values()
+            .filter(|asset| asset.icon.is_none())
+            .map(|asset| asset.asset_id)
+            .collect::<Vec<_>>();
+        if asset_ids.is_empty() {
+            return;
+        }
+        let gdk_assets = match self.load_gdk_assets(asset_ids.iter()) {
+            Ok(gdk_assets) => gdk_assets,
+            Err(err) => {
+                warn!("loading GDK assets failed: {err}");
+                return;
+            }
+        };
+        for gdk_asset in gdk_assets {
+            if gdk_asset.icon.is_none() {
+                continue;
+            }
+            if let Some(asset) = self.assets.get(&gdk_asset.asset_id).cloned() {
+                self.register_asset(api::Asset {
+                    icon: gdk_asset.icon,
+                    ..asset
+                });
+            }
+        }
     }
 
     fn process_proxy_settings(&mut self, req: proto::to::ProxySettings) {
@@ -2169,7 +2223,7 @@ impl Data {
                     self.proxy_address = proxy_new.clone();
                     self.recreate_wallets();
                     self.restart_websocket();
-                    assets_registry::refresh(self.env, self.master_xpub(), proxy_new);
+                    self.refresh_gdk_assets(proxy_new);
                 }
             }
             Err(err) => {
@@ -3608,6 +3662,7 @@ pub fn start_processing(
         market,
         assets: BTreeMap::new(),
         amp_assets: BTreeSet::new(),
+        assets_cache: assets_registry::AssetsCache::new(env),
         msg_sender,
         ws_sender,
         ws_hint,
@@ -3641,6 +3696,7 @@ pub fn start_processing(
             Message::WalletEvent(account_id, event) => data.process_wallet_event(account_id, event),
             Message::WalletNotif(account_id, msg) => data.process_wallet_notif(account_id, msg),
             Message::BackgroundMessage(msg, sender) => data.process_background_message(msg, sender),
+            Message::AssetsRefreshed => data.process_assets_refreshed(),
             Message::Quit => {
                 warn!("quit message received, exit");
                 break;
@@ -1,9 +1,17 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use sideswap_api::{Asset, AssetId, IssuancePrevout, Ticker};
 use sideswap_common::env::Env;
 use sideswap_types::{asset_precision::AssetPrecision, proxy_address::ProxyAddress};
 
+/// Cached registry lookups older than this are loaded again
+const MAX_CACHE_AGE: Duration = Duration::from_secs(24 * 3600);
+
 pub fn init(registry_path: &std::path::Path) {
     if let Err(error) = gdk_registry::init(registry_path) {
         match error {
@@ -13,24 +21,144 @@ pub fn init(registry_path: &std::path::Path) {
     }
 }
 
-pub fn refresh(env: Env, xpub: bitcoin::bip32::Xpub, proxy: Option<ProxyAddress>) {
-    std::thread::spawn(move || {
-        gdk_registry::refresh_assets(gdk_registry::RefreshAssetsParams {
-            assets: true,
-            icons: true,
-            xpub: Some(xpub),
-            config: get_registry_config(env, &proxy),
+pub struct LoadedAssets {
+    /// Found assets, in the requested order
+    pub assets: Vec<Asset>,
+    /// Requested asset ids that are not in the registry
+    pub unknown: Vec<AssetId>,
+}
+
+struct CachedAsset {
+    asset: Asset,
+    loaded_at: Instant,
+}
+
+/// Serves repeated registry lookups from memory.
+/// The cached assets are reloaded after every background refresh (to pick up the downloaded icons).
+pub struct AssetsCache {
+    env: Env,
+    assets: Mutex<HashMap<AssetId, CachedAsset>>,
+    /// The number of running refreshes
+    refreshing: tokio::sync::watch::Sender<usize>,
+}
+
+impl AssetsCache {
+    pub fn new(env: Env) -> Arc<AssetsCache> {
+        Arc::new(AssetsCache {
+            env,
+            assets: Mutex::new(HashMap::new()),
+            refreshing: tokio::sync::watch::Sender::new(0),
         })
-    });
+    }
+
+    fn store(&self, assets: &[Asset]) {
+        let now = Instant::now();
+        let mut cached = self.assets.lock().expect("must not fail");
+        for asset in assets {
+            cached.insert(
+                asset.asset_id,
+                CachedAsset {
+                    asset: asset.clone(),
+                    loaded_at: now,
+                },
+            );
+        }
+    }
+
+    /// Only the assets that are not cached (or cached too long ago) are looked up in the registry
+    pub fn get_assets(
+        &self,
+        xpub: bitcoin::bip32::Xpub,
+        asset_ids: Vec<AssetId>,
+        proxy: &Option<ProxyAddress>,
+    ) -> Result<LoadedAssets, anyhow::Error> {
+        let missing = {
+            let cached = self.assets.lock().expect("must not fail");
+            asset_ids
+                .iter()
+                .filter(|asset_id| {
+                    cached
+                        .get(asset_id)
+                        .is_none_or(|cached| cached.loaded_at.elapsed() > MAX_CACHE_AGE)
+                })
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        let mut unknown = Vec::new();
+        if !missing.is_empty() {
+            let loaded = get_assets(self.env, xpub, missing, proxy)?;
+            self.store(&loaded.assets);
+            unknown = loaded.unknown;
+        }
+
+        let cached = self.assets.lock().expect("must not fail");
+        let assets = asset_ids
+            .iter()
+            .filter_map(|asset_id| cached.get(asset_id))
+            .map(|cached| cached.asset.clone())
+            .collect();
+        Ok(LoadedAssets { assets, unknown })
+    }
+
+    /// Downloads the registry updates in the background, see `wait_refreshed`
+    pub fn refresh(self: &Arc<Self>, xpub: bitcoin::bip32::Xpub, proxy: Option<ProxyAddress>) {
+        self.refreshing.send_modify(|refreshing| *refreshing += 1);
+        let cache = Arc::clone(self);
+        std::thread::spawn(move || {
+            let res = gdk_registry::refresh_assets(gdk_registry::RefreshAssetsParams {
+                assets: true,
+                icons: true,
+                xpub: Some(xpub),
+                config: get_registry_config(cache.env, &proxy),
+            });
+            match res {
+                Ok(_source) => {
+                    let asset_ids = cache
+                        .assets
+                        .lock()
+                        .expect("must not fail")
+                        .keys()
+                        .copied()
+                        .collect::<Vec<_>>();
+                    if !asset_ids.is_empty() {
+                        match get_assets(cache.env, xpub, asset_ids, &proxy) {
+                            Ok(loaded) => cache.store(&loaded.assets),
+                            Err(err) => log::error!("reloading cached assets failed: {err}"),
+                        }
+                    }
+                }
+                Err(err) => log::error!("registry refresh failed: {err}"),
+            }
+            cache.refreshing.send_modify(|refreshing| *refreshing -= 1);
+        });
+    }
+
+    /// Resolves once no refresh is running (successful or not), immediately if no refresh was started
+    pub fn wait_refreshed(&self) -> impl Future<Output = ()> {
+        let mut receiver = self.refreshing.subscribe();
+        async move {
+            // The sender is owned by the cache, it can't be dropped while the receiver is used
+            let _ = receiver.wait_for(|refreshing| *refreshing == 0).await;
+        }
+    }
+}
+
+fn get_registry_xpub(
+    xpub: &bitcoin::bip32::Xpub,
+) -> Result<gdk_common::bitcoin::bip32::Xpub, anyhow::Error> {
+    gdk_common::bitcoin::bip32::Xpub::decode(&xpub.encode())
+        .map_err(|err| anyhow::anyhow!("can't convert xpub: {err}"))
 }
 
+/// Unknown asset ids are returned in `LoadedAssets::unknown`
 pub fn get_assets(
     env: Env,
     xpub: bitcoin::bip32::Xpub,
     asset_ids: Vec<AssetId>,
     proxy: &Option<ProxyAddress>,
-) -> Result<Vec<Asset>, anyhow::Error> {
-    let xpub = gdk_common::bitcoin::bip32::Xpub::decode(&xpub.encode()).unwrap();
+) -> Result<LoadedAssets, anyhow::Error> {
+    let xpub = get_registry_xpub(&xpub)?;
     let loaded_assets = gdk_registry::get_assets(gdk_registry::GetAssetsParams {
         assets_id: Some(asset_ids.clone()),
         xpub: Some(xpub),
@@ -40,40 +168,42 @@ pub fn get_assets(
         category: None,
     })?;
 
-    let result = asset_ids
-        .iter()
-        .filter_map(|asset_id| {
-            loaded_assets.assets.get(asset_id).map(|v| {
-                let icon = loaded_assets.icons.get(asset_id).cloned();
-                let default_ticker = || format!("{:0.4}", &asset_id.to_string());
-                Asset {
-                    asset_id: *asset_id,
-                    name: v.name.clone(),
-                    ticker: Ticker(v.ticker.clone().unwrap_or_else(default_ticker)),
-                    icon,
-                    precision: AssetPrecision::new(v.precision)
-                        .expect("only precision in the 0..8 range is allowed in the GDK registry"),
-                    icon_url: None,
-                    instant_swaps: Some(false),
-                    domain: v.entity["domain"].as_str().map(|s| s.to_owned()),
-                    domain_agent: None,
-                    domain_agent_link: None,
-                    always_show: None,
-                    issuance_prevout: Some(IssuancePrevout {
-                        txid: v.issuance_prevout.txid,
-                        vout: v.issuance_prevout.vout,
-                    }),
-                    issuer_pubkey: Some(v.issuer_pubkey.clone()),
-                    contract: Some(v.contract.clone()),
-                    market_type: Some(sideswap_api::MarketType::Token),
-                    server_fee: None,
-                    amp_asset_restrictions: None,
-                    payjoin: None,
-                }
-            })
-        })
-        .collect();
-    Ok(result)
+    let mut assets = Vec::new();
+    let mut unknown = Vec::new();
+    for asset_id in asset_ids.iter() {
+        let Some(v) = loaded_assets.assets.get(asset_id) else {
+            unknown.push(*asset_id);
+            continue;
+        };
+        let icon = loaded_assets.icons.get(asset_id).cloned();
+        let default_ticker = || format!("{:0.4}", &asset_id.to_string());
+        let precision = AssetPrecision::new(v.precision)
+            .map_err(|err| anyhow::anyhow!("invalid precision of asset {asset_id}: {err}"))?;
+        assets.push(Asset {
+            asset_id: *asset_id,
+            name: v.name.clone(),
+            ticker: Ticker(v.ticker.clone().unwrap_or_else(default_ticker)),
+            icon,
+            precision,
+            icon_url: None,
+            instant_swaps: Some(false),
+            domain: v.entity["domain"].as_str().map(|s| s.to_owned()),
+            domain_agent: None,
+            domain_agent_link: None,
+            always_show: None,
+            issuance_prevout: Some(IssuancePrevout {
+                txid: v.issuance_prevout.txid,
+                vout: v.issuance_prevout.vout,
+            }),
+            issuer_pubkey: Some(v.issuer_pubkey.clone()),
+            contract: Some(v.contract.clone()),
+            market_type: Some(sideswap_api::MarketType::Token),
+            server_fee: None,
+            amp_asset_restrictions: None,
+            payjoin: None,
+        });
+    }
+    Ok(LoadedAssets { assets, unknown })
 }
 
 fn get_registry_config(env: Env, proxy: &Option<ProxyAddress>) -> gdk_registry::Config {
@@ -7,6 +7,7 @@ pub trait Cipher {
 }
 
 pub mod aes;
+pub mod keyring;
 
 pub enum Info {}
 
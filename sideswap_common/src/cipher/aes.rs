@@ -1,4 +1,7 @@
-use aes_gcm_siv::{aead::Aead, AeadCore, Aes256GcmSiv, KeyInit, Nonce};
+use aes_gcm_siv::{
+    aead::{Aead, Payload},
+    AeadCore, Aes256GcmSiv, KeyInit, Nonce,
+};
 
 use super::Cipher;
 
@@ -8,14 +11,14 @@ impl AesCipher {
     pub fn new(key: &[u8; 32]) -> Self {
         Self(Aes256GcmSiv::new(key.into()))
     }
-}
-
-impl Cipher for AesCipher {
-    type Error = aes_gcm_siv::Error;
 
-    fn encrypt(&mut self, data: &[u8]) -> Vec<u8> {
+    /// Returns the nonce and the ciphertext, `aad` is authenticated but not included
+    pub(super) fn seal(&self, aad: &[u8], data: &[u8]) -> Vec<u8> {
         let nonce = Aes256GcmSiv::generate_nonce(rand::thread_rng());
-        let encrypted = self.0.encrypt(&nonce, data).expect("must not fail");
+        let encrypted = self
+            .0
+            .encrypt(&nonce, Payload { msg: data, aad })
+            .expect("must not fail");
 
         let mut output = Vec::new();
         output.extend_from_slice(&nonce);
@@ -23,12 +26,30 @@ impl Cipher for AesCipher {
         output
     }
 
-    fn decrypt(&mut self, data: &[u8]) -> Result<Vec<u8>, Self::Error> {
+    pub(super) fn open(&self, aad: &[u8], data: &[u8]) -> Result<Vec<u8>, aes_gcm_siv::Error> {
         if data.len() < std::mem::size_of::<aes_gcm_siv::Nonce>() {
             return Err(aes_gcm_siv::aead::Error);
         }
         let (nonce, encrypted_data) = data.split_at(std::mem::size_of::<aes_gcm_siv::Nonce>());
         let nonce = Nonce::from_slice(nonce);
-        self.0.decrypt(nonce, encrypted_data.as_ref())
+        self.0.decrypt(
+            nonce,
+            Payload {
+                msg: encrypted_data,
+                aad,
+            },
+        )
+    }
+}
+
+impl Cipher for AesCipher {
+    type Error = aes_gcm_siv::Error;
+
+    fn encrypt(&mut self, data: &[u8]) -> Vec<u8> {
+        self.seal(&[], data)
+    }
+
+    fn decrypt(&mut self, data: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        self.open(&[], data)
     }
 }
@@ -0,0 +1,87 @@
+use std::collections::BTreeMap;
+
+use super::{aes::AesCipher, Cipher};
+
+/// AES-256-GCM-SIV, the same as `AesCipher` but with the envelope header
+const VERSION_AES_GCM_SIV: u8 = 1;
+
+const HEADER_LEN: usize = 1 + std::mem::size_of::<KeyId>();
+
+pub type KeyId = u32;
+
+#[derive(Debug, thiserror::Error)]
+#[error("duplicate key id {0}")]
+pub struct DuplicateKeyId(pub KeyId);
+
+/// Encrypts with the current key and decrypts with any known key (current or retired).
+/// Envelope format: `version (1 byte) || key_id (4 bytes, big-endian) || nonce || ciphertext`,
+/// the version and the key id are authenticated as associated data.
+/// Blobs without the header (`nonce || ciphertext`, written by `AesCipher`) are version 0.
+pub struct CipherKeyring {
+    primary: KeyId,
+    current: KeyId,
+    keys: BTreeMap<KeyId, AesCipher>,
+}
+
+impl CipherKeyring {
+    /// The first key is the primary one, it is also used to decrypt the legacy (version 0) blobs
+    pub fn new(key_id: KeyId, key: &[u8; 32]) -> Self {
+        CipherKeyring {
+            primary: key_id,
+            current: key_id,
+            keys: BTreeMap::from([(key_id, AesCipher::new(key))]),
+        }
+    }
+
+    /// Adds a key that is only used for decryption
+    pub fn add_retired(&mut self, key_id: KeyId, key: &[u8; 32]) -> Result<(), DuplicateKeyId> {
+        if self.keys.contains_key(&key_id) {
+            return Err(DuplicateKeyId(key_id));
+        }
+        self.keys.insert(key_id, AesCipher::new(key));
+        Ok(())
+    }
+
+    /// Makes the new key current, the previous one is kept for decryption
+    pub fn rotate(&mut self, key_id: KeyId, key: &[u8; 32]) -> Result<(), DuplicateKeyId> {
+        self.add_retired(key_id, key)?;
+        self.current = key_id;
+        Ok(())
+    }
+
+    pub fn current_key_id(&self) -> KeyId {
+        self.current
+    }
+
+    fn decrypt_envelope(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let (header, encrypted) = data.split_first_chunk::<HEADER_LEN>()?;
+        let (version, key_id) = header.split_first()?;
+        if *version != VERSION_AES_GCM_SIV {
+            return None;
+        }
+        let key_id = KeyId::from_be_bytes(key_id.try_into().expect("must not fail"));
+        self.keys.get(&key_id)?.open(header, encrypted).ok()
+    }
+}
+
+impl Cipher for CipherKeyring {
+    type Error = aes_gcm_siv::Error;
+
+    fn encrypt(&mut self, data: &[u8]) -> Vec<u8> {
+        let mut header = [0; HEADER_LEN];
+        header[0] = VERSION_AES_GCM_SIV;
+        header[1..].copy_from_slice(&self.current.to_be_bytes());
+
+        let mut output = header.to_vec();
+        output.extend(self.keys[&self.current].seal(&header, data));
+        output
+    }
+
+    fn decrypt(&mut self, data: &[u8]) -> Result<Vec<u8>, Self::Error> {
+        // A legacy blob can start with a valid header by chance (the nonce is random)
+        match self.decrypt_envelope(data) {
+            Some(decrypted) => Ok(decrypted),
+            None => self.keys[&self.primary].open(&[], data),
+        }
+    }
+}
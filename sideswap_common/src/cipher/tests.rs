@@ -1,6 +1,6 @@
 use rand::Rng;
 
-use super::{aes::AesCipher, Cipher};
+use super::{aes::AesCipher, keyring::CipherKeyring, Cipher};
 
 fn generate_random_vector(n: usize) -> Vec<u8> {
     let mut rng = rand::thread_rng();
@@ -40,3 +40,56 @@ fn test_aes() {
     let cipher = AesCipher::new(&key);
     test_cipher(cipher);
 }
+
+#[test]
+fn test_keyring() {
+    let cipher = CipherKeyring::new(1, &generate_random_key());
+    test_cipher(cipher);
+}
+
+#[test]
+fn keyring_rotation() {
+    let key_a = generate_random_key();
+    let key_b = generate_random_key();
+    let data_a = generate_random_vector(100);
+    let data_b = generate_random_vector(100);
+
+    let mut keyring = CipherKeyring::new(1, &key_a);
+    let encrypted_a = keyring.encrypt(&data_a);
+
+    keyring.rotate(2, &key_b).unwrap();
+    assert_eq!(keyring.current_key_id(), 2);
+    let encrypted_b = keyring.encrypt(&data_b);
+    assert_eq!(keyring.decrypt(&encrypted_a).unwrap(), data_a);
+    assert_eq!(keyring.decrypt(&encrypted_b).unwrap(), data_b);
+
+    // Loaded again, with key A retired
+    let mut keyring = CipherKeyring::new(2, &key_b);
+    keyring.decrypt(&encrypted_a).unwrap_err();
+    keyring.add_retired(1, &key_a).unwrap();
+    assert_eq!(keyring.decrypt(&encrypted_a).unwrap(), data_a);
+    assert_eq!(keyring.decrypt(&encrypted_b).unwrap(), data_b);
+
+    keyring.rotate(1, &key_b).unwrap_err();
+    assert_eq!(keyring.current_key_id(), 2);
+}
+
+#[test]
+fn keyring_decrypts_legacy_blobs() {
+    let key_a = generate_random_key();
+    let key_b = generate_random_key();
+
+    for _ in 0..1000 {
+        let data = generate_random_vector(10);
+        let legacy = AesCipher::new(&key_a).encrypt(&data);
+
+        let mut keyring = CipherKeyring::new(1, &key_a);
+        keyring.rotate(2, &key_b).unwrap();
+        assert_eq!(keyring.decrypt(&legacy).unwrap(), data);
+
+        // Only the primary key is used for the legacy blobs
+        let mut keyring = CipherKeyring::new(2, &key_b);
+        keyring.add_retired(1, &key_a).unwrap();
+        keyring.decrypt(&legacy).unwrap_err();
+    }
+}
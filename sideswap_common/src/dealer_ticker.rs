@@ -65,6 +65,78 @@ impl std::fmt::Display for DealerTicker {
 
 impl std::error::Error for InvalidTickerError {}
 
+#[derive(Debug, thiserror::Error)]
+pub enum TickerResolveError {
+    #[error("unknown ticker: {ticker}{}{}", format_suggestions(.suggestions), format_supported(.supported.as_deref()))]
+    Unknown {
+        ticker: String,
+        /// Known tickers that look similar to the requested one
+        suggestions: Vec<DealerTicker>,
+        /// All known tickers (only set if the list is short)
+        supported: Option<Vec<DealerTicker>>,
+    },
+    #[error("ambiguous ticker: {ticker}, matches: {}", format_list(.candidates))]
+    Ambiguous {
+        ticker: String,
+        candidates: Vec<DealerTicker>,
+    },
+}
+
+fn format_list(tickers: &[DealerTicker]) -> String {
+    tickers
+        .iter()
+        .map(DealerTicker::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn format_suggestions(suggestions: &[DealerTicker]) -> String {
+    if suggestions.is_empty() {
+        String::new()
+    } else {
+        format!(", did you mean: {}", format_list(suggestions))
+    }
+}
+
+fn format_supported(supported: Option<&[DealerTicker]>) -> String {
+    match supported {
+        Some(supported) => format!(", supported tickers: {}", format_list(supported)),
+        None => String::new(),
+    }
+}
+
+/// Used for case-insensitive ticker matching ("L-BTC", "lbtc" and "Lbtc" are the same)
+fn normalize_ticker(value: &str) -> String {
+    value
+        .chars()
+        .filter(|c| !matches!(c, '-' | '_' | ' '))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
+
+/// Tickers with a larger edit distance are not suggested
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+/// The full list of supported tickers is reported only if there are not too many of them
+const MAX_SUPPORTED_LIST_LEN: usize = 16;
+
+const DEFAULT_TICKER_ALIASES: [(&str, DealerTicker); 2] =
+    [("LBTC", DealerTicker::LBTC), ("USDT", DealerTicker::USDT)];
+
 impl std::str::FromStr for DealerTicker {
     type Err = InvalidTickerError;
 
@@ -97,14 +169,26 @@ impl<'de> serde::Deserialize<'de> for DealerTicker {
     }
 }
 
+#[derive(Clone)]
 pub struct TickerLoader {
     asset_ids: BTreeMap<AssetId, DealerTicker>,
     precisions: BTreeMap<DealerTicker, AssetPrecision>,
     tickers: BTreeMap<DealerTicker, AssetId>,
+    /// Normalized alias -> canonical ticker
+    aliases: BTreeMap<String, DealerTicker>,
 }
 
+pub type TickerAliases = BTreeMap<String, DealerTicker>;
+
 pub type WhitelistedAssets = Vec<AssetId>;
 
+fn default_aliases() -> BTreeMap<String, DealerTicker> {
+    DEFAULT_TICKER_ALIASES
+        .iter()
+        .map(|(alias, ticker)| (normalize_ticker(alias), *ticker))
+        .collect()
+}
+
 impl TickerLoader {
     pub fn new(gdk_registry: &GdkRegistryCache, network: Network) -> TickerLoader {
         let known_assets = &network.d().known_assets;
@@ -112,6 +196,7 @@ impl TickerLoader {
             asset_ids: BTreeMap::new(),
             precisions: BTreeMap::new(),
             tickers: BTreeMap::new(),
+            aliases: default_aliases(),
         };
 
         ticker_loader
@@ -171,37 +256,159 @@ impl TickerLoader {
                 .ok_or_else(|| anyhow!("asset {asset_id} has no ticker"))?;
             let ticker = DealerTicker::from_str(&ticker.0)?;
 
-            let old_value = self.asset_ids.insert(asset.asset_id, ticker);
-            assert!(old_value.is_none());
-
-            let old_value = self.tickers.insert(ticker, asset.asset_id);
-            assert!(old_value.is_none());
-
-            let old_value = self.precisions.insert(ticker, asset.precision);
-            assert!(old_value.is_none());
+            self.insert_asset(asset.asset_id, ticker, asset.precision);
         }
 
         Ok(())
     }
 
+    fn insert_asset(&mut self, asset_id: AssetId, ticker: DealerTicker, precision: AssetPrecision) {
+        let old_value = self.asset_ids.insert(asset_id, ticker);
+        assert!(old_value.is_none());
+
+        let old_value = self.tickers.insert(ticker, asset_id);
+        assert!(old_value.is_none());
+
+        let old_value = self.precisions.insert(ticker, precision);
+        assert!(old_value.is_none());
+    }
+
+    /// Build the loader from a fixed list of assets (no asset registry lookups)
+    pub fn from_assets(
+        assets: impl IntoIterator<Item = (AssetId, DealerTicker, AssetPrecision)>,
+    ) -> TickerLoader {
+        let mut ticker_loader = TickerLoader {
+            asset_ids: BTreeMap::new(),
+            precisions: BTreeMap::new(),
+            tickers: BTreeMap::new(),
+            aliases: default_aliases(),
+        };
+        for (asset_id, ticker, precision) in assets {
+            ticker_loader.insert_asset(asset_id, ticker, precision);
+        }
+        ticker_loader
+    }
+
     pub async fn load(
         work_dir: &Path,
         whitelisted_assets: Option<&WhitelistedAssets>,
         network: Network,
     ) -> Result<TickerLoader, anyhow::Error> {
         let gdk_registry = GdkRegistryCache::new(network, work_dir).await;
+        TickerLoader::from_registry(&gdk_registry, whitelisted_assets, network)
+    }
 
-        let mut ticker_loader = TickerLoader::new(&gdk_registry, network);
+    /// Same as `load`, but with an already loaded registry (it can be kept to rebuild the loader later)
+    pub fn from_registry(
+        gdk_registry: &GdkRegistryCache,
+        whitelisted_assets: Option<&WhitelistedAssets>,
+        network: Network,
+    ) -> Result<TickerLoader, anyhow::Error> {
+        let mut ticker_loader = TickerLoader::new(gdk_registry, network);
 
         if let Some(config) = whitelisted_assets {
             for asset_id in config {
-                ticker_loader.add_asset(&gdk_registry, asset_id)?;
+                ticker_loader.add_asset(gdk_registry, asset_id)?;
             }
         }
 
         Ok(ticker_loader)
     }
 
+    /// Add the assets from `other` that are not known yet, returns the added tickers.
+    /// The known assets are never changed, assets with an already used ticker are skipped.
+    pub fn merge(&mut self, other: &TickerLoader) -> Vec<DealerTicker> {
+        let mut added = Vec::new();
+        for (asset_id, ticker) in other.asset_ids.iter() {
+            if self.asset_ids.contains_key(asset_id) {
+                continue;
+            }
+            if self.tickers.contains_key(ticker) {
+                log::warn!("skip asset {asset_id}, ticker {ticker} is already used");
+                continue;
+            }
+            self.insert_asset(*asset_id, *ticker, other.precision(*ticker));
+            added.push(*ticker);
+        }
+        added
+    }
+
+    /// Register additional ticker aliases (matched case-insensitively).
+    /// All aliases must point to the known tickers.
+    pub fn add_aliases(&mut self, aliases: &TickerAliases) -> Result<(), anyhow::Error> {
+        for (alias, ticker) in aliases {
+            anyhow::ensure!(
+                self.has_ticker(*ticker),
+                "alias {alias} points to unknown ticker {ticker}"
+            );
+            self.aliases.insert(normalize_ticker(alias), *ticker);
+        }
+        Ok(())
+    }
+
+    /// Find the canonical ticker for user input.
+    /// Matching is case-insensitive, ignores separators and accepts the known aliases.
+    pub fn resolve_ticker(&self, value: &str) -> Result<DealerTicker, TickerResolveError> {
+        if let Ok(ticker) = DealerTicker::from_str(value) {
+            if self.has_ticker(ticker) {
+                return Ok(ticker);
+            }
+        }
+
+        let normalized = normalize_ticker(value);
+
+        let mut candidates = self
+            .tickers
+            .keys()
+            .filter(|ticker| normalize_ticker(ticker.as_str()) == normalized)
+            .copied()
+            .collect::<Vec<_>>();
+
+        if let Some(ticker) = self.aliases.get(&normalized) {
+            if self.has_ticker(*ticker) && !candidates.contains(ticker) {
+                candidates.push(*ticker);
+            }
+        }
+
+        match candidates.len() {
+            0 => {
+                let mut suggestions = self
+                    .tickers
+                    .keys()
+                    .map(|ticker| {
+                        (
+                            edit_distance(&normalized, &normalize_ticker(ticker.as_str())),
+                            *ticker,
+                        )
+                    })
+                    .filter(|(distance, _ticker)| *distance <= MAX_SUGGESTION_DISTANCE)
+                    .collect::<Vec<_>>();
+                suggestions.sort();
+
+                let supported = (self.tickers.len() <= MAX_SUPPORTED_LIST_LEN)
+                    .then(|| self.tickers.keys().copied().collect());
+
+                Err(TickerResolveError::Unknown {
+                    ticker: value.to_owned(),
+                    suggestions: suggestions
+                        .into_iter()
+                        .map(|(_distance, ticker)| ticker)
+                        .collect(),
+                    supported,
+                })
+            }
+            1 => Ok(candidates[0]),
+            _ => Err(TickerResolveError::Ambiguous {
+                ticker: value.to_owned(),
+                candidates,
+            }),
+        }
+    }
+
+    pub fn tickers(&self) -> impl Iterator<Item = DealerTicker> + '_ {
+        self.tickers.keys().copied()
+    }
+
     pub fn has_ticker(&self, ticker: DealerTicker) -> bool {
         self.tickers.contains_key(&ticker)
     }
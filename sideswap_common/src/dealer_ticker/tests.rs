@@ -19,3 +19,110 @@ fn long_ticker_name() {
 fn too_long_err() {
     let _err = DealerTicker::from_str("123456789").unwrap_err();
 }
+
+fn test_loader(tickers: &[&str]) -> TickerLoader {
+    TickerLoader::from_assets(tickers.iter().enumerate().map(|(index, ticker)| {
+        let asset_id = AssetId::from_slice(&[index as u8 + 1; 32]).unwrap();
+        (
+            asset_id,
+            DealerTicker::from_str(ticker).unwrap(),
+            AssetPrecision::BITCOIN_PRECISION,
+        )
+    }))
+}
+
+#[test]
+fn resolve_case_variations() {
+    let loader = test_loader(&["L-BTC", "USDt", "EURx", "DePix"]);
+
+    for value in ["L-BTC", "l-btc", "lbtc", "Lbtc", "LBTC", "l_btc"] {
+        assert_eq!(loader.resolve_ticker(value).unwrap(), DealerTicker::LBTC);
+    }
+    for value in ["USDt", "usdt", "USDT", "Usdt"] {
+        assert_eq!(loader.resolve_ticker(value).unwrap(), DealerTicker::USDT);
+    }
+    assert_eq!(loader.resolve_ticker("depix").unwrap(), DealerTicker::DEPIX);
+}
+
+#[test]
+fn resolve_aliases() {
+    let mut loader = test_loader(&["L-BTC", "USDt"]);
+
+    loader
+        .add_aliases(&TickerAliases::from([(
+            "Tether".to_owned(),
+            DealerTicker::USDT,
+        )]))
+        .unwrap();
+    assert_eq!(loader.resolve_ticker("TETHER").unwrap(), DealerTicker::USDT);
+    assert_eq!(loader.resolve_ticker("tether").unwrap(), DealerTicker::USDT);
+
+    loader
+        .add_aliases(&TickerAliases::from([(
+            "euro".to_owned(),
+            DealerTicker::EURX,
+        )]))
+        .unwrap_err();
+}
+
+#[test]
+fn resolve_ambiguous() {
+    let loader = test_loader(&["USDt", "USDT"]);
+
+    assert_eq!(
+        loader.resolve_ticker("USDT").unwrap(),
+        DealerTicker::from_str("USDT").unwrap()
+    );
+
+    match loader.resolve_ticker("usdt").unwrap_err() {
+        TickerResolveError::Ambiguous { candidates, .. } => assert_eq!(candidates.len(), 2),
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn resolve_suggestions() {
+    let loader = test_loader(&["L-BTC", "USDt", "EURx", "MEX"]);
+
+    let err = loader.resolve_ticker("USD").unwrap_err();
+    match &err {
+        TickerResolveError::Unknown {
+            suggestions,
+            supported,
+            ..
+        } => {
+            assert_eq!(suggestions, &[DealerTicker::USDT]);
+            assert_eq!(supported.as_ref().unwrap().len(), 4);
+        }
+        err => panic!("unexpected error: {err}"),
+    }
+    assert_eq!(
+        err.to_string(),
+        "unknown ticker: USD, did you mean: USDt, supported tickers: EURx, L-BTC, MEX, USDt"
+    );
+
+    match loader.resolve_ticker("DOGECOIN").unwrap_err() {
+        TickerResolveError::Unknown { suggestions, .. } => assert!(suggestions.is_empty()),
+        err => panic!("unexpected error: {err}"),
+    }
+}
+
+#[test]
+fn merge_adds_only_new_assets() {
+    let mut loader = test_loader(&["L-BTC", "USDt"]);
+    let asset = |index: u8| AssetId::from_slice(&[index; 32]).unwrap();
+
+    let other = TickerLoader::from_assets([
+        (asset(1), DealerTicker::LBTC, AssetPrecision::BITCOIN_PRECISION),
+        // The known asset with another ticker and the known ticker with another asset
+        (asset(2), DealerTicker::EURX, AssetPrecision::BITCOIN_PRECISION),
+        (asset(9), DealerTicker::USDT, AssetPrecision::BITCOIN_PRECISION),
+        (asset(3), DealerTicker::DEPIX, AssetPrecision::TWO),
+    ]);
+    assert_eq!(loader.merge(&other), [DealerTicker::DEPIX]);
+    assert_eq!(loader.ticker(&asset(2)), Some(DealerTicker::USDT));
+    assert_eq!(*loader.asset_id(DealerTicker::USDT), asset(2));
+    assert_eq!(loader.precision(DealerTicker::DEPIX).value(), 2);
+
+    assert!(loader.merge(&other).is_empty());
+}
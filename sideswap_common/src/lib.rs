@@ -23,6 +23,7 @@ pub mod pin;
 pub mod price_stream;
 pub mod pset;
 pub mod pset_blind;
+pub mod quote_amounts;
 pub mod random_id;
 pub mod recipient;
 pub mod registration;
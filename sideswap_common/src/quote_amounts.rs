@@ -0,0 +1,152 @@
+//! Swap amounts derived from the quote numbers reported by the server.
+//! The same function is used to process quotes and to explain them, so the two can't diverge.
+
+use sideswap_api::mkt::{AssetType, TradeDir};
+use sideswap_types::asset_precision::AssetPrecision;
+
+use crate::types::asset_float_amount_;
+
+/// The numbers from `QuoteStatus::Success` and the market details
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct QuoteNumbers {
+    pub base_amount: u64,
+    pub quote_amount: u64,
+    pub server_fee: u64,
+    pub fixed_fee: u64,
+    /// The base asset trade direction (`Sell` if the base asset is sent)
+    pub trade_dir: TradeDir,
+    /// The fee is paid with this asset
+    pub fee_asset: AssetType,
+    pub base_precision: AssetPrecision,
+    pub quote_precision: AssetPrecision,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuoteAmounts {
+    pub send_asset: AssetType,
+    pub recv_asset: AssetType,
+    /// Amounts in the asset base units
+    pub send_amount: u64,
+    pub recv_amount: u64,
+    pub total_fee: u64,
+    /// Amounts using the asset precision
+    pub send_amount_float: f64,
+    pub recv_amount_float: f64,
+    pub total_fee_float: f64,
+    /// Human-readable explanation, one step per line
+    pub breakdown: Vec<String>,
+}
+
+fn asset_name(asset_type: AssetType) -> &'static str {
+    match asset_type {
+        AssetType::Base => "base",
+        AssetType::Quote => "quote",
+    }
+}
+
+/// Formats an amount with all decimal places of the precision (without float arithmetic)
+pub fn format_amount(amount: u64, precision: AssetPrecision) -> String {
+    let precision = usize::from(precision.value());
+    if precision == 0 {
+        return amount.to_string();
+    }
+    let scale = 10u64.pow(precision as u32);
+    format!("{}.{:0precision$}", amount / scale, amount % scale)
+}
+
+pub fn quote_amounts(numbers: &QuoteNumbers) -> QuoteAmounts {
+    let QuoteNumbers {
+        base_amount,
+        quote_amount,
+        server_fee,
+        fixed_fee,
+        trade_dir,
+        fee_asset,
+        base_precision,
+        quote_precision,
+    } = *numbers;
+
+    let total_fee = server_fee.saturating_add(fixed_fee);
+
+    let (send_asset, recv_asset) = match trade_dir {
+        TradeDir::Sell => (AssetType::Base, AssetType::Quote),
+        TradeDir::Buy => (AssetType::Quote, AssetType::Base),
+    };
+
+    let (send_amount, recv_amount, fee_rule) = match (trade_dir, fee_asset) {
+        (TradeDir::Sell, AssetType::Base) => (
+            base_amount.saturating_add(total_fee),
+            quote_amount,
+            "the fee is paid with the sent base asset and added to the base amount",
+        ),
+        (TradeDir::Sell, AssetType::Quote) => (
+            base_amount,
+            quote_amount.saturating_sub(total_fee),
+            "the fee is paid with the received quote asset and deducted from the quote amount",
+        ),
+        (TradeDir::Buy, AssetType::Base) => (
+            quote_amount,
+            base_amount.saturating_sub(total_fee),
+            "the fee is paid with the received base asset and deducted from the base amount",
+        ),
+        (TradeDir::Buy, AssetType::Quote) => (
+            quote_amount.saturating_add(total_fee),
+            base_amount,
+            "the fee is paid with the sent quote asset and added to the quote amount",
+        ),
+    };
+
+    let precision = |asset_type| match asset_type {
+        AssetType::Base => base_precision,
+        AssetType::Quote => quote_precision,
+    };
+    let format = |amount, asset_type| {
+        format!(
+            "{} {}",
+            format_amount(amount, precision(asset_type)),
+            asset_name(asset_type)
+        )
+    };
+
+    let mut breakdown = vec![
+        format!(
+            "{} {} for {}",
+            match trade_dir {
+                TradeDir::Sell => "sell",
+                TradeDir::Buy => "buy",
+            },
+            format(base_amount, AssetType::Base),
+            format(quote_amount, AssetType::Quote),
+        ),
+        format!(
+            "fee: {} (server fee {} + fixed fee {})",
+            format(total_fee, fee_asset),
+            format_amount(server_fee, precision(fee_asset)),
+            format_amount(fixed_fee, precision(fee_asset)),
+        ),
+        fee_rule.to_owned(),
+    ];
+    if fee_asset == recv_asset && recv_amount == 0 {
+        breakdown.push("the fee is not less than the amount, nothing is received".to_owned());
+    }
+    breakdown.push(format!(
+        "send {}, receive {}",
+        format(send_amount, send_asset),
+        format(recv_amount, recv_asset),
+    ));
+
+    QuoteAmounts {
+        send_asset,
+        recv_asset,
+        send_amount,
+        recv_amount,
+        total_fee,
+        send_amount_float: asset_float_amount_(send_amount, precision(send_asset)),
+        recv_amount_float: asset_float_amount_(recv_amount, precision(recv_asset)),
+        total_fee_float: asset_float_amount_(total_fee, precision(fee_asset)),
+        breakdown,
+    }
+}
+
+#[cfg(test)]
+mod tests;
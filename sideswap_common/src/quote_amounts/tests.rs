@@ -0,0 +1,119 @@
+use super::*;
+
+fn numbers(trade_dir: TradeDir, fee_asset: AssetType) -> QuoteNumbers {
+    QuoteNumbers {
+        base_amount: 100_000,
+        quote_amount: 95_000_000,
+        server_fee: 200,
+        fixed_fee: 50,
+        trade_dir,
+        fee_asset,
+        base_precision: AssetPrecision::BITCOIN_PRECISION,
+        quote_precision: AssetPrecision::BITCOIN_PRECISION,
+    }
+}
+
+/// Everything that is returned, in a stable text form
+fn golden(numbers: &QuoteNumbers) -> String {
+    let amounts = quote_amounts(numbers);
+    let mut lines = vec![format!(
+        "send {} {:?} ({}), recv {} {:?} ({}), fee {} ({})",
+        amounts.send_amount,
+        amounts.send_asset,
+        amounts.send_amount_float,
+        amounts.recv_amount,
+        amounts.recv_asset,
+        amounts.recv_amount_float,
+        amounts.total_fee,
+        amounts.total_fee_float,
+    )];
+    lines.extend(amounts.breakdown);
+    lines.join("\n")
+}
+
+#[test]
+fn format_amount_uses_precision() {
+    assert_eq!(
+        format_amount(0, AssetPrecision::BITCOIN_PRECISION),
+        "0.00000000"
+    );
+    assert_eq!(
+        format_amount(123_456_789, AssetPrecision::BITCOIN_PRECISION),
+        "1.23456789"
+    );
+    assert_eq!(format_amount(5, AssetPrecision::TWO), "0.05");
+    assert_eq!(format_amount(1234, AssetPrecision::ZERO), "1234");
+}
+
+#[test]
+fn golden_matrix() {
+    let whole_quote = QuoteNumbers {
+        quote_amount: 95,
+        server_fee: 2,
+        fixed_fee: 1,
+        quote_precision: AssetPrecision::ZERO,
+        ..numbers(TradeDir::Buy, AssetType::Quote)
+    };
+    let cents_base = QuoteNumbers {
+        base_amount: 1_000,
+        server_fee: 1_200,
+        fixed_fee: 0,
+        base_precision: AssetPrecision::TWO,
+        ..numbers(TradeDir::Buy, AssetType::Base)
+    };
+
+    let cases = [
+        (
+            numbers(TradeDir::Sell, AssetType::Base),
+            "send 100250 Base (0.0010025), recv 95000000 Quote (0.95), fee 250 (0.0000025)
+sell 0.00100000 base for 0.95000000 quote
+fee: 0.00000250 base (server fee 0.00000200 + fixed fee 0.00000050)
+the fee is paid with the sent base asset and added to the base amount
+send 0.00100250 base, receive 0.95000000 quote",
+        ),
+        (
+            numbers(TradeDir::Sell, AssetType::Quote),
+            "send 100000 Base (0.001), recv 94999750 Quote (0.9499975), fee 250 (0.0000025)
+sell 0.00100000 base for 0.95000000 quote
+fee: 0.00000250 quote (server fee 0.00000200 + fixed fee 0.00000050)
+the fee is paid with the received quote asset and deducted from the quote amount
+send 0.00100000 base, receive 0.94999750 quote",
+        ),
+        (
+            numbers(TradeDir::Buy, AssetType::Base),
+            "send 95000000 Quote (0.95), recv 99750 Base (0.0009975), fee 250 (0.0000025)
+buy 0.00100000 base for 0.95000000 quote
+fee: 0.00000250 base (server fee 0.00000200 + fixed fee 0.00000050)
+the fee is paid with the received base asset and deducted from the base amount
+send 0.95000000 quote, receive 0.00099750 base",
+        ),
+        (
+            numbers(TradeDir::Buy, AssetType::Quote),
+            "send 95000250 Quote (0.9500025), recv 100000 Base (0.001), fee 250 (0.0000025)
+buy 0.00100000 base for 0.95000000 quote
+fee: 0.00000250 quote (server fee 0.00000200 + fixed fee 0.00000050)
+the fee is paid with the sent quote asset and added to the quote amount
+send 0.95000250 quote, receive 0.00100000 base",
+        ),
+        (
+            whole_quote,
+            "send 98 Quote (98), recv 100000 Base (0.001), fee 3 (3)
+buy 0.00100000 base for 95 quote
+fee: 3 quote (server fee 2 + fixed fee 1)
+the fee is paid with the sent quote asset and added to the quote amount
+send 98 quote, receive 0.00100000 base",
+        ),
+        (
+            cents_base,
+            "send 95000000 Quote (0.95), recv 0 Base (0), fee 1200 (12)
+buy 10.00 base for 0.95000000 quote
+fee: 12.00 base (server fee 12.00 + fixed fee 0.00)
+the fee is paid with the received base asset and deducted from the base amount
+the fee is not less than the amount, nothing is received
+send 0.95000000 quote, receive 0.00 base",
+        ),
+    ];
+    for (numbers, expected) in cases {
+        assert_eq!(golden(&numbers), expected, "{numbers:?}");
+    }
+}
@@ -5,7 +5,10 @@ use super::*;
 use futures::prelude::*;
 use log::{debug, error, info};
 use sideswap_api::*;
-use std::time::Instant;
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 use tungstenite::Message;
 
@@ -21,6 +24,116 @@ pub enum WrappedResponse {
     Response(ResponseMessage),
 }
 
+/// Server messages that could not be decoded as is (most likely sent by a newer server version)
+static SKIPPED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+/// The largest server message received since start (in bytes)
+static LARGEST_MESSAGE: AtomicU64 = AtomicU64::new(0);
+
+/// Only this many first bytes of undecodable messages are logged
+const MAX_LOGGED_LEN: usize = 1000;
+
+/// The number of server messages that were skipped or decoded with a fallback since start
+pub fn skipped_messages() -> u64 {
+    SKIPPED_MESSAGES.load(Ordering::Relaxed)
+}
+
+/// The size of the largest server message received since start (in bytes)
+pub fn largest_message() -> u64 {
+    LARGEST_MESSAGE.load(Ordering::Relaxed)
+}
+
+fn truncated(text: &str) -> &str {
+    let mut len = text.len().min(MAX_LOGGED_LEN);
+    while !text.is_char_boundary(len) {
+        len -= 1;
+    }
+    &text[..len]
+}
+
+/// Replaces an unknown quote status with `QuoteStatus::Error`
+fn quote_status_fallback(value: &mut serde_json::Value) -> Option<()> {
+    let status = value
+        .get_mut("Notification")?
+        .get_mut("Market")?
+        .get_mut("quote")?
+        .get_mut("status")?;
+    let name = match status {
+        serde_json::Value::Object(map) => map.keys().next()?.clone(),
+        serde_json::Value::String(name) => name.clone(),
+        _ => return None,
+    };
+    *status = serde_json::json!({
+        "Error": {
+            "error_msg": format!("unsupported quote status: {name}"),
+        },
+    });
+    Some(())
+}
+
+/// Replaces an unknown response with an error response for the same request,
+/// so the request fails instead of waiting for the timeout
+fn response_fallback(value: &serde_json::Value) -> Option<ResponseMessage> {
+    let response = value.get("Response")?.as_array()?;
+    let request_id = serde_json::from_value::<Option<RequestId>>(response.first()?.clone()).ok()?;
+    let message = response
+        .get(1)
+        .and_then(|res| res.get("Err"))
+        .and_then(|err| err.get("message"))
+        .and_then(|message| message.as_str())
+        .unwrap_or("unsupported server response")
+        .to_owned();
+    Some(ResponseMessage::Response(
+        request_id,
+        Err(Error {
+            code: ErrorCode::ServerError,
+            message,
+        }),
+    ))
+}
+
+/// Decodes a server message, tolerating protocol additions:
+/// - unknown fields are ignored (by serde),
+/// - a quote notification with an unknown status is delivered with `QuoteStatus::Error`,
+/// - a response that can't be decoded is replaced with an error response for the same request,
+/// - other undecodable messages (new notification types, for example) are logged and skipped.
+pub fn parse_response(text: &str) -> Option<ResponseMessage> {
+    let err = match serde_json::from_str::<ResponseMessage>(text) {
+        Ok(msg) => return Some(msg),
+        Err(err) => err,
+    };
+
+    SKIPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
+
+    let Ok(mut value) = serde_json::from_str::<serde_json::Value>(text) else {
+        error!("parsing response failed: {}: {}", err, truncated(text));
+        return None;
+    };
+
+    if quote_status_fallback(&mut value).is_some() {
+        if let Ok(msg) = serde_json::from_value::<ResponseMessage>(value.clone()) {
+            log::warn!(
+                "unknown quote status replaced with an error: {}: {}",
+                err,
+                truncated(text)
+            );
+            return Some(msg);
+        }
+    }
+
+    if let Some(msg) = response_fallback(&value) {
+        log::warn!(
+            "unknown response replaced with an error: {}: {}",
+            err,
+            truncated(text)
+        );
+        return Some(msg);
+    }
+
+    log::warn!("unknown message skipped: {}: {}", err, truncated(text));
+    None
+}
+
 pub async fn run(
     base_url: String,
     mut req_rx: UnboundedReceiver<WrappedRequest>,
@@ -72,14 +185,9 @@ pub async fn run(
                     last_recv_timestamp = Instant::now();
                     match msg {
                         Message::Text(text) => {
-                            let server_msg = serde_json::from_str::<ResponseMessage>(&text);
-                            match server_msg {
-                                Ok(v) => {
-                                    resp_tx.send(WrappedResponse::Response(v));
-                                }
-                                Err(e) => {
-                                    error!("parsing response failed: {}: {}", e, &text);
-                                }
+                            LARGEST_MESSAGE.fetch_max(text.len() as u64, Ordering::Relaxed);
+                            if let Some(server_msg) = parse_response(&text) {
+                                resp_tx.send(WrappedResponse::Response(server_msg));
                             }
                         }
 
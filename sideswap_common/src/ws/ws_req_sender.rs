@@ -3,8 +3,10 @@ use std::{
     time::Duration,
 };
 
+use serde::Deserialize;
 use sideswap_api::ErrorCode;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::Instrument;
 
 use crate::verify;
 
@@ -23,6 +25,8 @@ pub enum Error {
     Timeout(#[from] tokio::time::error::Elapsed),
     #[error("Unexpected response")]
     UnexpectedResponse,
+    #[error("Request too large: {size} bytes (the limit is {limit} bytes)")]
+    RequestTooLarge { size: usize, limit: usize },
 }
 
 impl Error {
@@ -32,10 +36,75 @@ impl Error {
             Error::Disconnected | Error::Timeout(_) | Error::UnexpectedResponse => {
                 sideswap_api::ErrorCode::ServerError
             }
+            Error::RequestTooLarge { .. } => sideswap_api::ErrorCode::InvalidRequest,
         }
     }
 }
 
+/// Serialized size limits for the outgoing requests (in bytes)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct SizeLimits {
+    /// Larger requests are sent, but logged with a warning
+    pub soft_bytes: usize,
+    /// Larger requests fail with `Error::RequestTooLarge` and are not sent
+    /// (the server drops the connection when a message exceeds its limit)
+    pub hard_bytes: usize,
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        Self {
+            soft_bytes: 1 << 20,
+            // The default tungstenite message size limit
+            hard_bytes: 16 << 20,
+        }
+    }
+}
+
+/// Upper bounds of the request size histogram buckets (the last bucket counts larger requests)
+pub const SIZE_BUCKETS: [usize; 5] = [1 << 10, 16 << 10, 256 << 10, 1 << 20, 16 << 20];
+
+/// Serialized sizes of one request type
+#[derive(Debug, Clone, Default)]
+pub struct SizeStats {
+    /// Measured requests (including rejected)
+    pub count: u64,
+    pub total_bytes: u64,
+    pub max_bytes: usize,
+    /// Requests not sent because of the hard limit
+    pub rejected: u64,
+    /// Request counts per `SIZE_BUCKETS` bucket
+    pub buckets: [u64; SIZE_BUCKETS.len() + 1],
+}
+
+impl SizeStats {
+    fn add(&mut self, size: usize) {
+        self.count += 1;
+        self.total_bytes += size as u64;
+        self.max_bytes = self.max_bytes.max(size);
+        let bucket = SIZE_BUCKETS
+            .iter()
+            .position(|bound| size <= *bound)
+            .unwrap_or(SIZE_BUCKETS.len());
+        self.buckets[bucket] += 1;
+    }
+}
+
+/// The request type name, for example `Market.start_quotes`
+fn request_type(value: &serde_json::Value) -> String {
+    let mut names = Vec::new();
+    let mut value = value;
+    // Request enums are serialized as `{"Variant": {"variant": {...}}}`
+    while let Some((name, inner)) = value.as_object().and_then(|obj| obj.iter().next()) {
+        names.push(name.as_str());
+        if names.len() == 2 {
+            break;
+        }
+        value = inner;
+    }
+    names.join(".")
+}
+
 pub type Callback = Box<dyn FnOnce(Result<&sideswap_api::Response, Error>) + Send + Sync>;
 
 pub struct WsReqSender {
@@ -45,6 +114,8 @@ pub struct WsReqSender {
     received: VecDeque<WrappedResponse>,
     // TODO: Implement timeouts for callbacks (can be done inside WsReqSender::recv)
     callbacks: BTreeMap<sideswap_api::RequestId, Callback>,
+    size_limits: SizeLimits,
+    size_stats: BTreeMap<String, SizeStats>,
 }
 
 impl WsReqSender {
@@ -58,7 +129,58 @@ impl WsReqSender {
             resp_receiver,
             received: Default::default(),
             callbacks: Default::default(),
+            size_limits: Default::default(),
+            size_stats: Default::default(),
+        }
+    }
+
+    pub fn set_size_limits(&mut self, size_limits: SizeLimits) {
+        self.size_limits = size_limits;
+    }
+
+    /// Outgoing request sizes by request type (since start)
+    pub fn size_stats(&self) -> &BTreeMap<String, SizeStats> {
+        &self.size_stats
+    }
+
+    /// Measure the serialized request and send it if it's below the hard limit
+    fn send_message(
+        &mut self,
+        request_id: sideswap_api::RequestId,
+        req: sideswap_api::Request,
+    ) -> Result<(), Error> {
+        let request_type = request_type(&serde_json::to_value(&req).expect("must not fail"));
+        let msg = sideswap_api::RequestMessage::Request(request_id, req);
+        let size = serde_json::to_string(&msg).expect("must not fail").len();
+        let stats = self.size_stats.entry(request_type.clone()).or_default();
+        stats.add(size);
+
+        if size > self.size_limits.hard_bytes {
+            stats.rejected += 1;
+            tracing::error!(
+                request_type,
+                size,
+                limit = self.size_limits.hard_bytes,
+                "upstream request rejected, it's too large"
+            );
+            return Err(Error::RequestTooLarge {
+                size,
+                limit: self.size_limits.hard_bytes,
+            });
         }
+        if size > self.size_limits.soft_bytes {
+            tracing::warn!(
+                request_type,
+                size,
+                limit = self.size_limits.soft_bytes,
+                "large upstream request"
+            );
+        }
+
+        self.req_sender
+            .send(WrappedRequest::Request(msg))
+            .expect("must be open");
+        Ok(())
     }
 
     pub fn connected(&self) -> bool {
@@ -117,13 +239,10 @@ impl WsReqSender {
         Ok(resp)
     }
 
+    /// Oversized requests are not sent (the error is only logged)
     pub fn send_request(&mut self, req: sideswap_api::Request) -> sideswap_api::RequestId {
         let request_id = next_request_id();
-        self.req_sender
-            .send(WrappedRequest::Request(
-                sideswap_api::RequestMessage::Request(request_id.clone(), req),
-            ))
-            .expect("must be open");
+        let _ = self.send_message(request_id.clone(), req);
         request_id
     }
 
@@ -135,18 +254,17 @@ impl WsReqSender {
 
         let expected_id = next_request_id();
 
-        self.req_sender
-            .send(WrappedRequest::Request(
-                sideswap_api::RequestMessage::Request(expected_id.clone(), req),
-            ))
-            .expect("must be open");
+        self.send_message(expected_id.clone(), req)?;
 
         while let Ok(msg) = self.resp_receiver.try_recv() {
             self.on_received(&msg);
             self.received.push_back(msg);
         }
 
-        tokio::time::timeout(Duration::from_secs(60), async move {
+        let span = tracing::debug_span!("upstream_request", request_id = ?expected_id);
+        let started_at = std::time::Instant::now();
+
+        let res = tokio::time::timeout(Duration::from_secs(60), async move {
             loop {
                 let event = self.resp_receiver.recv().await.expect("must be open");
                 self.on_received(&event);
@@ -171,7 +289,18 @@ impl WsReqSender {
                 }
             }
         })
-        .await?
+        .instrument(span.clone())
+        .await;
+
+        span.in_scope(|| {
+            tracing::debug!(
+                duration_ms = started_at.elapsed().as_millis() as u64,
+                ok = matches!(res, Ok(Ok(_))),
+                "upstream request finished"
+            )
+        });
+
+        res?
     }
 
     pub fn callback_request(&mut self, req: sideswap_api::Request, callback: Callback) {
@@ -179,12 +308,12 @@ impl WsReqSender {
             callback(Err(Error::Disconnected));
         } else {
             let request_id = next_request_id();
-            self.req_sender
-                .send(WrappedRequest::Request(
-                    sideswap_api::RequestMessage::Request(request_id.clone(), req),
-                ))
-                .expect("must be open");
-            self.callbacks.insert(request_id, callback);
+            match self.send_message(request_id.clone(), req) {
+                Ok(()) => {
+                    self.callbacks.insert(request_id, callback);
+                }
+                Err(err) => callback(Err(err)),
+            }
         }
     }
 
@@ -5,6 +5,11 @@ const DEFAULT: &str = include_str!("../config/log_config.toml");
 pub const GIT_COMMIT_HASH: &str = env!("VERGEN_GIT_SHA");
 
 pub fn init(work_dir: impl AsRef<Path>) {
+    init_with_config(work_dir, DEFAULT);
+}
+
+/// Same as `init`, but with a different default log config
+pub fn init_with_config(work_dir: impl AsRef<Path>, default_config: &str) {
     std::fs::create_dir_all(&work_dir).expect("must not fail");
     std::env::set_current_dir(work_dir).expect("must not fail");
 
@@ -16,11 +21,13 @@ pub fn init(work_dir: impl AsRef<Path>) {
     // Overwrite custom config only if it's empty or has not changed (compared to the older content)
     let can_update_config = config_default == config_custom || config_custom.is_empty();
     if can_update_config {
-        std::fs::write(log_config_custom_path, DEFAULT).expect("writing custom config file failed");
+        std::fs::write(log_config_custom_path, default_config)
+            .expect("writing custom config file failed");
     }
 
     // Always overwrite default config for reference
-    std::fs::write(log_config_default_path, DEFAULT).expect("writing default config file failed");
+    std::fs::write(log_config_default_path, default_config)
+        .expect("writing default config file failed");
 
     log4rs::init_file(log_config_custom_path, Default::default())
         .expect("can't open log settings");
@@ -6,6 +6,14 @@ use std::{
     time::{Duration, Instant},
 };
 
+use bitcoin::{
+    hashes::Hash,
+    secp256k1::{
+        ecdsa::{RecoverableSignature, RecoveryId},
+        Message,
+    },
+    sign_message::signed_msg_hash,
+};
 use elements::{
     bitcoin::bip32,
     confidential::{AssetBlindingFactor, ValueBlindingFactor},
@@ -17,6 +25,7 @@ use lwk_wollet::{
     WolletDescriptor,
 };
 use sideswap_common::{
+    b64,
     channel_helpers::{UncheckedOneshotSender, UncheckedUnboundedSender},
     network::Network,
     recipient::Recipient,
@@ -28,7 +37,7 @@ use sideswap_dealer::{
 };
 use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 
-pub use lwk_wollet::{WalletTx, WalletTxOut};
+pub use lwk_wollet::{Chain, WalletTx, WalletTxOut};
 
 #[derive(Debug, Copy, Clone)]
 pub struct ScriptVariant(lwk_common::Singlesig);
@@ -39,12 +48,17 @@ impl<'de> serde::Deserialize<'de> for ScriptVariant {
         D: serde::Deserializer<'de>,
     {
         let value = String::deserialize(deserializer)?;
-        let value =
-            lwk_common::Singlesig::from_str(&value).map_err(serde::de::Error::custom)?;
+        let value = lwk_common::Singlesig::from_str(&value).map_err(serde::de::Error::custom)?;
         Ok(ScriptVariant(value))
     }
 }
 
+impl PartialEq for ScriptVariant {
+    fn eq(&self, other: &Self) -> bool {
+        std::mem::discriminant(&self.0) == std::mem::discriminant(&other.0)
+    }
+}
+
 pub struct Params {
     pub network: Network,
     pub work_dir: PathBuf,
@@ -84,14 +98,36 @@ pub struct GetUtxosReq {}
 
 pub struct GetUtxosResp {
     pub utxos: Vec<WalletTxOut>,
+    /// The last synced block height
+    pub tip_height: u32,
 }
 
 pub struct CreateTxReq {
     pub recipients: Vec<Recipient>,
+    /// Send all L-BTC left after paying the recipients and the network fee to this address
+    pub drain_lbtc_to: Option<elements::Address>,
+    /// Fee rate in sat/vbyte (the wallet default is used if not set)
+    pub fee_rate: Option<f64>,
+    /// Spend only these wallet UTXOs (all wallet UTXOs can be spent if not set)
+    pub utxos: Option<Vec<elements::OutPoint>>,
 }
 
 pub struct CreateTxResp {
     pub tx: elements::Transaction,
+    /// The amount sent to `drain_lbtc_to`
+    pub drained_amount: Option<u64>,
+}
+
+pub struct SignMessageReq {
+    /// External address index
+    pub index: u32,
+    pub message: String,
+}
+
+pub struct SignMessageResp {
+    pub address: elements::Address,
+    /// Base64 encoded recoverable signature with the BIP137 header byte
+    pub signature: String,
 }
 
 pub enum Command {
@@ -119,6 +155,10 @@ pub enum Command {
         req: GetUtxosReq,
         res_sender: UncheckedOneshotSender<Result<GetUtxosResp, Error>>,
     },
+    SignMessage {
+        req: SignMessageReq,
+        res_sender: UncheckedOneshotSender<Result<SignMessageResp, Error>>,
+    },
 }
 
 pub enum Event {
@@ -149,7 +189,13 @@ fn create_tx(
     wallet: &lwk_wollet::Wollet,
     signer: &lwk_signer::SwSigner,
 ) -> Result<CreateTxResp, Error> {
-    let mut tx_builder = wallet.tx_builder().enable_ct_discount();
+    let mut tx_builder = wallet
+        .tx_builder()
+        .enable_ct_discount()
+        .fee_rate(req.fee_rate.map(|fee_rate| (fee_rate * 1000.0) as f32));
+    if let Some(utxos) = req.utxos {
+        tx_builder = tx_builder.set_wallet_utxos(utxos);
+    }
     for recipient in req.recipients {
         tx_builder = tx_builder.add_unvalidated_recipient(&lwk_wollet::UnvalidatedRecipient {
             satoshi: recipient.amount,
@@ -157,10 +203,24 @@ fn create_tx(
             asset: recipient.asset_id.to_string(),
         })?;
     }
+    if let Some(address) = &req.drain_lbtc_to {
+        tx_builder = tx_builder
+            .drain_lbtc_wallet()
+            .drain_lbtc_to(address.clone());
+    }
     let mut pset = tx_builder.finish()?;
+    let drained_amount = req.drain_lbtc_to.and_then(|address| {
+        let script_pubkey = address.script_pubkey();
+        pset.outputs()
+            .iter()
+            .find(|output| {
+                output.script_pubkey == script_pubkey && output.asset == Some(wallet.policy_asset())
+            })
+            .and_then(|output| output.amount)
+    });
     signer.sign(&mut pset)?;
     let tx = wallet.finalize(&mut pset)?;
-    Ok(CreateTxResp { tx })
+    Ok(CreateTxResp { tx, drained_amount })
 }
 
 fn broadcast_tx(electrum_client: &lwk_wollet::ElectrumClient, tx: &str) -> Result<Txid, Error> {
@@ -214,7 +274,114 @@ fn get_utxos(
     wallet: &lwk_wollet::Wollet,
 ) -> Result<GetUtxosResp, Error> {
     let utxos = wallet.utxos()?;
-    Ok(GetUtxosResp { utxos })
+    let tip_height = wallet.tip().height();
+    Ok(GetUtxosResp { utxos, tip_height })
+}
+
+fn derive_priv_key(
+    descriptor: &WolletDescriptor,
+    master_key: &bip32::Xpriv,
+    ext_int: Chain,
+    index: u32,
+) -> Result<bitcoin::PrivateKey, Error> {
+    let desc = descriptor.definite_descriptor(ext_int, index)?;
+
+    let mut priv_key = None;
+
+    use elements_miniscript::ForEachKey;
+    desc.for_each_key(|d| {
+        let full_path = d.full_derivation_path().expect("must be set");
+        assert!(priv_key.is_none());
+        priv_key = Some(
+            master_key
+                .derive_priv(SECP256K1, &full_path)
+                .expect("must not fail")
+                .to_priv(),
+        );
+        true
+    });
+
+    Ok(priv_key.expect("must be set"))
+}
+
+/// BIP137 header byte without the recovery id
+fn message_header(script_variant: ScriptVariant) -> u8 {
+    match script_variant.0 {
+        lwk_common::Singlesig::Wpkh => 39,
+        lwk_common::Singlesig::ShWpkh => 35,
+    }
+}
+
+fn sign_message(
+    req: SignMessageReq,
+    wallet: &lwk_wollet::Wollet,
+    descriptor: &WolletDescriptor,
+    master_key: &bip32::Xpriv,
+    script_variant: ScriptVariant,
+) -> Result<SignMessageResp, Error> {
+    let address = wallet.address(Some(req.index))?.address().clone();
+    let priv_key = derive_priv_key(descriptor, master_key, Chain::External, req.index)?;
+
+    let msg = Message::from_digest(signed_msg_hash(&req.message).to_byte_array());
+    let (recovery_id, compact) = SECP256K1
+        .sign_ecdsa_recoverable(&msg, &priv_key.inner)
+        .serialize_compact();
+
+    let mut signature = [0; 65];
+    signature[0] = message_header(script_variant) + recovery_id.to_i32() as u8;
+    signature[1..].copy_from_slice(&compact);
+
+    Ok(SignMessageResp {
+        address,
+        signature: b64::encode(&signature),
+    })
+}
+
+/// Verifies a signed message (BIP137 signature in base64, as returned by `SignMessage`).
+/// The header byte selects the address type (P2PKH, P2SH-P2WPKH or P2WPKH),
+/// compressed P2PKH headers are accepted for segwit addresses too (some wallets sign that way).
+/// Returns an error if the signature can't be decoded and `false` if it does not match the address.
+pub fn verify_message(
+    address: &elements::Address,
+    message: &str,
+    signature: &str,
+) -> Result<bool, Error> {
+    let signature =
+        b64::decode(signature).map_err(|_| Error::InvalidArg("signature must be base64"))?;
+    if signature.len() != 65 || !(27..=42).contains(&signature[0]) {
+        return Err(Error::InvalidArg("invalid signature length or header"));
+    }
+    let header = signature[0] - 27;
+    let recovery_id = RecoveryId::from_i32(i32::from(header % 4)).expect("must be valid");
+    let signature = RecoverableSignature::from_compact(&signature[1..], recovery_id)
+        .map_err(|_| Error::InvalidArg("invalid signature"))?;
+
+    let msg = Message::from_digest(signed_msg_hash(message).to_byte_array());
+    let Ok(pubkey) = SECP256K1.recover_ecdsa(&msg, &signature) else {
+        return Ok(false);
+    };
+
+    let params = address.params;
+    let compressed = bitcoin::PublicKey::new(pubkey);
+    let candidates = match header / 4 {
+        0 => vec![elements::Address::p2pkh(
+            &bitcoin::PublicKey::new_uncompressed(pubkey),
+            None,
+            params,
+        )],
+        1 => vec![
+            elements::Address::p2pkh(&compressed, None, params),
+            elements::Address::p2shwpkh(&compressed, None, params),
+            elements::Address::p2wpkh(&compressed, None, params),
+        ],
+        2 => vec![elements::Address::p2shwpkh(&compressed, None, params)],
+        _ => vec![elements::Address::p2wpkh(&compressed, None, params)],
+    };
+
+    let script_pubkey = address.script_pubkey();
+    Ok(candidates
+        .iter()
+        .any(|candidate| candidate.script_pubkey() == script_pubkey))
 }
 
 fn run(
@@ -282,36 +449,21 @@ fn run(
                         && utxo.unblinded.value_bf != ValueBlindingFactor::zero()
                 })
                 .map(|utxo| {
-                    let utxo_desc = descriptor
-                        .definite_descriptor(utxo.ext_int, utxo.wildcard_index)
-                        .expect("must not fail");
-
-                    let mut utxo_details = None;
-
-                    use elements_miniscript::ForEachKey;
-                    utxo_desc.for_each_key(|d| {
-                        let full_path = d.full_derivation_path().expect("must be set");
-
-                        let priv_key = master_key
-                            .derive_priv(SECP256K1, &full_path)
-                            .expect("must not fail")
-                            .to_priv();
-
-                        let redeem_script = match script_variant.0 {
-                            lwk_common::Singlesig::Wpkh => None,
-                            lwk_common::Singlesig::ShWpkh => {
-                                let pub_key = priv_key.public_key(SECP256K1);
-                                Some(sideswap_common::pset::p2shwpkh_redeem_script(&pub_key))
-                            }
-                        };
-
-                        assert!(utxo_details.is_none());
-                        utxo_details = Some((redeem_script, priv_key));
-
-                        true
-                    });
-
-                    let (redeem_script, priv_key) = utxo_details.expect("must be set");
+                    let priv_key = derive_priv_key(
+                        &descriptor,
+                        &master_key,
+                        utxo.ext_int,
+                        utxo.wildcard_index,
+                    )
+                    .expect("must not fail");
+
+                    let redeem_script = match script_variant.0 {
+                        lwk_common::Singlesig::Wpkh => None,
+                        lwk_common::Singlesig::ShWpkh => {
+                            let pub_key = priv_key.public_key(SECP256K1);
+                            Some(sideswap_common::pset::p2shwpkh_redeem_script(&pub_key))
+                        }
+                    };
 
                     UtxoWithKey {
                         utxo: sideswap_api::Utxo {
@@ -394,6 +546,12 @@ fn run(
                         let res = get_utxos(req, &wallet);
                         res_sender.send(res);
                     }
+
+                    Command::SignMessage { req, res_sender } => {
+                        let res =
+                            sign_message(req, &wallet, &descriptor, &master_key, script_variant);
+                        res_sender.send(res);
+                    }
                 },
 
                 Err(err) => match err {
@@ -469,6 +627,17 @@ impl Wallet {
         &self.descriptor
     }
 
+    /// Same as `Command::SignMessage`, but does not require starting the wallet
+    pub fn sign_message(&self, req: SignMessageReq) -> Result<SignMessageResp, Error> {
+        sign_message(
+            req,
+            &self.wallet,
+            &self.descriptor,
+            &self.master_key,
+            self.script_variant,
+        )
+    }
+
     pub fn wallet_id(&self) -> String {
         use elements::bitcoin::hashes::Hash;
         sideswap_common::wallet_id::WalletIdHash::hash(self.descriptor().to_string().as_bytes())
@@ -0,0 +1,112 @@
+//! The per-broadcast cost of `send_notifs`: every client serializing its own copy of the notification
+//! (as before `EncodedNotif`) against one `EncodedNotif` shared by all clients (serialized once per encoding).
+//!
+//! Run with `cargo bench -p sideswap_manager --bench notif_encoding`.
+
+// The manager is a binary crate, so the encoding modules are compiled into the bench directly
+// (`error` is only used by the `api` tests, benches are built with `cfg(test)` too)
+#[allow(dead_code)]
+#[path = "../src"]
+mod manager {
+    pub mod api;
+    pub mod error;
+    pub mod notif_encoding;
+}
+
+use manager::{api, error, notif_encoding};
+
+mod wallets {
+    pub const DEFAULT_WALLET: &str = "default";
+}
+
+use std::sync::Arc;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use sideswap_common::dealer_ticker::DealerTicker;
+
+use notif_encoding::{EncodedNotif, NotifEncoding};
+
+const CLIENTS: [usize; 3] = [1, 10, 100];
+
+fn sample_notifs() -> Vec<(&'static str, api::Notif)> {
+    vec![
+        (
+            "MarketPrice",
+            api::Notif::MarketPrice(api::MarketPriceNotif {
+                base: DealerTicker::LBTC,
+                quote: DealerTicker::USDT,
+                ind_price: Some(95_123.45),
+                last_price: Some(95_100.0),
+                stale: false,
+            }),
+        ),
+        (
+            "Balances",
+            api::Notif::Balances(api::BalancesNotif {
+                wallet: wallets::DEFAULT_WALLET.to_owned(),
+                balances: [
+                    (DealerTicker::LBTC, 0.12345678),
+                    (DealerTicker::USDT, 1250.5),
+                    (DealerTicker::EURX, 310.25),
+                ]
+                .into(),
+                confirmed: [(DealerTicker::LBTC, 0.1), (DealerTicker::USDT, 1250.5)].into(),
+                unconfirmed: [
+                    (DealerTicker::LBTC, 0.02345678),
+                    (DealerTicker::EURX, 310.25),
+                ]
+                .into(),
+            }),
+        ),
+    ]
+}
+
+fn encode(notif: &EncodedNotif, encoding: NotifEncoding) -> usize {
+    match encoding {
+        NotifEncoding::Json => notif.json().len(),
+        NotifEncoding::Protobuf => notif.protobuf().len(),
+    }
+}
+
+fn broadcast(c: &mut Criterion) {
+    for (name, notif) in sample_notifs() {
+        for (encoding_name, encoding) in [
+            ("json", NotifEncoding::Json),
+            ("protobuf", NotifEncoding::Protobuf),
+        ] {
+            let mut group = c.benchmark_group(format!("broadcast/{name}/{encoding_name}"));
+            for clients in CLIENTS {
+                group.throughput(Throughput::Elements(clients as u64));
+
+                group.bench_with_input(
+                    BenchmarkId::new("per_client", clients),
+                    &clients,
+                    |b, &clients| {
+                        b.iter(|| {
+                            (0..clients)
+                                .map(|_| encode(&EncodedNotif::new(notif.clone()), encoding))
+                                .sum::<usize>()
+                        })
+                    },
+                );
+
+                group.bench_with_input(
+                    BenchmarkId::new("shared", clients),
+                    &clients,
+                    |b, &clients| {
+                        b.iter(|| {
+                            let shared = EncodedNotif::new(notif.clone());
+                            (0..clients)
+                                .map(|_| encode(&Arc::clone(&shared), encoding))
+                                .sum::<usize>()
+                        })
+                    },
+                );
+            }
+            group.finish();
+        }
+    }
+}
+
+criterion_group!(benches, broadcast);
+criterion_main!(benches);
@@ -0,0 +1,8 @@
+create table balance_history (
+    id integer primary key autoincrement,
+    created_at int not null,
+    asset_id text not null,
+    amount int not null
+);
+
+create index balance_history_created_at on balance_history (created_at);
@@ -0,0 +1,7 @@
+create table peg_notif_stages (
+    order_id text not null,
+    tx_hash text not null,
+    vout int not null,
+    stage text not null,
+    primary key (order_id, tx_hash, vout)
+);
@@ -0,0 +1,3 @@
+alter table pegs add column created_by text;
+
+alter table monitored_txs add column created_by text;
@@ -0,0 +1,6 @@
+create table payment_references (
+    id integer primary key not null,
+    reference text unique not null,
+    kind text not null,
+    target text not null
+);
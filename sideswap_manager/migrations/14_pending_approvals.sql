@@ -0,0 +1,10 @@
+create table pending_approvals (
+    approval_id text primary key not null,
+    operation text not null,
+    txid text not null,
+    description text not null,
+    amounts text not null,
+    requested_by text,
+    created_at integer not null,
+    expires_at integer not null
+);
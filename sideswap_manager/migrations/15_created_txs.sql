@@ -0,0 +1,8 @@
+create table created_txs (
+    txid text primary key not null,
+    tx text not null,
+    note text not null,
+    created_by text,
+    amounts text not null,
+    created_at integer not null
+);
@@ -0,0 +1 @@
+alter table pegs add column completed_at integer;
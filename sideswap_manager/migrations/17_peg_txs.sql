@@ -0,0 +1,11 @@
+create table peg_txs (
+    order_id text not null,
+    tx_hash text not null,
+    vout int not null,
+    amount int not null,
+    status text not null,
+    payout_txid text,
+    payout_amount int,
+    updated_at int not null,
+    primary key (order_id, tx_hash, vout)
+);
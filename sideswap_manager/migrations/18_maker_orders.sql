@@ -0,0 +1,7 @@
+create table maker_orders (
+    order_id integer primary key not null,
+    receive_address text not null,
+    change_address text not null,
+    created_by text,
+    created_at integer not null
+);
@@ -0,0 +1,17 @@
+create table swaps (
+    quote_id integer primary key not null,
+    txid text not null,
+    send_ticker text not null,
+    send_amount real not null,
+    recv_ticker text not null,
+    recv_amount real not null,
+    price real not null,
+    fee_ticker text not null,
+    fee_amount real not null,
+    server_fee integer not null,
+    fixed_fee integer not null,
+    created_by text,
+    created_at integer not null
+);
+
+create index swaps_created_at on swaps (created_at);
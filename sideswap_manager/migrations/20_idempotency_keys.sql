@@ -0,0 +1,8 @@
+create table idempotency_keys (
+    key text primary key not null,
+    request text not null,
+    response text,
+    created_at integer not null
+);
+
+alter table monitored_txs add column send_result text;
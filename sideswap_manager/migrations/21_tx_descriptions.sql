@@ -0,0 +1 @@
+alter table created_txs rename column note to description;
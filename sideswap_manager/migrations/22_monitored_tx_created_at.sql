@@ -0,0 +1,8 @@
+-- Milliseconds, the older rows are backfilled from the swaps and the created transactions (or get the migration time)
+alter table monitored_txs add column created_at integer not null default 0;
+
+update monitored_txs set created_at = coalesce(
+    (select min(created_at) from swaps where swaps.txid = monitored_txs.txid),
+    (select created_at from created_txs where created_txs.txid = monitored_txs.txid),
+    cast(strftime('%s', 'now') as integer) * 1000
+);
@@ -0,0 +1,18 @@
+-- Several named wallets share the DB, the existing rows belong to the default wallet.
+-- Every wallet has its own address indexes, so the primary key of addresses is (wallet, ind).
+create table addresses_by_wallet (
+    wallet text not null default 'default',
+    ind int not null,
+    address text unique not null,
+    user_note text,
+    primary key (wallet, ind)
+);
+
+insert into addresses_by_wallet (wallet, ind, address, user_note)
+    select 'default', ind, address, user_note from addresses;
+
+drop table addresses;
+
+alter table addresses_by_wallet rename to addresses;
+
+alter table created_txs add column wallet text not null default 'default';
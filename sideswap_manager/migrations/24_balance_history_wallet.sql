@@ -0,0 +1,4 @@
+-- Every wallet has its own balance snapshots, the existing rows belong to the default wallet
+alter table balance_history add column wallet text not null default 'default';
+
+create index balance_history_wallet_created_at on balance_history (wallet, created_at);
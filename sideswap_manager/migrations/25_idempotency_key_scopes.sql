@@ -0,0 +1,32 @@
+-- The idempotency keys are scoped by the client name (empty for anonymous clients), the wallet and the request kind.
+-- The existing keys belong to anonymous clients.
+create table idempotency_keys_scoped (
+    client text not null,
+    wallet text not null,
+    kind text not null,
+    key text not null,
+    request text not null,
+    response text,
+    created_at integer not null,
+    primary key (client, wallet, kind, key)
+);
+
+insert into idempotency_keys_scoped (client, wallet, kind, key, request, response, created_at)
+    select
+        '',
+        case when request like '{"SendTx"%' then 'default' else '' end,
+        case when request like '{"SendTx"%' then 'SendTx' else 'AcceptQuote' end,
+        key,
+        request,
+        response,
+        created_at
+    from idempotency_keys;
+
+drop table idempotency_keys;
+
+alter table idempotency_keys_scoped rename to idempotency_keys;
+
+-- The stored SendTx result is returned only for the same wallet
+alter table monitored_txs add column wallet text;
+
+update monitored_txs set wallet = 'default' where send_result is not null;
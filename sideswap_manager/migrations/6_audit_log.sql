@@ -0,0 +1,5 @@
+create table audit_log (
+    id integer primary key autoincrement,
+    created_at int not null,
+    event text not null
+);
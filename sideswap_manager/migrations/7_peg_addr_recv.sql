@@ -0,0 +1 @@
+alter table pegs add column addr_recv text;
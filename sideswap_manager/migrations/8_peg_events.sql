@@ -0,0 +1,11 @@
+create table peg_events (
+    id integer primary key autoincrement,
+    order_id text not null,
+    created_at int not null,
+    tx_hash text not null,
+    vout int not null,
+    tx_state text not null,
+    payout_txid text
+);
+
+create index peg_events_order_id on peg_events (order_id);
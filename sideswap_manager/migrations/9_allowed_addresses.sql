@@ -0,0 +1,6 @@
+create table allowed_addresses (
+    address text primary key not null,
+    label text,
+    added_by text,
+    added_at int not null
+);
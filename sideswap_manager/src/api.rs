@@ -1,23 +1,355 @@
 use std::collections::BTreeMap;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sideswap_types::{duration_ms::DurationMs, fee_rate::FeeRateSats, timestamp_ms::TimestampMs};
 
+/// Accepts amounts as JSON numbers or as decimal strings
+fn deserialize_amount<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    parse_amount(serde_json::Value::deserialize(deserializer)?)
+}
+
+/// Same as `deserialize_amount`, `null` is None
+fn deserialize_optional_amount<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<f64>, D::Error> {
+    match serde_json::Value::deserialize(deserializer)? {
+        serde_json::Value::Null => Ok(None),
+        value => parse_amount(value).map(Some),
+    }
+}
+
+fn parse_amount<E: serde::de::Error>(value: serde_json::Value) -> Result<f64, E> {
+    let amount = match value {
+        serde_json::Value::Number(number) => number.as_f64(),
+        serde_json::Value::String(value) => value.trim().parse::<f64>().ok(),
+        _ => None,
+    };
+    amount
+        .filter(|amount| amount.is_finite())
+        .ok_or_else(|| E::custom("amount must be a number or a decimal string"))
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_wallet() -> String {
+    crate::wallets::DEFAULT_WALLET.to_owned()
+}
+
+/// Outpoints are serialized as `txid:vout` (without the `[elements]` prefix, which is also accepted when parsing)
+fn serialize_outpoint<S: Serializer>(
+    outpoint: &elements::OutPoint,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(&format_args!("{}:{}", outpoint.txid, outpoint.vout))
+}
+
+fn serialize_outpoints<S: Serializer>(
+    outpoints: &Option<Vec<elements::OutPoint>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let outpoints = outpoints.as_ref().map(|outpoints| {
+        outpoints
+            .iter()
+            .map(|outpoint| format!("{}:{}", outpoint.txid, outpoint.vout))
+            .collect::<Vec<_>>()
+    });
+    outpoints.serialize(serializer)
+}
+
+/// Every error has its own code, `details` (if set) has the same name as the code
 #[derive(Debug, Serialize)]
 pub enum ErrorCode {
-    /// Something wrong with the request arguments
+    /// Something wrong with the request arguments (not covered by the other codes)
     InvalidRequest,
     /// Server error
     ServerError,
     /// Network error (e.g., cannot reach Electrs or SideSwap backend)
     NetworkError,
+    /// The SideSwap server did not respond in time, the request may still be processed
+    ServerTimeout,
+    /// The upstream request is larger than `upstream_size_limits` allow
+    RequestTooLarge,
+    /// The ticker is not valid (too long)
+    InvalidTicker,
+    /// The ticker is unknown, `details` contains the similar known tickers
+    UnknownTicker,
+    /// The ticker matches several assets, `details` contains the candidates
+    AmbiguousTicker,
+    /// The wallet failed the operation
+    WalletError,
+    /// The wallet did not respond in time (e.g., during a long scan), see `wallet_timeout_seconds`
+    WalletTimeout,
+    /// The amount has more decimal places than the asset precision allows
+    InvalidAssetAmount,
+    /// No market for the asset pair (see `ListMarkets`)
+    NoMarket,
+    /// The wallet balance is too low (top up the wallet), `details` contains the amounts in sats
+    NotEnoughAmount,
+    /// The server could not provide the quote
+    QuoteFailed,
+    /// The request contains invalid base64
+    InvalidBase64,
+    /// The request contains an invalid transaction or PSET encoding
+    InvalidEncoding,
+    /// The PSET is invalid
+    InvalidPset,
+    /// The wallet has no UTXOs for the quote
+    NoUtxos,
+    /// The quote expired, request a new one
+    QuoteExpired,
+    /// The quote is unknown (already accepted, cancelled or never returned)
+    UnknownQuote,
+    /// The upstream connection was restored after the quote was returned, the server no longer knows it.
+    /// Request a new quote.
+    QuoteInvalidatedByReconnect,
+    /// The transaction is not created by `CreateTx` (already sent, expired or never created)
+    UnknownCreatedTx,
+    /// The gap limit is reached, the unused addresses must receive funds first
+    GapLimit,
+    /// The `Unlock` password is wrong
+    WrongPassword,
+    /// Too many failed `Unlock` attempts, `details` contains the delay
+    UnlockBackoff,
+    /// The AMP asset recipient must be resolved with `ResolveGaid` first
+    AmpAddressRequired,
+    /// The GAID can't be resolved to an address
+    GaidResolveFailed,
+    /// Exactly one of `receive_address` and `gaid` must be set
+    InvalidQuoteReceiver,
+    /// The peg address is invalid for the chain
+    InvalidPegAddress,
+    /// The peg amount is below the server minimum, `details` contains the minimum
+    PegAmountTooLow,
+    /// No fee rate for the confirmation target, `details` contains the available targets
+    UnknownFeeTarget,
+    /// The peg order is unknown
+    UnknownPeg,
+    /// No wallet with the requested name (see the `wallets` setting), `details` contains the configured wallets
+    UnknownWallet,
+    /// The recipient address is not on the allow-list (see `enforce_allowlist`)
+    AddressNotAllowed,
+    /// The message is too long, `details` contains the maximum length
+    MessageTooLong,
+    /// The address is not a wallet address returned by `NewAddress`
+    NotOwnAddress,
+    /// A recipient address is invalid, `details` contains the recipient index
+    InvalidAddress,
+    /// Two recipients have the same address and asset
+    DuplicateRecipient,
+    /// A `send_all` recipient is not the only recipient of its asset
+    SendAllNotAlone,
+    /// The wallet has nothing to send for a `send_all` recipient
+    NothingToSend,
+    /// The `utxos` list can't be used with the asset
+    UtxoSelectionNotSupported,
+    /// `allow_unconfirmed` is not set and the wallet could only create the transaction with unconfirmed inputs
+    UnconfirmedInputs,
+    /// The fee rate is out of range
+    InvalidFeeRate,
+    /// `GetQuoteReq::timeout_secs` is out of range
+    InvalidQuoteTimeout,
+    /// The config file is invalid (`ReloadConfig` and `RefreshTickers`)
+    InvalidConfig,
+    /// The changed settings require a restart, `details` contains their names
+    RestartRequired,
+    /// The transaction is not monitored
+    UnknownMonitoredTx,
+    /// The monitored transaction is not confirmed yet
+    MonitoredTxUnconfirmed,
+    /// The payment reference is not valid
+    InvalidReference,
+    /// The payment reference is unknown
+    UnknownReference,
+    /// The approval is unknown or expired
+    UnknownApproval,
+    /// The approver is not allowed to approve the operation
+    ApproverNotAllowed,
+    /// The swap PSET from the server does not match the quote, the swap was not signed
+    PsetMismatch,
+    /// Maker orders are not enabled (see the `maker_orders` setting)
+    MakerOrdersDisabled,
+    /// The order is not one of the own orders (see `ListOwnOrders`)
+    UnknownOrder,
+    /// The order sends more than an `approvals` threshold.
+    /// The matched maker swaps are signed automatically, so such orders are not accepted (and such swaps are not signed).
+    OrderAboveApprovalThreshold,
+    /// The idempotency key was used for another request (see `SendTxReq::idempotency_key`)
+    IdempotencyKeyReused,
+    /// The request with the same idempotency key was interrupted by a restart and may have completed.
+    /// It is not processed again, check the result (for example with `GetMonitoredTxs`) or use a new key.
+    IdempotencyKeyInProgress,
     /// Transaction send failed due to a failed UTXO check.
     /// Since the transaction did not leave the wallet, it is safe to cancel the transaction and try again.
     UtxoCheckFailed,
+    /// Signing is locked due to inactivity, send `Unlock` first
+    Locked,
+    /// The manager is draining or shutting down (about to exit), retry the request with another instance
+    Draining,
+    /// The client reached one of its `client_quotas` limits (see `GetQuotas`)
+    QuotaExceeded,
+    /// The operation exceeds an `approvals` threshold and was queued until another client approves it (see `ListPendingApprovals`)
+    ApprovalRequired,
+    /// The wallet balance is too low for the quote, `details` contains the amounts (see `GetQuoteReq::allow_partial`)
+    QuoteLowBalance,
+    /// The wallet does not have the intermediate L-BTC amount for the second leg of a routed quote,
+    /// `details` contains the amounts (see `GetQuoteReq::allow_routing`)
+    RouteLowBalance,
+    /// `auth_token` or `client_tokens` is configured and the connection is not logged in (see `To::Login`).
+    /// The connection is closed after 3 failed attempts.
+    Unauthorized,
+    /// The WS connection has 100 requests in progress already (or the batch has more than 100 messages),
+    /// wait for the responses before sending more
+    TooManyRequests,
 }
 
+/// The structured fields of the error (the variant has the same name as the error code)
 #[derive(Debug, Serialize)]
-pub struct ErrorDetails {}
+pub enum ErrorDetails {
+    /// Amounts of the sent asset
+    QuoteLowBalance {
+        /// The amount that can be swapped
+        available: f64,
+        /// The amount required by the quote
+        required: f64,
+    },
+    /// The peg amount is less than the server minimum (see `PegFeeEstimate`)
+    PegAmountTooLow {
+        /// The minimum amount (BTC for peg-ins, L-BTC for peg-outs)
+        min_amount: f64,
+    },
+    UnknownTicker {
+        /// The requested ticker
+        ticker: String,
+        /// Known tickers that look similar to the requested one
+        suggestions: Vec<Ticker>,
+    },
+    AmbiguousTicker {
+        /// The requested ticker
+        ticker: String,
+        /// The matching tickers
+        candidates: Vec<Ticker>,
+    },
+    InvalidAssetAmount {
+        amount: f64,
+        /// The maximum number of decimal places
+        precision: u8,
+    },
+    /// Amounts in sats
+    NotEnoughAmount {
+        asset_id: elements::AssetId,
+        required: u64,
+        available: u64,
+    },
+    UnlockBackoff {
+        /// `Unlock` can be retried after this delay
+        retry_in_seconds: u64,
+    },
+    AmpAddressRequired {
+        asset: Ticker,
+    },
+    GaidResolveFailed {
+        gaid: String,
+    },
+    InvalidPegAddress {
+        /// `Bitcoin` or `Liquid`
+        expected_chain: String,
+    },
+    UnknownFeeTarget {
+        /// The requested confirmation target (in blocks)
+        target: i32,
+        /// The available confirmation targets
+        available: Vec<i32>,
+    },
+    UnknownWallet {
+        /// The requested wallet name
+        wallet: String,
+        /// The configured wallet names
+        available: Vec<String>,
+    },
+    AddressNotAllowed {
+        address: String,
+    },
+    MessageTooLong {
+        /// The maximum length in bytes
+        max_length: usize,
+    },
+    NotOwnAddress {
+        address: String,
+    },
+    QuotaExceeded {
+        resource: QuotaResource,
+        limit: u32,
+    },
+    InvalidAddress {
+        /// The recipient index
+        index: usize,
+    },
+    DuplicateRecipient {
+        /// The recipient indexes
+        index_a: usize,
+        index_b: usize,
+    },
+    SendAllNotAlone {
+        asset: Ticker,
+    },
+    NothingToSend {
+        asset: Ticker,
+    },
+    UtxoSelectionNotSupported {
+        asset: Ticker,
+    },
+    UnconfirmedInputs {
+        /// The number of the unconfirmed inputs
+        count: usize,
+    },
+    InvalidFeeRate {
+        /// The requested fee rate (sats/vbyte)
+        fee_rate: f64,
+    },
+    InvalidQuoteTimeout {
+        /// The requested timeout (in seconds)
+        timeout_secs: u64,
+    },
+    RestartRequired {
+        /// The names of the changed settings
+        fields: Vec<String>,
+    },
+    UnknownMonitoredTx {
+        txid: elements::Txid,
+    },
+    MonitoredTxUnconfirmed {
+        txid: elements::Txid,
+    },
+    InvalidReference {
+        reference: String,
+    },
+    ApprovalRequired {
+        /// See `ListPendingApprovals`
+        approval_id: String,
+    },
+    OrderAboveApprovalThreshold {
+        /// The sent asset
+        asset: Ticker,
+        /// The configured threshold of the asset
+        threshold: f64,
+    },
+    RouteLowBalance {
+        /// The intermediate asset (L-BTC)
+        asset: Ticker,
+        /// The amount sent by the second leg
+        required: f64,
+        /// The wallet balance of the intermediate asset
+        available: f64,
+    },
+    RequestTooLarge {
+        /// The request size in bytes
+        size: usize,
+        /// The limit in bytes
+        limit: usize,
+    },
+}
 
 #[derive(Debug, Serialize)]
 pub struct Error {
@@ -39,15 +371,22 @@ pub type QuoteId = sideswap_api::mkt::QuoteId;
 /// Unique string ID (random 32 bytes in hex encoding)
 pub type OrderId = sideswap_api::OrderId;
 
+/// Unique integer ID of a market order (assigned by the SideSwap server)
+pub type OrdId = sideswap_api::mkt::OrdId;
+
 /// Only selected whitelisted assets can be used here:
 /// L-BTC, USDt, EURx, MEX, DePix, AMP assets and some token assets.
 /// All asset balances are reported/accepted as floating point numbers using the asset precision.
+/// Tickers in requests are matched case-insensitively ("lbtc" and "LBTC" are accepted for "L-BTC"),
+/// configured `ticker_aliases` are accepted too.
 pub type Ticker = sideswap_common::dealer_ticker::DealerTicker;
 
 /// Wallet balance as float point number in the asset precision.
+/// Assets that are not whitelisted are included by the first 8 characters of the asset ID,
+/// with the amount in the asset base units (precision 0, see `GetAssetInfo`).
 pub type Balances = BTreeMap<Ticker, f64>;
 
-#[derive(Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
 pub enum TxStatus {
     /// Transaction is in the mempool
     Mempool,
@@ -70,19 +409,44 @@ pub struct MonitoredTx {
     pub description: String,
     /// Optional user note when the transaction was created (via SendTx or AcceptQuote)
     pub user_note: Option<String>,
+    /// Payment reference (assigned by `CreateTx` or `AcceptQuote`), not set for the transactions added before references were introduced
+    pub reference: Option<String>,
+    /// When the transaction was added to the monitored list.
+    /// The transactions added by older versions have the swap or the `CreateTx` time if known (the upgrade time otherwise).
+    pub created_at: TimestampMs,
 }
 
-#[derive(Deserialize)]
+/// A recipient is set either with `address` and `asset` or with `uri`
+#[derive(Serialize, Deserialize)]
 pub struct Recipient {
-    /// Recipient address. Must be confidential Liquid Bitcoin address.
-    pub address: elements::Address,
-    /// Asset to send (must be a whitelisted Ticker)
-    pub asset: Ticker,
-    /// Asset amount as a floating point number (in asset precision)
+    /// Recipient address. Must be a confidential Liquid address of the configured network
+    /// (unless `CreateTxReq::allow_unconfidential` is set), checked by `CreateTx`.
+    /// For AMP restricted assets it must be an address returned by `ResolveGaid` for the same asset.
+    /// If `enforce_allowlist` is enabled, it must be on the allow-list or belong to the wallet.
+    /// Required unless `uri` is set.
+    #[serde(default)]
+    pub address: Option<String>,
+    /// Asset to send (must be a whitelisted Ticker). Required unless `uri` is set.
+    #[serde(default)]
+    pub asset: Option<Ticker>,
+    /// BIP21-style payment URI, for example `liquidnetwork:<address>?amount=0.001&assetid=<asset_id>&label=Invoice%2042`
+    /// (`liquidtestnet:` on Testnet). The address is checked the same way as `address`.
+    /// `assetid` defaults to L-BTC and may be an asset that is not whitelisted if the asset registry knows its precision.
+    /// `label` is added to the transaction description (see `MonitoredTx`). If the URI has no `amount`, `amount` or `send_all` must be set.
+    #[serde(default)]
+    pub uri: Option<String>,
+    /// Asset amount as a number or a decimal string (in asset precision, `5`, `5.0` and `"5"` are the same).
+    /// Must not have more decimal places than the asset precision.
+    /// Ignored (can be omitted) if `send_all` is set. Must be omitted if `uri` has an amount.
+    #[serde(default, deserialize_with = "deserialize_amount")]
     pub amount: f64,
+    /// Send the whole wallet balance of the asset (for L-BTC, minus the network fee and the other L-BTC recipients).
+    /// The recipient must be the only one with this asset, the sent amount is returned in `CreateTxResp`.
+    #[serde(default)]
+    pub send_all: bool,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum BroadcastStatus {
     /// Broadcast succeeded (according to the specific node/service queried).
@@ -119,12 +483,16 @@ pub struct WalletTx {
     pub height: Option<u32>,
     /// Net change in the wallet balance (only whitelisted assets) in the asset precisions
     pub balance: BTreeMap<Ticker, f64>,
-    /// Network fee (in L-BTC satoshis)
-    pub network_fee: u64,
+    /// Network fee (in L-BTC satoshis), only set if it was paid by the wallet (the L-BTC balance decreased)
+    pub network_fee: Option<u64>,
     /// Transaction block timestamp (block timestamp if confirmed, none is if in mempool)
     pub timestamp: Option<TimestampMs>,
     /// Transaction type determined from the balance change (heuristic)
     pub tx_type: TxType,
+    /// Description of the monitored transaction with this txid (see `GetMonitoredTxs`)
+    pub description: Option<String>,
+    /// User note of the monitored transaction with this txid
+    pub user_note: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -137,7 +505,7 @@ pub struct Address {
     pub user_note: Option<String>,
 }
 
-#[derive(Debug, Copy, Clone, Serialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum PegTxState {
     /// Peg amount is less than the minimum and will not be processed
     InsufficientAmount,
@@ -192,6 +560,41 @@ pub struct PegStatus {
     pub created_at: TimestampMs,
     /// Optional user-submitted return address used for refunding `InsufficientAmount` peg-outs (liquid bitcoin address).
     pub return_address: Option<String>,
+    /// Payment reference (assigned by `NewPeg`), not set for the pegs added before references were introduced
+    pub reference: Option<String>,
+}
+
+/// Peg transaction state change
+#[derive(Serialize)]
+pub struct PegEvent {
+    /// When the change was received from the SideSwap server
+    pub timestamp: TimestampMs,
+    /// Txid of the user's payment (BTC for peg-in, L-BTC for peg-out).
+    pub tx_hash: sideswap_api::Hash32,
+    /// Output index (vout) of the user's payment.
+    pub vout: u32,
+    /// The new peg state
+    pub tx_state: PegTxState,
+    /// Payout txid (set if `tx_state` is `Done`)
+    pub payout_txid: Option<sideswap_api::Hash32>,
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum AssetType {
+    /// Base asset of the market
+    Base,
+    /// Quote asset of the market
+    Quote,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Market {
+    /// Base asset of the market
+    pub base: Ticker,
+    /// Quote asset of the market
+    pub quote: Ticker,
+    /// The market asset used to pay the server and network fees
+    pub fee_asset: AssetType,
 }
 
 // --- Requests ---
@@ -201,14 +604,18 @@ pub struct PegStatus {
 /// Generates a new unused address derived from the wallet's mnemonic.
 /// The application searches for the first unused index by checking both the blockchain history and the local DB (`Address` entries).
 /// It returns the address corresponding to the largest of the first unused index found in either source + 1.
-/// A gap limit (typically 20) of consecutive unused addresses is enforced, starting from the last address with blockchain activity.
+/// A gap limit (`gap_limit` in the config, 20 by default) of consecutive unused addresses is enforced, starting from the last address with blockchain activity.
 /// If the next address would exceed this gap limit, an error is returned.
 /// On success, the newly generated address info (index, address, optional note) is stored in the local DB.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct NewAddressReq {
     /// Optional user note to store alongside the address in the DB.
     /// This note is not stored on the blockchain.
     pub user_note: Option<String>,
+    /// The wallet name (see the `wallets` setting), `default` if not set.
+    /// Every wallet has its own address indexes and gap limit.
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
 }
 
 /// NewAddress response
@@ -220,20 +627,174 @@ pub struct NewAddressResp {
     /// Addresses can be reused. While they can receive any asset, only whitelisted assets
     /// will be reported in balances and handled by the manager.
     pub address: elements::Address,
+    /// How many more new addresses can be generated before the gap limit is reached
+    pub gap_limit_remaining: u32,
+}
+
+/// NewAddressBatch request
+///
+/// Generates up to `count` new addresses at once, the same way as `NewAddress`.
+/// Generation stops early if the gap limit is reached, the addresses generated before that are still stored in the local DB.
+#[derive(Serialize, Deserialize)]
+pub struct NewAddressBatchReq {
+    /// The number of addresses to generate
+    pub count: u32,
+    /// Optional user note prefix, the stored note is the prefix followed by the sequence number in the batch (starting from 1).
+    /// For example, `invoice-` gives `invoice-1`, `invoice-2`, etc.
+    pub note_prefix: Option<String>,
+    /// The wallet name (see the `wallets` setting), `default` if not set
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum AddressBatchStopReason {
+    /// The gap limit was reached
+    GapLimit,
+}
+
+/// NewAddressBatch response
+#[derive(Serialize)]
+pub struct NewAddressBatchResp {
+    /// The addresses actually generated (in the index order)
+    pub addresses: Vec<Address>,
+    /// Set if fewer than `count` addresses were generated
+    pub stopped_reason: Option<AddressBatchStopReason>,
+    /// How many more new addresses can be generated before the gap limit is reached
+    pub gap_limit_remaining: u32,
 }
 
 /// ListAddresses request
 ///
-/// Load all addresses from the local DB that were previously generated via `NewAddress`.
-#[derive(Deserialize)]
-pub struct ListAddressesReq {}
+/// Load all addresses from the local DB that were previously generated via `NewAddress`,
+/// together with their usage status from the wallet.
+#[derive(Serialize, Deserialize)]
+pub struct ListAddressesReq {
+    /// Also return the change addresses that received funds (change addresses are not stored in the local DB)
+    #[serde(default)]
+    pub include_change: bool,
+    /// The wallet name (see the `wallets` setting), `default` if not set
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
+}
+
+#[derive(Serialize)]
+pub struct ListedAddress {
+    /// Index in the address derivation path (BIP32 index)
+    pub index: u32,
+    /// Confidential Liquid Bitcoin address
+    pub address: elements::Address,
+    /// Optional user note associated when the address was generated (via `NewAddress`)
+    pub user_note: Option<String>,
+    /// Change (internal) address
+    pub change: bool,
+    /// The address received funds in any wallet transaction (including unconfirmed)
+    pub used: bool,
+}
 
 /// ListAddresses response
 #[derive(Serialize)]
 pub struct ListAddressesResp {
-    /// The list of addresses stored in the local DB.
+    /// The list of addresses stored in the local DB, sorted by index (change addresses follow if requested).
     /// The list might have gaps in indices or not start at 0 if the wallet mnemonic was used elsewhere previously.
-    pub addresses: Vec<Address>,
+    pub addresses: Vec<ListedAddress>,
+}
+
+#[derive(Serialize)]
+pub struct AllowedAddress {
+    /// Allowed destination address
+    pub address: elements::Address,
+    /// Optional label
+    pub label: Option<String>,
+    /// Who added the address (free text provided with `AddAllowedAddress`)
+    pub added_by: Option<String>,
+    /// When the address was added
+    pub added_at: TimestampMs,
+}
+
+/// AddAllowedAddress request
+///
+/// Adds a destination address to the allow-list (or updates the label if it's already there).
+/// If `enforce_allowlist` is enabled, `CreateTx` and `GetQuote` can only pay to allowed addresses or to the own wallet addresses.
+/// Requires `Unlock` first if `auto_lock` is configured.
+#[derive(Serialize, Deserialize)]
+pub struct AddAllowedAddressReq {
+    /// Destination address
+    pub address: elements::Address,
+    /// Optional label
+    pub label: Option<String>,
+    /// Optional name of the operator adding the address (stored for reference)
+    pub added_by: Option<String>,
+}
+
+/// AddAllowedAddress response
+#[derive(Serialize)]
+pub struct AddAllowedAddressResp {}
+
+/// RemoveAllowedAddress request
+///
+/// Removes a destination address from the allow-list.
+/// Requires `Unlock` first if `auto_lock` is configured.
+#[derive(Serialize, Deserialize)]
+pub struct RemoveAllowedAddressReq {
+    /// Destination address
+    pub address: elements::Address,
+}
+
+/// RemoveAllowedAddress response
+#[derive(Serialize)]
+pub struct RemoveAllowedAddressResp {}
+
+/// ListAllowedAddresses request
+#[derive(Serialize, Deserialize)]
+pub struct ListAllowedAddressesReq {}
+
+/// ListAllowedAddresses response
+#[derive(Serialize)]
+pub struct ListAllowedAddressesResp {
+    /// Whether the allow-list is enforced (the `enforce_allowlist` setting)
+    pub enforced: bool,
+    /// The allowed addresses, in the order they were added
+    pub addresses: Vec<AllowedAddress>,
+}
+
+/// GetBalanceHistory request
+///
+/// Returns the wallet balances recorded every `balance_history.interval_seconds` (and on clean shutdown), oldest first.
+/// Snapshots are not recorded until the wallet is synced.
+/// If `granularity_seconds` is set, or if the range contains more than 1000 snapshots,
+/// only the last snapshot of every period (aligned to the UNIX epoch) is returned.
+#[derive(Serialize, Deserialize)]
+pub struct GetBalanceHistoryReq {
+    /// Return only the balance of this asset
+    pub asset: Option<Ticker>,
+    /// The start of the range (inclusive), from the first recorded snapshot if not set
+    pub from: Option<TimestampMs>,
+    /// The end of the range (inclusive), up to the last recorded snapshot if not set
+    pub to: Option<TimestampMs>,
+    /// The period length, chosen automatically for long ranges if not set
+    pub granularity_seconds: Option<u64>,
+    /// The wallet name (see the `wallets` setting), `default` if not set
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
+}
+
+/// Recorded wallet balances
+#[derive(Serialize)]
+pub struct BalancePoint {
+    /// When the snapshot was recorded
+    pub timestamp: TimestampMs,
+    /// Balances of the known assets (including zero balances of the assets that were held before)
+    pub balances: Balances,
+}
+
+/// GetBalanceHistory response
+#[derive(Serialize)]
+pub struct GetBalanceHistoryResp {
+    /// The recorded snapshots, oldest first
+    pub points: Vec<BalancePoint>,
+    /// The period length used for downsampling, `None` if all snapshots are returned
+    pub granularity_seconds: Option<u64>,
 }
 
 /// CreateTx request
@@ -246,10 +807,48 @@ pub struct ListAddressesResp {
 ///   the amounts and sufficient L-BTC UTXOs for the network fee.
 /// - The created transaction is signed using the wallet's keys and stored temporarily in memory.
 /// - It is *not* saved to disk persistently nor broadcast to the network by this request. Use `SendTx` for that.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct CreateTxReq {
     /// The list of recipients, each specifying an address, asset, and amount.
+    /// Recipients with the same address and asset are rejected with an error, unless `aggregate_duplicates` is set.
     pub recipients: Vec<Recipient>,
+    /// Merge the recipients with the same address and asset into one output with the summed amount
+    #[serde(default)]
+    pub aggregate_duplicates: bool,
+    /// Network fee rate (in sats/vbyte), from 0.1 (the minimum relay fee) to 5.0.
+    /// If not set, the wallet default (0.1) is used.
+    pub fee_rate: Option<FeeRateSats>,
+    /// Accept non-confidential recipient addresses. Defaults to false.
+    #[serde(default)]
+    pub allow_unconfidential: bool,
+    /// Spend only these wallet UTXOs (see `ListUtxos`), in the `txid:vout` format.
+    /// Outpoints not owned by the wallet are ignored.
+    /// If the listed UTXOs don't cover the amounts, `ErrorCode::NotEnoughAmount` is returned.
+    /// The wallet supports manual coin selection only for L-BTC, so all recipients must be L-BTC
+    /// (listed UTXOs of other assets are not spent).
+    #[serde(default, serialize_with = "serialize_outpoints")]
+    pub utxos: Option<Vec<elements::OutPoint>>,
+    /// Allow spending unconfirmed UTXOs (see `BalancesNotif::unconfirmed`). Defaults to true.
+    /// If false, the L-BTC UTXOs are selected from the confirmed ones and if the wallet still used
+    /// unconfirmed inputs (of other assets or for the fee), `ErrorCode::UnconfirmedInputs` is returned.
+    #[serde(default = "default_true")]
+    pub allow_unconfirmed: bool,
+    /// The wallet that funds and signs the transaction (see the `wallets` setting), `default` if not set
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
+}
+
+/// A transaction output created for the request recipients
+#[derive(Serialize)]
+pub struct CreatedTxRecipient {
+    /// Recipient address (as specified in the first merged recipient)
+    pub address: elements::Address,
+    /// Asset sent
+    pub asset: Ticker,
+    /// Output amount (the sum of the merged recipient amounts)
+    pub amount: f64,
+    /// Indices of the request recipients paid by this output (more than one if duplicates were aggregated)
+    pub recipient_indices: Vec<usize>,
 }
 
 /// CreateTx response
@@ -260,6 +859,14 @@ pub struct CreateTxResp {
     pub txid: elements::Txid,
     /// Network fee (in L-sats) calculated for the created transaction.
     pub network_fee: u64,
+    /// The effective fee rate (`network_fee` divided by `vsize`, in sats/vbyte)
+    pub fee_rate: FeeRateSats,
+    /// Discounted virtual size of the created transaction (in vbytes, confidential outputs are discounted)
+    pub vsize: usize,
+    /// The outputs paying the request recipients, in the request order
+    pub recipients: Vec<CreatedTxRecipient>,
+    /// Payment reference, stays the same after the transaction is sent (see `FindByReference`)
+    pub reference: String,
 }
 
 /// SendTx request
@@ -283,21 +890,26 @@ pub struct CreateTxResp {
 ///     via the configured Electrs server. The success/failure of this attempt is reported in `res_wallet`.
 ///     Network errors during this step are captured in `res_wallet`.
 /// 6.  **Cleanup:** Regardless of broadcast outcomes (unless an early `UtxoCheckFailed` occurred),
-///     the temporary storage of *all* previously created (but not yet sent) transactions is cleared.
-///     Only one transaction can be "pending send" at a time.
+///     the sent transaction is removed from the created transactions.
+///     Other created transactions can still be sent (they fail the input check if they spend the same UTXOs).
+/// 7.  **Retry (Server):** If only the server broadcast failed, it is retried after the next reconnects
+///     to the SideSwap server (up to 5 times).
 ///
 /// **Client Handling:**
 /// - Call `CreateTx` then `SendTx` with the resulting `txid`.
 /// - If `SendTx` returns `ErrorCode::UtxoCheckFailed`, the transaction definitely wasn't broadcast.
 ///   It's safe to abandon or retry (`CreateTx` again).
-/// - If `SendTx` returns `ErrorCode::InvalidRequest`, the `txid` was not found (likely already sent/cleaned up or never created).
+/// - If `SendTx` returns `ErrorCode::UnknownCreatedTx`, the `txid` was not found (likely already sent/cleaned up or never created).
 /// - If `SendTx` succeeds (returns `SendTxResp`), **check both `res_wallet` and `res_server`**:
 ///     - If both show `Success`, broadcast is likely successful, but confirmation is not guaranteed. Monitor via `GetMonitoredTxs`.
 ///     - If both show `Error`, broadcast likely failed. Monitor via `GetMonitoredTxs` (as the DB record was created).
 ///     - If one is `Success` and one is `Error`, broadcast status is uncertain. Monitor via `GetMonitoredTxs`.
+///       The manager retries the server broadcast if the wallet broadcast succeeded.
 /// - If `SendTx` fails with any other `ErrorCode` (e.g., `ServerError`, `NetworkError`), with timeout or with closed connection,
 ///   the transaction *might* have been broadcast before the error occurred. Monitor via `GetMonitoredTxs` because the DB record is created early.
-#[derive(Deserialize)]
+/// - Repeating `SendTx` for an already sent transaction returns the stored result without broadcasting again.
+///   Set `idempotency_key` to get the same for retries after a lost response.
+#[derive(Serialize, Deserialize)]
 pub struct SendTxReq {
     /// Transaction ID returned by a previous `CreateTx` response.
     pub txid: elements::Txid,
@@ -307,10 +919,21 @@ pub struct SendTxReq {
     /// Use only the local wallet state and Electrs server. Defaults to false.
     #[serde(default)]
     pub wallet_only: bool,
+    /// Client-chosen key (from 1 to 128 bytes, e.g., a UUID): a request with an already used key returns the stored response
+    /// without sending the transaction again. The key must not be reused for another transaction (`ErrorCode::IdempotencyKeyReused`).
+    /// Only successful responses are stored, for `idempotency_key_retention_seconds` (24 hours by default).
+    /// The keys are scoped by the client name, the wallet and the request kind.
+    /// A key of a request interrupted by a restart fails with `ErrorCode::IdempotencyKeyInProgress` (the request is not repeated).
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
+    /// The wallet that created the transaction (`CreateTxReq::wallet`), `default` if not set.
+    /// Transactions created by another wallet are rejected with `ErrorCode::UnknownCreatedTx`.
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
 }
 
 /// SendTx response
-#[derive(Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SendTxResp {
     /// The broadcast status reported by the connected Electrs server.
     pub res_wallet: BroadcastStatus,
@@ -323,24 +946,85 @@ pub struct SendTxResp {
 ///
 /// Requests a swap quote from the SideSwap market maker backend.
 /// - A market (`send_asset` <-> `recv_asset`) must exist and have available liquidity.
-/// - An error is returned if no matching orders can fulfill the requested `send_amount`.
-/// - Quoted amounts (`recv_amount`) include SideSwap server fees and fixed network fees.
+/// - The quote is for the exact `send_amount` or for the exact `recv_amount` (exactly one of them must be set).
+/// - An error is returned if no matching orders can fulfill the requested amount.
+/// - Quoted amounts (`GetQuoteResp::send_amount` and `GetQuoteResp::recv_amount`) include SideSwap server fees and fixed network fees.
 /// - Requires an active WebSocket connection to the SideSwap server backend (managed internally).
-#[derive(Deserialize)]
+/// - Without a direct market, the swap can be routed through L-BTC (see `allow_routing`).
+#[derive(Serialize, Deserialize)]
 pub struct GetQuoteReq {
     /// The asset the user wants to sell.
     pub send_asset: Ticker,
     /// The asset the user wants to buy.
     pub recv_asset: Ticker,
-    /// The exact amount of `send_asset` the user will provide.
-    pub send_amount: f64,
+    /// The exact amount of `send_asset` the user will provide (a number or a decimal string, see `Recipient::amount`).
+    /// Exactly one of `send_amount` and `recv_amount` must be set.
+    #[serde(default, deserialize_with = "deserialize_optional_amount")]
+    pub send_amount: Option<f64>,
+    /// The exact amount of `recv_asset` the user will receive (the quoted `send_amount` includes the fees).
+    #[serde(default, deserialize_with = "deserialize_optional_amount")]
+    pub recv_amount: Option<f64>,
     /// The Liquid confidential address that will receive the `recv_asset`.
     /// This address does *not* need to belong to the user's wallet.
-    pub receive_address: elements::Address,
+    /// If `enforce_allowlist` is enabled, it must be on the allow-list or belong to the wallet.
+    /// Exactly one of `receive_address` and `gaid` must be set.
+    #[serde(default)]
+    pub receive_address: Option<elements::Address>,
+    /// GAID of the AMP subaccount that will receive the `recv_asset`,
+    /// resolved to an address with the server (as with `ResolveGaid`).
+    #[serde(default)]
+    pub gaid: Option<String>,
     /// If true, use only orders within a predefined price range (within 1-2% of the index price).
     /// This reduces liquidity but is safer.
     #[serde(default)]
     pub instant_swap: bool,
+    /// If the wallet balance is too low for the quote, quote the available `send_asset` amount instead of returning
+    /// `ErrorCode::QuoteLowBalance` (check `GetQuoteResp::send_amount` and `GetQuoteResp::recv_amount`,
+    /// the partial quote is always for the send amount).
+    #[serde(default)]
+    pub allow_partial: bool,
+    /// Verify the swap PSET received from the server against the quoted amounts before keeping the quote
+    /// (see `GetQuoteResp::pset_breakdown`). Defaults to true.
+    #[serde(default = "default_true")]
+    pub verify_pset: bool,
+    /// Send only these wallet UTXOs to the server (see `CreateTxReq::utxos`).
+    /// Quotes with the UTXO list are never coalesced.
+    #[serde(default, serialize_with = "serialize_outpoints")]
+    pub utxos: Option<Vec<elements::OutPoint>>,
+    /// Send unconfirmed UTXOs to the server too (see `BalancesNotif::unconfirmed`). Defaults to true.
+    /// The unconfirmed UTXOs are not counted if false (`ErrorCode::NotEnoughAmount` tells the confirmed amount),
+    /// such quotes are never coalesced.
+    #[serde(default = "default_true")]
+    pub allow_unconfirmed: bool,
+    /// How long to wait for the server quote (in seconds, from 1 to 60, 15 by default).
+    /// The quote subscription is stopped if no quote is received in time (the request fails with `ErrorCode::ServerTimeout`).
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// If there is no direct market, quote `send_asset` -> L-BTC and L-BTC -> `recv_asset` one after the other
+    /// (see `GetQuoteResp::route`). The first leg is received by the wallet, the second leg is paid
+    /// with the wallet L-BTC UTXOs (it is signed before the first leg is confirmed),
+    /// so the wallet must already have the intermediate L-BTC amount (`ErrorCode::RouteLowBalance` otherwise).
+    /// `allow_partial` applies to the first leg only if quoting by `send_amount`.
+    /// Does nothing if a direct market exists.
+    #[serde(default)]
+    pub allow_routing: bool,
+    /// The wallet that pays the quote and receives the change (see the `wallets` setting), `default` if not set.
+    /// `AcceptQuote` signs with the same wallet.
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
+}
+
+/// The verified swap PSET amounts
+#[derive(Debug, Clone, Serialize)]
+pub struct PsetBreakdown {
+    /// Total of the wallet inputs (`send_asset`)
+    pub total_in: f64,
+    /// The amount paid to `receive_address` (`recv_asset`)
+    pub receive_out: f64,
+    /// The amount paid back to the wallet change address (`send_asset`)
+    pub change_out: f64,
+    /// Network fee of the swap transaction (L-BTC)
+    pub network_fee: f64,
 }
 
 /// GetQuote response
@@ -348,12 +1032,36 @@ pub struct GetQuoteReq {
 pub struct GetQuoteResp {
     /// Quote ID, needed to accept the quote via `AcceptQuote`. Valid only for the `ttl` duration.
     pub quote_id: QuoteId,
+    /// The amount of `send_asset` the user will provide (quoted if requested with `recv_amount`),
+    /// less than requested if re-quoted because of `allow_partial`.
+    pub send_amount: f64,
     /// The exact amount of `recv_asset` the user will receive if the quote is accepted.
     pub recv_amount: f64,
+    /// The address that will receive the `recv_asset` (resolved from `gaid` if it was used)
+    pub receive_address: elements::Address,
     /// Time-To-Live: Duration (in milliseconds) for which this quote is valid and can be accepted. Typically around 30 seconds.
     pub ttl: DurationMs,
     /// Transaction ID (txid) of the atomic swap transaction prepared by the server. This txid will be monitored if the quote is accepted.
     pub txid: elements::Txid,
+    /// The verified PSET amounts, `None` if `verify_pset` was false
+    pub pset_breakdown: Option<PsetBreakdown>,
+    /// Set if the quote is routed through L-BTC, the other fields are for the whole swap then
+    /// (`quote_id`, `txid` and `pset_breakdown` belong to the first leg, `ttl` is the shorter leg TTL)
+    pub route: Option<QuoteRoute>,
+}
+
+/// The legs of a routed quote (see `GetQuoteReq::allow_routing`)
+#[derive(Debug, Clone, Serialize)]
+pub struct QuoteRoute {
+    /// The asset received by the first leg and sent by the second one (L-BTC)
+    pub intermediate_asset: Ticker,
+    pub intermediate_amount: f64,
+    /// The first leg quote ID (the same as `GetQuoteResp::quote_id`)
+    pub first_quote_id: QuoteId,
+    /// The second leg quote ID, it is accepted together with the first leg
+    pub second_quote_id: QuoteId,
+    pub second_txid: elements::Txid,
+    pub second_pset_breakdown: Option<PsetBreakdown>,
 }
 
 /// AcceptQuote request
@@ -362,33 +1070,143 @@ pub struct GetQuoteResp {
 ///
 /// **Process:**
 /// 1.  **Validation:** The manager checks if the `quote_id` exists and is still within its `ttl`.
-///     If not, `ErrorCode::InvalidRequest` is returned.
+///     If not, `ErrorCode::UnknownQuote` or `ErrorCode::QuoteExpired` is returned.
+///     Quotes returned before an upstream reconnect fail with `ErrorCode::QuoteInvalidatedByReconnect`.
 /// 2.  **DB Record:** A record for the swap transaction (`txid` from the original quote) is added
 ///     to the local database for monitoring via `GetMonitoredTxs`, including the optional `user_note`.
 /// 3.  **Server Request:** The manager sends the acceptance request to the SideSwap backend.
 ///     The backend handles the atomic swap execution.
+/// 4.  **Routed Quotes:** Both legs are validated first, then accepted in order (steps 2 and 3 for every leg).
+///     If the second leg fails, the first leg is not reverted and `AcceptQuoteResp::route` reports the error.
 ///
 /// **Client Handling:**
-/// - If the request returns `ErrorCode::InvalidRequest`,
+/// - If the request returns `ErrorCode::UnknownQuote`, `ErrorCode::QuoteExpired` or `ErrorCode::QuoteInvalidatedByReconnect`,
 ///   the quote is likely expired or invalid. Request a new quote (`GetQuote`).
 /// - If the request succeeds OR fails with any other error code,
 ///   the client should assume the swap *might* proceed or *might* have failed.
 ///   The definitive status should be checked by monitoring the transaction `txid`
 ///   (obtained from the original `GetQuoteResp`) using the `GetMonitoredTxs` request.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct AcceptQuoteReq {
     /// Quote ID obtained from a previous `GetQuoteResp`.
     pub quote_id: QuoteId,
     /// Optional user note to associate with this swap transaction in the monitored list.
     pub user_note: Option<String>,
+    /// Same as `SendTxReq::idempotency_key`, the swap is not signed again (the key must not be reused for another quote)
+    #[serde(default)]
+    pub idempotency_key: Option<String>,
 }
 
 /// AcceptQuote response
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize)]
 pub struct AcceptQuoteResp {
     /// Transaction ID (txid) of the swap transaction being executed.
     /// This should match the `txid` from the corresponding `GetQuoteResp`.
     pub txid: elements::Txid,
+    /// Payment reference (see `FindByReference`)
+    pub reference: String,
+    /// The second leg of a routed quote (see `GetQuoteResp::route`), `txid` and `reference` belong to the first leg
+    #[serde(default)]
+    pub route: Option<AcceptedRoute>,
+}
+
+/// The legs are accepted in order, the swap is partially completed if the second leg fails
+/// (the first leg is still executed and the intermediate amount stays in the wallet).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AcceptedRoute {
+    pub intermediate_asset: Ticker,
+    pub intermediate_amount: f64,
+    /// Not set if the second leg failed
+    pub second_txid: Option<elements::Txid>,
+    pub second_reference: Option<String>,
+    /// Why the second leg failed
+    pub second_leg_error: Option<String>,
+}
+
+/// CancelQuote request
+///
+/// Forgets a quote returned by `GetQuote` that is not going to be accepted.
+/// If the quote belongs to the latest quoting session, the session is stopped on the SideSwap server too
+/// (so the dealer does not keep the UTXOs reserved for the swap).
+/// The second leg of a routed quote is forgotten too.
+#[derive(Serialize, Deserialize)]
+pub struct CancelQuoteReq {
+    /// Quote ID obtained from a previous `GetQuoteResp`.
+    pub quote_id: QuoteId,
+}
+
+/// CancelQuote response
+#[derive(Serialize)]
+pub struct CancelQuoteResp {}
+
+/// Trade direction of the base asset
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub enum TradeDir {
+    /// The base asset is sent
+    Sell,
+    /// The base asset is received
+    Buy,
+}
+
+/// The quote numbers reported by the SideSwap server (amounts in the asset base units)
+#[derive(Serialize, Deserialize)]
+pub struct QuoteNumbers {
+    /// Base asset of the market
+    pub base: Ticker,
+    /// Quote asset of the market
+    pub quote: Ticker,
+    /// The base asset amount before fees
+    pub base_amount: u64,
+    /// The quote asset amount before fees
+    pub quote_amount: u64,
+    /// Server fee (paid with `fee_asset`)
+    pub server_fee: u64,
+    /// Fixed fee (paid with `fee_asset`)
+    pub fixed_fee: u64,
+    /// Trade direction of the base asset
+    pub trade_dir: TradeDir,
+    /// The market fee asset
+    pub fee_asset: AssetType,
+}
+
+/// ExplainQuote request
+///
+/// Explains how the sent and received amounts are derived from the quote numbers
+/// (using the same calculation as `GetQuote`), without contacting the SideSwap server.
+/// Exactly one of `quote_id` and `numbers` must be set.
+#[derive(Serialize, Deserialize)]
+pub struct ExplainQuoteReq {
+    /// A quote returned by `GetQuote` (only quotes that were not accepted and not expired yet are stored)
+    pub quote_id: Option<QuoteId>,
+    /// The raw quote numbers
+    pub numbers: Option<QuoteNumbers>,
+}
+
+/// ExplainQuote response
+#[derive(Serialize)]
+pub struct ExplainQuoteResp {
+    /// The quote numbers used
+    pub numbers: QuoteNumbers,
+    /// The sent asset
+    pub send_asset: Ticker,
+    /// The sent amount (including the fee if it's paid with the sent asset)
+    pub send_amount: f64,
+    /// The sent amount in the asset base units
+    pub send_amount_raw: u64,
+    /// The received asset
+    pub recv_asset: Ticker,
+    /// The received amount (without the fee if it's paid with the received asset)
+    pub recv_amount: f64,
+    /// The received amount in the asset base units
+    pub recv_amount_raw: u64,
+    /// The fee asset
+    pub fee_asset: Ticker,
+    /// The total fee (server fee + fixed fee)
+    pub fee_amount: f64,
+    /// The total fee in the asset base units
+    pub fee_amount_raw: u64,
+    /// Human-readable explanation, one step per line
+    pub breakdown: Vec<String>,
 }
 
 /// NewPeg request
@@ -399,10 +1217,11 @@ pub struct AcceptQuoteResp {
 /// - On success, the server returns a `PegStatus` containing the details
 /// (server address, order ID, etc.), and this order ID is stored locally in the DB for tracking.
 /// Subsequent status updates will be delivered via `PegStatusNotif`.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct NewPegReq {
     /// The user's address that will receive the converted funds.
     /// (Liquid address for peg-ins, Bitcoin address for peg-outs).
+    /// Checked locally against the peg direction and the network, peg-ins require a confidential address.
     pub addr_recv: String,
     /// `true` for peg-in (BTC -> L-BTC), `false` for peg-out (L-BTC -> BTC).
     pub peg_in: bool,
@@ -410,6 +1229,9 @@ pub struct NewPegReq {
     /// If not set, the current value is used for 2 blocks.
     /// Cannot be less than 1.0.
     pub fee_rate: Option<FeeRateSats>,
+    /// Accept a non-confidential Liquid address for peg-ins. Defaults to false.
+    #[serde(default)]
+    pub allow_unconfidential: bool,
 }
 
 /// NewPeg response
@@ -420,12 +1242,124 @@ pub struct NewPegResp {
     pub peg: PegStatus,
 }
 
+/// PegFeeEstimate request
+///
+/// Estimates the received amount for the sent amount using the current SideSwap server fees, without creating a peg.
+/// Amounts below the server minimum fail with `ErrorDetails::PegAmountTooLow` (which includes the minimum).
+#[derive(Serialize, Deserialize)]
+pub struct PegFeeEstimateReq {
+    /// `true` for peg-in (BTC -> L-BTC), `false` for peg-out (L-BTC -> BTC).
+    pub peg_in: bool,
+    /// The sent amount (BTC for peg-ins, L-BTC for peg-outs)
+    pub amount: f64,
+    /// Confirmation target of the peg-out bitcoin transaction (in blocks, 2 by default).
+    /// Must be one of the server fee rate targets. Not used for peg-ins.
+    pub blocks: Option<i32>,
+}
+
+/// PegFeeEstimate response
+#[derive(Serialize)]
+pub struct PegFeeEstimateResp {
+    /// The server fee (percent of the sent amount)
+    pub server_fee_percent: f64,
+    /// The bitcoin network fee of the peg-out transaction (zero for peg-ins)
+    pub fixed_fee: f64,
+    /// The fee rate (in sats/vbyte) used for the peg-out transaction, not set for peg-ins
+    pub fee_rate: Option<FeeRateSats>,
+    /// The estimated received amount
+    pub recv_amount: f64,
+    /// The server minimum for the sent amount
+    pub min_amount: f64,
+}
+
+/// GetPegTimeline request
+///
+/// Returns the recorded state changes of the peg transactions, oldest first.
+/// A new event is recorded when a transaction is detected for the first time or when its state or payout txid changes
+/// (confirmation count updates are not recorded). Only the last 50 events are kept for each peg.
+#[derive(Serialize, Deserialize)]
+pub struct GetPegTimelineReq {
+    /// Peg order id (must be stored in the local DB)
+    pub order_id: OrderId,
+}
+
+/// GetPegTimeline response
+#[derive(Serialize)]
+pub struct GetPegTimelineResp {
+    /// The recorded peg events, oldest first
+    pub events: Vec<PegEvent>,
+}
+
+/// GetPegDetails request
+///
+/// Returns the latest state of every peg transaction (stored in the local DB and updated from the server peg statuses).
+/// For peg-ins, `txid` is the mainchain (bitcoin) txid of the user's payment.
+#[derive(Serialize, Deserialize)]
+pub struct GetPegDetailsReq {
+    /// Peg order id (must be stored in the local DB)
+    pub order_id: OrderId,
+}
+
+/// The peg transaction status reported by the server
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum PegTxProgress {
+    InsufficientAmount,
+    Detected,
+    Processing,
+    Done,
+    /// A status not known to this version (as received from the server)
+    Unknown(String),
+}
+
+impl PegTxProgress {
+    /// Case, spaces and underscores are ignored ("Insufficient amount" is `InsufficientAmount`)
+    pub fn from_server(status: &str) -> PegTxProgress {
+        let normalized = status
+            .chars()
+            .filter(|c| c.is_alphanumeric())
+            .collect::<String>()
+            .to_lowercase();
+        match normalized.as_str() {
+            "insufficientamount" => PegTxProgress::InsufficientAmount,
+            "detected" => PegTxProgress::Detected,
+            "processing" => PegTxProgress::Processing,
+            "done" => PegTxProgress::Done,
+            _ => PegTxProgress::Unknown(status.to_owned()),
+        }
+    }
+}
+
+/// A peg transaction
+#[derive(Serialize)]
+pub struct PegTxDetails {
+    /// Txid of the user's payment (BTC for peg-in, L-BTC for peg-out)
+    pub txid: sideswap_api::Hash32,
+    /// Output index (vout) of the user's payment
+    pub vout: u32,
+    /// How much the user has paid (in bitcoins for peg-in, liquid bitcoins for peg-out)
+    pub amount: f64,
+    pub status: PegTxProgress,
+    /// Payout txid (Liquid Bitcoin for peg-ins and Bitcoin for peg-outs)
+    pub payout_txid: Option<sideswap_api::Hash32>,
+    /// How much will be paid or has been paid (in L-BTC for peg-ins, BTC for peg-outs)
+    pub payout_amount: Option<f64>,
+    /// When the transaction was detected or last changed
+    pub updated_at: TimestampMs,
+}
+
+/// GetPegDetails response
+#[derive(Serialize)]
+pub struct GetPegDetailsResp {
+    /// The peg transactions, in the detection order
+    pub txs: Vec<PegTxDetails>,
+}
+
 /// DelPeg request
 ///
 /// Removes a peg order (identified by `order_id`) from the local database.
 /// - This stops the manager from tracking this peg order and sending `PegStatusNotif` updates for it.
 /// - This request only affects the local client/manager; it does *not* cancel or delete the order on the SideSwap server.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct DelPegReq {
     /// The ID of the peg order to stop monitoring locally.
     pub order_id: OrderId,
@@ -440,7 +1374,7 @@ pub struct DelPegResp {}
 /// Retrieves the list of all transactions currently being monitored by the manager.
 /// This includes transactions initiated via `SendTx` and `AcceptQuote` that haven't been removed by `DelMonitoredTx`.
 /// The status (`TxStatus`) reflects the latest information obtained from the Electrs server.
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct GetMonitoredTxsReq {}
 
 /// GetMonitoredTxs response
@@ -456,22 +1390,95 @@ pub struct GetMonitoredTxsResp {
 /// - This stops the transaction from appearing in the `GetMonitoredTxs` response.
 /// - It does *not* affect the transaction's presence in the wallet history (`GetWalletTxs`) or on the blockchain.
 /// - Useful for cleaning up completed or irrelevant monitored transactions.
-#[derive(Deserialize)]
+///
+/// Fails if the transaction is not monitored, or if it is still in the mempool and `force` is not set.
+#[derive(Serialize, Deserialize)]
 pub struct DelMonitoredTxReq {
     /// The ID of the transaction to remove from monitoring.
     pub txid: elements::Txid,
+    /// Remove the transaction even if it is not confirmed yet. Defaults to false.
+    #[serde(default)]
+    pub force: bool,
 }
 
 /// DelMonitoredTx response
 #[derive(Serialize)]
 pub struct DelMonitoredTxResp {}
 
+/// SetTxNote request
+///
+/// Changes the user note of a monitored transaction, connected clients are notified with `TxNote`.
+#[derive(Serialize, Deserialize)]
+pub struct SetTxNoteReq {
+    pub txid: elements::Txid,
+    /// The new note, null removes it
+    pub user_note: Option<String>,
+}
+
+/// SetTxNote response
+#[derive(Serialize)]
+pub struct SetTxNoteResp {}
+
+/// The notes of a monitored transaction (see `MonitoredTx`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TxNotes {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub user_note: Option<String>,
+}
+
+/// ExportNotes request
+///
+/// Returns the notes of all monitored transactions (for backups, see `ImportNotes`).
+#[derive(Serialize, Deserialize)]
+pub struct ExportNotesReq {}
+
+/// ExportNotes response
+#[derive(Serialize)]
+pub struct ExportNotesResp {
+    pub notes: BTreeMap<elements::Txid, TxNotes>,
+}
+
+/// ImportNotes request
+///
+/// Restores the notes exported by `ExportNotes`, for example after the database was recreated.
+/// The transactions that are not monitored are added to the monitored transactions.
+/// A monitored transaction with a different description or user note is skipped, unless `overwrite` is set.
+/// Nothing is imported if any txid is invalid.
+/// Connected clients are notified with `TxNote` about every imported transaction.
+#[derive(Serialize, Deserialize)]
+pub struct ImportNotesReq {
+    /// The `ExportNotesResp::notes` value
+    pub notes: BTreeMap<String, TxNotes>,
+    /// Replace the conflicting notes. Defaults to false.
+    #[serde(default)]
+    pub overwrite: bool,
+}
+
+/// ImportNotes response
+#[derive(Serialize)]
+pub struct ImportNotesResp {
+    /// Added or updated transactions
+    pub imported: Vec<elements::Txid>,
+    /// Transactions with conflicting notes (not changed)
+    pub skipped: Vec<elements::Txid>,
+}
+
 /// GetWalletTxs request
 ///
 /// Retrieves the transaction history for the wallet,
 /// as reported by the underlying LWK instance (via Electrs).
-#[derive(Deserialize)]
-pub struct GetWalletTxsReq {}
+/// Unlike `GetMonitoredTxs`, incoming transactions not created by the manager are included too.
+#[derive(Serialize, Deserialize)]
+pub struct GetWalletTxsReq {
+    /// Return only the transactions confirmed above this block height and the mempool transactions
+    /// (pollers can pass the largest height seen so far)
+    pub after_height: Option<u32>,
+    /// The wallet to list the transactions of (see the `wallets` setting), `default` if not set
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
+}
 
 /// GetWalletTxs response
 #[derive(Serialize)]
@@ -480,22 +1487,708 @@ pub struct GetWalletTxsResp {
     pub txs: Vec<WalletTx>,
 }
 
-// --- Notifications ---
-
-/// Wallet balances notification
+/// Unlock request
 ///
-/// Sent automatically when:
-/// - A new client connects (providing the initial balance state).
-/// - The wallet balance for any whitelisted asset changes (due to incoming/outgoing txs, swaps).
-#[derive(Debug, Serialize, PartialEq, Clone)]
-pub struct BalancesNotif {
-    /// Current wallet balances for all whitelisted assets (UTXOs on the blockchain and in the mempool)
-    pub balances: Balances,
-    /// Current wallet balances for all whitelisted assets (only UTXOs on the blockchain)
-    pub confirmed: Balances,
+/// Unlocks signing after it was locked due to inactivity (only if `auto_lock` is configured).
+/// While locked, `CreateTx`, `SendTx`, `GetQuote`, `AcceptQuote` and `SignMessage` fail with the `Locked` error, read-only requests keep working.
+/// After a wrong password, the next attempt is accepted only after a delay (which doubles after every wrong attempt).
+#[derive(Serialize, Deserialize)]
+pub struct UnlockReq {
+    /// The unlock password (the hash of it must match `auto_lock.password_hash`)
+    pub password: String,
 }
 
-/// Peg status notification
+/// Unlock response
+#[derive(Serialize)]
+pub struct UnlockResp {}
+
+/// GetServerInfo request
+///
+/// Returns the manager status and diagnostics.
+#[derive(Serialize, Deserialize)]
+pub struct GetServerInfoReq {}
+
+/// GetServerInfo response
+#[derive(Serialize)]
+pub struct GetServerInfoResp {
+    /// True if the local clock differs from the configured `clock_check.time_source_url` by more than `clock_check.max_skew_seconds`.
+    /// While set, the quote TTL reported by `GetQuote` is reduced to keep a safety margin.
+    pub clock_skew_detected: bool,
+    /// The last measured clock skew (the time source time minus the local time, in milliseconds), if known
+    pub clock_skew_ms: Option<i64>,
+    /// The number of discarded Quote notifications from the SideSwap server that did not match any quote subscription in the current session
+    pub stale_quote_notifs: u64,
+    /// The number of SideSwap server messages that could not be decoded (most likely because of a newer server version).
+    /// Unknown notifications are skipped, unknown responses fail the request, unknown quote statuses fail the quote.
+    pub skipped_upstream_msgs: u64,
+    /// The size of the largest SideSwap server message received since start (in bytes)
+    pub largest_upstream_msg: u64,
+    /// Serialized sizes of the requests sent to the SideSwap server since start, by request type (for example `Market.start_quotes`)
+    pub upstream_request_sizes: BTreeMap<String, RequestSizes>,
+    /// True if draining was started (with `Drain` or SIGUSR2), new clients should be routed to another instance
+    pub draining: bool,
+    /// The manager version
+    pub version: String,
+    /// The DB schema version (the latest applied migration)
+    pub schema_version: i64,
+    /// The onion service address (`<service_id>.onion:<port>`), set while the onion service is published (see `tor` in the config)
+    pub onion_address: Option<String>,
+    /// The reason the configured onion service is not published (for example, the tor controller is not reachable).
+    /// The WS server keeps accepting TCP connections.
+    pub tor_error: Option<String>,
+}
+
+/// GetDiagnostics request
+///
+/// Returns the counters of unusual upstream events since start, and the stored data that does not match the configured env.
+#[derive(Serialize, Deserialize)]
+pub struct GetDiagnosticsReq {}
+
+/// GetDiagnostics response
+#[derive(Serialize)]
+pub struct GetDiagnosticsResp {
+    /// The number of Quote notifications from the SideSwap server that were not used by a `GetQuote` request
+    /// (including the updates of the already received quotes and the quotes of stopped subscriptions).
+    /// A steadily growing value means the quote subscriptions are not stopped.
+    pub discarded_quote_notifs: u64,
+    /// The part of `discarded_quote_notifs` that did not match any quote subscription in the current session
+    /// (same as `GetServerInfoResp::stale_quote_notifs`)
+    pub stale_quote_notifs: u64,
+    /// The number of `GetQuote` requests that received no quote in time
+    pub quote_timeouts: u64,
+    /// Stored pegs whose status the server refused to report after the last reconnect (most likely created with another env)
+    pub rejected_pegs: Vec<OrderId>,
+    /// The number of stored addresses of another network, skipped on startup
+    pub foreign_addresses: u64,
+}
+
+/// Serialized sizes of one upstream request type
+#[derive(Serialize)]
+pub struct RequestSizes {
+    /// The number of requests (including rejected)
+    pub count: u64,
+    /// The total size (in bytes)
+    pub total_bytes: u64,
+    /// The largest request size (in bytes)
+    pub max_bytes: u64,
+    /// The number of requests that were not sent because they exceeded `upstream_size_limits.hard_bytes`
+    pub rejected: u64,
+    /// Request counts by size: up to 1 KiB, 16 KiB, 256 KiB, 1 MiB, 16 MiB and larger
+    pub histogram: Vec<u64>,
+}
+
+/// A resource limited by `client_quotas`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum QuotaResource {
+    /// Pegs created with `NewPeg` that are not deleted and not finished (all detected payments completed or failed)
+    Pegs,
+    /// Transactions sent with `SendTx` or `AcceptQuote` that are not confirmed yet
+    MonitoredTxs,
+    /// Transactions created with `CreateTx` and not sent yet
+    CreatedTxs,
+    /// Quotes returned by `GetQuote` that are not accepted, cancelled or expired yet
+    Quotes,
+}
+
+impl std::fmt::Display for QuotaResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            QuotaResource::Pegs => "pegs",
+            QuotaResource::MonitoredTxs => "monitored txs",
+            QuotaResource::CreatedTxs => "created txs",
+            QuotaResource::Quotes => "quotes",
+        };
+        f.write_str(name)
+    }
+}
+
+/// GetQuotas request
+///
+/// Returns the quota usage of the calling client.
+/// Clients are identified by the `client_name` WS URL query parameter (for example `ws://127.0.0.1:3102/?client_name=team-a`),
+/// all clients without a name share one set of quotas.
+/// Requests that would exceed a quota fail with the `QuotaExceeded` error.
+#[derive(Serialize, Deserialize)]
+pub struct GetQuotasReq {}
+
+/// The usage of one quota
+#[derive(Serialize)]
+pub struct QuotaUsage {
+    /// The limited resource
+    pub resource: QuotaResource,
+    /// How many resources are currently used by the client
+    pub used: u32,
+    /// The configured limit, not set if the resource is not limited
+    pub limit: Option<u32>,
+}
+
+/// GetQuotas response
+#[derive(Serialize)]
+pub struct GetQuotasResp {
+    /// The client name (from the WS URL), not set for clients without a name
+    pub client_name: Option<String>,
+    /// The usage of every quota
+    pub quotas: Vec<QuotaUsage>,
+}
+
+/// The resource kind a payment reference is assigned to
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ReferenceKind {
+    Peg,
+    Tx,
+}
+
+/// FindByReference request
+///
+/// Payment references are short codes like `0000-Z9Q` assigned by `CreateTx`, `AcceptQuote` and `NewPeg`,
+/// unique across all resource types. The last character is a checksum.
+/// Lookup is case-insensitive, the `-` is optional, and `I`, `L` and `O` are read as `1`, `1` and `0`.
+#[derive(Serialize, Deserialize)]
+pub struct FindByReferenceReq {
+    pub reference: String,
+}
+
+#[derive(Serialize)]
+pub enum ReferencedResource {
+    /// Sent transaction (see `GetMonitoredTxs`)
+    MonitoredTx { txid: elements::Txid },
+    /// Created but not yet sent transaction
+    CreatedTx { txid: elements::Txid },
+    /// Peg order
+    Peg { order_id: OrderId },
+}
+
+/// FindByReference response
+#[derive(Serialize)]
+pub struct FindByReferenceResp {
+    /// The reference in the canonical form
+    pub reference: String,
+    pub resource: ReferencedResource,
+}
+
+/// The queued operation type
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum ApprovalOperation {
+    SendTx,
+    AcceptQuote,
+}
+
+#[derive(Serialize)]
+pub struct PendingApproval {
+    pub approval_id: String,
+    pub operation: ApprovalOperation,
+    /// The transaction that is sent once approved
+    pub txid: elements::Txid,
+    /// Operation description, for example "send 1 L-BTC to ..."
+    pub description: String,
+    /// The sent amounts by asset
+    pub amounts: BTreeMap<Ticker, f64>,
+    /// The requester client name
+    pub requested_by: Option<String>,
+    pub created_at: TimestampMs,
+    /// The operation is rejected if not approved by this time
+    pub expires_at: TimestampMs,
+}
+
+/// ListPendingApprovals request
+///
+/// `SendTx` and `AcceptQuote` requests sending more than the configured `approvals` thresholds are not executed,
+/// they fail with `ErrorCode::ApprovalRequired` (the error text contains the approval id) and are queued instead.
+/// Queued operations are executed with `Approve` (by another named client) or dropped with `Reject`.
+/// The requester gets the `ApprovalResolved` notification in either case (and when the approval expires).
+#[derive(Serialize, Deserialize)]
+pub struct ListPendingApprovalsReq {}
+
+/// ListPendingApprovals response
+#[derive(Serialize)]
+pub struct ListPendingApprovalsResp {
+    /// Oldest first
+    pub approvals: Vec<PendingApproval>,
+}
+
+/// Approve request
+///
+/// Executes the queued operation the same way as the original request.
+/// The approver must log in with its own token from `[ws_server].client_tokens` (the name must differ from the requester's).
+/// `AcceptQuote` operations must be approved before the quote expires.
+/// Requires `Unlock` first if `auto_lock` is configured.
+#[derive(Serialize, Deserialize)]
+pub struct ApproveReq {
+    pub approval_id: String,
+}
+
+/// Approve response
+#[derive(Serialize)]
+pub struct ApproveResp {
+    /// The sent transaction (see `GetMonitoredTxs`)
+    pub txid: elements::Txid,
+    /// The broadcast results (for `SendTx` operations)
+    pub send_tx: Option<SendTxResp>,
+}
+
+/// Reject request
+#[derive(Serialize, Deserialize)]
+pub struct RejectReq {
+    pub approval_id: String,
+    /// Optional reason, forwarded to the requester
+    pub reason: Option<String>,
+}
+
+/// Reject response
+#[derive(Serialize)]
+pub struct RejectResp {}
+
+/// SubscribePrice request
+///
+/// Subscribes the client to `MarketPrice` notifications of the market (without creating quotes).
+/// The last known price is sent right away if available.
+/// The manager subscribes upstream while at least one client is subscribed (the subscription is dropped on disconnect).
+#[derive(Serialize, Deserialize)]
+pub struct SubscribePriceReq {
+    /// Base asset of the market
+    pub base: Ticker,
+    /// Quote asset of the market
+    pub quote: Ticker,
+}
+
+/// SubscribePrice response
+#[derive(Serialize)]
+pub struct SubscribePriceResp {}
+
+/// UnsubscribePrice request
+///
+/// Stops the `MarketPrice` notifications started with `SubscribePrice` (does nothing if not subscribed)
+#[derive(Serialize, Deserialize)]
+pub struct UnsubscribePriceReq {
+    /// Base asset of the market
+    pub base: Ticker,
+    /// Quote asset of the market
+    pub quote: Ticker,
+}
+
+/// UnsubscribePrice response
+#[derive(Serialize)]
+pub struct UnsubscribePriceResp {}
+
+/// ReloadConfig request
+///
+/// Re-read the config file and apply the changed settings that don't require a restart
+/// (`gap_limit`, `gap_limit_warning`, `drain_grace_seconds`, `shutdown_timeout_seconds`, `created_tx_max_age_seconds`, `enforce_allowlist`, `balance_history`, `upstream_size_limits`, `client_quotas` and `approvals`).
+/// Nothing is applied if any other setting was changed. Same as sending SIGHUP to the process.
+/// Requires `Unlock` first if `auto_lock` is configured.
+#[derive(Serialize, Deserialize)]
+pub struct ReloadConfigReq {}
+
+/// ReloadConfig response
+#[derive(Serialize)]
+pub struct ReloadConfigResp {
+    /// The names of the changed settings (empty if nothing was changed)
+    pub changed_fields: Vec<String>,
+}
+
+/// RefreshTickers request
+///
+/// Reload the assets from the asset registry (updated in the background) and `whitelisted_assets` from the config file.
+/// Only new assets are added, the known ones are not changed. Removing whitelisted assets requires a restart.
+/// The new assets are included in `Balances` with the next wallet update.
+/// Requires `Unlock` first if `auto_lock` is configured.
+#[derive(Serialize, Deserialize)]
+pub struct RefreshTickersReq {}
+
+/// RefreshTickers response
+#[derive(Serialize)]
+pub struct RefreshTickersResp {
+    /// The tickers of the added assets (empty if nothing was added)
+    pub added: Vec<Ticker>,
+}
+
+/// Drain request
+///
+/// Prepares the manager for an upgrade: the WS server stops accepting new connections,
+/// connected clients get the `Draining` notification and the process exits when idle
+/// (no pending quotes and no created transactions) or when the grace period lapses.
+/// While draining, `NewPeg`, `DelPeg`, `NewAddress`, `NewAddressBatch`, `CreateTx`, `GetQuote` and `DelMonitoredTx`
+/// fail with the `Draining` error. `SendTx` and `AcceptQuote` keep working to finish the started operations,
+/// read-only requests keep working too.
+/// Sending `Drain` again does not extend the grace period.
+#[derive(Serialize, Deserialize)]
+pub struct DrainReq {
+    /// The process exits after this many seconds even if not idle
+    pub grace_seconds: u64,
+}
+
+/// Drain response
+#[derive(Serialize)]
+pub struct DrainResp {}
+
+/// Asset details
+#[derive(Serialize)]
+pub struct AssetInfo {
+    /// Asset ticker
+    pub ticker: Ticker,
+    /// Asset ID
+    pub asset_id: elements::AssetId,
+    /// Asset precision (the number of decimal places)
+    pub precision: u8,
+    /// True if the asset is an AMP asset with transfer restrictions.
+    /// Such assets can only be sent to addresses returned by `ResolveGaid`.
+    /// Not set until the asset metadata is received from the SideSwap server.
+    pub amp_restricted: Option<bool>,
+    /// True if the asset can be used to pay network fees with payjoin.
+    /// Not set until the asset metadata is received from the SideSwap server.
+    pub payjoin: Option<bool>,
+}
+
+/// ListAssets request
+///
+/// Returns the whitelisted assets.
+#[derive(Serialize, Deserialize)]
+pub struct ListAssetsReq {}
+
+/// ListAssets response
+#[derive(Serialize)]
+pub struct ListAssetsResp {
+    /// The list of whitelisted assets
+    pub assets: Vec<AssetInfo>,
+}
+
+/// GetAssetInfo request
+///
+/// Returns the asset registry information, for example, to label the balances of assets that are not whitelisted.
+#[derive(Serialize, Deserialize)]
+pub struct GetAssetInfoReq {
+    pub asset_id: elements::AssetId,
+}
+
+/// GetAssetInfo response
+#[derive(Serialize)]
+pub struct GetAssetInfoResp {
+    pub asset_id: elements::AssetId,
+    /// The key of the asset in `Balances`
+    pub balance_key: Ticker,
+    /// True if the asset is whitelisted (can be used in requests, `balance_key` is its ticker)
+    pub whitelisted: bool,
+    /// True if the asset is found in the asset registry (the fields below are set then)
+    pub registered: bool,
+    pub name: Option<String>,
+    /// The registered ticker (can differ from `balance_key` and can be longer than the supported tickers)
+    pub ticker: Option<String>,
+    /// The registered precision, the balances of assets that are not whitelisted are reported with precision 0
+    pub precision: Option<u8>,
+}
+
+/// ListMarkets request
+///
+/// Returns the markets currently known to the manager (same as the `Markets` notification).
+/// Markets with assets that are not whitelisted are not included.
+/// The server does not report the market min/max amounts, so they are not returned.
+#[derive(Serialize, Deserialize)]
+pub struct ListMarketsReq {}
+
+/// ListMarkets response
+#[derive(Serialize)]
+pub struct ListMarketsResp {
+    /// The list of available markets
+    pub markets: Vec<Market>,
+    /// True if the connection to the SideSwap server is down and the list was received too long ago to be trusted
+    pub stale: bool,
+}
+
+/// ListUtxos request
+///
+/// Returns the wallet UTXOs that can be spent (and are sent to the server for quotes).
+/// UTXOs with assets that are not whitelisted are not included.
+#[derive(Serialize, Deserialize)]
+pub struct ListUtxosReq {
+    /// The wallet to list the UTXOs of (see the `wallets` setting), `default` if not set
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
+}
+
+/// A wallet UTXO
+#[derive(Serialize)]
+pub struct UtxoInfo {
+    /// The UTXO outpoint (`txid:vout`)
+    #[serde(serialize_with = "serialize_outpoint")]
+    pub outpoint: elements::OutPoint,
+    /// Asset ticker
+    pub ticker: Ticker,
+    pub amount: f64,
+    /// 0 if not confirmed yet
+    pub confirmations: u32,
+}
+
+/// ListUtxos response
+#[derive(Serialize)]
+pub struct ListUtxosResp {
+    pub utxos: Vec<UtxoInfo>,
+}
+
+/// ResolveGaid request
+///
+/// Resolves a GAID (Green Account ID of an AMP subaccount) to a receive address for an AMP asset.
+/// The resolved address is remembered (until restart) and can be used with `CreateTx` to send this asset.
+#[derive(Serialize, Deserialize)]
+pub struct ResolveGaidReq {
+    /// AMP asset to send
+    pub asset: Ticker,
+    /// GAID of the recipient AMP subaccount
+    pub gaid: String,
+}
+
+/// ResolveGaid response
+#[derive(Serialize)]
+pub struct ResolveGaidResp {
+    /// The recipient address for this asset
+    pub address: elements::Address,
+}
+
+/// Own wallet address, either the index or the address itself
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IndexOrAddress {
+    /// Address index (as returned by `NewAddress`)
+    Index(u32),
+    /// Address (as returned by `NewAddress`)
+    Address(elements::Address),
+}
+
+/// SignMessage request
+///
+/// Signs a message with the key behind one of the wallet addresses, to prove that the address is controlled by the wallet.
+/// Only addresses returned by `NewAddress` or `NewAddressBatch` (listed by `ListAddresses`) can be used.
+/// The signature uses the Bitcoin signed message format ("Bitcoin Signed Message:\n" prefix)
+/// and is encoded as a base64 recoverable signature with the BIP137 header byte for the wallet script type (P2WPKH or P2SH-P2WPKH).
+/// The message must not be longer than 1024 bytes.
+/// Requires `Unlock` first if `auto_lock` is configured. Every signed message is recorded in the audit log.
+#[derive(Serialize, Deserialize)]
+pub struct SignMessageReq {
+    /// The address used for signing
+    pub index_or_address: IndexOrAddress,
+    /// The message to sign
+    pub message: String,
+    /// The wallet name (see the `wallets` setting), `default` if not set
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
+}
+
+/// SignMessage response
+#[derive(Serialize)]
+pub struct SignMessageResp {
+    /// The address index
+    pub index: u32,
+    /// The address the message was signed with
+    pub address: elements::Address,
+    /// Base64 encoded signature
+    pub signature: String,
+}
+
+/// VerifyMessage request
+///
+/// Verifies a signed message locally (for example, an address ownership proof from a counterparty).
+/// Accepts signatures in the `SignMessage` format made with the key of a P2WPKH, P2SH-P2WPKH or P2PKH address
+/// (the compressed P2PKH header is accepted for segwit addresses too).
+/// The message must not be longer than 1024 bytes.
+#[derive(Serialize, Deserialize)]
+pub struct VerifyMessageReq {
+    /// The address that is expected to have signed the message
+    pub address: elements::Address,
+    /// The signed message
+    pub message: String,
+    /// Base64 encoded signature
+    pub signature: String,
+}
+
+/// VerifyMessage response
+#[derive(Serialize)]
+pub struct VerifyMessageResp {
+    /// True if the signature is valid for this message and address.
+    /// Malformed signatures are reported with an error instead.
+    pub valid: bool,
+}
+
+/// Own market order (placed with `SubmitOrder`)
+#[derive(Serialize)]
+pub struct OwnOrder {
+    pub order_id: OrdId,
+    /// Base asset of the market
+    pub base: Ticker,
+    /// Quote asset of the market
+    pub quote: Ticker,
+    /// Trade direction of the base asset
+    pub trade_dir: TradeDir,
+    /// Price (the quote asset amount for one base asset)
+    pub price: f64,
+    /// The base asset amount of the submitted order
+    pub orig_amount: f64,
+    /// The base asset amount that is not swapped yet
+    pub active_amount: f64,
+    pub created_at: TimestampMs,
+    /// The order is removed after this time (if set)
+    pub ttl: Option<DurationMs>,
+    /// Set for private orders, the ID can be shared with the counterparty to take the order
+    pub private_id: Option<String>,
+    pub client_order_id: Option<String>,
+    /// False while the manager is not connected to the SideSwap server (the order can't be matched then)
+    pub online: bool,
+}
+
+/// SubmitOrder request
+///
+/// Places a maker order on the SideSwap market. Requires the `maker_orders` setting.
+/// The default wallet UTXOs are registered with the server, and the matched swaps are signed automatically
+/// once the swap PSET is checked against the order terms (every signed swap is recorded in the audit log).
+/// Both the received asset and the change are paid to a wallet change address.
+/// Requires `Unlock` first if `auto_lock` is configured (swaps matched while locked are not signed).
+#[derive(Serialize, Deserialize)]
+pub struct SubmitOrderReq {
+    /// Base asset of the market
+    pub base: Ticker,
+    /// Quote asset of the market
+    pub quote: Ticker,
+    /// Trade direction of the base asset (`Sell` sends the base asset)
+    pub trade_dir: TradeDir,
+    /// The base asset amount (a number or a decimal string, see `Recipient::amount`)
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub amount: f64,
+    /// Price (the quote asset amount for one base asset)
+    #[serde(deserialize_with = "deserialize_amount")]
+    pub price: f64,
+    /// Remove the order after this time (in seconds, the order is kept until cancelled by default)
+    #[serde(default)]
+    pub ttl_seconds: Option<u64>,
+    /// Private orders are not listed publicly and can be taken only with `OwnOrder::private_id`
+    #[serde(default)]
+    pub private: bool,
+    /// Optional client reference, returned in `OwnOrder::client_order_id`
+    #[serde(default)]
+    pub client_order_id: Option<String>,
+    /// Must be `default` (if set), the market session is funded from the default wallet UTXOs only
+    #[serde(default = "default_wallet")]
+    pub wallet: String,
+}
+
+/// SubmitOrder response
+#[derive(Serialize)]
+pub struct SubmitOrderResp {
+    pub order: OwnOrder,
+}
+
+/// EditOrder request
+///
+/// Changes the amount or the price of an own order (at least one must be set).
+/// Requires `Unlock` first if `auto_lock` is configured.
+#[derive(Serialize, Deserialize)]
+pub struct EditOrderReq {
+    pub order_id: OrdId,
+    /// The new base asset amount
+    #[serde(default)]
+    pub amount: Option<f64>,
+    /// The new price
+    #[serde(default)]
+    pub price: Option<f64>,
+}
+
+/// EditOrder response
+#[derive(Serialize)]
+pub struct EditOrderResp {
+    pub order: OwnOrder,
+}
+
+/// CancelOrder request
+///
+/// Removes an own order from the market
+#[derive(Serialize, Deserialize)]
+pub struct CancelOrderReq {
+    pub order_id: OrdId,
+}
+
+/// CancelOrder response
+#[derive(Serialize)]
+pub struct CancelOrderResp {}
+
+/// ListOwnOrders request
+///
+/// Lists the own orders known to the SideSwap server (restored after reconnects and restarts)
+#[derive(Serialize, Deserialize)]
+pub struct ListOwnOrdersReq {}
+
+/// ListOwnOrders response
+#[derive(Serialize)]
+pub struct ListOwnOrdersResp {
+    pub orders: Vec<OwnOrder>,
+}
+
+/// GetSwaps request
+///
+/// Returns the quotes accepted with `AcceptQuote`, oldest first.
+/// The numbers are stored when the swap is signed, so the fee breakdown is available after the quote is gone.
+#[derive(Serialize, Deserialize)]
+pub struct GetSwapsReq {
+    /// The start of the range (inclusive), from the first swap if not set
+    pub from: Option<TimestampMs>,
+    /// The end of the range (inclusive), up to the last swap if not set
+    pub to: Option<TimestampMs>,
+}
+
+/// An accepted quote
+#[derive(Serialize)]
+pub struct Swap {
+    pub quote_id: QuoteId,
+    /// Transaction ID of the swap
+    pub txid: elements::Txid,
+    /// The sent asset
+    pub send_asset: Ticker,
+    /// The sent amount (including the fee if it's paid with the sent asset)
+    pub send_amount: f64,
+    /// The received asset
+    pub recv_asset: Ticker,
+    /// The received amount (without the fee if it's paid with the received asset)
+    pub recv_amount: f64,
+    /// The quote asset amount paid for one base asset (before fees)
+    pub price: f64,
+    /// The fee asset
+    pub fee_asset: Ticker,
+    /// The total fee (server fee + fixed fee)
+    pub fee_amount: f64,
+    /// Server fee (in the fee asset base units)
+    pub server_fee: u64,
+    /// Fixed fee (in the fee asset base units)
+    pub fixed_fee: u64,
+    /// When the quote was accepted
+    pub timestamp: TimestampMs,
+    /// The current transaction status as reported by the used Electrs server
+    pub status: TxStatus,
+    /// Payment reference (see `FindByReference`)
+    pub reference: Option<String>,
+}
+
+/// GetSwaps response
+#[derive(Serialize)]
+pub struct GetSwapsResp {
+    pub swaps: Vec<Swap>,
+}
+
+// --- Notifications ---
+
+/// Wallet balances notification
+///
+/// Sent automatically when:
+/// - A new client connects (providing the initial balance state).
+/// - The wallet balance for any whitelisted asset changes (due to incoming/outgoing txs, swaps).
+#[derive(Debug, Serialize, PartialEq, Clone)]
+pub struct BalancesNotif {
+    /// The wallet name (see the `wallets` setting), every wallet sends its own notifications
+    pub wallet: String,
+    /// Current wallet balances for all assets (UTXOs on the blockchain and in the mempool)
+    pub balances: Balances,
+    /// Current wallet balances for all assets (only UTXOs on the blockchain)
+    pub confirmed: Balances,
+    /// Current wallet balances for all assets (only UTXOs in the mempool), `balances` minus `confirmed`
+    pub unconfirmed: Balances,
+}
+
+/// Peg status notification
 ///
 /// Provides updates on the status of ongoing peg-in/peg-out orders stored in the local DB.
 /// Sent automatically when:
@@ -509,38 +2202,359 @@ pub struct PegStatusNotif {
     pub peg: PegStatus,
 }
 
+/// Peg deposit detected notification
+///
+/// Sent once per peg transaction when the SideSwap server detects a new user payment towards a peg order.
+/// The semantic peg notifications (`PegDepositDetected`, `PegDepositConfirmed`, `PegPayoutBroadcast`, `PegCompleted` and `PegFailed`)
+/// are derived from the `PegStatus` updates, sent in this order and not repeated after resent statuses or restarts.
+/// Skipped stages are still reported (for example, all of them at once if the first received state is `Done`).
+#[derive(Debug, Serialize, Clone)]
+pub struct PegDepositDetectedNotif {
+    /// Peg order id
+    pub order_id: OrderId,
+    /// Txid of the user's payment (BTC for peg-in, L-BTC for peg-out).
+    pub txid: sideswap_api::Hash32,
+    /// Output index (vout) of the user's payment.
+    pub vout: u32,
+    /// How much the user has paid (in bitcoins for peg-in, liquid bitcoins for peg-out)
+    pub amount: f64,
+}
+
+/// Peg deposit confirmed notification
+///
+/// Sent once per peg transaction when the user's payment has enough confirmations and the server starts processing it.
+#[derive(Debug, Serialize, Clone)]
+pub struct PegDepositConfirmedNotif {
+    /// Peg order id
+    pub order_id: OrderId,
+    /// Txid of the user's payment
+    pub txid: sideswap_api::Hash32,
+    /// Output index (vout) of the user's payment
+    pub vout: u32,
+}
+
+/// Peg payout broadcast notification
+///
+/// Sent once per peg transaction when the payout txid becomes known.
+#[derive(Debug, Serialize, Clone)]
+pub struct PegPayoutBroadcastNotif {
+    /// Peg order id
+    pub order_id: OrderId,
+    /// Txid of the user's payment
+    pub txid: sideswap_api::Hash32,
+    /// Output index (vout) of the user's payment
+    pub vout: u32,
+    /// Payout txid (Liquid Bitcoin for peg-ins and Bitcoin for peg-outs)
+    pub payout_txid: sideswap_api::Hash32,
+    /// How much has been paid (in L-BTC for peg-ins, BTC for peg-outs)
+    pub payout_amount: Option<f64>,
+}
+
+/// Peg completed notification
+///
+/// Sent once per peg transaction when the server reports the payout as done.
+#[derive(Debug, Serialize, Clone)]
+pub struct PegCompletedNotif {
+    /// Peg order id
+    pub order_id: OrderId,
+    /// Txid of the user's payment
+    pub txid: sideswap_api::Hash32,
+    /// Output index (vout) of the user's payment
+    pub vout: u32,
+    /// How much has been paid (in L-BTC for peg-ins, BTC for peg-outs)
+    pub payout_amount: Option<f64>,
+}
+
+/// Peg failed notification
+///
+/// Sent once per peg transaction when the server refuses to process the payment (the amount is less than the minimum).
+#[derive(Debug, Serialize, Clone)]
+pub struct PegFailedNotif {
+    /// Peg order id
+    pub order_id: OrderId,
+    /// Txid of the user's payment
+    pub txid: sideswap_api::Hash32,
+    /// Output index (vout) of the user's payment
+    pub vout: u32,
+    /// Human-readable reason
+    pub reason: String,
+}
+
+/// Peg complete notification
+///
+/// Sent once per peg when all its transactions are done (after the `PegCompleted` notifications).
+#[derive(Debug, Serialize, Clone)]
+pub struct PegCompleteNotif {
+    /// Peg order id
+    pub order_id: OrderId,
+    /// The total paid out for all peg transactions (in L-BTC for peg-ins, BTC for peg-outs)
+    pub amount_received: f64,
+}
+
+/// Markets notification
+///
+/// Sent automatically when a new client connects (snapshot of the markets currently known to the manager).
+/// Markets with assets that are not whitelisted are not included.
+#[derive(Debug, Serialize, Clone)]
+pub struct MarketsNotif {
+    /// The list of available markets
+    pub markets: Vec<Market>,
+    /// True if the connection to the SideSwap server is down and the list was received too long ago to be trusted
+    pub stale: bool,
+}
+
+/// Market added notification
+///
+/// Sent when the SideSwap server adds a market (with whitelisted assets) after the `Markets` snapshot.
+#[derive(Debug, Serialize, Clone)]
+pub struct MarketAddedNotif {
+    /// The new market
+    pub market: Market,
+}
+
+/// Market removed notification
+///
+/// Sent when the SideSwap server removes a market (with whitelisted assets).
+#[derive(Debug, Serialize, Clone)]
+pub struct MarketRemovedNotif {
+    /// Base asset of the removed market
+    pub base: Ticker,
+    /// Quote asset of the removed market
+    pub quote: Ticker,
+}
+
+/// Market price notification
+///
+/// Sent to the clients subscribed with `SubscribePrice` when:
+/// - The client subscribes (the last known price, if available).
+/// - The SideSwap server pushes a price update for the market.
+#[derive(Debug, Serialize, Clone)]
+pub struct MarketPriceNotif {
+    /// Base asset of the market
+    pub base: Ticker,
+    /// Quote asset of the market
+    pub quote: Ticker,
+    /// Index price (the price of the base asset in the quote asset), if known
+    pub ind_price: Option<f64>,
+    /// Last trade price, if known
+    pub last_price: Option<f64>,
+    /// True if the connection to the SideSwap server is down and the price was received too long ago to be trusted
+    pub stale: bool,
+}
+
+/// Lock status notification
+///
+/// Sent automatically when:
+/// - A new client connects (only if `auto_lock` is configured).
+/// - Signing is locked due to inactivity or unlocked with `Unlock`.
+#[derive(Debug, Serialize, Clone)]
+pub struct LockStatusNotif {
+    /// True if signing is locked and `Unlock` is required
+    pub locked: bool,
+}
+
+/// Draining notification
+///
+/// Sent automatically when:
+/// - Draining is started (with `Drain` or SIGUSR2).
+/// - A new client connects (only while draining).
+#[derive(Debug, Serialize, Clone)]
+pub struct DrainingNotif {
+    /// The process exits at this time at the latest (milliseconds since UNIX epoch)
+    pub shutdown_at: TimestampMs,
+}
+
+/// Upstream connection notification
+///
+/// Sent automatically when:
+/// - The connection to the SideSwap server is lost or restored.
+/// - A new client connects (only while disconnected).
+///
+/// Quotes, swaps and server broadcasts are not available while disconnected.
+/// The quotes returned before the connection was lost can't be accepted after it is restored.
+#[derive(Debug, Serialize, Clone)]
+pub struct ServerConnectionNotif {
+    pub connected: bool,
+}
+
+/// Config reloaded notification
+///
+/// Sent after the config file was reloaded (with SIGHUP or `ReloadConfig`).
+#[derive(Debug, Serialize, Clone)]
+pub struct ConfigReloadedNotif {
+    /// The names of the changed settings
+    pub changed_fields: Vec<String>,
+}
+
+/// Sent when the notes of a monitored transaction are changed by `SetTxNote` or `ImportNotes`
+#[derive(Debug, Serialize, Clone)]
+pub struct TxNoteNotif {
+    pub txid: elements::Txid,
+    /// See `MonitoredTx::description`
+    pub description: String,
+    pub user_note: Option<String>,
+}
+
+/// Sent when the status of a monitored transaction changes (checked after every wallet sync).
+/// The last known statuses of all monitored transactions are sent to newly connected clients.
+#[derive(Debug, Serialize, Clone)]
+pub struct TxStatusNotif {
+    pub txid: elements::Txid,
+    pub status: TxStatus,
+    /// Payment reference (see `FindByReference`)
+    pub reference: Option<String>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+pub enum ApprovalState {
+    /// Approved and executed
+    Approved,
+    /// Approved, but the execution failed (see `error`)
+    Failed,
+    /// Rejected with `Reject`
+    Rejected,
+    /// Not approved in time
+    Expired,
+}
+
+/// Sent to the requester when a queued operation is approved, rejected or expires
+#[derive(Debug, Serialize, Clone)]
+pub struct ApprovalResolvedNotif {
+    pub approval_id: String,
+    pub state: ApprovalState,
+    /// The sent transaction (if approved)
+    pub txid: Option<elements::Txid>,
+    /// The reject reason (if rejected) or the execution error (if failed)
+    pub error: Option<String>,
+}
+
+/// Gap limit warning notification
+///
+/// Sent automatically when:
+/// - A new address is generated and fewer than `gap_limit_warning` new addresses can be generated after it (`active` is true).
+/// - Previously generated addresses receive funds and the headroom goes back above the threshold (`active` is false).
+/// - A new client connects (only while the warning is active).
+#[derive(Debug, Serialize, Clone)]
+pub struct GapLimitWarningNotif {
+    /// The wallet name (see the `wallets` setting)
+    pub wallet: String,
+    /// How many more new addresses can be generated before the gap limit is reached
+    pub remaining: u32,
+    /// The first address index without blockchain activity (the gap is counted from it)
+    pub first_unused_index: u32,
+    /// True while the headroom is below the threshold, false when the warning is cleared
+    pub active: bool,
+}
+
 // --- Top level WS messages ---
 
 /// Request messages (Client -> Manager)
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub enum Req {
     NewPeg(NewPegReq),
+    PegFeeEstimate(PegFeeEstimateReq),
     DelPeg(DelPegReq),
     NewAddress(NewAddressReq),
+    NewAddressBatch(NewAddressBatchReq),
     ListAddresses(ListAddressesReq),
     CreateTx(CreateTxReq),
     SendTx(SendTxReq),
-    GetQuote(GetQuoteReq),
+    // Boxed, it's the largest request
+    GetQuote(Box<GetQuoteReq>),
     AcceptQuote(AcceptQuoteReq),
+    CancelQuote(CancelQuoteReq),
     GetMonitoredTxs(GetMonitoredTxsReq),
     DelMonitoredTx(DelMonitoredTxReq),
+    SetTxNote(SetTxNoteReq),
+    ExportNotes(ExportNotesReq),
+    ImportNotes(ImportNotesReq),
     GetWalletTxs(GetWalletTxsReq),
+    Unlock(UnlockReq),
+    GetServerInfo(GetServerInfoReq),
+    GetDiagnostics(GetDiagnosticsReq),
+    ListAssets(ListAssetsReq),
+    GetAssetInfo(GetAssetInfoReq),
+    ListMarkets(ListMarketsReq),
+    ListUtxos(ListUtxosReq),
+    ResolveGaid(ResolveGaidReq),
+    Drain(DrainReq),
+    GetPegTimeline(GetPegTimelineReq),
+    GetPegDetails(GetPegDetailsReq),
+    AddAllowedAddress(AddAllowedAddressReq),
+    RemoveAllowedAddress(RemoveAllowedAddressReq),
+    ListAllowedAddresses(ListAllowedAddressesReq),
+    GetBalanceHistory(GetBalanceHistoryReq),
+    SignMessage(SignMessageReq),
+    VerifyMessage(VerifyMessageReq),
+    ExplainQuote(ExplainQuoteReq),
+    GetQuotas(GetQuotasReq),
+    ReloadConfig(ReloadConfigReq),
+    RefreshTickers(RefreshTickersReq),
+    FindByReference(FindByReferenceReq),
+    ListPendingApprovals(ListPendingApprovalsReq),
+    Approve(ApproveReq),
+    Reject(RejectReq),
+    SubscribePrice(SubscribePriceReq),
+    UnsubscribePrice(UnsubscribePriceReq),
+    SubmitOrder(SubmitOrderReq),
+    EditOrder(EditOrderReq),
+    CancelOrder(CancelOrderReq),
+    ListOwnOrders(ListOwnOrdersReq),
+    GetSwaps(GetSwapsReq),
 }
 
 /// Response messages (Manager -> Client)
 #[derive(Serialize)]
 pub enum Resp {
     NewPeg(NewPegResp),
+    PegFeeEstimate(PegFeeEstimateResp),
     DelPeg(DelPegResp),
     NewAddress(NewAddressResp),
+    NewAddressBatch(NewAddressBatchResp),
     ListAddresses(ListAddressesResp),
     CreateTx(CreateTxResp),
     SendTx(SendTxResp),
     GetQuote(GetQuoteResp),
     AcceptQuote(AcceptQuoteResp),
+    CancelQuote(CancelQuoteResp),
     GetMonitoredTxs(GetMonitoredTxsResp),
     DelMonitoredTx(DelMonitoredTxResp),
+    SetTxNote(SetTxNoteResp),
+    ExportNotes(ExportNotesResp),
+    ImportNotes(ImportNotesResp),
     GetWalletTxs(GetWalletTxsResp),
+    Unlock(UnlockResp),
+    GetServerInfo(GetServerInfoResp),
+    GetDiagnostics(GetDiagnosticsResp),
+    ListAssets(ListAssetsResp),
+    GetAssetInfo(GetAssetInfoResp),
+    ListMarkets(ListMarketsResp),
+    ListUtxos(ListUtxosResp),
+    ResolveGaid(ResolveGaidResp),
+    Drain(DrainResp),
+    GetPegTimeline(GetPegTimelineResp),
+    GetPegDetails(GetPegDetailsResp),
+    AddAllowedAddress(AddAllowedAddressResp),
+    RemoveAllowedAddress(RemoveAllowedAddressResp),
+    ListAllowedAddresses(ListAllowedAddressesResp),
+    GetBalanceHistory(GetBalanceHistoryResp),
+    SignMessage(SignMessageResp),
+    VerifyMessage(VerifyMessageResp),
+    ExplainQuote(ExplainQuoteResp),
+    GetQuotas(GetQuotasResp),
+    ReloadConfig(ReloadConfigResp),
+    RefreshTickers(RefreshTickersResp),
+    FindByReference(FindByReferenceResp),
+    ListPendingApprovals(ListPendingApprovalsResp),
+    Approve(ApproveResp),
+    Reject(RejectResp),
+    SubscribePrice(SubscribePriceResp),
+    UnsubscribePrice(UnsubscribePriceResp),
+    SubmitOrder(SubmitOrderResp),
+    EditOrder(EditOrderResp),
+    CancelOrder(CancelOrderResp),
+    ListOwnOrders(ListOwnOrdersResp),
+    GetSwaps(GetSwapsResp),
 }
 
 /// Notification messages (Manager -> Client)
@@ -548,10 +2562,30 @@ pub enum Resp {
 pub enum Notif {
     Balances(BalancesNotif),
     PegStatus(PegStatusNotif),
+    Markets(MarketsNotif),
+    MarketPrice(MarketPriceNotif),
+    LockStatus(LockStatusNotif),
+    GapLimitWarning(GapLimitWarningNotif),
+    Draining(DrainingNotif),
+    PegDepositDetected(PegDepositDetectedNotif),
+    PegDepositConfirmed(PegDepositConfirmedNotif),
+    PegPayoutBroadcast(PegPayoutBroadcastNotif),
+    PegCompleted(PegCompletedNotif),
+    PegFailed(PegFailedNotif),
+    ConfigReloaded(ConfigReloadedNotif),
+    TxStatus(TxStatusNotif),
+    ApprovalResolved(ApprovalResolvedNotif),
+    MarketAdded(MarketAddedNotif),
+    MarketRemoved(MarketRemovedNotif),
+    PegComplete(PegCompleteNotif),
+    ServerConnection(ServerConnectionNotif),
+    TxNote(TxNoteNotif),
 }
 
 /// Top-level message envelope sent BY clients TO the manager via WebSocket.
-#[derive(Deserialize)]
+/// A text frame can also contain a JSON array of up to 100 messages, every request gets its own response.
+/// The responses are sent in the completion order.
+#[derive(Serialize, Deserialize)]
 pub enum To {
     /// A request that expects a response or error.
     Req {
@@ -560,6 +2594,12 @@ pub enum To {
         /// The actual request payload.
         req: Req,
     },
+    /// Must be the first message if `auth_token` or `client_tokens` is configured in `ws_server`, answered with `From::LoggedIn`.
+    /// No notifications are sent before that.
+    Login {
+        /// The configured `auth_token` or one of `client_tokens`
+        token: String,
+    },
 }
 
 /// Top-level message envelope sent TO clients FROM the manager via WebSocket.
@@ -584,4 +2624,9 @@ pub enum From {
         /// The actual notification payload.
         notif: Notif,
     },
+    /// The `To::Login` token is accepted (a wrong token is reported with `From::Error`, `id` is 0)
+    LoggedIn {},
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1208 @@
+//! Golden files for the WS protocol messages (`golden/` in the crate root).
+//!
+//! Every request, response, error code and notification has one fixture with fixed values.
+//! After an intended protocol change, regenerate the fixtures and review the diff:
+//!
+//! ```text
+//! UPDATE_GOLDEN=1 cargo test -p sideswap_manager golden
+//! git diff sideswap_manager/golden
+//! ```
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use elements::hashes::Hash;
+use sideswap_common::{b64, dealer_ticker::DealerTicker, network::Network, ws::ws_req_sender};
+use sideswap_types::asset_precision::AssetPrecision;
+
+use super::*;
+
+const REQ_ID: ReqId = 1;
+
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("golden")
+}
+
+fn txid(byte: u8) -> elements::Txid {
+    elements::Txid::from_byte_array([byte; 32])
+}
+
+fn hash(byte: u8) -> sideswap_api::Hash32 {
+    sideswap_api::HashN([byte; 32])
+}
+
+fn address() -> elements::Address {
+    "vjU3KGnCKrsZkVPMTzTBo31fPrcXpqNsyoSAEvLP2apepS1JZqvN69oj4deXt3AiBuY1ZjzRCdLkb1aQ"
+        .parse()
+        .unwrap()
+}
+
+fn timestamp() -> TimestampMs {
+    TimestampMs::from_millis(1743746770000)
+}
+
+fn quote_id() -> QuoteId {
+    QuoteId::new(1743746771234)
+}
+
+fn balances() -> Balances {
+    BTreeMap::from([(DealerTicker::LBTC, 0.00087251), (DealerTicker::USDT, 10.5)])
+}
+
+fn peg_status() -> PegStatus {
+    PegStatus {
+        order_id: hash(2),
+        peg_in: true,
+        addr_server: "tb1qzfvwfexuzn4w7xr0hv7hkm4ny65a5cxwkkr5ta".to_owned(),
+        addr_recv: address().to_string(),
+        list: vec![PegTxStatus {
+            tx_hash: hash(3),
+            vout: 1,
+            peg_amount: 0.001,
+            payout_amount: Some(0.000999),
+            tx_state: PegTxState::Detected,
+            detected_confs: Some(1),
+            total_confs: Some(2),
+            created_at: timestamp(),
+            payout_txid: None,
+        }],
+        created_at: timestamp(),
+        return_address: None,
+        reference: Some("0000-016".to_owned()),
+    }
+}
+
+fn quote_numbers() -> QuoteNumbers {
+    QuoteNumbers {
+        base: DealerTicker::LBTC,
+        quote: DealerTicker::USDT,
+        base_amount: 10000,
+        quote_amount: 1000000000,
+        server_fee: 2000000,
+        fixed_fee: 5000000,
+        trade_dir: TradeDir::Sell,
+        fee_asset: AssetType::Quote,
+    }
+}
+
+fn send_tx_resp() -> SendTxResp {
+    SendTxResp {
+        res_wallet: BroadcastStatus::Success {},
+        res_server: Some(BroadcastStatus::Error {
+            error_msg: "bad-txns-inputs-missingorspent".to_owned(),
+        }),
+    }
+}
+
+fn req_name(req: &Req) -> &'static str {
+    match req {
+        Req::NewPeg(_) => "NewPeg",
+        Req::PegFeeEstimate(_) => "PegFeeEstimate",
+        Req::DelPeg(_) => "DelPeg",
+        Req::NewAddress(_) => "NewAddress",
+        Req::NewAddressBatch(_) => "NewAddressBatch",
+        Req::ListAddresses(_) => "ListAddresses",
+        Req::CreateTx(_) => "CreateTx",
+        Req::SendTx(_) => "SendTx",
+        Req::GetQuote(_) => "GetQuote",
+        Req::AcceptQuote(_) => "AcceptQuote",
+        Req::CancelQuote(_) => "CancelQuote",
+        Req::GetMonitoredTxs(_) => "GetMonitoredTxs",
+        Req::DelMonitoredTx(_) => "DelMonitoredTx",
+        Req::SetTxNote(_) => "SetTxNote",
+        Req::ExportNotes(_) => "ExportNotes",
+        Req::ImportNotes(_) => "ImportNotes",
+        Req::GetWalletTxs(_) => "GetWalletTxs",
+        Req::Unlock(_) => "Unlock",
+        Req::GetServerInfo(_) => "GetServerInfo",
+        Req::GetDiagnostics(_) => "GetDiagnostics",
+        Req::ListAssets(_) => "ListAssets",
+        Req::GetAssetInfo(_) => "GetAssetInfo",
+        Req::ListMarkets(_) => "ListMarkets",
+        Req::ListUtxos(_) => "ListUtxos",
+        Req::ResolveGaid(_) => "ResolveGaid",
+        Req::Drain(_) => "Drain",
+        Req::GetPegTimeline(_) => "GetPegTimeline",
+        Req::GetPegDetails(_) => "GetPegDetails",
+        Req::AddAllowedAddress(_) => "AddAllowedAddress",
+        Req::RemoveAllowedAddress(_) => "RemoveAllowedAddress",
+        Req::ListAllowedAddresses(_) => "ListAllowedAddresses",
+        Req::GetBalanceHistory(_) => "GetBalanceHistory",
+        Req::SignMessage(_) => "SignMessage",
+        Req::VerifyMessage(_) => "VerifyMessage",
+        Req::ExplainQuote(_) => "ExplainQuote",
+        Req::GetQuotas(_) => "GetQuotas",
+        Req::ReloadConfig(_) => "ReloadConfig",
+        Req::RefreshTickers(_) => "RefreshTickers",
+        Req::FindByReference(_) => "FindByReference",
+        Req::ListPendingApprovals(_) => "ListPendingApprovals",
+        Req::Approve(_) => "Approve",
+        Req::Reject(_) => "Reject",
+        Req::SubscribePrice(_) => "SubscribePrice",
+        Req::UnsubscribePrice(_) => "UnsubscribePrice",
+        Req::SubmitOrder(_) => "SubmitOrder",
+        Req::EditOrder(_) => "EditOrder",
+        Req::CancelOrder(_) => "CancelOrder",
+        Req::ListOwnOrders(_) => "ListOwnOrders",
+        Req::GetSwaps(_) => "GetSwaps",
+    }
+}
+
+fn resp_name(resp: &Resp) -> &'static str {
+    match resp {
+        Resp::NewPeg(_) => "NewPeg",
+        Resp::PegFeeEstimate(_) => "PegFeeEstimate",
+        Resp::DelPeg(_) => "DelPeg",
+        Resp::NewAddress(_) => "NewAddress",
+        Resp::NewAddressBatch(_) => "NewAddressBatch",
+        Resp::ListAddresses(_) => "ListAddresses",
+        Resp::CreateTx(_) => "CreateTx",
+        Resp::SendTx(_) => "SendTx",
+        Resp::GetQuote(_) => "GetQuote",
+        Resp::AcceptQuote(_) => "AcceptQuote",
+        Resp::CancelQuote(_) => "CancelQuote",
+        Resp::GetMonitoredTxs(_) => "GetMonitoredTxs",
+        Resp::DelMonitoredTx(_) => "DelMonitoredTx",
+        Resp::SetTxNote(_) => "SetTxNote",
+        Resp::ExportNotes(_) => "ExportNotes",
+        Resp::ImportNotes(_) => "ImportNotes",
+        Resp::GetWalletTxs(_) => "GetWalletTxs",
+        Resp::Unlock(_) => "Unlock",
+        Resp::GetServerInfo(_) => "GetServerInfo",
+        Resp::GetDiagnostics(_) => "GetDiagnostics",
+        Resp::ListAssets(_) => "ListAssets",
+        Resp::GetAssetInfo(_) => "GetAssetInfo",
+        Resp::ListMarkets(_) => "ListMarkets",
+        Resp::ListUtxos(_) => "ListUtxos",
+        Resp::ResolveGaid(_) => "ResolveGaid",
+        Resp::Drain(_) => "Drain",
+        Resp::GetPegTimeline(_) => "GetPegTimeline",
+        Resp::GetPegDetails(_) => "GetPegDetails",
+        Resp::AddAllowedAddress(_) => "AddAllowedAddress",
+        Resp::RemoveAllowedAddress(_) => "RemoveAllowedAddress",
+        Resp::ListAllowedAddresses(_) => "ListAllowedAddresses",
+        Resp::GetBalanceHistory(_) => "GetBalanceHistory",
+        Resp::SignMessage(_) => "SignMessage",
+        Resp::VerifyMessage(_) => "VerifyMessage",
+        Resp::ExplainQuote(_) => "ExplainQuote",
+        Resp::GetQuotas(_) => "GetQuotas",
+        Resp::ReloadConfig(_) => "ReloadConfig",
+        Resp::RefreshTickers(_) => "RefreshTickers",
+        Resp::FindByReference(_) => "FindByReference",
+        Resp::ListPendingApprovals(_) => "ListPendingApprovals",
+        Resp::Approve(_) => "Approve",
+        Resp::Reject(_) => "Reject",
+        Resp::SubscribePrice(_) => "SubscribePrice",
+        Resp::UnsubscribePrice(_) => "UnsubscribePrice",
+        Resp::SubmitOrder(_) => "SubmitOrder",
+        Resp::EditOrder(_) => "EditOrder",
+        Resp::CancelOrder(_) => "CancelOrder",
+        Resp::ListOwnOrders(_) => "ListOwnOrders",
+        Resp::GetSwaps(_) => "GetSwaps",
+    }
+}
+
+fn notif_name(notif: &Notif) -> &'static str {
+    match notif {
+        Notif::Balances(_) => "Balances",
+        Notif::PegStatus(_) => "PegStatus",
+        Notif::Markets(_) => "Markets",
+        Notif::MarketPrice(_) => "MarketPrice",
+        Notif::LockStatus(_) => "LockStatus",
+        Notif::GapLimitWarning(_) => "GapLimitWarning",
+        Notif::Draining(_) => "Draining",
+        Notif::PegDepositDetected(_) => "PegDepositDetected",
+        Notif::PegDepositConfirmed(_) => "PegDepositConfirmed",
+        Notif::PegPayoutBroadcast(_) => "PegPayoutBroadcast",
+        Notif::PegCompleted(_) => "PegCompleted",
+        Notif::PegFailed(_) => "PegFailed",
+        Notif::ConfigReloaded(_) => "ConfigReloaded",
+        Notif::TxStatus(_) => "TxStatus",
+        Notif::ApprovalResolved(_) => "ApprovalResolved",
+        Notif::MarketAdded(_) => "MarketAdded",
+        Notif::MarketRemoved(_) => "MarketRemoved",
+        Notif::PegComplete(_) => "PegComplete",
+        Notif::ServerConnection(_) => "ServerConnection",
+        Notif::TxNote(_) => "TxNote",
+    }
+}
+
+fn error_code_name(code: &ErrorCode) -> &'static str {
+    match code {
+        ErrorCode::InvalidRequest => "InvalidRequest",
+        ErrorCode::ServerError => "ServerError",
+        ErrorCode::NetworkError => "NetworkError",
+        ErrorCode::ServerTimeout => "ServerTimeout",
+        ErrorCode::RequestTooLarge => "RequestTooLarge",
+        ErrorCode::InvalidTicker => "InvalidTicker",
+        ErrorCode::UnknownTicker => "UnknownTicker",
+        ErrorCode::AmbiguousTicker => "AmbiguousTicker",
+        ErrorCode::WalletError => "WalletError",
+        ErrorCode::WalletTimeout => "WalletTimeout",
+        ErrorCode::InvalidAssetAmount => "InvalidAssetAmount",
+        ErrorCode::NoMarket => "NoMarket",
+        ErrorCode::NotEnoughAmount => "NotEnoughAmount",
+        ErrorCode::QuoteFailed => "QuoteFailed",
+        ErrorCode::InvalidBase64 => "InvalidBase64",
+        ErrorCode::InvalidEncoding => "InvalidEncoding",
+        ErrorCode::InvalidPset => "InvalidPset",
+        ErrorCode::NoUtxos => "NoUtxos",
+        ErrorCode::QuoteExpired => "QuoteExpired",
+        ErrorCode::UnknownQuote => "UnknownQuote",
+        ErrorCode::QuoteInvalidatedByReconnect => "QuoteInvalidatedByReconnect",
+        ErrorCode::UnknownCreatedTx => "UnknownCreatedTx",
+        ErrorCode::GapLimit => "GapLimit",
+        ErrorCode::WrongPassword => "WrongPassword",
+        ErrorCode::UnlockBackoff => "UnlockBackoff",
+        ErrorCode::AmpAddressRequired => "AmpAddressRequired",
+        ErrorCode::GaidResolveFailed => "GaidResolveFailed",
+        ErrorCode::InvalidQuoteReceiver => "InvalidQuoteReceiver",
+        ErrorCode::InvalidPegAddress => "InvalidPegAddress",
+        ErrorCode::PegAmountTooLow => "PegAmountTooLow",
+        ErrorCode::UnknownFeeTarget => "UnknownFeeTarget",
+        ErrorCode::UnknownPeg => "UnknownPeg",
+        ErrorCode::UnknownWallet => "UnknownWallet",
+        ErrorCode::AddressNotAllowed => "AddressNotAllowed",
+        ErrorCode::MessageTooLong => "MessageTooLong",
+        ErrorCode::NotOwnAddress => "NotOwnAddress",
+        ErrorCode::InvalidAddress => "InvalidAddress",
+        ErrorCode::DuplicateRecipient => "DuplicateRecipient",
+        ErrorCode::SendAllNotAlone => "SendAllNotAlone",
+        ErrorCode::NothingToSend => "NothingToSend",
+        ErrorCode::UtxoSelectionNotSupported => "UtxoSelectionNotSupported",
+        ErrorCode::UnconfirmedInputs => "UnconfirmedInputs",
+        ErrorCode::InvalidFeeRate => "InvalidFeeRate",
+        ErrorCode::InvalidQuoteTimeout => "InvalidQuoteTimeout",
+        ErrorCode::InvalidConfig => "InvalidConfig",
+        ErrorCode::RestartRequired => "RestartRequired",
+        ErrorCode::UnknownMonitoredTx => "UnknownMonitoredTx",
+        ErrorCode::MonitoredTxUnconfirmed => "MonitoredTxUnconfirmed",
+        ErrorCode::InvalidReference => "InvalidReference",
+        ErrorCode::UnknownReference => "UnknownReference",
+        ErrorCode::UnknownApproval => "UnknownApproval",
+        ErrorCode::ApproverNotAllowed => "ApproverNotAllowed",
+        ErrorCode::PsetMismatch => "PsetMismatch",
+        ErrorCode::MakerOrdersDisabled => "MakerOrdersDisabled",
+        ErrorCode::UnknownOrder => "UnknownOrder",
+        ErrorCode::IdempotencyKeyReused => "IdempotencyKeyReused",
+        ErrorCode::IdempotencyKeyInProgress => "IdempotencyKeyInProgress",
+        ErrorCode::UtxoCheckFailed => "UtxoCheckFailed",
+        ErrorCode::Locked => "Locked",
+        ErrorCode::Draining => "Draining",
+        ErrorCode::QuotaExceeded => "QuotaExceeded",
+        ErrorCode::ApprovalRequired => "ApprovalRequired",
+        ErrorCode::OrderAboveApprovalThreshold => "OrderAboveApprovalThreshold",
+        ErrorCode::QuoteLowBalance => "QuoteLowBalance",
+        ErrorCode::RouteLowBalance => "RouteLowBalance",
+        ErrorCode::Unauthorized => "Unauthorized",
+        ErrorCode::TooManyRequests => "TooManyRequests",
+    }
+}
+
+/// One request of every variant (add new variants to `req_name` too)
+fn sample_reqs() -> Vec<Req> {
+    vec![
+        Req::NewPeg(NewPegReq {
+            addr_recv: address().to_string(),
+            peg_in: true,
+            fee_rate: None,
+            allow_unconfidential: false,
+        }),
+        Req::PegFeeEstimate(PegFeeEstimateReq {
+            peg_in: false,
+            amount: 0.01,
+            blocks: Some(2),
+        }),
+        Req::DelPeg(DelPegReq { order_id: hash(2) }),
+        Req::NewAddress(NewAddressReq {
+            user_note: Some("invoice 42".to_owned()),
+            wallet: "default".to_owned(),
+        }),
+        Req::NewAddressBatch(NewAddressBatchReq {
+            count: 3,
+            note_prefix: Some("invoice-".to_owned()),
+            wallet: "default".to_owned(),
+        }),
+        Req::ListAddresses(ListAddressesReq {
+            include_change: true,
+            wallet: "default".to_owned(),
+        }),
+        Req::CreateTx(CreateTxReq {
+            recipients: vec![
+                Recipient {
+                    address: Some(address().to_string()),
+                    asset: Some(DealerTicker::USDT),
+                    uri: None,
+                    amount: 10.0,
+                    send_all: false,
+                },
+                Recipient {
+                    address: None,
+                    asset: None,
+                    uri: Some(format!("liquidnetwork:{}?amount=0.001", address())),
+                    amount: 0.0,
+                    send_all: false,
+                },
+            ],
+            aggregate_duplicates: false,
+            fee_rate: Some(FeeRateSats::from_raw(0.1)),
+            allow_unconfidential: false,
+            utxos: Some(vec![elements::OutPoint::new(txid(3), 1)]),
+            allow_unconfirmed: true,
+            wallet: "default".to_owned(),
+        }),
+        Req::SendTx(SendTxReq {
+            txid: txid(1),
+            user_note: Some("payout".to_owned()),
+            wallet_only: false,
+            idempotency_key: Some("payout-42".to_owned()),
+            wallet: "default".to_owned(),
+        }),
+        Req::GetQuote(Box::new(GetQuoteReq {
+            send_asset: DealerTicker::USDT,
+            recv_asset: DealerTicker::LBTC,
+            send_amount: Some(10.0),
+            recv_amount: None,
+            receive_address: Some(address()),
+            gaid: None,
+            instant_swap: false,
+            allow_partial: true,
+            verify_pset: true,
+            utxos: None,
+            allow_unconfirmed: true,
+            timeout_secs: Some(30),
+            allow_routing: false,
+            wallet: "default".to_owned(),
+        })),
+        Req::AcceptQuote(AcceptQuoteReq {
+            quote_id: quote_id(),
+            user_note: None,
+            idempotency_key: None,
+        }),
+        Req::CancelQuote(CancelQuoteReq {
+            quote_id: quote_id(),
+        }),
+        Req::GetMonitoredTxs(GetMonitoredTxsReq {}),
+        Req::DelMonitoredTx(DelMonitoredTxReq {
+            txid: txid(1),
+            force: false,
+        }),
+        Req::SetTxNote(SetTxNoteReq {
+            txid: txid(1),
+            user_note: Some("invoice 42".to_owned()),
+        }),
+        Req::ExportNotes(ExportNotesReq {}),
+        Req::ImportNotes(ImportNotesReq {
+            notes: BTreeMap::from([(
+                txid(1).to_string(),
+                TxNotes {
+                    description: Some(format!("send 10 USDt to {}", address())),
+                    user_note: Some("invoice 42".to_owned()),
+                },
+            )]),
+            overwrite: false,
+        }),
+        Req::GetWalletTxs(GetWalletTxsReq {
+            after_height: Some(3320000),
+            wallet: "default".to_owned(),
+        }),
+        Req::Unlock(UnlockReq {
+            password: "secret".to_owned(),
+        }),
+        Req::GetServerInfo(GetServerInfoReq {}),
+        Req::GetDiagnostics(GetDiagnosticsReq {}),
+        Req::ListAssets(ListAssetsReq {}),
+        Req::GetAssetInfo(GetAssetInfoReq {
+            asset_id: Network::LiquidTestnet.d().policy_asset,
+        }),
+        Req::ListMarkets(ListMarketsReq {}),
+        Req::ListUtxos(ListUtxosReq {
+            wallet: "default".to_owned(),
+        }),
+        Req::ResolveGaid(ResolveGaidReq {
+            asset: DealerTicker::USDT,
+            gaid: "GA2nfrGmvNfxrJhtBM2W3u1GytGx5U".to_owned(),
+        }),
+        Req::Drain(DrainReq { grace_seconds: 300 }),
+        Req::GetPegTimeline(GetPegTimelineReq { order_id: hash(2) }),
+        Req::GetPegDetails(GetPegDetailsReq { order_id: hash(2) }),
+        Req::AddAllowedAddress(AddAllowedAddressReq {
+            address: address(),
+            label: Some("exchange".to_owned()),
+            added_by: Some("alice".to_owned()),
+        }),
+        Req::RemoveAllowedAddress(RemoveAllowedAddressReq { address: address() }),
+        Req::ListAllowedAddresses(ListAllowedAddressesReq {}),
+        Req::GetBalanceHistory(GetBalanceHistoryReq {
+            asset: Some(DealerTicker::LBTC),
+            from: Some(timestamp()),
+            to: None,
+            granularity_seconds: Some(3600),
+            wallet: "default".to_owned(),
+        }),
+        Req::SignMessage(SignMessageReq {
+            index_or_address: IndexOrAddress::Index(0),
+            message: "I control this address".to_owned(),
+            wallet: "default".to_owned(),
+        }),
+        Req::VerifyMessage(VerifyMessageReq {
+            address: address(),
+            message: "I control this address".to_owned(),
+            signature: "KBmwcCSgmEjiROxKUxxOQRlrBP/SB3VqC1Cc7hEDKHuNUow+ijZFVm2FUPmGhaH7yIiqF3bEZQlS9xY4UVxtGks=".to_owned(),
+        }),
+        Req::ExplainQuote(ExplainQuoteReq {
+            quote_id: None,
+            numbers: Some(quote_numbers()),
+        }),
+        Req::GetQuotas(GetQuotasReq {}),
+        Req::ReloadConfig(ReloadConfigReq {}),
+        Req::RefreshTickers(RefreshTickersReq {}),
+        Req::FindByReference(FindByReferenceReq {
+            reference: "0000-016".to_owned(),
+        }),
+        Req::ListPendingApprovals(ListPendingApprovalsReq {}),
+        Req::Approve(ApproveReq {
+            approval_id: "4f1c2b7e9a0d3e6f".to_owned(),
+        }),
+        Req::Reject(RejectReq {
+            approval_id: "4f1c2b7e9a0d3e6f".to_owned(),
+            reason: Some("unexpected amount".to_owned()),
+        }),
+        Req::SubscribePrice(SubscribePriceReq {
+            base: DealerTicker::LBTC,
+            quote: DealerTicker::USDT,
+        }),
+        Req::UnsubscribePrice(UnsubscribePriceReq {
+            base: DealerTicker::LBTC,
+            quote: DealerTicker::USDT,
+        }),
+        Req::SubmitOrder(SubmitOrderReq {
+            base: DealerTicker::LBTC,
+            quote: DealerTicker::USDT,
+            trade_dir: TradeDir::Sell,
+            amount: 0.01,
+            price: 98000.0,
+            ttl_seconds: Some(3600),
+            private: false,
+            client_order_id: Some("order-42".to_owned()),
+            wallet: "default".to_owned(),
+        }),
+        Req::EditOrder(EditOrderReq {
+            order_id: OrdId::new(1742000000000),
+            amount: None,
+            price: Some(97500.0),
+        }),
+        Req::CancelOrder(CancelOrderReq {
+            order_id: OrdId::new(1742000000000),
+        }),
+        Req::ListOwnOrders(ListOwnOrdersReq {}),
+        Req::GetSwaps(GetSwapsReq {
+            from: Some(TimestampMs::from_millis(1743700000000)),
+            to: None,
+        }),
+    ]
+}
+
+fn own_order() -> OwnOrder {
+    OwnOrder {
+        order_id: OrdId::new(1742000000000),
+        base: DealerTicker::LBTC,
+        quote: DealerTicker::USDT,
+        trade_dir: TradeDir::Sell,
+        price: 98000.0,
+        orig_amount: 0.01,
+        active_amount: 0.004,
+        created_at: timestamp(),
+        ttl: Some(DurationMs::from_millis(3600000)),
+        private_id: None,
+        client_order_id: Some("order-42".to_owned()),
+        online: true,
+    }
+}
+
+/// One response of every variant (add new variants to `resp_name` too)
+fn sample_resps() -> Vec<Resp> {
+    let policy_asset = Network::LiquidTestnet.d().policy_asset;
+    vec![
+        Resp::NewPeg(NewPegResp {
+            peg: PegStatus {
+                list: Vec::new(),
+                ..peg_status()
+            },
+        }),
+        Resp::PegFeeEstimate(PegFeeEstimateResp {
+            server_fee_percent: 0.1,
+            fixed_fee: 0.00002,
+            fee_rate: Some(FeeRateSats::from_raw(10.0)),
+            recv_amount: 0.00997,
+            min_amount: 0.001,
+        }),
+        Resp::DelPeg(DelPegResp {}),
+        Resp::NewAddress(NewAddressResp {
+            index: 0,
+            address: address(),
+            gap_limit_remaining: 19,
+        }),
+        Resp::NewAddressBatch(NewAddressBatchResp {
+            addresses: vec![Address {
+                index: 1,
+                address: address(),
+                user_note: Some("invoice-1".to_owned()),
+            }],
+            stopped_reason: Some(AddressBatchStopReason::GapLimit),
+            gap_limit_remaining: 0,
+        }),
+        Resp::ListAddresses(ListAddressesResp {
+            addresses: vec![ListedAddress {
+                index: 0,
+                address: address(),
+                user_note: None,
+                change: false,
+                used: true,
+            }],
+        }),
+        Resp::CreateTx(CreateTxResp {
+            txid: txid(1),
+            network_fee: 47,
+            fee_rate: FeeRateSats::from_raw(0.1),
+            vsize: 470,
+            recipients: vec![CreatedTxRecipient {
+                address: address(),
+                asset: DealerTicker::USDT,
+                amount: 10.0,
+                recipient_indices: vec![0],
+            }],
+            reference: "0000-016".to_owned(),
+        }),
+        Resp::SendTx(send_tx_resp()),
+        Resp::GetQuote(GetQuoteResp {
+            quote_id: quote_id(),
+            send_amount: 10.0,
+            recv_amount: 0.0001163,
+            receive_address: address(),
+            ttl: DurationMs::from_millis(30000),
+            txid: txid(4),
+            pset_breakdown: Some(PsetBreakdown {
+                total_in: 12.5,
+                receive_out: 0.0001163,
+                change_out: 2.5,
+                network_fee: 0.0000003,
+            }),
+            route: None,
+        }),
+        Resp::AcceptQuote(AcceptQuoteResp {
+            txid: txid(4),
+            reference: "0000-02C".to_owned(),
+            route: None,
+        }),
+        Resp::CancelQuote(CancelQuoteResp {}),
+        Resp::GetMonitoredTxs(GetMonitoredTxsResp {
+            txs: vec![MonitoredTx {
+                txid: txid(1),
+                status: TxStatus::Mempool,
+                description: format!("send 10 USDt to {}", address()),
+                user_note: Some("payout".to_owned()),
+                reference: Some("0000-016".to_owned()),
+                created_at: TimestampMs::from_millis(1_700_000_000_000),
+            }],
+        }),
+        Resp::DelMonitoredTx(DelMonitoredTxResp {}),
+        Resp::SetTxNote(SetTxNoteResp {}),
+        Resp::ExportNotes(ExportNotesResp {
+            notes: BTreeMap::from([(
+                txid(1),
+                TxNotes {
+                    description: Some(format!("send 10 USDt to {}", address())),
+                    user_note: Some("invoice 42".to_owned()),
+                },
+            )]),
+        }),
+        Resp::ImportNotes(ImportNotesResp {
+            imported: vec![txid(1)],
+            skipped: vec![txid(2)],
+        }),
+        Resp::GetWalletTxs(GetWalletTxsResp {
+            txs: vec![WalletTx {
+                txid: txid(5),
+                height: Some(3320223),
+                balance: BTreeMap::from([(DealerTicker::LBTC, 0.00037277)]),
+                network_fee: None,
+                timestamp: Some(timestamp()),
+                tx_type: TxType::Incoming,
+                description: None,
+                user_note: None,
+            }],
+        }),
+        Resp::Unlock(UnlockResp {}),
+        Resp::GetServerInfo(GetServerInfoResp {
+            clock_skew_detected: false,
+            clock_skew_ms: Some(-120),
+            stale_quote_notifs: 0,
+            skipped_upstream_msgs: 0,
+            largest_upstream_msg: 65536,
+            upstream_request_sizes: BTreeMap::from([(
+                "Market.start_quotes".to_owned(),
+                RequestSizes {
+                    count: 2,
+                    total_bytes: 1024,
+                    max_bytes: 600,
+                    rejected: 0,
+                    histogram: vec![2, 0, 0, 0, 0, 0],
+                },
+            )]),
+            draining: false,
+            version: "0.1.2".to_owned(),
+            schema_version: 15,
+            onion_address: None,
+            tor_error: None,
+        }),
+        Resp::GetDiagnostics(GetDiagnosticsResp {
+            discarded_quote_notifs: 12,
+            stale_quote_notifs: 2,
+            quote_timeouts: 1,
+            rejected_pegs: vec![hash(2)],
+            foreign_addresses: 0,
+        }),
+        Resp::ListAssets(ListAssetsResp {
+            assets: vec![AssetInfo {
+                ticker: DealerTicker::LBTC,
+                asset_id: policy_asset,
+                precision: 8,
+                amp_restricted: Some(false),
+                payjoin: None,
+            }],
+        }),
+        Resp::GetAssetInfo(GetAssetInfoResp {
+            asset_id: elements::AssetId::from_str(
+                "ce091c998b83c78bb71a632313ba3760f1763d9cfcffae02258ffa9865a37bd2",
+            )
+            .unwrap(),
+            balance_key: DealerTicker::from_str("ce091c99").unwrap(),
+            whitelisted: false,
+            registered: true,
+            name: Some("Tether USD".to_owned()),
+            ticker: Some("USDt".to_owned()),
+            precision: Some(8),
+        }),
+        Resp::ListMarkets(ListMarketsResp {
+            markets: vec![Market {
+                base: DealerTicker::LBTC,
+                quote: DealerTicker::USDT,
+                fee_asset: AssetType::Quote,
+            }],
+            stale: true,
+        }),
+        Resp::ListUtxos(ListUtxosResp {
+            utxos: vec![UtxoInfo {
+                outpoint: elements::OutPoint::new(txid(3), 1),
+                ticker: DealerTicker::USDT,
+                amount: 10.5,
+                confirmations: 2,
+            }],
+        }),
+        Resp::ResolveGaid(ResolveGaidResp { address: address() }),
+        Resp::Drain(DrainResp {}),
+        Resp::GetPegTimeline(GetPegTimelineResp {
+            events: vec![PegEvent {
+                timestamp: timestamp(),
+                tx_hash: hash(3),
+                vout: 1,
+                tx_state: PegTxState::Done,
+                payout_txid: Some(hash(6)),
+            }],
+        }),
+        Resp::GetPegDetails(GetPegDetailsResp {
+            txs: vec![
+                PegTxDetails {
+                    txid: hash(3),
+                    vout: 1,
+                    amount: 0.001,
+                    status: PegTxProgress::Done,
+                    payout_txid: Some(hash(6)),
+                    payout_amount: Some(0.000999),
+                    updated_at: timestamp(),
+                },
+                PegTxDetails {
+                    txid: hash(4),
+                    vout: 0,
+                    amount: 0.002,
+                    status: PegTxProgress::Unknown("Refunded".to_owned()),
+                    payout_txid: None,
+                    payout_amount: None,
+                    updated_at: timestamp(),
+                },
+            ],
+        }),
+        Resp::AddAllowedAddress(AddAllowedAddressResp {}),
+        Resp::RemoveAllowedAddress(RemoveAllowedAddressResp {}),
+        Resp::ListAllowedAddresses(ListAllowedAddressesResp {
+            enforced: true,
+            addresses: vec![AllowedAddress {
+                address: address(),
+                label: Some("exchange".to_owned()),
+                added_by: Some("alice".to_owned()),
+                added_at: timestamp(),
+            }],
+        }),
+        Resp::GetBalanceHistory(GetBalanceHistoryResp {
+            points: vec![BalancePoint {
+                timestamp: timestamp(),
+                balances: balances(),
+            }],
+            granularity_seconds: Some(3600),
+        }),
+        Resp::SignMessage(SignMessageResp {
+            index: 0,
+            address: address(),
+            signature: "KBmwcCSgmEjiROxKUxxOQRlrBP/SB3VqC1Cc7hEDKHuNUow+ijZFVm2FUPmGhaH7yIiqF3bEZQlS9xY4UVxtGks=".to_owned(),
+        }),
+        Resp::VerifyMessage(VerifyMessageResp { valid: true }),
+        Resp::ExplainQuote(ExplainQuoteResp {
+            numbers: quote_numbers(),
+            send_asset: DealerTicker::LBTC,
+            send_amount: 0.0001,
+            send_amount_raw: 10000,
+            recv_asset: DealerTicker::USDT,
+            recv_amount: 9.93,
+            recv_amount_raw: 993000000,
+            fee_asset: DealerTicker::USDT,
+            fee_amount: 0.07,
+            fee_amount_raw: 7000000,
+            breakdown: vec![
+                "send 0.0001 L-BTC".to_owned(),
+                "receive 10 USDt - 0.07 USDt fee = 9.93 USDt".to_owned(),
+            ],
+        }),
+        Resp::GetQuotas(GetQuotasResp {
+            client_name: Some("payouts".to_owned()),
+            quotas: vec![QuotaUsage {
+                resource: QuotaResource::Pegs,
+                used: 1,
+                limit: Some(10),
+            }],
+        }),
+        Resp::ReloadConfig(ReloadConfigResp {
+            changed_fields: vec!["client_quotas".to_owned()],
+        }),
+        Resp::RefreshTickers(RefreshTickersResp {
+            added: vec![DealerTicker::DEPIX],
+        }),
+        Resp::FindByReference(FindByReferenceResp {
+            reference: "0000-016".to_owned(),
+            resource: ReferencedResource::MonitoredTx { txid: txid(1) },
+        }),
+        Resp::ListPendingApprovals(ListPendingApprovalsResp {
+            approvals: vec![PendingApproval {
+                approval_id: "4f1c2b7e9a0d3e6f".to_owned(),
+                operation: ApprovalOperation::SendTx,
+                txid: txid(1),
+                description: format!("send 1 L-BTC to {}", address()),
+                amounts: BTreeMap::from([(DealerTicker::LBTC, 1.0)]),
+                requested_by: Some("payouts".to_owned()),
+                created_at: timestamp(),
+                expires_at: TimestampMs::from_millis(timestamp().millis() + 3600000),
+            }],
+        }),
+        Resp::Approve(ApproveResp {
+            txid: txid(1),
+            send_tx: Some(send_tx_resp()),
+        }),
+        Resp::Reject(RejectResp {}),
+        Resp::SubscribePrice(SubscribePriceResp {}),
+        Resp::UnsubscribePrice(UnsubscribePriceResp {}),
+        Resp::SubmitOrder(SubmitOrderResp { order: own_order() }),
+        Resp::EditOrder(EditOrderResp { order: own_order() }),
+        Resp::CancelOrder(CancelOrderResp {}),
+        Resp::ListOwnOrders(ListOwnOrdersResp {
+            orders: vec![own_order()],
+        }),
+        Resp::GetSwaps(GetSwapsResp {
+            swaps: vec![Swap {
+                quote_id: QuoteId::new(1742000000042),
+                txid: txid(4),
+                send_asset: DealerTicker::USDT,
+                send_amount: 20.0,
+                recv_asset: DealerTicker::LBTC,
+                recv_amount: 0.00020399,
+                price: 97500.0,
+                fee_asset: DealerTicker::USDT,
+                fee_amount: 0.11,
+                server_fee: 10000000,
+                fixed_fee: 1000000,
+                timestamp: TimestampMs::from_millis(1743746770000),
+                status: TxStatus::Confirmed,
+                reference: Some("0000-02C".to_owned()),
+            }],
+        }),
+    ]
+}
+
+/// One notification of every variant (add new variants to `notif_name` too)
+fn sample_notifs() -> Vec<Notif> {
+    vec![
+        Notif::Balances(BalancesNotif {
+            wallet: "default".to_owned(),
+            balances: balances(),
+            confirmed: BTreeMap::from([(DealerTicker::LBTC, 0.00037277)]),
+            unconfirmed: BTreeMap::from([
+                (DealerTicker::LBTC, 0.00049974),
+                (DealerTicker::USDT, 10.5),
+            ]),
+        }),
+        Notif::PegStatus(PegStatusNotif { peg: peg_status() }),
+        Notif::Markets(MarketsNotif {
+            markets: vec![Market {
+                base: DealerTicker::LBTC,
+                quote: DealerTicker::USDT,
+                fee_asset: AssetType::Quote,
+            }],
+            stale: false,
+        }),
+        Notif::MarketPrice(MarketPriceNotif {
+            base: DealerTicker::LBTC,
+            quote: DealerTicker::USDT,
+            ind_price: Some(85000.5),
+            last_price: None,
+            stale: false,
+        }),
+        Notif::LockStatus(LockStatusNotif { locked: true }),
+        Notif::GapLimitWarning(GapLimitWarningNotif {
+            wallet: "default".to_owned(),
+            remaining: 4,
+            first_unused_index: 16,
+            active: true,
+        }),
+        Notif::Draining(DrainingNotif {
+            shutdown_at: timestamp(),
+        }),
+        Notif::PegDepositDetected(PegDepositDetectedNotif {
+            order_id: hash(2),
+            txid: hash(3),
+            vout: 1,
+            amount: 0.001,
+        }),
+        Notif::PegDepositConfirmed(PegDepositConfirmedNotif {
+            order_id: hash(2),
+            txid: hash(3),
+            vout: 1,
+        }),
+        Notif::PegPayoutBroadcast(PegPayoutBroadcastNotif {
+            order_id: hash(2),
+            txid: hash(3),
+            vout: 1,
+            payout_txid: hash(6),
+            payout_amount: Some(0.000999),
+        }),
+        Notif::PegCompleted(PegCompletedNotif {
+            order_id: hash(2),
+            txid: hash(3),
+            vout: 1,
+            payout_amount: Some(0.000999),
+        }),
+        Notif::PegFailed(PegFailedNotif {
+            order_id: hash(2),
+            txid: hash(3),
+            vout: 1,
+            reason: "amount is less than the minimum".to_owned(),
+        }),
+        Notif::ConfigReloaded(ConfigReloadedNotif {
+            changed_fields: vec!["gap_limit".to_owned()],
+        }),
+        Notif::TxStatus(TxStatusNotif {
+            txid: txid(1),
+            status: TxStatus::Confirmed,
+            reference: Some("0000-016".to_owned()),
+        }),
+        Notif::ApprovalResolved(ApprovalResolvedNotif {
+            approval_id: "4f1c2b7e9a0d3e6f".to_owned(),
+            state: ApprovalState::Approved,
+            txid: Some(txid(1)),
+            error: None,
+        }),
+        Notif::MarketAdded(MarketAddedNotif {
+            market: Market {
+                base: DealerTicker::LBTC,
+                quote: DealerTicker::USDT,
+                fee_asset: AssetType::Base,
+            },
+        }),
+        Notif::MarketRemoved(MarketRemovedNotif {
+            base: DealerTicker::LBTC,
+            quote: DealerTicker::USDT,
+        }),
+        Notif::PegComplete(PegCompleteNotif {
+            order_id: hash(2),
+            amount_received: 0.000999,
+        }),
+        Notif::ServerConnection(ServerConnectionNotif { connected: false }),
+        Notif::TxNote(TxNoteNotif {
+            txid: txid(1),
+            description: format!("send 10 USDt to {}", address()),
+            user_note: Some("invoice 42".to_owned()),
+        }),
+    ]
+}
+
+/// One error of every code (add new codes to `error_code_name` too)
+fn sample_errors() -> Vec<Error> {
+    [
+        (
+            ErrorCode::InvalidRequest,
+            "invalid balance history request: from must be before to",
+        ),
+        (ErrorCode::ServerError, "ws error: Unexpected response"),
+        (ErrorCode::NetworkError, "ws error: Disconnected"),
+        (ErrorCode::ServerTimeout, "wS error: Request timeout"),
+        (
+            ErrorCode::UtxoCheckFailed,
+            "UTXO check failed: Can't find wallet UTXOs, please retry",
+        ),
+        (ErrorCode::Locked, "signing is locked, unlock is required"),
+        (
+            ErrorCode::Draining,
+            "the manager is draining, please retry with another instance",
+        ),
+        (ErrorCode::Unauthorized, "login required"),
+        (
+            ErrorCode::TooManyRequests,
+            "too many requests in progress (max 100)",
+        ),
+    ]
+    .into_iter()
+    .map(|(code, text)| Error {
+        text: text.to_owned(),
+        code,
+        details: None,
+    })
+    .chain([Error {
+        text: "not enough USDt for the quote, required: 10.5, available: 7.25".to_owned(),
+        code: ErrorCode::QuoteLowBalance,
+        details: Some(ErrorDetails::QuoteLowBalance {
+            available: 7.25,
+            required: 10.5,
+        }),
+    }])
+    .chain(worker_errors().into_iter().map(Error::from))
+    .collect()
+}
+
+/// The errors returned by the worker, one for every other error code
+fn worker_errors() -> Vec<crate::error::Error> {
+    use crate::error::Error;
+
+    vec![
+        Error::WsError(ws_req_sender::Error::RequestTooLarge {
+            size: 200_000,
+            limit: 100_000,
+        }),
+        Error::InvalidTicker(DealerTicker::from_str("123456789").unwrap_err()),
+        Error::UnknownTicker(
+            sideswap_common::dealer_ticker::TickerResolveError::Unknown {
+                ticker: "USDX".to_owned(),
+                suggestions: vec![DealerTicker::USDT],
+                supported: None,
+            },
+        ),
+        Error::UnknownTicker(
+            sideswap_common::dealer_ticker::TickerResolveError::Ambiguous {
+                ticker: "EUR".to_owned(),
+                candidates: vec![DealerTicker::EURX, DealerTicker::from_str("EURC").unwrap()],
+            },
+        ),
+        Error::Lwk(sideswap_lwk::Error::InvalidArg("recipients")),
+        Error::InvalidAssetAmount(0.123456789, AssetPrecision::BITCOIN_PRECISION),
+        Error::NoMarket,
+        Error::NotEnoughAmount {
+            asset_id: Network::Liquid.d().policy_asset,
+            required: 150_000,
+            available: 100_000,
+        },
+        Error::QuoteError("no dealers".to_owned()),
+        Error::Base64(b64::decode("!").unwrap_err()),
+        Error::EncodeError(
+            elements::encode::deserialize::<elements::Transaction>(&[]).unwrap_err(),
+        ),
+        Error::PsetError(elements::pset::Error::MissingInputPrevTxId),
+        Error::NoUtxos,
+        Error::QuoteExpired,
+        Error::NoQuote,
+        Error::QuoteInvalidatedByReconnect,
+        Error::NoCreatedTx,
+        Error::GapLimit,
+        Error::WrongPassword,
+        Error::UnlockBackoff(Duration::from_secs(30)),
+        Error::AmpAddressRequired {
+            asset: DealerTicker::from_str("AMPT").unwrap(),
+        },
+        Error::GaidResolveFailed {
+            gaid: "GA2zxWdhAYtREeYCVFTGRhHQmYMPAP".to_owned(),
+            reason: "unknown GAID".to_owned(),
+        },
+        Error::InvalidQuoteReceiver,
+        Error::InvalidPegAddress {
+            expected_chain: "Bitcoin",
+            reason: "invalid checksum".to_owned(),
+        },
+        Error::PegAmountTooLow { min_amount: 0.001 },
+        Error::UnknownFeeTarget(3, vec![2, 6, 12]),
+        Error::UnknownPeg,
+        Error::UnknownWallet {
+            wallet: "cold".to_owned(),
+            available: vec!["default".to_owned(), "hot".to_owned()],
+        },
+        Error::AddressNotAllowed(address()),
+        Error::MessageTooLong(1024),
+        Error::NotOwnAddress(address().to_string()),
+        Error::QuotaExceeded {
+            resource: QuotaResource::Pegs,
+            limit: 10,
+        },
+        Error::InvalidAddress {
+            index: 1,
+            reason: "invalid checksum".to_owned(),
+        },
+        Error::DuplicateRecipient {
+            index_a: 0,
+            index_b: 2,
+        },
+        Error::SendAllNotAlone(DealerTicker::LBTC),
+        Error::NothingToSend(DealerTicker::USDT),
+        Error::UtxoSelectionNotSupported(DealerTicker::USDT),
+        Error::UnconfirmedInputs(2),
+        Error::InvalidFeeRate(10.0),
+        Error::InvalidQuoteTimeout(600),
+        Error::WalletTimeout(60),
+        Error::InvalidConfig("missing field `mnemonic`".to_owned()),
+        Error::RestartRequired(vec!["mnemonic", "ws_server"]),
+        Error::UnknownMonitoredTx(txid(1)),
+        Error::MonitoredTxUnconfirmed(txid(2)),
+        Error::InvalidReference("0000-01X".to_owned()),
+        Error::UnknownReference,
+        Error::ApprovalRequired("4f1c2b7e9a0d3e6f".to_owned()),
+        Error::UnknownApproval,
+        Error::ApproverNotAllowed,
+        Error::PsetMismatch {
+            reason: "receive output is 0, expected 95000000".to_owned(),
+        },
+        Error::MakerOrdersDisabled,
+        Error::UnknownOrder(OrdId::new(1742000000000)),
+        Error::OrderAboveApprovalThreshold {
+            asset: DealerTicker::LBTC,
+            threshold: 0.5,
+        },
+        Error::IdempotencyKeyReused("payout-42".to_owned()),
+        Error::IdempotencyKeyInProgress("payout-42".to_owned()),
+        Error::RouteLowBalance {
+            asset: DealerTicker::LBTC,
+            required: 0.001,
+            available: 0.0005,
+        },
+    ]
+}
+
+fn to_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).unwrap() + "\n"
+}
+
+/// All fixtures (relative path and the expected contents)
+fn golden_files() -> Vec<(String, String)> {
+    let mut files = Vec::new();
+    for req in sample_reqs() {
+        let name = format!("req/{}.json", req_name(&req));
+        files.push((name, to_json(&To::Req { id: REQ_ID, req })));
+    }
+    for resp in sample_resps() {
+        let name = format!("resp/{}.json", resp_name(&resp));
+        files.push((name, to_json(&From::Resp { id: REQ_ID, resp })));
+    }
+    for notif in sample_notifs() {
+        let name = format!("notif/{}.json", notif_name(&notif));
+        files.push((name, to_json(&From::Notif { notif })));
+    }
+    for err in sample_errors() {
+        let name = format!("error/{}.json", error_code_name(&err.code));
+        files.push((name, to_json(&From::Error { id: REQ_ID, err })));
+    }
+    files
+}
+
+fn existing_files(dir: &Path) -> Vec<String> {
+    let mut files = Vec::new();
+    for kind in ["req", "resp", "notif", "error"] {
+        let Ok(entries) = std::fs::read_dir(dir.join(kind)) else {
+            continue;
+        };
+        for entry in entries {
+            let name = entry.unwrap().file_name().into_string().unwrap();
+            files.push(format!("{kind}/{name}"));
+        }
+    }
+    files
+}
+
+#[test]
+fn golden_files_match() {
+    let dir = golden_dir();
+    let expected = golden_files();
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        for name in existing_files(&dir) {
+            std::fs::remove_file(dir.join(name)).unwrap();
+        }
+        for (name, contents) in expected.iter() {
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, contents).unwrap();
+        }
+        return;
+    }
+
+    let mut failed = Vec::new();
+    for (name, contents) in expected.iter() {
+        match std::fs::read_to_string(dir.join(name)) {
+            Ok(existing) if existing == *contents => {}
+            Ok(_) => failed.push(format!("{name} changed")),
+            Err(_) => failed.push(format!("{name} is missing")),
+        }
+    }
+    for name in existing_files(&dir) {
+        if !expected.iter().any(|(expected, _)| *expected == name) {
+            failed.push(format!("{name} is not expected"));
+        }
+    }
+    assert!(
+        failed.is_empty(),
+        "golden files are outdated:\n{}\nregenerate with `UPDATE_GOLDEN=1 cargo test -p sideswap_manager golden` and review the diff",
+        failed.join("\n")
+    );
+}
+
+#[test]
+fn golden_requests_decoded() {
+    for req in sample_reqs() {
+        let name = req_name(&req);
+        let json = to_json(&To::Req { id: REQ_ID, req });
+        let To::Req { id, req } = serde_json::from_str::<To>(&json).unwrap() else {
+            panic!("not a request");
+        };
+        assert_eq!(id, REQ_ID);
+        assert_eq!(req_name(&req), name);
+        assert_eq!(to_json(&To::Req { id, req }), json);
+    }
+}
+
+#[test]
+fn golden_every_request_has_response() {
+    let reqs = sample_reqs().iter().map(req_name).collect::<Vec<_>>();
+    let resps = sample_resps().iter().map(resp_name).collect::<Vec<_>>();
+    assert_eq!(reqs, resps);
+
+    let names = golden_files()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<std::collections::BTreeSet<_>>();
+    assert_eq!(names.len(), 49 + 49 + 20 + 67);
+}
@@ -0,0 +1,76 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use sideswap_api::mkt::QuoteId;
+use sideswap_common::dealer_ticker::DealerTicker;
+
+use crate::{api, wallets::DEFAULT_WALLET};
+
+const DEFAULT_TTL_SECONDS: u64 = 3600;
+
+/// `SendTx` and `AcceptQuote` requests sending more than the threshold of any asset are queued
+/// until approved by another client (see `ListPendingApprovals`)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// The largest amount (of the sent asset) that does not require an approval, by ticker
+    pub thresholds: BTreeMap<DealerTicker, f64>,
+    /// Not approved operations are rejected after this time (in seconds, 3600 by default)
+    pub ttl_seconds: Option<u64>,
+}
+
+impl Config {
+    pub fn ttl(&self) -> Duration {
+        Duration::from_secs(self.ttl_seconds.unwrap_or(DEFAULT_TTL_SECONDS))
+    }
+
+    /// True if any of the sent amounts is above its threshold
+    pub fn approval_required(&self, amounts: &BTreeMap<DealerTicker, f64>) -> bool {
+        amounts.iter().any(|(ticker, amount)| {
+            self.thresholds
+                .get(ticker)
+                .is_some_and(|threshold| amount > threshold)
+        })
+    }
+}
+
+/// The queued operation (stored in the DB), executed once approved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Operation {
+    SendTx {
+        /// The signed transaction (hex), the created transactions are not kept until the approval
+        tx: String,
+        /// Stored as `note` before it was renamed
+        #[serde(alias = "note")]
+        description: String,
+        user_note: Option<String>,
+        wallet_only: bool,
+        /// The wallet that created the transaction (not stored before several wallets were supported)
+        #[serde(default = "default_wallet")]
+        wallet: String,
+    },
+    AcceptQuote {
+        quote_id: QuoteId,
+        user_note: Option<String>,
+    },
+}
+
+impl Operation {
+    pub fn kind(&self) -> api::ApprovalOperation {
+        match self {
+            Operation::SendTx { .. } => api::ApprovalOperation::SendTx,
+            Operation::AcceptQuote { .. } => api::ApprovalOperation::AcceptQuote,
+        }
+    }
+}
+
+fn default_wallet() -> String {
+    DEFAULT_WALLET.to_owned()
+}
+
+/// The approver must be a client logged in with its own client token (`None` otherwise), other than the requester
+pub fn approver_allowed(requested_by: Option<&str>, approver: Option<&str>) -> bool {
+    approver.is_some() && approver != requested_by
+}
+
+#[cfg(test)]
+mod tests;
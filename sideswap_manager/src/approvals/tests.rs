@@ -0,0 +1,29 @@
+use super::*;
+
+#[test]
+fn thresholds_checked_per_asset() {
+    let config = Config {
+        thresholds: BTreeMap::from([(DealerTicker::LBTC, 0.01), (DealerTicker::USDT, 1000.0)]),
+        ttl_seconds: None,
+    };
+    let amounts = |values: &[(DealerTicker, f64)]| values.iter().copied().collect();
+
+    assert!(!config.approval_required(&amounts(&[(DealerTicker::LBTC, 0.01)])));
+    assert!(config.approval_required(&amounts(&[(DealerTicker::LBTC, 0.011)])));
+    assert!(config.approval_required(&amounts(&[
+        (DealerTicker::LBTC, 0.001),
+        (DealerTicker::USDT, 2000.0)
+    ])));
+    // Assets without a threshold are not limited
+    assert!(!config.approval_required(&amounts(&[(DealerTicker::EURX, 1e9)])));
+    assert_eq!(config.ttl(), Duration::from_secs(3600));
+}
+
+#[test]
+fn approver_must_differ() {
+    assert!(approver_allowed(Some("alice"), Some("bob")));
+    assert!(approver_allowed(None, Some("bob")));
+    assert!(!approver_allowed(Some("alice"), Some("alice")));
+    assert!(!approver_allowed(Some("alice"), None));
+    assert!(!approver_allowed(None, None));
+}
@@ -0,0 +1,85 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use elements::AssetId;
+use serde::Deserialize;
+
+use crate::models;
+
+/// Longer ranges are downsampled if `granularity_seconds` is not set
+pub const MAX_POINTS: usize = 1000;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// Record the wallet balances this often (and on clean shutdown)
+    pub interval_seconds: u64,
+    /// Delete older snapshots, keep everything if not set
+    pub retention_days: Option<u64>,
+}
+
+impl Config {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_seconds)
+    }
+
+    pub fn retention(&self) -> Option<Duration> {
+        self.retention_days
+            .map(|days| Duration::from_secs(days * 24 * 3600))
+    }
+}
+
+/// Wallet balances recorded at the same time (missing assets had zero balance)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub created_at: i64,
+    pub balances: BTreeMap<AssetId, u64>,
+}
+
+/// Group the DB rows (sorted by `created_at`) into snapshots
+pub fn snapshots(rows: Vec<models::BalanceHistory>) -> Vec<Snapshot> {
+    let mut snapshots = Vec::<Snapshot>::new();
+    for row in rows {
+        let amount = u64::try_from(row.amount).unwrap_or_default();
+        match snapshots.last_mut() {
+            Some(snapshot) if snapshot.created_at == row.created_at => {
+                snapshot.balances.insert(row.asset_id.0, amount);
+            }
+            _ => snapshots.push(Snapshot {
+                created_at: row.created_at,
+                balances: [(row.asset_id.0, amount)].into(),
+            }),
+        }
+    }
+    snapshots
+}
+
+/// The period length (in milliseconds) needed to return at most about `MAX_POINTS` points,
+/// `None` if the snapshots can be returned as is
+pub fn auto_granularity(snapshots: &[Snapshot]) -> Option<u64> {
+    if snapshots.len() <= MAX_POINTS {
+        return None;
+    }
+    let first = snapshots.first()?.created_at;
+    let last = snapshots.last()?.created_at;
+    let range = u64::try_from(last - first).unwrap_or_default() + 1;
+    // Round up to whole seconds
+    let granularity = range.div_ceil(MAX_POINTS as u64);
+    Some(granularity.div_ceil(1000) * 1000)
+}
+
+/// Keep only the last snapshot of every period (periods are aligned to the UNIX epoch)
+pub fn downsample(snapshots: Vec<Snapshot>, granularity: u64) -> Vec<Snapshot> {
+    let granularity = granularity.max(1) as i64;
+    let mut result = Vec::<Snapshot>::new();
+    for snapshot in snapshots {
+        match result.last_mut() {
+            Some(last) if last.created_at / granularity == snapshot.created_at / granularity => {
+                *last = snapshot;
+            }
+            _ => result.push(snapshot),
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests;
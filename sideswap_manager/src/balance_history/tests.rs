@@ -0,0 +1,69 @@
+use sqlx::types::Text;
+
+use super::*;
+
+fn asset(index: u8) -> AssetId {
+    AssetId::from_slice(&[index; 32]).unwrap()
+}
+
+fn snapshot(created_at: i64, balances: &[(u8, u64)]) -> Snapshot {
+    Snapshot {
+        created_at,
+        balances: balances
+            .iter()
+            .map(|(index, amount)| (asset(*index), *amount))
+            .collect(),
+    }
+}
+
+#[test]
+fn rows_grouped_by_time() {
+    let row = |created_at, index, amount| models::BalanceHistory {
+        wallet: "default".to_owned(),
+        created_at,
+        asset_id: Text(asset(index)),
+        amount,
+    };
+    let rows = vec![row(1000, 1, 5), row(1000, 2, 7), row(2000, 1, 6)];
+    assert_eq!(
+        snapshots(rows),
+        vec![snapshot(1000, &[(1, 5), (2, 7)]), snapshot(2000, &[(1, 6)])]
+    );
+}
+
+#[test]
+fn downsample_keeps_last_of_period() {
+    let snapshots = vec![
+        snapshot(0, &[(1, 1)]),
+        snapshot(30_000, &[(1, 2)]),
+        snapshot(60_000, &[(1, 3)]),
+        snapshot(119_999, &[(1, 4)]),
+        snapshot(180_000, &[(1, 5)]),
+    ];
+    assert_eq!(
+        downsample(snapshots, 60_000),
+        vec![
+            snapshot(30_000, &[(1, 2)]),
+            snapshot(119_999, &[(1, 4)]),
+            snapshot(180_000, &[(1, 5)]),
+        ]
+    );
+}
+
+#[test]
+fn auto_granularity_limits_points() {
+    let hourly = |count: i64| {
+        (0..count)
+            .map(|index| snapshot(index * 3_600_000, &[(1, index as u64)]))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(auto_granularity(&hourly(MAX_POINTS as i64)), None);
+
+    let snapshots = hourly(10 * MAX_POINTS as i64);
+    let granularity = auto_granularity(&snapshots).unwrap();
+    assert_eq!(granularity % 1000, 0);
+    let points = downsample(snapshots, granularity);
+    assert!(points.len() <= MAX_POINTS + 1);
+    assert!(points.len() >= MAX_POINTS / 2);
+}
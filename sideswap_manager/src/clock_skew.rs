@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use sideswap_types::timestamp_ms::TimestampMs;
+
+/// How often the local clock is compared against the time source
+pub const CHECK_PERIOD: Duration = Duration::from_secs(600);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn default_max_skew_seconds() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// HTTP(S) URL that is queried periodically, the `Date` response header is used as the reference time
+    pub time_source_url: String,
+    /// Clock skew is reported if the local clock differs from the time source by more than this (30 by default)
+    #[serde(default = "default_max_skew_seconds")]
+    pub max_skew_seconds: u64,
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct ClockSample {
+    /// Local time in the middle of the request
+    pub local_time: TimestampMs,
+    pub remote_time: TimestampMs,
+}
+
+pub struct ClockSkew {
+    threshold: Duration,
+    skew_ms: Option<i64>,
+    detected: bool,
+}
+
+impl ClockSkew {
+    pub fn new(max_skew: Duration) -> ClockSkew {
+        ClockSkew {
+            threshold: max_skew,
+            skew_ms: None,
+            detected: false,
+        }
+    }
+
+    pub fn detected(&self) -> bool {
+        self.detected
+    }
+
+    /// Remote time minus local time, in milliseconds (if known)
+    pub fn skew_ms(&self) -> Option<i64> {
+        self.skew_ms
+    }
+
+    /// Returns true if the detected state changed
+    pub fn update(&mut self, sample: ClockSample) -> bool {
+        let skew_ms = sample.remote_time.millis() as i64 - sample.local_time.millis() as i64;
+        self.skew_ms = Some(skew_ms);
+        let detected = Duration::from_millis(skew_ms.unsigned_abs()) > self.threshold;
+        let changed = detected != self.detected;
+        self.detected = detected;
+        changed
+    }
+
+    /// Returns the quote TTL to use locally.
+    /// While the skew is detected, the TTL is reduced by the skew amount (but not more than by half),
+    /// so that quotes are not accepted when the server could already consider them expired.
+    pub fn quote_ttl(&self, ttl: Duration) -> Duration {
+        match self.skew_ms {
+            Some(skew_ms) if self.detected => {
+                let margin = Duration::from_millis(skew_ms.unsigned_abs()).min(ttl / 2);
+                ttl - margin
+            }
+            _ => ttl,
+        }
+    }
+}
+
+/// Blocking, must be called from a blocking task
+pub fn fetch_sample(url: &str) -> Result<ClockSample, anyhow::Error> {
+    let started = TimestampMs::now();
+    let resp = match ureq::head(url).timeout(REQUEST_TIMEOUT).call() {
+        Ok(resp) | Err(ureq::Error::Status(_, resp)) => resp,
+        Err(err) => return Err(err.into()),
+    };
+    let finished = TimestampMs::now();
+
+    let date = resp
+        .header("date")
+        .ok_or_else(|| anyhow::anyhow!("no Date header in the response"))?;
+    let remote_time = chrono::DateTime::parse_from_rfc2822(date)?.timestamp_millis();
+    let remote_time = TimestampMs::from_millis(u64::try_from(remote_time)?);
+    let local_time = TimestampMs::from_millis(started.millis() / 2 + finished.millis() / 2);
+
+    Ok(ClockSample {
+        local_time,
+        remote_time,
+    })
+}
+
+#[cfg(test)]
+mod tests;
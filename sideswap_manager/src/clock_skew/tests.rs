@@ -0,0 +1,48 @@
+use super::*;
+
+fn sample(local_time: u64, remote_time: u64) -> ClockSample {
+    ClockSample {
+        local_time: TimestampMs::from_millis(local_time),
+        remote_time: TimestampMs::from_millis(remote_time),
+    }
+}
+
+#[test]
+fn skew_detection_and_recovery() {
+    let mut clock_skew = ClockSkew::new(Duration::from_secs(30));
+    assert!(!clock_skew.detected());
+    assert_eq!(clock_skew.skew_ms(), None);
+
+    assert!(!clock_skew.update(sample(1_000_000, 1_020_000)));
+    assert!(!clock_skew.detected());
+    assert_eq!(clock_skew.skew_ms(), Some(20_000));
+
+    assert!(clock_skew.update(sample(1_000_000, 940_000)));
+    assert!(clock_skew.detected());
+    assert_eq!(clock_skew.skew_ms(), Some(-60_000));
+
+    assert!(!clock_skew.update(sample(2_000_000, 2_045_000)));
+    assert!(clock_skew.detected());
+
+    assert!(clock_skew.update(sample(3_000_000, 3_000_500)));
+    assert!(!clock_skew.detected());
+}
+
+#[test]
+fn quote_ttl_margin() {
+    let ttl = Duration::from_secs(30);
+    let mut clock_skew = ClockSkew::new(Duration::from_secs(5));
+    assert_eq!(clock_skew.quote_ttl(ttl), ttl);
+
+    clock_skew.update(sample(1_000_000, 1_004_000));
+    assert_eq!(clock_skew.quote_ttl(ttl), ttl);
+
+    clock_skew.update(sample(1_000_000, 990_000));
+    assert_eq!(clock_skew.quote_ttl(ttl), Duration::from_secs(20));
+
+    clock_skew.update(sample(1_000_000, 1_600_000));
+    assert_eq!(clock_skew.quote_ttl(ttl), Duration::from_secs(15));
+
+    clock_skew.update(sample(1_000_000, 1_000_000));
+    assert_eq!(clock_skew.quote_ttl(ttl), ttl);
+}
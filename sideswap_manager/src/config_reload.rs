@@ -0,0 +1,154 @@
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+use crate::Settings;
+
+/// Notified on SIGHUP (reload the config file)
+pub struct ReloadSignal {
+    notify: Arc<Notify>,
+}
+
+impl Default for ReloadSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReloadSignal {
+    pub fn new() -> ReloadSignal {
+        let notify = Arc::new(Notify::new());
+
+        #[cfg(target_os = "linux")]
+        {
+            let notify_copy = Arc::clone(&notify);
+            tokio::spawn(async move {
+                let mut signal =
+                    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                        .expect("must not fail");
+                loop {
+                    signal.recv().await;
+                    tracing::debug!("received reload signal");
+                    notify_copy.notify_one();
+                }
+            });
+        }
+
+        ReloadSignal { notify }
+    }
+
+    pub async fn recv(&self) {
+        self.notify.notified().await;
+    }
+}
+
+/// Names of the changed settings
+#[derive(Debug, Default, PartialEq)]
+pub struct Changes {
+    /// Can be applied at runtime
+    pub reloadable: Vec<&'static str>,
+    /// Used only on startup, changing them requires a restart
+    pub boot_only: Vec<&'static str>,
+}
+
+macro_rules! changed_fields {
+    ($old:expr, $new:expr, $changed:expr, [$($field:ident),* $(,)?]) => {
+        $(
+            if $old.$field != $new.$field {
+                $changed.push(stringify!($field));
+            }
+        )*
+    };
+}
+
+pub fn compare(old: &Settings, new: &Settings) -> Changes {
+    // Every new setting must be listed below
+    let Settings {
+        env: _,
+        work_dir: _,
+        force_env_migration: _,
+        mnemonic: _,
+        encrypted_mnemonic: _,
+        mnemonic_key: _,
+        script_variant: _,
+        wallets: _,
+        ws_server: _,
+        http_listen_on: _,
+        metrics_listen_on: _,
+        whitelisted_assets: _,
+        gap_limit: _,
+        gap_limit_warning: _,
+        ticker_aliases: _,
+        auto_lock: _,
+        clock_check: _,
+        drain_grace_seconds: _,
+        shutdown_timeout_seconds: _,
+        created_tx_max_age_seconds: _,
+        wallet_timeout_seconds: _,
+        idempotency_key_retention_seconds: _,
+        enforce_allowlist: _,
+        quote_coalescing: _,
+        balance_history: _,
+        upstream_size_limits: _,
+        client_quotas: _,
+        approvals: _,
+        maker_orders: _,
+        tor: _,
+        webhooks: _,
+        log_format: _,
+        log_truncate_addresses: _,
+    } = old;
+
+    let mut changes = Changes::default();
+    changed_fields!(
+        old,
+        new,
+        changes.reloadable,
+        [
+            gap_limit,
+            gap_limit_warning,
+            drain_grace_seconds,
+            shutdown_timeout_seconds,
+            created_tx_max_age_seconds,
+            wallet_timeout_seconds,
+            idempotency_key_retention_seconds,
+            enforce_allowlist,
+            balance_history,
+            upstream_size_limits,
+            client_quotas,
+            approvals,
+        ]
+    );
+    changed_fields!(
+        old,
+        new,
+        changes.boot_only,
+        [
+            env,
+            work_dir,
+            force_env_migration,
+            mnemonic,
+            encrypted_mnemonic,
+            mnemonic_key,
+            script_variant,
+            wallets,
+            ws_server,
+            http_listen_on,
+            metrics_listen_on,
+            whitelisted_assets,
+            ticker_aliases,
+            auto_lock,
+            clock_check,
+            quote_coalescing,
+            maker_orders,
+            tor,
+            webhooks,
+            log_format,
+            log_truncate_addresses,
+        ]
+    );
+    changes
+}
+
+#[cfg(test)]
+mod tests;
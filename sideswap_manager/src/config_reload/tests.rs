@@ -0,0 +1,50 @@
+use super::*;
+
+fn settings(extra: serde_json::Value) -> Settings {
+    let mut value = serde_json::json!({
+        "env": "Testnet",
+        "work_dir": "/var/lib/sideswap_manager",
+        "mnemonic": "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about",
+        "script_variant": "wpkh",
+        "ws_server": {
+            "listen_on": "127.0.0.1:3102",
+        },
+    });
+    value
+        .as_object_mut()
+        .unwrap()
+        .extend(extra.as_object().unwrap().clone());
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn changes_classified() {
+    let old = settings(serde_json::json!({}));
+
+    assert_eq!(compare(&old, &old), Changes::default());
+
+    let new = settings(serde_json::json!({
+        "gap_limit": 50,
+        "client_quotas": { "max_pegs": 5 },
+    }));
+    assert_eq!(
+        compare(&old, &new),
+        Changes {
+            reloadable: vec!["gap_limit", "client_quotas"],
+            boot_only: vec![],
+        }
+    );
+
+    let new = settings(serde_json::json!({
+        "mnemonic": "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        "script_variant": "shwpkh",
+        "enforce_allowlist": true,
+    }));
+    assert_eq!(
+        compare(&old, &new),
+        Changes {
+            reloadable: vec!["enforce_allowlist"],
+            boot_only: vec!["mnemonic", "script_variant"],
+        }
+    );
+}
@@ -1,59 +1,371 @@
-use std::{path::Path, str::FromStr};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use sideswap_api::OrderId;
+use sideswap_common::{dealer_ticker::DealerTicker, env::Env};
 use sqlx::{
+    migrate::Migrator,
     sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions},
-    types::Text,
+    types::{Json, Text},
     SqlitePool,
 };
 
-use crate::models::{self, MonitoredTx, Peg};
+use crate::{
+    api, approvals, idempotency,
+    models::{self, MonitoredTx, Peg},
+    peg_notifs,
+};
+
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// The manager version (stored in the DB on every successful startup)
+pub const BINARY_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+const BINARY_VERSION_KEY: &str = "binary_version";
+const SCHEMA_VERSION_KEY: &str = "schema_version";
+const ENV_KEY: &str = "env";
+
+#[derive(thiserror::Error, Debug)]
+pub enum OpenError {
+    #[error("DB schema version {db_schema_version} (last started by sideswap_manager {db_binary_version}) is newer than schema version {schema_version} supported by sideswap_manager {BINARY_VERSION}, upgrade the binary or restore the DB backup")]
+    SchemaTooNew {
+        db_schema_version: i64,
+        db_binary_version: String,
+        schema_version: i64,
+    },
+    #[error("the DB was created for the {db_env} environment, but the config has env = {env}; use the old work_dir, or set force_env_migration to move the old DB aside and start with a new one")]
+    EnvMismatch { db_env: String, env: &'static str },
+    #[error("the DB contains wallet addresses from another network (for example {address}), but the config has env = {env}; use the old work_dir, or set force_env_migration to move the old DB aside and start with a new one")]
+    NetworkMismatch { address: String, env: &'static str },
+    #[error("moving the old DB aside failed: {0}")]
+    MoveAside(#[from] std::io::Error),
+}
 
 pub struct Db {
     pool: SqlitePool,
 }
 
+/// Renames the DB files (the DB must be closed), returns the new DB file path
+pub fn move_aside(path: &Path, suffix: &str) -> Result<PathBuf, OpenError> {
+    let backup = PathBuf::from(format!("{}.{suffix}", path.display()));
+    std::fs::rename(path, &backup)?;
+    for extension in ["-wal", "-shm"] {
+        let file = PathBuf::from(format!("{}{extension}", path.display()));
+        if file.exists() {
+            std::fs::rename(file, format!("{}{extension}", backup.display()))?;
+        }
+    }
+    Ok(backup)
+}
+
+/// The latest migration known to this binary
+pub fn schema_version() -> i64 {
+    MIGRATOR
+        .iter()
+        .map(|migration| migration.version)
+        .max()
+        .expect("must not be empty")
+}
+
+/// Refuse to open a DB migrated by a newer binary (before any other table is touched)
+async fn check_schema_version(pool: &SqlitePool) -> Result<(), OpenError> {
+    // Both tables might be missing if the DB is new
+    let db_schema_version =
+        sqlx::query_scalar::<_, Option<i64>>("select max(version) from _sqlx_migrations")
+            .fetch_one(pool)
+            .await
+            .ok()
+            .flatten();
+
+    match db_schema_version {
+        Some(db_schema_version) if db_schema_version > schema_version() => {
+            let db_binary_version =
+                sqlx::query_scalar::<_, String>("select value from settings where key = ?")
+                    .bind(BINARY_VERSION_KEY)
+                    .fetch_optional(pool)
+                    .await
+                    .ok()
+                    .flatten()
+                    .unwrap_or_else(|| "unknown".to_owned());
+            Err(OpenError::SchemaTooNew {
+                db_schema_version,
+                db_binary_version,
+                schema_version: schema_version(),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
 impl Db {
-    async fn open_with_options(option: SqliteConnectOptions) -> Self {
-        let pool = SqlitePoolOptions::new()
+    async fn open_with_options(
+        pool_options: SqlitePoolOptions,
+        option: SqliteConnectOptions,
+    ) -> Result<Self, OpenError> {
+        let pool = pool_options
             .connect_with(option.foreign_keys(true))
             .await
             .expect("should not fail");
 
-        sqlx::migrate!().run(&pool).await.expect("should not fail");
+        if let Err(err) = check_schema_version(&pool).await {
+            pool.close().await;
+            return Err(err);
+        }
+
+        MIGRATOR.run(&pool).await.expect("should not fail");
 
-        Self { pool }
+        let db = Self { pool };
+
+        db.set_setting(BINARY_VERSION_KEY, &BINARY_VERSION).await;
+        db.set_setting(SCHEMA_VERSION_KEY, &schema_version()).await;
+
+        Ok(db)
     }
 
-    pub async fn open_file(path: impl AsRef<Path>) -> Self {
+    pub async fn open_file(path: impl AsRef<Path>) -> Result<Self, OpenError> {
         let options = SqliteConnectOptions::new()
             .filename(path)
             .create_if_missing(true)
             .journal_mode(SqliteJournalMode::Wal);
 
-        Self::open_with_options(options).await
+        Self::open_with_options(SqlitePoolOptions::new(), options).await
+    }
+
+    #[cfg(test)]
+    pub async fn open_memory() -> Self {
+        let options: SqliteConnectOptions = ":memory:".parse().expect("must not fail");
+        // Every connection opens a separate in-memory DB
+        let pool_options = SqlitePoolOptions::new().max_connections(1);
+        Self::open_with_options(pool_options, options)
+            .await
+            .expect("must not fail")
+    }
+
+    /// Stores the environment on the first run and refuses it to be changed later.
+    /// The DBs created before the environment was stored are checked by the network of the wallet addresses.
+    pub async fn check_env(&self, env: Env) -> Result<(), OpenError> {
+        let env_name = env.d().name;
+        match self.get_setting::<String>(ENV_KEY).await {
+            Some(db_env) if db_env == env_name => Ok(()),
+            Some(db_env) => Err(OpenError::EnvMismatch {
+                db_env,
+                env: env_name,
+            }),
+            None => {
+                let params = env.d().network.d().elements_params;
+                let foreign_address = self
+                    .load_addresses()
+                    .await
+                    .into_iter()
+                    .find(|addr| addr.address.params != params);
+                if let Some(addr) = foreign_address {
+                    return Err(OpenError::NetworkMismatch {
+                        address: addr.address.0.to_string(),
+                        env: env_name,
+                    });
+                }
+                self.set_setting(ENV_KEY, &env_name).await;
+                Ok(())
+            }
+        }
     }
 
     pub async fn add_peg(&self, peg: Peg) {
         let order_id = Text(peg.order_id.0);
-        sqlx::query!("insert into pegs (order_id) values (?)", order_id)
-            .execute(&self.pool)
-            .await
-            .expect("must not fail");
+        sqlx::query!(
+            "insert into pegs (order_id, addr_recv, created_by, completed_at) values (?, ?, ?, ?)",
+            order_id,
+            peg.addr_recv,
+            peg.created_by,
+            peg.completed_at,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
     }
 
     pub async fn delete_peg(&self, order_id: OrderId) {
         let order_id = Text(order_id);
+        let mut tx = self.pool.begin().await.expect("must not fail");
         sqlx::query!("delete from pegs where order_id = ?", order_id)
-            .execute(&self.pool)
+            .execute(&mut *tx)
+            .await
+            .expect("must not fail");
+        sqlx::query!("delete from peg_events where order_id = ?", order_id)
+            .execute(&mut *tx)
             .await
             .expect("must not fail");
+        sqlx::query!("delete from peg_notif_stages where order_id = ?", order_id)
+            .execute(&mut *tx)
+            .await
+            .expect("must not fail");
+        sqlx::query!("delete from peg_txs where order_id = ?", order_id)
+            .execute(&mut *tx)
+            .await
+            .expect("must not fail");
+        tx.commit().await.expect("must not fail");
     }
 
     pub async fn load_pegs(&self) -> Vec<Peg> {
         sqlx::query_as!(
             Peg,
-            "select order_id as 'order_id!: Text<OrderId>' from pegs"
+            "select order_id as 'order_id!: Text<OrderId>', addr_recv, created_by, completed_at from pegs"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    pub async fn set_peg_completed(&self, order_id: OrderId, completed_at: i64) {
+        let order_id = Text(order_id);
+        sqlx::query!(
+            "update pegs set completed_at = ? where order_id = ?",
+            completed_at,
+            order_id,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    /// Only the last `max_events` events are kept for the peg
+    pub async fn add_peg_event(&self, event: &models::PegEvent, max_events: i64) {
+        let mut tx = self.pool.begin().await.expect("must not fail");
+        sqlx::query!(
+            "insert into peg_events (order_id, created_at, tx_hash, vout, tx_state, payout_txid) values (?, ?, ?, ?, ?, ?)",
+            event.order_id,
+            event.created_at,
+            event.tx_hash,
+            event.vout,
+            event.tx_state,
+            event.payout_txid,
+        )
+        .execute(&mut *tx)
+        .await
+        .expect("must not fail");
+        sqlx::query!(
+            "delete from peg_events where order_id = ? and id not in (select id from peg_events where order_id = ? order by id desc limit ?)",
+            event.order_id,
+            event.order_id,
+            max_events,
+        )
+        .execute(&mut *tx)
+        .await
+        .expect("must not fail");
+        tx.commit().await.expect("must not fail");
+    }
+
+    pub async fn load_peg_events(&self) -> Vec<models::PegEvent> {
+        sqlx::query_as!(
+            models::PegEvent,
+            r#"select order_id as "order_id!: Text<OrderId>", created_at, tx_hash as "tx_hash!: Text<sideswap_api::Hash32>", vout, tx_state as "tx_state!: Json<api::PegTxState>", payout_txid as "payout_txid: Text<sideswap_api::Hash32>" from peg_events order by id"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    /// Inserts the new transaction or updates the changed one (`updated_at` is kept if nothing else has changed)
+    pub async fn set_peg_tx(&self, peg_tx: &models::PegTx) {
+        sqlx::query!(
+            "insert into peg_txs (order_id, tx_hash, vout, amount, status, payout_txid, payout_amount, updated_at) values (?, ?, ?, ?, ?, ?, ?, ?)
+            on conflict (order_id, tx_hash, vout) do update set amount = excluded.amount, status = excluded.status, payout_txid = excluded.payout_txid, payout_amount = excluded.payout_amount, updated_at = excluded.updated_at
+            where amount != excluded.amount or status != excluded.status or payout_txid is not excluded.payout_txid or payout_amount is not excluded.payout_amount",
+            peg_tx.order_id,
+            peg_tx.tx_hash,
+            peg_tx.vout,
+            peg_tx.amount,
+            peg_tx.status,
+            peg_tx.payout_txid,
+            peg_tx.payout_amount,
+            peg_tx.updated_at,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn load_peg_txs(&self, order_id: OrderId) -> Vec<models::PegTx> {
+        let order_id = Text(order_id);
+        sqlx::query_as!(
+            models::PegTx,
+            r#"select order_id as "order_id!: Text<OrderId>", tx_hash as "tx_hash!: Text<sideswap_api::Hash32>", vout, amount, status, payout_txid as "payout_txid: Text<sideswap_api::Hash32>", payout_amount, updated_at from peg_txs where order_id = ? order by rowid"#,
+            order_id,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    pub async fn set_peg_notif_stage(&self, stage: &models::PegNotifStage) {
+        sqlx::query!(
+            "insert or replace into peg_notif_stages (order_id, tx_hash, vout, stage) values (?, ?, ?, ?)",
+            stage.order_id,
+            stage.tx_hash,
+            stage.vout,
+            stage.stage,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn load_peg_notif_stages(&self) -> Vec<models::PegNotifStage> {
+        sqlx::query_as!(
+            models::PegNotifStage,
+            r#"select order_id as "order_id!: Text<OrderId>", tx_hash as "tx_hash!: Text<sideswap_api::Hash32>", vout, stage as "stage!: Json<peg_notifs::Stage>" from peg_notif_stages"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    /// Add a balance snapshot (one row per asset) and delete the rows older than `delete_before`
+    pub async fn add_balance_snapshot(
+        &self,
+        rows: &[models::BalanceHistory],
+        delete_before: Option<i64>,
+    ) {
+        let mut tx = self.pool.begin().await.expect("must not fail");
+        for row in rows {
+            sqlx::query!(
+                "insert into balance_history (wallet, created_at, asset_id, amount) values (?, ?, ?, ?)",
+                row.wallet,
+                row.created_at,
+                row.asset_id,
+                row.amount,
+            )
+            .execute(&mut *tx)
+            .await
+            .expect("must not fail");
+        }
+        if let Some(delete_before) = delete_before {
+            sqlx::query!(
+                "delete from balance_history where created_at < ?",
+                delete_before
+            )
+            .execute(&mut *tx)
+            .await
+            .expect("must not fail");
+        }
+        tx.commit().await.expect("must not fail");
+    }
+
+    pub async fn load_balance_history(
+        &self,
+        wallet: &str,
+        from: i64,
+        to: i64,
+    ) -> Vec<models::BalanceHistory> {
+        sqlx::query_as!(
+            models::BalanceHistory,
+            r#"select wallet, created_at, asset_id as "asset_id!: Text<elements::AssetId>", amount from balance_history where wallet = ? and created_at >= ? and created_at <= ? order by created_at, id"#,
+            wallet,
+            from,
+            to,
         )
         .fetch_all(&self.pool)
         .await
@@ -63,10 +375,63 @@ impl Db {
     pub async fn add_monitored_tx(&self, tx: MonitoredTx) {
         let txid = Text(tx.txid.0);
         sqlx::query!(
-            "insert into monitored_txs (txid, description, user_note) values (?, ?, ?)",
+            "insert into monitored_txs (txid, description, user_note, created_by, send_result, wallet, created_at) values (?, ?, ?, ?, ?, ?, ?)",
             txid,
             tx.description,
             tx.user_note,
+            tx.created_by,
+            tx.send_result,
+            tx.wallet,
+            tx.created_at,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn set_monitored_tx_send_result(
+        &self,
+        txid: elements::Txid,
+        send_result: &Json<api::SendTxResp>,
+    ) {
+        let txid = Text(txid);
+        sqlx::query!(
+            "update monitored_txs set send_result = ? where txid = ?",
+            send_result,
+            txid,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn set_monitored_tx_user_note(&self, txid: elements::Txid, user_note: Option<&str>) {
+        let txid = Text(txid);
+        sqlx::query!(
+            "update monitored_txs set user_note = ? where txid = ?",
+            user_note,
+            txid,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    /// Adds the monitored tx if it does not exist (with `created_at`), otherwise replaces the notes only
+    pub async fn upsert_monitored_tx_notes(
+        &self,
+        txid: elements::Txid,
+        description: Option<&str>,
+        user_note: Option<&str>,
+        created_at: i64,
+    ) {
+        let txid = Text(txid);
+        sqlx::query!(
+            "insert into monitored_txs (txid, description, user_note, created_at) values (?, ?, ?, ?) on conflict(txid) do update set description = excluded.description, user_note = excluded.user_note",
+            txid,
+            description,
+            user_note,
+            created_at,
         )
         .execute(&self.pool)
         .await
@@ -84,7 +449,7 @@ impl Db {
     pub async fn load_monitored_txs(&self) -> Vec<MonitoredTx> {
         sqlx::query_as!(
             MonitoredTx,
-            "select txid as 'txid!: Text<elements::Txid>', description, user_note from monitored_txs"
+            "select txid as 'txid!: Text<elements::Txid>', description, user_note, created_by, send_result as 'send_result: Json<api::SendTxResp>', wallet, created_at from monitored_txs"
         )
         .fetch_all(&self.pool)
         .await
@@ -93,7 +458,8 @@ impl Db {
 
     pub async fn add_address(&self, addr: models::Address) {
         sqlx::query!(
-            "insert into addresses (ind, address, user_note) values (?, ?, ?)",
+            "insert into addresses (wallet, ind, address, user_note) values (?, ?, ?, ?)",
+            addr.wallet,
             addr.ind,
             addr.address,
             addr.user_note,
@@ -103,10 +469,315 @@ impl Db {
         .expect("must not fail");
     }
 
+    /// All addresses are inserted in a single transaction
+    pub async fn add_addresses(&self, addrs: &[models::Address]) {
+        let mut tx = self.pool.begin().await.expect("must not fail");
+        for addr in addrs {
+            sqlx::query!(
+                "insert into addresses (wallet, ind, address, user_note) values (?, ?, ?, ?)",
+                addr.wallet,
+                addr.ind,
+                addr.address,
+                addr.user_note,
+            )
+            .execute(&mut *tx)
+            .await
+            .expect("must not fail");
+        }
+        tx.commit().await.expect("must not fail");
+    }
+
     pub async fn load_addresses(&self) -> Vec<models::Address> {
         sqlx::query_as!(
             models::Address,
-            "select ind, address as 'address!: Text<elements::Address>', user_note from addresses"
+            "select wallet, ind, address as 'address!: Text<elements::Address>', user_note from addresses"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    pub async fn add_allowed_address(&self, addr: &models::AllowedAddress) {
+        sqlx::query!(
+            "insert or replace into allowed_addresses (address, label, added_by, added_at) values (?, ?, ?, ?)",
+            addr.address,
+            addr.label,
+            addr.added_by,
+            addr.added_at,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn delete_allowed_address(&self, address: &elements::Address) {
+        let address = Text(address);
+        sqlx::query!("delete from allowed_addresses where address = ?", address)
+            .execute(&self.pool)
+            .await
+            .expect("must not fail");
+    }
+
+    pub async fn load_allowed_addresses(&self) -> Vec<models::AllowedAddress> {
+        sqlx::query_as!(
+            models::AllowedAddress,
+            "select address as 'address!: Text<elements::Address>', label, added_by, added_at from allowed_addresses"
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    /// Returns false if the reference is already used
+    pub async fn add_payment_reference(&self, row: &models::PaymentReference) -> bool {
+        let res = sqlx::query!(
+            "insert or ignore into payment_references (id, reference, kind, target) values (?, ?, ?, ?)",
+            row.id,
+            row.reference,
+            row.kind,
+            row.target,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+        res.rows_affected() == 1
+    }
+
+    pub async fn load_payment_references(&self) -> Vec<models::PaymentReference> {
+        sqlx::query_as!(
+            models::PaymentReference,
+            r#"select id, reference, kind as "kind!: Json<api::ReferenceKind>", target from payment_references"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    pub async fn add_pending_approval(&self, approval: &models::PendingApproval) {
+        sqlx::query!(
+            "insert into pending_approvals (approval_id, operation, txid, description, amounts, requested_by, created_at, expires_at) values (?, ?, ?, ?, ?, ?, ?, ?)",
+            approval.approval_id,
+            approval.operation,
+            approval.txid,
+            approval.description,
+            approval.amounts,
+            approval.requested_by,
+            approval.created_at,
+            approval.expires_at,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn delete_pending_approval(&self, approval_id: &str) {
+        sqlx::query!(
+            "delete from pending_approvals where approval_id = ?",
+            approval_id
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn load_pending_approvals(&self) -> Vec<models::PendingApproval> {
+        sqlx::query_as!(
+            models::PendingApproval,
+            r#"select approval_id, operation as "operation!: Json<approvals::Operation>", txid as "txid!: Text<elements::Txid>", description, amounts as "amounts!: Json<BTreeMap<DealerTicker, f64>>", requested_by, created_at, expires_at from pending_approvals"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    /// Replaces the expired key with the same value
+    pub async fn add_idempotency_key(&self, row: &models::IdempotencyKey) {
+        sqlx::query!(
+            "insert or replace into idempotency_keys (client, wallet, kind, key, request, response, created_at) values (?, ?, ?, ?, ?, ?, ?)",
+            row.client,
+            row.wallet,
+            row.kind,
+            row.key,
+            row.request,
+            row.response,
+            row.created_at,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn load_idempotency_key(
+        &self,
+        id: &idempotency::KeyId,
+    ) -> Option<models::IdempotencyKey> {
+        sqlx::query_as!(
+            models::IdempotencyKey,
+            r#"select client, wallet, kind, key, request as "request!: Json<idempotency::Request>", response, created_at from idempotency_keys where client = ? and wallet = ? and kind = ? and key = ?"#,
+            id.client,
+            id.wallet,
+            id.kind,
+            id.key,
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    pub async fn set_idempotency_response(&self, id: &idempotency::KeyId, response: &str) {
+        sqlx::query!(
+            "update idempotency_keys set response = ? where client = ? and wallet = ? and kind = ? and key = ?",
+            response,
+            id.client,
+            id.wallet,
+            id.kind,
+            id.key,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn delete_idempotency_key(&self, id: &idempotency::KeyId) {
+        sqlx::query!(
+            "delete from idempotency_keys where client = ? and wallet = ? and kind = ? and key = ?",
+            id.client,
+            id.wallet,
+            id.kind,
+            id.key,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    /// Removes the keys created before `created_at`
+    pub async fn delete_idempotency_keys_before(&self, created_at: i64) {
+        sqlx::query!(
+            "delete from idempotency_keys where created_at < ?",
+            created_at
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn add_created_tx(&self, created_tx: &models::CreatedTx) {
+        sqlx::query!(
+            "insert or replace into created_txs (txid, wallet, tx, description, created_by, amounts, created_at) values (?, ?, ?, ?, ?, ?, ?)",
+            created_tx.txid,
+            created_tx.wallet,
+            created_tx.tx,
+            created_tx.description,
+            created_tx.created_by,
+            created_tx.amounts,
+            created_tx.created_at,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn delete_created_tx(&self, txid: elements::Txid) {
+        let txid = Text(txid);
+        sqlx::query!("delete from created_txs where txid = ?", txid)
+            .execute(&self.pool)
+            .await
+            .expect("must not fail");
+    }
+
+    pub async fn load_created_txs(&self) -> Vec<models::CreatedTx> {
+        sqlx::query_as!(
+            models::CreatedTx,
+            r#"select txid as "txid!: Text<elements::Txid>", wallet, tx, description, created_by, amounts as "amounts!: Json<BTreeMap<DealerTicker, f64>>", created_at from created_txs"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    pub async fn add_swap(&self, swap: &models::Swap) {
+        sqlx::query!(
+            "insert or replace into swaps (quote_id, txid, send_ticker, send_amount, recv_ticker, recv_amount, price, fee_ticker, fee_amount, server_fee, fixed_fee, created_by, created_at) values (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            swap.quote_id,
+            swap.txid,
+            swap.send_ticker,
+            swap.send_amount,
+            swap.recv_ticker,
+            swap.recv_amount,
+            swap.price,
+            swap.fee_ticker,
+            swap.fee_amount,
+            swap.server_fee,
+            swap.fixed_fee,
+            swap.created_by,
+            swap.created_at,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    /// Swaps accepted in the range (inclusive), oldest first
+    pub async fn load_swaps(&self, from: i64, to: i64) -> Vec<models::Swap> {
+        sqlx::query_as!(
+            models::Swap,
+            r#"select quote_id, txid as "txid!: Text<elements::Txid>", send_ticker as "send_ticker!: Text<DealerTicker>", send_amount, recv_ticker as "recv_ticker!: Text<DealerTicker>", recv_amount, price, fee_ticker as "fee_ticker!: Text<DealerTicker>", fee_amount, server_fee, fixed_fee, created_by, created_at from swaps where created_at >= ? and created_at <= ? order by created_at, quote_id"#,
+            from,
+            to,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    pub async fn add_maker_order(&self, order: &models::MakerOrder) {
+        sqlx::query!(
+            "insert or replace into maker_orders (order_id, receive_address, change_address, created_by, created_at) values (?, ?, ?, ?, ?)",
+            order.order_id,
+            order.receive_address,
+            order.change_address,
+            order.created_by,
+            order.created_at,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    pub async fn delete_maker_order(&self, order_id: i64) {
+        sqlx::query!("delete from maker_orders where order_id = ?", order_id)
+            .execute(&self.pool)
+            .await
+            .expect("must not fail");
+    }
+
+    pub async fn load_maker_orders(&self) -> Vec<models::MakerOrder> {
+        sqlx::query_as!(
+            models::MakerOrder,
+            r#"select order_id, receive_address as "receive_address!: Text<elements::Address>", change_address as "change_address!: Text<elements::Address>", created_by, created_at from maker_orders"#
+        )
+        .fetch_all(&self.pool)
+        .await
+        .expect("must not fail")
+    }
+
+    pub async fn add_audit_event(&self, created_at: i64, event: &str) {
+        sqlx::query!(
+            "insert into audit_log (created_at, event) values (?, ?)",
+            created_at,
+            event,
+        )
+        .execute(&self.pool)
+        .await
+        .expect("must not fail");
+    }
+
+    #[cfg(test)]
+    pub async fn load_audit_events(&self) -> Vec<models::AuditEvent> {
+        sqlx::query_as!(
+            models::AuditEvent,
+            "select created_at, event from audit_log order by id"
         )
         .fetch_all(&self.pool)
         .await
@@ -1,10 +1,12 @@
+use elements::hashes::Hash;
 use sideswap_common::random_id::random_hash32;
+use sideswap_types::timestamp_ms::TimestampMs;
+use sqlx::migrate::Migrate;
 
 use super::*;
 
 async fn create_test_db() -> Db {
-    let options: SqliteConnectOptions = ":memory:".parse().unwrap();
-    Db::open_with_options(options).await
+    Db::open_memory().await
 }
 
 #[tokio::test]
@@ -13,11 +15,19 @@ async fn db_test_1() {
     let order_id = random_hash32();
     db.add_peg(Peg {
         order_id: Text(order_id),
+        addr_recv: Some("tb1qpeg".to_owned()),
+        created_by: None,
+        completed_at: None,
     })
     .await;
     let orders = db.load_pegs().await;
     assert_eq!(orders.len(), 1);
     assert_eq!(orders[0].order_id.0, order_id);
+    assert_eq!(orders[0].addr_recv.as_deref(), Some("tb1qpeg"));
+    assert_eq!(orders[0].completed_at, None);
+
+    db.set_peg_completed(order_id, 1000).await;
+    assert_eq!(db.load_pegs().await[0].completed_at, Some(1000));
     db.delete_peg(order_id).await;
 
     let orders = db.load_pegs().await;
@@ -25,3 +35,216 @@ async fn db_test_1() {
 
     db.close().await;
 }
+
+#[tokio::test]
+async fn newer_schema_refused() {
+    let path = std::env::temp_dir().join(format!("manager_test_{}.sqlite", random_hash32()));
+
+    let db = Db::open_file(&path).await.unwrap();
+    assert_eq!(
+        db.get_setting::<String>(BINARY_VERSION_KEY)
+            .await
+            .as_deref(),
+        Some(BINARY_VERSION)
+    );
+    assert_eq!(
+        db.get_setting::<i64>(SCHEMA_VERSION_KEY).await,
+        Some(schema_version())
+    );
+    db.close().await;
+
+    // Reopening with the same binary works
+    let db = Db::open_file(&path).await.unwrap();
+
+    // Simulate a migration applied by a newer binary
+    let future_version = schema_version() + 1;
+    sqlx::query(
+        "insert into _sqlx_migrations (version, description, success, checksum, execution_time) values (?, 'future', true, x'00', 0)",
+    )
+    .bind(future_version)
+    .execute(&db.pool)
+    .await
+    .unwrap();
+    db.set_setting(BINARY_VERSION_KEY, &"9.9.9").await;
+    db.close().await;
+
+    let err = Db::open_file(&path).await.err().unwrap();
+    let OpenError::SchemaTooNew {
+        db_schema_version,
+        db_binary_version,
+        schema_version: supported,
+    } = &err
+    else {
+        panic!("unexpected error: {err}");
+    };
+    assert_eq!(*db_schema_version, future_version);
+    assert_eq!(db_binary_version, "9.9.9");
+    assert_eq!(*supported, schema_version());
+    let message = err.to_string();
+    assert!(!message.contains('\n'));
+    assert!(message.contains(&future_version.to_string()));
+    assert!(message.contains(BINARY_VERSION));
+    assert!(message.contains("restore the DB backup"));
+
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+}
+
+/// A DB file created by an older binary (migrated up to `version`)
+async fn create_fixture_db(path: &Path, version: i64) -> SqlitePool {
+    let options = SqliteConnectOptions::new()
+        .filename(path)
+        .create_if_missing(true);
+    let pool = SqlitePool::connect_with(options).await.unwrap();
+    let mut conn = pool.acquire().await.unwrap();
+    conn.ensure_migrations_table().await.unwrap();
+    for migration in MIGRATOR
+        .iter()
+        .filter(|migration| migration.version <= version)
+    {
+        conn.apply(migration).await.unwrap();
+    }
+    drop(conn);
+    pool
+}
+
+#[tokio::test]
+async fn monitored_txs_created_at_backfilled() {
+    let path = std::env::temp_dir().join(format!("manager_test_{}.sqlite", random_hash32()));
+    let txid = |byte| elements::Txid::from_byte_array([byte; 32]);
+    let order_id = random_hash32();
+
+    // Before `22_monitored_tx_created_at.sql`
+    let pool = create_fixture_db(&path, 21).await;
+    for (txid, description, user_note) in [
+        (txid(1), "swap", Some("invoice 1")),
+        (txid(2), "send", None),
+        (txid(3), "imported", Some("invoice 3")),
+    ] {
+        sqlx::query("insert into monitored_txs (txid, description, user_note) values (?, ?, ?)")
+            .bind(Text(txid))
+            .bind(description)
+            .bind(user_note)
+            .execute(&pool)
+            .await
+            .unwrap();
+    }
+    sqlx::query(
+        "insert into swaps (quote_id, txid, send_ticker, send_amount, recv_ticker, recv_amount, price, fee_ticker, fee_amount, server_fee, fixed_fee, created_at) values (1, ?, 'L-BTC', 0.001, 'USDt', 95, 95000, 'USDt', 0.1, 10000000, 0, 1000)",
+    )
+    .bind(Text(txid(1)))
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query(
+        "insert into created_txs (txid, tx, description, amounts, created_at) values (?, '', 'send', '{}', 2000)",
+    )
+    .bind(Text(txid(2)))
+    .execute(&pool)
+    .await
+    .unwrap();
+    sqlx::query("insert into pegs (order_id) values (?)")
+        .bind(Text(order_id))
+        .execute(&pool)
+        .await
+        .unwrap();
+    pool.close().await;
+
+    // The migration time is stored in seconds
+    let migrated_after = TimestampMs::now().millis() as i64 / 1000 * 1000;
+    let db = Db::open_file(&path).await.unwrap();
+    assert_eq!(
+        db.get_setting::<i64>(SCHEMA_VERSION_KEY).await,
+        Some(schema_version())
+    );
+
+    let mut txs = db.load_monitored_txs().await;
+    txs.sort_by_key(|tx| tx.txid.0);
+    let notes = txs
+        .iter()
+        .map(|tx| {
+            (
+                tx.txid.0,
+                tx.description.as_deref(),
+                tx.user_note.as_deref(),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        notes,
+        [
+            (txid(1), Some("swap"), Some("invoice 1")),
+            (txid(2), Some("send"), None),
+            (txid(3), Some("imported"), Some("invoice 3")),
+        ]
+    );
+    assert_eq!(txs[0].created_at, 1000);
+    assert_eq!(txs[1].created_at, 2000);
+    assert!(txs[2].created_at >= migrated_after);
+    assert!(txs[2].created_at <= TimestampMs::now().millis() as i64);
+
+    let pegs = db.load_pegs().await;
+    assert_eq!(pegs.len(), 1);
+    assert_eq!(pegs[0].order_id.0, order_id);
+    db.close().await;
+
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{suffix}", path.display()));
+    }
+}
+
+#[tokio::test]
+async fn env_change_refused() {
+    let path = std::env::temp_dir().join(format!("manager_test_{}.sqlite", random_hash32()));
+
+    let db = Db::open_file(&path).await.unwrap();
+    db.check_env(Env::Testnet).await.unwrap();
+    db.check_env(Env::Testnet).await.unwrap();
+    let err = db.check_env(Env::Prod).await.unwrap_err();
+    assert!(matches!(
+        &err,
+        OpenError::EnvMismatch { db_env, env: "prod" } if db_env == "testnet"
+    ));
+    assert!(err.to_string().contains("force_env_migration"));
+    db.close().await;
+
+    let backup = move_aside(&path, "bak").unwrap();
+    assert!(!path.exists());
+    let db = Db::open_file(&path).await.unwrap();
+    db.check_env(Env::Prod).await.unwrap();
+    db.close().await;
+
+    for file in [&path, &backup] {
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{suffix}", file.display()));
+        }
+    }
+}
+
+#[tokio::test]
+async fn env_checked_by_addresses() {
+    let db = create_test_db().await;
+    let address = elements::Address::from_str(
+        "tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn",
+    )
+    .unwrap();
+    db.add_address(models::Address {
+        wallet: "default".to_owned(),
+        ind: 0,
+        address: Text(address),
+        user_note: None,
+    })
+    .await;
+
+    let err = db.check_env(Env::Prod).await.unwrap_err();
+    assert!(matches!(
+        err,
+        OpenError::NetworkMismatch { env: "prod", .. }
+    ));
+    db.check_env(Env::Testnet).await.unwrap();
+    assert_eq!(
+        db.get_setting::<String>(ENV_KEY).await.as_deref(),
+        Some("testnet")
+    );
+}
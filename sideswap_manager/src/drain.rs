@@ -0,0 +1,50 @@
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::Notify;
+
+/// Grace period for draining started with SIGUSR2 (if `drain_grace_seconds` is not set)
+pub const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(300);
+
+/// Give the client tasks time to deliver the last responses before the process exits
+pub const FLUSH_PERIOD: Duration = Duration::from_secs(1);
+
+/// Upper bound of the shutdown after SIGTERM/SIGINT (if `shutdown_timeout_seconds` is not set)
+pub const DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Notified on SIGUSR2 (start draining)
+pub struct DrainSignal {
+    notify: Arc<Notify>,
+}
+
+impl Default for DrainSignal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DrainSignal {
+    pub fn new() -> DrainSignal {
+        let notify = Arc::new(Notify::new());
+
+        #[cfg(target_os = "linux")]
+        {
+            let notify_copy = Arc::clone(&notify);
+            tokio::spawn(async move {
+                let mut signal =
+                    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined2())
+                        .expect("must not fail");
+                loop {
+                    signal.recv().await;
+                    tracing::debug!("received drain signal");
+                    notify_copy.notify_one();
+                }
+            });
+        }
+
+        DrainSignal { notify }
+    }
+
+    pub async fn recv(&self) {
+        self.notify.notified().await;
+    }
+}
@@ -1,7 +1,7 @@
 use elements::AssetId;
 use sideswap_common::{
     b64,
-    dealer_ticker::{DealerTicker, InvalidTickerError},
+    dealer_ticker::{InvalidTickerError, TickerResolveError},
     ws::ws_req_sender,
 };
 use sideswap_types::asset_precision::AssetPrecision;
@@ -12,15 +12,17 @@ use crate::api;
 pub enum Error {
     #[error(transparent)]
     InvalidTicker(#[from] InvalidTickerError),
-    #[error("unknown ticker: {0}")]
-    UnknownTicker(DealerTicker),
+    #[error(transparent)]
+    UnknownTicker(#[from] TickerResolveError),
     #[error("channel closed, please report bug")]
     ChannelClosed,
     #[error("lwk error: {0}")]
     Lwk(#[from] sideswap_lwk::Error),
+    #[error("the wallet did not respond in {0} seconds, please retry (the operation may still complete)")]
+    WalletTimeout(u64),
     #[error("wS error: {0}")]
     WsError(#[from] ws_req_sender::Error),
-    #[error("invalid asset amount: {0} (asset_precison: {1})")]
+    #[error("invalid asset amount {0}: the asset precision is {1}, the amount can have at most {1} decimal places")]
     InvalidAssetAmount(f64, AssetPrecision),
     #[error("can't find market")]
     NoMarket,
@@ -32,6 +34,18 @@ pub enum Error {
         required: u64,
         available: u64,
     },
+    #[error("not enough {asset} for the quote, required: {required}, available: {available}")]
+    QuoteLowBalance {
+        asset: api::Ticker,
+        required: f64,
+        available: f64,
+    },
+    #[error("not enough {asset} to pay the second leg of the routed quote, required: {required}, available: {available}")]
+    RouteLowBalance {
+        asset: api::Ticker,
+        required: f64,
+        available: f64,
+    },
     #[error("quote error: {0}")]
     QuoteError(String),
     #[error("base64 error: {0}")]
@@ -40,18 +54,133 @@ pub enum Error {
     EncodeError(#[from] elements::encode::Error),
     #[error("PSET error: {0}")]
     PsetError(#[from] elements::pset::Error),
+    #[error("the PSET is too large: {size} base64 characters, the limit is {limit}")]
+    PsetTooLarge { size: usize, limit: usize },
     #[error("no UTXOs")]
     NoUtxos,
     #[error("quote expired")]
     QuoteExpired,
     #[error("no quote")]
     NoQuote,
+    #[error("the quote was returned before the server reconnect, please request a new one")]
+    QuoteInvalidatedByReconnect,
     #[error("no stored tx with this txid, please try again")]
     NoCreatedTx,
     #[error("UTXO check failed: {0}, please retry")]
     UtxoCheckFailed(String),
     #[error("gap limit reached")]
     GapLimit,
+    #[error("signing is locked, unlock is required")]
+    Locked,
+    #[error("wrong password")]
+    WrongPassword,
+    #[error("too many unlock attempts, retry in {} seconds", .0.as_secs_f64().ceil())]
+    UnlockBackoff(std::time::Duration),
+    #[error(
+        "asset {asset} is AMP restricted, the recipient address must be resolved with ResolveGaid"
+    )]
+    AmpAddressRequired { asset: api::Ticker },
+    #[error("can't resolve GAID {gaid}: {reason}")]
+    GaidResolveFailed { gaid: String, reason: String },
+    #[error("exactly one of receive_address and gaid must be set")]
+    InvalidQuoteReceiver,
+    #[error("exactly one of send_amount and recv_amount must be set")]
+    InvalidQuoteAmount,
+    #[error("the manager is draining, please retry with another instance")]
+    Draining,
+    #[error("invalid {expected_chain} address: {reason}")]
+    InvalidPegAddress {
+        expected_chain: &'static str,
+        reason: String,
+    },
+    #[error("the peg amount is below the server minimum of {min_amount}")]
+    PegAmountTooLow { min_amount: f64 },
+    #[error("no fee rate for {0} blocks, available targets: {1:?}")]
+    UnknownFeeTarget(i32, Vec<i32>),
+    #[error("unknown peg order")]
+    UnknownPeg,
+    #[error("unknown wallet {wallet:?}, available wallets: {}", .available.join(", "))]
+    UnknownWallet {
+        wallet: String,
+        available: Vec<String>,
+    },
+    #[error("address {0} is not on the allow-list")]
+    AddressNotAllowed(elements::Address),
+    #[error("invalid balance history request: {0}")]
+    InvalidHistoryRequest(&'static str),
+    #[error("invalid explain quote request: {0}")]
+    InvalidExplainRequest(&'static str),
+    #[error("invalid swaps request: {0}")]
+    InvalidSwapsRequest(&'static str),
+    #[error("the message is too long, the maximum length is {0} bytes")]
+    MessageTooLong(usize),
+    #[error("address {0} is not a wallet address returned by NewAddress")]
+    NotOwnAddress(String),
+    #[error("quota exceeded for {resource}, the limit is {limit}")]
+    QuotaExceeded {
+        resource: api::QuotaResource,
+        limit: u32,
+    },
+    #[error("invalid address of recipient {index}: {reason}")]
+    InvalidAddress { index: usize, reason: String },
+    #[error("recipients {index_a} and {index_b} have the same address and asset, set aggregate_duplicates to merge them")]
+    DuplicateRecipient { index_a: usize, index_b: usize },
+    #[error("send_all recipient for {0} must be the only recipient of this asset")]
+    SendAllNotAlone(api::Ticker),
+    #[error("the wallet has no {0} to send")]
+    NothingToSend(api::Ticker),
+    #[error(
+        "can't send {0} with the utxos list, the wallet supports coin selection only for L-BTC"
+    )]
+    UtxoSelectionNotSupported(api::Ticker),
+    #[error("the wallet selected {0} unconfirmed inputs, wait for the confirmations or set allow_unconfirmed")]
+    UnconfirmedInputs(usize),
+    #[error("invalid fee rate: {0} sats/vbyte, must be from 0.1 to 5.0")]
+    InvalidFeeRate(f64),
+    #[error("invalid quote timeout: {0} seconds, must be from 1 to 60")]
+    InvalidQuoteTimeout(u64),
+    #[error("invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("restart is required to change {}", .0.join(", "))]
+    RestartRequired(Vec<&'static str>),
+    #[error("transaction {0} is not monitored")]
+    UnknownMonitoredTx(elements::Txid),
+    #[error("transaction {0} is not confirmed yet, set force to remove it anyway")]
+    MonitoredTxUnconfirmed(elements::Txid),
+    #[error("invalid payment reference {0:?}, check for typos")]
+    InvalidReference(String),
+    #[error("unknown payment reference")]
+    UnknownReference,
+    #[error("the operation requires an approval, approval_id: {0}")]
+    ApprovalRequired(String),
+    #[error("unknown or expired approval")]
+    UnknownApproval,
+    #[error("the approver must log in with its own client token and not be the requester")]
+    ApproverNotAllowed,
+    #[error("the swap PSET does not match the quote: {reason}")]
+    PsetMismatch { reason: String },
+    #[error("maker orders are disabled, set maker_orders in the config")]
+    MakerOrdersDisabled,
+    #[error("unknown order {0}")]
+    UnknownOrder(api::OrdId),
+    #[error("the order sends more than the {asset} approval threshold of {threshold}, maker swaps are signed without an approval")]
+    OrderAboveApprovalThreshold { asset: api::Ticker, threshold: f64 },
+    #[error("invalid order request: {0}")]
+    InvalidOrderRequest(&'static str),
+    #[error("invalid idempotency key: {0}")]
+    InvalidIdempotencyKey(&'static str),
+    #[error("idempotency key {0} was used for another request")]
+    IdempotencyKeyReused(String),
+    #[error("the request with idempotency key {0} was interrupted and may have completed, check its result instead of retrying")]
+    IdempotencyKeyInProgress(String),
+    #[error("the stored idempotent response is invalid: {0}")]
+    InvalidStoredResponse(String),
+    #[error("invalid recipient {index}: {reason}")]
+    InvalidRecipient { index: usize, reason: String },
+    #[error("invalid txid {0:?} in the imported notes")]
+    InvalidImportedTxid(String),
+    #[error("the manager is shutting down, please retry with another instance")]
+    ShuttingDown,
 }
 
 impl From<tokio::sync::oneshot::error::RecvError> for Error {
@@ -75,33 +204,281 @@ impl<T> From<std::sync::mpsc::SendError<T>> for Error {
 impl Error {
     pub fn error_code(&self) -> api::ErrorCode {
         match self {
-            Error::InvalidTicker(_)
-            | Error::UnknownTicker(_)
-            | Error::Lwk(_)
-            | Error::InvalidAssetAmount(_, _)
-            | Error::NoMarket
-            | Error::NotEnoughAmount { .. }
-            | Error::QuoteError(_)
-            | Error::Base64(_)
-            | Error::EncodeError(_)
-            | Error::PsetError(_)
-            | Error::QuoteExpired
-            | Error::NoQuote
-            | Error::NoCreatedTx
-            | Error::GapLimit => api::ErrorCode::InvalidRequest,
+            Error::InvalidTicker(_) => api::ErrorCode::InvalidTicker,
+            Error::UnknownTicker(TickerResolveError::Unknown { .. }) => {
+                api::ErrorCode::UnknownTicker
+            }
+            Error::UnknownTicker(TickerResolveError::Ambiguous { .. }) => {
+                api::ErrorCode::AmbiguousTicker
+            }
+            Error::Lwk(_) => api::ErrorCode::WalletError,
+            Error::WalletTimeout(_) => api::ErrorCode::WalletTimeout,
+            Error::InvalidAssetAmount(_, _) => api::ErrorCode::InvalidAssetAmount,
+            Error::NoMarket => api::ErrorCode::NoMarket,
+            Error::NotEnoughAmount { .. } => api::ErrorCode::NotEnoughAmount,
+            Error::QuoteError(_) => api::ErrorCode::QuoteFailed,
+            Error::Base64(_) => api::ErrorCode::InvalidBase64,
+            Error::EncodeError(_) => api::ErrorCode::InvalidEncoding,
+            Error::PsetError(_) | Error::PsetTooLarge { .. } => api::ErrorCode::InvalidPset,
+            Error::NoUtxos => api::ErrorCode::NoUtxos,
+            Error::QuoteExpired => api::ErrorCode::QuoteExpired,
+            Error::NoQuote => api::ErrorCode::UnknownQuote,
+            Error::QuoteInvalidatedByReconnect => api::ErrorCode::QuoteInvalidatedByReconnect,
+            Error::NoCreatedTx => api::ErrorCode::UnknownCreatedTx,
+            Error::GapLimit => api::ErrorCode::GapLimit,
+            Error::WrongPassword => api::ErrorCode::WrongPassword,
+            Error::UnlockBackoff(_) => api::ErrorCode::UnlockBackoff,
+            Error::AmpAddressRequired { .. } => api::ErrorCode::AmpAddressRequired,
+            Error::GaidResolveFailed { .. } => api::ErrorCode::GaidResolveFailed,
+            Error::InvalidQuoteReceiver => api::ErrorCode::InvalidQuoteReceiver,
+            Error::InvalidPegAddress { .. } => api::ErrorCode::InvalidPegAddress,
+            Error::PegAmountTooLow { .. } => api::ErrorCode::PegAmountTooLow,
+            Error::UnknownFeeTarget(_, _) => api::ErrorCode::UnknownFeeTarget,
+            Error::UnknownPeg => api::ErrorCode::UnknownPeg,
+            Error::UnknownWallet { .. } => api::ErrorCode::UnknownWallet,
+            Error::AddressNotAllowed(_) => api::ErrorCode::AddressNotAllowed,
+            Error::MessageTooLong(_) => api::ErrorCode::MessageTooLong,
+            Error::NotOwnAddress(_) => api::ErrorCode::NotOwnAddress,
+            Error::InvalidAddress { .. } => api::ErrorCode::InvalidAddress,
+            Error::DuplicateRecipient { .. } => api::ErrorCode::DuplicateRecipient,
+            Error::SendAllNotAlone(_) => api::ErrorCode::SendAllNotAlone,
+            Error::NothingToSend(_) => api::ErrorCode::NothingToSend,
+            Error::UtxoSelectionNotSupported(_) => api::ErrorCode::UtxoSelectionNotSupported,
+            Error::UnconfirmedInputs(_) => api::ErrorCode::UnconfirmedInputs,
+            Error::InvalidFeeRate(_) => api::ErrorCode::InvalidFeeRate,
+            Error::InvalidQuoteTimeout(_) => api::ErrorCode::InvalidQuoteTimeout,
+            Error::InvalidConfig(_) => api::ErrorCode::InvalidConfig,
+            Error::RestartRequired(_) => api::ErrorCode::RestartRequired,
+            Error::UnknownMonitoredTx(_) => api::ErrorCode::UnknownMonitoredTx,
+            Error::MonitoredTxUnconfirmed(_) => api::ErrorCode::MonitoredTxUnconfirmed,
+            Error::InvalidReference(_) => api::ErrorCode::InvalidReference,
+            Error::UnknownReference => api::ErrorCode::UnknownReference,
+            Error::UnknownApproval => api::ErrorCode::UnknownApproval,
+            Error::ApproverNotAllowed => api::ErrorCode::ApproverNotAllowed,
+            Error::PsetMismatch { .. } => api::ErrorCode::PsetMismatch,
+            Error::MakerOrdersDisabled => api::ErrorCode::MakerOrdersDisabled,
+            Error::UnknownOrder(_) => api::ErrorCode::UnknownOrder,
+            Error::OrderAboveApprovalThreshold { .. } => {
+                api::ErrorCode::OrderAboveApprovalThreshold
+            }
+            Error::IdempotencyKeyReused(_) => api::ErrorCode::IdempotencyKeyReused,
+            Error::IdempotencyKeyInProgress(_) => api::ErrorCode::IdempotencyKeyInProgress,
+
+            Error::InvalidQuoteAmount
+            | Error::InvalidHistoryRequest(_)
+            | Error::InvalidExplainRequest(_)
+            | Error::InvalidSwapsRequest(_)
+            | Error::InvalidOrderRequest(_)
+            | Error::InvalidIdempotencyKey(_)
+            | Error::InvalidRecipient { .. }
+            | Error::InvalidImportedTxid(_) => api::ErrorCode::InvalidRequest,
+
+            Error::Locked => api::ErrorCode::Locked,
 
-            Error::ChannelClosed | Error::NoUtxos => api::ErrorCode::ServerError,
+            Error::Draining | Error::ShuttingDown => api::ErrorCode::Draining,
+
+            Error::QuotaExceeded { .. } => api::ErrorCode::QuotaExceeded,
+
+            Error::ApprovalRequired(_) => api::ErrorCode::ApprovalRequired,
+
+            Error::ChannelClosed | Error::InvalidStoredResponse(_) => api::ErrorCode::ServerError,
 
             Error::WsError(error) => match error {
                 ws_req_sender::Error::Disconnected => api::ErrorCode::NetworkError,
                 ws_req_sender::Error::BackendError(_, _error_code) => api::ErrorCode::ServerError,
-                ws_req_sender::Error::Timeout(_elapsed) => api::ErrorCode::ServerError,
+                ws_req_sender::Error::Timeout(_elapsed) => api::ErrorCode::ServerTimeout,
                 ws_req_sender::Error::UnexpectedResponse => api::ErrorCode::ServerError,
+                ws_req_sender::Error::RequestTooLarge { .. } => api::ErrorCode::RequestTooLarge,
             },
 
             Error::UtxoCheckFailed(_) => api::ErrorCode::UtxoCheckFailed,
+
+            Error::QuoteLowBalance { .. } => api::ErrorCode::QuoteLowBalance,
+            Error::RouteLowBalance { .. } => api::ErrorCode::RouteLowBalance,
         }
     }
+
+    pub fn details(&self) -> Option<api::ErrorDetails> {
+        let details = match self {
+            Error::UnknownTicker(TickerResolveError::Unknown {
+                ticker,
+                suggestions,
+                supported: _,
+            }) => api::ErrorDetails::UnknownTicker {
+                ticker: ticker.clone(),
+                suggestions: suggestions.clone(),
+            },
+            Error::UnknownTicker(TickerResolveError::Ambiguous { ticker, candidates }) => {
+                api::ErrorDetails::AmbiguousTicker {
+                    ticker: ticker.clone(),
+                    candidates: candidates.clone(),
+                }
+            }
+            Error::InvalidAssetAmount(amount, precision) => api::ErrorDetails::InvalidAssetAmount {
+                amount: *amount,
+                precision: precision.value(),
+            },
+            Error::NotEnoughAmount {
+                asset_id,
+                required,
+                available,
+            } => api::ErrorDetails::NotEnoughAmount {
+                asset_id: *asset_id,
+                required: *required,
+                available: *available,
+            },
+            Error::QuoteLowBalance {
+                asset: _,
+                required,
+                available,
+            } => api::ErrorDetails::QuoteLowBalance {
+                available: *available,
+                required: *required,
+            },
+            Error::UnlockBackoff(duration) => api::ErrorDetails::UnlockBackoff {
+                retry_in_seconds: duration.as_secs_f64().ceil() as u64,
+            },
+            Error::AmpAddressRequired { asset } => {
+                api::ErrorDetails::AmpAddressRequired { asset: *asset }
+            }
+            Error::GaidResolveFailed { gaid, reason: _ } => {
+                api::ErrorDetails::GaidResolveFailed { gaid: gaid.clone() }
+            }
+            Error::InvalidPegAddress {
+                expected_chain,
+                reason: _,
+            } => api::ErrorDetails::InvalidPegAddress {
+                expected_chain: expected_chain.to_string(),
+            },
+            Error::PegAmountTooLow { min_amount } => api::ErrorDetails::PegAmountTooLow {
+                min_amount: *min_amount,
+            },
+            Error::UnknownFeeTarget(target, available) => api::ErrorDetails::UnknownFeeTarget {
+                target: *target,
+                available: available.clone(),
+            },
+            Error::UnknownWallet { wallet, available } => api::ErrorDetails::UnknownWallet {
+                wallet: wallet.clone(),
+                available: available.clone(),
+            },
+            Error::AddressNotAllowed(address) => api::ErrorDetails::AddressNotAllowed {
+                address: address.to_string(),
+            },
+            Error::MessageTooLong(max_length) => api::ErrorDetails::MessageTooLong {
+                max_length: *max_length,
+            },
+            Error::NotOwnAddress(address) => api::ErrorDetails::NotOwnAddress {
+                address: address.clone(),
+            },
+            Error::QuotaExceeded { resource, limit } => api::ErrorDetails::QuotaExceeded {
+                resource: *resource,
+                limit: *limit,
+            },
+            Error::InvalidAddress { index, reason: _ } => {
+                api::ErrorDetails::InvalidAddress { index: *index }
+            }
+            Error::DuplicateRecipient { index_a, index_b } => {
+                api::ErrorDetails::DuplicateRecipient {
+                    index_a: *index_a,
+                    index_b: *index_b,
+                }
+            }
+            Error::SendAllNotAlone(asset) => api::ErrorDetails::SendAllNotAlone { asset: *asset },
+            Error::NothingToSend(asset) => api::ErrorDetails::NothingToSend { asset: *asset },
+            Error::UtxoSelectionNotSupported(asset) => {
+                api::ErrorDetails::UtxoSelectionNotSupported { asset: *asset }
+            }
+            Error::UnconfirmedInputs(count) => {
+                api::ErrorDetails::UnconfirmedInputs { count: *count }
+            }
+            Error::InvalidFeeRate(fee_rate) => api::ErrorDetails::InvalidFeeRate {
+                fee_rate: *fee_rate,
+            },
+            Error::InvalidQuoteTimeout(timeout_secs) => api::ErrorDetails::InvalidQuoteTimeout {
+                timeout_secs: *timeout_secs,
+            },
+            Error::RestartRequired(fields) => api::ErrorDetails::RestartRequired {
+                fields: fields.iter().map(|field| field.to_string()).collect(),
+            },
+            Error::UnknownMonitoredTx(txid) => {
+                api::ErrorDetails::UnknownMonitoredTx { txid: *txid }
+            }
+            Error::MonitoredTxUnconfirmed(txid) => {
+                api::ErrorDetails::MonitoredTxUnconfirmed { txid: *txid }
+            }
+            Error::InvalidReference(reference) => api::ErrorDetails::InvalidReference {
+                reference: reference.clone(),
+            },
+            Error::ApprovalRequired(approval_id) => api::ErrorDetails::ApprovalRequired {
+                approval_id: approval_id.clone(),
+            },
+            Error::RouteLowBalance {
+                asset,
+                required,
+                available,
+            } => api::ErrorDetails::RouteLowBalance {
+                asset: *asset,
+                required: *required,
+                available: *available,
+            },
+            Error::OrderAboveApprovalThreshold { asset, threshold } => {
+                api::ErrorDetails::OrderAboveApprovalThreshold {
+                    asset: *asset,
+                    threshold: *threshold,
+                }
+            }
+            Error::WsError(ws_req_sender::Error::RequestTooLarge { size, limit }) => {
+                api::ErrorDetails::RequestTooLarge {
+                    size: *size,
+                    limit: *limit,
+                }
+            }
+
+            Error::InvalidTicker(_)
+            | Error::ChannelClosed
+            | Error::Lwk(_)
+            | Error::WalletTimeout(_)
+            | Error::WsError(_)
+            | Error::NoMarket
+            | Error::QuoteError(_)
+            | Error::Base64(_)
+            | Error::EncodeError(_)
+            | Error::PsetError(_)
+            | Error::PsetTooLarge { .. }
+            | Error::NoUtxos
+            | Error::QuoteExpired
+            | Error::NoQuote
+            | Error::QuoteInvalidatedByReconnect
+            | Error::NoCreatedTx
+            | Error::UtxoCheckFailed(_)
+            | Error::GapLimit
+            | Error::Locked
+            | Error::WrongPassword
+            | Error::InvalidQuoteReceiver
+            | Error::InvalidQuoteAmount
+            | Error::Draining
+            | Error::UnknownPeg
+            | Error::InvalidHistoryRequest(_)
+            | Error::InvalidExplainRequest(_)
+            | Error::InvalidSwapsRequest(_)
+            | Error::InvalidConfig(_)
+            | Error::UnknownReference
+            | Error::UnknownApproval
+            | Error::ApproverNotAllowed
+            | Error::PsetMismatch { .. }
+            | Error::MakerOrdersDisabled
+            | Error::UnknownOrder(_)
+            | Error::InvalidOrderRequest(_)
+            | Error::InvalidIdempotencyKey(_)
+            | Error::IdempotencyKeyReused(_)
+            | Error::IdempotencyKeyInProgress(_)
+            | Error::InvalidStoredResponse(_)
+            | Error::InvalidRecipient { .. }
+            | Error::InvalidImportedTxid(_)
+            | Error::ShuttingDown => return None,
+        };
+        Some(details)
+    }
 }
 
 impl From<Error> for api::Error {
@@ -109,7 +486,7 @@ impl From<Error> for api::Error {
         api::Error {
             text: val.to_string(),
             code: val.error_code(),
-            details: None,
+            details: val.details(),
         }
     }
 }
@@ -0,0 +1,261 @@
+use std::{net::SocketAddr, time::Duration};
+
+use axum::{
+    body::Bytes,
+    extract::{Path, RawQuery, State},
+    http::{header, HeaderMap, StatusCode},
+    routing::post,
+    Json,
+};
+use sideswap_common::ws::ws_req_sender;
+use tokio::{
+    net::TcpListener,
+    sync::{
+        mpsc::{unbounded_channel, UnboundedSender},
+        watch,
+    },
+};
+
+use crate::{
+    api,
+    error::Error,
+    quotas,
+    worker::{self, Command},
+    ws_server::{self, Auth, Login},
+};
+
+/// Above the longest `GetQuote` deadline, so slow quotes fail with the worker error and not with the timeout
+const REQUEST_TIMEOUT: Duration = worker::MAX_QUOTE_DEADLINE.saturating_add(Duration::from_secs(5));
+
+#[derive(Clone)]
+struct ServerState {
+    command_sender: UnboundedSender<Command>,
+    /// The `[ws_server]` login tokens, sent as `Authorization: Bearer <token>`
+    auth: Auth,
+}
+
+type HttpResp = (StatusCode, Json<serde_json::Value>);
+
+fn error_resp(err: api::Error) -> HttpResp {
+    let status = status_code(&err.code);
+    let body = serde_json::to_value(err).expect("must not fail");
+    (status, Json(body))
+}
+
+fn invalid_request(text: String) -> HttpResp {
+    error_resp(api::Error {
+        code: api::ErrorCode::InvalidRequest,
+        text,
+        details: None,
+    })
+}
+
+fn status_code(code: &api::ErrorCode) -> StatusCode {
+    use api::ErrorCode as C;
+    match code {
+        C::NetworkError | C::ServerError | C::QuoteFailed | C::PsetMismatch => {
+            StatusCode::BAD_GATEWAY
+        }
+        C::ServerTimeout | C::WalletTimeout => StatusCode::GATEWAY_TIMEOUT,
+        C::WalletError => StatusCode::INTERNAL_SERVER_ERROR,
+        C::Draining => StatusCode::SERVICE_UNAVAILABLE,
+        C::Unauthorized => StatusCode::UNAUTHORIZED,
+        C::ApprovalRequired | C::ApproverNotAllowed | C::OrderAboveApprovalThreshold => {
+            StatusCode::FORBIDDEN
+        }
+        C::UnknownQuote
+        | C::UnknownCreatedTx
+        | C::UnknownPeg
+        | C::UnknownWallet
+        | C::UnknownMonitoredTx
+        | C::UnknownReference
+        | C::UnknownApproval
+        | C::UnknownOrder => StatusCode::NOT_FOUND,
+        C::UtxoCheckFailed | C::IdempotencyKeyReused | C::IdempotencyKeyInProgress => {
+            StatusCode::CONFLICT
+        }
+        C::Locked => StatusCode::LOCKED,
+        C::QuotaExceeded | C::UnlockBackoff | C::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+        C::InvalidRequest
+        | C::RequestTooLarge
+        | C::InvalidTicker
+        | C::UnknownTicker
+        | C::AmbiguousTicker
+        | C::InvalidAssetAmount
+        | C::NoMarket
+        | C::NotEnoughAmount
+        | C::InvalidBase64
+        | C::InvalidEncoding
+        | C::InvalidPset
+        | C::NoUtxos
+        | C::QuoteExpired
+        | C::QuoteInvalidatedByReconnect
+        | C::GapLimit
+        | C::WrongPassword
+        | C::AmpAddressRequired
+        | C::GaidResolveFailed
+        | C::InvalidQuoteReceiver
+        | C::InvalidPegAddress
+        | C::PegAmountTooLow
+        | C::UnknownFeeTarget
+        | C::AddressNotAllowed
+        | C::MessageTooLong
+        | C::NotOwnAddress
+        | C::InvalidAddress
+        | C::DuplicateRecipient
+        | C::SendAllNotAlone
+        | C::NothingToSend
+        | C::UtxoSelectionNotSupported
+        | C::UnconfirmedInputs
+        | C::InvalidFeeRate
+        | C::InvalidQuoteTimeout
+        | C::InvalidConfig
+        | C::RestartRequired
+        | C::MonitoredTxUnconfirmed
+        | C::InvalidReference
+        | C::QuoteLowBalance
+        | C::RouteLowBalance
+        | C::MakerOrdersDisabled => StatusCode::BAD_REQUEST,
+    }
+}
+
+fn login(state: &ServerState, headers: &HeaderMap) -> Option<Login> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) => state.auth.check(token),
+        None => (!state.auth.required()).then_some(Login::Shared),
+    }
+}
+
+/// The body is the request object (an empty body is the same as `{}`), the name is the `Req` variant
+fn parse_req(name: &str, body: &[u8]) -> Result<api::Req, String> {
+    let body = if body.iter().all(u8::is_ascii_whitespace) {
+        serde_json::Value::Object(Default::default())
+    } else {
+        serde_json::from_slice(body).map_err(|err| format!("invalid JSON: {err}"))?
+    };
+    let req = serde_json::from_value(serde_json::json!({ name: body }))
+        .map_err(|err| format!("invalid request: {err}"))?;
+    match req {
+        api::Req::SubscribePrice(_) | api::Req::UnsubscribePrice(_) => {
+            Err("price subscriptions are only available over WS".to_owned())
+        }
+        req => Ok(req),
+    }
+}
+
+/// Every HTTP request is a separate short-lived client (without notifications)
+async fn process_http_req(
+    state: ServerState,
+    (client_name, client_token): (Option<String>, bool),
+    req: api::Req,
+) -> Result<api::Resp, Error> {
+    let client_id = ws_server::next_client_id();
+    let (notif_sender, _notif_receiver) = unbounded_channel();
+    state.command_sender.send(Command::ClientConnected {
+        client_id,
+        client_name,
+        client_token,
+        notif_sender: notif_sender.into(),
+    })?;
+
+    let res = tokio::time::timeout(
+        REQUEST_TIMEOUT,
+        ws_server::process_req(state.command_sender.clone(), client_id, req),
+    )
+    .await
+    .unwrap_or_else(|elapsed| Err(ws_req_sender::Error::from(elapsed).into()));
+
+    let _ = state
+        .command_sender
+        .send(Command::ClientDisconnected { client_id });
+
+    res
+}
+
+async fn handle_req(
+    State(state): State<ServerState>,
+    Path(name): Path<String>,
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+    body: Bytes,
+) -> HttpResp {
+    let Some(login) = login(&state, &headers) else {
+        return error_resp(api::Error {
+            code: api::ErrorCode::Unauthorized,
+            text: "missing or wrong bearer token".to_owned(),
+            details: None,
+        });
+    };
+
+    let client_name = match quotas::client_name_from_query(query.as_deref()) {
+        Ok(client_name) => client_name,
+        Err(err) => return invalid_request(err.to_string()),
+    };
+    // The client tokens identify the client, the query name is ignored then
+    let client = match login {
+        Login::Shared => (client_name, false),
+        Login::Client(name) => (Some(name), true),
+    };
+
+    let req = match parse_req(&name, &body) {
+        Ok(req) => req,
+        Err(text) => return invalid_request(text),
+    };
+
+    match process_http_req(state, client, req).await {
+        Ok(resp) => (
+            StatusCode::OK,
+            Json(serde_json::to_value(resp).expect("must not fail")),
+        ),
+        Err(err) => error_resp(err.into()),
+    }
+}
+
+async fn run(
+    listener: TcpListener,
+    command_sender: UnboundedSender<Command>,
+    auth: Auth,
+    mut drain_receiver: watch::Receiver<bool>,
+) {
+    let state = ServerState {
+        command_sender,
+        auth,
+    };
+    let app = axum::Router::new()
+        .route("/:name", post(handle_req))
+        .with_state(state);
+
+    let res = axum::serve(listener, app)
+        .with_graceful_shutdown(async move {
+            let _ = drain_receiver.wait_for(|draining| *draining).await;
+            tracing::info!("draining, stop accepting new HTTP requests");
+        })
+        .await;
+    if let Err(err) = res {
+        tracing::error!("HTTP server failed: {err}");
+    }
+}
+
+/// Requests are sent as `POST /<Req variant>` with the request JSON in the body.
+/// The listening socket is closed once `drain_receiver` is set to true (the started requests are still answered).
+pub fn start(
+    listen_on: SocketAddr,
+    auth: Auth,
+    command_sender: UnboundedSender<Command>,
+    drain_receiver: watch::Receiver<bool>,
+) {
+    tokio::task::spawn(async move {
+        tracing::info!("start HTTP server on {listen_on}...");
+        let listener = TcpListener::bind(&listen_on)
+            .await
+            .expect("port must be open");
+        run(listener, command_sender, auth, drain_receiver).await;
+    });
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,231 @@
+use std::collections::BTreeMap;
+
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use super::*;
+
+/// The server is stopped when the returned drain sender is set to true (or dropped)
+async fn start_server(
+    auth_token: Option<&str>,
+) -> (SocketAddr, UnboundedReceiver<Command>, watch::Sender<bool>) {
+    let auth = Auth {
+        auth_token: auth_token.map(str::to_owned),
+        client_tokens: BTreeMap::new(),
+    };
+    start_server_with_auth(auth).await
+}
+
+async fn start_server_with_auth(
+    auth: Auth,
+) -> (SocketAddr, UnboundedReceiver<Command>, watch::Sender<bool>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let (command_sender, command_receiver) = unbounded_channel();
+    let (drain_sender, drain_receiver) = watch::channel(false);
+    tokio::spawn(run(listener, command_sender, auth, drain_receiver));
+    (address, command_receiver, drain_sender)
+}
+
+/// Returns the HTTP status and the JSON body
+async fn post(
+    url: String,
+    token: Option<&'static str>,
+    body: &'static str,
+) -> (u16, serde_json::Value) {
+    tokio::task::spawn_blocking(move || {
+        let mut req = ureq::post(&url);
+        if let Some(token) = token {
+            req = req.set("Authorization", &format!("Bearer {token}"));
+        }
+        let resp = match req.send_string(body) {
+            Ok(resp) | Err(ureq::Error::Status(_, resp)) => resp,
+            Err(err) => panic!("HTTP request failed: {err}"),
+        };
+        let status = resp.status();
+        (
+            status,
+            serde_json::from_str(&resp.into_string().unwrap()).unwrap(),
+        )
+    })
+    .await
+    .unwrap()
+}
+
+/// Sends the request and answers it with `res` as the worker
+async fn call(
+    address: SocketAddr,
+    command_receiver: &mut UnboundedReceiver<Command>,
+    path: &'static str,
+    body: &'static str,
+    res: Result<api::Resp, Error>,
+) -> (u16, serde_json::Value) {
+    let resp = tokio::spawn(post(format!("http://{address}{path}"), None, body));
+
+    assert!(matches!(
+        command_receiver.recv().await,
+        Some(Command::ClientConnected { .. })
+    ));
+    let client_id = match command_receiver.recv().await {
+        Some(Command::Request {
+            client_id,
+            req: _,
+            res_sender,
+        }) => {
+            res_sender.send(res);
+            client_id
+        }
+        _ => panic!("unexpected command"),
+    };
+    assert!(matches!(
+        command_receiver.recv().await,
+        Some(Command::ClientDisconnected { client_id: id }) if id == client_id
+    ));
+
+    resp.await.unwrap()
+}
+
+#[tokio::test]
+async fn requests_forwarded_to_worker() {
+    let (address, mut command_receiver, _drain_sender) = start_server(None).await;
+
+    // The client name is taken from the query, the empty body is the same as `{}`
+    let resp = tokio::spawn(post(
+        format!("http://{address}/NewAddress?client_name=tools"),
+        None,
+        "",
+    ));
+    match command_receiver.recv().await {
+        Some(Command::ClientConnected { client_name, .. }) => {
+            assert_eq!(client_name.as_deref(), Some("tools"));
+        }
+        _ => panic!("unexpected command"),
+    }
+    match command_receiver.recv().await {
+        Some(Command::Request {
+            req: api::Req::NewAddress(req),
+            res_sender,
+            ..
+        }) => {
+            assert_eq!(req.user_note, None);
+            res_sender.send(Err(Error::GapLimit));
+        }
+        _ => panic!("unexpected command"),
+    }
+    assert!(matches!(
+        command_receiver.recv().await,
+        Some(Command::ClientDisconnected { .. })
+    ));
+    let (status, body) = resp.await.unwrap();
+    assert_eq!(status, 400);
+    assert_eq!(body["code"], "GapLimit");
+
+    let (status, body) = call(
+        address,
+        &mut command_receiver,
+        "/ListAssets",
+        "{}",
+        Ok(api::Resp::ListAssets(api::ListAssetsResp {
+            assets: Vec::new(),
+        })),
+    )
+    .await;
+    assert_eq!(status, 200);
+    assert!(body["ListAssets"]["assets"].is_array());
+
+    let (status, body) = call(
+        address,
+        &mut command_receiver,
+        "/GetMonitoredTxs",
+        "{}",
+        Err(Error::WsError(ws_req_sender::Error::Disconnected)),
+    )
+    .await;
+    assert_eq!(status, 502);
+    assert_eq!(body["code"], "NetworkError");
+
+    let (status, body) = call(
+        address,
+        &mut command_receiver,
+        "/Approve",
+        r#"{"approval_id":"4f1c2b7e9a0d3e6f"}"#,
+        Err(Error::UnknownApproval),
+    )
+    .await;
+    assert_eq!(status, 404);
+    assert_eq!(body["code"], "UnknownApproval");
+
+    // Rejected without reaching the worker
+    for (path, body) in [
+        ("/Unknown", "{}"),
+        ("/ListAssets", "{"),
+        ("/ListAssets?client_name=a%20b", "{}"),
+        ("/SubscribePrice", r#"{"base":"L-BTC","quote":"USDt"}"#),
+    ] {
+        let (status, body) = post(format!("http://{address}{path}"), None, body).await;
+        assert_eq!(status, 400, "{path}");
+        assert_eq!(body["code"], "InvalidRequest", "{path}");
+    }
+    assert!(command_receiver.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn bearer_token_required_if_configured() {
+    let (address, mut command_receiver, _drain_sender) = start_server(Some("secret")).await;
+    let url = format!("http://{address}/ListAssets");
+
+    for token in [None, Some("wrong")] {
+        let (status, body) = post(url.clone(), token, "{}").await;
+        assert_eq!(status, 401);
+        assert_eq!(body["code"], "Unauthorized");
+    }
+    assert!(command_receiver.try_recv().is_err());
+
+    let resp = tokio::spawn(post(url, Some("secret"), "{}"));
+    assert!(matches!(
+        command_receiver.recv().await,
+        Some(Command::ClientConnected { .. })
+    ));
+    match command_receiver.recv().await {
+        Some(Command::Request { res_sender, .. }) => res_sender.send(Err(Error::Draining)),
+        _ => panic!("unexpected command"),
+    }
+    let (status, body) = resp.await.unwrap();
+    assert_eq!(status, 503);
+    assert_eq!(body["code"], "Draining");
+}
+
+#[tokio::test]
+async fn client_token_overrides_client_name() {
+    let auth = Auth {
+        auth_token: Some("secret".to_owned()),
+        client_tokens: BTreeMap::from([("bob".to_owned(), "bob-secret".to_owned())]),
+    };
+    let (address, mut command_receiver, _drain_sender) = start_server_with_auth(auth).await;
+    let url = format!("http://{address}/ListAssets?client_name=alice");
+
+    for (token, expected, client_token) in [("secret", "alice", false), ("bob-secret", "bob", true)]
+    {
+        let resp = tokio::spawn(post(url.clone(), Some(token), "{}"));
+        match command_receiver.recv().await {
+            Some(Command::ClientConnected {
+                client_name,
+                client_token: token_used,
+                ..
+            }) => {
+                assert_eq!(client_name.as_deref(), Some(expected));
+                assert_eq!(token_used, client_token);
+            }
+            _ => panic!("unexpected command"),
+        }
+        match command_receiver.recv().await {
+            Some(Command::Request { res_sender, .. }) => res_sender.send(Err(Error::Draining)),
+            _ => panic!("unexpected command"),
+        }
+        let (status, _body) = resp.await.unwrap();
+        assert_eq!(status, 503);
+        assert!(matches!(
+            command_receiver.recv().await,
+            Some(Command::ClientDisconnected { .. })
+        ));
+    }
+}
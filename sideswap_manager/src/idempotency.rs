@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use sideswap_api::mkt::QuoteId;
+use sideswap_common::verify;
+
+use crate::{error::Error, models};
+
+/// Used if `idempotency_key_retention_seconds` is not set
+pub const DEFAULT_RETENTION: Duration = Duration::from_secs(86400);
+
+/// The longest accepted key
+pub const MAX_KEY_LEN: usize = 128;
+
+/// The request a key is used for (stored in the DB), a replay must be for the same request
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Request {
+    SendTx { txid: elements::Txid },
+    AcceptQuote { quote_id: QuoteId },
+}
+
+impl Request {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Request::SendTx { .. } => "SendTx",
+            Request::AcceptQuote { .. } => "AcceptQuote",
+        }
+    }
+}
+
+/// Identifies a stored key, the same key of different clients, wallets or request kinds is independent
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyId {
+    /// The client name, empty for anonymous clients
+    pub client: String,
+    /// Empty for `AcceptQuote` (the quote selects the wallet)
+    pub wallet: String,
+    pub kind: &'static str,
+    pub key: String,
+}
+
+impl KeyId {
+    pub fn new(client: Option<String>, wallet: &str, key: String, request: &Request) -> KeyId {
+        KeyId {
+            client: client.unwrap_or_default(),
+            wallet: wallet.to_owned(),
+            kind: request.kind(),
+            key,
+        }
+    }
+}
+
+pub fn validate_key(key: &str) -> Result<(), Error> {
+    verify!(
+        !key.is_empty() && key.len() <= MAX_KEY_LEN,
+        Error::InvalidIdempotencyKey("the key must be from 1 to 128 bytes")
+    );
+    Ok(())
+}
+
+/// Returns the stored response (JSON) if the request is a replay, None if the request must be processed (the key is new or expired).
+/// A key without a stored response fails with `IdempotencyKeyInProgress`: the manager was restarted while processing it,
+/// and the request may have completed (failed requests release their keys).
+pub fn check(
+    stored: Option<&models::IdempotencyKey>,
+    request: &Request,
+    now: i64,
+    retention: Duration,
+) -> Result<Option<String>, Error> {
+    let stored = match stored {
+        Some(stored) if now - stored.created_at < retention.as_millis() as i64 => stored,
+        _ => return Ok(None),
+    };
+    verify!(
+        stored.request.0 == *request,
+        Error::IdempotencyKeyReused(stored.key.clone())
+    );
+    match &stored.response {
+        Some(response) => Ok(Some(response.clone())),
+        None => Err(Error::IdempotencyKeyInProgress(stored.key.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,71 @@
+use elements::hashes::Hash;
+use sqlx::types::Json;
+
+use super::*;
+
+fn txid(byte: u8) -> elements::Txid {
+    elements::Txid::from_byte_array([byte; 32])
+}
+
+fn stored(request: Request, response: Option<&str>) -> models::IdempotencyKey {
+    models::IdempotencyKey {
+        client: String::new(),
+        wallet: "default".to_owned(),
+        kind: request.kind().to_owned(),
+        key: "payout-42".to_owned(),
+        request: Json(request),
+        response: response.map(str::to_owned),
+        created_at: 1000,
+    }
+}
+
+#[test]
+fn replays_checked() {
+    let retention = Duration::from_secs(10);
+    let request = Request::SendTx { txid: txid(1) };
+
+    assert_eq!(check(None, &request, 2000, retention).unwrap(), None);
+
+    let done = stored(request.clone(), Some("{}"));
+    assert_eq!(
+        check(Some(&done), &request, 2000, retention).unwrap(),
+        Some("{}".to_owned())
+    );
+    // Expired
+    assert_eq!(
+        check(Some(&done), &request, 11000, retention).unwrap(),
+        None
+    );
+
+    // Not finished, the request might have been executed before a restart
+    let reserved = stored(request.clone(), None);
+    assert!(matches!(
+        check(Some(&reserved), &request, 2000, retention),
+        Err(Error::IdempotencyKeyInProgress(key)) if key == "payout-42"
+    ));
+    // Expired reservations are released
+    assert_eq!(
+        check(Some(&reserved), &request, 11000, retention).unwrap(),
+        None
+    );
+
+    for other in [
+        Request::SendTx { txid: txid(2) },
+        Request::AcceptQuote {
+            quote_id: QuoteId::new(1),
+        },
+    ] {
+        assert!(matches!(
+            check(Some(&done), &other, 2000, retention),
+            Err(Error::IdempotencyKeyReused(key)) if key == "payout-42"
+        ));
+    }
+}
+
+#[test]
+fn keys_validated() {
+    assert!(validate_key("payout-42").is_ok());
+    assert!(validate_key(&"a".repeat(MAX_KEY_LEN)).is_ok());
+    assert!(validate_key("").is_err());
+    assert!(validate_key(&"a".repeat(MAX_KEY_LEN + 1)).is_err());
+}
@@ -0,0 +1,367 @@
+//! The manager emits `tracing` events and spans with explicit fields,
+//! `LogSubscriber` redacts the field values and forwards the events to the `log` backend (log4rs).
+//! With the text format the fields are appended to the message (`message key=value ...`),
+//! with the JSON format they are passed in the log4rs JSON encoder `mdc` object.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use serde::Deserialize;
+use tracing::{
+    field::{Field, Visit},
+    span, Event, Level, Metadata, Subscriber,
+};
+
+const JSON_LOG_CONFIG: &str = include_str!("../config/log_config_json.toml");
+
+/// Field values that are never logged
+const SECRET_FIELDS: [&str; 5] = ["mnemonic", "password", "password_hash", "pset", "seed"];
+
+const REDACTED: &str = "[redacted]";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Config {
+    pub format: LogFormat,
+    pub truncate_addresses: bool,
+}
+
+/// A formatted event (the span fields go first, outermost span first)
+#[derive(Debug, Clone)]
+pub struct LogEvent {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+impl LogEvent {
+    #[cfg(test)]
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .rev()
+            .find(|(field, _value)| *field == name)
+            .map(|(_field, value)| value.as_str())
+    }
+
+    pub fn text(&self) -> String {
+        let mut text = self.message.clone();
+        for (name, value) in self.fields.iter() {
+            text.push_str(&format!(" {name}={value}"));
+        }
+        text
+    }
+}
+
+fn is_address_field(name: &str) -> bool {
+    name == "address" || name.ends_with("_address") || name == "addr" || name.starts_with("addr_")
+}
+
+pub fn redact(name: &str, value: String, truncate_addresses: bool) -> String {
+    if SECRET_FIELDS.contains(&name) {
+        REDACTED.to_owned()
+    } else if truncate_addresses && is_address_field(name) && value.chars().count() > 12 {
+        let chars = value.chars().collect::<Vec<_>>();
+        let head = chars[..6].iter().collect::<String>();
+        let tail = chars[chars.len() - 4..].iter().collect::<String>();
+        format!("{head}...{tail}")
+    } else {
+        value
+    }
+}
+
+fn log_level(level: Level) -> log::Level {
+    match level {
+        Level::ERROR => log::Level::Error,
+        Level::WARN => log::Level::Warn,
+        Level::INFO => log::Level::Info,
+        Level::DEBUG => log::Level::Debug,
+        Level::TRACE => log::Level::Trace,
+    }
+}
+
+struct FieldVisitor<'a> {
+    message: Option<String>,
+    fields: &'a mut Vec<(&'static str, String)>,
+    truncate_addresses: bool,
+}
+
+impl FieldVisitor<'_> {
+    fn add(&mut self, field: &Field, value: String) {
+        if field.name() == "message" {
+            self.message = Some(value);
+        } else {
+            let value = redact(field.name(), value, self.truncate_addresses);
+            self.fields.push((field.name(), value));
+        }
+    }
+}
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.add(field, value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.add(field, format!("{value:?}"));
+    }
+}
+
+enum Output {
+    Log,
+    #[cfg(test)]
+    Capture(std::sync::Arc<Mutex<Vec<LogEvent>>>),
+}
+
+struct SpanData {
+    parent: Option<u64>,
+    fields: Vec<(&'static str, String)>,
+    refs: usize,
+}
+
+thread_local! {
+    /// Entered spans
+    static CURRENT_SPANS: RefCell<Vec<u64>> = const { RefCell::new(Vec::new()) };
+}
+
+pub struct LogSubscriber {
+    config: Config,
+    output: Output,
+    next_id: AtomicU64,
+    spans: Mutex<HashMap<u64, SpanData>>,
+}
+
+impl LogSubscriber {
+    fn new(config: Config, output: Output) -> LogSubscriber {
+        LogSubscriber {
+            config,
+            output,
+            next_id: AtomicU64::new(1),
+            spans: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The subscriber that collects the formatted events instead of logging them
+    #[cfg(test)]
+    pub fn capture(config: Config) -> (LogSubscriber, std::sync::Arc<Mutex<Vec<LogEvent>>>) {
+        let events = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let subscriber =
+            LogSubscriber::new(config, Output::Capture(std::sync::Arc::clone(&events)));
+        (subscriber, events)
+    }
+
+    fn current_span() -> Option<u64> {
+        CURRENT_SPANS.with(|spans| spans.borrow().last().copied())
+    }
+
+    fn span_fields(&self, span: Option<u64>) -> Vec<(&'static str, String)> {
+        let spans = self.spans.lock().expect("must not fail");
+        let mut chain = Vec::new();
+        let mut next = span;
+        while let Some(span) = next.and_then(|id| spans.get(&id)) {
+            chain.push(span);
+            next = span.parent;
+        }
+        chain
+            .iter()
+            .rev()
+            .flat_map(|span| span.fields.iter().cloned())
+            .collect()
+    }
+
+    fn write(&self, event: LogEvent, metadata: &'static Metadata<'static>) {
+        match &self.output {
+            Output::Log => {
+                let json = self.config.format == LogFormat::Json;
+                let message = if json {
+                    for (name, value) in event.fields.iter() {
+                        log_mdc::insert(*name, value);
+                    }
+                    event.message.clone()
+                } else {
+                    event.text()
+                };
+                log::logger().log(
+                    &log::Record::builder()
+                        .level(log_level(event.level))
+                        .target(&event.target)
+                        .module_path_static(metadata.module_path())
+                        .file_static(metadata.file())
+                        .line(metadata.line())
+                        .args(format_args!("{message}"))
+                        .build(),
+                );
+                if json {
+                    for (name, _value) in event.fields.iter() {
+                        log_mdc::remove(*name);
+                    }
+                }
+            }
+            #[cfg(test)]
+            Output::Capture(events) => {
+                events.lock().expect("must not fail").push(event);
+            }
+        }
+    }
+}
+
+impl Subscriber for LogSubscriber {
+    fn register_callsite(
+        &self,
+        _metadata: &'static Metadata<'static>,
+    ) -> tracing::subscriber::Interest {
+        // The log level can be changed at runtime (log4rs `refresh_rate`)
+        tracing::subscriber::Interest::sometimes()
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        match self.output {
+            Output::Log if metadata.is_event() => log::logger().enabled(
+                &log::Metadata::builder()
+                    .level(log_level(*metadata.level()))
+                    .target(metadata.target())
+                    .build(),
+            ),
+            _ => true,
+        }
+    }
+
+    fn new_span(&self, attrs: &span::Attributes<'_>) -> span::Id {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let parent = if attrs.is_root() {
+            None
+        } else {
+            attrs
+                .parent()
+                .map(span::Id::into_u64)
+                .or_else(Self::current_span)
+        };
+        let mut fields = Vec::new();
+        attrs.record(&mut FieldVisitor {
+            message: None,
+            fields: &mut fields,
+            truncate_addresses: self.config.truncate_addresses,
+        });
+        self.spans.lock().expect("must not fail").insert(
+            id,
+            SpanData {
+                parent,
+                fields,
+                refs: 1,
+            },
+        );
+        span::Id::from_u64(id)
+    }
+
+    fn record(&self, span: &span::Id, values: &span::Record<'_>) {
+        let mut spans = self.spans.lock().expect("must not fail");
+        if let Some(span) = spans.get_mut(&span.into_u64()) {
+            values.record(&mut FieldVisitor {
+                message: None,
+                fields: &mut span.fields,
+                truncate_addresses: self.config.truncate_addresses,
+            });
+        }
+    }
+
+    fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let parent = if event.is_root() {
+            None
+        } else {
+            event
+                .parent()
+                .map(span::Id::into_u64)
+                .or_else(Self::current_span)
+        };
+        let mut fields = self.span_fields(parent);
+        let mut visitor = FieldVisitor {
+            message: None,
+            fields: &mut fields,
+            truncate_addresses: self.config.truncate_addresses,
+        };
+        event.record(&mut visitor);
+        let message = visitor.message.unwrap_or_default();
+        let metadata = event.metadata();
+        self.write(
+            LogEvent {
+                level: *metadata.level(),
+                target: metadata.target().to_owned(),
+                message,
+                fields,
+            },
+            metadata,
+        );
+    }
+
+    fn enter(&self, span: &span::Id) {
+        CURRENT_SPANS.with(|spans| spans.borrow_mut().push(span.into_u64()));
+    }
+
+    fn exit(&self, span: &span::Id) {
+        CURRENT_SPANS.with(|spans| {
+            let mut spans = spans.borrow_mut();
+            if let Some(pos) = spans.iter().rposition(|id| *id == span.into_u64()) {
+                spans.remove(pos);
+            }
+        });
+    }
+
+    fn clone_span(&self, span: &span::Id) -> span::Id {
+        if let Some(span) = self
+            .spans
+            .lock()
+            .expect("must not fail")
+            .get_mut(&span.into_u64())
+        {
+            span.refs += 1;
+        }
+        span.clone()
+    }
+
+    fn try_close(&self, span: span::Id) -> bool {
+        let mut spans = self.spans.lock().expect("must not fail");
+        let id = span.into_u64();
+        match spans.get_mut(&id) {
+            Some(span) if span.refs > 1 => {
+                span.refs -= 1;
+                false
+            }
+            Some(_) => {
+                spans.remove(&id);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Initialize log4rs (with the default config for the selected format) and install the subscriber
+pub fn init(work_dir: &std::path::Path, config: Config) {
+    match config.format {
+        LogFormat::Text => sideswap_dealer::logs::init(work_dir),
+        LogFormat::Json => sideswap_dealer::logs::init_with_config(work_dir, JSON_LOG_CONFIG),
+    }
+
+    tracing::subscriber::set_global_default(LogSubscriber::new(config, Output::Log))
+        .expect("must not fail");
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,72 @@
+use super::*;
+
+fn capture(truncate_addresses: bool, f: impl FnOnce()) -> Vec<LogEvent> {
+    let (subscriber, events) = LogSubscriber::capture(Config {
+        format: LogFormat::Json,
+        truncate_addresses,
+    });
+    tracing::subscriber::with_default(subscriber, f);
+    let events = events.lock().unwrap().clone();
+    events
+}
+
+#[test]
+fn secrets_redacted() {
+    let events = capture(false, || {
+        tracing::info!(
+            mnemonic = "abandon abandon about",
+            pset = "cHNldP8BAgQCAAAA",
+            txid = "00ff",
+            "secret fields"
+        );
+    });
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event.message, "secret fields");
+    assert_eq!(event.field("mnemonic"), Some(REDACTED));
+    assert_eq!(event.field("pset"), Some(REDACTED));
+    assert_eq!(event.field("txid"), Some("00ff"));
+}
+
+#[test]
+fn addresses_truncated_if_enabled() {
+    let address = "lq1qqwyqmshtvgm9ja7uhza5ls9xgvm3pnrg5f8yamhkj4ctjzcn";
+    for truncate_addresses in [false, true] {
+        let events = capture(truncate_addresses, || {
+            tracing::info!(address, receive_address = %address, "addresses");
+        });
+        let expected = if truncate_addresses {
+            "lq1qqw...jzcn".to_owned()
+        } else {
+            address.to_owned()
+        };
+        assert_eq!(events[0].field("address"), Some(expected.as_str()));
+        assert_eq!(events[0].field("receive_address"), Some(expected.as_str()));
+    }
+}
+
+#[test]
+fn span_fields_inherited() {
+    let events = capture(false, || {
+        let outer = tracing::info_span!("request", trace_id = "abc", client_id = 1);
+        let _outer = outer.enter();
+        let inner = tracing::debug_span!("upstream_request", request_id = 5);
+        inner.in_scope(|| tracing::debug!(ok = true, "inside"));
+        tracing::debug!("outside");
+    });
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].field("trace_id"), Some("abc"));
+    assert_eq!(events[0].field("request_id"), Some("5"));
+    assert_eq!(
+        events[0].text(),
+        "inside trace_id=abc client_id=1 request_id=5 ok=true"
+    );
+    assert_eq!(events[1].field("request_id"), None);
+    assert_eq!(events[1].text(), "outside trace_id=abc client_id=1");
+}
+
+#[test]
+fn plain_message_unchanged() {
+    let events = capture(false, || tracing::info!("started {}", 1));
+    assert_eq!(events[0].text(), "started 1");
+}
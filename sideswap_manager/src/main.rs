@@ -1,12 +1,40 @@
-use std::{path::PathBuf, sync::Arc};
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
 
 use serde::Deserialize;
-use sideswap_common::dealer_ticker::{TickerLoader, WhitelistedAssets};
+use sideswap_common::{
+    dealer_ticker::{TickerAliases, WhitelistedAssets},
+    gdk_registry_cache::GdkRegistryCache,
+};
 
 mod api;
+mod approvals;
+mod balance_history;
+mod clock_skew;
+mod config_reload;
 mod db;
+mod drain;
 mod error;
+mod http_server;
+mod idempotency;
+mod logging;
+mod maker;
+mod metrics;
+mod mnemonic_cipher;
 mod models;
+mod notif_encoding;
+mod payment_refs;
+mod payment_uri;
+mod peg_notifs;
+mod pset_check;
+mod quotas;
+mod quote_coalescing;
+mod signing_lock;
+mod tor;
+mod wallets;
+mod webhooks;
 mod worker;
 mod ws_server;
 
@@ -14,55 +42,243 @@ mod ws_server;
 struct Settings {
     env: sideswap_common::env::Env,
     work_dir: PathBuf,
+    /// Start with a new DB if the existing one was created for another `env` (the old DB files are renamed, not deleted)
+    #[serde(default)]
+    force_env_migration: bool,
 
-    mnemonic: bip39::Mnemonic,
+    /// Plaintext mnemonic, exactly one of `mnemonic` and `encrypted_mnemonic` must be set
+    mnemonic: Option<bip39::Mnemonic>,
+    /// Mnemonic encrypted with AES-256-GCM-SIV (base64 of the nonce and the ciphertext),
+    /// printed by `sideswap_manager encrypt-mnemonic <key_file>`
+    encrypted_mnemonic: Option<String>,
+    /// The `encrypted_mnemonic` key source, required if `encrypted_mnemonic` is set
+    mnemonic_key: Option<mnemonic_cipher::KeySource>,
     script_variant: sideswap_lwk::ScriptVariant,
+    /// Additional named wallets (the top-level wallet is named `default`).
+    /// The wallets share the DB, every wallet has its own wallet cache in its `work_dir`.
+    #[serde(default)]
+    wallets: Vec<wallets::Config>,
     ws_server: ws_server::Config,
+    /// Optional HTTP server, every request is sent as `POST /<Req variant>` with the request JSON in the body.
+    /// The `[ws_server].auth_token` value (if set) is required as `Authorization: Bearer <token>`.
+    http_listen_on: Option<SocketAddr>,
+    /// Optional Prometheus metrics listener (`GET /metrics`, no authentication)
+    metrics_listen_on: Option<SocketAddr>,
     whitelisted_assets: Option<WhitelistedAssets>,
+    /// Maximum number of consecutive unused addresses that can be generated with `NewAddress` (20 by default)
+    gap_limit: Option<u32>,
+    /// A `GapLimitWarning` notification is sent when fewer than this many new addresses can be generated (5 by default)
+    gap_limit_warning: Option<u32>,
+    /// Additional ticker aliases accepted in requests (alias -> ticker).
+    /// Tickers are matched case-insensitively and "LBTC"/"USDT" are always accepted.
+    ticker_aliases: Option<TickerAliases>,
+    /// Require an explicit `Unlock` before signing (and after a period without signing operations)
+    auto_lock: Option<signing_lock::Config>,
+    /// Periodically compare the local clock against an HTTP time source.
+    /// The SideSwap server responses used by the manager carry no server time, so an external source is required.
+    clock_check: Option<clock_skew::Config>,
+    /// Grace period for draining started with SIGUSR2 (in seconds, 300 by default)
+    drain_grace_seconds: Option<u64>,
+    /// Upper bound of the shutdown after SIGTERM/SIGINT (in seconds, 10 by default).
+    /// Queued requests are rejected and the pending server broadcasts are awaited before the DB is closed.
+    shutdown_timeout_seconds: Option<u64>,
+    /// Created but not sent transactions are removed after this time (in seconds, 86400 by default).
+    /// They are stored in the DB, so `SendTx` still works after a restart.
+    created_tx_max_age_seconds: Option<u64>,
+    /// Wallet commands fail with `WalletTimeout` if the wallet does not respond in this time (in seconds, 60 by default).
+    /// The read-only wallet requests (`GetWalletTxs`, `ListUtxos` and so on) do not block the other requests while waiting.
+    wallet_timeout_seconds: Option<u64>,
+    /// `SendTx` and `AcceptQuote` responses are kept for the `idempotency_key` replays this long (in seconds, 86400 by default)
+    idempotency_key_retention_seconds: Option<u64>,
+    /// Allow `CreateTx` and `GetQuote` to pay only to the allow-list addresses (or to the own wallet addresses)
+    #[serde(default)]
+    enforce_allowlist: bool,
+    /// Return the same quote for identical `GetQuote` requests received shortly after each other
+    quote_coalescing: Option<quote_coalescing::Config>,
+    /// Record the wallet balances periodically (returned by `GetBalanceHistory`)
+    balance_history: Option<balance_history::Config>,
+    /// Serialized size limits for the requests sent to the SideSwap server (1 MiB soft and 16 MiB hard limit by default).
+    /// Larger requests are logged with a warning (soft limit) or fail with `InvalidRequest` without being sent (hard limit).
+    upstream_size_limits: Option<sideswap_common::ws::ws_req_sender::SizeLimits>,
+    /// Limits for the resources a single client can hold (no limits by default).
+    /// Clients are identified by the `client_name` WS URL query parameter, clients without a name share the same limits.
+    client_quotas: Option<quotas::Config>,
+    /// Queue large `SendTx` and `AcceptQuote` requests until another client approves them
+    approvals: Option<approvals::Config>,
+    /// Log in to the SideSwap market as a maker (`SubmitOrder`, `EditOrder` and `CancelOrder`).
+    /// The wallet UTXOs are registered with the server and the matched swaps are signed automatically.
+    #[serde(default)]
+    maker_orders: bool,
+    /// Publish the WS server as a Tor onion service (requires the `tor` cargo feature)
+    tor: Option<tor::Config>,
+    /// POST the peg, confirmed tx and balance notifications to HTTP endpoints
+    webhooks: Option<webhooks::Config>,
+    /// Log format, `text` (default) or `json` (structured, the event fields are reported in `mdc`)
+    #[serde(default)]
+    log_format: logging::LogFormat,
+    /// Log only the beginning and the end of addresses
+    #[serde(default)]
+    log_truncate_addresses: bool,
+}
+
+impl Settings {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        wallets::validate_work_dir(&self.work_dir)?;
+        wallets::validate_mnemonic(
+            self.mnemonic.as_ref(),
+            self.encrypted_mnemonic.as_deref(),
+            self.mnemonic_key.as_ref(),
+        )?;
+        wallets::validate(&self.wallets, &self.work_dir)?;
+        if let Some(webhooks) = &self.webhooks {
+            webhooks.validate()?;
+        }
+        if let Some(auto_lock) = &self.auto_lock {
+            auto_lock.validate()?;
+        }
+        self.ws_server.validate()?;
+        Ok(())
+    }
+
+    /// Decrypts `encrypted_mnemonic` if there is no plaintext mnemonic
+    fn wallet_mnemonic(&self) -> Result<bip39::Mnemonic, anyhow::Error> {
+        wallets::load_mnemonic(
+            self.mnemonic.as_ref(),
+            self.encrypted_mnemonic.as_deref(),
+            self.mnemonic_key.as_ref(),
+        )
+    }
+}
+
+/// Opens the DB and checks that it was created for the configured env
+async fn open_db(db_file: &Path, settings: &Settings) -> Result<db::Db, db::OpenError> {
+    let db = db::Db::open_file(db_file).await?;
+    match db.check_env(settings.env).await {
+        Ok(()) => Ok(db),
+        Err(err) if settings.force_env_migration => {
+            db.close().await;
+            let suffix = format!(
+                "{}.bak",
+                sideswap_types::timestamp_ms::TimestampMs::now().millis()
+            );
+            let backup = db::move_aside(db_file, &suffix)?;
+            tracing::warn!(
+                "{err}; force_env_migration is set, the old DB is moved to {}",
+                backup.display()
+            );
+            let db = db::Db::open_file(db_file).await?;
+            db.check_env(settings.env).await?;
+            Ok(db)
+        }
+        Err(err) => {
+            db.close().await;
+            Err(err)
+        }
+    }
+}
+
+/// Load and validate the config file (values can be overridden with `APP_` environment variables)
+fn load_settings(config_path: &str) -> Result<Settings, anyhow::Error> {
+    let mut conf = config::Config::new();
+    conf.merge(config::File::with_name(config_path))?;
+    conf.merge(config::Environment::with_prefix("app").separator("_"))?;
+    let settings: Settings = conf.try_into()?;
+    settings.validate()?;
+    Ok(settings)
 }
 
 #[tokio::main]
 async fn main() {
     let args = std::env::args().collect::<Vec<_>>();
+
+    if args.get(1).map(String::as_str) == Some("password-hash") {
+        let mut password = String::new();
+        std::io::stdin()
+            .read_line(&mut password)
+            .expect("reading password failed");
+        println!(
+            "{}",
+            signing_lock::password_hash(password.trim_end_matches(['\r', '\n']))
+        );
+        return;
+    }
+
+    if args.get(1).map(String::as_str) == Some("encrypt-mnemonic") {
+        let key_file = args.get(2).expect("specify the key file path");
+        let key = mnemonic_cipher::KeySource::File(key_file.into())
+            .load()
+            .expect("invalid key file");
+        let mut mnemonic = zeroize::Zeroizing::new(String::new());
+        std::io::stdin()
+            .read_line(&mut mnemonic)
+            .expect("reading mnemonic failed");
+        let mnemonic = mnemonic.trim().parse().expect("invalid mnemonic");
+        println!("{}", mnemonic_cipher::encrypt(&key, &mnemonic));
+        return;
+    }
+
     assert!(
         args.len() == 2,
         "Specify a single argument for the path to the config file"
     );
     let config_path = &args[1];
 
-    let mut conf = config::Config::new();
-    conf.merge(config::File::with_name(config_path))
-        .expect("can't load config");
-    conf.merge(config::Environment::with_prefix("app").separator("_"))
-        .expect("reading env failed");
-    let settings: Settings = conf.try_into().expect("invalid config");
+    let settings = load_settings(config_path).expect("invalid config");
 
-    assert!(
-        !settings.work_dir.starts_with("/tmp"),
-        "invalid work_dir value: {:?}\nplease do not keep work dir in /tmp, the contents must be preserved",
-        settings.work_dir,
+    logging::init(
+        &settings.work_dir,
+        logging::Config {
+            format: settings.log_format,
+            truncate_addresses: settings.log_truncate_addresses,
+        },
     );
 
-    sideswap_dealer::logs::init(&settings.work_dir);
-
     sideswap_common::panic_handler::install_panic_handler();
 
     let db_file = settings.work_dir.join("db.sqlite");
-    let db = db::Db::open_file(db_file).await;
+    let db = match open_db(&db_file, &settings).await {
+        Ok(db) => db,
+        Err(err) => {
+            tracing::error!("{err}");
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    };
 
-    let ticker_loader = Arc::new(
-        TickerLoader::load(
-            &settings.work_dir,
-            settings.whitelisted_assets.as_ref(),
-            settings.env.d().network,
-        )
-        .await
-        .expect("must not fail"),
-    );
+    let gdk_registry = GdkRegistryCache::new(settings.env.d().network, &settings.work_dir).await;
 
     let (command_sender, command_receiver) = tokio::sync::mpsc::unbounded_channel();
 
-    ws_server::start(settings.ws_server.clone(), command_sender);
+    let (drain_sender, drain_receiver) = tokio::sync::watch::channel(false);
+
+    if let Some(listen_on) = settings.http_listen_on {
+        http_server::start(
+            listen_on,
+            settings.ws_server.auth(),
+            command_sender.clone(),
+            drain_receiver.clone(),
+        );
+    }
+
+    ws_server::start(settings.ws_server.clone(), command_sender, drain_receiver);
+
+    let tor_status = match &settings.tor {
+        Some(config) => tor::start(
+            config.clone(),
+            &settings.work_dir,
+            settings.ws_server.listen_on(),
+        ),
+        None => tokio::sync::watch::channel(tor::Status::default()).1,
+    };
 
-    worker::run(settings, command_receiver, ticker_loader, db).await;
+    worker::run(
+        settings,
+        config_path.clone(),
+        command_receiver,
+        gdk_registry,
+        db,
+        drain_sender,
+        tor_status,
+    )
+    .await;
 }
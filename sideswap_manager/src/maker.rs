@@ -0,0 +1,109 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use sideswap_api::mkt::{self, OrdId};
+use sideswap_common::{dealer_ticker::TickerLoader, types::asset_float_amount_, verify};
+
+use crate::error::Error;
+
+/// Allowed relative difference between the quote amount and the base amount multiplied by the price (rounding)
+const QUOTE_AMOUNT_TOLERANCE: f64 = 0.0001;
+
+/// The totals of the matched orders, all of them have the same market and direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Swap {
+    pub asset_pair: mkt::AssetPair,
+    pub trade_dir: mkt::TradeDir,
+    pub base_amount: u64,
+    pub quote_amount: u64,
+}
+
+fn mismatch(reason: String) -> Error {
+    Error::PsetMismatch { reason }
+}
+
+/// Checks the orders of a `MakerSign` notification against the own orders:
+/// every order must be known and matched once, at its price and for no more than its amount.
+/// The active amount is not used, the server may reduce it before the swap is signed.
+pub fn check_swap(
+    own_orders: &BTreeMap<OrdId, mkt::OwnOrder>,
+    swaps: &[mkt::MakerSwapInfo],
+    ticker_loader: &TickerLoader,
+) -> Result<Swap, Error> {
+    let first = swaps
+        .first()
+        .ok_or_else(|| mismatch("no matched orders".to_owned()))?;
+    let first_order = own_orders
+        .get(&first.order_id)
+        .ok_or_else(|| mismatch(format!("unknown order {}", first.order_id)))?;
+    let asset_pair = first_order.asset_pair;
+    let trade_dir = first_order.trade_dir;
+
+    let precision = |asset_id| {
+        ticker_loader
+            .ticker(&asset_id)
+            .map(|ticker| ticker_loader.precision(ticker))
+            .ok_or_else(|| mismatch(format!("unknown asset {asset_id}")))
+    };
+    let base_precision = precision(asset_pair.base)?;
+    let quote_precision = precision(asset_pair.quote)?;
+
+    let mut order_ids = BTreeSet::new();
+    let mut base_amount = 0;
+    let mut quote_amount = 0;
+    for swap in swaps {
+        verify!(
+            order_ids.insert(swap.order_id),
+            mismatch(format!("order {} is matched twice", swap.order_id))
+        );
+
+        let order = own_orders
+            .get(&swap.order_id)
+            .ok_or_else(|| mismatch(format!("unknown order {}", swap.order_id)))?;
+        verify!(
+            order.asset_pair == asset_pair && order.trade_dir == trade_dir,
+            mismatch(format!(
+                "order {} is from another market or direction",
+                swap.order_id
+            ))
+        );
+        verify!(
+            swap.price == order.price,
+            mismatch(format!(
+                "order {} is matched at {}, the order price is {}",
+                swap.order_id, swap.price, order.price
+            ))
+        );
+        verify!(
+            swap.base_amount > 0 && swap.base_amount <= order.orig_amount,
+            mismatch(format!(
+                "order {} is matched for {}, the order amount is {}",
+                swap.order_id, swap.base_amount, order.orig_amount
+            ))
+        );
+
+        let expected_quote_amount =
+            asset_float_amount_(swap.base_amount, base_precision) * swap.price.value();
+        let actual_quote_amount = asset_float_amount_(swap.quote_amount, quote_precision);
+        let diff = (1.0 - actual_quote_amount / expected_quote_amount).abs();
+        verify!(
+            diff < QUOTE_AMOUNT_TOLERANCE,
+            mismatch(format!(
+                "order {} quote amount is {actual_quote_amount}, expected {expected_quote_amount}",
+                swap.order_id
+            ))
+        );
+
+        base_amount += swap.base_amount;
+        quote_amount += swap.quote_amount;
+    }
+
+    Ok(Swap {
+        asset_pair,
+        trade_dir,
+        base_amount,
+        quote_amount,
+    })
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,145 @@
+use elements::AssetId;
+use sideswap_common::dealer_ticker::DealerTicker;
+use sideswap_types::{
+    asset_precision::AssetPrecision, normal_float::NormalFloat, timestamp_ms::TimestampMs,
+};
+
+use super::*;
+
+fn asset(index: u8) -> AssetId {
+    AssetId::from_slice(&[index; 32]).expect("must not fail")
+}
+
+fn ticker_loader() -> TickerLoader {
+    TickerLoader::from_assets([
+        (
+            asset(1),
+            DealerTicker::LBTC,
+            AssetPrecision::BITCOIN_PRECISION,
+        ),
+        (
+            asset(2),
+            DealerTicker::USDT,
+            AssetPrecision::BITCOIN_PRECISION,
+        ),
+    ])
+}
+
+fn price(value: f64) -> NormalFloat {
+    NormalFloat::new(value).expect("must be valid")
+}
+
+fn order(order_id: u64, trade_dir: mkt::TradeDir, active_amount: u64) -> mkt::OwnOrder {
+    mkt::OwnOrder {
+        order_id: OrdId::new(order_id),
+        created_at: TimestampMs::from_millis(0),
+        client_order_id: None,
+        asset_pair: mkt::AssetPair {
+            base: asset(1),
+            quote: asset(2),
+        },
+        price: price(100_000.0),
+        price_tracking: None,
+        orig_amount: active_amount,
+        active_amount,
+        trade_dir,
+        ttl: None,
+        private_id: None,
+        online: true,
+    }
+}
+
+fn swap(order_id: u64, base_amount: u64, quote_amount: u64) -> mkt::MakerSwapInfo {
+    mkt::MakerSwapInfo {
+        order_id: OrdId::new(order_id),
+        price: price(100_000.0),
+        base_amount,
+        quote_amount,
+    }
+}
+
+fn own_orders(orders: Vec<mkt::OwnOrder>) -> BTreeMap<OrdId, mkt::OwnOrder> {
+    orders
+        .into_iter()
+        .map(|order| (order.order_id, order))
+        .collect()
+}
+
+#[test]
+fn matched_orders_totals() {
+    let orders = own_orders(vec![
+        order(1, mkt::TradeDir::Sell, 100_000),
+        order(2, mkt::TradeDir::Sell, 200_000),
+    ]);
+
+    let swap = check_swap(
+        &orders,
+        &[
+            swap(1, 100_000, 10_000_000_000),
+            swap(2, 50_000, 5_000_000_100),
+        ],
+        &ticker_loader(),
+    )
+    .unwrap();
+    assert_eq!(
+        swap,
+        Swap {
+            asset_pair: orders[&OrdId::new(1)].asset_pair,
+            trade_dir: mkt::TradeDir::Sell,
+            base_amount: 150_000,
+            quote_amount: 15_000_000_100,
+        }
+    );
+}
+
+#[test]
+fn mismatched_orders_rejected() {
+    let mut orders = own_orders(vec![
+        order(1, mkt::TradeDir::Sell, 100_000),
+        order(2, mkt::TradeDir::Buy, 100_000),
+    ]);
+    let ticker_loader = ticker_loader();
+    let check = |orders: &BTreeMap<OrdId, mkt::OwnOrder>, swaps: &[mkt::MakerSwapInfo]| {
+        matches!(
+            check_swap(orders, swaps, &ticker_loader),
+            Err(Error::PsetMismatch { .. })
+        )
+    };
+
+    assert!(check(&orders, &[]));
+    // Unknown order
+    assert!(check(&orders, &[swap(3, 100_000, 10_000_000_000)]));
+    // Matched twice
+    assert!(check(
+        &orders,
+        &[
+            swap(1, 50_000, 5_000_000_000),
+            swap(1, 50_000, 5_000_000_000)
+        ]
+    ));
+    // Another direction
+    assert!(check(
+        &orders,
+        &[
+            swap(1, 50_000, 5_000_000_000),
+            swap(2, 50_000, 5_000_000_000)
+        ]
+    ));
+    // More than the order amount
+    assert!(check(&orders, &[swap(1, 100_001, 10_000_100_000)]));
+    assert!(check(&orders, &[swap(1, 0, 0)]));
+    // Another price
+    assert!(check(
+        &orders,
+        &[mkt::MakerSwapInfo {
+            price: price(90_000.0),
+            ..swap(1, 100_000, 9_000_000_000)
+        }]
+    ));
+    // The quote amount does not match the price
+    assert!(check(&orders, &[swap(1, 100_000, 9_990_000_000)]));
+
+    // Unknown asset
+    orders.get_mut(&OrdId::new(1)).unwrap().asset_pair.quote = asset(3);
+    assert!(check(&orders, &[swap(1, 100_000, 10_000_000_000)]));
+}
@@ -0,0 +1,225 @@
+use std::{collections::BTreeMap, fmt::Write, net::SocketAddr, sync::Arc, sync::Mutex};
+
+use axum::{extract::State, http::header, routing::get};
+use tokio::{net::TcpListener, time::Instant};
+
+use crate::api;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BroadcastTarget {
+    Wallet,
+    Server,
+}
+
+impl BroadcastTarget {
+    fn name(self) -> &'static str {
+        match self {
+            BroadcastTarget::Wallet => "wallet",
+            BroadcastTarget::Server => "server",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Values {
+    ws_reconnects: u64,
+    quotes_requested: u64,
+    quotes_accepted: u64,
+    /// By the error code name
+    quotes_failed: BTreeMap<String, u64>,
+    broadcast_errors: BTreeMap<BroadcastTarget, u64>,
+    connected_clients: usize,
+    monitored_txs: usize,
+    balances: api::Balances,
+    last_wallet_event: Option<Instant>,
+}
+
+/// Counters and gauges updated by the worker and served by the metrics listener
+#[derive(Default)]
+pub struct Metrics {
+    values: Mutex<Values>,
+}
+
+impl Metrics {
+    fn update(&self, f: impl FnOnce(&mut Values)) {
+        f(&mut self.values.lock().expect("must not fail"));
+    }
+
+    pub fn ws_reconnected(&self) {
+        self.update(|values| values.ws_reconnects += 1);
+    }
+
+    pub fn quote_requested(&self) {
+        self.update(|values| values.quotes_requested += 1);
+    }
+
+    pub fn quote_accepted(&self) {
+        self.update(|values| values.quotes_accepted += 1);
+    }
+
+    pub fn quote_failed(&self, code: &api::ErrorCode) {
+        // The variant name is the serialized error code
+        let kind = format!("{code:?}");
+        self.update(|values| *values.quotes_failed.entry(kind).or_default() += 1);
+    }
+
+    pub fn broadcast_failed(&self, target: BroadcastTarget) {
+        self.update(|values| *values.broadcast_errors.entry(target).or_default() += 1);
+    }
+
+    pub fn wallet_event(&self, now: Instant) {
+        self.update(|values| values.last_wallet_event = Some(now));
+    }
+
+    pub fn set_counts(&self, connected_clients: usize, monitored_txs: usize) {
+        self.update(|values| {
+            values.connected_clients = connected_clients;
+            values.monitored_txs = monitored_txs;
+        });
+    }
+
+    pub fn set_balances(&self, balances: &api::Balances) {
+        self.update(|values| values.balances = balances.clone());
+    }
+
+    /// The Prometheus text exposition format
+    pub fn render(&self, now: Instant) -> String {
+        let values = self.values.lock().expect("must not fail");
+        let mut out = String::new();
+
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(out, "# HELP {name} {help}");
+            let _ = writeln!(out, "# TYPE {name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(out, "{name}{labels} {value}");
+            }
+        };
+        let single = |value: String| vec![(String::new(), value)];
+        let labeled = |label: &str, samples: Vec<(&str, String)>| {
+            samples
+                .into_iter()
+                .map(|(value, sample)| (format!("{{{label}=\"{}\"}}", escape(value)), sample))
+                .collect::<Vec<_>>()
+        };
+
+        metric(
+            "ws_reconnects_total",
+            "counter",
+            "Upstream WS reconnects",
+            &single(values.ws_reconnects.to_string()),
+        );
+        metric(
+            "quotes_requested_total",
+            "counter",
+            "GetQuote requests",
+            &single(values.quotes_requested.to_string()),
+        );
+        metric(
+            "quotes_accepted_total",
+            "counter",
+            "Accepted quotes",
+            &single(values.quotes_accepted.to_string()),
+        );
+        metric(
+            "quotes_failed_total",
+            "counter",
+            "Failed GetQuote requests by the error code",
+            &labeled(
+                "kind",
+                values
+                    .quotes_failed
+                    .iter()
+                    .map(|(kind, count)| (kind.as_str(), count.to_string()))
+                    .collect(),
+            ),
+        );
+        metric(
+            "broadcast_errors_total",
+            "counter",
+            "Failed SendTx broadcasts (including the server broadcast retries)",
+            &labeled(
+                "target",
+                [BroadcastTarget::Wallet, BroadcastTarget::Server]
+                    .into_iter()
+                    .map(|target| {
+                        let count = values.broadcast_errors.get(&target).copied();
+                        (target.name(), count.unwrap_or_default().to_string())
+                    })
+                    .collect(),
+            ),
+        );
+        metric(
+            "connected_clients",
+            "gauge",
+            "Connected WS and in-flight HTTP clients",
+            &single(values.connected_clients.to_string()),
+        );
+        metric(
+            "monitored_txs",
+            "gauge",
+            "Monitored transactions",
+            &single(values.monitored_txs.to_string()),
+        );
+        metric(
+            "balance",
+            "gauge",
+            "Wallet balances (including the unconfirmed UTXOs)",
+            &labeled(
+                "ticker",
+                values
+                    .balances
+                    .iter()
+                    .map(|(ticker, amount)| (ticker.as_str(), amount.to_string()))
+                    .collect(),
+            ),
+        );
+        metric(
+            "wallet_event_age_seconds",
+            "gauge",
+            "Time since the last wallet event (not reported before the first one)",
+            &values
+                .last_wallet_event
+                .map(|last| single(now.saturating_duration_since(last).as_secs().to_string()))
+                .unwrap_or_default(),
+        );
+
+        out
+    }
+}
+
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+async fn handle_metrics(State(metrics): State<Arc<Metrics>>) -> impl axum::response::IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics.render(Instant::now()),
+    )
+}
+
+async fn run(listener: TcpListener, metrics: Arc<Metrics>) {
+    let app = axum::Router::new()
+        .route("/metrics", get(handle_metrics))
+        .with_state(metrics);
+    if let Err(err) = axum::serve(listener, app).await {
+        tracing::error!("metrics server failed: {err}");
+    }
+}
+
+/// Serves `GET /metrics` (without authentication, the listener should not be reachable from outside)
+pub fn start(listen_on: SocketAddr, metrics: Arc<Metrics>) {
+    tokio::task::spawn(async move {
+        tracing::info!("start metrics server on {listen_on}...");
+        let listener = TcpListener::bind(&listen_on)
+            .await
+            .expect("port must be open");
+        run(listener, metrics).await;
+    });
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use sideswap_common::dealer_ticker::DealerTicker;
+
+use super::*;
+
+fn samples(text: &str) -> Vec<&str> {
+    text.lines().filter(|line| !line.starts_with('#')).collect()
+}
+
+#[test]
+fn counters_and_gauges_rendered() {
+    let now = Instant::now();
+    let metrics = Metrics::default();
+    assert_eq!(
+        samples(&metrics.render(now)),
+        [
+            "ws_reconnects_total 0",
+            "quotes_requested_total 0",
+            "quotes_accepted_total 0",
+            "broadcast_errors_total{target=\"wallet\"} 0",
+            "broadcast_errors_total{target=\"server\"} 0",
+            "connected_clients 0",
+            "monitored_txs 0",
+        ]
+    );
+
+    metrics.ws_reconnected();
+    for _ in 0..3 {
+        metrics.quote_requested();
+    }
+    metrics.quote_accepted();
+    metrics.quote_failed(&api::ErrorCode::QuoteLowBalance);
+    metrics.quote_failed(&api::ErrorCode::ServerTimeout);
+    metrics.quote_failed(&api::ErrorCode::QuoteLowBalance);
+    metrics.broadcast_failed(BroadcastTarget::Server);
+    metrics.set_counts(2, 5);
+    metrics.set_balances(&api::Balances::from([
+        (DealerTicker::LBTC, 0.5),
+        (DealerTicker::USDT, 100.25),
+    ]));
+    metrics.wallet_event(now);
+
+    let text = metrics.render(now + Duration::from_secs(7));
+    assert_eq!(
+        samples(&text),
+        [
+            "ws_reconnects_total 1",
+            "quotes_requested_total 3",
+            "quotes_accepted_total 1",
+            "quotes_failed_total{kind=\"QuoteLowBalance\"} 2",
+            "quotes_failed_total{kind=\"ServerTimeout\"} 1",
+            "broadcast_errors_total{target=\"wallet\"} 0",
+            "broadcast_errors_total{target=\"server\"} 1",
+            "connected_clients 2",
+            "monitored_txs 5",
+            "balance{ticker=\"L-BTC\"} 0.5",
+            "balance{ticker=\"USDt\"} 100.25",
+            "wallet_event_age_seconds 7",
+        ]
+    );
+    assert!(text.contains("# TYPE quotes_failed_total counter\n"));
+    assert!(text.contains("# TYPE balance gauge\n"));
+}
+
+#[test]
+fn label_values_escaped() {
+    assert_eq!(escape("a\"b\\c\nd"), "a\\\"b\\\\c\\nd");
+}
+
+#[tokio::test]
+async fn metrics_served() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let metrics = Arc::new(Metrics::default());
+    metrics.quote_requested();
+    tokio::spawn(run(listener, Arc::clone(&metrics)));
+
+    let (content_type, body) = tokio::task::spawn_blocking(move || {
+        let resp = ureq::get(&format!("http://{address}/metrics"))
+            .call()
+            .unwrap();
+        (
+            resp.header("Content-Type").map(str::to_owned),
+            resp.into_string().unwrap(),
+        )
+    })
+    .await
+    .unwrap();
+    assert_eq!(content_type.as_deref(), Some("text/plain; version=0.0.4"));
+    assert!(samples(&body).contains(&"quotes_requested_total 1"));
+}
@@ -0,0 +1,64 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context};
+use serde::Deserialize;
+use sideswap_common::{
+    b64,
+    cipher::{aes::AesCipher, Cipher},
+};
+use zeroize::Zeroizing;
+
+/// Where the `encrypted_mnemonic` key is read from.
+/// The key is 32 bytes, hex-encoded (for example, generated with `openssl rand -hex 32`).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeySource {
+    /// The environment variable name
+    Env(String),
+    /// The key file path
+    File(PathBuf),
+}
+
+pub type Key = Zeroizing<[u8; 32]>;
+
+impl KeySource {
+    pub fn load(&self) -> Result<Key, anyhow::Error> {
+        let value = Zeroizing::new(match self {
+            KeySource::Env(name) => std::env::var(name)
+                .with_context(|| format!("can't read the mnemonic key from ${name}"))?,
+            KeySource::File(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("can't read the mnemonic key from {path:?}"))?,
+        });
+        parse_key(value.trim())
+    }
+}
+
+pub fn parse_key(value: &str) -> Result<Key, anyhow::Error> {
+    let mut key = Zeroizing::new([0; 32]);
+    hex::decode_to_slice(value, key.as_mut_slice())
+        .map_err(|err| anyhow!("invalid mnemonic key (32 hex-encoded bytes expected): {err}"))?;
+    Ok(key)
+}
+
+/// Returns the `encrypted_mnemonic` value (base64 of the nonce and the ciphertext)
+pub fn encrypt(key: &Key, mnemonic: &bip39::Mnemonic) -> String {
+    let plaintext = Zeroizing::new(mnemonic.to_string());
+    b64::encode(&AesCipher::new(key).encrypt(plaintext.as_bytes()))
+}
+
+/// The decrypted plaintext is zeroized before returning
+pub fn decrypt(key: &Key, encrypted_mnemonic: &str) -> Result<bip39::Mnemonic, anyhow::Error> {
+    let encrypted = b64::decode(encrypted_mnemonic.trim())
+        .map_err(|err| anyhow!("invalid encrypted_mnemonic: {err}"))?;
+    let plaintext = Zeroizing::new(
+        AesCipher::new(key)
+            .decrypt(&encrypted)
+            .map_err(|_err| anyhow!("can't decrypt encrypted_mnemonic, check the key"))?,
+    );
+    let plaintext =
+        std::str::from_utf8(&plaintext).map_err(|_err| anyhow!("invalid decrypted mnemonic"))?;
+    bip39::Mnemonic::parse(plaintext).map_err(|err| anyhow!("invalid decrypted mnemonic: {err}"))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,88 @@
+use super::*;
+
+const MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+const KEY: &str = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+
+#[test]
+fn encrypted_mnemonic_decrypted() {
+    let key = parse_key(KEY).unwrap();
+    let mnemonic = MNEMONIC.parse::<bip39::Mnemonic>().unwrap();
+
+    let encrypted = encrypt(&key, &mnemonic);
+    assert_ne!(
+        encrypted,
+        encrypt(&key, &mnemonic),
+        "the nonce must be random"
+    );
+    assert_eq!(decrypt(&key, &encrypted).unwrap(), mnemonic);
+
+    let wrong_key = parse_key(&KEY.replace("00", "ff")).unwrap();
+    assert!(decrypt(&wrong_key, &encrypted)
+        .unwrap_err()
+        .to_string()
+        .contains("check the key"));
+    assert!(decrypt(&key, "not base64!").is_err());
+}
+
+#[test]
+fn key_loaded_from_file() {
+    let path = std::env::temp_dir().join(format!("mnemonic_key_{}", std::process::id()));
+    std::fs::write(&path, format!("{KEY}\n")).unwrap();
+    let key = KeySource::File(path.clone()).load().unwrap();
+    assert_eq!(key, parse_key(KEY).unwrap());
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(KeySource::File(path).load().is_err());
+    assert!(parse_key(&KEY[2..]).is_err());
+    assert!(parse_key("zz").is_err());
+}
+
+#[test]
+fn settings_mnemonic_decrypted() {
+    let path = std::env::temp_dir().join(format!("settings_mnemonic_key_{}", std::process::id()));
+    std::fs::write(&path, KEY).unwrap();
+    let mnemonic = MNEMONIC.parse::<bip39::Mnemonic>().unwrap();
+    let encrypted = encrypt(&parse_key(KEY).unwrap(), &mnemonic);
+
+    let settings = |mnemonic: serde_json::Value| -> crate::Settings {
+        let mut value = serde_json::json!({
+            "env": "Testnet",
+            "work_dir": "/var/lib/sideswap_manager",
+            "script_variant": "wpkh",
+            "ws_server": {
+                "listen_on": "127.0.0.1:3102",
+            },
+        });
+        value
+            .as_object_mut()
+            .unwrap()
+            .extend(mnemonic.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    };
+
+    let encrypted_only = settings(serde_json::json!({
+        "encrypted_mnemonic": encrypted,
+        "mnemonic_key": { "file": path },
+    }));
+    encrypted_only.validate().unwrap();
+    assert_eq!(encrypted_only.wallet_mnemonic().unwrap(), mnemonic);
+
+    let both = settings(serde_json::json!({
+        "mnemonic": MNEMONIC,
+        "encrypted_mnemonic": encrypted,
+        "mnemonic_key": { "file": path },
+    }));
+    assert!(both
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .starts_with("both mnemonic and encrypted_mnemonic are set"));
+
+    let no_key = settings(serde_json::json!({ "encrypted_mnemonic": encrypted }));
+    assert!(no_key.validate().is_err());
+    assert!(settings(serde_json::json!({})).validate().is_err());
+
+    std::fs::remove_file(&path).unwrap();
+}
@@ -1,9 +1,52 @@
-use sideswap_api::OrderId;
-use sqlx::types::Text;
+use std::collections::BTreeMap;
+
+use sideswap_api::{Hash32, OrderId};
+use sideswap_common::dealer_ticker::DealerTicker;
+use sqlx::types::{Json, Text};
+
+use crate::{api, approvals, idempotency, peg_notifs};
 
 #[derive(Clone)]
 pub struct Peg {
     pub order_id: Text<OrderId>,
+    pub addr_recv: Option<String>,
+    pub created_by: Option<String>,
+    /// When all peg transactions were done (in milliseconds)
+    pub completed_at: Option<i64>,
+}
+
+#[derive(Clone)]
+pub struct PegEvent {
+    pub order_id: Text<OrderId>,
+    pub created_at: i64,
+    pub tx_hash: Text<Hash32>,
+    pub vout: i64,
+    pub tx_state: Json<api::PegTxState>,
+    pub payout_txid: Option<Text<Hash32>>,
+}
+
+/// The latest state of a peg transaction
+#[derive(Clone)]
+pub struct PegTx {
+    pub order_id: Text<OrderId>,
+    pub tx_hash: Text<Hash32>,
+    pub vout: i64,
+    /// The paid amount (in sats)
+    pub amount: i64,
+    /// The server status string, as received
+    pub status: String,
+    pub payout_txid: Option<Text<Hash32>>,
+    pub payout_amount: Option<i64>,
+    /// When the transaction was detected or last changed (in milliseconds)
+    pub updated_at: i64,
+}
+
+#[derive(Clone)]
+pub struct PegNotifStage {
+    pub order_id: Text<OrderId>,
+    pub tx_hash: Text<Hash32>,
+    pub vout: i64,
+    pub stage: Json<peg_notifs::Stage>,
 }
 
 #[derive(Clone)]
@@ -11,11 +54,116 @@ pub struct MonitoredTx {
     pub txid: Text<elements::Txid>,
     pub description: Option<String>,
     pub user_note: Option<String>,
+    pub created_by: Option<String>,
+    /// The `SendTx` result, returned again if the sent transaction is sent once more (from the same wallet)
+    pub send_result: Option<Json<api::SendTxResp>>,
+    /// The wallet that sent the transaction with `SendTx`
+    pub wallet: Option<String>,
+    /// Milliseconds (see `migrations/22_monitored_tx_created_at.sql` for the older transactions)
+    pub created_at: i64,
 }
 
 #[derive(Clone)]
 pub struct Address {
+    pub wallet: String,
     pub ind: i64,
     pub address: Text<elements::Address>,
     pub user_note: Option<String>,
 }
+
+#[derive(Clone)]
+pub struct AllowedAddress {
+    pub address: Text<elements::Address>,
+    pub label: Option<String>,
+    pub added_by: Option<String>,
+    pub added_at: i64,
+}
+
+#[derive(Clone)]
+pub struct BalanceHistory {
+    pub wallet: String,
+    pub created_at: i64,
+    pub asset_id: Text<elements::AssetId>,
+    pub amount: i64,
+}
+
+#[derive(Clone)]
+pub struct PaymentReference {
+    pub id: i64,
+    pub reference: String,
+    pub kind: Json<api::ReferenceKind>,
+    pub target: String,
+}
+
+#[derive(Clone)]
+pub struct PendingApproval {
+    pub approval_id: String,
+    pub operation: Json<approvals::Operation>,
+    pub txid: Text<elements::Txid>,
+    pub description: String,
+    pub amounts: Json<BTreeMap<DealerTicker, f64>>,
+    pub requested_by: Option<String>,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+pub struct IdempotencyKey {
+    /// See `idempotency::KeyId`
+    pub client: String,
+    pub wallet: String,
+    pub kind: String,
+    pub key: String,
+    pub request: Json<idempotency::Request>,
+    /// The response JSON, set once the request succeeded
+    pub response: Option<String>,
+    pub created_at: i64,
+}
+
+/// Created but not sent transaction (see `CreateTx`)
+#[derive(Clone)]
+pub struct CreatedTx {
+    pub txid: Text<elements::Txid>,
+    pub wallet: String,
+    /// Serialized transaction (hex)
+    pub tx: String,
+    pub description: String,
+    pub created_by: Option<String>,
+    pub amounts: Json<BTreeMap<DealerTicker, f64>>,
+    pub created_at: i64,
+}
+
+/// Submitted maker order (see `SubmitOrder`), the swap outputs must pay these addresses
+#[derive(Clone)]
+pub struct MakerOrder {
+    pub order_id: i64,
+    pub receive_address: Text<elements::Address>,
+    pub change_address: Text<elements::Address>,
+    pub created_by: Option<String>,
+    pub created_at: i64,
+}
+
+/// Accepted quote (see `GetSwaps`), the fees are in the fee asset base units
+#[derive(Clone)]
+pub struct Swap {
+    pub quote_id: i64,
+    pub txid: Text<elements::Txid>,
+    pub send_ticker: Text<DealerTicker>,
+    pub send_amount: f64,
+    pub recv_ticker: Text<DealerTicker>,
+    pub recv_amount: f64,
+    /// The quote asset amount paid for one base asset (before fees)
+    pub price: f64,
+    pub fee_ticker: Text<DealerTicker>,
+    pub fee_amount: f64,
+    pub server_fee: i64,
+    pub fixed_fee: i64,
+    pub created_by: Option<String>,
+    pub created_at: i64,
+}
+
+#[cfg(test)]
+#[derive(Clone)]
+pub struct AuditEvent {
+    pub created_at: i64,
+    pub event: String,
+}
@@ -0,0 +1,86 @@
+use std::sync::{Arc, OnceLock};
+
+use prost::Message;
+
+use crate::api;
+
+pub mod proto;
+
+/// Notification encoding, selected per WS connection with the `notif_encoding` URL query parameter
+/// (for example `ws://127.0.0.1:3102/?notif_encoding=protobuf`)
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum NotifEncoding {
+    /// Text messages with the `api::From::Notif` JSON (default)
+    #[default]
+    Json,
+    /// Binary messages with the `Notif` message from `proto/notif.proto`
+    Protobuf,
+}
+
+impl std::str::FromStr for NotifEncoding {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(NotifEncoding::Json),
+            "protobuf" => Ok(NotifEncoding::Protobuf),
+            _ => Err(anyhow::anyhow!("unknown notification encoding: {s}")),
+        }
+    }
+}
+
+impl NotifEncoding {
+    pub fn from_query(query: Option<&str>) -> Result<NotifEncoding, anyhow::Error> {
+        let value = query
+            .unwrap_or_default()
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find_map(|(key, value)| (key == "notif_encoding").then_some(value));
+        match value {
+            Some(value) => value.parse(),
+            None => Ok(NotifEncoding::default()),
+        }
+    }
+}
+
+/// A notification shared between all clients of a broadcast.
+/// Every encoding is serialized at most once, on first use.
+pub struct EncodedNotif {
+    notif: api::Notif,
+    json: OnceLock<String>,
+    protobuf: OnceLock<Vec<u8>>,
+}
+
+pub type SharedNotif = Arc<EncodedNotif>;
+
+impl EncodedNotif {
+    pub fn new(notif: api::Notif) -> SharedNotif {
+        Arc::new(EncodedNotif {
+            notif,
+            json: OnceLock::new(),
+            protobuf: OnceLock::new(),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn notif(&self) -> &api::Notif {
+        &self.notif
+    }
+
+    pub fn json(&self) -> &str {
+        self.json.get_or_init(|| {
+            let from = api::From::Notif {
+                notif: self.notif.clone(),
+            };
+            serde_json::to_string(&from).expect("must not fail")
+        })
+    }
+
+    pub fn protobuf(&self) -> &[u8] {
+        self.protobuf
+            .get_or_init(|| proto::Notif::from(&self.notif).encode_to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,538 @@
+//! Messages from `proto/notif.proto`.
+//! Written with the prost derive macros directly (as prost-build would generate them),
+//! so building the manager does not require `protoc`. Must be kept in sync with the proto file.
+
+use crate::api;
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Balance {
+    #[prost(string, tag = "1")]
+    pub ticker: String,
+    #[prost(double, tag = "2")]
+    pub amount: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BalancesNotif {
+    #[prost(message, repeated, tag = "1")]
+    pub balances: Vec<Balance>,
+    #[prost(message, repeated, tag = "2")]
+    pub confirmed: Vec<Balance>,
+    #[prost(message, repeated, tag = "3")]
+    pub unconfirmed: Vec<Balance>,
+    #[prost(string, tag = "4")]
+    pub wallet: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum PegTxState {
+    InsufficientAmount = 0,
+    Detected = 1,
+    Processing = 2,
+    Done = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PegTxStatus {
+    #[prost(string, tag = "1")]
+    pub tx_hash: String,
+    #[prost(uint32, tag = "2")]
+    pub vout: u32,
+    #[prost(double, tag = "3")]
+    pub peg_amount: f64,
+    #[prost(double, optional, tag = "4")]
+    pub payout_amount: Option<f64>,
+    #[prost(enumeration = "PegTxState", tag = "5")]
+    pub tx_state: i32,
+    #[prost(uint32, optional, tag = "6")]
+    pub detected_confs: Option<u32>,
+    #[prost(uint32, optional, tag = "7")]
+    pub total_confs: Option<u32>,
+    #[prost(uint64, tag = "8")]
+    pub created_at: u64,
+    #[prost(string, optional, tag = "9")]
+    pub payout_txid: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PegStatus {
+    #[prost(string, tag = "1")]
+    pub order_id: String,
+    #[prost(bool, tag = "2")]
+    pub peg_in: bool,
+    #[prost(string, tag = "3")]
+    pub addr_server: String,
+    #[prost(string, tag = "4")]
+    pub addr_recv: String,
+    #[prost(message, repeated, tag = "5")]
+    pub list: Vec<PegTxStatus>,
+    #[prost(uint64, tag = "6")]
+    pub created_at: u64,
+    #[prost(string, optional, tag = "7")]
+    pub return_address: Option<String>,
+    #[prost(string, optional, tag = "8")]
+    pub reference: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PegStatusNotif {
+    #[prost(message, optional, tag = "1")]
+    pub peg: Option<PegStatus>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum AssetType {
+    Base = 0,
+    Quote = 1,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Market {
+    #[prost(string, tag = "1")]
+    pub base: String,
+    #[prost(string, tag = "2")]
+    pub quote: String,
+    #[prost(enumeration = "AssetType", tag = "3")]
+    pub fee_asset: i32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MarketsNotif {
+    #[prost(message, repeated, tag = "1")]
+    pub markets: Vec<Market>,
+    #[prost(bool, tag = "2")]
+    pub stale: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MarketPriceNotif {
+    #[prost(string, tag = "1")]
+    pub base: String,
+    #[prost(string, tag = "2")]
+    pub quote: String,
+    #[prost(double, optional, tag = "3")]
+    pub ind_price: Option<f64>,
+    #[prost(double, optional, tag = "4")]
+    pub last_price: Option<f64>,
+    #[prost(bool, tag = "5")]
+    pub stale: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct LockStatusNotif {
+    #[prost(bool, tag = "1")]
+    pub locked: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GapLimitWarningNotif {
+    #[prost(uint32, tag = "1")]
+    pub remaining: u32,
+    #[prost(uint32, tag = "2")]
+    pub first_unused_index: u32,
+    #[prost(bool, tag = "3")]
+    pub active: bool,
+    #[prost(string, tag = "4")]
+    pub wallet: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DrainingNotif {
+    #[prost(uint64, tag = "1")]
+    pub shutdown_at: u64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PegDepositDetectedNotif {
+    #[prost(string, tag = "1")]
+    pub order_id: String,
+    #[prost(string, tag = "2")]
+    pub txid: String,
+    #[prost(uint32, tag = "3")]
+    pub vout: u32,
+    #[prost(double, tag = "4")]
+    pub amount: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PegDepositConfirmedNotif {
+    #[prost(string, tag = "1")]
+    pub order_id: String,
+    #[prost(string, tag = "2")]
+    pub txid: String,
+    #[prost(uint32, tag = "3")]
+    pub vout: u32,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PegPayoutBroadcastNotif {
+    #[prost(string, tag = "1")]
+    pub order_id: String,
+    #[prost(string, tag = "2")]
+    pub txid: String,
+    #[prost(uint32, tag = "3")]
+    pub vout: u32,
+    #[prost(string, tag = "4")]
+    pub payout_txid: String,
+    #[prost(double, optional, tag = "5")]
+    pub payout_amount: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PegCompletedNotif {
+    #[prost(string, tag = "1")]
+    pub order_id: String,
+    #[prost(string, tag = "2")]
+    pub txid: String,
+    #[prost(uint32, tag = "3")]
+    pub vout: u32,
+    #[prost(double, optional, tag = "4")]
+    pub payout_amount: Option<f64>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PegFailedNotif {
+    #[prost(string, tag = "1")]
+    pub order_id: String,
+    #[prost(string, tag = "2")]
+    pub txid: String,
+    #[prost(uint32, tag = "3")]
+    pub vout: u32,
+    #[prost(string, tag = "4")]
+    pub reason: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ConfigReloadedNotif {
+    #[prost(string, repeated, tag = "1")]
+    pub changed_fields: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum TxStatus {
+    Mempool = 0,
+    Confirmed = 1,
+    NotFound = 2,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TxStatusNotif {
+    #[prost(string, tag = "1")]
+    pub txid: String,
+    #[prost(enumeration = "TxStatus", tag = "2")]
+    pub status: i32,
+    #[prost(string, optional, tag = "3")]
+    pub reference: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ApprovalState {
+    Approved = 0,
+    Failed = 1,
+    Rejected = 2,
+    Expired = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ApprovalResolvedNotif {
+    #[prost(string, tag = "1")]
+    pub approval_id: String,
+    #[prost(enumeration = "ApprovalState", tag = "2")]
+    pub state: i32,
+    #[prost(string, optional, tag = "3")]
+    pub txid: Option<String>,
+    #[prost(string, optional, tag = "4")]
+    pub error: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MarketAddedNotif {
+    #[prost(message, optional, tag = "1")]
+    pub market: Option<Market>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct MarketRemovedNotif {
+    #[prost(string, tag = "1")]
+    pub base: String,
+    #[prost(string, tag = "2")]
+    pub quote: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct PegCompleteNotif {
+    #[prost(string, tag = "1")]
+    pub order_id: String,
+    #[prost(double, tag = "2")]
+    pub amount_received: f64,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct ServerConnectionNotif {
+    #[prost(bool, tag = "1")]
+    pub connected: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TxNoteNotif {
+    #[prost(string, tag = "1")]
+    pub txid: String,
+    #[prost(string, tag = "2")]
+    pub description: String,
+    #[prost(string, optional, tag = "3")]
+    pub user_note: Option<String>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Notif {
+    #[prost(
+        oneof = "notif::Notif",
+        tags = "1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20"
+    )]
+    pub notif: Option<notif::Notif>,
+}
+
+pub mod notif {
+    #[derive(Clone, PartialEq, prost::Oneof)]
+    pub enum Notif {
+        #[prost(message, tag = "1")]
+        Balances(super::BalancesNotif),
+        #[prost(message, tag = "2")]
+        PegStatus(super::PegStatusNotif),
+        #[prost(message, tag = "3")]
+        Markets(super::MarketsNotif),
+        #[prost(message, tag = "4")]
+        MarketPrice(super::MarketPriceNotif),
+        #[prost(message, tag = "5")]
+        LockStatus(super::LockStatusNotif),
+        #[prost(message, tag = "6")]
+        GapLimitWarning(super::GapLimitWarningNotif),
+        #[prost(message, tag = "7")]
+        Draining(super::DrainingNotif),
+        #[prost(message, tag = "8")]
+        PegDepositDetected(super::PegDepositDetectedNotif),
+        #[prost(message, tag = "9")]
+        PegDepositConfirmed(super::PegDepositConfirmedNotif),
+        #[prost(message, tag = "10")]
+        PegPayoutBroadcast(super::PegPayoutBroadcastNotif),
+        #[prost(message, tag = "11")]
+        PegCompleted(super::PegCompletedNotif),
+        #[prost(message, tag = "12")]
+        PegFailed(super::PegFailedNotif),
+        #[prost(message, tag = "13")]
+        ConfigReloaded(super::ConfigReloadedNotif),
+        #[prost(message, tag = "14")]
+        TxStatus(super::TxStatusNotif),
+        #[prost(message, tag = "15")]
+        ApprovalResolved(super::ApprovalResolvedNotif),
+        #[prost(message, tag = "16")]
+        MarketAdded(super::MarketAddedNotif),
+        #[prost(message, tag = "17")]
+        MarketRemoved(super::MarketRemovedNotif),
+        #[prost(message, tag = "18")]
+        PegComplete(super::PegCompleteNotif),
+        #[prost(message, tag = "19")]
+        ServerConnection(super::ServerConnectionNotif),
+        #[prost(message, tag = "20")]
+        TxNote(super::TxNoteNotif),
+    }
+}
+
+fn convert_market(market: &api::Market) -> Market {
+    Market {
+        base: market.base.to_string(),
+        quote: market.quote.to_string(),
+        fee_asset: convert_asset_type(market.fee_asset).into(),
+    }
+}
+
+fn convert_balances(balances: &api::Balances) -> Vec<Balance> {
+    balances
+        .iter()
+        .map(|(ticker, amount)| Balance {
+            ticker: ticker.to_string(),
+            amount: *amount,
+        })
+        .collect()
+}
+
+fn convert_peg_tx_state(tx_state: api::PegTxState) -> PegTxState {
+    match tx_state {
+        api::PegTxState::InsufficientAmount => PegTxState::InsufficientAmount,
+        api::PegTxState::Detected => PegTxState::Detected,
+        api::PegTxState::Processing => PegTxState::Processing,
+        api::PegTxState::Done => PegTxState::Done,
+    }
+}
+
+fn convert_peg_status(peg: &api::PegStatus) -> PegStatus {
+    PegStatus {
+        order_id: peg.order_id.to_string(),
+        peg_in: peg.peg_in,
+        addr_server: peg.addr_server.clone(),
+        addr_recv: peg.addr_recv.clone(),
+        list: peg
+            .list
+            .iter()
+            .map(|tx| PegTxStatus {
+                tx_hash: tx.tx_hash.to_string(),
+                vout: tx.vout,
+                peg_amount: tx.peg_amount,
+                payout_amount: tx.payout_amount,
+                tx_state: convert_peg_tx_state(tx.tx_state).into(),
+                detected_confs: tx.detected_confs,
+                total_confs: tx.total_confs,
+                created_at: tx.created_at.millis(),
+                payout_txid: tx.payout_txid.map(|txid| txid.to_string()),
+            })
+            .collect(),
+        created_at: peg.created_at.millis(),
+        return_address: peg.return_address.clone(),
+        reference: peg.reference.clone(),
+    }
+}
+
+fn convert_tx_status(status: api::TxStatus) -> TxStatus {
+    match status {
+        api::TxStatus::Mempool => TxStatus::Mempool,
+        api::TxStatus::Confirmed => TxStatus::Confirmed,
+        api::TxStatus::NotFound => TxStatus::NotFound,
+    }
+}
+
+fn convert_approval_state(state: api::ApprovalState) -> ApprovalState {
+    match state {
+        api::ApprovalState::Approved => ApprovalState::Approved,
+        api::ApprovalState::Failed => ApprovalState::Failed,
+        api::ApprovalState::Rejected => ApprovalState::Rejected,
+        api::ApprovalState::Expired => ApprovalState::Expired,
+    }
+}
+
+fn convert_asset_type(asset_type: api::AssetType) -> AssetType {
+    match asset_type {
+        api::AssetType::Base => AssetType::Base,
+        api::AssetType::Quote => AssetType::Quote,
+    }
+}
+
+impl From<&api::Notif> for Notif {
+    fn from(value: &api::Notif) -> Self {
+        let notif = match value {
+            api::Notif::Balances(notif) => notif::Notif::Balances(BalancesNotif {
+                balances: convert_balances(&notif.balances),
+                confirmed: convert_balances(&notif.confirmed),
+                unconfirmed: convert_balances(&notif.unconfirmed),
+                wallet: notif.wallet.clone(),
+            }),
+            api::Notif::PegStatus(notif) => notif::Notif::PegStatus(PegStatusNotif {
+                peg: Some(convert_peg_status(&notif.peg)),
+            }),
+            api::Notif::Markets(notif) => notif::Notif::Markets(MarketsNotif {
+                markets: notif.markets.iter().map(convert_market).collect(),
+                stale: notif.stale,
+            }),
+            api::Notif::MarketPrice(notif) => notif::Notif::MarketPrice(MarketPriceNotif {
+                base: notif.base.to_string(),
+                quote: notif.quote.to_string(),
+                ind_price: notif.ind_price,
+                last_price: notif.last_price,
+                stale: notif.stale,
+            }),
+            api::Notif::LockStatus(notif) => notif::Notif::LockStatus(LockStatusNotif {
+                locked: notif.locked,
+            }),
+            api::Notif::GapLimitWarning(notif) => {
+                notif::Notif::GapLimitWarning(GapLimitWarningNotif {
+                    remaining: notif.remaining,
+                    first_unused_index: notif.first_unused_index,
+                    active: notif.active,
+                    wallet: notif.wallet.clone(),
+                })
+            }
+            api::Notif::Draining(notif) => notif::Notif::Draining(DrainingNotif {
+                shutdown_at: notif.shutdown_at.millis(),
+            }),
+            api::Notif::PegDepositDetected(notif) => {
+                notif::Notif::PegDepositDetected(PegDepositDetectedNotif {
+                    order_id: notif.order_id.to_string(),
+                    txid: notif.txid.to_string(),
+                    vout: notif.vout,
+                    amount: notif.amount,
+                })
+            }
+            api::Notif::PegDepositConfirmed(notif) => {
+                notif::Notif::PegDepositConfirmed(PegDepositConfirmedNotif {
+                    order_id: notif.order_id.to_string(),
+                    txid: notif.txid.to_string(),
+                    vout: notif.vout,
+                })
+            }
+            api::Notif::PegPayoutBroadcast(notif) => {
+                notif::Notif::PegPayoutBroadcast(PegPayoutBroadcastNotif {
+                    order_id: notif.order_id.to_string(),
+                    txid: notif.txid.to_string(),
+                    vout: notif.vout,
+                    payout_txid: notif.payout_txid.to_string(),
+                    payout_amount: notif.payout_amount,
+                })
+            }
+            api::Notif::PegCompleted(notif) => notif::Notif::PegCompleted(PegCompletedNotif {
+                order_id: notif.order_id.to_string(),
+                txid: notif.txid.to_string(),
+                vout: notif.vout,
+                payout_amount: notif.payout_amount,
+            }),
+            api::Notif::PegFailed(notif) => notif::Notif::PegFailed(PegFailedNotif {
+                order_id: notif.order_id.to_string(),
+                txid: notif.txid.to_string(),
+                vout: notif.vout,
+                reason: notif.reason.clone(),
+            }),
+            api::Notif::ConfigReloaded(notif) => {
+                notif::Notif::ConfigReloaded(ConfigReloadedNotif {
+                    changed_fields: notif.changed_fields.clone(),
+                })
+            }
+            api::Notif::TxStatus(notif) => notif::Notif::TxStatus(TxStatusNotif {
+                txid: notif.txid.to_string(),
+                status: convert_tx_status(notif.status).into(),
+                reference: notif.reference.clone(),
+            }),
+            api::Notif::ApprovalResolved(notif) => {
+                notif::Notif::ApprovalResolved(ApprovalResolvedNotif {
+                    approval_id: notif.approval_id.clone(),
+                    state: convert_approval_state(notif.state).into(),
+                    txid: notif.txid.map(|txid| txid.to_string()),
+                    error: notif.error.clone(),
+                })
+            }
+            api::Notif::MarketAdded(notif) => notif::Notif::MarketAdded(MarketAddedNotif {
+                market: Some(convert_market(&notif.market)),
+            }),
+            api::Notif::MarketRemoved(notif) => notif::Notif::MarketRemoved(MarketRemovedNotif {
+                base: notif.base.to_string(),
+                quote: notif.quote.to_string(),
+            }),
+            api::Notif::PegComplete(notif) => notif::Notif::PegComplete(PegCompleteNotif {
+                order_id: notif.order_id.to_string(),
+                amount_received: notif.amount_received,
+            }),
+            api::Notif::ServerConnection(notif) => {
+                notif::Notif::ServerConnection(ServerConnectionNotif {
+                    connected: notif.connected,
+                })
+            }
+            api::Notif::TxNote(notif) => notif::Notif::TxNote(TxNoteNotif {
+                txid: notif.txid.to_string(),
+                description: notif.description.clone(),
+                user_note: notif.user_note.clone(),
+            }),
+        };
+        Notif { notif: Some(notif) }
+    }
+}
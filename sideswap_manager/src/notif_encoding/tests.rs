@@ -0,0 +1,262 @@
+use elements::hashes::Hash;
+use sideswap_common::dealer_ticker::DealerTicker;
+use sideswap_types::timestamp_ms::TimestampMs;
+
+use super::*;
+
+fn variant_name(notif: &api::Notif) -> &'static str {
+    match notif {
+        api::Notif::Balances(_) => "Balances",
+        api::Notif::PegStatus(_) => "PegStatus",
+        api::Notif::Markets(_) => "Markets",
+        api::Notif::MarketPrice(_) => "MarketPrice",
+        api::Notif::LockStatus(_) => "LockStatus",
+        api::Notif::GapLimitWarning(_) => "GapLimitWarning",
+        api::Notif::Draining(_) => "Draining",
+        api::Notif::PegDepositDetected(_) => "PegDepositDetected",
+        api::Notif::PegDepositConfirmed(_) => "PegDepositConfirmed",
+        api::Notif::PegPayoutBroadcast(_) => "PegPayoutBroadcast",
+        api::Notif::PegCompleted(_) => "PegCompleted",
+        api::Notif::PegFailed(_) => "PegFailed",
+        api::Notif::ConfigReloaded(_) => "ConfigReloaded",
+        api::Notif::TxStatus(_) => "TxStatus",
+        api::Notif::ApprovalResolved(_) => "ApprovalResolved",
+        api::Notif::MarketAdded(_) => "MarketAdded",
+        api::Notif::MarketRemoved(_) => "MarketRemoved",
+        api::Notif::PegComplete(_) => "PegComplete",
+        api::Notif::ServerConnection(_) => "ServerConnection",
+        api::Notif::TxNote(_) => "TxNote",
+    }
+}
+
+/// One notification of every variant (add new variants to `variant_name` too)
+fn sample_notifs() -> Vec<api::Notif> {
+    let tx_hash = sideswap_api::HashN([1; 32]);
+    vec![
+        api::Notif::Balances(api::BalancesNotif {
+            wallet: "default".to_owned(),
+            balances: [(DealerTicker::LBTC, 0.5), (DealerTicker::USDT, 100.25)].into(),
+            confirmed: [(DealerTicker::LBTC, 0.25)].into(),
+            unconfirmed: [(DealerTicker::LBTC, 0.25), (DealerTicker::USDT, 100.25)].into(),
+        }),
+        api::Notif::PegStatus(api::PegStatusNotif {
+            peg: api::PegStatus {
+                order_id: sideswap_api::HashN([2; 32]),
+                peg_in: true,
+                addr_server: "bc1qserver".to_owned(),
+                addr_recv: "lq1qrecv".to_owned(),
+                list: vec![api::PegTxStatus {
+                    tx_hash,
+                    vout: 1,
+                    peg_amount: 0.01,
+                    payout_amount: Some(0.0099),
+                    tx_state: api::PegTxState::Done,
+                    detected_confs: None,
+                    total_confs: None,
+                    created_at: TimestampMs::from_millis(1_700_000_000_000),
+                    payout_txid: Some(tx_hash),
+                }],
+                created_at: TimestampMs::from_millis(1_690_000_000_000),
+                return_address: None,
+                reference: Some("0000-016".to_owned()),
+            },
+        }),
+        api::Notif::Markets(api::MarketsNotif {
+            markets: vec![api::Market {
+                base: DealerTicker::LBTC,
+                quote: DealerTicker::USDT,
+                fee_asset: api::AssetType::Quote,
+            }],
+            stale: false,
+        }),
+        api::Notif::MarketPrice(api::MarketPriceNotif {
+            base: DealerTicker::LBTC,
+            quote: DealerTicker::USDT,
+            ind_price: Some(95000.5),
+            last_price: None,
+            stale: true,
+        }),
+        api::Notif::LockStatus(api::LockStatusNotif { locked: true }),
+        api::Notif::GapLimitWarning(api::GapLimitWarningNotif {
+            wallet: "default".to_owned(),
+            remaining: 2,
+            first_unused_index: 10,
+            active: true,
+        }),
+        api::Notif::Draining(api::DrainingNotif {
+            shutdown_at: TimestampMs::from_millis(1_700_000_300_000),
+        }),
+        api::Notif::PegDepositDetected(api::PegDepositDetectedNotif {
+            order_id: sideswap_api::HashN([2; 32]),
+            txid: tx_hash,
+            vout: 1,
+            amount: 0.01,
+        }),
+        api::Notif::PegDepositConfirmed(api::PegDepositConfirmedNotif {
+            order_id: sideswap_api::HashN([2; 32]),
+            txid: tx_hash,
+            vout: 1,
+        }),
+        api::Notif::PegPayoutBroadcast(api::PegPayoutBroadcastNotif {
+            order_id: sideswap_api::HashN([2; 32]),
+            txid: tx_hash,
+            vout: 1,
+            payout_txid: sideswap_api::HashN([3; 32]),
+            payout_amount: Some(0.0099),
+        }),
+        api::Notif::PegCompleted(api::PegCompletedNotif {
+            order_id: sideswap_api::HashN([2; 32]),
+            txid: tx_hash,
+            vout: 1,
+            payout_amount: None,
+        }),
+        api::Notif::PegFailed(api::PegFailedNotif {
+            order_id: sideswap_api::HashN([2; 32]),
+            txid: tx_hash,
+            vout: 1,
+            reason: "too small".to_owned(),
+        }),
+        api::Notif::ConfigReloaded(api::ConfigReloadedNotif {
+            changed_fields: vec!["client_quotas".to_owned()],
+        }),
+        api::Notif::TxStatus(api::TxStatusNotif {
+            txid: elements::Txid::from_byte_array([2; 32]),
+            status: api::TxStatus::Confirmed,
+            reference: Some("0000-016".to_owned()),
+        }),
+        api::Notif::ApprovalResolved(api::ApprovalResolvedNotif {
+            approval_id: "4f1c2b7e9a0d3e6f".to_owned(),
+            state: api::ApprovalState::Rejected,
+            txid: None,
+            error: Some("not expected".to_owned()),
+        }),
+        api::Notif::MarketAdded(api::MarketAddedNotif {
+            market: api::Market {
+                base: DealerTicker::LBTC,
+                quote: DealerTicker::USDT,
+                fee_asset: api::AssetType::Base,
+            },
+        }),
+        api::Notif::MarketRemoved(api::MarketRemovedNotif {
+            base: DealerTicker::LBTC,
+            quote: DealerTicker::USDT,
+        }),
+        api::Notif::PegComplete(api::PegCompleteNotif {
+            order_id: sideswap_api::HashN([2; 32]),
+            amount_received: 0.0099,
+        }),
+        api::Notif::ServerConnection(api::ServerConnectionNotif { connected: false }),
+        api::Notif::TxNote(api::TxNoteNotif {
+            txid: elements::Txid::from_byte_array([2; 32]),
+            description: "send 0.001 L-BTC".to_owned(),
+            user_note: None,
+        }),
+    ]
+}
+
+#[test]
+fn samples_cover_all_variants() {
+    let names = sample_notifs()
+        .iter()
+        .map(variant_name)
+        .collect::<std::collections::BTreeSet<_>>();
+    assert_eq!(names.len(), 20);
+}
+
+#[test]
+fn json_round_trip() {
+    for notif in sample_notifs() {
+        let encoded = EncodedNotif::new(notif);
+        let value = serde_json::from_str::<serde_json::Value>(encoded.json()).unwrap();
+        let expected = serde_json::to_value(api::From::Notif {
+            notif: encoded.notif().clone(),
+        })
+        .unwrap();
+        assert_eq!(value, expected);
+        assert!(value["Notif"]["notif"][variant_name(encoded.notif())].is_object());
+    }
+}
+
+#[test]
+fn protobuf_round_trip() {
+    for notif in sample_notifs() {
+        let encoded = EncodedNotif::new(notif);
+        let decoded = proto::Notif::decode(encoded.protobuf()).unwrap();
+        assert_eq!(decoded, proto::Notif::from(encoded.notif()));
+        assert!(decoded.notif.is_some());
+    }
+}
+
+#[test]
+fn protobuf_values() {
+    let notifs = sample_notifs();
+
+    let balances = EncodedNotif::new(notifs[0].clone());
+    match proto::Notif::decode(balances.protobuf()).unwrap().notif {
+        Some(proto::notif::Notif::Balances(notif)) => {
+            assert_eq!(
+                notif.balances,
+                [
+                    proto::Balance {
+                        ticker: "L-BTC".to_owned(),
+                        amount: 0.5,
+                    },
+                    proto::Balance {
+                        ticker: "USDt".to_owned(),
+                        amount: 100.25,
+                    },
+                ]
+            );
+            assert_eq!(notif.confirmed.len(), 1);
+        }
+        _ => panic!("balances expected"),
+    }
+
+    let peg = EncodedNotif::new(notifs[1].clone());
+    match proto::Notif::decode(peg.protobuf()).unwrap().notif {
+        Some(proto::notif::Notif::PegStatus(notif)) => {
+            let peg = notif.peg.unwrap();
+            assert_eq!(peg.order_id, "02".repeat(32));
+            assert_eq!(peg.created_at, 1_690_000_000_000);
+            assert_eq!(peg.list[0].tx_state(), proto::PegTxState::Done);
+            assert_eq!(peg.list[0].payout_txid, Some("01".repeat(32)));
+            assert_eq!(peg.list[0].detected_confs, None);
+        }
+        _ => panic!("peg status expected"),
+    }
+
+    let price = EncodedNotif::new(notifs[3].clone());
+    match proto::Notif::decode(price.protobuf()).unwrap().notif {
+        Some(proto::notif::Notif::MarketPrice(notif)) => {
+            assert_eq!(notif.ind_price, Some(95000.5));
+            assert_eq!(notif.last_price, None);
+            assert!(notif.stale);
+        }
+        _ => panic!("market price expected"),
+    }
+}
+
+#[test]
+fn encoded_once_per_broadcast() {
+    let encoded = EncodedNotif::new(sample_notifs().remove(0));
+    let clients = [encoded.clone(), encoded.clone()];
+    assert!(std::ptr::eq(clients[0].json(), clients[1].json()));
+    assert!(std::ptr::eq(clients[0].protobuf(), clients[1].protobuf()));
+}
+
+#[test]
+fn encoding_from_query() {
+    assert_eq!(
+        NotifEncoding::from_query(None).unwrap(),
+        NotifEncoding::Json
+    );
+    assert_eq!(
+        NotifEncoding::from_query(Some("a=1&notif_encoding=protobuf")).unwrap(),
+        NotifEncoding::Protobuf
+    );
+    assert_eq!(
+        NotifEncoding::from_query(Some("notif_encoding=json")).unwrap(),
+        NotifEncoding::Json
+    );
+    assert!(NotifEncoding::from_query(Some("notif_encoding=cbor")).is_err());
+}
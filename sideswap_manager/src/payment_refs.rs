@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use sqlx::types::Json;
+
+use crate::{api, models};
+
+/// Crockford base32 alphabet (without I, L, O and U)
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// The minimal number of digits (before the check digit)
+const MIN_DIGITS: usize = 6;
+
+/// Weighted sum of the digits modulo 31 (a prime number), catches single digit typos and swapped neighbours
+fn check_digit(digits: &[u8]) -> u8 {
+    let sum = digits
+        .iter()
+        .enumerate()
+        .map(|(index, digit)| (index as u32 % 30 + 1) * u32::from(*digit))
+        .sum::<u32>();
+    (sum % 31) as u8
+}
+
+fn format(digits: &[u8]) -> String {
+    let chars = digits
+        .iter()
+        .map(|digit| char::from(ALPHABET[usize::from(*digit)]))
+        .collect::<String>();
+    let (head, tail) = chars.split_at(chars.len() - 3);
+    format!("{head}-{tail}")
+}
+
+/// Reference for the counter value, for example `0000-016` for 1.
+/// The last character is the check digit.
+pub fn encode(id: u64) -> String {
+    let mut digits = Vec::new();
+    let mut value = id;
+    while value != 0 || digits.len() < MIN_DIGITS {
+        digits.push((value % 32) as u8);
+        value /= 32;
+    }
+    digits.reverse();
+    digits.push(check_digit(&digits));
+    format(&digits)
+}
+
+/// Accepts the references in lower case, without `-` and with the commonly confused characters
+/// (`I` and `L` for `1`, `O` for `0`), returns the canonical form if the check digit matches
+pub fn normalize(reference: &str) -> Option<String> {
+    let mut digits = Vec::new();
+    for c in reference
+        .chars()
+        .filter(|c| *c != '-' && !c.is_whitespace())
+    {
+        let c = match c.to_ascii_uppercase() {
+            'I' | 'L' => '1',
+            'O' => '0',
+            c => c,
+        };
+        let digit = ALPHABET.iter().position(|a| char::from(*a) == c)?;
+        digits.push(digit as u8);
+    }
+    let (check, body) = digits.split_last()?;
+    (body.len() >= MIN_DIGITS && check_digit(body) == *check).then(|| format(&digits))
+}
+
+/// All assigned references (loaded from the DB on startup)
+pub struct PaymentRefs {
+    by_reference: BTreeMap<String, (api::ReferenceKind, String)>,
+    by_target: BTreeMap<(api::ReferenceKind, String), String>,
+    next_id: i64,
+}
+
+impl PaymentRefs {
+    pub fn new(rows: Vec<models::PaymentReference>) -> PaymentRefs {
+        let mut refs = PaymentRefs {
+            by_reference: BTreeMap::new(),
+            by_target: BTreeMap::new(),
+            next_id: 1,
+        };
+        for row in rows {
+            refs.insert(row);
+        }
+        refs
+    }
+
+    pub fn get(&self, kind: api::ReferenceKind, target: &str) -> Option<&str> {
+        self.by_target
+            .get(&(kind, target.to_owned()))
+            .map(String::as_str)
+    }
+
+    /// The resource kind and the target (txid or peg order id) for the canonical reference
+    pub fn find(&self, reference: &str) -> Option<(api::ReferenceKind, &str)> {
+        self.by_reference
+            .get(reference)
+            .map(|(kind, target)| (*kind, target.as_str()))
+    }
+
+    /// The next candidate reference, must be saved to the DB and inserted
+    pub fn next_row(&mut self, kind: api::ReferenceKind, target: &str) -> models::PaymentReference {
+        let id = self.next_id;
+        self.next_id += 1;
+        models::PaymentReference {
+            id,
+            reference: encode(id as u64),
+            kind: Json(kind),
+            target: target.to_owned(),
+        }
+    }
+
+    pub fn insert(&mut self, row: models::PaymentReference) {
+        self.next_id = self.next_id.max(row.id + 1);
+        self.by_target
+            .insert((row.kind.0, row.target.clone()), row.reference.clone());
+        self.by_reference
+            .insert(row.reference, (row.kind.0, row.target));
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,78 @@
+use super::*;
+
+#[test]
+fn references_encoded() {
+    assert_eq!(encode(0), "0000-000");
+    assert_eq!(encode(1), "0000-016");
+    assert_eq!(encode(33), "0000-11B");
+    assert_eq!(encode(32u64.pow(6)).len(), "0000000-X".len());
+
+    for id in [0, 1, 33, 123_456, 32u64.pow(6) - 1, 32u64.pow(6), u64::MAX] {
+        let reference = encode(id);
+        assert_eq!(normalize(&reference).as_deref(), Some(reference.as_str()));
+    }
+}
+
+#[test]
+fn references_normalized() {
+    let reference = encode(1001);
+    assert_eq!(
+        normalize(&reference.to_lowercase()),
+        Some(reference.clone())
+    );
+    assert_eq!(
+        normalize(&reference.replace('-', "")),
+        Some(reference.clone())
+    );
+    assert_eq!(normalize("oooo-oi6").as_deref(), Some("0000-016"));
+
+    assert_eq!(normalize(""), None);
+    assert_eq!(normalize("0000-01"), None);
+    assert_eq!(normalize("0000-01U"), None);
+}
+
+#[test]
+fn typos_detected() {
+    let reference = encode(123_456).replace('-', "");
+    for index in 0..reference.len() {
+        for c in ALPHABET.iter().map(|c| char::from(*c)) {
+            let mut typo = reference.clone().into_bytes();
+            if typo[index] as char == c {
+                continue;
+            }
+            typo[index] = c as u8;
+            let typo = String::from_utf8(typo).unwrap();
+            // 0 and Z are the same modulo 31
+            if !matches!((reference.as_bytes()[index], c), (b'0', 'Z') | (b'Z', '0')) {
+                assert_eq!(normalize(&typo), None, "{typo}");
+            }
+        }
+    }
+
+    let mut swapped = reference.into_bytes();
+    swapped.swap(1, 2);
+    assert_eq!(normalize(&String::from_utf8(swapped).unwrap()), None);
+}
+
+#[test]
+fn refs_lookup() {
+    let mut refs = PaymentRefs::new(vec![models::PaymentReference {
+        id: 5,
+        reference: encode(5),
+        kind: Json(api::ReferenceKind::Peg),
+        target: "order".to_owned(),
+    }]);
+    assert_eq!(
+        refs.get(api::ReferenceKind::Peg, "order"),
+        Some(encode(5).as_str())
+    );
+    assert_eq!(refs.get(api::ReferenceKind::Tx, "order"), None);
+
+    let row = refs.next_row(api::ReferenceKind::Tx, "txid");
+    assert_eq!(row.id, 6);
+    refs.insert(row);
+    assert_eq!(
+        refs.find(&encode(6)),
+        Some((api::ReferenceKind::Tx, "txid"))
+    );
+}
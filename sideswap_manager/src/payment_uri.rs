@@ -0,0 +1,72 @@
+use std::str::FromStr;
+
+use elements::AssetId;
+use sideswap_common::{abort, verify};
+
+/// Liquid and Liquid Testnet
+const SCHEMES: [&str; 2] = ["liquidnetwork", "liquidtestnet"];
+
+/// The longest accepted label (it is added to the tx description)
+const MAX_LABEL_LEN: usize = 256;
+
+#[derive(Debug, PartialEq)]
+pub struct PaymentUri {
+    /// Not checked here, validated as a recipient address
+    pub address: String,
+    pub amount: Option<f64>,
+    pub asset_id: Option<AssetId>,
+    pub label: Option<String>,
+}
+
+/// Parses `liquidnetwork:<address>?amount=<amount>&assetid=<asset_id>&label=<label>`.
+/// Unknown parameters are ignored, except the `req-` ones (they must be understood by BIP21).
+pub fn parse(uri: &str) -> Result<PaymentUri, String> {
+    let url = url::Url::parse(uri.trim()).map_err(|err| err.to_string())?;
+    verify!(
+        SCHEMES.contains(&url.scheme()),
+        format!("unsupported URI scheme {}", url.scheme())
+    );
+    verify!(url.cannot_be_a_base(), "invalid URI".to_owned());
+    verify!(!url.path().is_empty(), "the address is missing".to_owned());
+
+    let mut parsed = PaymentUri {
+        address: url.path().to_owned(),
+        amount: None,
+        asset_id: None,
+        label: None,
+    };
+    let mut seen = Vec::new();
+    for (name, value) in url.query_pairs() {
+        verify!(!seen.contains(&name), format!("duplicate parameter {name}"));
+        match name.as_ref() {
+            "amount" => {
+                let amount = f64::from_str(&value)
+                    .ok()
+                    .filter(|amount| amount.is_finite() && *amount > 0.0)
+                    .ok_or_else(|| format!("invalid amount {value}"))?;
+                parsed.amount = Some(amount);
+            }
+            "assetid" => {
+                let asset_id =
+                    AssetId::from_str(&value).map_err(|_err| format!("invalid assetid {value}"))?;
+                parsed.asset_id = Some(asset_id);
+            }
+            "label" => {
+                verify!(
+                    value.len() <= MAX_LABEL_LEN,
+                    format!("the label is longer than {MAX_LABEL_LEN} bytes")
+                );
+                parsed.label = Some(value.into_owned());
+            }
+            name if name.starts_with("req-") => {
+                abort!(format!("unsupported required parameter {name}"))
+            }
+            _ => {}
+        }
+        seen.push(name);
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,49 @@
+use super::*;
+
+const LBTC: &str = "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d";
+
+#[test]
+fn uri_parsed() {
+    assert_eq!(
+        parse(&format!(
+            "liquidnetwork:VJLaddr?amount=0.001&assetid={LBTC}&label=Invoice%2042&message=ignored"
+        ))
+        .unwrap(),
+        PaymentUri {
+            address: "VJLaddr".to_owned(),
+            amount: Some(0.001),
+            asset_id: Some(AssetId::from_str(LBTC).unwrap()),
+            label: Some("Invoice 42".to_owned()),
+        }
+    );
+    assert_eq!(
+        parse(" LiquidTestnet:tlq1addr ").unwrap(),
+        PaymentUri {
+            address: "tlq1addr".to_owned(),
+            amount: None,
+            asset_id: None,
+            label: None,
+        }
+    );
+}
+
+#[test]
+fn invalid_uris_rejected() {
+    for uri in [
+        "VJLaddr",
+        "bitcoin:bc1addr?amount=1",
+        "liquidnetwork:",
+        "liquidnetwork:?amount=1",
+        "liquidnetwork://VJLaddr",
+        "liquidnetwork:VJLaddr?amount=0",
+        "liquidnetwork:VJLaddr?amount=-1",
+        "liquidnetwork:VJLaddr?amount=abc",
+        "liquidnetwork:VJLaddr?amount=1&amount=2",
+        "liquidnetwork:VJLaddr?assetid=6f02",
+        "liquidnetwork:VJLaddr?req-somethingnew=1",
+    ] {
+        assert!(parse(uri).is_err(), "{uri}");
+    }
+    let long_label = "a".repeat(MAX_LABEL_LEN + 1);
+    assert!(parse(&format!("liquidnetwork:VJLaddr?label={long_label}")).is_err());
+}
@@ -0,0 +1,114 @@
+//! Translates the raw peg statuses into semantic notifications (deposit detected, confirmed, paid out).
+//! The last reached stage of every peg transaction is stored in the DB,
+//! so resent statuses and restarts do not repeat the notifications.
+
+use serde::{Deserialize, Serialize};
+use sideswap_api::OrderId;
+
+use crate::api;
+
+/// Sent as `PegFailedNotif::reason` for `InsufficientAmount` transactions
+pub const INSUFFICIENT_AMOUNT_REASON: &str =
+    "the peg amount is less than the minimum and will not be processed";
+
+/// The last semantic notification sent for a peg transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Stage {
+    Detected,
+    Confirmed,
+    PayoutBroadcast,
+    Completed,
+    Failed,
+}
+
+/// The stages reached on the way to `Completed`, in order
+const PROGRESS: [Stage; 4] = [
+    Stage::Detected,
+    Stage::Confirmed,
+    Stage::PayoutBroadcast,
+    Stage::Completed,
+];
+
+fn target_stage(tx: &api::PegTxStatus) -> Stage {
+    match tx.tx_state {
+        api::PegTxState::InsufficientAmount => Stage::Failed,
+        api::PegTxState::Detected => Stage::Detected,
+        api::PegTxState::Processing if tx.payout_txid.is_some() => Stage::PayoutBroadcast,
+        api::PegTxState::Processing => Stage::Confirmed,
+        api::PegTxState::Done => Stage::Completed,
+    }
+}
+
+fn notif(order_id: OrderId, tx: &api::PegTxStatus, stage: Stage) -> Option<api::Notif> {
+    let txid = tx.tx_hash;
+    let vout = tx.vout;
+    let notif = match stage {
+        Stage::Detected => api::Notif::PegDepositDetected(api::PegDepositDetectedNotif {
+            order_id,
+            txid,
+            vout,
+            amount: tx.peg_amount,
+        }),
+        Stage::Confirmed => api::Notif::PegDepositConfirmed(api::PegDepositConfirmedNotif {
+            order_id,
+            txid,
+            vout,
+        }),
+        Stage::PayoutBroadcast => api::Notif::PegPayoutBroadcast(api::PegPayoutBroadcastNotif {
+            order_id,
+            txid,
+            vout,
+            // Not reported if the payout txid is not known
+            payout_txid: tx.payout_txid?,
+            payout_amount: tx.payout_amount,
+        }),
+        Stage::Completed => api::Notif::PegCompleted(api::PegCompletedNotif {
+            order_id,
+            txid,
+            vout,
+            payout_amount: tx.payout_amount,
+        }),
+        Stage::Failed => api::Notif::PegFailed(api::PegFailedNotif {
+            order_id,
+            txid,
+            vout,
+            reason: INSUFFICIENT_AMOUNT_REASON.to_owned(),
+        }),
+    };
+    Some(notif)
+}
+
+/// The new stage and the notifications for a transaction that was last at `last` (`None` if not seen before).
+/// Skipped stages are reported too (for example, if the first received state is already `Done`).
+/// Nothing is reported if the stage did not change or after `Completed` and `Failed`.
+pub fn transitions(
+    order_id: OrderId,
+    last: Option<Stage>,
+    tx: &api::PegTxStatus,
+) -> Option<(Stage, Vec<api::Notif>)> {
+    if matches!(last, Some(Stage::Completed | Stage::Failed)) {
+        return None;
+    }
+
+    let target = target_stage(tx);
+    let stages = if target == Stage::Failed {
+        vec![Stage::Failed]
+    } else {
+        PROGRESS
+            .into_iter()
+            .filter(|stage| Some(*stage) > last && *stage <= target)
+            .collect()
+    };
+    if stages.is_empty() {
+        return None;
+    }
+
+    let notifs = stages
+        .into_iter()
+        .filter_map(|stage| notif(order_id, tx, stage))
+        .collect();
+    Some((target, notifs))
+}
+
+#[cfg(test)]
+mod tests;
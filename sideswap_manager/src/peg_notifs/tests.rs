@@ -0,0 +1,109 @@
+use sideswap_types::timestamp_ms::TimestampMs;
+
+use super::*;
+
+const ORDER_ID: OrderId = sideswap_api::HashN([1; 32]);
+
+fn tx(tx_state: api::PegTxState, payout_txid: Option<u8>) -> api::PegTxStatus {
+    api::PegTxStatus {
+        tx_hash: sideswap_api::HashN([2; 32]),
+        vout: 0,
+        peg_amount: 0.001,
+        payout_amount: Some(0.00099),
+        tx_state,
+        detected_confs: None,
+        total_confs: None,
+        created_at: TimestampMs::from_millis(1_700_000_000_000),
+        payout_txid: payout_txid.map(|value| sideswap_api::HashN([value; 32])),
+    }
+}
+
+fn names(notifs: &[api::Notif]) -> Vec<&'static str> {
+    notifs
+        .iter()
+        .map(|notif| match notif {
+            api::Notif::PegDepositDetected(_) => "detected",
+            api::Notif::PegDepositConfirmed(_) => "confirmed",
+            api::Notif::PegPayoutBroadcast(_) => "payout",
+            api::Notif::PegCompleted(_) => "completed",
+            api::Notif::PegFailed(_) => "failed",
+            _ => panic!("unexpected notification"),
+        })
+        .collect()
+}
+
+#[test]
+fn each_stage_reported_once() {
+    use api::PegTxState::*;
+
+    let mut last = None;
+    let mut reported = Vec::new();
+    for tx in [
+        tx(Detected, None),
+        tx(Detected, None),
+        tx(Processing, None),
+        tx(Processing, Some(3)),
+        tx(Processing, Some(3)),
+        tx(Done, Some(3)),
+        tx(Done, Some(3)),
+    ] {
+        if let Some((stage, notifs)) = transitions(ORDER_ID, last, &tx) {
+            assert!(Some(stage) > last);
+            last = Some(stage);
+            reported.extend(names(&notifs));
+        }
+    }
+    assert_eq!(last, Some(Stage::Completed));
+    assert_eq!(reported, ["detected", "confirmed", "payout", "completed"]);
+}
+
+#[test]
+fn skipped_stages_reported() {
+    let (stage, notifs) = transitions(ORDER_ID, None, &tx(api::PegTxState::Done, Some(3))).unwrap();
+    assert_eq!(stage, Stage::Completed);
+    assert_eq!(
+        names(&notifs),
+        ["detected", "confirmed", "payout", "completed"]
+    );
+    match &notifs[2] {
+        api::Notif::PegPayoutBroadcast(notif) => {
+            assert_eq!(notif.payout_txid, sideswap_api::HashN([3; 32]));
+            assert_eq!(notif.payout_amount, Some(0.00099));
+        }
+        _ => panic!("payout expected"),
+    }
+
+    // The payout is not reported without the txid
+    let (stage, notifs) = transitions(
+        ORDER_ID,
+        Some(Stage::Confirmed),
+        &tx(api::PegTxState::Done, None),
+    )
+    .unwrap();
+    assert_eq!(stage, Stage::Completed);
+    assert_eq!(names(&notifs), ["completed"]);
+}
+
+#[test]
+fn failed_is_final() {
+    let (stage, notifs) = transitions(
+        ORDER_ID,
+        Some(Stage::Detected),
+        &tx(api::PegTxState::InsufficientAmount, None),
+    )
+    .unwrap();
+    assert_eq!(stage, Stage::Failed);
+    match &notifs[..] {
+        [api::Notif::PegFailed(notif)] => assert_eq!(notif.reason, INSUFFICIENT_AMOUNT_REASON),
+        _ => panic!("failed expected"),
+    }
+
+    for tx_state in [
+        api::PegTxState::InsufficientAmount,
+        api::PegTxState::Detected,
+        api::PegTxState::Done,
+    ] {
+        assert!(transitions(ORDER_ID, Some(Stage::Failed), &tx(tx_state, None)).is_none());
+        assert!(transitions(ORDER_ID, Some(Stage::Completed), &tx(tx_state, None)).is_none());
+    }
+}
@@ -0,0 +1,242 @@
+use elements::{
+    address::AddressParams,
+    pset::{self, PartiallySignedTransaction},
+    secp256k1_zkp::{Generator, SECP256K1},
+    Address, AssetId, BlindAssetProofs, BlindValueProofs, OutPoint, Script,
+};
+use sideswap_common::{b64, verify};
+use sideswap_dealer::utxo_data::UtxoData;
+
+use crate::error::Error;
+
+/// What the quote promised, the swap PSET received from the server must match it
+pub struct Expected<'a> {
+    /// The wallet UTXOs sent in `StartQuotes`
+    pub utxos: &'a [sideswap_api::Utxo],
+    /// The wallet keys, the PSET must not spend any other UTXO the wallet can sign
+    pub utxo_data: &'a UtxoData,
+    pub send_asset: AssetId,
+    pub send_amount: u64,
+    pub recv_asset: AssetId,
+    pub recv_amount: u64,
+    pub receive_script: Script,
+    pub change_script: Script,
+}
+
+/// The verified PSET amounts (in the asset base units)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Breakdown {
+    /// Total of the wallet inputs (`send_asset`)
+    pub total_in: u64,
+    /// Paid to the receive address (`recv_asset`)
+    pub receive_out: u64,
+    /// Paid back to the change address (`send_asset`)
+    pub change_out: u64,
+    /// Network fee of the whole transaction (L-BTC)
+    pub network_fee: u64,
+}
+
+/// Server PSETs are rejected above this size (in base64 characters)
+pub const MAX_PSET_BASE64_LEN: usize = 1024 * 1024;
+
+fn mismatch(reason: String) -> Error {
+    Error::PsetMismatch { reason }
+}
+
+/// The server amounts can be anything, so the totals must not wrap around
+fn add_amount(total: u64, amount: u64, name: &str) -> Result<u64, Error> {
+    total
+        .checked_add(amount)
+        .ok_or_else(|| mismatch(format!("{name} amount overflow")))
+}
+
+/// Decodes a server PSET, the transaction must be extractable (`UtxoData::sign_pset` panics otherwise)
+pub fn decode(pset: &str) -> Result<PartiallySignedTransaction, Error> {
+    verify!(
+        pset.len() <= MAX_PSET_BASE64_LEN,
+        Error::PsetTooLarge {
+            size: pset.len(),
+            limit: MAX_PSET_BASE64_LEN,
+        }
+    );
+    let pset = b64::decode(pset)?;
+    let pset: PartiallySignedTransaction = elements::encode::deserialize(&pset)?;
+    pset.extract_tx()?;
+    Ok(pset)
+}
+
+/// The output asset and amount.
+/// Blinded outputs must carry the explicit values with the blind proofs (PSET v2), otherwise `None` is returned.
+fn explicit_value(output: &pset::Output) -> Option<(AssetId, u64)> {
+    let asset = output.asset?;
+    let amount = output.amount?;
+
+    let asset_gen = match output.asset_comm {
+        Some(asset_comm) => {
+            let valid = output
+                .blind_asset_proof
+                .as_ref()?
+                .blind_asset_proof_verify(SECP256K1, asset, asset_comm);
+            valid.then_some(asset_comm)?
+        }
+        None => Generator::new_unblinded(SECP256K1, asset.into_tag()),
+    };
+
+    if let Some(amount_comm) = output.amount_comm {
+        let valid = output.blind_value_proof.as_ref()?.blind_value_proof_verify(
+            SECP256K1,
+            amount,
+            asset_gen,
+            amount_comm,
+        );
+        valid.then_some(())?;
+    }
+
+    Some((asset, amount))
+}
+
+/// Checks that the wallet inputs spend only the quoted `send_asset` UTXOs, that the receive output pays at least `recv_amount`,
+/// and that everything above `send_amount` is paid back to the change address.
+/// Outputs to other addresses are not ours and are not checked.
+pub fn verify(
+    pset: &PartiallySignedTransaction,
+    expected: &Expected<'_>,
+) -> Result<Breakdown, Error> {
+    let mut total_in = 0;
+    for input in pset.inputs() {
+        let utxo = expected.utxos.iter().find(|utxo| {
+            utxo.txid == input.previous_txid && utxo.vout == input.previous_output_index
+        });
+        match utxo {
+            Some(utxo) => {
+                verify!(
+                    utxo.asset == expected.send_asset,
+                    mismatch(format!(
+                        "wallet input {}:{} has unexpected asset {}",
+                        utxo.txid, utxo.vout, utxo.asset
+                    ))
+                );
+                total_in = add_amount(total_in, utxo.value, "wallet input")?;
+            }
+            None => {
+                let outpoint = OutPoint::new(input.previous_txid, input.previous_output_index);
+                verify!(
+                    expected.utxo_data.get_priv_key(&outpoint).is_none(),
+                    mismatch(format!(
+                        "input {}:{} spends a wallet UTXO that was not quoted",
+                        input.previous_txid, input.previous_output_index
+                    ))
+                );
+            }
+        }
+    }
+    verify!(total_in > 0, mismatch("no wallet inputs".to_owned()));
+
+    let mut receive_out = 0;
+    let mut change_out = 0;
+    let mut network_fee = 0;
+    for (index, output) in pset.outputs().iter().enumerate() {
+        if output.script_pubkey.is_empty() {
+            network_fee = add_amount(network_fee, output.amount.unwrap_or_default(), "fee")?;
+            continue;
+        }
+
+        let is_receive = output.script_pubkey == expected.receive_script;
+        let is_change = output.script_pubkey == expected.change_script;
+        if !is_receive && !is_change {
+            continue;
+        }
+
+        let (asset, amount) = explicit_value(output)
+            .ok_or_else(|| mismatch(format!("can't verify the amount of output {index}")))?;
+        if is_receive && asset == expected.recv_asset {
+            receive_out = add_amount(receive_out, amount, "receive output")?;
+        } else if is_change && asset == expected.send_asset {
+            change_out = add_amount(change_out, amount, "change output")?;
+        }
+    }
+
+    verify!(
+        receive_out >= expected.recv_amount,
+        mismatch(format!(
+            "receive output is {receive_out}, expected {}",
+            expected.recv_amount
+        ))
+    );
+
+    let spent = total_in.saturating_sub(change_out);
+    verify!(
+        spent <= expected.send_amount,
+        mismatch(format!(
+            "wallet inputs {total_in} minus change {change_out} is more than the send amount {}",
+            expected.send_amount
+        ))
+    );
+
+    Ok(Breakdown {
+        total_in,
+        receive_out,
+        change_out,
+        network_fee,
+    })
+}
+
+/// Checks done right before a server PSET is signed.
+/// `UtxoData::sign_pset` signs every input it has a key for (the keys of the spent UTXOs are kept too),
+/// so all such inputs must spend the current wallet UTXOs sent to the server (`quoted_utxos`).
+/// Output scripts must be standard (or empty for the fee), and the receive outputs must have verifiable amounts.
+pub fn verify_signable(
+    pset: &PartiallySignedTransaction,
+    utxo_data: &UtxoData,
+    quoted_utxos: &[sideswap_api::Utxo],
+    params: &'static AddressParams,
+    receive_script: &Script,
+) -> Result<(), Error> {
+    for input in pset.inputs() {
+        let outpoint = OutPoint::new(input.previous_txid, input.previous_output_index);
+        if utxo_data.get_priv_key(&outpoint).is_none() {
+            continue;
+        }
+        let current = utxo_data.utxos().iter().any(|utxo| {
+            utxo.txid == input.previous_txid && utxo.vout == input.previous_output_index
+        });
+        verify!(
+            current,
+            mismatch(format!(
+                "input {}:{} spends a wallet UTXO that is no longer available",
+                input.previous_txid, input.previous_output_index
+            ))
+        );
+        let quoted = quoted_utxos.iter().any(|utxo| {
+            utxo.txid == input.previous_txid && utxo.vout == input.previous_output_index
+        });
+        verify!(
+            quoted,
+            mismatch(format!(
+                "input {}:{} spends a wallet UTXO that was not quoted",
+                input.previous_txid, input.previous_output_index
+            ))
+        );
+    }
+
+    for (index, output) in pset.outputs().iter().enumerate() {
+        if output.script_pubkey.is_empty() {
+            continue;
+        }
+        verify!(
+            Address::from_script(&output.script_pubkey, None, params).is_some(),
+            mismatch(format!("output {index} has a non-standard script"))
+        );
+        if output.script_pubkey == *receive_script {
+            verify!(
+                explicit_value(output).is_some(),
+                mismatch(format!("can't verify the amount of receive output {index}"))
+            );
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
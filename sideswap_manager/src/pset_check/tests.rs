@@ -0,0 +1,387 @@
+use elements::{
+    confidential::{AssetBlindingFactor, ValueBlindingFactor},
+    hashes::Hash,
+    secp256k1_zkp::{rand::thread_rng, PedersenCommitment, RangeProof, SurjectionProof},
+};
+
+use super::*;
+
+const SEND_AMOUNT: u64 = 100_000;
+
+const RECV_AMOUNT: u64 = 95_000_000;
+
+fn asset(index: u8) -> AssetId {
+    AssetId::from_slice(&[index; 32]).expect("must not fail")
+}
+
+fn script(index: u8) -> Script {
+    Script::from(
+        vec![0x00, 0x14]
+            .into_iter()
+            .chain([index; 20])
+            .collect::<Vec<_>>(),
+    )
+}
+
+fn utxo(vout: u32, asset: AssetId, value: u64) -> sideswap_api::Utxo {
+    sideswap_api::Utxo {
+        txid: elements::Txid::from_byte_array([1; 32]),
+        vout,
+        asset,
+        asset_bf: AssetBlindingFactor::zero(),
+        value,
+        value_bf: ValueBlindingFactor::zero(),
+        redeem_script: None,
+    }
+}
+
+fn input(txid: u8, vout: u32) -> pset::Input {
+    pset::Input::from_prevout(elements::OutPoint::new(
+        elements::Txid::from_byte_array([txid; 32]),
+        vout,
+    ))
+}
+
+fn output(script_pubkey: Script, asset: AssetId, amount: u64) -> pset::Output {
+    pset::Output {
+        script_pubkey,
+        asset: Some(asset),
+        amount: Some(amount),
+        ..Default::default()
+    }
+}
+
+/// The output with commitments and (optionally) the blind proofs of its explicit values
+fn blinded_output(
+    script_pubkey: Script,
+    asset: AssetId,
+    amount: u64,
+    with_proofs: bool,
+) -> pset::Output {
+    let rng = &mut thread_rng();
+    let abf = AssetBlindingFactor::new(rng);
+    let vbf = ValueBlindingFactor::new(rng);
+    let asset_comm = Generator::new_blinded(SECP256K1, asset.into_tag(), abf.into_inner());
+    let amount_comm = PedersenCommitment::new(SECP256K1, amount, vbf.into_inner(), asset_comm);
+    let mut output = output(script_pubkey, asset, amount);
+    output.asset_comm = Some(asset_comm);
+    output.amount_comm = Some(amount_comm);
+    if with_proofs {
+        output.blind_asset_proof = Some(Box::new(
+            SurjectionProof::blind_asset_proof(rng, SECP256K1, asset, abf).unwrap(),
+        ));
+        output.blind_value_proof = Some(Box::new(
+            RangeProof::blind_value_proof(rng, SECP256K1, amount, amount_comm, asset_comm, vbf)
+                .unwrap(),
+        ));
+    }
+    output
+}
+
+fn swap_pset(inputs: Vec<pset::Input>, outputs: Vec<pset::Output>) -> PartiallySignedTransaction {
+    let mut pset = PartiallySignedTransaction::new_v2();
+    for input in inputs {
+        pset.add_input(input);
+    }
+    for output in outputs {
+        pset.add_output(output);
+    }
+    pset
+}
+
+fn utxo_with_key(utxo: sideswap_api::Utxo) -> sideswap_dealer::utxo_data::UtxoWithKey {
+    let secret_key =
+        elements::secp256k1_zkp::SecretKey::from_slice(&[1; 32]).expect("must not fail");
+    sideswap_dealer::utxo_data::UtxoWithKey {
+        utxo,
+        priv_key: elements::bitcoin::PrivateKey::new(
+            secret_key,
+            elements::bitcoin::Network::Testnet,
+        ),
+    }
+}
+
+fn wallet_utxo_data(utxos: &[sideswap_api::Utxo]) -> UtxoData {
+    let mut utxo_data = UtxoData::new(sideswap_dealer::utxo_data::Params {
+        confifential_only: false,
+    });
+    utxo_data.reset(utxos.iter().cloned().map(utxo_with_key).collect());
+    utxo_data
+}
+
+/// The quoted UTXOs, the wallet has one more UTXO (`1:2`, not quoted)
+fn check(pset: &PartiallySignedTransaction) -> Result<Breakdown, String> {
+    let utxos = [utxo(0, asset(1), 60_000), utxo(1, asset(1), 60_000)];
+    let utxo_data =
+        wallet_utxo_data(&[utxos[0].clone(), utxos[1].clone(), utxo(2, asset(3), 1_000)]);
+    verify(
+        pset,
+        &Expected {
+            utxos: &utxos,
+            utxo_data: &utxo_data,
+            send_asset: asset(1),
+            send_amount: SEND_AMOUNT,
+            recv_asset: asset(2),
+            recv_amount: RECV_AMOUNT,
+            receive_script: script(1),
+            change_script: script(2),
+        },
+    )
+    .map_err(|err| err.to_string())
+}
+
+#[test]
+fn matching_pset_accepted() {
+    // Wallet inputs, a server input, the fee output and a server output (not checked)
+    let inputs = || vec![input(1, 0), input(1, 1), input(5, 0)];
+    let pset = swap_pset(
+        inputs(),
+        vec![
+            output(script(1), asset(2), RECV_AMOUNT),
+            output(script(2), asset(1), 20_000),
+            output(script(9), asset(1), SEND_AMOUNT),
+            output(Script::new(), asset(3), 40),
+        ],
+    );
+    assert_eq!(
+        check(&pset).unwrap(),
+        Breakdown {
+            total_in: 120_000,
+            receive_out: RECV_AMOUNT,
+            change_out: 20_000,
+            network_fee: 40,
+        }
+    );
+
+    let pset = swap_pset(
+        inputs(),
+        vec![
+            blinded_output(script(1), asset(2), RECV_AMOUNT, true),
+            blinded_output(script(2), asset(1), 20_000, true),
+        ],
+    );
+    assert_eq!(check(&pset).unwrap().receive_out, RECV_AMOUNT);
+}
+
+#[test]
+fn mismatching_pset_rejected() {
+    let inputs = || vec![input(1, 0), input(1, 1)];
+
+    let pset = swap_pset(
+        inputs(),
+        vec![
+            output(script(1), asset(2), RECV_AMOUNT - 1),
+            output(script(2), asset(1), 20_000),
+        ],
+    );
+    assert_eq!(
+        check(&pset).unwrap_err(),
+        "the swap PSET does not match the quote: receive output is 94999999, expected 95000000"
+    );
+
+    // The missing change is spent elsewhere
+    let pset = swap_pset(
+        inputs(),
+        vec![
+            output(script(1), asset(2), RECV_AMOUNT),
+            output(script(2), asset(1), 19_000),
+        ],
+    );
+    assert!(check(&pset)
+        .unwrap_err()
+        .ends_with("wallet inputs 120000 minus change 19000 is more than the send amount 100000"));
+
+    // The receive output has the wrong asset
+    let pset = swap_pset(
+        inputs(),
+        vec![
+            output(script(1), asset(3), RECV_AMOUNT),
+            output(script(2), asset(1), 20_000),
+        ],
+    );
+    assert!(check(&pset).unwrap_err().contains("receive output is 0"));
+
+    // Blinded output without the blind proofs
+    let pset = swap_pset(
+        inputs(),
+        vec![
+            blinded_output(script(1), asset(2), RECV_AMOUNT, false),
+            output(script(2), asset(1), 20_000),
+        ],
+    );
+    assert!(check(&pset)
+        .unwrap_err()
+        .ends_with("can't verify the amount of output 0"));
+
+    // Blinded output with the proofs of another amount
+    let mut receive = blinded_output(script(1), asset(2), RECV_AMOUNT - 1, true);
+    receive.amount = Some(RECV_AMOUNT);
+    let pset = swap_pset(inputs(), vec![receive]);
+    assert!(check(&pset)
+        .unwrap_err()
+        .ends_with("can't verify the amount of output 0"));
+
+    // No wallet inputs
+    let pset = swap_pset(
+        vec![input(5, 0)],
+        vec![output(script(1), asset(2), RECV_AMOUNT)],
+    );
+    assert!(check(&pset).unwrap_err().ends_with("no wallet inputs"));
+
+    // Another wallet UTXO is spent by the server
+    let pset = swap_pset(
+        vec![input(1, 0), input(1, 1), input(1, 2)],
+        vec![
+            output(script(1), asset(2), RECV_AMOUNT),
+            output(script(2), asset(1), 20_000),
+            output(script(9), asset(3), 1_000),
+        ],
+    );
+    assert!(check(&pset).unwrap_err().ends_with(&format!(
+        "input {}:2 spends a wallet UTXO that was not quoted",
+        elements::Txid::from_byte_array([1; 32])
+    )));
+}
+
+/// The wallet knows the keys of both UTXOs, but only the first one is still unspent.
+/// The wallet has another UTXO (`1:2`), it is not quoted.
+fn spent_utxo_data() -> UtxoData {
+    let mut utxo_data = wallet_utxo_data(&[utxo(0, asset(1), 60_000), utxo(1, asset(1), 60_000)]);
+    utxo_data.reset(vec![
+        utxo_with_key(utxo(0, asset(1), 60_000)),
+        utxo_with_key(utxo(2, asset(3), 1_000)),
+    ]);
+    utxo_data
+}
+
+fn check_signable(pset: &PartiallySignedTransaction) -> Result<(), String> {
+    verify_signable(
+        pset,
+        &spent_utxo_data(),
+        &[utxo(0, asset(1), 60_000)],
+        &elements::AddressParams::LIQUID_TESTNET,
+        &script(1),
+    )
+    .map_err(|err| err.to_string())
+}
+
+#[test]
+fn unsignable_pset_rejected() {
+    // A server input and a fee output are fine
+    let pset = swap_pset(
+        vec![input(1, 0), input(5, 0)],
+        vec![
+            output(script(1), asset(2), RECV_AMOUNT),
+            output(Script::new(), asset(3), 40),
+        ],
+    );
+    check_signable(&pset).unwrap();
+
+    let pset = swap_pset(
+        vec![input(1, 0), input(1, 1)],
+        vec![output(script(1), asset(2), RECV_AMOUNT)],
+    );
+    assert!(check_signable(&pset).unwrap_err().ends_with(&format!(
+        "input {}:1 spends a wallet UTXO that is no longer available",
+        elements::Txid::from_byte_array([1; 32])
+    )));
+
+    // Another wallet UTXO is added by the server (and paid to a server output)
+    let pset = swap_pset(
+        vec![input(1, 0), input(1, 2)],
+        vec![
+            output(script(1), asset(2), RECV_AMOUNT),
+            output(script(9), asset(3), 1_000),
+        ],
+    );
+    assert!(check_signable(&pset).unwrap_err().ends_with(&format!(
+        "input {}:2 spends a wallet UTXO that was not quoted",
+        elements::Txid::from_byte_array([1; 32])
+    )));
+
+    let pset = swap_pset(
+        vec![input(1, 0)],
+        vec![
+            output(script(1), asset(2), RECV_AMOUNT),
+            output(Script::from(vec![0x51, 0x52]), asset(1), 20_000),
+        ],
+    );
+    assert!(check_signable(&pset)
+        .unwrap_err()
+        .ends_with("output 1 has a non-standard script"));
+
+    let pset = swap_pset(
+        vec![input(1, 0)],
+        vec![blinded_output(script(1), asset(2), RECV_AMOUNT, false)],
+    );
+    assert!(check_signable(&pset)
+        .unwrap_err()
+        .ends_with("can't verify the amount of receive output 0"));
+}
+
+#[test]
+fn malformed_pset_rejected() {
+    let pset = swap_pset(
+        vec![input(1, 0), input(5, 0)],
+        vec![
+            blinded_output(script(1), asset(2), RECV_AMOUNT, true),
+            output(Script::new(), asset(3), 40),
+        ],
+    );
+    let bytes = elements::encode::serialize(&pset);
+    decode(&b64::encode(&bytes)).unwrap();
+
+    for len in 0..bytes.len() {
+        assert!(decode(&b64::encode(&bytes[..len])).is_err(), "len: {len}");
+    }
+
+    // Corrupted PSETs must be rejected with an error or be signable safely
+    for index in 0..bytes.len() {
+        for mask in [0x01, 0x80, 0xff] {
+            let mut corrupted = bytes.clone();
+            corrupted[index] ^= mask;
+            if let Ok(pset) = decode(&b64::encode(&corrupted)) {
+                let _ = check_signable(&pset);
+                spent_utxo_data().sign_pset(pset);
+            }
+        }
+    }
+
+    // The totals of oversized amounts are rejected instead of wrapping around
+    let receive = || output(script(1), asset(2), RECV_AMOUNT);
+    for (outputs, reason) in [
+        (
+            vec![receive(), output(script(1), asset(2), u64::MAX)],
+            "receive output amount overflow",
+        ),
+        (
+            vec![
+                receive(),
+                output(script(2), asset(1), u64::MAX),
+                output(script(2), asset(1), 1),
+            ],
+            "change output amount overflow",
+        ),
+        (
+            vec![
+                receive(),
+                output(Script::new(), asset(3), u64::MAX),
+                output(Script::new(), asset(3), 1),
+            ],
+            "fee amount overflow",
+        ),
+    ] {
+        let pset = swap_pset(vec![input(1, 0)], outputs);
+        let pset = decode(&b64::encode(&elements::encode::serialize(&pset))).unwrap();
+        assert_eq!(
+            check(&pset).unwrap_err(),
+            format!("the swap PSET does not match the quote: {reason}")
+        );
+    }
+
+    assert!(decode("not base64!").is_err());
+    assert!(matches!(
+        decode(&"A".repeat(MAX_PSET_BASE64_LEN + 1)),
+        Err(Error::PsetTooLarge { .. })
+    ));
+}
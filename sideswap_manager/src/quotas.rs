@@ -0,0 +1,62 @@
+use serde::Deserialize;
+
+use crate::api;
+
+/// Per-client limits (clients are identified by the `client_name` WS URL query parameter,
+/// all clients without a name share one set of quotas)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// Pegs created with `NewPeg` that are not deleted and not finished (all detected payments completed or failed)
+    pub max_pegs: Option<u32>,
+    /// Transactions sent with `SendTx` or `AcceptQuote` that are not confirmed yet (and not deleted)
+    pub max_monitored_txs: Option<u32>,
+    /// Transactions created with `CreateTx` and not sent yet
+    pub max_created_txs: Option<u32>,
+    /// Quotes returned by `GetQuote` that are not accepted, cancelled or expired yet
+    pub max_quotes: Option<u32>,
+}
+
+impl Config {
+    pub fn limit(&self, resource: api::QuotaResource) -> Option<u32> {
+        match resource {
+            api::QuotaResource::Pegs => self.max_pegs,
+            api::QuotaResource::MonitoredTxs => self.max_monitored_txs,
+            api::QuotaResource::CreatedTxs => self.max_created_txs,
+            api::QuotaResource::Quotes => self.max_quotes,
+        }
+    }
+}
+
+/// The longest accepted client name
+pub const MAX_CLIENT_NAME_LEN: usize = 64;
+
+/// Parse the optional `client_name` WS URL query parameter (letters, digits, `-`, `_` and `.` are allowed)
+pub fn client_name_from_query(query: Option<&str>) -> Result<Option<String>, anyhow::Error> {
+    let value = query
+        .unwrap_or_default()
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find_map(|(key, value)| (key == "client_name").then_some(value));
+    match value {
+        Some(value) => {
+            validate_client_name(value)?;
+            Ok(Some(value.to_owned()))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn validate_client_name(value: &str) -> Result<(), anyhow::Error> {
+    anyhow::ensure!(
+        !value.is_empty()
+            && value.len() <= MAX_CLIENT_NAME_LEN
+            && value
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')),
+        "invalid client_name: {value:?}"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
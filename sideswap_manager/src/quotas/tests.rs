@@ -0,0 +1,33 @@
+use super::*;
+
+#[test]
+fn client_name_parsed() {
+    assert_eq!(client_name_from_query(None).unwrap(), None);
+    assert_eq!(
+        client_name_from_query(Some("notif_encoding=json")).unwrap(),
+        None
+    );
+    assert_eq!(
+        client_name_from_query(Some("notif_encoding=json&client_name=team-a.bot_1")).unwrap(),
+        Some("team-a.bot_1".to_owned())
+    );
+    for invalid in ["client_name=", "client_name=team%20a", "client_name=a/b"] {
+        assert!(client_name_from_query(Some(invalid)).is_err(), "{invalid}");
+    }
+    let long = format!("client_name={}", "a".repeat(MAX_CLIENT_NAME_LEN + 1));
+    assert!(client_name_from_query(Some(&long)).is_err());
+}
+
+#[test]
+fn limits_by_resource() {
+    let config = Config {
+        max_pegs: Some(1),
+        max_monitored_txs: None,
+        max_created_txs: Some(3),
+        max_quotes: Some(4),
+    };
+    assert_eq!(config.limit(api::QuotaResource::Pegs), Some(1));
+    assert_eq!(config.limit(api::QuotaResource::MonitoredTxs), None);
+    assert_eq!(config.limit(api::QuotaResource::CreatedTxs), Some(3));
+    assert_eq!(config.limit(api::QuotaResource::Quotes), Some(4));
+}
@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+use elements::AssetId;
+use serde::Deserialize;
+use sideswap_api::mkt::QuoteId;
+use tokio::time::Instant;
+
+use crate::ws_server::ClientId;
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// A quote is returned again for identical GetQuote requests received within this many milliseconds
+    pub window_ms: u64,
+    /// Coalesce only requests from the same WS client (requests from all clients are coalesced by default)
+    #[serde(default)]
+    pub per_client: bool,
+}
+
+/// The exact amount of the quote request, in the asset base units
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteAmount {
+    Send(u64),
+    Recv(u64),
+}
+
+/// Requests are coalesced only if all fields match (the receive address and the wallet are always a part of the key)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Key {
+    pub client_id: Option<ClientId>,
+    pub send_asset: AssetId,
+    pub recv_asset: AssetId,
+    pub amount: QuoteAmount,
+    pub receive_address: elements::Address,
+    pub instant_swap: bool,
+    pub wallet: String,
+}
+
+struct Entry {
+    key: Key,
+    quote_id: QuoteId,
+    created_at: Instant,
+}
+
+/// Recently returned quotes, the worker processes requests one at a time,
+/// so identical requests that were queued while a quote was requested are served from here
+pub struct QuoteCoalescing {
+    window: Duration,
+    per_client: bool,
+    entries: Vec<Entry>,
+}
+
+impl QuoteCoalescing {
+    pub fn new(config: &Config) -> QuoteCoalescing {
+        QuoteCoalescing {
+            window: Duration::from_millis(config.window_ms),
+            per_client: config.per_client,
+            entries: Vec::new(),
+        }
+    }
+
+    /// The key for a request (`client_id` is dropped unless `per_client` is set)
+    pub fn key(&self, key: Key) -> Key {
+        Key {
+            client_id: key.client_id.filter(|_| self.per_client),
+            ..key
+        }
+    }
+
+    pub fn get(&mut self, key: &Key, now: Instant) -> Option<QuoteId> {
+        let window = self.window;
+        self.entries
+            .retain(|entry| now.saturating_duration_since(entry.created_at) < window);
+        self.entries
+            .iter()
+            .find(|entry| entry.key == *key)
+            .map(|entry| entry.quote_id)
+    }
+
+    pub fn insert(&mut self, key: Key, quote_id: QuoteId, now: Instant) {
+        self.entries.retain(|entry| entry.key != key);
+        self.entries.push(Entry {
+            key,
+            quote_id,
+            created_at: now,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests;
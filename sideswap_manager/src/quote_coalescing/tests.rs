@@ -0,0 +1,79 @@
+use super::*;
+
+fn address(index: u8) -> elements::Address {
+    let secret_key =
+        elements::secp256k1_zkp::SecretKey::from_slice(&[index; 32]).expect("must not fail");
+    let pubkey = elements::bitcoin::PublicKey::new(
+        secret_key.public_key(elements::secp256k1_zkp::SECP256K1),
+    );
+    elements::Address::p2wpkh(&pubkey, None, &elements::AddressParams::LIQUID_TESTNET)
+}
+
+fn key(coalescing: &QuoteCoalescing, client_id: u64, receive_address: u8) -> Key {
+    coalescing.key(Key {
+        client_id: Some(ClientId(client_id)),
+        send_asset: AssetId::from_slice(&[1; 32]).expect("must not fail"),
+        recv_asset: AssetId::from_slice(&[2; 32]).expect("must not fail"),
+        amount: QuoteAmount::Send(100_000),
+        receive_address: address(receive_address),
+        instant_swap: false,
+        wallet: "default".to_owned(),
+    })
+}
+
+#[test]
+fn coalescing_window_and_key() {
+    let now = Instant::now();
+    let mut coalescing = QuoteCoalescing::new(&Config {
+        window_ms: 2000,
+        per_client: false,
+    });
+    coalescing.insert(key(&coalescing, 1, 1), QuoteId::new(10), now);
+
+    assert_eq!(
+        coalescing.get(&key(&coalescing, 2, 1), now + Duration::from_secs(1)),
+        Some(QuoteId::new(10))
+    );
+    assert_eq!(
+        coalescing.get(&key(&coalescing, 1, 2), now + Duration::from_secs(1)),
+        None
+    );
+    // The same amount of the received asset
+    let recv_key = Key {
+        amount: QuoteAmount::Recv(100_000),
+        ..key(&coalescing, 1, 1)
+    };
+    assert_eq!(
+        coalescing.get(&recv_key, now + Duration::from_secs(1)),
+        None
+    );
+    // Paid by another wallet
+    let wallet_key = Key {
+        wallet: "cold".to_owned(),
+        ..key(&coalescing, 1, 1)
+    };
+    assert_eq!(
+        coalescing.get(&wallet_key, now + Duration::from_secs(1)),
+        None
+    );
+    assert_eq!(
+        coalescing.get(&key(&coalescing, 1, 1), now + Duration::from_secs(2)),
+        None
+    );
+}
+
+#[test]
+fn coalescing_per_client() {
+    let now = Instant::now();
+    let mut coalescing = QuoteCoalescing::new(&Config {
+        window_ms: 2000,
+        per_client: true,
+    });
+    coalescing.insert(key(&coalescing, 1, 1), QuoteId::new(10), now);
+
+    assert_eq!(
+        coalescing.get(&key(&coalescing, 1, 1), now),
+        Some(QuoteId::new(10))
+    );
+    assert_eq!(coalescing.get(&key(&coalescing, 2, 1), now), None);
+}
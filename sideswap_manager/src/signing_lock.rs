@@ -0,0 +1,199 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use tokio::time::Instant;
+
+use crate::ws_server::token_matches;
+
+/// scrypt parameters of the new hashes (every hash stores its own parameters)
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
+/// Limits for the parameters read from the config (the hash is derived on every unlock attempt)
+const MAX_SCRYPT_LOG_N: u8 = 20;
+const MAX_SCRYPT_R: u32 = 32;
+const MAX_SCRYPT_P: u32 = 4;
+const MAX_SCRYPT_MEMORY: u64 = 256 * 1024 * 1024;
+
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// Signing is locked after this many minutes without any signing operation
+    pub timeout_minutes: u64,
+    /// The unlock password hash (`scrypt$<log_n>$<r>$<p>$<salt>$<key>`), printed by `sideswap_manager password-hash`
+    pub password_hash: String,
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        anyhow::ensure!(
+            PasswordHash::parse(&self.password_hash).is_some(),
+            "invalid auto_lock.password_hash, generate a new one with `sideswap_manager password-hash`"
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum UnlockError {
+    WrongPassword,
+    Backoff(Duration),
+    /// Another unlock attempt is still checking its password
+    InProgress,
+}
+
+#[derive(Clone)]
+struct PasswordHash {
+    params: scrypt::Params,
+    salt: Vec<u8>,
+    /// Hex-encoded
+    key: String,
+}
+
+impl PasswordHash {
+    fn parse(hash: &str) -> Option<PasswordHash> {
+        let ["scrypt", log_n, r, p, salt, key] = hash.split('$').collect::<Vec<_>>()[..] else {
+            return None;
+        };
+        let (log_n, r, p): (u8, u32, u32) = (log_n.parse().ok()?, r.parse().ok()?, p.parse().ok()?);
+        let memory = (128 * u64::from(r)) << log_n.min(MAX_SCRYPT_LOG_N);
+        if log_n > MAX_SCRYPT_LOG_N
+            || r > MAX_SCRYPT_R
+            || p > MAX_SCRYPT_P
+            || memory > MAX_SCRYPT_MEMORY
+        {
+            return None;
+        }
+        let params = scrypt::Params::new(log_n, r, p, KEY_LEN).ok()?;
+        let salt = hex::decode(salt).ok()?;
+        let key = key.to_lowercase();
+        let key_valid = hex::decode(&key).is_ok_and(|key| key.len() == KEY_LEN);
+        (!salt.is_empty() && key_valid).then_some(PasswordHash { params, salt, key })
+    }
+
+    fn derive_key(password: &str, salt: &[u8], params: &scrypt::Params) -> String {
+        let mut key = [0; KEY_LEN];
+        scrypt::scrypt(password.as_bytes(), salt, params, &mut key).expect("must not fail");
+        hex::encode(key)
+    }
+
+    fn matches(&self, password: &str) -> bool {
+        let key = PasswordHash::derive_key(password, &self.salt, &self.params);
+        token_matches(Some(&self.key), &key)
+    }
+}
+
+/// The password check of one unlock attempt, slow on purpose (run it with `spawn_blocking`)
+pub struct PasswordCheck {
+    password_hash: Option<PasswordHash>,
+}
+
+impl PasswordCheck {
+    pub fn matches(&self, password: &str) -> bool {
+        self.password_hash
+            .as_ref()
+            .is_some_and(|hash| hash.matches(password))
+    }
+}
+
+/// Refuses the signing requests in the worker while locked.
+/// The wallets keep their signers (and the decrypted mnemonics) while locked,
+/// dropping them would require a full wallet restart and resync on every unlock.
+pub struct SigningLock {
+    timeout: Duration,
+    password_hash: Option<PasswordHash>,
+    locked: bool,
+    last_activity: Instant,
+    failed_attempts: u32,
+    retry_after: Option<Instant>,
+    checking: bool,
+}
+
+/// Hashes with a new random salt, so the same password gives a different hash every time
+pub fn password_hash(password: &str) -> String {
+    let salt = rand::random::<[u8; SALT_LEN]>();
+    let params =
+        scrypt::Params::new(SCRYPT_LOG_N, SCRYPT_R, SCRYPT_P, KEY_LEN).expect("must be valid");
+    let key = PasswordHash::derive_key(password, &salt, &params);
+    format!(
+        "scrypt${}${}${}${}${key}",
+        params.log_n(),
+        params.r(),
+        params.p(),
+        hex::encode(salt),
+    )
+}
+
+impl SigningLock {
+    /// The manager starts locked, an explicit unlock is required before the first signing
+    pub fn new(config: &Config, now: Instant) -> SigningLock {
+        SigningLock {
+            timeout: Duration::from_secs(config.timeout_minutes * 60),
+            password_hash: PasswordHash::parse(&config.password_hash),
+            locked: true,
+            last_activity: now,
+            failed_attempts: 0,
+            retry_after: None,
+            checking: false,
+        }
+    }
+
+    pub fn locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Returns true if the lock state changed
+    pub fn check_timeout(&mut self, now: Instant) -> bool {
+        if !self.locked && now.saturating_duration_since(self.last_activity) >= self.timeout {
+            self.locked = true;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Must be called after every signing operation
+    pub fn touch(&mut self, now: Instant) {
+        self.last_activity = now;
+    }
+
+    /// The returned check result must be passed to `finish_unlock`, only one attempt can run at a time
+    pub fn start_unlock(&mut self, now: Instant) -> Result<PasswordCheck, UnlockError> {
+        if self.checking {
+            return Err(UnlockError::InProgress);
+        }
+        if let Some(retry_after) = self.retry_after {
+            if now < retry_after {
+                return Err(UnlockError::Backoff(retry_after - now));
+            }
+        }
+        self.checking = true;
+        Ok(PasswordCheck {
+            password_hash: self.password_hash.clone(),
+        })
+    }
+
+    pub fn finish_unlock(&mut self, password_valid: bool, now: Instant) -> Result<(), UnlockError> {
+        self.checking = false;
+        if !password_valid {
+            self.failed_attempts += 1;
+            let backoff = Duration::from_secs(1 << (self.failed_attempts - 1).min(16));
+            self.retry_after = Some(now + backoff.min(MAX_BACKOFF));
+            return Err(UnlockError::WrongPassword);
+        }
+
+        self.locked = false;
+        self.last_activity = now;
+        self.failed_attempts = 0;
+        self.retry_after = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests;
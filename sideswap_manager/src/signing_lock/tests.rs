@@ -0,0 +1,117 @@
+use super::*;
+
+fn test_config() -> Config {
+    Config {
+        timeout_minutes: 5,
+        password_hash: password_hash("secret"),
+    }
+}
+
+fn unlock(lock: &mut SigningLock, password: &str, now: Instant) -> Result<(), UnlockError> {
+    let check = lock.start_unlock(now)?;
+    lock.finish_unlock(check.matches(password), now)
+}
+
+#[test]
+fn locks_after_timeout() {
+    let start = Instant::now();
+    let mut lock = SigningLock::new(&test_config(), start);
+    assert!(lock.locked());
+
+    unlock(&mut lock, "secret", start).unwrap();
+    assert!(!lock.locked());
+
+    assert!(!lock.check_timeout(start + Duration::from_secs(299)));
+    lock.touch(start + Duration::from_secs(200));
+    assert!(!lock.check_timeout(start + Duration::from_secs(450)));
+
+    assert!(lock.check_timeout(start + Duration::from_secs(500)));
+    assert!(lock.locked());
+    assert!(!lock.check_timeout(start + Duration::from_secs(600)));
+}
+
+#[test]
+fn wrong_password_backoff() {
+    let start = Instant::now();
+    let mut lock = SigningLock::new(&test_config(), start);
+
+    assert_eq!(
+        unlock(&mut lock, "wrong", start),
+        Err(UnlockError::WrongPassword)
+    );
+    assert_eq!(
+        unlock(&mut lock, "secret", start),
+        Err(UnlockError::Backoff(Duration::from_secs(1)))
+    );
+
+    let now = start + Duration::from_secs(1);
+    assert_eq!(
+        unlock(&mut lock, "wrong", now),
+        Err(UnlockError::WrongPassword)
+    );
+    assert_eq!(
+        unlock(&mut lock, "secret", now + Duration::from_secs(1)),
+        Err(UnlockError::Backoff(Duration::from_secs(1)))
+    );
+    assert!(lock.locked());
+
+    unlock(&mut lock, "secret", now + Duration::from_secs(2)).unwrap();
+    assert!(!lock.locked());
+}
+
+#[test]
+fn password_hash_salted() {
+    let hash = password_hash("secret");
+    assert!(hash.starts_with("scrypt$15$8$1$"));
+    assert_ne!(hash, password_hash("secret"));
+
+    let config = Config {
+        timeout_minutes: 5,
+        password_hash: hash.to_uppercase().replace("SCRYPT", "scrypt"),
+    };
+    config.validate().unwrap();
+    let start = Instant::now();
+    unlock(&mut SigningLock::new(&config, start), "secret", start).unwrap();
+
+    // The old unsalted hex hashes are not accepted
+    for password_hash in [hex::encode([1; 32]), "scrypt$15$8$1$$00".to_owned()] {
+        let config = Config {
+            timeout_minutes: 5,
+            password_hash,
+        };
+        assert!(config.validate().is_err());
+        assert_eq!(
+            unlock(&mut SigningLock::new(&config, start), "secret", start),
+            Err(UnlockError::WrongPassword)
+        );
+    }
+}
+
+#[test]
+fn one_unlock_attempt_at_a_time() {
+    let start = Instant::now();
+    let mut lock = SigningLock::new(&test_config(), start);
+
+    let check = lock.start_unlock(start).unwrap();
+    assert!(matches!(
+        lock.start_unlock(start),
+        Err(UnlockError::InProgress)
+    ));
+    lock.finish_unlock(check.matches("secret"), start).unwrap();
+    assert!(!lock.locked());
+}
+
+#[test]
+fn expensive_scrypt_params_rejected() {
+    let hash = password_hash("secret");
+    let [key, salt, _] = hash.rsplitn(3, '$').collect::<Vec<_>>()[..] else {
+        panic!("unexpected hash: {hash}");
+    };
+    for params in ["21$8$1", "15$33$1", "15$8$5", "20$8$1"] {
+        let config = Config {
+            timeout_minutes: 5,
+            password_hash: format!("scrypt${params}${salt}${key}"),
+        };
+        assert!(config.validate().is_err(), "{params}");
+    }
+}
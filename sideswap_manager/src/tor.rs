@@ -0,0 +1,275 @@
+use std::{
+    net::{Ipv4Addr, SocketAddr},
+    path::{Path, PathBuf},
+};
+
+use serde::Deserialize;
+use tokio::sync::watch;
+
+/// Publish the WS server as a Tor v3 onion service (using the control port of a running tor daemon).
+/// Requires the `tor` cargo feature.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// Tor control port address (usually 127.0.0.1:9051)
+    pub control_address: SocketAddr,
+    /// Tor control password (`HashedControlPassword` in torrc)
+    pub password: Option<String>,
+    /// Tor control auth cookie (`CookieAuthentication` in torrc), used if `password` is not set
+    pub cookie_file: Option<PathBuf>,
+    /// The onion service port (80 by default)
+    pub virtual_port: Option<u16>,
+}
+
+/// Onion service state, reported in `GetServerInfo`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    /// Set while the onion service is published (`<service_id>.onion:<port>`)
+    pub onion_address: Option<String>,
+    /// The last error if the onion service is not published
+    pub error: Option<String>,
+}
+
+/// The onion service private key in the work dir (keeps the onion address stable across restarts)
+const KEY_FILE: &str = "onion_service_key";
+
+/// Tor forwards the onion service connections here (the WS listener address, unspecified IP replaced with localhost)
+fn forward_target(listen_on: SocketAddr) -> SocketAddr {
+    if listen_on.ip().is_unspecified() {
+        SocketAddr::new(Ipv4Addr::LOCALHOST.into(), listen_on.port())
+    } else {
+        listen_on
+    }
+}
+
+/// Starts publishing the onion service in the background.
+/// The WS server keeps working over TCP if the tor daemon is not reachable, the publishing is retried periodically.
+pub fn start(config: Config, work_dir: &Path, listen_on: SocketAddr) -> watch::Receiver<Status> {
+    let (status_sender, status_receiver) = watch::channel(Status::default());
+    imp::start(
+        config,
+        work_dir.join(KEY_FILE),
+        forward_target(listen_on),
+        status_sender,
+    );
+    status_receiver
+}
+
+#[cfg(not(feature = "tor"))]
+mod imp {
+    use super::*;
+
+    pub fn start(
+        _config: Config,
+        _key_file: PathBuf,
+        _target: SocketAddr,
+        status_sender: watch::Sender<Status>,
+    ) {
+        let error = "the manager is built without the `tor` feature".to_owned();
+        tracing::error!("onion service is not published: {error}");
+        status_sender.send_replace(Status {
+            onion_address: None,
+            error: Some(error),
+        });
+    }
+}
+
+#[cfg(feature = "tor")]
+mod imp {
+    use std::time::Duration;
+
+    use anyhow::{anyhow, ensure};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::{
+            tcp::{OwnedReadHalf, OwnedWriteHalf},
+            TcpStream,
+        },
+    };
+
+    use super::*;
+
+    const DEFAULT_VIRTUAL_PORT: u16 = 80;
+
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+    pub const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+    pub struct Controller {
+        reader: BufReader<OwnedReadHalf>,
+        writer: OwnedWriteHalf,
+    }
+
+    impl Controller {
+        pub async fn connect(address: SocketAddr) -> Result<Controller, anyhow::Error> {
+            let stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+                .await
+                .map_err(|_| anyhow!("connection timeout"))??;
+            let (reader, writer) = stream.into_split();
+            Ok(Controller {
+                reader: BufReader::new(reader),
+                writer,
+            })
+        }
+
+        async fn read_line(&mut self) -> Result<String, anyhow::Error> {
+            let mut line = String::new();
+            let size = self.reader.read_line(&mut line).await?;
+            ensure!(size != 0, "connection closed");
+            Ok(line.trim_end_matches(['\r', '\n']).to_owned())
+        }
+
+        /// Sends the command and returns the reply lines (without the status code) if it succeeded
+        pub async fn command(&mut self, command: &str) -> Result<Vec<String>, anyhow::Error> {
+            self.writer
+                .write_all(format!("{command}\r\n").as_bytes())
+                .await?;
+            let mut lines = Vec::new();
+            loop {
+                let line = self.read_line().await?;
+                ensure!(line.len() >= 4, "invalid reply: {line:?}");
+                let (code, rest) = line.split_at(3);
+                let (separator, text) = rest.split_at(1);
+                match (code, separator) {
+                    // Asynchronous events (none are subscribed)
+                    ("650", _) => continue,
+                    ("250", "-") => lines.push(text.to_owned()),
+                    ("250", " ") => {
+                        lines.push(text.to_owned());
+                        return Ok(lines);
+                    }
+                    (_, " ") => return Err(anyhow!("{line}")),
+                    _ => return Err(anyhow!("unexpected reply: {line:?}")),
+                }
+            }
+        }
+
+        /// Returns once the connection is closed (tor removes the onion service at that moment)
+        pub async fn wait_closed(&mut self) -> anyhow::Error {
+            loop {
+                if let Err(err) = self.read_line().await {
+                    return err;
+                }
+            }
+        }
+    }
+
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    fn save_key(key_file: &Path, key: &str) -> Result<(), anyhow::Error> {
+        std::fs::write(key_file, key)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(key_file, std::fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    /// Authenticates and adds the onion service, returns the onion address.
+    /// The service is removed when the controller connection is closed.
+    pub async fn publish(
+        controller: &mut Controller,
+        config: &Config,
+        key_file: &Path,
+        target: SocketAddr,
+    ) -> Result<String, anyhow::Error> {
+        let auth = match (&config.password, &config.cookie_file) {
+            (Some(password), _) => format!("AUTHENTICATE {}", quote(password)),
+            (None, Some(cookie_file)) => {
+                let cookie = std::fs::read(cookie_file)
+                    .map_err(|err| anyhow!("can't read {cookie_file:?}: {err}"))?;
+                format!("AUTHENTICATE {}", hex::encode(cookie))
+            }
+            (None, None) => "AUTHENTICATE".to_owned(),
+        };
+        controller
+            .command(&auth)
+            .await
+            .map_err(|err| anyhow!("authentication failed: {err}"))?;
+
+        let stored_key = match std::fs::read_to_string(key_file) {
+            Ok(key) => Some(key.trim().to_owned()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(anyhow!("can't read {key_file:?}: {err}")),
+        };
+        let key = stored_key.as_deref().unwrap_or("NEW:ED25519-V3");
+        let virtual_port = config.virtual_port.unwrap_or(DEFAULT_VIRTUAL_PORT);
+
+        let lines = controller
+            .command(&format!("ADD_ONION {key} Port={virtual_port},{target}"))
+            .await
+            .map_err(|err| anyhow!("ADD_ONION failed: {err}"))?;
+        let field = |name: &str| {
+            lines
+                .iter()
+                .find_map(|line| line.strip_prefix(name)?.strip_prefix('='))
+        };
+
+        let service_id = field("ServiceID").ok_or_else(|| anyhow!("ServiceID is not returned"))?;
+        if stored_key.is_none() {
+            let private_key =
+                field("PrivateKey").ok_or_else(|| anyhow!("PrivateKey is not returned"))?;
+            save_key(key_file, private_key)
+                .map_err(|err| anyhow!("can't save {key_file:?}: {err}"))?;
+        }
+
+        Ok(format!("{service_id}.onion:{virtual_port}"))
+    }
+
+    /// Publishes the onion service and keeps it until the controller connection is lost, returns the reason
+    async fn serve(
+        config: &Config,
+        key_file: &Path,
+        target: SocketAddr,
+        status_sender: &watch::Sender<Status>,
+    ) -> anyhow::Error {
+        let mut controller = match Controller::connect(config.control_address).await {
+            Ok(controller) => controller,
+            Err(err) => {
+                return anyhow!(
+                    "can't connect to the tor controller at {}: {err}",
+                    config.control_address
+                )
+            }
+        };
+
+        let onion_address = match publish(&mut controller, config, key_file, target).await {
+            Ok(onion_address) => onion_address,
+            Err(err) => return err,
+        };
+        tracing::info!(onion_address, "onion service published");
+        status_sender.send_replace(Status {
+            onion_address: Some(onion_address),
+            error: None,
+        });
+
+        anyhow!(
+            "tor controller connection lost: {}",
+            controller.wait_closed().await
+        )
+    }
+
+    pub fn start(
+        config: Config,
+        key_file: PathBuf,
+        target: SocketAddr,
+        status_sender: watch::Sender<Status>,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                let err = serve(&config, &key_file, target, &status_sender).await;
+                tracing::error!("onion service is not published: {err}");
+                status_sender.send_replace(Status {
+                    onion_address: None,
+                    error: Some(err.to_string()),
+                });
+                tokio::time::sleep(RETRY_DELAY).await;
+            }
+        });
+    }
+}
+
+#[cfg(all(test, feature = "tor"))]
+mod tests;
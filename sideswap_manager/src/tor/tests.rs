@@ -0,0 +1,156 @@
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+
+use super::*;
+
+const SERVICE_ID: &str = "pg6mmjiyjmcrsslvykfwnntlaru7p5svn6y2ymmju6nubxndf4pscryd";
+
+const PRIVATE_KEY: &str = "ED25519-V3:yLSDc8b11PaIHTtNtvi9lNW99IME2mdrO4k381zDkHv//WRUGrkBALBQ9MbHy2SLA/NmfS7YxmcR/FY8toRSBA==";
+
+fn test_work_dir(name: &str) -> PathBuf {
+    let work_dir = std::env::temp_dir().join(format!(
+        "sideswap_manager_tor_{name}_{}",
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&work_dir);
+    std::fs::create_dir_all(&work_dir).unwrap();
+    work_dir
+}
+
+fn test_config(control_address: SocketAddr) -> Config {
+    Config {
+        control_address,
+        password: Some("secret \"pass\"".to_owned()),
+        cookie_file: None,
+        virtual_port: None,
+    }
+}
+
+/// Accepts one controller connection, answers `AUTHENTICATE` and `ADD_ONION` and returns the received commands
+/// once the client closes the connection (or after an error reply).
+async fn mock_controller(listener: &TcpListener, auth_reply: &str) -> Vec<String> {
+    let (stream, _) = listener.accept().await.unwrap();
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut commands = Vec::new();
+    while let Some(line) = lines.next_line().await.unwrap() {
+        let reply = if line.starts_with("AUTHENTICATE") {
+            auth_reply.to_owned()
+        } else if line.starts_with("ADD_ONION NEW:") {
+            format!("250-ServiceID={SERVICE_ID}\r\n250-PrivateKey={PRIVATE_KEY}\r\n250 OK\r\n")
+        } else if line.starts_with("ADD_ONION ") {
+            format!("250-ServiceID={SERVICE_ID}\r\n250 OK\r\n")
+        } else {
+            "510 Unrecognized command\r\n".to_owned()
+        };
+        commands.push(line);
+        writer.write_all(reply.as_bytes()).await.unwrap();
+        if !reply.starts_with("250") {
+            break;
+        }
+    }
+    commands
+}
+
+#[tokio::test]
+async fn add_onion_handshake_and_key_persistence() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = test_config(listener.local_addr().unwrap());
+    let work_dir = test_work_dir("persistence");
+    let key_file = work_dir.join(KEY_FILE);
+    let target = forward_target("0.0.0.0:3102".parse().unwrap());
+
+    let publish = || async {
+        let mut controller = imp::Controller::connect(config.control_address)
+            .await
+            .unwrap();
+        imp::publish(&mut controller, &config, &key_file, target).await
+    };
+
+    // New key
+    let (res, commands) = tokio::join!(publish(), mock_controller(&listener, "250 OK\r\n"));
+    assert_eq!(res.unwrap(), format!("{SERVICE_ID}.onion:80"));
+    assert_eq!(
+        commands,
+        [
+            r#"AUTHENTICATE "secret \"pass\"""#.to_owned(),
+            "ADD_ONION NEW:ED25519-V3 Port=80,127.0.0.1:3102".to_owned(),
+        ]
+    );
+    assert_eq!(std::fs::read_to_string(&key_file).unwrap(), PRIVATE_KEY);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&key_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // The stored key is reused, the onion address stays the same
+    let (res, commands) = tokio::join!(publish(), mock_controller(&listener, "250 OK\r\n"));
+    assert_eq!(res.unwrap(), format!("{SERVICE_ID}.onion:80"));
+    assert_eq!(
+        commands[1],
+        format!("ADD_ONION {PRIVATE_KEY} Port=80,127.0.0.1:3102")
+    );
+
+    // Authentication failure
+    let (res, commands) = tokio::join!(
+        publish(),
+        mock_controller(
+            &listener,
+            "515 Authentication failed: Password did not match\r\n"
+        )
+    );
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "authentication failed: 515 Authentication failed: Password did not match"
+    );
+    assert_eq!(commands.len(), 1);
+
+    std::fs::remove_dir_all(&work_dir).unwrap();
+}
+
+#[tokio::test]
+async fn status_reported_and_unreachable_controller_tolerated() {
+    let work_dir = test_work_dir("status");
+
+    // Nothing listens on the port
+    let control_address = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut status = start(
+        test_config(control_address),
+        &work_dir,
+        "127.0.0.1:3102".parse().unwrap(),
+    );
+    status.changed().await.unwrap();
+    let current = status.borrow().clone();
+    assert_eq!(current.onion_address, None);
+    assert!(current
+        .error
+        .unwrap()
+        .starts_with("can't connect to the tor controller"));
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut status = start(
+        test_config(listener.local_addr().unwrap()),
+        &work_dir,
+        "127.0.0.1:3102".parse().unwrap(),
+    );
+    // The controller connection stays open while the onion service is published
+    tokio::spawn(async move { mock_controller(&listener, "250 OK\r\n").await });
+    status.changed().await.unwrap();
+    assert_eq!(
+        *status.borrow(),
+        Status {
+            onion_address: Some(format!("{SERVICE_ID}.onion:80")),
+            error: None,
+        }
+    );
+
+    std::fs::remove_dir_all(&work_dir).unwrap();
+}
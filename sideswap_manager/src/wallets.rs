@@ -0,0 +1,119 @@
+use std::{collections::BTreeSet, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::mnemonic_cipher;
+
+/// The wallet configured with the top-level `mnemonic`, `work_dir` and `script_variant` settings
+pub const DEFAULT_WALLET: &str = "default";
+
+/// Additional wallet, requests select it with the `wallet` field (`NewAddress`, `CreateTx`, `SendTx` and `GetQuote`)
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    /// Unique wallet name (`default` is reserved for the top-level wallet)
+    pub name: String,
+    /// The wallet cache directory, must not be shared with another wallet
+    pub work_dir: PathBuf,
+    /// Plaintext mnemonic, exactly one of `mnemonic` and `encrypted_mnemonic` must be set
+    pub mnemonic: Option<bip39::Mnemonic>,
+    /// Encrypted mnemonic, printed by `sideswap_manager encrypt-mnemonic <key_file>`
+    pub encrypted_mnemonic: Option<String>,
+    /// The `encrypted_mnemonic` key source, required if `encrypted_mnemonic` is set
+    pub mnemonic_key: Option<mnemonic_cipher::KeySource>,
+    pub script_variant: sideswap_lwk::ScriptVariant,
+}
+
+impl Config {
+    pub fn mnemonic(&self) -> Result<bip39::Mnemonic, anyhow::Error> {
+        load_mnemonic(
+            self.mnemonic.as_ref(),
+            self.encrypted_mnemonic.as_deref(),
+            self.mnemonic_key.as_ref(),
+        )
+    }
+}
+
+pub fn validate_mnemonic(
+    mnemonic: Option<&bip39::Mnemonic>,
+    encrypted_mnemonic: Option<&str>,
+    mnemonic_key: Option<&mnemonic_cipher::KeySource>,
+) -> Result<(), anyhow::Error> {
+    match (mnemonic, encrypted_mnemonic) {
+        (Some(_), Some(_)) => anyhow::bail!(
+            "both mnemonic and encrypted_mnemonic are set, please remove the plaintext mnemonic"
+        ),
+        (None, None) => anyhow::bail!("either mnemonic or encrypted_mnemonic must be set"),
+        (None, Some(_)) => anyhow::ensure!(
+            mnemonic_key.is_some(),
+            "mnemonic_key must be set to decrypt encrypted_mnemonic"
+        ),
+        (Some(_), None) => {}
+    }
+    Ok(())
+}
+
+/// Decrypts `encrypted_mnemonic` if there is no plaintext mnemonic
+pub fn load_mnemonic(
+    mnemonic: Option<&bip39::Mnemonic>,
+    encrypted_mnemonic: Option<&str>,
+    mnemonic_key: Option<&mnemonic_cipher::KeySource>,
+) -> Result<bip39::Mnemonic, anyhow::Error> {
+    if let Some(mnemonic) = mnemonic {
+        return Ok(mnemonic.clone());
+    }
+    let encrypted_mnemonic =
+        encrypted_mnemonic.ok_or_else(|| anyhow::anyhow!("mnemonic is not set"))?;
+    let key = mnemonic_key
+        .ok_or_else(|| anyhow::anyhow!("mnemonic_key is not set"))?
+        .load()?;
+    mnemonic_cipher::decrypt(&key, encrypted_mnemonic)
+}
+
+pub fn validate_work_dir(work_dir: &std::path::Path) -> Result<(), anyhow::Error> {
+    anyhow::ensure!(
+        !work_dir.starts_with("/tmp"),
+        "invalid work_dir value: {:?}\nplease do not keep work dir in /tmp, the contents must be preserved",
+        work_dir,
+    );
+    Ok(())
+}
+
+/// Checks the additional wallets, `default_work_dir` is the top-level wallet work dir
+pub fn validate(
+    wallets: &[Config],
+    default_work_dir: &std::path::Path,
+) -> Result<(), anyhow::Error> {
+    let mut names = BTreeSet::from([DEFAULT_WALLET]);
+    let mut work_dirs = BTreeSet::from([default_work_dir]);
+    for wallet in wallets {
+        anyhow::ensure!(
+            !wallet.name.is_empty() && wallet.name.len() <= 64,
+            "invalid wallet name: {:?}",
+            wallet.name
+        );
+        anyhow::ensure!(
+            names.insert(wallet.name.as_str()),
+            "duplicate wallet name: {}",
+            wallet.name
+        );
+        anyhow::ensure!(
+            work_dirs.insert(wallet.work_dir.as_path()),
+            "wallet {} uses an already used work_dir: {:?}",
+            wallet.name,
+            wallet.work_dir
+        );
+        validate_work_dir(&wallet.work_dir)
+            .and_then(|()| {
+                validate_mnemonic(
+                    wallet.mnemonic.as_ref(),
+                    wallet.encrypted_mnemonic.as_deref(),
+                    wallet.mnemonic_key.as_ref(),
+                )
+            })
+            .map_err(|err| anyhow::anyhow!("wallet {}: {err}", wallet.name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
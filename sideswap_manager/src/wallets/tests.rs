@@ -0,0 +1,46 @@
+use super::*;
+
+fn wallet(name: &str, work_dir: &str) -> Config {
+    serde_json::from_value(serde_json::json!({
+        "name": name,
+        "work_dir": work_dir,
+        "mnemonic": "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        "script_variant": "wpkh",
+    }))
+    .unwrap()
+}
+
+#[test]
+fn wallets_validated() {
+    let default_work_dir = std::path::Path::new("/var/lib/sideswap_manager");
+    let check = |wallets: &[Config]| validate(wallets, default_work_dir);
+
+    check(&[]).unwrap();
+    check(&[
+        wallet("cold", "/var/lib/cold"),
+        wallet("hot", "/var/lib/hot"),
+    ])
+    .unwrap();
+
+    check(&[wallet("default", "/var/lib/cold")]).unwrap_err();
+    check(&[
+        wallet("cold", "/var/lib/cold"),
+        wallet("cold", "/var/lib/hot"),
+    ])
+    .unwrap_err();
+    check(&[wallet("cold", "/var/lib/sideswap_manager")]).unwrap_err();
+    check(&[
+        wallet("cold", "/var/lib/cold"),
+        wallet("hot", "/var/lib/cold"),
+    ])
+    .unwrap_err();
+    check(&[wallet("cold", "/tmp/cold")]).unwrap_err();
+    check(&[wallet("", "/var/lib/cold")]).unwrap_err();
+
+    let no_mnemonic = Config {
+        mnemonic: None,
+        ..wallet("cold", "/var/lib/cold")
+    };
+    let err = check(&[no_mnemonic]).unwrap_err().to_string();
+    assert!(err.starts_with("wallet cold: "), "{err}");
+}
@@ -0,0 +1,318 @@
+use std::{
+    collections::BTreeSet,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
+
+use elements::hashes::{hmac, sha256, Hash, HashEngine};
+use serde::Deserialize;
+use sideswap_common::retry_delay::RetryDelay;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use crate::api;
+
+/// The HMAC-SHA256 (hex) of `signed_payload`, set if the endpoint has a `secret`
+pub const SIGNATURE_HEADER: &str = "X-Signature-Sha256";
+
+/// The delivery attempt time (Unix seconds), signed together with the body.
+/// Receivers should reject old timestamps, so a captured request can't be replayed later.
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+/// The `Event` name (like `tx_confirmed`)
+pub const EVENT_HEADER: &str = "X-Webhook-Event";
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn default_max_retries() -> u32 {
+    5
+}
+
+fn default_queue_size() -> usize {
+    1000
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// `PegStatus` and the peg stage notifications (`PegDepositDetected`, `PegCompleted` and so on)
+    PegStatus,
+    /// `TxStatus` with the `Confirmed` status
+    TxConfirmed,
+    /// `Balances`
+    BalanceChanged,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::PegStatus => "peg_status",
+            Event::TxConfirmed => "tx_confirmed",
+            Event::BalanceChanged => "balance_changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Endpoint {
+    /// HTTP(S) URL, the notification JSON (like `{"TxStatus":{...}}`) is sent in the `POST` body
+    pub url: String,
+    /// The sent events (`peg_status`, `tx_confirmed` and `balance_changed`)
+    pub events: BTreeSet<Event>,
+    /// HMAC-SHA256 key, the signature of the timestamp and the body is sent in the `X-Signature-Sha256` header if set
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Config {
+    pub endpoints: Vec<Endpoint>,
+    /// Failed deliveries are retried this many times before the event is dropped (5 by default)
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// Undelivered events kept for one endpoint, new events are dropped while the queue is full (1000 by default)
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+}
+
+impl Config {
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        for endpoint in self.endpoints.iter() {
+            let url = url::Url::parse(&endpoint.url)
+                .map_err(|err| anyhow::anyhow!("invalid webhook URL: {err}"))?;
+            anyhow::ensure!(
+                ["http", "https"].contains(&url.scheme()),
+                "invalid webhook URL scheme: {}",
+                url.scheme()
+            );
+            anyhow::ensure!(
+                !endpoint.events.is_empty(),
+                "webhook events are not set for {}",
+                host(&endpoint.url)
+            );
+        }
+        anyhow::ensure!(self.queue_size > 0, "webhooks queue_size must be positive");
+        Ok(())
+    }
+}
+
+/// The endpoint URLs can contain secrets (like Slack webhooks), only the host is logged
+fn host(url: &str) -> String {
+    url::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_owned))
+        .unwrap_or_default()
+}
+
+/// The webhook event and the summary logged if the event is dropped
+fn classify(notif: &api::Notif) -> Option<(Event, String)> {
+    let peg = |name: &str, order_id: &sideswap_api::OrderId| {
+        Some((Event::PegStatus, format!("{name}, order_id: {order_id}")))
+    };
+    match notif {
+        api::Notif::PegStatus(notif) => peg("PegStatus", &notif.peg.order_id),
+        api::Notif::PegDepositDetected(notif) => peg("PegDepositDetected", &notif.order_id),
+        api::Notif::PegDepositConfirmed(notif) => peg("PegDepositConfirmed", &notif.order_id),
+        api::Notif::PegPayoutBroadcast(notif) => peg("PegPayoutBroadcast", &notif.order_id),
+        api::Notif::PegCompleted(notif) => peg("PegCompleted", &notif.order_id),
+        api::Notif::PegFailed(notif) => peg("PegFailed", &notif.order_id),
+        api::Notif::PegComplete(notif) => peg("PegComplete", &notif.order_id),
+
+        api::Notif::TxStatus(notif) if notif.status == api::TxStatus::Confirmed => Some((
+            Event::TxConfirmed,
+            format!("TxStatus, txid: {}", notif.txid),
+        )),
+
+        api::Notif::Balances(_) => Some((Event::BalanceChanged, "Balances".to_owned())),
+
+        api::Notif::TxStatus(_)
+        | api::Notif::Markets(_)
+        | api::Notif::MarketPrice(_)
+        | api::Notif::LockStatus(_)
+        | api::Notif::GapLimitWarning(_)
+        | api::Notif::Draining(_)
+        | api::Notif::ConfigReloaded(_)
+        | api::Notif::ApprovalResolved(_)
+        | api::Notif::MarketAdded(_)
+        | api::Notif::MarketRemoved(_)
+        | api::Notif::ServerConnection(_)
+        | api::Notif::TxNote(_) => None,
+    }
+}
+
+/// The signed message, the timestamp (`X-Webhook-Timestamp` value) and the body separated by a dot
+fn signed_payload(timestamp: u64, body: &str) -> String {
+    format!("{timestamp}.{body}")
+}
+
+fn signature(secret: &str, msg: &str) -> String {
+    let mut engine = hmac::HmacEngine::<sha256::Hash>::new(secret.as_bytes());
+    engine.input(msg.as_bytes());
+    hmac::Hmac::<sha256::Hash>::from_engine(engine).to_string()
+}
+
+struct Delivery {
+    event: Event,
+    summary: String,
+    body: Arc<String>,
+}
+
+struct EndpointSender {
+    host: String,
+    events: BTreeSet<Event>,
+    /// Sent but not delivered (or dropped) yet
+    queued: Arc<AtomicUsize>,
+    sender: UnboundedSender<Delivery>,
+}
+
+/// Every endpoint has its own delivery task, so a dead endpoint does not delay the others (or the worker)
+pub struct Webhooks {
+    endpoints: Vec<EndpointSender>,
+    queue_size: usize,
+}
+
+impl Webhooks {
+    /// Starts the delivery tasks (if configured), must be called from the tokio runtime
+    pub fn new(config: Option<&Config>) -> Webhooks {
+        Webhooks::with_retry_delay(config, RetryDelay::default())
+    }
+
+    fn with_retry_delay(config: Option<&Config>, retry_delay: RetryDelay) -> Webhooks {
+        let Some(config) = config else {
+            return Webhooks {
+                endpoints: Vec::new(),
+                queue_size: 0,
+            };
+        };
+        let endpoints = config
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                let (sender, receiver) = unbounded_channel();
+                let queued = Arc::new(AtomicUsize::new(0));
+                tokio::spawn(run(
+                    endpoint.clone(),
+                    config.max_retries,
+                    retry_delay.clone(),
+                    receiver,
+                    Arc::clone(&queued),
+                ));
+                EndpointSender {
+                    host: host(&endpoint.url),
+                    events: endpoint.events.clone(),
+                    queued,
+                    sender,
+                }
+            })
+            .collect();
+        Webhooks {
+            endpoints,
+            queue_size: config.queue_size,
+        }
+    }
+
+    /// Queues the notification for the endpoints subscribed to its event, never blocks
+    pub fn send(&self, notif: &api::Notif) {
+        if self.endpoints.is_empty() {
+            return;
+        }
+        let Some((event, summary)) = classify(notif) else {
+            return;
+        };
+        let body = Arc::new(serde_json::to_string(notif).expect("must not fail"));
+        for endpoint in self.endpoints.iter() {
+            if !endpoint.events.contains(&event) {
+                continue;
+            }
+            if endpoint.queued.load(Ordering::Relaxed) >= self.queue_size {
+                tracing::error!(
+                    host = endpoint.host,
+                    "webhook queue is full, drop {summary}"
+                );
+                continue;
+            }
+            endpoint.queued.fetch_add(1, Ordering::Relaxed);
+            let res = endpoint.sender.send(Delivery {
+                event,
+                summary: summary.clone(),
+                body: Arc::clone(&body),
+            });
+            if res.is_err() {
+                endpoint.queued.fetch_sub(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Blocking, must be called from a blocking task.
+/// Every attempt is signed with its own timestamp, so the retries are not rejected as stale.
+fn post(endpoint: &Endpoint, event: Event, body: &str) -> Result<(), anyhow::Error> {
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .expect("must not fail")
+        .as_secs();
+    let mut req = ureq::post(&endpoint.url)
+        .timeout(REQUEST_TIMEOUT)
+        .set("Content-Type", "application/json")
+        .set(EVENT_HEADER, event.name())
+        .set(TIMESTAMP_HEADER, &timestamp.to_string());
+    if let Some(secret) = &endpoint.secret {
+        req = req.set(
+            SIGNATURE_HEADER,
+            &signature(secret, &signed_payload(timestamp, body)),
+        );
+    }
+    req.send_string(body)?;
+    Ok(())
+}
+
+async fn deliver(
+    endpoint: &Arc<Endpoint>,
+    max_retries: u32,
+    mut retry_delay: RetryDelay,
+    delivery: &Delivery,
+) {
+    let host = host(&endpoint.url);
+    for attempt in 0.. {
+        let endpoint_copy = Arc::clone(endpoint);
+        let event = delivery.event;
+        let body = Arc::clone(&delivery.body);
+        let res = tokio::task::spawn_blocking(move || post(&endpoint_copy, event, &body))
+            .await
+            .expect("must not fail");
+        match res {
+            Ok(()) => return,
+            Err(err) if attempt < max_retries => {
+                tracing::debug!(host, attempt, "webhook delivery failed: {err}");
+                tokio::time::sleep(retry_delay.next_delay()).await;
+            }
+            Err(err) => {
+                tracing::error!(
+                    host,
+                    "webhook delivery failed after {} attempts: {err}, drop {}",
+                    attempt + 1,
+                    delivery.summary
+                );
+                return;
+            }
+        }
+    }
+}
+
+async fn run(
+    endpoint: Endpoint,
+    max_retries: u32,
+    retry_delay: RetryDelay,
+    mut receiver: UnboundedReceiver<Delivery>,
+    queued: Arc<AtomicUsize>,
+) {
+    let endpoint = Arc::new(endpoint);
+    while let Some(delivery) = receiver.recv().await {
+        deliver(&endpoint, max_retries, retry_delay.clone(), &delivery).await;
+        queued.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,198 @@
+use std::sync::Mutex;
+
+use axum::{extract::State, http::HeaderMap, routing::post};
+use sideswap_common::retry_delay::RetryDelayOptions;
+use tokio::net::TcpListener;
+
+use super::*;
+
+#[derive(Default)]
+struct Received {
+    /// Failed with 500 before the deliveries are accepted
+    fail_first: usize,
+    attempts: usize,
+    /// The event header, the timestamp header, the signature header and the body
+    bodies: Vec<(String, u64, Option<String>, String)>,
+}
+
+async fn handle(
+    State(received): State<Arc<Mutex<Received>>>,
+    headers: HeaderMap,
+    body: String,
+) -> axum::http::StatusCode {
+    let mut received = received.lock().unwrap();
+    received.attempts += 1;
+    if received.attempts <= received.fail_first {
+        return axum::http::StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    let header = |name| {
+        headers
+            .get(name)
+            .map(|value| value.to_str().unwrap().to_owned())
+    };
+    received.bodies.push((
+        header(EVENT_HEADER).unwrap(),
+        header(TIMESTAMP_HEADER).unwrap().parse().unwrap(),
+        header(SIGNATURE_HEADER),
+        body,
+    ));
+    axum::http::StatusCode::OK
+}
+
+async fn start_receiver(fail_first: usize) -> (String, Arc<Mutex<Received>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let received = Arc::new(Mutex::new(Received {
+        fail_first,
+        ..Default::default()
+    }));
+    let app = axum::Router::new()
+        .route("/hook", post(handle))
+        .with_state(Arc::clone(&received));
+    tokio::spawn(async move { axum::serve(listener, app).await });
+    (format!("http://{address}/hook"), received)
+}
+
+fn fast_retries() -> RetryDelay {
+    RetryDelay::new(RetryDelayOptions {
+        base: 0.01,
+        max: 0.01,
+        multiply: 1.0,
+        spread: 0.1,
+    })
+}
+
+fn config(url: &str, events: &[Event], max_retries: u32) -> Config {
+    Config {
+        endpoints: vec![Endpoint {
+            url: url.to_owned(),
+            events: events.iter().copied().collect(),
+            secret: Some("Jefe".to_owned()),
+        }],
+        max_retries,
+        queue_size: 10,
+    }
+}
+
+fn tx_status(status: api::TxStatus) -> api::Notif {
+    api::Notif::TxStatus(api::TxStatusNotif {
+        txid: elements::Txid::from_byte_array([1; 32]),
+        status,
+        reference: None,
+    })
+}
+
+async fn wait_idle(webhooks: &Webhooks) {
+    while webhooks.endpoints[0].queued.load(Ordering::Relaxed) != 0 {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+#[test]
+fn signature_matches_rfc_4231() {
+    assert_eq!(
+        signature("Jefe", "what do ya want for nothing?"),
+        "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+    );
+}
+
+#[test]
+fn notifs_classified() {
+    let event = |notif: &api::Notif| classify(notif).map(|(event, _summary)| event);
+    assert_eq!(
+        event(&tx_status(api::TxStatus::Confirmed)),
+        Some(Event::TxConfirmed)
+    );
+    assert_eq!(event(&tx_status(api::TxStatus::Mempool)), None);
+    assert_eq!(
+        event(&api::Notif::PegComplete(api::PegCompleteNotif {
+            order_id: sideswap_api::HashN([2; 32]),
+            amount_received: 0.01,
+        })),
+        Some(Event::PegStatus)
+    );
+    assert_eq!(
+        event(&api::Notif::LockStatus(api::LockStatusNotif {
+            locked: true
+        })),
+        None
+    );
+}
+
+#[test]
+fn config_validated() {
+    let settings = |url: &str, events: &[Event]| config(url, events, 1).validate();
+    settings("https://hooks.example.com/x", &[Event::TxConfirmed]).unwrap();
+    settings("ftp://hooks.example.com/x", &[Event::TxConfirmed]).unwrap_err();
+    settings("not a url", &[Event::TxConfirmed]).unwrap_err();
+    settings("https://hooks.example.com/x", &[]).unwrap_err();
+}
+
+#[tokio::test]
+async fn delivered_with_retries() {
+    let (url, received) = start_receiver(2).await;
+    let config = config(&url, &[Event::TxConfirmed], 2);
+    let webhooks = Webhooks::with_retry_delay(Some(&config), fast_retries());
+
+    webhooks.send(&tx_status(api::TxStatus::Mempool));
+    webhooks.send(&tx_status(api::TxStatus::Confirmed));
+    wait_idle(&webhooks).await;
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.attempts, 3);
+    assert_eq!(received.bodies.len(), 1);
+    let (event, timestamp, signature_header, body) = &received.bodies[0];
+    assert_eq!(event, "tx_confirmed");
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    assert!(now.abs_diff(*timestamp) < 60);
+    assert_eq!(
+        signature_header.as_deref(),
+        Some(signature("Jefe", &format!("{timestamp}.{body}")).as_str())
+    );
+    // The timestamp can't be changed without the secret
+    assert_ne!(
+        signature_header.as_deref(),
+        Some(signature("Jefe", &signed_payload(timestamp + 1, body)).as_str())
+    );
+    let value = serde_json::from_str::<serde_json::Value>(body).unwrap();
+    assert_eq!(value["TxStatus"]["status"], "Confirmed");
+}
+
+#[tokio::test]
+async fn dropped_after_max_retries() {
+    let (url, received) = start_receiver(usize::MAX).await;
+    let config = config(&url, &[Event::TxConfirmed], 1);
+    let webhooks = Webhooks::with_retry_delay(Some(&config), fast_retries());
+
+    webhooks.send(&tx_status(api::TxStatus::Confirmed));
+    webhooks.send(&tx_status(api::TxStatus::Confirmed));
+    wait_idle(&webhooks).await;
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.attempts, 4);
+    assert!(received.bodies.is_empty());
+}
+
+#[tokio::test]
+async fn queue_bounded() {
+    // Nothing listens on the port, every delivery fails
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/hook", listener.local_addr().unwrap());
+    drop(listener);
+    let config = config(&url, &[Event::BalanceChanged], 1000);
+    let webhooks = Webhooks::with_retry_delay(Some(&config), fast_retries());
+
+    let balances = api::Notif::Balances(api::BalancesNotif {
+        wallet: "default".to_owned(),
+        balances: Default::default(),
+        confirmed: Default::default(),
+        unconfirmed: Default::default(),
+    });
+    for _ in 0..20 {
+        webhooks.send(&balances);
+    }
+    assert_eq!(webhooks.endpoints[0].queued.load(Ordering::Relaxed), 10);
+}
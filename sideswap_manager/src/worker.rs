@@ -1,121 +1,445 @@
 use std::{
-    collections::{BTreeMap, BTreeSet},
+    collections::{BTreeMap, BTreeSet, HashSet},
+    future::Future,
+    str::FromStr,
     sync::{mpsc, Arc},
     time::Duration,
 };
 
 use elements::{pset::PartiallySignedTransaction, AssetId};
+use futures::{
+    future::{self, BoxFuture},
+    FutureExt, TryFutureExt,
+};
 use sideswap_api::{
-    mkt::{self, AssetType, QuoteId, TradeDir},
+    mkt::{self, AssetType, QuoteId, QuoteSubId, TradeDir},
     OrderId, ResponseMessage,
 };
 use sideswap_common::{
     abort, b64,
     channel_helpers::{UncheckedOneshotSender, UncheckedUnboundedSender},
     dealer_ticker::{DealerTicker, TickerLoader},
+    gdk_registry_cache::{GdkRegistryCache, ShortAssetInfo},
     make_market_request, make_request,
-    types::{asset_float_amount, asset_float_amount_, asset_int_amount_},
+    network::Network,
+    quote_amounts::{self, QuoteNumbers},
+    random_id,
+    types::{
+        asset_float_amount, asset_float_amount_, asset_scale, peg_out_amount, PegOutAmountReq,
+    },
     verify,
     ws::{
+        self,
         auto::{WrappedRequest, WrappedResponse},
         ws_req_sender::{self, WsReqSender},
     },
 };
 use sideswap_dealer::utxo_data::UtxoData;
 use sideswap_types::utxo_ext::UtxoExt;
-use sideswap_types::{asset_precision::AssetPrecision, timestamp_ms::TimestampMs};
-use sqlx::types::Text;
+use sideswap_types::{
+    asset_precision::AssetPrecision, fee_rate::FeeRateSats, normal_float::NormalFloat,
+    timestamp_ms::TimestampMs,
+};
+use sqlx::types::{Json, Text};
 use tokio::{
-    sync::mpsc::{unbounded_channel, UnboundedReceiver},
+    sync::{
+        mpsc::{unbounded_channel, UnboundedReceiver},
+        watch,
+    },
     time::Instant,
 };
+use tracing::Instrument;
 
 use crate::{
-    api,
-    db::Db,
+    api, approvals, balance_history,
+    clock_skew::{self, ClockSample, ClockSkew},
+    config_reload,
+    db::{self, Db},
+    drain::{self, DrainSignal},
     error::Error,
+    idempotency, maker,
+    metrics::{self, BroadcastTarget, Metrics},
     models::{self, MonitoredTx, Peg},
+    notif_encoding::{EncodedNotif, SharedNotif},
+    payment_refs::{self, PaymentRefs},
+    payment_uri, peg_notifs, pset_check,
+    quote_coalescing::{self, QuoteAmount, QuoteCoalescing},
+    signing_lock::{SigningLock, UnlockError},
+    tor,
+    wallets::DEFAULT_WALLET,
+    webhooks::Webhooks,
     ws_server::ClientId,
     Settings,
 };
 
-const GAP_LIMIT: u32 = 20;
+const DEFAULT_GAP_LIMIT: u32 = 20;
+
+const DEFAULT_GAP_LIMIT_WARNING: u32 = 5;
+
+/// Only this many last events are kept for every peg
+const MAX_PEG_EVENTS: usize = 50;
+
+/// The Liquid minimum relay fee rate (in sats/vbyte)
+const MIN_FEE_RATE: f64 = 0.1;
+
+/// Larger `CreateTx` fee rates are most likely a mistake (in sats/vbyte)
+const MAX_FEE_RATE: f64 = 5.0;
+
+const DEFAULT_CREATED_TX_MAX_AGE: Duration = Duration::from_secs(86400);
+
+/// Wallet commands fail after this time (the wallet thread can be busy with a long scan)
+const DEFAULT_WALLET_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Cached market data is reported as stale if it's older than this while the server connection is down
+const MARKET_DATA_STALE_PERIOD: Duration = Duration::from_secs(60);
+
+/// The maximum length of signed and verified messages (in bytes)
+const MAX_MESSAGE_LEN: usize = 1024;
+
+/// How many times the server `BroadcastTx` is retried (once per upstream reconnect)
+const MAX_BROADCAST_RETRIES: u32 = 5;
+
+/// Completed pegs are still queried after reconnects for this long (new payments can be sent to the same peg address)
+const COMPLETED_PEG_RETENTION: Duration = Duration::from_secs(7 * 86400);
+
+/// `GetQuote` waits this long for the first quote from the server (unless `timeout_secs` is set)
+const QUOTE_DEADLINE: Duration = Duration::from_secs(15);
+
+/// The DB setting with the market login token (see `market_login`)
+const MARKET_TOKEN_KEY: &str = "market_token";
+
+/// The largest allowed `GetQuoteReq::timeout_secs`
+pub const MAX_QUOTE_DEADLINE: Duration = Duration::from_secs(60);
 
 pub enum Command {
     ClientConnected {
         client_id: ClientId,
-        notif_sender: UncheckedUnboundedSender<api::Notif>,
+        /// From the `client_name` WS URL query parameter (or the client token name)
+        client_name: Option<String>,
+        /// The name is proven with one of `[ws_server].client_tokens` (and not taken from the query)
+        client_token: bool,
+        notif_sender: UncheckedUnboundedSender<SharedNotif>,
     },
     ClientDisconnected {
         client_id: ClientId,
     },
     Request {
+        client_id: ClientId,
         req: api::Req,
         res_sender: UncheckedOneshotSender<Result<api::Resp, Error>>,
     },
 }
 
 struct ClientData {
-    notif_sender: UncheckedUnboundedSender<api::Notif>,
+    name: Option<String>,
+    /// See `Command::ClientConnected`
+    client_token: bool,
+    notif_sender: UncheckedUnboundedSender<SharedNotif>,
 }
 
 struct Quote {
+    quote_sub_id: QuoteSubId,
     txid: elements::Txid,
+    send_amount: f64,
+    recv_amount: f64,
+    /// Base and quote asset tickers
+    asset_pair: (DealerTicker, DealerTicker),
+    numbers: QuoteNumbers,
+    /// Unsigned, it is signed only in `AcceptQuote` (after checking it against the wallet UTXOs again)
     pset: PartiallySignedTransaction,
     expires_at: Instant,
-    note: String,
+    description: String,
+    created_by: Option<String>,
+    receive_address: elements::Address,
+    pset_breakdown: Option<api::PsetBreakdown>,
+    /// The WS generation the quote was returned on (the server forgets the quotes after reconnects)
+    ws_generation: u64,
+    /// Set for the first leg of a routed quote
+    second_leg: Option<SecondLeg>,
+    /// The wallet that signs the quote PSET
+    wallet: String,
+    /// The wallet UTXOs sent in `StartQuotes`, the PSET can't spend other wallet UTXOs
+    utxos: Vec<sideswap_api::Utxo>,
+}
+
+/// The second leg of a routed quote (see `GetQuoteReq::allow_routing`)
+#[derive(Clone)]
+struct SecondLeg {
+    quote_id: QuoteId,
+    intermediate_asset: DealerTicker,
+    intermediate_amount: f64,
 }
 
 impl Quote {
     fn ttl_valid(&self) -> bool {
         Instant::now() < self.expires_at
     }
+
+    fn acceptable(&self, ws_generation: u64) -> bool {
+        self.ttl_valid() && self.ws_generation == ws_generation
+    }
+
+    fn verify_acceptable(&self, ws_generation: u64) -> Result<(), Error> {
+        verify!(
+            self.ws_generation == ws_generation,
+            Error::QuoteInvalidatedByReconnect
+        );
+        verify!(self.ttl_valid(), Error::QuoteExpired);
+        Ok(())
+    }
 }
 
 struct CreatedTx {
     tx: elements::Transaction,
-    note: String,
+    /// The wallet that signed the transaction
+    wallet: String,
+    description: String,
+    created_by: Option<String>,
+    /// The sent amounts by asset (without the network fee)
+    amounts: BTreeMap<DealerTicker, f64>,
+    created_at: TimestampMs,
+}
+
+/// Broadcast by the wallet but not by the server, retried after reconnects
+struct PendingBroadcast {
+    tx: elements::Transaction,
+    attempts: u32,
+    /// The last sent `BroadcastTx` request
+    request_id: Option<sideswap_api::RequestId>,
+}
+
+impl CreatedTx {
+    fn from_row(row: models::CreatedTx) -> CreatedTx {
+        let tx = elements::encode::deserialize(&hex::decode(row.tx).expect("must be valid"))
+            .expect("must be valid");
+        CreatedTx {
+            tx,
+            wallet: row.wallet,
+            description: row.description,
+            created_by: row.created_by,
+            amounts: row.amounts.0,
+            created_at: TimestampMs::from_millis(row.created_at as u64),
+        }
+    }
+
+    fn to_row(&self, txid: elements::Txid) -> models::CreatedTx {
+        models::CreatedTx {
+            txid: Text(txid),
+            tx: elements::encode::serialize_hex(&self.tx),
+            wallet: self.wallet.clone(),
+            description: self.description.clone(),
+            created_by: self.created_by.clone(),
+            amounts: Json(self.amounts.clone()),
+            created_at: self.created_at.millis() as i64,
+        }
+    }
 }
 
 type MonitoredTxs = BTreeMap<elements::Txid, models::MonitoredTx>;
 
 struct PegData {
     status: Option<api::PegStatus>,
+    timeline: Vec<models::PegEvent>,
+    /// The last semantic notification stage by the peg transaction (tx_hash, vout)
+    notif_stages: BTreeMap<(sideswap_api::Hash32, u32), peg_notifs::Stage>,
+    created_by: Option<String>,
+    /// When all peg transactions were done (in milliseconds)
+    completed_at: Option<i64>,
+    /// The pending `PegStatus` request sent after the last reconnect
+    status_request_id: Option<sideswap_api::RequestId>,
+    /// The error returned for the last `PegStatus` request (the order is most likely from another env)
+    server_error: Option<String>,
+}
+
+impl PegData {
+    /// All detected payments are completed or failed
+    fn finished(&self) -> bool {
+        !self.notif_stages.is_empty()
+            && self.notif_stages.values().all(|stage| {
+                matches!(
+                    stage,
+                    peg_notifs::Stage::Completed | peg_notifs::Stage::Failed
+                )
+            })
+    }
+
+    /// Completed long enough ago to stop querying the status after reconnects
+    fn retired(&self, now: i64) -> bool {
+        self.completed_at.is_some_and(|completed_at| {
+            now - completed_at >= COMPLETED_PEG_RETENTION.as_millis() as i64
+        })
+    }
+}
+
+struct AssetFlags {
+    amp_restricted: bool,
+    payjoin: bool,
+}
+
+struct MarketPrice {
+    ind_price: Option<f64>,
+    last_price: Option<f64>,
+    updated_at: Instant,
+}
+
+/// The default wallet and the `wallets` from the config, every wallet runs its own wallet thread
+struct WalletData {
+    command_sender: mpsc::Sender<sideswap_lwk::Command>,
+
+    last_balances: Option<api::BalancesNotif>,
+    /// Set after the first wallet sync
+    balances: Option<BTreeMap<AssetId, u64>>,
+    /// Same as `balances`, only the confirmed UTXOs
+    confirmed_balances: Option<BTreeMap<AssetId, u64>>,
+    /// Updated with the balances, so the just received UTXOs are unconfirmed until the next update
+    confirmed_utxos: BTreeSet<elements::OutPoint>,
+
+    utxo_data: Option<UtxoData>,
+
+    /// Used as the change address for all quotes until it receives funds (or a quote is accepted)
+    quote_change_address: Option<elements::Address>,
+
+    addresses: BTreeMap<u32, models::Address>,
+
+    /// Set while the gap limit headroom is below the warning threshold
+    gap_limit_warning: Option<api::GapLimitWarningNotif>,
+}
+
+impl WalletData {
+    fn new(command_sender: mpsc::Sender<sideswap_lwk::Command>) -> WalletData {
+        WalletData {
+            command_sender,
+            last_balances: None,
+            balances: None,
+            confirmed_balances: None,
+            confirmed_utxos: BTreeSet::new(),
+            utxo_data: None,
+            quote_change_address: None,
+            addresses: BTreeMap::new(),
+            gap_limit_warning: None,
+        }
+    }
 }
 
 struct Data {
-    _settings: Settings,
+    settings: Settings,
 
     policy_asset: AssetId,
 
     ticker_loader: Arc<TickerLoader>,
 
+    /// Updated in the background, used to rebuild `ticker_loader` with `RefreshTickers` (not set in tests)
+    gdk_registry: Option<GdkRegistryCache>,
+
     db: Db,
 
     ws: WsReqSender,
 
-    wallet_command_sender: mpsc::Sender<sideswap_lwk::Command>,
+    /// Always contains `DEFAULT_WALLET`
+    wallets: BTreeMap<String, WalletData>,
 
     markets: Vec<mkt::MarketInfo>,
 
-    clients: BTreeMap<ClientId, ClientData>,
+    markets_updated_at: Option<Instant>,
 
-    last_balances: Option<api::BalancesNotif>,
+    market_prices: BTreeMap<mkt::AssetPair, MarketPrice>,
 
-    utxo_data: Option<UtxoData>,
+    /// Clients subscribed with `SubscribePrice`, the market is subscribed upstream while not empty
+    price_subs: BTreeMap<mkt::AssetPair, BTreeSet<ClientId>>,
+
+    clients: BTreeMap<ClientId, ClientData>,
+
+    balance_snapshot_at: Option<Instant>,
 
     pegs: BTreeMap<OrderId, PegData>,
 
     monitored_txs: MonitoredTxs,
 
+    /// The last known statuses of the monitored txs (updated after every wallet sync)
+    tx_statuses: BTreeMap<elements::Txid, api::TxStatus>,
+
     quotes: BTreeMap<QuoteId, Quote>,
 
     created_txs: BTreeMap<elements::Txid, CreatedTx>,
 
-    addresses: BTreeMap<u32, models::Address>,
+    pending_broadcasts: BTreeMap<elements::Txid, PendingBroadcast>,
+
+    signing_lock: Option<SigningLock>,
+
+    clock_skew: ClockSkew,
+
+    clock_check_at: Option<Instant>,
+
+    clock_sample_sender: UncheckedUnboundedSender<ClockSample>,
+
+    unlock_result_sender: UncheckedUnboundedSender<UnlockResult>,
+
+    /// Incremented on every new upstream WS connection
+    ws_generation: u64,
+
+    ws_connected: bool,
+
+    /// Quote subscriptions started by the manager (with the WS generation they were started on)
+    quote_subs: BTreeMap<QuoteSubId, u64>,
+
+    /// The last started quote subscription (the server keeps one quoting session per connection)
+    last_quote_sub: Option<QuoteSubId>,
+
+    stale_quote_notifs: u64,
+
+    /// Quote notifications not used by `get_quote` (including the stale ones)
+    discarded_quote_notifs: u64,
+
+    /// `GetQuote` requests that received no quote before the deadline
+    quote_timeouts: u64,
+
+    /// Stored addresses of another network, skipped on startup
+    foreign_addresses: u64,
+
+    /// Whitelisted asset flags from the SideSwap server asset metadata
+    asset_flags: BTreeMap<AssetId, AssetFlags>,
+
+    /// Recipient addresses returned by ResolveGaid (for the asset they were resolved for)
+    gaid_addresses: HashSet<(AssetId, elements::Address)>,
+
+    /// Set once draining is started, the manager exits when idle or at this time
+    drain_deadline: Option<Instant>,
+
+    /// Tells the WS server to stop accepting new connections
+    drain_sender: watch::Sender<bool>,
+
+    allowed_addresses: BTreeMap<String, models::AllowedAddress>,
+
+    /// Queued operations, by approval id
+    pending_approvals: BTreeMap<String, models::PendingApproval>,
+
+    payment_refs: PaymentRefs,
+
+    quote_coalescing: Option<QuoteCoalescing>,
+
+    /// Own market orders (from the market login and the order notifications)
+    own_orders: BTreeMap<mkt::OrdId, mkt::OwnOrder>,
+
+    /// The addresses of the orders placed with `SubmitOrder` (stored in the DB)
+    maker_orders: BTreeMap<mkt::OrdId, models::MakerOrder>,
+
+    /// The UTXOs registered with the server, set while logged in to the market (only with `maker_orders`)
+    market_utxos: Option<BTreeSet<elements::OutPoint>>,
+
+    /// Re-read by `ReloadConfig` and SIGHUP, not set in tests
+    config_path: Option<String>,
+
+    /// Served by the metrics listener (if `metrics_listen_on` is set)
+    metrics: Arc<Metrics>,
+
+    tor_status: watch::Receiver<tor::Status>,
+
+    /// Receives the `send_notifs` notifications (if `webhooks` is configured)
+    webhooks: Webhooks,
 }
 
 struct Asset {
+    ticker: DealerTicker,
     asset_id: AssetId,
     precision: AssetPrecision,
 }
@@ -131,40 +455,94 @@ fn encode_pset(pset: &PartiallySignedTransaction) -> String {
     b64::encode(&pset)
 }
 
-fn decode_pset(pset: &str) -> Result<PartiallySignedTransaction, Error> {
-    let pset = b64::decode(pset)?;
-    let pset = elements::encode::deserialize(&pset)?;
-    Ok(pset)
-}
-
-fn send_notifs(data: &Data, notif: &api::Notif) {
+/// Sends to the connected clients only, not to the webhooks
+fn send_client_notifs(data: &Data, notif: &api::Notif) {
+    let notif = EncodedNotif::new(notif.clone());
     for client in data.clients.values() {
         client.notif_sender.send(notif.clone());
     }
 }
 
+fn send_notifs(data: &Data, notif: &api::Notif) {
+    send_client_notifs(data, notif);
+    data.webhooks.send(notif);
+}
+
+fn market_data_stale(data: &Data, updated_at: Instant) -> bool {
+    !data.ws.connected() && updated_at.elapsed() > MARKET_DATA_STALE_PERIOD
+}
+
+fn convert_market(ticker_loader: &TickerLoader, market: &mkt::MarketInfo) -> Option<api::Market> {
+    Some(api::Market {
+        base: ticker_loader.ticker(&market.asset_pair.base)?,
+        quote: ticker_loader.ticker(&market.asset_pair.quote)?,
+        fee_asset: match market.fee_asset {
+            AssetType::Base => api::AssetType::Base,
+            AssetType::Quote => api::AssetType::Quote,
+        },
+    })
+}
+
+fn convert_market_price(
+    data: &Data,
+    asset_pair: &mkt::AssetPair,
+    price: &MarketPrice,
+) -> Option<api::MarketPriceNotif> {
+    Some(api::MarketPriceNotif {
+        base: data.ticker_loader.ticker(&asset_pair.base)?,
+        quote: data.ticker_loader.ticker(&asset_pair.quote)?,
+        ind_price: price.ind_price,
+        last_price: price.last_price,
+        stale: market_data_stale(data, price.updated_at),
+    })
+}
+
 fn try_get_asset(ticker_loader: &TickerLoader, ticker: DealerTicker) -> Result<Asset, Error> {
-    verify!(
-        ticker_loader.has_ticker(ticker),
-        Error::UnknownTicker(ticker)
-    );
+    let ticker = ticker_loader.resolve_ticker(ticker.as_str())?;
     Ok(Asset {
+        ticker,
         asset_id: *ticker_loader.asset_id(ticker),
         precision: ticker_loader.precision(ticker),
     })
 }
 
+/// Parses a non-negative decimal amount, `None` if it has more decimal places than the precision
+fn parse_asset_amount(amount: &str, asset_precision: AssetPrecision) -> Option<u64> {
+    let (int_part, frac_part) = amount.split_once('.').unwrap_or((amount, ""));
+    let frac_part = frac_part.trim_end_matches('0');
+    let precision = usize::from(asset_precision.value());
+    let is_digits = |value: &str| value.bytes().all(|c| c.is_ascii_digit());
+    if int_part.is_empty() || !is_digits(int_part) || !is_digits(frac_part) {
+        return None;
+    }
+    if frac_part.len() > precision {
+        return None;
+    }
+    let scale = 10u64.pow(u32::from(asset_precision.value()));
+    let frac = format!("{frac_part:0<precision$}");
+    let frac = if frac.is_empty() {
+        0
+    } else {
+        frac.parse::<u64>().ok()?
+    };
+    int_part
+        .parse::<u64>()
+        .ok()?
+        .checked_mul(scale)?
+        .checked_add(frac)
+}
+
+/// Converts without float arithmetic, the shortest decimal representation of the amount is used
+/// (so `5.1` is rejected for precision 0 and `0.3` is exactly `30000000` for precision 8)
 fn try_convert_asset_amount(amount: f64, asset_precision: AssetPrecision) -> Result<u64, Error> {
-    let int_amount = asset_int_amount_(amount, asset_precision);
-    let float_amount = asset_float_amount_(int_amount, asset_precision);
-    verify!(
-        float_amount == amount,
-        Error::InvalidAssetAmount(amount, asset_precision)
-    );
-    Ok(int_amount)
+    parse_asset_amount(&amount.to_string(), asset_precision)
+        .ok_or(Error::InvalidAssetAmount(amount, asset_precision))
 }
 
-fn convert_peg_status(status: sideswap_api::PegStatus) -> api::PegStatus {
+fn convert_peg_status(
+    status: sideswap_api::PegStatus,
+    reference: Option<String>,
+) -> api::PegStatus {
     let list = status
         .list
         .into_iter()
@@ -196,6 +574,7 @@ fn convert_peg_status(status: sideswap_api::PegStatus) -> api::PegStatus {
         list,
         created_at: TimestampMs::from_millis(status.created_at as u64),
         return_address: status.return_address.clone(),
+        reference,
     }
 }
 
@@ -225,7 +604,12 @@ fn convert_wallet_tx(
     ticker_loader: &TickerLoader,
     tx: &sideswap_lwk::WalletTx,
     policy_asset: &AssetId,
+    monitored_tx: Option<&models::MonitoredTx>,
 ) -> api::WalletTx {
+    let fee_paid = tx
+        .balance
+        .get(policy_asset)
+        .is_some_and(|amount| *amount < 0);
     api::WalletTx {
         txid: tx.txid,
         height: tx.height,
@@ -239,672 +623,5001 @@ fn convert_wallet_tx(
                 Some((ticker, amount))
             })
             .collect(),
-        network_fee: tx.fee,
+        network_fee: fee_paid.then_some(tx.fee),
         timestamp: tx
             .timestamp
             .map(|value| TimestampMs::from_millis(u64::from(value) * 1000)),
         tx_type: get_tx_type(&tx.balance, policy_asset, tx.fee),
+        description: monitored_tx.and_then(|monitored_tx| monitored_tx.description.clone()),
+        user_note: monitored_tx.and_then(|monitored_tx| monitored_tx.user_note.clone()),
     }
 }
 
-async fn new_monitored_tx(
-    db: &Db,
-    monitored_txs: &mut MonitoredTxs,
-    monitored_tx: models::MonitoredTx,
-) {
-    db.add_monitored_tx(monitored_tx.clone()).await;
-    monitored_txs.insert(monitored_tx.txid.0, monitored_tx);
-}
-
-async fn new_peg(
-    data: &mut Data,
-    api::NewPegReq {
-        addr_recv: recv_addr,
-        peg_in,
-        fee_rate,
-    }: api::NewPegReq,
-) -> Result<api::NewPegResp, Error> {
-    let resp = make_request!(
-        data.ws,
-        Peg,
-        sideswap_api::PegRequest {
-            recv_addr,
-            send_amount: None,
-            peg_in,
-            device_key: None,
-            blocks: None,
-            peg_out_amounts: None,
-            fee_rate,
-        }
-    )?;
-
-    let status = make_request!(
-        data.ws,
-        PegStatus,
-        sideswap_api::PegStatusRequest {
-            order_id: resp.order_id,
-            peg_in: None,
-        }
-    )?;
-
-    log::debug!("new peg registered, order_id: {}", resp.order_id);
-
+async fn audit(data: &Data, event: String) {
+    tracing::info!("audit: {event}");
     data.db
-        .add_peg(Peg {
-            order_id: Text(resp.order_id),
-        })
+        .add_audit_event(TimestampMs::now().millis() as i64, &event)
         .await;
+}
 
-    data.pegs.insert(resp.order_id, PegData { status: None });
+fn send_lock_status(data: &Data) {
+    if let Some(signing_lock) = &data.signing_lock {
+        send_notifs(
+            data,
+            &api::Notif::LockStatus(api::LockStatusNotif {
+                locked: signing_lock.locked(),
+            }),
+        );
+    }
+}
 
-    process_peg_status(data, status.clone());
+/// Must be called before any operation that signs or broadcasts
+fn check_signing_allowed(data: &mut Data) -> Result<(), Error> {
+    if let Some(signing_lock) = data.signing_lock.as_mut() {
+        verify!(!signing_lock.locked(), Error::Locked);
+        signing_lock.touch(Instant::now());
+    }
+    Ok(())
+}
 
-    Ok(api::NewPegResp {
-        peg: convert_peg_status(status),
-    })
+fn unlock_error(err: UnlockError) -> Error {
+    match err {
+        UnlockError::WrongPassword => Error::WrongPassword,
+        UnlockError::Backoff(retry_in) => Error::UnlockBackoff(retry_in),
+        UnlockError::InProgress => Error::UnlockBackoff(Duration::from_secs(1)),
+    }
 }
 
-async fn del_peg(
+/// The password is checked in a blocking task (scrypt is slow on purpose),
+/// the result is processed in the worker loop (see `finish_unlock`)
+fn unlock(
     data: &mut Data,
-    api::DelPegReq { order_id }: api::DelPegReq,
-) -> Result<api::DelPegResp, Error> {
-    log::debug!("del peg, order_id: {}", order_id);
-
-    data.pegs.remove(&order_id);
-
-    data.db.delete_peg(order_id).await;
-
-    Ok(api::DelPegResp {})
-}
+    api::UnlockReq { password }: api::UnlockReq,
+) -> Result<BoxFuture<'static, Result<api::UnlockResp, Error>>, Error> {
+    let Some(signing_lock) = data.signing_lock.as_mut() else {
+        return Ok(future::ready(Ok(api::UnlockResp {})).boxed());
+    };
+    if !signing_lock.locked() {
+        return Ok(future::ready(Ok(api::UnlockResp {})).boxed());
+    }
 
-async fn get_new_address(
-    data: &Data,
-    change: bool,
-    index: Option<u32>,
-) -> Result<sideswap_lwk::NewAddrResp, Error> {
+    let check = signing_lock
+        .start_unlock(Instant::now())
+        .map_err(unlock_error)?;
     let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
-    data.wallet_command_sender
-        .send(sideswap_lwk::Command::NewAdddress {
-            req: sideswap_lwk::NewAddrReq { change, index },
+    let result_sender = data.unlock_result_sender.clone();
+    tokio::task::spawn_blocking(move || {
+        let password_valid = check.matches(&password);
+        result_sender.send(UnlockResult {
+            password_valid,
             res_sender: res_sender.into(),
-        })?;
-    let resp = res_receiver.await??;
-    Ok(resp)
+        });
+    });
+    Ok(async move { res_receiver.await? }.boxed())
 }
 
-async fn new_address(
+async fn finish_unlock(
     data: &mut Data,
-    api::NewAddressReq { user_note }: api::NewAddressReq,
-) -> Result<api::NewAddressResp, Error> {
-    let first_unused_wallet = get_new_address(data, false, None).await?.index;
-    let first_unused_db = data
-        .addresses
-        .last_key_value()
-        .map(|(_key, value)| value.ind as u32 + 1)
-        .unwrap_or_default();
-    let new_index = u32::max(first_unused_wallet, first_unused_db);
-    verify!(new_index - first_unused_wallet < GAP_LIMIT, Error::GapLimit);
+    UnlockResult {
+        password_valid,
+        res_sender,
+    }: UnlockResult,
+) {
+    let Some(signing_lock) = data.signing_lock.as_mut() else {
+        res_sender.send(Ok(api::UnlockResp {}));
+        return;
+    };
 
-    let new_address = get_new_address(data, false, Some(new_index)).await?;
+    let res = signing_lock.finish_unlock(password_valid, Instant::now());
+    let res = match res {
+        Ok(()) => {
+            audit(data, "signing unlocked".to_owned()).await;
+            send_lock_status(data);
+            Ok(api::UnlockResp {})
+        }
+        Err(err) => {
+            audit(data, "unlock failed: wrong password".to_owned()).await;
+            Err(unlock_error(err))
+        }
+    };
+    res_sender.send(res);
+}
 
-    let addr = models::Address {
-        ind: new_index.into(),
-        address: Text(new_address.address.clone()),
-        user_note,
+fn start_clock_check(data: &mut Data) {
+    let Some(config) = &data.settings.clock_check else {
+        return;
     };
-    data.db.add_address(addr.clone()).await;
-    data.addresses.insert(new_index, addr);
+    data.clock_check_at = Some(Instant::now() + clock_skew::CHECK_PERIOD);
+    let url = config.time_source_url.clone();
+    let sample_sender = data.clock_sample_sender.clone();
+    tokio::task::spawn_blocking(move || match clock_skew::fetch_sample(&url) {
+        Ok(sample) => sample_sender.send(sample),
+        Err(err) => tracing::warn!("clock check failed: {err}"),
+    });
+}
 
-    Ok(api::NewAddressResp {
-        index: new_index,
-        address: new_address.address,
-    })
+fn process_clock_sample(data: &mut Data, sample: ClockSample) {
+    let changed = data.clock_skew.update(sample);
+    let skew_ms = data.clock_skew.skew_ms().unwrap_or_default();
+    tracing::debug!(skew_ms, "clock skew");
+    if changed && data.clock_skew.detected() {
+        tracing::error!("CLOCK SKEW DETECTED: the local clock differs from the time source by {skew_ms} ms, quote TTLs are reduced until the clock is fixed");
+    } else if changed {
+        tracing::info!(
+            "clock skew cleared: the local clock differs from the time source by {skew_ms} ms"
+        );
+    }
 }
 
-async fn list_addresses(
-    data: &mut Data,
-    api::ListAddressesReq {}: api::ListAddressesReq,
-) -> Result<api::ListAddressesResp, Error> {
-    let addresses = data
-        .addresses
-        .values()
-        .map(|address| api::Address {
-            index: address.ind as u32,
-            address: address.address.0.clone(),
-            user_note: address.user_note.clone(),
-        })
-        .collect();
+fn check_not_draining(data: &Data) -> Result<(), Error> {
+    verify!(data.drain_deadline.is_none(), Error::Draining);
+    Ok(())
+}
 
-    Ok(api::ListAddressesResp { addresses })
+fn draining_notif(drain_deadline: Instant) -> api::DrainingNotif {
+    let remaining = drain_deadline.saturating_duration_since(Instant::now());
+    api::DrainingNotif {
+        shutdown_at: TimestampMs::from_millis(
+            TimestampMs::now().millis() + remaining.as_millis() as u64,
+        ),
+    }
 }
 
-async fn create_tx(
-    data: &mut Data,
-    api::CreateTxReq { recipients }: api::CreateTxReq,
-) -> Result<api::CreateTxResp, Error> {
-    let note = recipients
-        .iter()
-        .map(|recipient| {
+/// Re-read the config file and apply the reloadable settings (all or nothing).
+/// Concurrent reloads are not possible because requests and signals are processed one at a time.
+async fn reload_config(data: &mut Data) -> Result<Vec<&'static str>, Error> {
+    let config_path = data
+        .config_path
+        .as_deref()
+        .ok_or_else(|| Error::InvalidConfig("the config file path is unknown".to_owned()))?;
+    let settings =
+        crate::load_settings(config_path).map_err(|err| Error::InvalidConfig(err.to_string()))?;
+
+    let changes = config_reload::compare(&data.settings, &settings);
+    if !changes.boot_only.is_empty() {
+        audit(
+            data,
             format!(
-                "send {} {} to {}",
-                recipient.amount, recipient.asset, recipient.address
-            )
-        })
-        .collect::<Vec<_>>();
-    let note = note.join(", ");
+                "config reload refused, restart is required to change: {}",
+                changes.boot_only.join(", ")
+            ),
+        )
+        .await;
+        abort!(Error::RestartRequired(changes.boot_only));
+    }
 
-    let recipients = recipients
-        .into_iter()
-        .map(|recipient| {
-            verify!(
-                data.ticker_loader.has_ticker(recipient.asset),
-                Error::UnknownTicker(recipient.asset)
-            );
+    // The boot-only settings are the same, so replacing everything applies only the reloadable ones
+    data.settings = settings;
+    if changes.reloadable.contains(&"upstream_size_limits") {
+        data.ws
+            .set_size_limits(data.settings.upstream_size_limits.unwrap_or_default());
+    }
+    if changes.reloadable.contains(&"balance_history") {
+        data.balance_snapshot_at = next_balance_snapshot_at(&data.settings);
+    }
 
-            let asset_id = data.ticker_loader.asset_id(recipient.asset);
-            let precision = data.ticker_loader.precision(recipient.asset);
-            let amount = try_convert_asset_amount(recipient.amount, precision)?;
+    audit(
+        data,
+        format!(
+            "config reloaded, changed: {}",
+            changes.reloadable.join(", ")
+        ),
+    )
+    .await;
 
-            Ok(sideswap_common::recipient::Recipient {
-                address: recipient.address,
-                asset_id: *asset_id,
-                amount,
-            })
-        })
-        .collect::<Result<Vec<sideswap_common::recipient::Recipient>, Error>>()?;
+    send_notifs(
+        data,
+        &api::Notif::ConfigReloaded(api::ConfigReloadedNotif {
+            changed_fields: changes
+                .reloadable
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+        }),
+    );
 
-    let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
-    data.wallet_command_sender
-        .send(sideswap_lwk::Command::CreateTx {
-            req: sideswap_lwk::CreateTxReq { recipients },
-            res_sender: res_sender.into(),
-        })?;
-    let resp = res_receiver.await??;
+    Ok(changes.reloadable)
+}
 
-    let txid = resp.tx.txid();
-    let network_fee = resp.tx.fee_in(data.policy_asset);
+/// Rebuild the ticker set from the asset registry and the `whitelisted_assets` from the config file.
+/// Only new assets are added (started quotes keep their assets), removing assets requires a restart.
+async fn refresh_tickers(data: &mut Data) -> Result<Vec<DealerTicker>, Error> {
+    let whitelisted_assets = match data.config_path.as_deref() {
+        Some(config_path) => {
+            crate::load_settings(config_path)
+                .map_err(|err| Error::InvalidConfig(err.to_string()))?
+                .whitelisted_assets
+        }
+        None => data.settings.whitelisted_assets.clone(),
+    };
+    let removed = data
+        .settings
+        .whitelisted_assets
+        .iter()
+        .flatten()
+        .any(|asset_id| {
+            !whitelisted_assets
+                .iter()
+                .flatten()
+                .any(|new| new == asset_id)
+        });
+    verify!(!removed, Error::RestartRequired(vec!["whitelisted_assets"]));
+
+    let gdk_registry = data
+        .gdk_registry
+        .as_ref()
+        .ok_or_else(|| Error::InvalidConfig("the asset registry is not loaded".to_owned()))?;
+    let ticker_loader = TickerLoader::from_registry(
+        gdk_registry,
+        whitelisted_assets.as_ref(),
+        data.settings.env.d().network,
+    )
+    .map_err(|err| Error::InvalidConfig(err.to_string()))?;
+    data.settings.whitelisted_assets = whitelisted_assets;
+
+    let added = merge_tickers(data, &ticker_loader);
+
+    audit(
+        data,
+        format!(
+            "tickers refreshed, added: {}",
+            added
+                .iter()
+                .map(DealerTicker::as_str)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    )
+    .await;
 
-    data.created_txs
-        .insert(txid, CreatedTx { tx: resp.tx, note });
+    Ok(added)
+}
 
-    Ok(api::CreateTxResp { txid, network_fee })
+/// The new balances are reported with the next wallet update
+fn merge_tickers(data: &mut Data, ticker_loader: &TickerLoader) -> Vec<DealerTicker> {
+    let mut merged = TickerLoader::clone(&data.ticker_loader);
+    let added = merged.merge(ticker_loader);
+    if !added.is_empty() {
+        data.ticker_loader = Arc::new(merged);
+        // The asset flags and the markets are kept only for the known assets
+        request_markets_and_assets(data);
+    }
+    added
 }
 
-async fn send_tx(
-    data: &mut Data,
-    api::SendTxReq {
-        txid,
-        user_note,
-        wallet_only,
-    }: api::SendTxReq,
-) -> Result<api::SendTxResp, Error> {
-    let created = data.created_txs.get(&txid).ok_or(Error::NoCreatedTx)?;
+async fn start_drain(data: &mut Data, grace_period: Duration) {
+    if data.drain_deadline.is_some() {
+        return;
+    }
 
-    let outpoints = created
-        .tx
-        .input
-        .iter()
-        .map(|input| input.previous_output)
-        .collect::<Vec<_>>();
+    let drain_deadline = Instant::now() + grace_period;
+    data.drain_deadline = Some(drain_deadline);
+    data.drain_sender.send_replace(true);
 
-    {
-        let mut tx_outpoints = outpoints.iter().copied().collect::<BTreeSet<_>>();
-        let utxo_data = data
-            .utxo_data
-            .as_ref()
-            .ok_or_else(|| Error::UtxoCheckFailed("utxo_data is None".to_owned()))?;
-        for utxo in utxo_data.utxos() {
-            tx_outpoints.remove(&utxo.outpoint());
+    tracing::info!(
+        "draining started, grace period: {} seconds",
+        grace_period.as_secs()
+    );
+    audit(
+        data,
+        format!(
+            "draining started, grace period: {} seconds",
+            grace_period.as_secs()
+        ),
+    )
+    .await;
+
+    send_notifs(data, &api::Notif::Draining(draining_notif(drain_deadline)));
+}
+
+/// Draining is finished when nothing started before it can still be completed (or the grace period lapsed)
+fn drain_finished(data: &Data, now: Instant) -> bool {
+    match data.drain_deadline {
+        Some(drain_deadline) => {
+            now >= drain_deadline || (data.quotes.is_empty() && data.created_txs.is_empty())
         }
-        verify!(
-            tx_outpoints.is_empty(),
-            Error::UtxoCheckFailed("Can't find wallet UTXOs".to_owned())
-        );
+        None => false,
     }
+}
 
-    if !wallet_only {
-        // Verify that UTXOs are not spent and known on the server
-        let _verify_resp = make_market_request!(
-            data.ws,
-            CheckOutpoints,
-            mkt::CheckOutpointsRequest { outpoints }
-        )
-        .map_err(|err| Error::UtxoCheckFailed(err.to_string()))?;
-    }
+/// The server broadcast retries sent but not answered yet (the transactions are already broadcast by the wallet)
+fn broadcasts_in_flight(data: &Data) -> bool {
+    data.pending_broadcasts
+        .values()
+        .any(|pending| pending.request_id.is_some())
+}
 
-    new_monitored_tx(
-        &data.db,
-        &mut data.monitored_txs,
-        MonitoredTx {
-            txid: Text(txid),
-            description: Some(created.note.clone()),
-            user_note,
-        },
-    )
-    .await;
+/// Requests are rejected, the client (dis)connections are still tracked
+async fn process_shutdown_command(data: &mut Data, command: Command) {
+    match command {
+        Command::Request { res_sender, .. } => res_sender.send(Err(Error::ShuttingDown)),
+        command => process_command(data, command).await,
+    }
+}
 
-    let tx = elements::encode::serialize_hex(&created.tx);
+/// Stops accepting new WS connections, rejects the queued and new requests
+/// and waits for the server broadcasts in flight, bounded by `shutdown_timeout_seconds`.
+/// The started `SendTx` and `AcceptQuote` requests are always finished, because the commands are processed one at a time.
+async fn shutdown(data: &mut Data, command_receiver: &mut UnboundedReceiver<Command>) {
+    let timeout = data
+        .settings
+        .shutdown_timeout_seconds
+        .map(Duration::from_secs)
+        .unwrap_or(drain::DEFAULT_SHUTDOWN_TIMEOUT);
+    let deadline = Instant::now() + timeout;
+    data.drain_sender.send_replace(true);
 
-    let res_server = if wallet_only {
-        None
-    } else {
-        let res = make_market_request!(
-            data.ws,
-            BroadcastTx,
-            mkt::BroadcastTxRequest {
-                tx: created.tx.clone().into()
-            }
-        );
+    loop {
+        if let Ok(command) = command_receiver.try_recv() {
+            process_shutdown_command(data, command).await;
+            continue;
+        }
 
-        match res {
-            Ok(_txid) => Some(api::BroadcastStatus::Success {}),
-            Err(err) => Some(api::BroadcastStatus::Error {
-                error_msg: err.to_string(),
-            }),
+        if !broadcasts_in_flight(data) {
+            break;
         }
-    };
 
-    let res_wallet = {
-        let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
-        data.wallet_command_sender
-            .send(sideswap_lwk::Command::BroadcastTx {
-                tx,
-                res_sender: Some(res_sender.into()),
-            })
-            .expect("must not fail");
-        let res_wallet = res_receiver.await.expect("must not fail");
+        tokio::select! {
+            Some(command) = command_receiver.recv() => {
+                process_shutdown_command(data, command).await;
+            },
 
-        match res_wallet {
-            Ok(_txid) => api::BroadcastStatus::Success {},
-            Err(err) => api::BroadcastStatus::Error {
-                error_msg: err.to_string(),
+            event = data.ws.recv() => {
+                process_ws_event(data, event).await;
+            },
+
+            _ = tokio::time::sleep_until(deadline) => {
+                tracing::warn!(
+                    "shutdown timeout, {} server broadcasts are not finished",
+                    data.pending_broadcasts.len()
+                );
+                break;
             },
         }
-    };
+    }
 
-    data.created_txs.clear();
+    data.clients.clear();
+    tokio::time::sleep_until(deadline.min(Instant::now() + drain::FLUSH_PERIOD)).await;
+}
 
-    Ok(api::SendTxResp {
-        res_wallet,
-        res_server,
-    })
+async fn drain(
+    data: &mut Data,
+    api::DrainReq { grace_seconds }: api::DrainReq,
+) -> Result<api::DrainResp, Error> {
+    start_drain(data, Duration::from_secs(grace_seconds)).await;
+    Ok(api::DrainResp {})
 }
 
-async fn get_quote(data: &mut Data, req: api::GetQuoteReq) -> Result<api::GetQuoteResp, Error> {
-    let send_asset = try_get_asset(&data.ticker_loader, req.send_asset)?;
-    let recv_asset = try_get_asset(&data.ticker_loader, req.recv_asset)?;
+async fn new_monitored_tx(
+    db: &Db,
+    monitored_txs: &mut MonitoredTxs,
+    monitored_tx: models::MonitoredTx,
+) {
+    db.add_monitored_tx(monitored_tx.clone()).await;
+    monitored_txs.insert(monitored_tx.txid.0, monitored_tx);
+}
 
-    log::debug!(
-        "try to find market for send_asset: {}, recv_asset: {}",
-        send_asset.asset_id,
-        recv_asset.asset_id
+/// Parses the `CreateTx` recipient address, returns the reason if it's not usable
+fn parse_recipient_address(
+    network: Network,
+    address: &str,
+    allow_unconfidential: bool,
+) -> Result<elements::Address, String> {
+    let address = address.trim();
+    let address = match elements::Address::from_str(address) {
+        Ok(address) => address,
+        Err(_err) if elements::bitcoin::Address::from_str(address).is_ok() => {
+            abort!("Bitcoin address used, use NewPeg for peg-outs")
+        }
+        Err(err) => abort!(err.to_string()),
+    };
+    verify!(
+        address.params == network.d().elements_params,
+        format!("not a {} address", network.d().name)
     );
+    verify!(
+        address.is_blinded() || allow_unconfidential,
+        "the address is not confidential".to_owned()
+    );
+    Ok(address)
+}
 
-    let market = data
-        .markets
-        .iter()
-        .find(|market| {
-            market.asset_pair.base == send_asset.asset_id
-                && market.asset_pair.quote == recv_asset.asset_id
-                || market.asset_pair.base == recv_asset.asset_id
-                    && market.asset_pair.quote == send_asset.asset_id
-        })
-        .ok_or(Error::NoMarket)?;
+/// Checks that the peg receive address belongs to the chain the funds are paid out on (and to the used network).
+/// Returns the normalized address.
+fn validate_peg_addr(
+    network: Network,
+    peg_in: bool,
+    recv_addr: &str,
+    allow_unconfidential: bool,
+) -> Result<String, Error> {
+    let recv_addr = recv_addr.trim();
+
+    if peg_in {
+        let invalid = |reason: String| Error::InvalidPegAddress {
+            expected_chain: "Liquid",
+            reason,
+        };
+        let address = match elements::Address::from_str(recv_addr) {
+            Ok(address) => address,
+            Err(err) => {
+                return Err(if elements::bitcoin::Address::from_str(recv_addr).is_ok() {
+                    invalid("Bitcoin address used, peg-ins are paid out on Liquid".to_owned())
+                } else {
+                    invalid(err.to_string())
+                })
+            }
+        };
+        verify!(
+            address.params == network.d().elements_params,
+            invalid(format!("not a {} address", network.d().name))
+        );
+        verify!(
+            address.is_blinded() || allow_unconfidential,
+            invalid("the address is not confidential".to_owned())
+        );
+        Ok(address.to_string())
+    } else {
+        let invalid = |reason: String| Error::InvalidPegAddress {
+            expected_chain: "Bitcoin",
+            reason,
+        };
+        let address = match elements::bitcoin::Address::from_str(recv_addr) {
+            Ok(address) => address,
+            Err(err) => {
+                return Err(if elements::Address::from_str(recv_addr).is_ok() {
+                    invalid("Liquid address used, peg-outs are paid out on Bitcoin".to_owned())
+                } else {
+                    invalid(err.to_string())
+                })
+            }
+        };
+        let bitcoin_network = network.d().bitcoin_network;
+        let address = address
+            .require_network(bitcoin_network)
+            .map_err(|_err| invalid(format!("not a {bitcoin_network} address")))?;
+        Ok(address.to_string())
+    }
+}
 
-    let fee_asset = market.fee_asset;
+/// Resources are attributed to the client name (clients without a name share the `None` owner)
+fn client_name(data: &Data, client_id: ClientId) -> Option<String> {
+    data.clients
+        .get(&client_id)
+        .and_then(|client| client.name.clone())
+}
 
-    let asset_type = if market.asset_pair.base == send_asset.asset_id {
-        AssetType::Base
-    } else {
-        AssetType::Quote
-    };
+/// The number of resources of the type currently used by the owner
+async fn quota_usage(
+    data: &Data,
+    owner: &Option<String>,
+    resource: api::QuotaResource,
+) -> Result<u32, Error> {
+    let used = match resource {
+        api::QuotaResource::Pegs => data
+            .pegs
+            .values()
+            .filter(|peg| peg.created_by == *owner && !peg.finished())
+            .count(),
+
+        api::QuotaResource::MonitoredTxs => {
+            let txids = data
+                .monitored_txs
+                .values()
+                .filter(|monitored_tx| monitored_tx.created_by == *owner)
+                .map(|monitored_tx| monitored_tx.txid.0)
+                .collect::<BTreeSet<_>>();
+            if txids.is_empty() {
+                0
+            } else {
+                let txs = all_wallets_txs(data, Some(txids.clone())).await?;
+                let confirmed = txs.iter().filter(|tx| tx.height.is_some()).count();
+                txids.len() - confirmed
+            }
+        }
 
-    let base_trade_dir = match asset_type {
-        AssetType::Base => TradeDir::Sell,
-        AssetType::Quote => TradeDir::Buy,
+        api::QuotaResource::CreatedTxs => data
+            .created_txs
+            .values()
+            .filter(|created| created.created_by == *owner)
+            .count(),
+
+        api::QuotaResource::Quotes => data
+            .quotes
+            .values()
+            .filter(|quote| quote.created_by == *owner && quote.acceptable(data.ws_generation))
+            .count(),
     };
+    Ok(used as u32)
+}
 
-    let send_amount = try_convert_asset_amount(req.send_amount, send_asset.precision)?;
+/// Fails with `QuotaExceeded` if the owner can't get one more resource of the type
+async fn check_quota(
+    data: &Data,
+    owner: &Option<String>,
+    resource: api::QuotaResource,
+) -> Result<(), Error> {
+    let limit = data
+        .settings
+        .client_quotas
+        .as_ref()
+        .and_then(|quotas| quotas.limit(resource));
+    if let Some(limit) = limit {
+        let used = quota_usage(data, owner, resource).await?;
+        if used >= limit {
+            tracing::info!(
+                client_name = ?owner,
+                resource = %resource,
+                used,
+                limit,
+                "quota exceeded"
+            );
+            abort!(Error::QuotaExceeded { resource, limit });
+        }
+    }
+    Ok(())
+}
 
-    // TODO: Reuse addresses
-    let receive_address = req.receive_address;
-    let change_address = get_new_address(data, true, None).await?.address;
+async fn get_quotas(
+    data: &mut Data,
+    client_id: ClientId,
+    api::GetQuotasReq {}: api::GetQuotasReq,
+) -> Result<api::GetQuotasResp, Error> {
+    let client_name = client_name(data, client_id);
+    let mut quotas = Vec::new();
+    for resource in [
+        api::QuotaResource::Pegs,
+        api::QuotaResource::MonitoredTxs,
+        api::QuotaResource::CreatedTxs,
+        api::QuotaResource::Quotes,
+    ] {
+        quotas.push(api::QuotaUsage {
+            resource,
+            used: quota_usage(data, &client_name, resource).await?,
+            limit: data
+                .settings
+                .client_quotas
+                .as_ref()
+                .and_then(|quotas| quotas.limit(resource)),
+        });
+    }
+    Ok(api::GetQuotasResp {
+        client_name,
+        quotas,
+    })
+}
 
-    let utxos = data
-        .utxo_data
-        .as_ref()
-        .ok_or(Error::NoUtxos)?
-        .utxos()
-        .iter()
-        .filter(|utxo| utxo.asset == send_asset.asset_id)
-        .cloned()
-        .collect::<Vec<_>>();
+async fn new_peg(
+    data: &mut Data,
+    client_id: ClientId,
+    api::NewPegReq {
+        addr_recv,
+        peg_in,
+        fee_rate,
+        allow_unconfidential,
+    }: api::NewPegReq,
+) -> Result<api::NewPegResp, Error> {
+    let recv_addr = validate_peg_addr(
+        data.settings.env.d().network,
+        peg_in,
+        &addr_recv,
+        allow_unconfidential,
+    )?;
 
-    let total = utxos.iter().map(|utxo| utxo.value).sum::<u64>();
+    let created_by = client_name(data, client_id);
+    check_quota(data, &created_by, api::QuotaResource::Pegs).await?;
 
-    verify!(
-        total >= send_amount,
-        Error::NotEnoughAmount {
-            asset_id: send_asset.asset_id,
-            required: send_amount,
-            available: total,
+    let resp = make_request!(
+        data.ws,
+        Peg,
+        sideswap_api::PegRequest {
+            recv_addr: recv_addr.clone(),
+            send_amount: None,
+            peg_in,
+            device_key: None,
+            blocks: None,
+            peg_out_amounts: None,
+            fee_rate,
         }
-    );
+    )?;
 
-    let start_quote_resp = make_market_request!(
+    let status = make_request!(
         data.ws,
-        StartQuotes,
-        mkt::StartQuotesRequest {
-            asset_pair: market.asset_pair,
-            asset_type,
-            amount: send_amount,
-            trade_dir: TradeDir::Sell,
-            utxos,
-            receive_address: receive_address.clone(),
-            change_address,
-            order_id: None,
-            private_id: None,
-            instant_swap: req.instant_swap,
+        PegStatus,
+        sideswap_api::PegStatusRequest {
+            order_id: resp.order_id,
+            peg_in: None,
         }
     )?;
 
-    let deadline = tokio::time::Instant::now() + Duration::from_secs(15);
+    tracing::debug!(order_id = %resp.order_id, "new peg registered");
 
-    let status = loop {
-        let res = tokio::time::timeout_at(deadline, data.ws.recv()).await;
+    data.db
+        .add_peg(Peg {
+            order_id: Text(resp.order_id),
+            addr_recv: Some(recv_addr),
+            created_by: created_by.clone(),
+            completed_at: None,
+        })
+        .await;
 
-        match res {
-            Ok(resp) => {
-                let status = match &resp {
-                    WrappedResponse::Connected => None,
-                    WrappedResponse::Disconnected => Some(QuoteStatus::Disconnected),
-                    WrappedResponse::Response(ResponseMessage::Response(_, _)) => None,
-                    WrappedResponse::Response(ResponseMessage::Notification(
-                        sideswap_api::Notification::Market(mkt::Notification::Quote(quote)),
-                    )) if quote.quote_sub_id == start_quote_resp.quote_sub_id => {
-                        Some(QuoteStatus::Quote(quote.clone()))
-                    }
-                    WrappedResponse::Response(ResponseMessage::Notification(_)) => None,
-                };
+    data.pegs.insert(
+        resp.order_id,
+        PegData {
+            status: None,
+            timeline: Vec::new(),
+            notif_stages: BTreeMap::new(),
+            created_by,
+            completed_at: None,
+            status_request_id: None,
+            server_error: None,
+        },
+    );
 
-                process_ws_event(data, resp).await;
+    let reference =
+        assign_reference(data, api::ReferenceKind::Peg, resp.order_id.to_string()).await;
 
-                if let Some(status) = status {
-                    break status;
-                }
+    process_peg_status(data, status.clone()).await;
 
-                continue;
-            }
+    Ok(api::NewPegResp {
+        peg: convert_peg_status(status, Some(reference)),
+    })
+}
 
-            Err(err) => break QuoteStatus::Timeout(err),
-        };
-    };
+/// The peg-out fee rate target used if not set in `PegFeeEstimate`
+const DEFAULT_PEG_OUT_BLOCKS: i32 = 2;
 
-    let quote = match status {
-        QuoteStatus::Disconnected => abort!(Error::WsError(ws_req_sender::Error::Disconnected)),
-        QuoteStatus::Timeout(err) => abort!(Error::WsError(ws_req_sender::Error::Timeout(err))),
-        QuoteStatus::Quote(quote) => quote,
+async fn peg_fee_estimate(
+    data: &mut Data,
+    api::PegFeeEstimateReq {
+        peg_in,
+        amount,
+        blocks,
+    }: api::PegFeeEstimateReq,
+) -> Result<api::PegFeeEstimateResp, Error> {
+    let precision = AssetPrecision::BITCOIN_PRECISION;
+    let send_amount = try_convert_asset_amount(amount, precision)? as i64;
+
+    let status = make_request!(data.ws, ServerStatus, None)?;
+
+    let (min_amount, server_fee_percent) = if peg_in {
+        (status.min_peg_in_amount, status.server_fee_percent_peg_in)
+    } else {
+        (status.min_peg_out_amount, status.server_fee_percent_peg_out)
+    };
+    let too_low = || Error::PegAmountTooLow {
+        min_amount: asset_float_amount(min_amount, precision),
+    };
+    verify!(send_amount >= min_amount, too_low());
+
+    let (fee_rate, fixed_fee, recv_amount) = if peg_in {
+        let recv_amount = (send_amount as f64 * (1.0 - server_fee_percent / 100.0)).round() as i64;
+        (None, 0, recv_amount)
+    } else {
+        let blocks = blocks.unwrap_or(DEFAULT_PEG_OUT_BLOCKS);
+        let fee_rate = status
+            .bitcoin_fee_rates
+            .iter()
+            .find(|fee_rate| fee_rate.blocks == blocks)
+            .map(|fee_rate| fee_rate.value)
+            .ok_or_else(|| {
+                Error::UnknownFeeTarget(
+                    blocks,
+                    status
+                        .bitcoin_fee_rates
+                        .iter()
+                        .map(|fee_rate| fee_rate.blocks)
+                        .collect(),
+                )
+            })?;
+        let amounts = peg_out_amount(PegOutAmountReq {
+            amount: send_amount,
+            is_send_entered: true,
+            fee_rate,
+            min_peg_out_amount: status.min_peg_out_amount,
+            server_fee_percent_peg_out: status.server_fee_percent_peg_out,
+            peg_out_bitcoin_tx_vsize: status.peg_out_bitcoin_tx_vsize,
+        })
+        // The fees are larger than the amount
+        .map_err(|_err| too_low())?;
+        // Rounded the same way as in `peg_out_amount`
+        let fixed_fee = (status.peg_out_bitcoin_tx_vsize as f64 * fee_rate.raw()).round() as i64;
+        (Some(fee_rate), fixed_fee, amounts.recv_amount)
+    };
+
+    Ok(api::PegFeeEstimateResp {
+        server_fee_percent,
+        fixed_fee: asset_float_amount(fixed_fee, precision),
+        fee_rate,
+        recv_amount: asset_float_amount(recv_amount, precision),
+        min_amount: asset_float_amount(min_amount, precision),
+    })
+}
+
+async fn get_peg_timeline(
+    data: &mut Data,
+    api::GetPegTimelineReq { order_id }: api::GetPegTimelineReq,
+) -> Result<api::GetPegTimelineResp, Error> {
+    let peg = data.pegs.get(&order_id).ok_or(Error::UnknownPeg)?;
+
+    let events = peg
+        .timeline
+        .iter()
+        .map(|event| api::PegEvent {
+            timestamp: TimestampMs::from_millis(event.created_at as u64),
+            tx_hash: event.tx_hash.0,
+            vout: event.vout as u32,
+            tx_state: event.tx_state.0,
+            payout_txid: event.payout_txid.as_ref().map(|txid| txid.0),
+        })
+        .collect();
+
+    Ok(api::GetPegTimelineResp { events })
+}
+
+async fn get_peg_details(
+    data: &mut Data,
+    api::GetPegDetailsReq { order_id }: api::GetPegDetailsReq,
+) -> Result<api::GetPegDetailsResp, Error> {
+    verify!(data.pegs.contains_key(&order_id), Error::UnknownPeg);
+
+    let txs = data
+        .db
+        .load_peg_txs(order_id)
+        .await
+        .into_iter()
+        .map(|tx| api::PegTxDetails {
+            txid: tx.tx_hash.0,
+            vout: tx.vout as u32,
+            amount: asset_float_amount(tx.amount, AssetPrecision::BITCOIN_PRECISION),
+            status: api::PegTxProgress::from_server(&tx.status),
+            payout_txid: tx.payout_txid.map(|txid| txid.0),
+            payout_amount: tx
+                .payout_amount
+                .map(|amount| asset_float_amount(amount, AssetPrecision::BITCOIN_PRECISION)),
+            updated_at: TimestampMs::from_millis(tx.updated_at as u64),
+        })
+        .collect();
+
+    Ok(api::GetPegDetailsResp { txs })
+}
+
+async fn del_peg(
+    data: &mut Data,
+    api::DelPegReq { order_id }: api::DelPegReq,
+) -> Result<api::DelPegResp, Error> {
+    tracing::debug!(order_id = %order_id, "del peg");
+
+    data.pegs.remove(&order_id);
+
+    data.db.delete_peg(order_id).await;
+
+    Ok(api::DelPegResp {})
+}
+
+fn unknown_wallet(data: &Data, name: &str) -> Error {
+    Error::UnknownWallet {
+        wallet: name.to_owned(),
+        available: data.wallets.keys().cloned().collect(),
+    }
+}
+
+/// Fails with `UnknownWallet` if there is no such wallet in the config
+fn wallet<'a>(data: &'a Data, name: &str) -> Result<&'a WalletData, Error> {
+    data.wallets
+        .get(name)
+        .ok_or_else(|| unknown_wallet(data, name))
+}
+
+fn wallet_mut<'a>(data: &'a mut Data, name: &str) -> Result<&'a mut WalletData, Error> {
+    if !data.wallets.contains_key(name) {
+        return Err(unknown_wallet(data, name));
+    }
+    Ok(data.wallets.get_mut(name).expect("must be known"))
+}
+
+/// Returns the change address for new quotes (a new one is requested only after the last one was used)
+async fn quote_change_address(
+    data: &mut Data,
+    wallet_name: &str,
+) -> Result<elements::Address, Error> {
+    if let Some(change_address) = &wallet(data, wallet_name)?.quote_change_address {
+        return Ok(change_address.clone());
+    }
+    let new_address = get_new_address(data, wallet_name, true, None).await?;
+    tracing::debug!(
+        wallet = wallet_name,
+        index = new_address.index,
+        "new quote change address"
+    );
+    wallet_mut(data, wallet_name)?.quote_change_address = Some(new_address.address.clone());
+    Ok(new_address.address)
+}
+
+/// Sends the command to the wallet thread, the response must arrive within `wallet_timeout_seconds`.
+/// The returned future does not borrow `data`, so it can be awaited outside of the worker loop.
+fn wallet_request<T: Send + 'static>(
+    data: &Data,
+    wallet_name: &str,
+    command: impl FnOnce(
+        UncheckedOneshotSender<Result<T, sideswap_lwk::Error>>,
+    ) -> sideswap_lwk::Command,
+) -> impl Future<Output = Result<T, Error>> + Send + 'static {
+    let timeout = data
+        .settings
+        .wallet_timeout_seconds
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_WALLET_TIMEOUT);
+    let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
+    let sent = wallet(data, wallet_name).and_then(|wallet| {
+        wallet
+            .command_sender
+            .send(command(res_sender.into()))
+            .map_err(Error::from)
+    });
+    async move {
+        sent?;
+        let res = tokio::time::timeout(timeout, res_receiver)
+            .await
+            .map_err(|_elapsed| Error::WalletTimeout(timeout.as_secs()))??;
+        Ok(res?)
+    }
+}
+
+/// Monitored txs can be sent by any wallet, the txs of all wallets are merged (without duplicates)
+fn all_wallets_txs(
+    data: &Data,
+    txids: Option<BTreeSet<elements::Txid>>,
+) -> impl Future<Output = Result<Vec<sideswap_lwk::WalletTx>, Error>> + Send + 'static {
+    let requests = data
+        .wallets
+        .keys()
+        .map(|wallet_name| {
+            let txids = txids.clone();
+            wallet_request(data, wallet_name, move |res_sender| {
+                sideswap_lwk::Command::GetTxs {
+                    req: sideswap_lwk::GetTxsReq { txids },
+                    res_sender,
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    async move {
+        let mut seen = BTreeSet::new();
+        let txs = futures::future::try_join_all(requests)
+            .await?
+            .into_iter()
+            .flat_map(|resp| resp.txs)
+            .filter(|tx| seen.insert(tx.txid))
+            .collect();
+        Ok(txs)
+    }
+}
+
+async fn get_new_address(
+    data: &Data,
+    wallet_name: &str,
+    change: bool,
+    index: Option<u32>,
+) -> Result<sideswap_lwk::NewAddrResp, Error> {
+    wallet_request(data, wallet_name, |res_sender| {
+        sideswap_lwk::Command::NewAdddress {
+            req: sideswap_lwk::NewAddrReq { change, index },
+            res_sender,
+        }
+    })
+    .await
+}
+
+/// Returns the first unused address index known to the wallet and the index for the next new address
+async fn next_address_index(data: &Data, wallet_name: &str) -> Result<(u32, u32), Error> {
+    let first_unused_wallet = get_new_address(data, wallet_name, false, None).await?.index;
+    let first_unused_db = wallet(data, wallet_name)?
+        .addresses
+        .last_key_value()
+        .map(|(_key, value)| value.ind as u32 + 1)
+        .unwrap_or_default();
+    let new_index = u32::max(first_unused_wallet, first_unused_db);
+    Ok((first_unused_wallet, new_index))
+}
+
+fn gap_limit(data: &Data) -> u32 {
+    data.settings.gap_limit.unwrap_or(DEFAULT_GAP_LIMIT)
+}
+
+/// How many more addresses can be generated before the gap limit is reached
+fn gap_limit_remaining(data: &Data, wallet: &WalletData, first_unused_wallet: u32) -> u32 {
+    let unused_run = wallet
+        .addresses
+        .last_key_value()
+        .map(|(index, _addr)| (index + 1).saturating_sub(first_unused_wallet))
+        .unwrap_or_default();
+    gap_limit(data).saturating_sub(unused_run)
+}
+
+/// Sends `GapLimitWarning` if the headroom goes below the threshold or goes back above it.
+/// Returns the remaining headroom.
+fn update_gap_limit_warning(data: &mut Data, wallet_name: &str, first_unused_wallet: u32) -> u32 {
+    let wallet = data.wallets.get(wallet_name).expect("must be known");
+    let remaining = gap_limit_remaining(data, wallet, first_unused_wallet);
+    let threshold = data
+        .settings
+        .gap_limit_warning
+        .unwrap_or(DEFAULT_GAP_LIMIT_WARNING);
+    let active = remaining < threshold;
+
+    if active != wallet.gap_limit_warning.is_some() {
+        let notif = api::GapLimitWarningNotif {
+            wallet: wallet_name.to_owned(),
+            remaining,
+            first_unused_index: first_unused_wallet,
+            active,
+        };
+        if active {
+            tracing::warn!(wallet = wallet_name, "gap limit is close, only {remaining} new addresses can be generated, first unused index: {first_unused_wallet}");
+        } else {
+            tracing::info!(
+                wallet = wallet_name,
+                "gap limit warning cleared, {remaining} new addresses can be generated"
+            );
+        }
+        let wallet = data.wallets.get_mut(wallet_name).expect("must be known");
+        wallet.gap_limit_warning = active.then(|| notif.clone());
+        send_notifs(data, &api::Notif::GapLimitWarning(notif));
+    }
+
+    remaining
+}
+
+async fn new_address(
+    data: &mut Data,
+    api::NewAddressReq {
+        user_note,
+        wallet: wallet_name,
+    }: api::NewAddressReq,
+) -> Result<api::NewAddressResp, Error> {
+    let (first_unused_wallet, new_index) = next_address_index(data, &wallet_name).await?;
+    verify!(
+        new_index - first_unused_wallet < gap_limit(data),
+        Error::GapLimit
+    );
+
+    let new_address = get_new_address(data, &wallet_name, false, Some(new_index)).await?;
+
+    let addr = models::Address {
+        wallet: wallet_name.clone(),
+        ind: new_index.into(),
+        address: Text(new_address.address.clone()),
+        user_note,
+    };
+    data.db.add_address(addr.clone()).await;
+    wallet_mut(data, &wallet_name)?
+        .addresses
+        .insert(new_index, addr);
+
+    let gap_limit_remaining = update_gap_limit_warning(data, &wallet_name, first_unused_wallet);
+
+    Ok(api::NewAddressResp {
+        index: new_index,
+        address: new_address.address,
+        gap_limit_remaining,
+    })
+}
+
+async fn new_address_batch(
+    data: &mut Data,
+    api::NewAddressBatchReq {
+        count,
+        note_prefix,
+        wallet: wallet_name,
+    }: api::NewAddressBatchReq,
+) -> Result<api::NewAddressBatchResp, Error> {
+    let (first_unused_wallet, first_index) = next_address_index(data, &wallet_name).await?;
+    let available = (first_unused_wallet + gap_limit(data)).saturating_sub(first_index);
+    let created_count = u32::min(count, available);
+
+    let mut addresses = Vec::new();
+    for (seq, index) in (first_index..first_index + created_count).enumerate() {
+        let new_address = get_new_address(data, &wallet_name, false, Some(index)).await?;
+        addresses.push(models::Address {
+            wallet: wallet_name.clone(),
+            ind: index.into(),
+            address: Text(new_address.address),
+            user_note: note_prefix
+                .as_ref()
+                .map(|note_prefix| format!("{note_prefix}{}", seq + 1)),
+        });
+    }
+
+    data.db.add_addresses(&addresses).await;
+
+    let own_addresses = &mut wallet_mut(data, &wallet_name)?.addresses;
+    let addresses = addresses
+        .into_iter()
+        .map(|addr| {
+            let resp = api::Address {
+                index: addr.ind as u32,
+                address: addr.address.0.clone(),
+                user_note: addr.user_note.clone(),
+            };
+            own_addresses.insert(addr.ind as u32, addr);
+            resp
+        })
+        .collect();
+
+    let stopped_reason = (created_count < count).then_some(api::AddressBatchStopReason::GapLimit);
+
+    let gap_limit_remaining = update_gap_limit_warning(data, &wallet_name, first_unused_wallet);
+
+    Ok(api::NewAddressBatchResp {
+        addresses,
+        stopped_reason,
+        gap_limit_remaining,
+    })
+}
+
+/// Answered outside of the worker loop (see `start_request`)
+fn list_addresses(
+    data: &Data,
+    api::ListAddressesReq {
+        include_change,
+        wallet: wallet_name,
+    }: api::ListAddressesReq,
+) -> impl Future<Output = Result<api::ListAddressesResp, Error>> + Send + 'static {
+    let resp = wallet_request(data, &wallet_name, |res_sender| {
+        sideswap_lwk::Command::GetTxs {
+            req: sideswap_lwk::GetTxsReq { txids: None },
+            res_sender,
+        }
+    });
+    let own_addresses = wallet(data, &wallet_name)
+        .map(|wallet| wallet.addresses.values().cloned().collect::<Vec<_>>());
+
+    async move {
+        let own_addresses = own_addresses?;
+        let resp = resp.await?;
+
+        // (change, index) -> address
+        let used = resp
+            .txs
+            .iter()
+            .flat_map(|tx| tx.outputs.iter().flatten())
+            .map(|output| {
+                let change = output.ext_int == sideswap_lwk::Chain::Internal;
+                ((change, output.wildcard_index), output.address.clone())
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut addresses = own_addresses
+            .into_iter()
+            .map(|address| api::ListedAddress {
+                index: address.ind as u32,
+                address: address.address.0,
+                user_note: address.user_note,
+                change: false,
+                used: used.contains_key(&(false, address.ind as u32)),
+            })
+            .collect::<Vec<_>>();
+
+        if include_change {
+            addresses.extend(used.into_iter().filter(|((change, _), _)| *change).map(
+                |((_, index), address)| api::ListedAddress {
+                    index,
+                    address,
+                    user_note: None,
+                    change: true,
+                    used: true,
+                },
+            ));
+        }
+
+        Ok(api::ListAddressesResp { addresses })
+    }
+}
+
+/// Own wallet addresses (of any wallet) are always allowed
+fn check_address_allowed(data: &Data, address: &elements::Address) -> Result<(), Error> {
+    let own_address = |wallet: &WalletData| {
+        wallet.addresses.values().any(|own| own.address.0 == *address)
+        // The first leg of a routed quote is received there
+        || wallet.quote_change_address.as_ref() == Some(address)
+    };
+    if !data.settings.enforce_allowlist
+        || data.allowed_addresses.contains_key(&address.to_string())
+        || data.wallets.values().any(own_address)
+    {
+        Ok(())
+    } else {
+        Err(Error::AddressNotAllowed(address.clone()))
+    }
+}
+
+async fn add_allowed_address(
+    data: &mut Data,
+    api::AddAllowedAddressReq {
+        address,
+        label,
+        added_by,
+    }: api::AddAllowedAddressReq,
+) -> Result<api::AddAllowedAddressResp, Error> {
+    let allowed = models::AllowedAddress {
+        address: Text(address.clone()),
+        label,
+        added_by,
+        added_at: TimestampMs::now().millis() as i64,
+    };
+
+    data.db.add_allowed_address(&allowed).await;
+
+    audit(
+        data,
+        format!(
+            "allowed address added: {address}, label: {:?}, added_by: {:?}",
+            allowed.label, allowed.added_by
+        ),
+    )
+    .await;
+
+    data.allowed_addresses.insert(address.to_string(), allowed);
+
+    Ok(api::AddAllowedAddressResp {})
+}
+
+async fn remove_allowed_address(
+    data: &mut Data,
+    api::RemoveAllowedAddressReq { address }: api::RemoveAllowedAddressReq,
+) -> Result<api::RemoveAllowedAddressResp, Error> {
+    if data
+        .allowed_addresses
+        .remove(&address.to_string())
+        .is_some()
+    {
+        data.db.delete_allowed_address(&address).await;
+        audit(data, format!("allowed address removed: {address}")).await;
+    }
+
+    Ok(api::RemoveAllowedAddressResp {})
+}
+
+async fn sign_message(
+    data: &mut Data,
+    api::SignMessageReq {
+        index_or_address,
+        message,
+        wallet: wallet_name,
+    }: api::SignMessageReq,
+) -> Result<api::SignMessageResp, Error> {
+    verify!(
+        message.len() <= MAX_MESSAGE_LEN,
+        Error::MessageTooLong(MAX_MESSAGE_LEN)
+    );
+
+    let own_addresses = &wallet(data, &wallet_name)?.addresses;
+    let own = match &index_or_address {
+        api::IndexOrAddress::Index(index) => own_addresses.get(index),
+        api::IndexOrAddress::Address(address) => {
+            own_addresses.values().find(|own| own.address.0 == *address)
+        }
+    };
+    let Some(own) = own else {
+        let address = match index_or_address {
+            api::IndexOrAddress::Index(index) => format!("#{index}"),
+            api::IndexOrAddress::Address(address) => address.to_string(),
+        };
+        return Err(Error::NotOwnAddress(address));
+    };
+    let index = own.ind as u32;
+    let address = own.address.0.clone();
+
+    let resp = wallet_request(data, &wallet_name, |res_sender| {
+        sideswap_lwk::Command::SignMessage {
+            req: sideswap_lwk::SignMessageReq {
+                index,
+                message: message.clone(),
+            },
+            res_sender,
+        }
+    })
+    .await?;
+    verify!(
+        resp.address.script_pubkey() == address.script_pubkey(),
+        Error::NotOwnAddress(address.to_string())
+    );
+
+    audit(
+        data,
+        format!("message signed with {wallet_name} wallet address {address} (index {index}): {message:?}"),
+    )
+    .await;
+
+    Ok(api::SignMessageResp {
+        index,
+        address,
+        signature: resp.signature,
+    })
+}
+
+fn verify_message(
+    api::VerifyMessageReq {
+        address,
+        message,
+        signature,
+    }: api::VerifyMessageReq,
+) -> Result<api::VerifyMessageResp, Error> {
+    verify!(
+        message.len() <= MAX_MESSAGE_LEN,
+        Error::MessageTooLong(MAX_MESSAGE_LEN)
+    );
+    let valid = sideswap_lwk::verify_message(&address, &message, &signature)?;
+    Ok(api::VerifyMessageResp { valid })
+}
+
+fn list_allowed_addresses(
+    data: &Data,
+    api::ListAllowedAddressesReq {}: api::ListAllowedAddressesReq,
+) -> Result<api::ListAllowedAddressesResp, Error> {
+    let mut addresses = data
+        .allowed_addresses
+        .values()
+        .map(|allowed| api::AllowedAddress {
+            address: allowed.address.0.clone(),
+            label: allowed.label.clone(),
+            added_by: allowed.added_by.clone(),
+            added_at: TimestampMs::from_millis(allowed.added_at as u64),
+        })
+        .collect::<Vec<_>>();
+    addresses.sort_by_key(|allowed| allowed.added_at);
+
+    Ok(api::ListAllowedAddressesResp {
+        enforced: data.settings.enforce_allowlist,
+        addresses,
+    })
+}
+
+async fn get_balance_history(
+    data: &Data,
+    req: api::GetBalanceHistoryReq,
+) -> Result<api::GetBalanceHistoryResp, Error> {
+    let asset = req
+        .asset
+        .map(|ticker| try_get_asset(&data.ticker_loader, ticker))
+        .transpose()?;
+    let from = req.from.map_or(0, |from| from.millis() as i64);
+    let to = req.to.map_or(i64::MAX, |to| to.millis() as i64);
+    verify!(
+        from <= to,
+        Error::InvalidHistoryRequest("from must not be after to")
+    );
+    verify!(
+        req.granularity_seconds != Some(0),
+        Error::InvalidHistoryRequest("granularity_seconds must not be zero")
+    );
+
+    wallet(data, &req.wallet)?;
+
+    let snapshots =
+        balance_history::snapshots(data.db.load_balance_history(&req.wallet, from, to).await);
+
+    let granularity = req
+        .granularity_seconds
+        .map(|seconds| seconds * 1000)
+        .or_else(|| balance_history::auto_granularity(&snapshots));
+    let snapshots = match granularity {
+        Some(granularity) => balance_history::downsample(snapshots, granularity),
+        None => snapshots,
+    };
+
+    // Report zero balances for the assets held before
+    let mut known_assets = BTreeSet::new();
+    let points = snapshots
+        .into_iter()
+        .map(|snapshot| {
+            known_assets.extend(snapshot.balances.keys().copied());
+            let balances = known_assets
+                .iter()
+                .filter(|asset_id| {
+                    asset
+                        .as_ref()
+                        .is_none_or(|asset| asset.asset_id == **asset_id)
+                })
+                .filter_map(|asset_id| {
+                    let ticker = data.ticker_loader.ticker(asset_id)?;
+                    let precision = data.ticker_loader.precision(ticker);
+                    let amount = snapshot.balances.get(asset_id).copied().unwrap_or_default();
+                    Some((ticker, asset_float_amount_(amount, precision)))
+                })
+                .collect();
+            api::BalancePoint {
+                timestamp: TimestampMs::from_millis(snapshot.created_at as u64),
+                balances,
+            }
+        })
+        .collect();
+
+    Ok(api::GetBalanceHistoryResp {
+        points,
+        granularity_seconds: granularity.map(|granularity| granularity / 1000),
+    })
+}
+
+/// A request recipient with the parsed address and the resolved ticker
+struct ParsedRecipient {
+    address: elements::Address,
+    /// `unknown_asset_key` for a payment URI asset that is not whitelisted
+    asset: DealerTicker,
+    asset_id: AssetId,
+    precision: AssetPrecision,
+    amount: f64,
+    send_all: bool,
+    /// The payment URI label
+    label: Option<String>,
+}
+
+/// A transaction output paying one or more (aggregated) request recipients
+struct TxOutput {
+    recipient: ParsedRecipient,
+    precision: AssetPrecision,
+    /// The total amount in the asset base units
+    amount: u64,
+    /// Indices of the paid request recipients
+    recipient_indices: Vec<usize>,
+    /// The payment URI labels of the paid request recipients
+    labels: Vec<String>,
+}
+
+/// Recipients with the same asset and the same destination script are duplicates.
+/// The parsed addresses are compared, so the different string forms of the same address are caught too.
+/// Duplicates are merged into one output if `aggregate` is set, otherwise `DuplicateRecipient` is returned.
+fn merge_duplicate_recipients(
+    recipients: Vec<(ParsedRecipient, AssetPrecision, u64)>,
+    aggregate: bool,
+) -> Result<Vec<TxOutput>, Error> {
+    let mut outputs = Vec::<TxOutput>::new();
+    for (index, (recipient, precision, amount)) in recipients.into_iter().enumerate() {
+        let duplicate = outputs.iter_mut().find(|output| {
+            output.recipient.asset_id == recipient.asset_id
+                && output.recipient.address.script_pubkey() == recipient.address.script_pubkey()
+        });
+        match duplicate {
+            Some(output) => {
+                verify!(
+                    aggregate,
+                    Error::DuplicateRecipient {
+                        index_a: output.recipient_indices[0],
+                        index_b: index,
+                    }
+                );
+                output.amount = output
+                    .amount
+                    .checked_add(amount)
+                    .ok_or(Error::InvalidAssetAmount(recipient.amount, precision))?;
+                output.recipient_indices.push(index);
+                output.labels.extend(recipient.label);
+            }
+            None => outputs.push(TxOutput {
+                labels: recipient.label.iter().cloned().collect(),
+                recipient,
+                precision,
+                amount,
+                recipient_indices: vec![index],
+            }),
+        }
+    }
+    Ok(outputs)
+}
+
+/// Resolves a recipient set either with the address and asset or with the payment URI
+fn parse_recipient(
+    data: &Data,
+    index: usize,
+    recipient: api::Recipient,
+    allow_unconfidential: bool,
+) -> Result<ParsedRecipient, Error> {
+    let invalid = |reason: &str| Error::InvalidRecipient {
+        index,
+        reason: reason.to_owned(),
+    };
+
+    let (address, asset, asset_id, precision, amount, label) =
+        match (recipient.address, recipient.asset, recipient.uri) {
+            (Some(address), Some(asset), None) => {
+                let asset = data.ticker_loader.resolve_ticker(asset.as_str())?;
+                let asset_id = *data.ticker_loader.asset_id(asset);
+                let precision = data.ticker_loader.precision(asset);
+                (address, asset, asset_id, precision, recipient.amount, None)
+            }
+            (None, None, Some(uri)) => {
+                let uri = payment_uri::parse(&uri)
+                    .map_err(|reason| Error::InvalidRecipient { index, reason })?;
+                let amount = match uri.amount {
+                    Some(amount) => {
+                        verify!(
+                            recipient.amount == 0.0 && !recipient.send_all,
+                            invalid("amount and send_all must not be set if the URI has an amount")
+                        );
+                        amount
+                    }
+                    None => recipient.amount,
+                };
+                let asset_id = uri.asset_id.unwrap_or(data.policy_asset);
+                let (asset, precision) = match data.ticker_loader.ticker(&asset_id) {
+                    Some(ticker) => (ticker, data.ticker_loader.precision(ticker)),
+                    // Not whitelisted, the registry precision is used to convert the amount
+                    None => {
+                        let precision = data
+                            .gdk_registry
+                            .as_ref()
+                            .and_then(|registry| registry.get_short_asset(&asset_id))
+                            .map(|asset| asset.precision)
+                            .ok_or_else(|| invalid(&format!("unknown asset {asset_id}")))?;
+                        (unknown_asset_key(&asset_id), precision)
+                    }
+                };
+                (uri.address, asset, asset_id, precision, amount, uri.label)
+            }
+            _ => abort!(invalid("either address and asset or uri must be set")),
+        };
+
+    let network = data.settings.env.d().network;
+    let address = parse_recipient_address(network, &address, allow_unconfidential)
+        .map_err(|reason| Error::InvalidAddress { index, reason })?;
+
+    Ok(ParsedRecipient {
+        address,
+        asset,
+        asset_id,
+        precision,
+        amount,
+        send_all: recipient.send_all,
+        label,
+    })
+}
+
+async fn create_tx(
+    data: &mut Data,
+    client_id: ClientId,
+    api::CreateTxReq {
+        recipients,
+        aggregate_duplicates,
+        fee_rate,
+        allow_unconfidential,
+        utxos,
+        allow_unconfirmed,
+        wallet: wallet_name,
+    }: api::CreateTxReq,
+) -> Result<api::CreateTxResp, Error> {
+    let wallet = wallet(data, &wallet_name)?;
+
+    if let Some(fee_rate) = fee_rate {
+        verify!(
+            (MIN_FEE_RATE..=MAX_FEE_RATE).contains(&fee_rate.raw()),
+            Error::InvalidFeeRate(fee_rate.raw())
+        );
+    }
+
+    let recipients = recipients
+        .into_iter()
+        .enumerate()
+        .map(|(index, recipient)| parse_recipient(data, index, recipient, allow_unconfidential))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    for recipient in recipients.iter().filter(|recipient| recipient.send_all) {
+        let same_asset = recipients
+            .iter()
+            .filter(|other| other.asset_id == recipient.asset_id)
+            .count();
+        verify!(same_asset == 1, Error::SendAllNotAlone(recipient.asset));
+    }
+
+    for recipient in recipients.iter() {
+        check_address_allowed(data, &recipient.address)?;
+
+        let asset_id = recipient.asset_id;
+        let amp_restricted = data
+            .asset_flags
+            .get(&asset_id)
+            .is_some_and(|flags| flags.amp_restricted);
+        verify!(
+            !amp_restricted
+                || data
+                    .gaid_addresses
+                    .contains(&(asset_id, recipient.address.clone())),
+            Error::AmpAddressRequired {
+                asset: recipient.asset
+            }
+        );
+    }
+
+    // Only L-BTC UTXOs can be selected manually with the wallet, other assets are rejected
+    let lbtc_only = recipients
+        .iter()
+        .find(|recipient| recipient.asset_id != data.policy_asset);
+    let utxos = match (utxos, lbtc_only) {
+        (Some(_), Some(recipient)) => abort!(Error::UtxoSelectionNotSupported(recipient.asset)),
+        (Some(utxos), None) => Some(utxos),
+        // The wallet picks the L-BTC UTXOs only from the confirmed ones
+        (None, None) if !allow_unconfirmed => {
+            Some(wallet.confirmed_utxos.iter().copied().collect())
+        }
+        (None, _) => None,
+    };
+    let utxos = match utxos {
+        Some(utxos) => {
+            let utxos = selected_utxos(data, &wallet_name, Some(&utxos), allow_unconfirmed)?
+                .into_iter()
+                .filter(|utxo| utxo.asset == data.policy_asset)
+                .collect::<Vec<_>>();
+            Some(utxos)
+        }
+        None => None,
+    };
+
+    let recipients = recipients
+        .into_iter()
+        .map(|recipient| {
+            let precision = recipient.precision;
+            let asset_id = recipient.asset_id;
+            let amount = if !recipient.send_all {
+                try_convert_asset_amount(recipient.amount, precision)?
+            } else if asset_id == data.policy_asset {
+                // Drained by the wallet, the amount is known after the tx is created
+                0
+            } else {
+                let balances = if allow_unconfirmed {
+                    &wallet.balances
+                } else {
+                    &wallet.confirmed_balances
+                };
+                let balance = balances
+                    .as_ref()
+                    .and_then(|balances| balances.get(&asset_id))
+                    .copied()
+                    .unwrap_or_default();
+                verify!(balance > 0, Error::NothingToSend(recipient.asset));
+                balance
+            };
+            Ok((recipient, precision, amount))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let outputs = merge_duplicate_recipients(recipients, aggregate_duplicates)?;
+
+    if let Some(utxos) = &utxos {
+        let available = utxos.iter().map(|utxo| utxo.value).sum::<u64>();
+        let required = outputs.iter().map(|output| output.amount).sum::<u64>();
+        verify!(
+            available >= required && available > 0,
+            Error::NotEnoughAmount {
+                asset_id: data.policy_asset,
+                required,
+                available,
+            }
+        );
+    }
+
+    let created_by = client_name(data, client_id);
+    check_quota(data, &created_by, api::QuotaResource::CreatedTxs).await?;
+
+    let is_drained = |output: &TxOutput| {
+        output.recipient.send_all && output.recipient.asset_id == data.policy_asset
+    };
+
+    let drain_lbtc_to = outputs
+        .iter()
+        .find(|output| is_drained(output))
+        .map(|output| output.recipient.address.clone());
+
+    let recipients = outputs
+        .iter()
+        .filter(|output| !is_drained(output))
+        .map(|output| sideswap_common::recipient::Recipient {
+            address: output.recipient.address.clone(),
+            asset_id: output.recipient.asset_id,
+            amount: output.amount,
+        })
+        .collect::<Vec<_>>();
+
+    for recipient in recipients.iter() {
+        tracing::debug!(
+            address = %recipient.address,
+            asset_id = %recipient.asset_id,
+            amount = recipient.amount,
+            "tx recipient"
+        );
+    }
+
+    let confirmed_utxos = wallet.confirmed_utxos.clone();
+
+    let resp = wallet_request(data, &wallet_name, |res_sender| {
+        sideswap_lwk::Command::CreateTx {
+            req: sideswap_lwk::CreateTxReq {
+                recipients,
+                drain_lbtc_to,
+                fee_rate: fee_rate.map(|fee_rate| fee_rate.raw()),
+                utxos: utxos.map(|utxos| utxos.iter().map(UtxoExt::outpoint).collect()),
+            },
+            res_sender,
+        }
+    })
+    .await?;
+
+    // The wallet can't be restricted for other assets (and the fee inputs), the created tx is checked instead
+    if !allow_unconfirmed {
+        let unconfirmed = resp
+            .tx
+            .input
+            .iter()
+            .filter(|input| !confirmed_utxos.contains(&input.previous_output))
+            .count();
+        verify!(unconfirmed == 0, Error::UnconfirmedInputs(unconfirmed));
+    }
+
+    let txid = resp.tx.txid();
+    let network_fee = resp.tx.fee_in(data.policy_asset);
+    let vsize = resp.tx.discount_vsize();
+    let fee_rate = FeeRateSats::from_fee(network_fee, vsize);
+    tracing::debug!(txid = %txid, wallet = wallet_name, network_fee, vsize, drained_amount = ?resp.drained_amount, "tx created");
+
+    let created_recipients = outputs
+        .iter()
+        .map(|output| {
+            let amount = if is_drained(output) {
+                resp.drained_amount.unwrap_or_default()
+            } else {
+                output.amount
+            };
+            api::CreatedTxRecipient {
+                address: output.recipient.address.clone(),
+                asset: output.recipient.asset,
+                amount: asset_float_amount(amount as i64, output.precision),
+                recipient_indices: output.recipient_indices.clone(),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let description = created_recipients
+        .iter()
+        .zip(outputs.iter())
+        .map(|(recipient, output)| {
+            let description = format!(
+                "send {} {} to {}",
+                recipient.amount, recipient.asset, recipient.address
+            );
+            if output.labels.is_empty() {
+                description
+            } else {
+                format!("{description} ({})", output.labels.join(", "))
+            }
+        })
+        .collect::<Vec<_>>();
+    let description = description.join(", ");
+
+    let mut amounts = BTreeMap::<DealerTicker, f64>::new();
+    for recipient in created_recipients.iter() {
+        *amounts.entry(recipient.asset).or_default() += recipient.amount;
+    }
+
+    add_created_tx(
+        data,
+        txid,
+        CreatedTx {
+            tx: resp.tx,
+            wallet: wallet_name,
+            description,
+            created_by,
+            amounts,
+            created_at: TimestampMs::now(),
+        },
+    )
+    .await;
+
+    let reference = assign_reference(data, api::ReferenceKind::Tx, txid.to_string()).await;
+
+    Ok(api::CreateTxResp {
+        txid,
+        network_fee,
+        fee_rate,
+        vsize,
+        recipients: created_recipients,
+        reference,
+    })
+}
+
+async fn send_tx(
+    data: &mut Data,
+    client_id: ClientId,
+    mut req: api::SendTxReq,
+) -> Result<api::SendTxResp, Error> {
+    if let Some(key) = req.idempotency_key.take() {
+        let request = idempotency::Request::SendTx { txid: req.txid };
+        let id = idempotency::KeyId::new(client_name(data, client_id), &req.wallet, key, &request);
+        if let Some(resp) = reserve_idempotency_key(data, &id, request).await? {
+            return Ok(resp);
+        }
+        let res = Box::pin(send_tx(data, client_id, req)).await;
+        finish_idempotency_key(data, &id, &res).await;
+        return res;
+    }
+
+    // The created transaction is removed once sent, a repeated request (from the same wallet) returns the stored result
+    if !data.created_txs.contains_key(&req.txid) {
+        if let Some(send_result) = data
+            .monitored_txs
+            .get(&req.txid)
+            .filter(|monitored_tx| monitored_tx.wallet.as_deref() == Some(req.wallet.as_str()))
+            .and_then(|monitored_tx| monitored_tx.send_result.as_ref())
+        {
+            tracing::info!(txid = %req.txid, "the tx is sent already, return the stored result");
+            return Ok(send_result.0.clone());
+        }
+    }
+
+    wallet(data, &req.wallet)?;
+    // The txid of another wallet is not leaked (it is not created as far as the requested wallet is concerned)
+    if let Some(created) = data.created_txs.get(&req.txid) {
+        verify!(created.wallet == req.wallet, Error::NoCreatedTx);
+    }
+
+    let created_by = client_name(data, client_id);
+
+    if let Some(config) = &data.settings.approvals {
+        let created = data.created_txs.get(&req.txid).ok_or(Error::NoCreatedTx)?;
+        if config.approval_required(&created.amounts) {
+            let ttl = config.ttl();
+            let operation = approvals::Operation::SendTx {
+                tx: elements::encode::serialize_hex(&created.tx),
+                description: created.description.clone(),
+                user_note: req.user_note,
+                wallet_only: req.wallet_only,
+                wallet: req.wallet,
+            };
+            let description = created.description.clone();
+            let amounts = created.amounts.clone();
+            return Err(request_approval(
+                data,
+                created_by,
+                operation,
+                req.txid,
+                description,
+                amounts,
+                ttl,
+            )
+            .await);
+        }
+    }
+
+    execute_send_tx(data, created_by, req).await
+}
+
+async fn execute_send_tx(
+    data: &mut Data,
+    created_by: Option<String>,
+    api::SendTxReq {
+        txid,
+        user_note,
+        wallet_only,
+        idempotency_key: _,
+        wallet: _,
+    }: api::SendTxReq,
+) -> Result<api::SendTxResp, Error> {
+    if !data.monitored_txs.contains_key(&txid) {
+        check_quota(data, &created_by, api::QuotaResource::MonitoredTxs).await?;
+    }
+
+    let created = data.created_txs.get(&txid).ok_or(Error::NoCreatedTx)?;
+
+    let outpoints = created
+        .tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<_>>();
+
+    {
+        let mut tx_outpoints = outpoints.iter().copied().collect::<BTreeSet<_>>();
+        let utxo_data = wallet(data, &created.wallet)?
+            .utxo_data
+            .as_ref()
+            .ok_or_else(|| Error::UtxoCheckFailed("utxo_data is None".to_owned()))?;
+        for utxo in utxo_data.utxos() {
+            tx_outpoints.remove(&utxo.outpoint());
+        }
+        verify!(
+            tx_outpoints.is_empty(),
+            Error::UtxoCheckFailed("Can't find wallet UTXOs".to_owned())
+        );
+    }
+
+    if !wallet_only {
+        // Verify that UTXOs are not spent and known on the server
+        let _verify_resp = make_market_request!(
+            data.ws,
+            CheckOutpoints,
+            mkt::CheckOutpointsRequest { outpoints }
+        )
+        .map_err(|err| Error::UtxoCheckFailed(err.to_string()))?;
+    }
+
+    new_monitored_tx(
+        &data.db,
+        &mut data.monitored_txs,
+        MonitoredTx {
+            txid: Text(txid),
+            description: Some(created.description.clone()),
+            user_note,
+            created_by,
+            send_result: None,
+            wallet: Some(created.wallet.clone()),
+            created_at: TimestampMs::now().millis() as i64,
+        },
+    )
+    .await;
+
+    let tx = elements::encode::serialize_hex(&created.tx);
+
+    let res_server = if wallet_only {
+        None
+    } else {
+        let res = make_market_request!(
+            data.ws,
+            BroadcastTx,
+            mkt::BroadcastTxRequest {
+                tx: created.tx.clone().into()
+            }
+        );
+
+        match res {
+            Ok(_txid) => Some(api::BroadcastStatus::Success {}),
+            Err(err) => Some(api::BroadcastStatus::Error {
+                error_msg: err.to_string(),
+            }),
+        }
+    };
+
+    let res_wallet = {
+        let res_wallet = wallet_request(data, &created.wallet, |res_sender| {
+            sideswap_lwk::Command::BroadcastTx {
+                tx,
+                res_sender: Some(res_sender),
+            }
+        })
+        .await;
+
+        match res_wallet {
+            Ok(_txid) => api::BroadcastStatus::Success {},
+            Err(err) => api::BroadcastStatus::Error {
+                error_msg: err.to_string(),
+            },
+        }
+    };
+
+    // Other created transactions might still be sent (if they don't spend the same UTXOs)
+    let created = data.created_txs.remove(&txid).expect("must be known");
+    data.db.delete_created_tx(txid).await;
+
+    let server_failed = matches!(res_server, Some(api::BroadcastStatus::Error { .. }));
+    let wallet_ok = matches!(res_wallet, api::BroadcastStatus::Success {});
+    if server_failed {
+        data.metrics.broadcast_failed(BroadcastTarget::Server);
+    }
+    if !wallet_ok {
+        data.metrics.broadcast_failed(BroadcastTarget::Wallet);
+    }
+    if server_failed && wallet_ok {
+        // The tx is in the mempool now, but the server does not know about it yet
+        data.pending_broadcasts.insert(
+            txid,
+            PendingBroadcast {
+                tx: created.tx,
+                attempts: 0,
+                request_id: None,
+            },
+        );
+    }
+
+    tracing::info!(
+        txid = %txid,
+        server_ok = matches!(res_server, Some(api::BroadcastStatus::Success {})),
+        wallet_ok = matches!(res_wallet, api::BroadcastStatus::Success {}),
+        "tx sent"
+    );
+
+    let resp = api::SendTxResp {
+        res_wallet,
+        res_server,
+    };
+
+    let send_result = Json(resp.clone());
+    data.db
+        .set_monitored_tx_send_result(txid, &send_result)
+        .await;
+    if let Some(monitored_tx) = data.monitored_txs.get_mut(&txid) {
+        monitored_tx.send_result = Some(send_result);
+    }
+
+    Ok(resp)
+}
+
+/// Returns the stored response if the key was used for the same request already,
+/// otherwise the key is reserved for the request (and the expired keys are removed)
+async fn reserve_idempotency_key<T: serde::de::DeserializeOwned>(
+    data: &Data,
+    id: &idempotency::KeyId,
+    request: idempotency::Request,
+) -> Result<Option<T>, Error> {
+    idempotency::validate_key(&id.key)?;
+
+    let retention = data
+        .settings
+        .idempotency_key_retention_seconds
+        .map(Duration::from_secs)
+        .unwrap_or(idempotency::DEFAULT_RETENTION);
+    let now = TimestampMs::now().millis() as i64;
+    data.db
+        .delete_idempotency_keys_before(now - retention.as_millis() as i64)
+        .await;
+
+    let stored = data.db.load_idempotency_key(id).await;
+    if let Some(response) = idempotency::check(stored.as_ref(), &request, now, retention)? {
+        tracing::info!(key = id.key, "replayed request, return the stored response");
+        let resp = serde_json::from_str(&response)
+            .map_err(|err| Error::InvalidStoredResponse(err.to_string()))?;
+        return Ok(Some(resp));
+    }
+
+    data.db
+        .add_idempotency_key(&models::IdempotencyKey {
+            client: id.client.clone(),
+            wallet: id.wallet.clone(),
+            kind: id.kind.to_owned(),
+            key: id.key.clone(),
+            request: Json(request),
+            response: None,
+            created_at: now,
+        })
+        .await;
+    Ok(None)
+}
+
+/// Stores the successful response, the key is released if the request failed (so it can be retried)
+async fn finish_idempotency_key<T: serde::Serialize>(
+    data: &Data,
+    id: &idempotency::KeyId,
+    res: &Result<T, Error>,
+) {
+    match res {
+        Ok(resp) => {
+            let response = serde_json::to_string(resp).expect("must not fail");
+            data.db.set_idempotency_response(id, &response).await;
+        }
+        Err(_) => data.db.delete_idempotency_key(id).await,
+    }
+}
+
+fn coalesced_quote(data: &mut Data, key: &quote_coalescing::Key) -> Option<api::GetQuoteResp> {
+    let now = Instant::now();
+    let quote_id = data.quote_coalescing.as_mut()?.get(key, now)?;
+    // The quote must still be acceptable (not expired or accepted already)
+    let quote = data
+        .quotes
+        .get(&quote_id)
+        .filter(|quote| quote.acceptable(data.ws_generation))?;
+    tracing::debug!(quote_id = ?quote_id, txid = %quote.txid, "return coalesced quote");
+    Some(api::GetQuoteResp {
+        quote_id,
+        send_amount: quote.send_amount,
+        recv_amount: quote.recv_amount,
+        receive_address: quote.receive_address.clone(),
+        ttl: quote.expires_at.saturating_duration_since(now).into(),
+        txid: quote.txid,
+        pset_breakdown: quote.pset_breakdown.clone(),
+        route: None,
+    })
+}
+
+fn has_market(data: &Data, asset_1: AssetId, asset_2: AssetId) -> bool {
+    data.markets.iter().any(|market| {
+        market.asset_pair.base == asset_1 && market.asset_pair.quote == asset_2
+            || market.asset_pair.base == asset_2 && market.asset_pair.quote == asset_1
+    })
+}
+
+async fn get_quote(
+    data: &mut Data,
+    client_id: ClientId,
+    req: api::GetQuoteReq,
+) -> Result<api::GetQuoteResp, Error> {
+    wallet(data, &req.wallet)?;
+    if req.allow_routing {
+        let send_asset = try_get_asset(&data.ticker_loader, req.send_asset)?;
+        let recv_asset = try_get_asset(&data.ticker_loader, req.recv_asset)?;
+        if !has_market(data, send_asset.asset_id, recv_asset.asset_id) {
+            return get_routed_quote(data, client_id, req).await;
+        }
+    }
+    get_pair_quote(data, client_id, req, true).await
+}
+
+/// The quoted leg is forgotten if the other leg fails
+fn forget_leg_on_error<T>(
+    data: &mut Data,
+    quote_id: QuoteId,
+    res: Result<T, Error>,
+) -> Result<T, Error> {
+    if res.is_err() {
+        data.quotes.remove(&quote_id);
+    }
+    res
+}
+
+/// The second leg can't spend the first leg output (it's signed before the first leg is confirmed),
+/// so the wallet must already have the intermediate amount
+fn check_intermediate_balance(
+    data: &Data,
+    wallet_name: &str,
+    allow_unconfirmed: bool,
+    intermediate: &Asset,
+    amount: f64,
+) -> Result<(), Error> {
+    let required = try_convert_asset_amount(amount, intermediate.precision)?;
+    let available = selected_utxos(data, wallet_name, None, allow_unconfirmed)?
+        .iter()
+        .filter(|utxo| utxo.asset == intermediate.asset_id)
+        .map(|utxo| utxo.value)
+        .sum::<u64>();
+    verify!(
+        available >= required,
+        Error::RouteLowBalance {
+            asset: intermediate.ticker,
+            required: amount,
+            available: asset_float_amount_(available, intermediate.precision),
+        }
+    );
+    Ok(())
+}
+
+/// Quotes `send_asset` -> L-BTC and L-BTC -> `recv_asset` (in the reverse order if quoting by `recv_amount`).
+/// The first leg is received to the quote change address, the second leg is paid with the wallet L-BTC UTXOs.
+async fn get_routed_quote(
+    data: &mut Data,
+    client_id: ClientId,
+    req: api::GetQuoteReq,
+) -> Result<api::GetQuoteResp, Error> {
+    let api::GetQuoteReq {
+        send_asset,
+        recv_asset,
+        send_amount,
+        recv_amount,
+        receive_address,
+        gaid,
+        instant_swap,
+        allow_partial,
+        verify_pset,
+        utxos,
+        allow_unconfirmed,
+        timeout_secs,
+        allow_routing: _,
+        wallet: wallet_name,
+    } = req;
+
+    let intermediate = try_get_asset(&data.ticker_loader, DealerTicker::LBTC)?;
+    let send_asset_id = try_get_asset(&data.ticker_loader, send_asset)?.asset_id;
+    let recv_asset_id = try_get_asset(&data.ticker_loader, recv_asset)?.asset_id;
+    verify!(
+        send_asset_id != intermediate.asset_id
+            && recv_asset_id != intermediate.asset_id
+            && has_market(data, send_asset_id, intermediate.asset_id)
+            && has_market(data, intermediate.asset_id, recv_asset_id),
+        Error::NoMarket
+    );
+
+    let leg_address = quote_change_address(data, &wallet_name).await?;
+
+    let leg = |send_asset, recv_asset, send_amount, recv_amount| api::GetQuoteReq {
+        send_asset,
+        recv_asset,
+        send_amount,
+        recv_amount,
+        receive_address: None,
+        gaid: None,
+        instant_swap,
+        allow_partial: false,
+        verify_pset,
+        utxos: None,
+        allow_unconfirmed,
+        timeout_secs,
+        allow_routing: false,
+        wallet: wallet_name.clone(),
+    };
+    let first_leg =
+        |send_amount, recv_amount| leg(send_asset, intermediate.ticker, send_amount, recv_amount);
+    let second_leg =
+        |send_amount, recv_amount| leg(intermediate.ticker, recv_asset, send_amount, recv_amount);
+
+    let (first, second) = match (send_amount, recv_amount) {
+        (Some(send_amount), None) => {
+            let first_req = api::GetQuoteReq {
+                receive_address: Some(leg_address),
+                allow_partial,
+                utxos,
+                ..first_leg(Some(send_amount), None)
+            };
+            let first = get_pair_quote(data, client_id, first_req, false).await?;
+            let res = check_intermediate_balance(
+                data,
+                &wallet_name,
+                allow_unconfirmed,
+                &intermediate,
+                first.recv_amount,
+            );
+            forget_leg_on_error(data, first.quote_id, res)?;
+            let second_req = api::GetQuoteReq {
+                receive_address,
+                gaid,
+                ..second_leg(Some(first.recv_amount), None)
+            };
+            let res = get_pair_quote(data, client_id, second_req, false).await;
+            let second = forget_leg_on_error(data, first.quote_id, res)?;
+            (first, second)
+        }
+        (None, Some(recv_amount)) => {
+            let second_req = api::GetQuoteReq {
+                receive_address,
+                gaid,
+                ..second_leg(None, Some(recv_amount))
+            };
+            let second = get_pair_quote(data, client_id, second_req, false).await?;
+            let res = check_intermediate_balance(
+                data,
+                &wallet_name,
+                allow_unconfirmed,
+                &intermediate,
+                second.send_amount,
+            );
+            forget_leg_on_error(data, second.quote_id, res)?;
+            let first_req = api::GetQuoteReq {
+                receive_address: Some(leg_address),
+                utxos,
+                ..first_leg(None, Some(second.send_amount))
+            };
+            let res = get_pair_quote(data, client_id, first_req, false).await;
+            let first = forget_leg_on_error(data, second.quote_id, res)?;
+            (first, second)
+        }
+        (Some(_), Some(_)) | (None, None) => abort!(Error::InvalidQuoteAmount),
+    };
+
+    let first_quote = data.quotes.get_mut(&first.quote_id).ok_or(Error::NoQuote)?;
+    first_quote.second_leg = Some(SecondLeg {
+        quote_id: second.quote_id,
+        intermediate_asset: intermediate.ticker,
+        intermediate_amount: first.recv_amount,
+    });
+
+    tracing::debug!(
+        first_quote_id = ?first.quote_id,
+        second_quote_id = ?second.quote_id,
+        intermediate_amount = first.recv_amount,
+        "routed quote received"
+    );
+
+    Ok(api::GetQuoteResp {
+        quote_id: first.quote_id,
+        send_amount: first.send_amount,
+        recv_amount: second.recv_amount,
+        receive_address: second.receive_address,
+        ttl: first.ttl.min(second.ttl),
+        txid: first.txid,
+        pset_breakdown: first.pset_breakdown,
+        route: Some(api::QuoteRoute {
+            intermediate_asset: intermediate.ticker,
+            intermediate_amount: first.recv_amount,
+            first_quote_id: first.quote_id,
+            second_quote_id: second.quote_id,
+            second_txid: second.txid,
+            second_pset_breakdown: second.pset_breakdown,
+        }),
+    })
+}
+
+/// `coalesce` is false for the routed quote legs
+async fn get_pair_quote(
+    data: &mut Data,
+    client_id: ClientId,
+    req: api::GetQuoteReq,
+    coalesce: bool,
+) -> Result<api::GetQuoteResp, Error> {
+    let send_asset = try_get_asset(&data.ticker_loader, req.send_asset)?;
+    let recv_asset = try_get_asset(&data.ticker_loader, req.recv_asset)?;
+
+    let receive_address = match (&req.receive_address, &req.gaid) {
+        (Some(receive_address), None) => receive_address.clone(),
+        (None, Some(gaid)) => resolve_gaid_address(data, recv_asset.asset_id, gaid.clone()).await?,
+        (Some(_), Some(_)) | (None, None) => abort!(Error::InvalidQuoteReceiver),
+    };
+
+    check_address_allowed(data, &receive_address)?;
+
+    let requested_amount = match (req.send_amount, req.recv_amount) {
+        (Some(send_amount), None) => {
+            QuoteAmount::Send(try_convert_asset_amount(send_amount, send_asset.precision)?)
+        }
+        (None, Some(recv_amount)) => {
+            QuoteAmount::Recv(try_convert_asset_amount(recv_amount, recv_asset.precision)?)
+        }
+        (Some(_), Some(_)) | (None, None) => abort!(Error::InvalidQuoteAmount),
+    };
+
+    let quote_deadline = match req.timeout_secs {
+        Some(timeout_secs) => {
+            let deadline = Duration::from_secs(timeout_secs);
+            verify!(
+                timeout_secs > 0 && deadline <= MAX_QUOTE_DEADLINE,
+                Error::InvalidQuoteTimeout(timeout_secs)
+            );
+            deadline
+        }
+        None => QUOTE_DEADLINE,
+    };
+
+    // Quotes with the UTXO list or confirmed UTXOs only are not coalesced, the key does not include them
+    let coalescing_key = data
+        .quote_coalescing
+        .as_ref()
+        .filter(|_| coalesce && req.utxos.is_none() && req.allow_unconfirmed)
+        .map(|coalescing| {
+            coalescing.key(quote_coalescing::Key {
+                client_id: Some(client_id),
+                send_asset: send_asset.asset_id,
+                recv_asset: recv_asset.asset_id,
+                amount: requested_amount,
+                receive_address: receive_address.clone(),
+                instant_swap: req.instant_swap,
+                wallet: req.wallet.clone(),
+            })
+        });
+    if let Some(resp) = coalescing_key
+        .as_ref()
+        .and_then(|key| coalesced_quote(data, key))
+    {
+        return Ok(resp);
+    }
+
+    let created_by = client_name(data, client_id);
+    check_quota(data, &created_by, api::QuotaResource::Quotes).await?;
+
+    tracing::debug!(
+        send_asset = %send_asset.asset_id,
+        recv_asset = %recv_asset.asset_id,
+        "try to find market"
+    );
+
+    let market = data
+        .markets
+        .iter()
+        .find(|market| {
+            market.asset_pair.base == send_asset.asset_id
+                && market.asset_pair.quote == recv_asset.asset_id
+                || market.asset_pair.base == recv_asset.asset_id
+                    && market.asset_pair.quote == send_asset.asset_id
+        })
+        .ok_or(Error::NoMarket)?;
+
+    let fee_asset = market.fee_asset;
+    let asset_pair = market.asset_pair;
+
+    let asset_type = if asset_pair.base == send_asset.asset_id {
+        AssetType::Base
+    } else {
+        AssetType::Quote
+    };
+
+    let base_trade_dir = match asset_type {
+        AssetType::Base => TradeDir::Sell,
+        AssetType::Quote => TradeDir::Buy,
+    };
+
+    let change_address = quote_change_address(data, &req.wallet).await?;
+
+    let utxos = selected_utxos(
+        data,
+        &req.wallet,
+        req.utxos.as_deref(),
+        req.allow_unconfirmed,
+    )?
+    .into_iter()
+    .filter(|utxo| utxo.asset == send_asset.asset_id)
+    .collect::<Vec<_>>();
+
+    let total = utxos.iter().map(|utxo| utxo.value).sum::<u64>();
+
+    // The sent amount is known only after the quote if quoting by the received amount
+    if let QuoteAmount::Send(send_amount) = requested_amount {
+        verify!(
+            total >= send_amount,
+            Error::NotEnoughAmount {
+                asset_id: send_asset.asset_id,
+                required: send_amount,
+                available: total,
+            }
+        );
+    }
+
+    // The amount is denominated in the asset (and the direction) it's requested for
+    let (amount_asset_type, amount, amount_trade_dir) = match requested_amount {
+        QuoteAmount::Send(send_amount) => (asset_type, send_amount, TradeDir::Sell),
+        QuoteAmount::Recv(recv_amount) => (asset_type.inv(), recv_amount, TradeDir::Buy),
+    };
+
+    let start_quote_resp = make_market_request!(
+        data.ws,
+        StartQuotes,
+        mkt::StartQuotesRequest {
+            asset_pair,
+            asset_type: amount_asset_type,
+            amount,
+            trade_dir: amount_trade_dir,
+            utxos: utxos.clone(),
+            receive_address: receive_address.clone(),
+            change_address: change_address.clone(),
+            order_id: None,
+            private_id: None,
+            instant_swap: req.instant_swap,
+        }
+    )?;
+
+    let quote_sub_id = start_quote_resp.quote_sub_id;
+    let ws_generation = data.ws_generation;
+    data.quote_subs.insert(quote_sub_id, ws_generation);
+    data.last_quote_sub = Some(quote_sub_id);
+
+    let deadline = tokio::time::Instant::now() + quote_deadline;
+
+    let status = loop {
+        let res = tokio::time::timeout_at(deadline, data.ws.recv()).await;
+
+        match res {
+            Ok(resp) => {
+                let status = match &resp {
+                    // The quote subscription does not survive reconnects
+                    WrappedResponse::Connected | WrappedResponse::Disconnected => {
+                        Some(QuoteStatus::Disconnected)
+                    }
+                    WrappedResponse::Response(ResponseMessage::Response(_, _)) => None,
+                    WrappedResponse::Response(ResponseMessage::Notification(
+                        sideswap_api::Notification::Market(mkt::Notification::Quote(quote)),
+                    )) if quote.quote_sub_id == quote_sub_id
+                        && data.ws_generation == ws_generation =>
+                    {
+                        Some(QuoteStatus::Quote(quote.clone()))
+                    }
+                    WrappedResponse::Response(ResponseMessage::Notification(_)) => None,
+                };
+
+                // The awaited quote is not discarded
+                if !matches!(status, Some(QuoteStatus::Quote(_))) {
+                    process_ws_event(data, resp).await;
+                }
+
+                if let Some(status) = status {
+                    break status;
+                }
+
+                continue;
+            }
+
+            Err(err) => break QuoteStatus::Timeout(err),
+        };
+    };
+
+    let quote = match status {
+        QuoteStatus::Disconnected => {
+            stop_quote_sub(data, quote_sub_id, ws_generation);
+            abort!(Error::WsError(ws_req_sender::Error::Disconnected))
+        }
+        QuoteStatus::Timeout(err) => {
+            tracing::warn!(
+                quote_sub_id = quote_sub_id.value(),
+                "no quote received in {}s, stop quotes",
+                quote_deadline.as_secs()
+            );
+            data.quote_timeouts += 1;
+            stop_quote_sub(data, quote_sub_id, ws_generation);
+            abort!(Error::WsError(ws_req_sender::Error::Timeout(err)))
+        }
+        QuoteStatus::Quote(quote) => quote,
+    };
+
+    match quote.status {
+        mkt::QuoteStatus::Success {
+            quote_id,
+            base_amount,
+            quote_amount,
+            server_fee,
+            fixed_fee,
+            ttl,
+        } => {
+            let (base_asset, quote_asset) = match asset_type {
+                AssetType::Base => (&send_asset, &recv_asset),
+                AssetType::Quote => (&recv_asset, &send_asset),
+            };
+            let numbers = QuoteNumbers {
+                base_amount,
+                quote_amount,
+                server_fee,
+                fixed_fee,
+                trade_dir: base_trade_dir,
+                fee_asset,
+                base_precision: base_asset.precision,
+                quote_precision: quote_asset.precision,
+            };
+            let asset_pair = (base_asset.ticker, quote_asset.ticker);
+            let amounts = quote_amounts::quote_amounts(&numbers);
+
+            match requested_amount {
+                QuoteAmount::Send(send_amount) => verify!(
+                    amounts.send_amount == send_amount,
+                    Error::NotEnoughAmount {
+                        asset_id: send_asset.asset_id,
+                        required: send_amount,
+                        available: amounts.send_amount,
+                    }
+                ),
+                QuoteAmount::Recv(recv_amount) => verify!(
+                    amounts.recv_amount == recv_amount,
+                    Error::NotEnoughAmount {
+                        asset_id: recv_asset.asset_id,
+                        required: recv_amount,
+                        available: amounts.recv_amount,
+                    }
+                ),
+            }
+            verify!(
+                amounts.send_amount <= total,
+                Error::NotEnoughAmount {
+                    asset_id: send_asset.asset_id,
+                    required: amounts.send_amount,
+                    available: total,
+                }
+            );
+
+            let quote_send_amount = amounts.send_amount_float;
+            let quote_recv_amount = amounts.recv_amount_float;
+
+            let quote_resp =
+                make_market_request!(data.ws, GetQuote, mkt::GetQuoteRequest { quote_id })?;
+
+            let pset = pset_check::decode(&quote_resp.pset)?;
+            let utxo_data = wallet(data, &req.wallet)?
+                .utxo_data
+                .as_ref()
+                .ok_or(Error::NoUtxos)?;
+            pset_check::verify_signable(
+                &pset,
+                utxo_data,
+                &utxos,
+                data.settings.env.d().network.d().elements_params,
+                &receive_address.script_pubkey(),
+            )?;
+
+            let pset_breakdown = if req.verify_pset {
+                let breakdown = pset_check::verify(
+                    &pset,
+                    &pset_check::Expected {
+                        utxos: &utxos,
+                        utxo_data,
+                        send_asset: send_asset.asset_id,
+                        send_amount: amounts.send_amount,
+                        recv_asset: recv_asset.asset_id,
+                        recv_amount: amounts.recv_amount,
+                        receive_script: receive_address.script_pubkey(),
+                        change_script: change_address.script_pubkey(),
+                    },
+                )?;
+                Some(api::PsetBreakdown {
+                    total_in: asset_float_amount_(breakdown.total_in, send_asset.precision),
+                    receive_out: asset_float_amount_(breakdown.receive_out, recv_asset.precision),
+                    change_out: asset_float_amount_(breakdown.change_out, send_asset.precision),
+                    network_fee: asset_float_amount_(
+                        breakdown.network_fee,
+                        AssetPrecision::BITCOIN_PRECISION,
+                    ),
+                })
+            } else {
+                None
+            };
+
+            let txid = pset.extract_tx()?.txid();
+
+            let expires_at = Instant::now() + data.clock_skew.quote_ttl(quote_resp.ttl.duration());
+
+            let description = format!(
+                "swap {} {} for {} {} to {}",
+                quote_send_amount,
+                send_asset.ticker,
+                quote_recv_amount,
+                recv_asset.ticker,
+                receive_address
+            );
+
+            data.quotes.insert(
+                quote_id,
+                Quote {
+                    quote_sub_id,
+                    txid,
+                    send_amount: quote_send_amount,
+                    recv_amount: quote_recv_amount,
+                    asset_pair,
+                    numbers,
+                    pset,
+                    expires_at,
+                    description,
+                    created_by,
+                    receive_address: receive_address.clone(),
+                    pset_breakdown: pset_breakdown.clone(),
+                    ws_generation,
+                    second_leg: None,
+                    wallet: req.wallet.clone(),
+                    utxos: utxos.clone(),
+                },
+            );
+
+            tracing::debug!(
+                quote_id = ?quote_id,
+                txid = %txid,
+                recv_amount = quote_recv_amount,
+                "quote received"
+            );
+
+            if let (Some(coalescing), Some(key)) = (data.quote_coalescing.as_mut(), coalescing_key)
+            {
+                coalescing.insert(key, quote_id, Instant::now());
+            }
+
+            Ok(api::GetQuoteResp {
+                quote_id,
+                send_amount: quote_send_amount,
+                recv_amount: quote_recv_amount,
+                receive_address,
+                ttl: data.clock_skew.quote_ttl(ttl.duration()).into(),
+                txid,
+                pset_breakdown,
+                route: None,
+            })
+        }
+
+        mkt::QuoteStatus::LowBalance {
+            base_amount,
+            quote_amount,
+            server_fee,
+            fixed_fee,
+            available,
+        } => {
+            let (base_asset, quote_asset) = match asset_type {
+                AssetType::Base => (&send_asset, &recv_asset),
+                AssetType::Quote => (&recv_asset, &send_asset),
+            };
+            let quoted = quote_amounts::quote_amounts(&QuoteNumbers {
+                base_amount,
+                quote_amount,
+                server_fee,
+                fixed_fee,
+                trade_dir: base_trade_dir,
+                fee_asset,
+                base_precision: base_asset.precision,
+                quote_precision: quote_asset.precision,
+            })
+            .send_amount;
+            let send_amount = match requested_amount {
+                QuoteAmount::Send(send_amount) => send_amount,
+                QuoteAmount::Recv(_) => quoted,
+            };
+            let required = quoted.max(send_amount);
+
+            tracing::info!(
+                send_asset = %send_asset.asset_id,
+                required,
+                available,
+                allow_partial = req.allow_partial,
+                "quote balance is too low"
+            );
+
+            if req.allow_partial && available > 0 && available < send_amount {
+                // Quote the available amount once (by the sent amount), another LowBalance fails the request
+                let partial_req = api::GetQuoteReq {
+                    send_amount: Some(asset_float_amount_(available, send_asset.precision)),
+                    recv_amount: None,
+                    // Not resolved again
+                    receive_address: Some(receive_address),
+                    gaid: None,
+                    allow_partial: false,
+                    ..req
+                };
+                return Box::pin(get_pair_quote(data, client_id, partial_req, coalesce)).await;
+            }
+
+            abort!(Error::QuoteLowBalance {
+                asset: send_asset.ticker,
+                required: asset_float_amount_(required, send_asset.precision),
+                available: asset_float_amount_(available, send_asset.precision),
+            })
+        }
+
+        mkt::QuoteStatus::Error { error_msg } => abort!(Error::QuoteError(error_msg)),
+    }
+}
+
+/// Stops the quote subscription without a quote, otherwise the server keeps sending quote notifications.
+/// The StopQuotes response is not awaited (the request has already failed).
+/// Nothing is sent after a reconnect, the quoting session does not survive it.
+fn stop_quote_sub(data: &mut Data, quote_sub_id: QuoteSubId, ws_generation: u64) {
+    data.quote_subs.remove(&quote_sub_id);
+    if data.last_quote_sub == Some(quote_sub_id) {
+        data.last_quote_sub = None;
+        if data.ws_generation == ws_generation {
+            data.ws
+                .send_request(sideswap_api::Request::Market(mkt::Request::StopQuotes(
+                    mkt::StopQuotesRequest {},
+                )));
+        }
+    }
+}
+
+async fn cancel_quote(
+    data: &mut Data,
+    api::CancelQuoteReq { quote_id }: api::CancelQuoteReq,
+) -> Result<api::CancelQuoteResp, Error> {
+    let quote = data.quotes.remove(&quote_id).ok_or(Error::NoQuote)?;
+
+    let active = data.last_quote_sub == Some(quote.quote_sub_id)
+        && data.quote_subs.get(&quote.quote_sub_id) == Some(&data.ws_generation);
+    tracing::debug!(quote_id = ?quote_id, txid = %quote.txid, active, "cancel quote");
+
+    if active {
+        data.last_quote_sub = None;
+        let res = make_market_request!(data.ws, StopQuotes, mkt::StopQuotesRequest {});
+        match res {
+            Ok(mkt::StopQuotesResponse {}) => {}
+            // The quoting session does not survive reconnects
+            Err(ws_req_sender::Error::Disconnected) => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    if let Some(second_leg) = quote.second_leg {
+        if data.quotes.contains_key(&second_leg.quote_id) {
+            let req = api::CancelQuoteReq {
+                quote_id: second_leg.quote_id,
+            };
+            return Box::pin(cancel_quote(data, req)).await;
+        }
+    }
+
+    Ok(api::CancelQuoteResp {})
+}
+
+fn explain_quote(
+    data: &Data,
+    api::ExplainQuoteReq { quote_id, numbers }: api::ExplainQuoteReq,
+) -> Result<api::ExplainQuoteResp, Error> {
+    let ((base, quote), numbers) = match (quote_id, numbers) {
+        (Some(quote_id), None) => {
+            let quote = data.quotes.get(&quote_id).ok_or(Error::NoQuote)?;
+            (quote.asset_pair, quote.numbers)
+        }
+
+        (None, Some(numbers)) => {
+            let base = try_get_asset(&data.ticker_loader, numbers.base)?;
+            let quote = try_get_asset(&data.ticker_loader, numbers.quote)?;
+            let quote_numbers = QuoteNumbers {
+                base_amount: numbers.base_amount,
+                quote_amount: numbers.quote_amount,
+                server_fee: numbers.server_fee,
+                fixed_fee: numbers.fixed_fee,
+                trade_dir: match numbers.trade_dir {
+                    api::TradeDir::Sell => TradeDir::Sell,
+                    api::TradeDir::Buy => TradeDir::Buy,
+                },
+                fee_asset: match numbers.fee_asset {
+                    api::AssetType::Base => AssetType::Base,
+                    api::AssetType::Quote => AssetType::Quote,
+                },
+                base_precision: base.precision,
+                quote_precision: quote.precision,
+            };
+            ((base.ticker, quote.ticker), quote_numbers)
+        }
+
+        (Some(_), Some(_)) | (None, None) => abort!(Error::InvalidExplainRequest(
+            "exactly one of quote_id and numbers must be set"
+        )),
+    };
+
+    let amounts = quote_amounts::quote_amounts(&numbers);
+
+    let ticker = |asset_type| match asset_type {
+        AssetType::Base => base,
+        AssetType::Quote => quote,
+    };
+
+    Ok(api::ExplainQuoteResp {
+        numbers: api::QuoteNumbers {
+            base,
+            quote,
+            base_amount: numbers.base_amount,
+            quote_amount: numbers.quote_amount,
+            server_fee: numbers.server_fee,
+            fixed_fee: numbers.fixed_fee,
+            trade_dir: match numbers.trade_dir {
+                TradeDir::Sell => api::TradeDir::Sell,
+                TradeDir::Buy => api::TradeDir::Buy,
+            },
+            fee_asset: match numbers.fee_asset {
+                AssetType::Base => api::AssetType::Base,
+                AssetType::Quote => api::AssetType::Quote,
+            },
+        },
+        send_asset: ticker(amounts.send_asset),
+        send_amount: amounts.send_amount_float,
+        send_amount_raw: amounts.send_amount,
+        recv_asset: ticker(amounts.recv_asset),
+        recv_amount: amounts.recv_amount_float,
+        recv_amount_raw: amounts.recv_amount,
+        fee_asset: ticker(numbers.fee_asset),
+        fee_amount: amounts.total_fee_float,
+        fee_amount_raw: amounts.total_fee,
+        breakdown: amounts.breakdown,
+    })
+}
+
+fn price_asset_pair(
+    data: &Data,
+    base: DealerTicker,
+    quote: DealerTicker,
+) -> Result<mkt::AssetPair, Error> {
+    Ok(mkt::AssetPair {
+        base: try_get_asset(&data.ticker_loader, base)?.asset_id,
+        quote: try_get_asset(&data.ticker_loader, quote)?.asset_id,
+    })
+}
+
+fn subscribe_price(
+    data: &mut Data,
+    client_id: ClientId,
+    api::SubscribePriceReq { base, quote }: api::SubscribePriceReq,
+) -> Result<api::SubscribePriceResp, Error> {
+    let asset_pair = price_asset_pair(data, base, quote)?;
+    verify!(
+        data.markets
+            .iter()
+            .any(|market| market.asset_pair == asset_pair),
+        Error::NoMarket
+    );
+
+    let clients = data.price_subs.entry(asset_pair).or_default();
+    if clients.is_empty() {
+        tracing::debug!(%base, %quote, "subscribe market price");
+        data.ws
+            .send_request(sideswap_api::Request::Market(mkt::Request::Subscribe(
+                mkt::SubscribeRequest { asset_pair },
+            )));
+    }
+    clients.insert(client_id);
+
+    let last_price = data
+        .market_prices
+        .get(&asset_pair)
+        .and_then(|price| convert_market_price(data, &asset_pair, price));
+    if let (Some(notif), Some(client)) = (last_price, data.clients.get(&client_id)) {
+        client
+            .notif_sender
+            .send(EncodedNotif::new(api::Notif::MarketPrice(notif)));
+    }
+
+    Ok(api::SubscribePriceResp {})
+}
+
+fn unsubscribe_price(
+    data: &mut Data,
+    client_id: ClientId,
+    api::UnsubscribePriceReq { base, quote }: api::UnsubscribePriceReq,
+) -> Result<api::UnsubscribePriceResp, Error> {
+    let asset_pair = price_asset_pair(data, base, quote)?;
+    remove_price_sub(data, client_id, asset_pair);
+    Ok(api::UnsubscribePriceResp {})
+}
+
+/// Unsubscribes upstream once the last client is removed
+fn remove_price_sub(data: &mut Data, client_id: ClientId, asset_pair: mkt::AssetPair) {
+    let Some(clients) = data.price_subs.get_mut(&asset_pair) else {
+        return;
+    };
+    clients.remove(&client_id);
+    if clients.is_empty() {
+        tracing::debug!(?asset_pair, "unsubscribe market price");
+        data.price_subs.remove(&asset_pair);
+        data.market_prices.remove(&asset_pair);
+        data.ws
+            .send_request(sideswap_api::Request::Market(mkt::Request::Unsubscribe(
+                mkt::UnsubscribeRequest { asset_pair },
+            )));
+    }
+}
+
+/// Fails with `MakerOrdersDisabled` unless `maker_orders` is set in the config
+fn check_maker_orders(data: &Data) -> Result<(), Error> {
+    verify!(data.settings.maker_orders, Error::MakerOrdersDisabled);
+    Ok(())
+}
+
+fn convert_own_order(ticker_loader: &TickerLoader, order: &mkt::OwnOrder) -> Option<api::OwnOrder> {
+    let base = ticker_loader.ticker(&order.asset_pair.base)?;
+    let quote = ticker_loader.ticker(&order.asset_pair.quote)?;
+    let base_precision = ticker_loader.precision(base);
+    Some(api::OwnOrder {
+        order_id: order.order_id,
+        base,
+        quote,
+        trade_dir: match order.trade_dir {
+            TradeDir::Sell => api::TradeDir::Sell,
+            TradeDir::Buy => api::TradeDir::Buy,
+        },
+        price: order.price.value(),
+        orig_amount: asset_float_amount_(order.orig_amount, base_precision),
+        active_amount: asset_float_amount_(order.active_amount, base_precision),
+        created_at: order.created_at,
+        ttl: order.ttl,
+        private_id: order.private_id.as_deref().cloned(),
+        client_order_id: order.client_order_id.as_deref().cloned(),
+        online: order.online,
+    })
+}
+
+fn order_amount(amount: f64, precision: AssetPrecision) -> Result<u64, Error> {
+    let amount = try_convert_asset_amount(amount, precision)?;
+    verify!(
+        amount > 0,
+        Error::InvalidOrderRequest("amount must be positive")
+    );
+    Ok(amount)
+}
+
+fn order_price(price: f64) -> Result<NormalFloat, Error> {
+    NormalFloat::new(price)
+        .ok()
+        .filter(|price| price.value() > 0.0)
+        .ok_or(Error::InvalidOrderRequest("price must be positive"))
+}
+
+/// Matched maker swaps are signed without an approval,
+/// so an order must not send more than the `approvals` threshold of its asset
+fn check_order_threshold(
+    data: &Data,
+    send_asset: DealerTicker,
+    send_amount: f64,
+) -> Result<(), Error> {
+    let Some(config) = &data.settings.approvals else {
+        return Ok(());
+    };
+    if config.approval_required(&BTreeMap::from([(send_asset, send_amount)])) {
+        return Err(Error::OrderAboveApprovalThreshold {
+            asset: send_asset,
+            threshold: config.thresholds[&send_asset],
+        });
+    }
+    Ok(())
+}
+
+/// The asset and the amount sent by the wallet if the order is fully matched
+fn order_send_amount(
+    trade_dir: TradeDir,
+    (base, quote): (DealerTicker, DealerTicker),
+    base_amount: f64,
+    price: f64,
+) -> (DealerTicker, f64) {
+    match trade_dir {
+        TradeDir::Sell => (base, base_amount),
+        TradeDir::Buy => (quote, base_amount * price),
+    }
+}
+
+/// Returns the converted order, the server order is kept in `own_orders`
+fn insert_own_order(data: &mut Data, order: mkt::OwnOrder) -> Result<api::OwnOrder, Error> {
+    let converted = convert_own_order(&data.ticker_loader, &order);
+    let order_id = order.order_id;
+    data.own_orders.insert(order_id, order);
+    converted.ok_or(Error::UnknownOrder(order_id))
+}
+
+async fn remove_own_order(data: &mut Data, order_id: mkt::OrdId) {
+    data.own_orders.remove(&order_id);
+    if data.maker_orders.remove(&order_id).is_some() {
+        data.db.delete_maker_order(order_id.value() as i64).await;
+    }
+}
+
+async fn submit_order(
+    data: &mut Data,
+    client_id: ClientId,
+    req: api::SubmitOrderReq,
+) -> Result<api::SubmitOrderResp, Error> {
+    check_maker_orders(data)?;
+    wallet(data, &req.wallet)?;
+    verify!(
+        req.wallet == DEFAULT_WALLET,
+        Error::InvalidOrderRequest("maker orders can only be placed from the default wallet")
+    );
+
+    let base = try_get_asset(&data.ticker_loader, req.base)?;
+    let quote = try_get_asset(&data.ticker_loader, req.quote)?;
+    let asset_pair = data
+        .markets
+        .iter()
+        .map(|market| market.asset_pair)
+        .find(|asset_pair| asset_pair.base == base.asset_id && asset_pair.quote == quote.asset_id)
+        .ok_or(Error::NoMarket)?;
+
+    let base_amount = order_amount(req.amount, base.precision)?;
+    let price = order_price(req.price)?;
+    verify!(
+        req.ttl_seconds != Some(0),
+        Error::InvalidOrderRequest("ttl_seconds must be positive")
+    );
+    let trade_dir = match req.trade_dir {
+        api::TradeDir::Sell => TradeDir::Sell,
+        api::TradeDir::Buy => TradeDir::Buy,
+    };
+    let (send_asset, send_amount) = order_send_amount(
+        trade_dir,
+        (base.ticker, quote.ticker),
+        req.amount,
+        req.price,
+    );
+    check_order_threshold(data, send_asset, send_amount)?;
+
+    // The swap pays both the received asset and the change there
+    let address = quote_change_address(data, DEFAULT_WALLET).await?;
+
+    let resp = make_market_request!(
+        data.ws,
+        AddOrder,
+        mkt::AddOrderRequest {
+            asset_pair,
+            base_amount,
+            price: Some(price),
+            price_tracking: None,
+            min_price: None,
+            max_price: None,
+            trade_dir,
+            ttl: req
+                .ttl_seconds
+                .map(|ttl_seconds| Duration::from_secs(ttl_seconds).into()),
+            receive_address: address.clone(),
+            change_address: address.clone(),
+            private: req.private,
+            client_order_id: req.client_order_id.map(Box::new),
+            signature: None,
+        }
+    )?;
+
+    let order_id = resp.order.order_id;
+    let maker_order = models::MakerOrder {
+        order_id: order_id.value() as i64,
+        receive_address: Text(address.clone()),
+        change_address: Text(address),
+        created_by: client_name(data, client_id),
+        created_at: TimestampMs::now().millis() as i64,
+    };
+    data.db.add_maker_order(&maker_order).await;
+    data.maker_orders.insert(order_id, maker_order);
+
+    tracing::info!(order_id = order_id.value(), "order submitted");
+
+    let order = insert_own_order(data, resp.order)?;
+    Ok(api::SubmitOrderResp { order })
+}
+
+async fn edit_order(
+    data: &mut Data,
+    api::EditOrderReq {
+        order_id,
+        amount,
+        price,
+    }: api::EditOrderReq,
+) -> Result<api::EditOrderResp, Error> {
+    check_maker_orders(data)?;
+
+    let order = data
+        .own_orders
+        .get(&order_id)
+        .ok_or(Error::UnknownOrder(order_id))?;
+    verify!(
+        amount.is_some() || price.is_some(),
+        Error::InvalidOrderRequest("amount or price must be set")
+    );
+    let base = data
+        .ticker_loader
+        .ticker(&order.asset_pair.base)
+        .ok_or(Error::UnknownOrder(order_id))?;
+    let quote = data
+        .ticker_loader
+        .ticker(&order.asset_pair.quote)
+        .ok_or(Error::UnknownOrder(order_id))?;
+    let base_precision = data.ticker_loader.precision(base);
+
+    let base_amount = amount
+        .map(|amount| order_amount(amount, base_precision))
+        .transpose()?;
+    let price = price.map(order_price).transpose()?;
+
+    let (send_asset, send_amount) = order_send_amount(
+        order.trade_dir,
+        (base, quote),
+        base_amount
+            .map(|amount| asset_float_amount_(amount, base_precision))
+            .unwrap_or_else(|| asset_float_amount_(order.active_amount, base_precision)),
+        price.unwrap_or(order.price).value(),
+    );
+    check_order_threshold(data, send_asset, send_amount)?;
+
+    let resp = make_market_request!(
+        data.ws,
+        EditOrder,
+        mkt::EditOrderRequest {
+            order_id,
+            base_amount,
+            price,
+            price_tracking: None,
+            min_price: None,
+            max_price: None,
+            receive_address: None,
+            change_address: None,
+            signature: None,
+        }
+    )?;
+
+    tracing::info!(order_id = order_id.value(), "order edited");
+
+    let order = insert_own_order(data, resp.order)?;
+    Ok(api::EditOrderResp { order })
+}
+
+async fn cancel_order(
+    data: &mut Data,
+    api::CancelOrderReq { order_id }: api::CancelOrderReq,
+) -> Result<api::CancelOrderResp, Error> {
+    check_maker_orders(data)?;
+
+    verify!(
+        data.own_orders.contains_key(&order_id),
+        Error::UnknownOrder(order_id)
+    );
+
+    make_market_request!(data.ws, CancelOrder, mkt::CancelOrderRequest { order_id })?;
+
+    tracing::info!(order_id = order_id.value(), "order cancelled");
+
+    remove_own_order(data, order_id).await;
+
+    Ok(api::CancelOrderResp {})
+}
+
+fn list_own_orders(
+    data: &Data,
+    api::ListOwnOrdersReq {}: api::ListOwnOrdersReq,
+) -> Result<api::ListOwnOrdersResp, Error> {
+    check_maker_orders(data)?;
+
+    let orders = data
+        .own_orders
+        .values()
+        .filter_map(|order| convert_own_order(&data.ticker_loader, order))
+        .collect();
+
+    Ok(api::ListOwnOrdersResp { orders })
+}
+
+/// Logs in to the market with the stored token (a new one is registered if the server does not know it),
+/// then registers the wallet UTXOs, so the own orders can be matched
+async fn market_login(data: &mut Data) -> Result<(), Error> {
+    let login_req = |token| mkt::LoginRequest {
+        token,
+        is_mobile: false,
+        is_jade: false,
+        event_count: 0,
+    };
+
+    let resp = match data.db.get_setting::<String>(MARKET_TOKEN_KEY).await {
+        Some(token) => match make_market_request!(data.ws, Login, login_req(token)) {
+            Ok(resp) => Some(resp),
+            Err(ws_req_sender::Error::BackendError(
+                message,
+                sideswap_api::ErrorCode::UnknownToken,
+            )) => {
+                tracing::warn!(
+                    "market token is not known to the server, register again: {message}"
+                );
+                None
+            }
+            Err(err) => return Err(err.into()),
+        },
+        None => None,
+    };
+
+    let resp = match resp {
+        Some(resp) => resp,
+        None => {
+            let mkt::RegisterResponse { token } =
+                make_market_request!(data.ws, Register, mkt::RegisterRequest { wallet_key: None })?;
+            let resp = make_market_request!(data.ws, Login, login_req(token.clone()))?;
+            data.db.set_setting(MARKET_TOKEN_KEY, &token).await;
+            resp
+        }
+    };
+
+    data.own_orders = resp
+        .orders
+        .into_iter()
+        .map(|order| (order.order_id, order))
+        .collect();
+
+    // Removed while the manager was offline
+    let removed = data
+        .maker_orders
+        .keys()
+        .filter(|order_id| !data.own_orders.contains_key(order_id))
+        .copied()
+        .collect::<Vec<_>>();
+    for order_id in removed {
+        remove_own_order(data, order_id).await;
+    }
+
+    tracing::debug!(orders = data.own_orders.len(), "logged in to the market");
+
+    data.market_utxos = Some(resp.utxos.into_iter().collect());
+    sync_market_utxos(data).await
+}
+
+/// Registers the new default wallet UTXOs with the server and removes the spent ones (the own orders are funded from them).
+/// Does nothing if not logged in to the market.
+async fn sync_market_utxos(data: &mut Data) -> Result<(), Error> {
+    let utxo_data = &wallet(data, DEFAULT_WALLET)?.utxo_data;
+    let (Some(market_utxos), Some(utxo_data)) = (&data.market_utxos, utxo_data) else {
+        return Ok(());
+    };
+
+    let wallet_outpoints = utxo_data
+        .utxos()
+        .iter()
+        .map(UtxoExt::outpoint)
+        .collect::<BTreeSet<_>>();
+    let removed = market_utxos
+        .difference(&wallet_outpoints)
+        .copied()
+        .collect::<Vec<_>>();
+    let added = utxo_data
+        .utxos()
+        .iter()
+        .filter(|utxo| !market_utxos.contains(&utxo.outpoint()))
+        .cloned()
+        .collect::<Vec<_>>();
+
+    if !removed.is_empty() {
+        make_market_request!(
+            data.ws,
+            RemoveUtxos,
+            mkt::RemoveUtxosRequest {
+                utxos: removed.clone()
+            }
+        )?;
+        tracing::debug!(count = removed.len(), "market UTXOs removed");
+        if let Some(market_utxos) = data.market_utxos.as_mut() {
+            for outpoint in removed {
+                market_utxos.remove(&outpoint);
+            }
+        }
+    }
+
+    if !added.is_empty() {
+        let count = added.len();
+        let resp = make_market_request!(data.ws, AddUtxos, mkt::AddUtxosRequest { utxos: added })?;
+        tracing::debug!(count, "market UTXOs added");
+        if let Some(market_utxos) = data.market_utxos.as_mut() {
+            market_utxos.extend(resp.utxos);
+        }
+    }
+
+    Ok(())
+}
+
+struct MakerSwap {
+    pset: PartiallySignedTransaction,
+    txid: elements::Txid,
+    description: String,
+    created_by: Option<String>,
+}
+
+/// The matched orders must be known (see `maker::check_swap`),
+/// and the PSET must pay their amounts to the addresses they were submitted with
+fn sign_maker_swap(data: &Data, notif: &mkt::MakerSignNotif) -> Result<MakerSwap, Error> {
+    // Automatic signing is not an activity, it does not extend the signing lock timeout
+    verify!(
+        !data.signing_lock.as_ref().is_some_and(SigningLock::locked),
+        Error::Locked
+    );
+
+    let pset = pset_check::decode(&notif.pset)?;
+    let swap = maker::check_swap(&data.own_orders, &notif.orders, &data.ticker_loader)?;
+
+    let rows = notif
+        .orders
+        .iter()
+        .map(|swap| {
+            data.maker_orders
+                .get(&swap.order_id)
+                .ok_or_else(|| Error::PsetMismatch {
+                    reason: format!("order {} was not submitted by the manager", swap.order_id),
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let row = rows[0];
+    verify!(
+        rows.iter()
+            .all(|other| other.receive_address.0 == row.receive_address.0
+                && other.change_address.0 == row.change_address.0),
+        Error::PsetMismatch {
+            reason: "the matched orders have different addresses".to_owned()
+        }
+    );
+
+    let (send_asset, send_amount, recv_asset, recv_amount) = match swap.trade_dir {
+        TradeDir::Sell => (
+            swap.asset_pair.base,
+            swap.base_amount,
+            swap.asset_pair.quote,
+            swap.quote_amount,
+        ),
+        TradeDir::Buy => (
+            swap.asset_pair.quote,
+            swap.quote_amount,
+            swap.asset_pair.base,
+            swap.base_amount,
+        ),
+    };
+
+    // The maker orders are funded from the default wallet (see `SubmitOrderReq::wallet`)
+    let utxos = selected_utxos(data, DEFAULT_WALLET, None, true)?;
+    let utxo_data = wallet(data, DEFAULT_WALLET)?
+        .utxo_data
+        .as_ref()
+        .ok_or(Error::NoUtxos)?;
+    pset_check::verify(
+        &pset,
+        &pset_check::Expected {
+            utxos: &utxos,
+            utxo_data,
+            send_asset,
+            send_amount,
+            recv_asset,
+            recv_amount,
+            receive_script: row.receive_address.0.script_pubkey(),
+            change_script: row.change_address.0.script_pubkey(),
+        },
+    )?;
+
+    let txid = pset.extract_tx()?.txid();
+
+    let float_amount = |asset_id, amount| {
+        let ticker = data.ticker_loader.ticker(asset_id).expect("must be known");
+        let precision = data.ticker_loader.precision(ticker);
+        (asset_float_amount_(amount, precision), ticker)
+    };
+    let (send_amount, send_ticker) = float_amount(&send_asset, send_amount);
+    let (recv_amount, recv_ticker) = float_amount(&recv_asset, recv_amount);
+    // The orders are checked when submitted, but the approvals config can be reloaded since
+    check_order_threshold(data, send_ticker, send_amount)?;
+    let order_ids = notif
+        .orders
+        .iter()
+        .map(|swap| swap.order_id.to_string())
+        .collect::<Vec<_>>()
+        .join(", ");
+    let description = format!(
+        "maker swap {send_amount} {send_ticker} for {recv_amount} {recv_ticker}, orders: {order_ids}"
+    );
+
+    pset_check::verify_signable(
+        &pset,
+        utxo_data,
+        &utxos,
+        data.settings.env.d().network.d().elements_params,
+        &row.receive_address.0.script_pubkey(),
+    )?;
+    let pset = utxo_data.sign_pset(pset);
+
+    Ok(MakerSwap {
+        pset,
+        txid,
+        description,
+        created_by: row.created_by.clone(),
+    })
+}
+
+async fn process_maker_sign(data: &mut Data, notif: mkt::MakerSignNotif) {
+    let quote_id = notif.quote_id;
+    let swap = match sign_maker_swap(data, &notif) {
+        Ok(swap) => swap,
+        Err(err) => {
+            tracing::error!(
+                quote_id = quote_id.value(),
+                "maker swap is not signed: {err}"
+            );
+            return;
+        }
+    };
+
+    data.ws.callback_request(
+        sideswap_api::Request::Market(mkt::Request::MakerSign(mkt::MakerSignRequest {
+            quote_id,
+            pset: encode_pset(&swap.pset),
+        })),
+        Box::new(move |res| {
+            if let Err(err) = res {
+                tracing::error!(quote_id = quote_id.value(), "MakerSign failed: {err}");
+            }
+        }),
+    );
+
+    audit(data, format!("{}, txid: {}", swap.description, swap.txid)).await;
+
+    if !data.monitored_txs.contains_key(&swap.txid) {
+        new_monitored_tx(
+            &data.db,
+            &mut data.monitored_txs,
+            MonitoredTx {
+                txid: Text(swap.txid),
+                description: Some(swap.description),
+                user_note: None,
+                created_by: swap.created_by,
+                send_result: None,
+                wallet: None,
+                created_at: TimestampMs::now().millis() as i64,
+            },
+        )
+        .await;
+    }
+}
+
+/// The swap record stored once the quote is accepted (`created_at` is not set yet)
+fn swap_row(quote_id: QuoteId, quote: &Quote, created_by: Option<String>) -> models::Swap {
+    let numbers = &quote.numbers;
+    let amounts = quote_amounts::quote_amounts(numbers);
+    let ticker = |asset_type| {
+        Text(match asset_type {
+            AssetType::Base => quote.asset_pair.0,
+            AssetType::Quote => quote.asset_pair.1,
+        })
+    };
+    // Scaled once to avoid the rounding errors of the float amounts
+    let price = (numbers.quote_amount as f64 * asset_scale(numbers.base_precision))
+        / (numbers.base_amount as f64 * asset_scale(numbers.quote_precision));
+    models::Swap {
+        quote_id: quote_id.value() as i64,
+        txid: Text(quote.txid),
+        send_ticker: ticker(amounts.send_asset),
+        send_amount: amounts.send_amount_float,
+        recv_ticker: ticker(amounts.recv_asset),
+        recv_amount: amounts.recv_amount_float,
+        price,
+        fee_ticker: ticker(numbers.fee_asset),
+        fee_amount: amounts.total_fee_float,
+        server_fee: numbers.server_fee as i64,
+        fixed_fee: numbers.fixed_fee as i64,
+        created_by,
+        created_at: 0,
+    }
+}
+
+/// The amount sent by the wallet if the quote is accepted
+fn quote_send_amounts(quote: &Quote) -> BTreeMap<DealerTicker, f64> {
+    let amounts = quote_amounts::quote_amounts(&quote.numbers);
+    let ticker = match amounts.send_asset {
+        AssetType::Base => quote.asset_pair.0,
+        AssetType::Quote => quote.asset_pair.1,
     };
+    BTreeMap::from([(ticker, amounts.send_amount_float)])
+}
 
-    match quote.status {
-        mkt::QuoteStatus::Success {
+async fn accept_quote(
+    data: &mut Data,
+    client_id: ClientId,
+    mut req: api::AcceptQuoteReq,
+) -> Result<api::AcceptQuoteResp, Error> {
+    if let Some(key) = req.idempotency_key.take() {
+        let request = idempotency::Request::AcceptQuote {
+            quote_id: req.quote_id,
+        };
+        // Not scoped by the wallet, the quote selects it (and the quote is gone once accepted)
+        let id = idempotency::KeyId::new(client_name(data, client_id), "", key, &request);
+        if let Some(resp) = reserve_idempotency_key(data, &id, request).await? {
+            return Ok(resp);
+        }
+        let res = Box::pin(accept_quote(data, client_id, req)).await;
+        finish_idempotency_key(data, &id, &res).await;
+        return res;
+    }
+
+    let created_by = client_name(data, client_id);
+
+    if let Some(config) = &data.settings.approvals {
+        let quote = data.quotes.get(&req.quote_id).ok_or(Error::NoQuote)?;
+        quote.verify_acceptable(data.ws_generation)?;
+        let amounts = quote_send_amounts(quote);
+        if config.approval_required(&amounts) {
+            // The approval is useless once the quote expires
+            let ttl = config
+                .ttl()
+                .min(quote.expires_at.saturating_duration_since(Instant::now()));
+            let txid = quote.txid;
+            let description = quote.description.clone();
+            let operation = approvals::Operation::AcceptQuote {
+                quote_id: req.quote_id,
+                user_note: req.user_note,
+            };
+            return Err(request_approval(
+                data,
+                created_by,
+                operation,
+                txid,
+                description,
+                amounts,
+                ttl,
+            )
+            .await);
+        }
+    }
+
+    execute_accept_quote(data, created_by, req).await
+}
+
+async fn execute_accept_quote(
+    data: &mut Data,
+    created_by: Option<String>,
+    req: api::AcceptQuoteReq,
+) -> Result<api::AcceptQuoteResp, Error> {
+    let quote = data.quotes.get(&req.quote_id).ok_or(Error::NoQuote)?;
+    quote.verify_acceptable(data.ws_generation)?;
+    let Some(second_leg) = quote.second_leg.clone() else {
+        return accept_leg(data, created_by, req).await;
+    };
+
+    // Nothing is accepted if the second leg can't be
+    data.quotes
+        .get(&second_leg.quote_id)
+        .ok_or(Error::NoQuote)?
+        .verify_acceptable(data.ws_generation)?;
+
+    let second_req = api::AcceptQuoteReq {
+        quote_id: second_leg.quote_id,
+        user_note: req.user_note.clone(),
+        idempotency_key: None,
+    };
+    let resp = accept_leg(data, created_by.clone(), req).await?;
+    let second = accept_leg(data, created_by, second_req).await;
+    if let Err(err) = &second {
+        tracing::error!(
+            txid = %resp.txid,
+            intermediate_amount = second_leg.intermediate_amount,
+            "routed quote partially completed, second leg failed: {err}"
+        );
+    }
+
+    Ok(api::AcceptQuoteResp {
+        route: Some(api::AcceptedRoute {
+            intermediate_asset: second_leg.intermediate_asset,
+            intermediate_amount: second_leg.intermediate_amount,
+            second_txid: second.as_ref().ok().map(|second| second.txid),
+            second_reference: second.as_ref().ok().map(|second| second.reference.clone()),
+            second_leg_error: second.err().map(|err| err.to_string()),
+        }),
+        ..resp
+    })
+}
+
+async fn accept_leg(
+    data: &mut Data,
+    created_by: Option<String>,
+    req: api::AcceptQuoteReq,
+) -> Result<api::AcceptQuoteResp, Error> {
+    let quote = data.quotes.get(&req.quote_id).ok_or(Error::NoQuote)?;
+
+    quote.verify_acceptable(data.ws_generation)?;
+
+    if !data.monitored_txs.contains_key(&quote.txid) {
+        check_quota(data, &created_by, api::QuotaResource::MonitoredTxs).await?;
+    }
+    let quote = data.quotes.get(&req.quote_id).ok_or(Error::NoQuote)?;
+
+    // The wallet UTXOs could be spent after the quote was received
+    let utxo_data = wallet(data, &quote.wallet)?
+        .utxo_data
+        .as_ref()
+        .ok_or(Error::NoUtxos)?;
+    pset_check::verify_signable(
+        &quote.pset,
+        utxo_data,
+        &quote.utxos,
+        data.settings.env.d().network.d().elements_params,
+        &quote.receive_address.script_pubkey(),
+    )?;
+    let pset = encode_pset(&utxo_data.sign_pset(quote.pset.clone()));
+
+    let swap = swap_row(req.quote_id, quote, created_by.clone());
+
+    let wallet_name = quote.wallet.clone();
+
+    if !data.monitored_txs.contains_key(&quote.txid) {
+        new_monitored_tx(
+            &data.db,
+            &mut data.monitored_txs,
+            MonitoredTx {
+                txid: Text(quote.txid),
+                description: Some(quote.description.clone()),
+                user_note: req.user_note,
+                created_by,
+                send_result: None,
+                wallet: None,
+                created_at: TimestampMs::now().millis() as i64,
+            },
+        )
+        .await;
+    }
+
+    let accept_resp = make_market_request!(
+        data.ws,
+        TakerSign,
+        mkt::TakerSignRequest {
+            quote_id: req.quote_id,
+            pset,
+        }
+    )?;
+
+    verify!(
+        accept_resp.txid == quote.txid,
+        Error::PsetMismatch {
+            reason: format!(
+                "the server swap txid {} is not the quoted txid {}",
+                accept_resp.txid, quote.txid
+            ),
+        }
+    );
+
+    tracing::info!(quote_id = ?req.quote_id, txid = %accept_resp.txid, "quote accepted");
+    data.metrics.quote_accepted();
+
+    data.db
+        .add_swap(&models::Swap {
+            created_at: TimestampMs::now().millis() as i64,
+            ..swap
+        })
+        .await;
+
+    // The swap pays the change there
+    if let Some(wallet) = data.wallets.get_mut(&wallet_name) {
+        wallet.quote_change_address = None;
+    }
+
+    let reference =
+        assign_reference(data, api::ReferenceKind::Tx, accept_resp.txid.to_string()).await;
+
+    Ok(api::AcceptQuoteResp {
+        txid: accept_resp.txid,
+        reference,
+        route: None,
+    })
+}
+
+/// Stored in the DB too, so the transaction can be sent after a restart
+async fn add_created_tx(data: &mut Data, txid: elements::Txid, created: CreatedTx) {
+    data.db.add_created_tx(&created.to_row(txid)).await;
+    data.created_txs.insert(txid, created);
+}
+
+/// Removes the created transactions older than `created_tx_max_age_seconds`.
+/// Older transactions are likely to spend UTXOs that are no longer available.
+async fn expire_created_txs(data: &mut Data, now: TimestampMs) {
+    let max_age = data
+        .settings
+        .created_tx_max_age_seconds
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_CREATED_TX_MAX_AGE);
+    let expired = data
+        .created_txs
+        .iter()
+        .filter(|(_txid, created)| {
+            now.millis() >= created.created_at.millis() + max_age.as_millis() as u64
+        })
+        .map(|(txid, _created)| *txid)
+        .collect::<Vec<_>>();
+    for txid in expired {
+        tracing::debug!(%txid, "expired created tx removed");
+        data.created_txs.remove(&txid);
+        data.db.delete_created_tx(txid).await;
+    }
+}
+
+/// Queues the operation until approved, returns the error for the requester.
+/// Repeated requests for the same transaction return the already queued approval.
+async fn request_approval(
+    data: &mut Data,
+    requested_by: Option<String>,
+    operation: approvals::Operation,
+    txid: elements::Txid,
+    description: String,
+    amounts: BTreeMap<DealerTicker, f64>,
+    ttl: Duration,
+) -> Error {
+    if let Some(approval) = data
+        .pending_approvals
+        .values()
+        .find(|approval| approval.txid.0 == txid && approval.operation.kind() == operation.kind())
+    {
+        return Error::ApprovalRequired(approval.approval_id.clone());
+    }
+
+    let created_at = TimestampMs::now().millis() as i64;
+    let approval = models::PendingApproval {
+        approval_id: random_id::hex_string(16),
+        operation: Json(operation),
+        txid: Text(txid),
+        description,
+        amounts: Json(amounts),
+        requested_by,
+        created_at,
+        expires_at: created_at + ttl.as_millis() as i64,
+    };
+    data.db.add_pending_approval(&approval).await;
+    audit(
+        data,
+        format!(
+            "approval {} requested by {:?}: {}",
+            approval.approval_id, approval.requested_by, approval.description
+        ),
+    )
+    .await;
+
+    let approval_id = approval.approval_id.clone();
+    data.pending_approvals.insert(approval_id.clone(), approval);
+    Error::ApprovalRequired(approval_id)
+}
+
+/// Removes the approval that is still valid at `now`
+async fn take_approval(
+    data: &mut Data,
+    approval_id: &str,
+    now: TimestampMs,
+) -> Result<models::PendingApproval, Error> {
+    let valid = data
+        .pending_approvals
+        .get(approval_id)
+        .is_some_and(|approval| approval.expires_at > now.millis() as i64);
+    verify!(valid, Error::UnknownApproval);
+    data.db.delete_pending_approval(approval_id).await;
+    Ok(data
+        .pending_approvals
+        .remove(approval_id)
+        .expect("must exist"))
+}
+
+/// Sent only to the requester (all connected clients with the same name)
+fn send_approval_resolved(
+    data: &Data,
+    requested_by: &Option<String>,
+    notif: api::ApprovalResolvedNotif,
+) {
+    let notif = EncodedNotif::new(api::Notif::ApprovalResolved(notif));
+    for client in data
+        .clients
+        .values()
+        .filter(|client| client.name == *requested_by)
+    {
+        client.notif_sender.send(notif.clone());
+    }
+}
+
+fn list_pending_approvals(
+    data: &Data,
+    api::ListPendingApprovalsReq {}: api::ListPendingApprovalsReq,
+) -> Result<api::ListPendingApprovalsResp, Error> {
+    let mut approvals = data
+        .pending_approvals
+        .values()
+        .map(|approval| api::PendingApproval {
+            approval_id: approval.approval_id.clone(),
+            operation: approval.operation.kind(),
+            txid: approval.txid.0,
+            description: approval.description.clone(),
+            amounts: approval.amounts.0.clone(),
+            requested_by: approval.requested_by.clone(),
+            created_at: TimestampMs::from_millis(approval.created_at as u64),
+            expires_at: TimestampMs::from_millis(approval.expires_at as u64),
+        })
+        .collect::<Vec<_>>();
+    approvals.sort_by_key(|approval| approval.created_at);
+    Ok(api::ListPendingApprovalsResp { approvals })
+}
+
+async fn approve(
+    data: &mut Data,
+    client_id: ClientId,
+    api::ApproveReq { approval_id }: api::ApproveReq,
+) -> Result<api::ApproveResp, Error> {
+    // Anyone can connect with any `client_name`, so the approver must be identified by its own token
+    let approver = data
+        .clients
+        .get(&client_id)
+        .filter(|client| client.client_token)
+        .and_then(|client| client.name.clone());
+    let requested_by = data
+        .pending_approvals
+        .get(&approval_id)
+        .ok_or(Error::UnknownApproval)?
+        .requested_by
+        .clone();
+    verify!(
+        approvals::approver_allowed(requested_by.as_deref(), approver.as_deref()),
+        Error::ApproverNotAllowed
+    );
+
+    let approval = take_approval(data, &approval_id, TimestampMs::now()).await?;
+    audit(
+        data,
+        format!("approval {approval_id} approved by {approver:?}"),
+    )
+    .await;
+
+    let res = match approval.operation.0 {
+        approvals::Operation::SendTx {
+            tx,
+            description,
+            user_note,
+            wallet_only,
+            wallet,
+        } => {
+            let tx = elements::encode::deserialize::<elements::Transaction>(
+                &hex::decode(tx).expect("must be valid"),
+            )
+            .expect("must be valid");
+            let txid = tx.txid();
+            add_created_tx(
+                data,
+                txid,
+                CreatedTx {
+                    tx,
+                    wallet: wallet.clone(),
+                    description,
+                    created_by: requested_by.clone(),
+                    amounts: approval.amounts.0,
+                    created_at: TimestampMs::now(),
+                },
+            )
+            .await;
+            let req = api::SendTxReq {
+                txid,
+                user_note,
+                wallet_only,
+                idempotency_key: None,
+                wallet,
+            };
+            execute_send_tx(data, requested_by.clone(), req)
+                .await
+                .map(|resp| api::ApproveResp {
+                    txid,
+                    send_tx: Some(resp),
+                })
+        }
+        approvals::Operation::AcceptQuote {
             quote_id,
-            base_amount,
-            quote_amount,
-            server_fee,
-            fixed_fee,
-            ttl,
+            user_note,
         } => {
-            let total_fee = server_fee + fixed_fee;
-
-            let (quote_send_amount, quote_recv_amount) = match (base_trade_dir, fee_asset) {
-                (TradeDir::Sell, AssetType::Base) => {
-                    (base_amount.saturating_add(total_fee), quote_amount)
-                }
-                (TradeDir::Sell, AssetType::Quote) => {
-                    (base_amount, quote_amount.saturating_sub(total_fee))
-                }
-                (TradeDir::Buy, AssetType::Base) => {
-                    (quote_amount, base_amount.saturating_sub(total_fee))
-                }
-                (TradeDir::Buy, AssetType::Quote) => {
-                    (quote_amount.saturating_add(total_fee), base_amount)
-                }
+            let req = api::AcceptQuoteReq {
+                quote_id,
+                user_note,
+                idempotency_key: None,
             };
+            execute_accept_quote(data, requested_by.clone(), req)
+                .await
+                .map(|resp| api::ApproveResp {
+                    txid: resp.txid,
+                    send_tx: None,
+                })
+        }
+    };
+
+    let notif = match &res {
+        Ok(resp) => api::ApprovalResolvedNotif {
+            approval_id,
+            state: api::ApprovalState::Approved,
+            txid: Some(resp.txid),
+            error: None,
+        },
+        Err(err) => {
+            audit(
+                data,
+                format!("approved operation {approval_id} failed: {err}"),
+            )
+            .await;
+            api::ApprovalResolvedNotif {
+                approval_id,
+                state: api::ApprovalState::Failed,
+                txid: None,
+                error: Some(err.to_string()),
+            }
+        }
+    };
+    send_approval_resolved(data, &requested_by, notif);
+
+    res
+}
+
+async fn reject(
+    data: &mut Data,
+    client_id: ClientId,
+    api::RejectReq {
+        approval_id,
+        reason,
+    }: api::RejectReq,
+) -> Result<api::RejectResp, Error> {
+    let approval = take_approval(data, &approval_id, TimestampMs::now()).await?;
+    audit(
+        data,
+        format!(
+            "approval {approval_id} rejected by {:?}, reason: {reason:?}",
+            client_name(data, client_id)
+        ),
+    )
+    .await;
+    send_approval_resolved(
+        data,
+        &approval.requested_by,
+        api::ApprovalResolvedNotif {
+            approval_id,
+            state: api::ApprovalState::Rejected,
+            txid: None,
+            error: reason,
+        },
+    );
+    Ok(api::RejectResp {})
+}
+
+async fn expire_approvals(data: &mut Data, now: TimestampMs) {
+    let expired = data
+        .pending_approvals
+        .values()
+        .filter(|approval| approval.expires_at <= now.millis() as i64)
+        .map(|approval| approval.approval_id.clone())
+        .collect::<Vec<_>>();
+    for approval_id in expired {
+        data.db.delete_pending_approval(&approval_id).await;
+        let approval = data
+            .pending_approvals
+            .remove(&approval_id)
+            .expect("must exist");
+        audit(data, format!("approval {approval_id} expired")).await;
+        send_approval_resolved(
+            data,
+            &approval.requested_by,
+            api::ApprovalResolvedNotif {
+                approval_id,
+                state: api::ApprovalState::Expired,
+                txid: None,
+                error: None,
+            },
+        );
+    }
+}
+
+/// Answered outside of the worker loop (see `start_request`)
+fn get_monitored_txs(
+    data: &Data,
+    api::GetMonitoredTxsReq {}: api::GetMonitoredTxsReq,
+) -> impl Future<Output = Result<api::GetMonitoredTxsResp, Error>> + Send + 'static {
+    let txids = data.monitored_txs.keys().copied().collect::<BTreeSet<_>>();
+    let txs = all_wallets_txs(data, Some(txids));
+
+    // The status is set once the wallets respond
+    let monitored_txs = data
+        .monitored_txs
+        .values()
+        .map(|monitored_txid| api::MonitoredTx {
+            txid: monitored_txid.txid.0,
+            status: api::TxStatus::NotFound,
+            description: monitored_txid.description.clone().unwrap_or_default(),
+            user_note: monitored_txid.user_note.clone(),
+            reference: data
+                .payment_refs
+                .get(api::ReferenceKind::Tx, &monitored_txid.txid.0.to_string())
+                .map(str::to_owned),
+            created_at: TimestampMs::from_millis(monitored_txid.created_at as u64),
+        })
+        .collect::<Vec<_>>();
+
+    async move {
+        let txs = txs.await?;
+        let monitored_txs = monitored_txs
+            .into_iter()
+            .map(|monitored_tx| api::MonitoredTx {
+                status: monitored_tx_status(&txs, &monitored_tx.txid),
+                ..monitored_tx
+            })
+            .collect();
+        Ok(api::GetMonitoredTxsResp { txs: monitored_txs })
+    }
+}
+
+/// The statuses are answered outside of the worker loop (like `get_monitored_txs`)
+async fn get_swaps(
+    data: &Data,
+    api::GetSwapsReq { from, to }: api::GetSwapsReq,
+) -> Result<impl Future<Output = Result<api::GetSwapsResp, Error>> + Send + 'static, Error> {
+    let from = from.map_or(0, |from| from.millis() as i64);
+    let to = to.map_or(i64::MAX, |to| to.millis() as i64);
+    verify!(
+        from <= to,
+        Error::InvalidSwapsRequest("from must not be after to")
+    );
+
+    let swaps = data
+        .db
+        .load_swaps(from, to)
+        .await
+        .into_iter()
+        .map(|swap| api::Swap {
+            quote_id: QuoteId::new(swap.quote_id as u64),
+            txid: swap.txid.0,
+            send_asset: swap.send_ticker.0,
+            send_amount: swap.send_amount,
+            recv_asset: swap.recv_ticker.0,
+            recv_amount: swap.recv_amount,
+            price: swap.price,
+            fee_asset: swap.fee_ticker.0,
+            fee_amount: swap.fee_amount,
+            server_fee: swap.server_fee as u64,
+            fixed_fee: swap.fixed_fee as u64,
+            timestamp: TimestampMs::from_millis(swap.created_at as u64),
+            status: api::TxStatus::NotFound,
+            reference: data
+                .payment_refs
+                .get(api::ReferenceKind::Tx, &swap.txid.0.to_string())
+                .map(str::to_owned),
+        })
+        .collect::<Vec<_>>();
+
+    let txids = swaps.iter().map(|swap| swap.txid).collect::<BTreeSet<_>>();
+    let txs = all_wallets_txs(data, Some(txids));
+
+    Ok(async move {
+        let txs = txs.await?;
+        let swaps = swaps
+            .into_iter()
+            .map(|swap| api::Swap {
+                status: monitored_tx_status(&txs, &swap.txid),
+                ..swap
+            })
+            .collect();
+        Ok(api::GetSwapsResp { swaps })
+    })
+}
+
+/// Returns the already assigned reference or assigns a new one.
+/// Candidates already taken in the DB (the reference column is unique) are skipped.
+async fn assign_reference(data: &mut Data, kind: api::ReferenceKind, target: String) -> String {
+    if let Some(reference) = data.payment_refs.get(kind, &target) {
+        return reference.to_owned();
+    }
+    loop {
+        let row = data.payment_refs.next_row(kind, &target);
+        if data.db.add_payment_reference(&row).await {
+            let reference = row.reference.clone();
+            tracing::debug!(reference, ?kind, target, "payment reference assigned");
+            data.payment_refs.insert(row);
+            return reference;
+        }
+        tracing::warn!(
+            reference = row.reference,
+            "payment reference is already used, skip it"
+        );
+    }
+}
+
+fn find_by_reference(
+    data: &Data,
+    api::FindByReferenceReq { reference }: api::FindByReferenceReq,
+) -> Result<api::FindByReferenceResp, Error> {
+    let reference =
+        payment_refs::normalize(&reference).ok_or(Error::InvalidReference(reference))?;
+    let (kind, target) = data
+        .payment_refs
+        .find(&reference)
+        .ok_or(Error::UnknownReference)?;
+    let resource = match kind {
+        api::ReferenceKind::Peg => {
+            let order_id = OrderId::from_str(target).expect("must be valid");
+            verify!(data.pegs.contains_key(&order_id), Error::UnknownReference);
+            api::ReferencedResource::Peg { order_id }
+        }
+        api::ReferenceKind::Tx => {
+            let txid = elements::Txid::from_str(target).expect("must be valid");
+            if data.monitored_txs.contains_key(&txid) {
+                api::ReferencedResource::MonitoredTx { txid }
+            } else if data.created_txs.contains_key(&txid) {
+                api::ReferencedResource::CreatedTx { txid }
+            } else {
+                return Err(Error::UnknownReference);
+            }
+        }
+    };
+    Ok(api::FindByReferenceResp {
+        reference,
+        resource,
+    })
+}
+
+fn monitored_tx_status(txs: &[sideswap_lwk::WalletTx], txid: &elements::Txid) -> api::TxStatus {
+    match txs.iter().find(|tx| tx.txid == *txid) {
+        Some(tx) if tx.height.is_some() => api::TxStatus::Confirmed,
+        Some(_) => api::TxStatus::Mempool,
+        None => api::TxStatus::NotFound,
+    }
+}
+
+fn tx_status_notif(data: &Data, txid: elements::Txid, status: api::TxStatus) -> api::Notif {
+    api::Notif::TxStatus(api::TxStatusNotif {
+        txid,
+        status,
+        reference: data
+            .payment_refs
+            .get(api::ReferenceKind::Tx, &txid.to_string())
+            .map(str::to_owned),
+    })
+}
+
+/// Re-evaluates the monitored tx statuses and notifies clients about the changes
+async fn update_tx_statuses(data: &mut Data) {
+    data.tx_statuses
+        .retain(|txid, _status| data.monitored_txs.contains_key(txid));
+    if data.monitored_txs.is_empty() {
+        return;
+    }
+
+    let txids = data.monitored_txs.keys().copied().collect::<BTreeSet<_>>();
+    let res = all_wallets_txs(data, Some(txids)).await;
+    let txs = match res {
+        Ok(txs) => txs,
+        Err(err) => {
+            tracing::error!("loading monitored txs failed: {err}");
+            return;
+        }
+    };
+
+    let txids = data.monitored_txs.keys().copied().collect::<Vec<_>>();
+    for txid in txids {
+        let status = monitored_tx_status(&txs, &txid);
+        let old_status = data.tx_statuses.insert(txid, status);
+        if old_status != Some(status) {
+            tracing::debug!(%txid, ?old_status, ?status, "monitored tx status changed");
+            let notif = tx_status_notif(data, txid, status);
+            // The first known status is not a change (for example, after a restart)
+            if old_status.is_some() {
+                send_notifs(data, &notif);
+            } else {
+                send_client_notifs(data, &notif);
+            }
+        }
+    }
+}
+
+async fn del_monitored_tx(
+    data: &mut Data,
+    api::DelMonitoredTxReq { txid, force }: api::DelMonitoredTxReq,
+) -> Result<api::DelMonitoredTxResp, Error> {
+    verify!(
+        data.monitored_txs.contains_key(&txid),
+        Error::UnknownMonitoredTx(txid)
+    );
+
+    if !force {
+        let txs = all_wallets_txs(data, Some(BTreeSet::from([txid]))).await?;
+        verify!(
+            monitored_tx_status(&txs, &txid) != api::TxStatus::Mempool,
+            Error::MonitoredTxUnconfirmed(txid)
+        );
+    }
 
-            verify!(
-                quote_send_amount == send_amount,
-                Error::NotEnoughAmount {
-                    asset_id: send_asset.asset_id,
-                    required: send_amount,
-                    available: quote_send_amount,
-                }
-            );
+    tracing::debug!(%txid, force, "del monitored tx");
 
-            let quote_recv_amount = asset_float_amount_(quote_recv_amount, recv_asset.precision);
+    data.db.delete_monitored_tx(txid).await;
 
-            let quote_resp =
-                make_market_request!(data.ws, GetQuote, mkt::GetQuoteRequest { quote_id })?;
+    data.monitored_txs.remove(&txid);
+    data.tx_statuses.remove(&txid);
 
-            let pset = decode_pset(&quote_resp.pset)?;
+    Ok(api::DelMonitoredTxResp {})
+}
 
-            let txid = pset.extract_tx()?.txid();
+fn tx_note_notif(monitored_tx: &MonitoredTx) -> api::Notif {
+    api::Notif::TxNote(api::TxNoteNotif {
+        txid: monitored_tx.txid.0,
+        description: monitored_tx.description.clone().unwrap_or_default(),
+        user_note: monitored_tx.user_note.clone(),
+    })
+}
 
-            let expires_at = Instant::now() + quote_resp.ttl.duration();
+async fn set_tx_note(
+    data: &mut Data,
+    api::SetTxNoteReq { txid, user_note }: api::SetTxNoteReq,
+) -> Result<api::SetTxNoteResp, Error> {
+    let monitored_tx = data
+        .monitored_txs
+        .get_mut(&txid)
+        .ok_or(Error::UnknownMonitoredTx(txid))?;
 
-            let pset = data
-                .utxo_data
-                .as_ref()
-                .ok_or(Error::NoUtxos)?
-                .sign_pset(pset);
+    data.db
+        .set_monitored_tx_user_note(txid, user_note.as_deref())
+        .await;
+    monitored_tx.user_note = user_note;
 
-            let note = format!(
-                "swap {} {} for {} {} to {}",
-                req.send_amount, req.send_asset, quote_recv_amount, req.recv_asset, receive_address
-            );
+    let notif = tx_note_notif(monitored_tx);
+    send_notifs(data, &notif);
 
-            data.quotes.insert(
-                quote_id,
-                Quote {
-                    txid,
-                    pset,
-                    expires_at,
-                    note,
+    Ok(api::SetTxNoteResp {})
+}
+
+fn export_notes(
+    data: &Data,
+    api::ExportNotesReq {}: api::ExportNotesReq,
+) -> Result<api::ExportNotesResp, Error> {
+    let notes = data
+        .monitored_txs
+        .values()
+        .map(|monitored_tx| {
+            (
+                monitored_tx.txid.0,
+                api::TxNotes {
+                    description: monitored_tx.description.clone(),
+                    user_note: monitored_tx.user_note.clone(),
                 },
-            );
+            )
+        })
+        .collect();
+    Ok(api::ExportNotesResp { notes })
+}
 
-            Ok(api::GetQuoteResp {
-                quote_id,
-                recv_amount: quote_recv_amount,
-                ttl,
+/// Without `overwrite`, a note is only set if it is missing or the same
+fn notes_conflict(monitored_tx: &MonitoredTx, notes: &api::TxNotes) -> bool {
+    let conflict = |old: &Option<String>, new: &Option<String>| old.is_some() && old != new;
+    conflict(&monitored_tx.description, &notes.description)
+        || conflict(&monitored_tx.user_note, &notes.user_note)
+}
+
+async fn import_notes(
+    data: &mut Data,
+    api::ImportNotesReq { notes, overwrite }: api::ImportNotesReq,
+) -> Result<api::ImportNotesResp, Error> {
+    let notes = notes
+        .into_iter()
+        .map(|(txid, notes)| {
+            elements::Txid::from_str(&txid)
+                .map(|txid| (txid, notes))
+                .map_err(|_err| Error::InvalidImportedTxid(txid))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut imported = Vec::new();
+    let mut skipped = Vec::new();
+    for (txid, notes) in notes {
+        let monitored_tx = match data.monitored_txs.get(&txid) {
+            Some(monitored_tx) => {
+                if !overwrite && notes_conflict(monitored_tx, &notes) {
+                    skipped.push(txid);
+                    continue;
+                }
+                MonitoredTx {
+                    description: notes
+                        .description
+                        .or_else(|| monitored_tx.description.clone()),
+                    user_note: notes.user_note.or_else(|| monitored_tx.user_note.clone()),
+                    ..monitored_tx.clone()
+                }
+            }
+            None => MonitoredTx {
+                txid: Text(txid),
+                description: notes.description,
+                user_note: notes.user_note,
+                created_by: None,
+                send_result: None,
+                wallet: None,
+                created_at: TimestampMs::now().millis() as i64,
+            },
+        };
+
+        data.db
+            .upsert_monitored_tx_notes(
                 txid,
-            })
-        }
+                monitored_tx.description.as_deref(),
+                monitored_tx.user_note.as_deref(),
+                monitored_tx.created_at,
+            )
+            .await;
+        send_notifs(data, &tx_note_notif(&monitored_tx));
+        data.monitored_txs.insert(txid, monitored_tx);
+        imported.push(txid);
+    }
 
-        mkt::QuoteStatus::LowBalance {
-            base_amount: _,
-            quote_amount: _,
-            server_fee: _,
-            fixed_fee: _,
-            available,
-        } => {
-            log::error!("unexpected LowBalance quote status");
-            abort!(Error::NotEnoughAmount {
-                asset_id: send_asset.asset_id,
-                required: send_amount,
-                available,
+    tracing::debug!(
+        imported = imported.len(),
+        skipped = skipped.len(),
+        "notes imported"
+    );
+
+    Ok(api::ImportNotesResp { imported, skipped })
+}
+
+/// Answered outside of the worker loop (see `start_request`)
+fn get_wallet_txs(
+    data: &Data,
+    api::GetWalletTxsReq {
+        after_height,
+        wallet,
+    }: api::GetWalletTxsReq,
+) -> impl Future<Output = Result<api::GetWalletTxsResp, Error>> + Send + 'static {
+    let resp = wallet_request(data, &wallet, |res_sender| sideswap_lwk::Command::GetTxs {
+        req: sideswap_lwk::GetTxsReq { txids: None },
+        res_sender,
+    });
+    let ticker_loader = Arc::clone(&data.ticker_loader);
+    let policy_asset = data.policy_asset;
+    let monitored_txs = data.monitored_txs.clone();
+
+    async move {
+        let txs = resp
+            .await?
+            .txs
+            .into_iter()
+            .filter(|tx| match (after_height, tx.height) {
+                (Some(after_height), Some(height)) => height > after_height,
+                (Some(_), None) | (None, _) => true,
             })
-        }
+            .map(|tx| {
+                convert_wallet_tx(
+                    &ticker_loader,
+                    &tx,
+                    &policy_asset,
+                    monitored_txs.get(&tx.txid),
+                )
+            })
+            .collect();
 
-        mkt::QuoteStatus::Error { error_msg } => abort!(Error::QuoteError(error_msg)),
+        Ok(api::GetWalletTxsResp { txs })
     }
 }
 
-async fn accept_quote(
-    data: &mut Data,
-    req: api::AcceptQuoteReq,
-) -> Result<api::AcceptQuoteResp, Error> {
-    let quote = data.quotes.get(&req.quote_id).ok_or(Error::NoQuote)?;
+fn request_name(req: &api::Req) -> &'static str {
+    match req {
+        api::Req::NewPeg(_) => "NewPeg",
+        api::Req::PegFeeEstimate(_) => "PegFeeEstimate",
+        api::Req::DelPeg(_) => "DelPeg",
+        api::Req::NewAddress(_) => "NewAddress",
+        api::Req::NewAddressBatch(_) => "NewAddressBatch",
+        api::Req::ListAddresses(_) => "ListAddresses",
+        api::Req::CreateTx(_) => "CreateTx",
+        api::Req::SendTx(_) => "SendTx",
+        api::Req::GetQuote(_) => "GetQuote",
+        api::Req::AcceptQuote(_) => "AcceptQuote",
+        api::Req::CancelQuote(_) => "CancelQuote",
+        api::Req::GetQuotas(_) => "GetQuotas",
+        api::Req::ReloadConfig(_) => "ReloadConfig",
+        api::Req::RefreshTickers(_) => "RefreshTickers",
+        api::Req::FindByReference(_) => "FindByReference",
+        api::Req::ListPendingApprovals(_) => "ListPendingApprovals",
+        api::Req::Approve(_) => "Approve",
+        api::Req::Reject(_) => "Reject",
+        api::Req::GetMonitoredTxs(_) => "GetMonitoredTxs",
+        api::Req::DelMonitoredTx(_) => "DelMonitoredTx",
+        api::Req::SetTxNote(_) => "SetTxNote",
+        api::Req::ExportNotes(_) => "ExportNotes",
+        api::Req::ImportNotes(_) => "ImportNotes",
+        api::Req::GetWalletTxs(_) => "GetWalletTxs",
+        api::Req::Unlock(_) => "Unlock",
+        api::Req::GetServerInfo(_) => "GetServerInfo",
+        api::Req::GetDiagnostics(_) => "GetDiagnostics",
+        api::Req::ListAssets(_) => "ListAssets",
+        api::Req::GetAssetInfo(_) => "GetAssetInfo",
+        api::Req::ListMarkets(_) => "ListMarkets",
+        api::Req::ListUtxos(_) => "ListUtxos",
+        api::Req::ResolveGaid(_) => "ResolveGaid",
+        api::Req::Drain(_) => "Drain",
+        api::Req::GetPegTimeline(_) => "GetPegTimeline",
+        api::Req::GetPegDetails(_) => "GetPegDetails",
+        api::Req::AddAllowedAddress(_) => "AddAllowedAddress",
+        api::Req::RemoveAllowedAddress(_) => "RemoveAllowedAddress",
+        api::Req::ListAllowedAddresses(_) => "ListAllowedAddresses",
+        api::Req::GetBalanceHistory(_) => "GetBalanceHistory",
+        api::Req::SignMessage(_) => "SignMessage",
+        api::Req::VerifyMessage(_) => "VerifyMessage",
+        api::Req::ExplainQuote(_) => "ExplainQuote",
+        api::Req::SubscribePrice(_) => "SubscribePrice",
+        api::Req::UnsubscribePrice(_) => "UnsubscribePrice",
+        api::Req::SubmitOrder(_) => "SubmitOrder",
+        api::Req::EditOrder(_) => "EditOrder",
+        api::Req::CancelOrder(_) => "CancelOrder",
+        api::Req::ListOwnOrders(_) => "ListOwnOrders",
+        api::Req::GetSwaps(_) => "GetSwaps",
+    }
+}
 
-    verify!(quote.ttl_valid(), Error::QuoteExpired);
+/// The password check result of `Unlock`
+struct UnlockResult {
+    password_valid: bool,
+    res_sender: UncheckedOneshotSender<Result<api::UnlockResp, Error>>,
+}
 
-    let pset = encode_pset(&quote.pset);
+/// The request result, or the future answering a read-only wallet request
+enum Processed {
+    Done(api::Resp),
+    Pending(BoxFuture<'static, Result<api::Resp, Error>>),
+}
 
-    if !data.monitored_txs.contains_key(&quote.txid) {
-        new_monitored_tx(
-            &data.db,
-            &mut data.monitored_txs,
-            MonitoredTx {
-                txid: Text(quote.txid),
-                description: Some(quote.note.clone()),
-                user_note: req.user_note,
-            },
-        )
-        .await;
+/// The read-only wallet requests (and `Unlock`) return `Processed::Pending`, so a slow wallet does not stall the worker loop.
+/// Everything else is processed in order (`CreateTx` is always done before the next `SendTx`).
+async fn start_request(
+    data: &mut Data,
+    client_id: ClientId,
+    req: api::Req,
+) -> Result<Processed, Error> {
+    match &req {
+        api::Req::NewPeg(_)
+        | api::Req::DelPeg(_)
+        | api::Req::NewAddress(_)
+        | api::Req::NewAddressBatch(_)
+        | api::Req::CreateTx(_)
+        | api::Req::GetQuote(_)
+        | api::Req::DelMonitoredTx(_)
+        | api::Req::AddAllowedAddress(_)
+        | api::Req::RemoveAllowedAddress(_)
+        | api::Req::SubmitOrder(_)
+        | api::Req::EditOrder(_) => check_not_draining(data)?,
+
+        api::Req::SendTx(_)
+        | api::Req::AcceptQuote(_)
+        | api::Req::ListAddresses(_)
+        | api::Req::GetMonitoredTxs(_)
+        | api::Req::GetWalletTxs(_)
+        | api::Req::Unlock(_)
+        | api::Req::GetServerInfo(_)
+        | api::Req::GetDiagnostics(_)
+        | api::Req::ListAssets(_)
+        | api::Req::GetAssetInfo(_)
+        | api::Req::ListMarkets(_)
+        | api::Req::ListUtxos(_)
+        | api::Req::ResolveGaid(_)
+        | api::Req::Drain(_)
+        | api::Req::GetPegTimeline(_)
+        | api::Req::GetPegDetails(_)
+        | api::Req::ListAllowedAddresses(_)
+        | api::Req::GetBalanceHistory(_)
+        | api::Req::SignMessage(_)
+        | api::Req::VerifyMessage(_)
+        | api::Req::ExplainQuote(_)
+        | api::Req::CancelQuote(_)
+        | api::Req::GetQuotas(_)
+        | api::Req::ReloadConfig(_)
+        | api::Req::RefreshTickers(_)
+        | api::Req::FindByReference(_)
+        | api::Req::ListPendingApprovals(_)
+        | api::Req::Approve(_)
+        | api::Req::Reject(_)
+        | api::Req::PegFeeEstimate(_)
+        | api::Req::SubscribePrice(_)
+        | api::Req::UnsubscribePrice(_)
+        | api::Req::CancelOrder(_)
+        | api::Req::ListOwnOrders(_)
+        | api::Req::GetSwaps(_)
+        | api::Req::SetTxNote(_)
+        | api::Req::ExportNotes(_)
+        | api::Req::ImportNotes(_) => {}
     }
 
-    let accept_resp = make_market_request!(
-        data.ws,
-        TakerSign,
-        mkt::TakerSignRequest {
-            quote_id: req.quote_id,
-            pset,
+    match &req {
+        api::Req::CreateTx(_)
+        | api::Req::SendTx(_)
+        | api::Req::GetQuote(_)
+        | api::Req::AcceptQuote(_)
+        | api::Req::AddAllowedAddress(_)
+        | api::Req::RemoveAllowedAddress(_)
+        | api::Req::SignMessage(_)
+        | api::Req::ReloadConfig(_)
+        | api::Req::RefreshTickers(_)
+        | api::Req::Approve(_)
+        | api::Req::SubmitOrder(_)
+        | api::Req::EditOrder(_) => check_signing_allowed(data)?,
+
+        api::Req::NewPeg(_)
+        | api::Req::DelPeg(_)
+        | api::Req::NewAddress(_)
+        | api::Req::NewAddressBatch(_)
+        | api::Req::ListAddresses(_)
+        | api::Req::GetMonitoredTxs(_)
+        | api::Req::DelMonitoredTx(_)
+        | api::Req::GetWalletTxs(_)
+        | api::Req::Unlock(_)
+        | api::Req::GetServerInfo(_)
+        | api::Req::GetDiagnostics(_)
+        | api::Req::ListAssets(_)
+        | api::Req::GetAssetInfo(_)
+        | api::Req::ListMarkets(_)
+        | api::Req::ListUtxos(_)
+        | api::Req::ResolveGaid(_)
+        | api::Req::Drain(_)
+        | api::Req::GetPegTimeline(_)
+        | api::Req::GetPegDetails(_)
+        | api::Req::ListAllowedAddresses(_)
+        | api::Req::GetBalanceHistory(_)
+        | api::Req::VerifyMessage(_)
+        | api::Req::ExplainQuote(_)
+        | api::Req::CancelQuote(_)
+        | api::Req::GetQuotas(_)
+        | api::Req::FindByReference(_)
+        | api::Req::ListPendingApprovals(_)
+        | api::Req::Reject(_)
+        | api::Req::PegFeeEstimate(_)
+        | api::Req::SubscribePrice(_)
+        | api::Req::UnsubscribePrice(_)
+        | api::Req::CancelOrder(_)
+        | api::Req::ListOwnOrders(_)
+        | api::Req::GetSwaps(_)
+        | api::Req::SetTxNote(_)
+        | api::Req::ExportNotes(_)
+        | api::Req::ImportNotes(_) => {}
+    }
+
+    let resp = match req {
+        api::Req::ListAddresses(req) => {
+            return Ok(Processed::Pending(
+                list_addresses(data, req)
+                    .map_ok(api::Resp::ListAddresses)
+                    .boxed(),
+            ))
         }
-    )?;
+        api::Req::GetMonitoredTxs(req) => {
+            return Ok(Processed::Pending(
+                get_monitored_txs(data, req)
+                    .map_ok(api::Resp::GetMonitoredTxs)
+                    .boxed(),
+            ))
+        }
+        api::Req::GetSwaps(req) => {
+            return Ok(Processed::Pending(
+                get_swaps(data, req)
+                    .await?
+                    .map_ok(api::Resp::GetSwaps)
+                    .boxed(),
+            ))
+        }
+        api::Req::GetWalletTxs(req) => {
+            return Ok(Processed::Pending(
+                get_wallet_txs(data, req)
+                    .map_ok(api::Resp::GetWalletTxs)
+                    .boxed(),
+            ))
+        }
+        api::Req::ListUtxos(req) => {
+            return Ok(Processed::Pending(
+                list_utxos(data, req).map_ok(api::Resp::ListUtxos).boxed(),
+            ))
+        }
+
+        api::Req::NewPeg(req) => new_peg(data, client_id, req).await.map(api::Resp::NewPeg),
+        api::Req::PegFeeEstimate(req) => peg_fee_estimate(data, req)
+            .await
+            .map(api::Resp::PegFeeEstimate),
+        api::Req::DelPeg(req) => del_peg(data, req).await.map(api::Resp::DelPeg),
+        api::Req::NewAddress(req) => new_address(data, req).await.map(api::Resp::NewAddress),
+        api::Req::NewAddressBatch(req) => new_address_batch(data, req)
+            .await
+            .map(api::Resp::NewAddressBatch),
+        api::Req::CreateTx(req) => create_tx(data, client_id, req)
+            .await
+            .map(api::Resp::CreateTx),
+        api::Req::SendTx(req) => send_tx(data, client_id, req).await.map(api::Resp::SendTx),
+        api::Req::GetQuote(req) => {
+            data.metrics.quote_requested();
+            let res = get_quote(data, client_id, *req).await;
+            if let Err(err) = &res {
+                data.metrics.quote_failed(&err.error_code());
+            }
+            res.map(api::Resp::GetQuote)
+        }
+        api::Req::AcceptQuote(req) => accept_quote(data, client_id, req)
+            .await
+            .map(api::Resp::AcceptQuote),
+        api::Req::DelMonitoredTx(req) => del_monitored_tx(data, req)
+            .await
+            .map(api::Resp::DelMonitoredTx),
+        api::Req::SetTxNote(req) => set_tx_note(data, req).await.map(api::Resp::SetTxNote),
+        api::Req::ExportNotes(req) => export_notes(data, req).map(api::Resp::ExportNotes),
+        api::Req::ImportNotes(req) => import_notes(data, req).await.map(api::Resp::ImportNotes),
+        api::Req::Unlock(req) => {
+            return Ok(Processed::Pending(
+                unlock(data, req)?.map_ok(api::Resp::Unlock).boxed(),
+            ))
+        }
+        api::Req::GetServerInfo(req) => get_server_info(data, req).map(api::Resp::GetServerInfo),
+        api::Req::GetDiagnostics(req) => get_diagnostics(data, req).map(api::Resp::GetDiagnostics),
+        api::Req::ListAssets(req) => list_assets(data, req).map(api::Resp::ListAssets),
+        api::Req::GetAssetInfo(req) => get_asset_info(data, req).await.map(api::Resp::GetAssetInfo),
+        api::Req::ListMarkets(req) => list_markets(data, req).map(api::Resp::ListMarkets),
+        api::Req::ResolveGaid(req) => resolve_gaid(data, req).await.map(api::Resp::ResolveGaid),
+        api::Req::Drain(req) => drain(data, req).await.map(api::Resp::Drain),
+        api::Req::GetPegTimeline(req) => get_peg_timeline(data, req)
+            .await
+            .map(api::Resp::GetPegTimeline),
+        api::Req::GetPegDetails(req) => get_peg_details(data, req)
+            .await
+            .map(api::Resp::GetPegDetails),
+        api::Req::AddAllowedAddress(req) => add_allowed_address(data, req)
+            .await
+            .map(api::Resp::AddAllowedAddress),
+        api::Req::RemoveAllowedAddress(req) => remove_allowed_address(data, req)
+            .await
+            .map(api::Resp::RemoveAllowedAddress),
+        api::Req::ListAllowedAddresses(req) => {
+            list_allowed_addresses(data, req).map(api::Resp::ListAllowedAddresses)
+        }
+        api::Req::GetBalanceHistory(req) => get_balance_history(data, req)
+            .await
+            .map(api::Resp::GetBalanceHistory),
+        api::Req::SignMessage(req) => sign_message(data, req).await.map(api::Resp::SignMessage),
+        api::Req::VerifyMessage(req) => verify_message(req).map(api::Resp::VerifyMessage),
+        api::Req::ExplainQuote(req) => explain_quote(data, req).map(api::Resp::ExplainQuote),
+        api::Req::CancelQuote(req) => cancel_quote(data, req).await.map(api::Resp::CancelQuote),
+        api::Req::GetQuotas(req) => get_quotas(data, client_id, req)
+            .await
+            .map(api::Resp::GetQuotas),
+        api::Req::ReloadConfig(api::ReloadConfigReq {}) => {
+            reload_config(data).await.map(|changed_fields| {
+                api::Resp::ReloadConfig(api::ReloadConfigResp {
+                    changed_fields: changed_fields.into_iter().map(str::to_owned).collect(),
+                })
+            })
+        }
+        api::Req::RefreshTickers(api::RefreshTickersReq {}) => refresh_tickers(data)
+            .await
+            .map(|added| api::Resp::RefreshTickers(api::RefreshTickersResp { added })),
+        api::Req::FindByReference(req) => {
+            find_by_reference(data, req).map(api::Resp::FindByReference)
+        }
+        api::Req::ListPendingApprovals(req) => {
+            list_pending_approvals(data, req).map(api::Resp::ListPendingApprovals)
+        }
+        api::Req::Approve(req) => approve(data, client_id, req).await.map(api::Resp::Approve),
+        api::Req::Reject(req) => reject(data, client_id, req).await.map(api::Resp::Reject),
+        api::Req::SubscribePrice(req) => {
+            subscribe_price(data, client_id, req).map(api::Resp::SubscribePrice)
+        }
+        api::Req::UnsubscribePrice(req) => {
+            unsubscribe_price(data, client_id, req).map(api::Resp::UnsubscribePrice)
+        }
+        api::Req::SubmitOrder(req) => submit_order(data, client_id, req)
+            .await
+            .map(api::Resp::SubmitOrder),
+        api::Req::EditOrder(req) => edit_order(data, req).await.map(api::Resp::EditOrder),
+        api::Req::CancelOrder(req) => cancel_order(data, req).await.map(api::Resp::CancelOrder),
+        api::Req::ListOwnOrders(req) => list_own_orders(data, req).map(api::Resp::ListOwnOrders),
+    }?;
+    Ok(Processed::Done(resp))
+}
 
-    assert_eq!(quote.txid, accept_resp.txid);
+fn get_diagnostics(
+    data: &Data,
+    _req: api::GetDiagnosticsReq,
+) -> Result<api::GetDiagnosticsResp, Error> {
+    Ok(api::GetDiagnosticsResp {
+        discarded_quote_notifs: data.discarded_quote_notifs,
+        stale_quote_notifs: data.stale_quote_notifs,
+        quote_timeouts: data.quote_timeouts,
+        rejected_pegs: data
+            .pegs
+            .iter()
+            .filter(|(_order_id, peg)| peg.server_error.is_some())
+            .map(|(order_id, _peg)| *order_id)
+            .collect(),
+        foreign_addresses: data.foreign_addresses,
+    })
+}
 
-    Ok(api::AcceptQuoteResp {
-        txid: accept_resp.txid,
+fn get_server_info(
+    data: &Data,
+    _req: api::GetServerInfoReq,
+) -> Result<api::GetServerInfoResp, Error> {
+    Ok(api::GetServerInfoResp {
+        clock_skew_detected: data.clock_skew.detected(),
+        clock_skew_ms: data.clock_skew.skew_ms(),
+        stale_quote_notifs: data.stale_quote_notifs,
+        skipped_upstream_msgs: ws::auto::skipped_messages(),
+        largest_upstream_msg: ws::auto::largest_message(),
+        upstream_request_sizes: data
+            .ws
+            .size_stats()
+            .iter()
+            .map(|(request_type, stats)| {
+                (
+                    request_type.clone(),
+                    api::RequestSizes {
+                        count: stats.count,
+                        total_bytes: stats.total_bytes,
+                        max_bytes: stats.max_bytes as u64,
+                        rejected: stats.rejected,
+                        histogram: stats.buckets.to_vec(),
+                    },
+                )
+            })
+            .collect(),
+        draining: data.drain_deadline.is_some(),
+        version: db::BINARY_VERSION.to_owned(),
+        schema_version: db::schema_version(),
+        onion_address: data.tor_status.borrow().onion_address.clone(),
+        tor_error: data.tor_status.borrow().error.clone(),
     })
 }
 
-async fn get_monitored_txs(
+async fn get_asset_info(
     data: &mut Data,
-    api::GetMonitoredTxsReq {}: api::GetMonitoredTxsReq,
-) -> Result<api::GetMonitoredTxsResp, Error> {
-    let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
-    let txids = data.monitored_txs.keys().copied().collect::<BTreeSet<_>>();
-    data.wallet_command_sender
-        .send(sideswap_lwk::Command::GetTxs {
-            req: sideswap_lwk::GetTxsReq { txids: Some(txids) },
-            res_sender: res_sender.into(),
-        })?;
-    let txs = res_receiver.await??;
+    api::GetAssetInfoReq { asset_id }: api::GetAssetInfoReq,
+) -> Result<api::GetAssetInfoResp, Error> {
+    let ticker = data.ticker_loader.ticker(&asset_id);
 
-    let monitored_txs = data
-        .monitored_txs
-        .values()
-        .map(|monitored_txid| {
-            let tx = txs.txs.iter().find(|tx| tx.txid == monitored_txid.txid.0);
+    let cached = data
+        .gdk_registry
+        .as_ref()
+        .and_then(|registry| registry.get_short_asset(&asset_id));
+    let registered = match cached {
+        Some(asset) => Some(asset),
+        // The server also knows the assets registered after the local registry update
+        None => match make_request!(
+            data.ws,
+            AssetDetails,
+            sideswap_api::AssetDetailsRequest { asset_id }
+        ) {
+            Ok(resp) => Some(ShortAssetInfo {
+                asset_id,
+                name: resp.name,
+                ticker: Some(resp.ticker),
+                precision: resp.precision,
+            }),
+            Err(ws_req_sender::Error::BackendError(_, _)) => None,
+            Err(err) => return Err(err.into()),
+        },
+    };
 
-            let status = if let Some(tx) = tx {
-                if tx.height.is_some() {
-                    api::TxStatus::Confirmed
-                } else {
-                    api::TxStatus::Mempool
-                }
-            } else {
-                api::TxStatus::NotFound
-            };
+    Ok(api::GetAssetInfoResp {
+        asset_id,
+        balance_key: ticker.unwrap_or_else(|| unknown_asset_key(&asset_id)),
+        whitelisted: ticker.is_some(),
+        registered: registered.is_some(),
+        precision: registered.as_ref().map(|asset| asset.precision.value()),
+        name: registered.as_ref().map(|asset| asset.name.clone()),
+        ticker: registered.and_then(|asset| asset.ticker.map(|ticker| ticker.0)),
+    })
+}
 
-            api::MonitoredTx {
-                txid: monitored_txid.txid.0,
-                status,
-                description: monitored_txid.description.clone().unwrap_or_default(),
-                user_note: monitored_txid.user_note.clone(),
+fn list_assets(
+    data: &Data,
+    api::ListAssetsReq {}: api::ListAssetsReq,
+) -> Result<api::ListAssetsResp, Error> {
+    let assets = data
+        .ticker_loader
+        .tickers()
+        .map(|ticker| {
+            let asset_id = *data.ticker_loader.asset_id(ticker);
+            let flags = data.asset_flags.get(&asset_id);
+            api::AssetInfo {
+                ticker,
+                asset_id,
+                precision: data.ticker_loader.precision(ticker).value(),
+                amp_restricted: flags.map(|flags| flags.amp_restricted),
+                payjoin: flags.map(|flags| flags.payjoin),
             }
         })
-        .collect::<Vec<_>>();
+        .collect();
 
-    Ok(api::GetMonitoredTxsResp { txs: monitored_txs })
+    Ok(api::ListAssetsResp { assets })
 }
 
-async fn del_monitored_tx(
-    data: &mut Data,
-    api::DelMonitoredTxReq { txid }: api::DelMonitoredTxReq,
-) -> Result<api::DelMonitoredTxResp, Error> {
-    data.db.delete_monitored_tx(txid).await;
+fn known_markets(data: &Data) -> Vec<api::Market> {
+    data.markets
+        .iter()
+        .filter_map(|market| convert_market(&data.ticker_loader, market))
+        .collect()
+}
 
-    data.monitored_txs.remove(&txid);
+fn list_markets(
+    data: &Data,
+    api::ListMarketsReq {}: api::ListMarketsReq,
+) -> Result<api::ListMarketsResp, Error> {
+    let stale = data
+        .markets_updated_at
+        .is_none_or(|updated_at| market_data_stale(data, updated_at));
+    Ok(api::ListMarketsResp {
+        markets: known_markets(data),
+        stale,
+    })
+}
 
-    Ok(api::DelMonitoredTxResp {})
+/// The wallet UTXOs, restricted to the request list if set (outpoints not owned by the wallet are ignored)
+fn selected_utxos(
+    data: &Data,
+    wallet_name: &str,
+    outpoints: Option<&[elements::OutPoint]>,
+    allow_unconfirmed: bool,
+) -> Result<Vec<sideswap_api::Utxo>, Error> {
+    let wallet = wallet(data, wallet_name)?;
+    let utxos = wallet
+        .utxo_data
+        .as_ref()
+        .ok_or(Error::NoUtxos)?
+        .utxos()
+        .iter()
+        .filter(|utxo| outpoints.is_none_or(|outpoints| outpoints.contains(&utxo.outpoint())))
+        .filter(|utxo| allow_unconfirmed || wallet.confirmed_utxos.contains(&utxo.outpoint()))
+        .cloned()
+        .collect();
+    Ok(utxos)
 }
 
-async fn get_wallet_txs(
-    data: &mut Data,
-    api::GetWalletTxsReq {}: api::GetWalletTxsReq,
-) -> Result<api::GetWalletTxsResp, Error> {
-    let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
-    data.wallet_command_sender
-        .send(sideswap_lwk::Command::GetTxs {
-            req: sideswap_lwk::GetTxsReq { txids: None },
-            res_sender: res_sender.into(),
-        })?;
-    let resp = res_receiver.await??;
+/// Answered outside of the worker loop (see `start_request`)
+fn list_utxos(
+    data: &Data,
+    api::ListUtxosReq { wallet }: api::ListUtxosReq,
+) -> impl Future<Output = Result<api::ListUtxosResp, Error>> + Send + 'static {
+    let resp = wallet_request(data, &wallet, |res_sender| {
+        sideswap_lwk::Command::GetUtxos {
+            req: sideswap_lwk::GetUtxosReq {},
+            res_sender,
+        }
+    });
+    let utxos = selected_utxos(data, &wallet, None, true);
+    let ticker_loader = Arc::clone(&data.ticker_loader);
 
-    let txs = resp
-        .txs
-        .into_iter()
-        .map(|tx| convert_wallet_tx(&data.ticker_loader, &tx, &data.policy_asset))
-        .collect();
+    async move {
+        let resp = resp.await?;
+        let utxos = utxos?;
 
-    Ok(api::GetWalletTxsResp { txs })
-}
+        let heights = resp
+            .utxos
+            .iter()
+            .map(|utxo| (utxo.outpoint, utxo.height))
+            .collect::<BTreeMap<_, _>>();
 
-async fn process_request(data: &mut Data, req: api::Req) -> Result<api::Resp, Error> {
-    match req {
-        api::Req::NewPeg(req) => new_peg(data, req).await.map(api::Resp::NewPeg),
-        api::Req::DelPeg(req) => del_peg(data, req).await.map(api::Resp::DelPeg),
-        api::Req::NewAddress(req) => new_address(data, req).await.map(api::Resp::NewAddress),
-        api::Req::ListAddresses(req) => list_addresses(data, req)
-            .await
-            .map(api::Resp::ListAddresses),
-        api::Req::CreateTx(req) => create_tx(data, req).await.map(api::Resp::CreateTx),
-        api::Req::SendTx(req) => send_tx(data, req).await.map(api::Resp::SendTx),
-        api::Req::GetQuote(req) => get_quote(data, req).await.map(api::Resp::GetQuote),
-        api::Req::AcceptQuote(req) => accept_quote(data, req).await.map(api::Resp::AcceptQuote),
-        api::Req::GetMonitoredTxs(req) => get_monitored_txs(data, req)
-            .await
-            .map(api::Resp::GetMonitoredTxs),
-        api::Req::DelMonitoredTx(req) => del_monitored_tx(data, req)
-            .await
-            .map(api::Resp::DelMonitoredTx),
-        api::Req::GetWalletTxs(req) => get_wallet_txs(data, req).await.map(api::Resp::GetWalletTxs),
+        let utxos = utxos
+            .into_iter()
+            .filter_map(|utxo| {
+                let ticker = ticker_loader.ticker(&utxo.asset)?;
+                let precision = ticker_loader.precision(ticker);
+                let outpoint = utxo.outpoint();
+                let confirmations = heights
+                    .get(&outpoint)
+                    .copied()
+                    .flatten()
+                    .map_or(0, |height| resp.tip_height.saturating_sub(height) + 1);
+                Some(api::UtxoInfo {
+                    outpoint,
+                    ticker,
+                    amount: asset_float_amount_(utxo.value, precision),
+                    confirmations,
+                })
+            })
+            .collect();
+
+        Ok(api::ListUtxosResp { utxos })
     }
 }
 
+async fn resolve_gaid(
+    data: &mut Data,
+    api::ResolveGaidReq { asset, gaid }: api::ResolveGaidReq,
+) -> Result<api::ResolveGaidResp, Error> {
+    let asset = try_get_asset(&data.ticker_loader, asset)?;
+    let address = resolve_gaid_address(data, asset.asset_id, gaid).await?;
+    Ok(api::ResolveGaidResp { address })
+}
+
+/// Backend errors (unknown GAID, not an AMP asset) are reported as `GaidResolveFailed`
+async fn resolve_gaid_address(
+    data: &mut Data,
+    asset_id: AssetId,
+    gaid: String,
+) -> Result<elements::Address, Error> {
+    let res = make_market_request!(
+        data.ws,
+        ResolveGaid,
+        mkt::ResolveGaidRequest {
+            asset_id,
+            gaid: gaid.clone()
+        }
+    );
+    let resp = match res {
+        Ok(resp) => resp,
+        Err(ws_req_sender::Error::BackendError(reason, _error_code)) => {
+            abort!(Error::GaidResolveFailed { gaid, reason })
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    data.gaid_addresses.insert((asset_id, resp.address.clone()));
+
+    Ok(resp.address)
+}
+
 async fn process_command(data: &mut Data, command: Command) {
     match command {
         Command::ClientConnected {
             client_id,
+            client_name,
+            client_token,
             notif_sender,
         } => {
-            if let Some(balance) = &data.last_balances {
-                notif_sender.send(api::Notif::Balances(balance.clone()));
+            for balance in data
+                .wallets
+                .values()
+                .filter_map(|wallet| wallet.last_balances.as_ref())
+            {
+                notif_sender.send(EncodedNotif::new(api::Notif::Balances(balance.clone())));
             }
 
             for status in data.pegs.values().filter_map(|peg| peg.status.as_ref()) {
-                notif_sender.send(api::Notif::PegStatus(api::PegStatusNotif {
-                    peg: status.clone(),
-                }));
+                notif_sender.send(EncodedNotif::new(api::Notif::PegStatus(
+                    api::PegStatusNotif {
+                        peg: status.clone(),
+                    },
+                )));
+            }
+
+            for (txid, status) in data.tx_statuses.iter() {
+                notif_sender.send(EncodedNotif::new(tx_status_notif(data, *txid, *status)));
+            }
+
+            if let Some(updated_at) = data.markets_updated_at {
+                notif_sender.send(EncodedNotif::new(api::Notif::Markets(api::MarketsNotif {
+                    markets: known_markets(data),
+                    stale: market_data_stale(data, updated_at),
+                })));
+            }
+
+            if let Some(signing_lock) = &data.signing_lock {
+                notif_sender.send(EncodedNotif::new(api::Notif::LockStatus(
+                    api::LockStatusNotif {
+                        locked: signing_lock.locked(),
+                    },
+                )));
+            }
+
+            for notif in data
+                .wallets
+                .values()
+                .filter_map(|wallet| wallet.gap_limit_warning.as_ref())
+            {
+                notif_sender.send(EncodedNotif::new(api::Notif::GapLimitWarning(
+                    notif.clone(),
+                )));
+            }
+
+            if let Some(drain_deadline) = data.drain_deadline {
+                notif_sender.send(EncodedNotif::new(api::Notif::Draining(draining_notif(
+                    drain_deadline,
+                ))));
+            }
+
+            if !data.ws_connected {
+                notif_sender.send(EncodedNotif::new(api::Notif::ServerConnection(
+                    api::ServerConnectionNotif { connected: false },
+                )));
             }
 
-            data.clients.insert(client_id, ClientData { notif_sender });
+            data.clients.insert(
+                client_id,
+                ClientData {
+                    name: client_name,
+                    client_token,
+                    notif_sender,
+                },
+            );
         }
 
         Command::ClientDisconnected { client_id } => {
             data.clients.remove(&client_id).expect("must not fail");
+
+            let asset_pairs = data.price_subs.keys().copied().collect::<Vec<_>>();
+            for asset_pair in asset_pairs {
+                remove_price_sub(data, client_id, asset_pair);
+            }
         }
 
-        Command::Request { req, res_sender } => {
-            let res = process_request(data, req).await;
+        Command::Request {
+            client_id,
+            req,
+            res_sender,
+        } => {
+            // Every request gets a new trace id, the upstream requests are logged in the same span
+            let span = tracing::info_span!(
+                "request",
+                client_id = client_id.0,
+                trace_id = %random_id::hex_string(16),
+                request = request_name(&req),
+            );
+            let started_at = Instant::now();
+            let res = match start_request(data, client_id, req)
+                .instrument(span.clone())
+                .await
+            {
+                Ok(Processed::Done(resp)) => Ok(resp),
+                Ok(Processed::Pending(resp)) => {
+                    // The next commands and events are processed while waiting for the wallet
+                    let task_span = span.clone();
+                    tokio::spawn(
+                        async move {
+                            let res = resp.await;
+                            log_request_result(&span, started_at, &res);
+                            res_sender.send(res);
+                        }
+                        .instrument(task_span),
+                    );
+                    return;
+                }
+                Err(err) => Err(err),
+            };
+            log_request_result(&span, started_at, &res);
             res_sender.send(res);
         }
     }
 }
 
+fn log_request_result(span: &tracing::Span, started_at: Instant, res: &Result<api::Resp, Error>) {
+    let duration_ms = started_at.elapsed().as_millis() as u64;
+    span.in_scope(|| match res {
+        Ok(_) => tracing::debug!(duration_ms, "request processed"),
+        Err(err) => tracing::debug!(duration_ms, error = %err, "request failed"),
+    });
+}
+
+fn request_markets_and_assets(data: &mut Data) {
+    data.ws
+        .send_request(sideswap_api::Request::Market(mkt::Request::ListMarkets(
+            mkt::ListMarketsRequest {},
+        )));
+
+    data.ws.send_request(sideswap_api::Request::Assets(Some(
+        sideswap_api::AssetsRequestParam {
+            embedded_icons: Some(false),
+            all_assets: Some(true),
+            amp_asset_restrictions: Some(true),
+        },
+    )));
+}
+
 fn process_ws_connected(data: &mut Data) {
+    data.ws_generation += 1;
+    if data.ws_generation > 1 {
+        data.metrics.ws_reconnected();
+    }
+    data.ws_connected = true;
+    send_notifs(
+        data,
+        &api::Notif::ServerConnection(api::ServerConnectionNotif { connected: true }),
+    );
+    data.quote_subs.clear();
+    data.last_quote_sub = None;
+
+    start_clock_check(data);
+
     data.ws.send_request(sideswap_api::Request::LoginClient(
         sideswap_api::LoginClientRequest {
             api_key: None,
@@ -914,27 +5627,125 @@ fn process_ws_connected(data: &mut Data) {
         },
     ));
 
-    data.ws
-        .send_request(sideswap_api::Request::Market(mkt::Request::ListMarkets(
-            mkt::ListMarketsRequest {},
-        )));
+    request_markets_and_assets(data);
+
+    // Upstream subscriptions do not survive reconnects
+    for asset_pair in data.price_subs.keys() {
+        data.ws
+            .send_request(sideswap_api::Request::Market(mkt::Request::Subscribe(
+                mkt::SubscribeRequest {
+                    asset_pair: *asset_pair,
+                },
+            )));
+    }
+
+    retry_broadcasts(data);
 
-    for order_id in data.pegs.keys() {
-        data.ws.send_request(sideswap_api::Request::PegStatus(
+    let now = TimestampMs::now().millis() as i64;
+    for (order_id, peg) in data.pegs.iter_mut() {
+        if peg.retired(now) {
+            continue;
+        }
+        peg.status_request_id = Some(data.ws.send_request(sideswap_api::Request::PegStatus(
             sideswap_api::PegStatusRequest {
                 order_id: *order_id,
                 peg_in: None,
             },
-        ));
+        )));
+    }
+}
+
+/// Marks the pegs the server refuses to report (for example, the order was created with another env)
+fn process_peg_status_resp(
+    data: &mut Data,
+    request_id: &sideswap_api::RequestId,
+    res: &Result<sideswap_api::Response, sideswap_api::Error>,
+) {
+    let Some((order_id, peg)) = data
+        .pegs
+        .iter_mut()
+        .find(|(_order_id, peg)| peg.status_request_id.as_ref() == Some(request_id))
+    else {
+        return;
+    };
+    peg.status_request_id = None;
+    match res {
+        Ok(_resp) => {
+            peg.server_error = None;
+        }
+        Err(err) => {
+            tracing::error!(%order_id, "peg status request failed, the order might be from another env: {err}");
+            peg.server_error = Some(err.to_string());
+        }
+    }
+}
+
+fn retry_broadcasts(data: &mut Data) {
+    data.pending_broadcasts.retain(|txid, pending| {
+        if pending.attempts >= MAX_BROADCAST_RETRIES {
+            tracing::warn!(%txid, "server broadcast retries exhausted");
+            return false;
+        }
+        pending.attempts += 1;
+        tracing::debug!(%txid, attempt = pending.attempts, "retry server broadcast");
+        pending.request_id = Some(data.ws.send_request(sideswap_api::Request::Market(
+            mkt::Request::BroadcastTx(mkt::BroadcastTxRequest {
+                tx: pending.tx.clone().into(),
+            }),
+        )));
+        true
+    });
+}
+
+/// Removes the pending broadcast once the server accepts it (failed retries are repeated after the next reconnect)
+fn process_broadcast_retry_resp(
+    data: &mut Data,
+    request_id: &sideswap_api::RequestId,
+    res: &Result<sideswap_api::Response, sideswap_api::Error>,
+) {
+    let txid = data
+        .pending_broadcasts
+        .iter()
+        .find(|(_txid, pending)| pending.request_id.as_ref() == Some(request_id))
+        .map(|(txid, _pending)| *txid);
+    let Some(txid) = txid else {
+        return;
+    };
+    match res {
+        Ok(_resp) => {
+            tracing::info!(%txid, "server broadcast retry succeeded");
+            data.pending_broadcasts.remove(&txid);
+        }
+        Err(err) => {
+            tracing::warn!(%txid, "server broadcast retry failed: {err}");
+            data.metrics.broadcast_failed(BroadcastTarget::Server);
+            if let Some(pending) = data.pending_broadcasts.get_mut(&txid) {
+                pending.request_id = None;
+            }
+        }
     }
 }
 
-fn process_ws_disconnected(_data: &mut Data) {}
+fn process_ws_disconnected(data: &mut Data) {
+    // Restored by the next market login
+    data.market_utxos = None;
+    for order in data.own_orders.values_mut() {
+        order.online = false;
+    }
+
+    // The quotes are kept until they expire, so accepting them returns `QuoteInvalidatedByReconnect` (and not `NoQuote`)
+    data.ws_connected = false;
+    send_notifs(
+        data,
+        &api::Notif::ServerConnection(api::ServerConnectionNotif { connected: false }),
+    );
+}
 
 fn process_market_resp(data: &mut Data, resp: mkt::Response) {
     match resp {
         mkt::Response::ListMarkets(resp) => {
             data.markets = resp.markets;
+            data.markets_updated_at = Some(Instant::now());
         }
 
         mkt::Response::Challenge(_)
@@ -964,49 +5775,263 @@ fn process_market_resp(data: &mut Data, resp: mkt::Response) {
     }
 }
 
-fn process_peg_status(data: &mut Data, status: sideswap_api::PegStatus) {
-    log::debug!(
+fn process_assets(data: &mut Data, assets: sideswap_api::Assets) {
+    for asset in assets {
+        if data.ticker_loader.ticker(&asset.asset_id).is_some() {
+            let amp_restricted = asset.market_type == Some(sideswap_api::MarketType::Amp)
+                || asset.amp_asset_restrictions.is_some();
+            data.asset_flags.insert(
+                asset.asset_id,
+                AssetFlags {
+                    amp_restricted,
+                    payjoin: asset.payjoin.unwrap_or_default(),
+                },
+            );
+        }
+    }
+}
+
+/// Records the transactions that are new or have a changed state (the server can resend identical statuses)
+async fn record_peg_events(db: &Db, peg: &mut PegData, status: &api::PegStatus) {
+    let now = TimestampMs::now().millis() as i64;
+
+    for tx in status.list.iter() {
+        let last_event = peg
+            .timeline
+            .iter()
+            .rev()
+            .find(|event| event.tx_hash.0 == tx.tx_hash && event.vout == tx.vout as i64);
+        let changed = last_event.is_none_or(|event| {
+            event.tx_state.0 != tx.tx_state
+                || event.payout_txid.as_ref().map(|txid| txid.0) != tx.payout_txid
+        });
+
+        if changed {
+            let event = models::PegEvent {
+                order_id: Text(status.order_id),
+                created_at: now,
+                tx_hash: Text(tx.tx_hash),
+                vout: tx.vout.into(),
+                tx_state: Json(tx.tx_state),
+                payout_txid: tx.payout_txid.map(Text),
+            };
+            db.add_peg_event(&event, MAX_PEG_EVENTS as i64).await;
+            peg.timeline.push(event);
+        }
+    }
+
+    if peg.timeline.len() > MAX_PEG_EVENTS {
+        peg.timeline.drain(..peg.timeline.len() - MAX_PEG_EVENTS);
+    }
+}
+
+/// The semantic notifications for the transactions that reached a new stage (the stages are stored in the DB)
+async fn peg_semantic_notifs(
+    db: &Db,
+    peg: &mut PegData,
+    status: &api::PegStatus,
+) -> Vec<api::Notif> {
+    let mut notifs = Vec::new();
+    for tx in status.list.iter() {
+        let key = (tx.tx_hash, tx.vout);
+        let last = peg.notif_stages.get(&key).copied();
+        if let Some((stage, tx_notifs)) = peg_notifs::transitions(status.order_id, last, tx) {
+            db.set_peg_notif_stage(&models::PegNotifStage {
+                order_id: Text(status.order_id),
+                tx_hash: Text(tx.tx_hash),
+                vout: tx.vout.into(),
+                stage: Json(stage),
+            })
+            .await;
+            peg.notif_stages.insert(key, stage);
+            tracing::debug!(
+                order_id = %status.order_id,
+                tx_hash = %tx.tx_hash,
+                vout = tx.vout,
+                stage = ?stage,
+                "peg transaction stage changed"
+            );
+            notifs.extend(tx_notifs);
+        }
+    }
+    notifs
+}
+
+/// Marks the peg complete once all its transactions are done (only once, new payments do not reset it)
+async fn check_peg_complete(
+    db: &Db,
+    peg: &mut PegData,
+    status: &api::PegStatus,
+) -> Option<api::Notif> {
+    let all_done = !status.list.is_empty()
+        && status
+            .list
+            .iter()
+            .all(|tx| tx.tx_state == api::PegTxState::Done);
+    if peg.completed_at.is_some() || !all_done {
+        return None;
+    }
+
+    let completed_at = TimestampMs::now().millis() as i64;
+    db.set_peg_completed(status.order_id, completed_at).await;
+    peg.completed_at = Some(completed_at);
+
+    let amount_received = status
+        .list
+        .iter()
+        .filter_map(|tx| tx.payout_amount)
+        .sum::<f64>();
+    tracing::info!(order_id = %status.order_id, amount_received, "peg completed");
+
+    Some(api::Notif::PegComplete(api::PegCompleteNotif {
+        order_id: status.order_id,
+        amount_received,
+    }))
+}
+
+async fn process_peg_status(data: &mut Data, status: sideswap_api::PegStatus) {
+    tracing::debug!(
         "new peg status: {}",
         serde_json::to_string(&status).expect("must not fail")
     );
 
-    let status = convert_peg_status(status);
+    if data.pegs.contains_key(&status.order_id) {
+        let now = TimestampMs::now().millis() as i64;
+        for tx in status.list.iter() {
+            data.db
+                .set_peg_tx(&models::PegTx {
+                    order_id: Text(status.order_id),
+                    tx_hash: Text(tx.tx_hash),
+                    vout: tx.vout.into(),
+                    amount: tx.amount,
+                    status: tx.status.clone(),
+                    payout_txid: tx.payout_txid.map(Text),
+                    payout_amount: tx.payout,
+                    updated_at: now,
+                })
+                .await;
+        }
+    }
+
+    let reference = data
+        .payment_refs
+        .get(api::ReferenceKind::Peg, &status.order_id.to_string())
+        .map(str::to_owned);
+    let status = convert_peg_status(status, reference);
 
     if let Some(peg) = data.pegs.get_mut(&status.order_id) {
-        log::debug!("send peg status update to connected clients");
+        record_peg_events(&data.db, peg, &status).await;
+        let mut semantic_notifs = peg_semantic_notifs(&data.db, peg, &status).await;
+        semantic_notifs.extend(check_peg_complete(&data.db, peg, &status).await);
+
+        tracing::debug!("send peg status update to connected clients");
         peg.status = Some(status.clone());
         send_notifs(
             data,
             &api::Notif::PegStatus(api::PegStatusNotif { peg: status }),
         );
+        for notif in semantic_notifs {
+            send_notifs(data, &notif);
+        }
     } else {
-        log::debug!(
-            "ignore unexpected peg status update, order_id: {}",
-            status.order_id
+        tracing::debug!(
+            order_id = %status.order_id,
+            "ignore unexpected peg status update"
         );
     }
 }
 
-fn process_market_notif(data: &mut Data, notif: mkt::Notification) {
+async fn process_market_notif(data: &mut Data, notif: mkt::Notification) {
     match notif {
         mkt::Notification::MarketAdded(notif) => {
+            if let Some(market) = convert_market(&data.ticker_loader, &notif.market) {
+                send_notifs(
+                    data,
+                    &api::Notif::MarketAdded(api::MarketAddedNotif { market }),
+                );
+            }
             data.markets.push(notif.market);
+            data.markets_updated_at = Some(Instant::now());
         }
 
         mkt::Notification::MarketRemoved(notif) => {
+            let removed = data
+                .markets
+                .iter()
+                .find(|market| market.asset_pair == notif.asset_pair)
+                .and_then(|market| convert_market(&data.ticker_loader, market));
+            if let Some(market) = removed {
+                send_notifs(
+                    data,
+                    &api::Notif::MarketRemoved(api::MarketRemovedNotif {
+                        base: market.base,
+                        quote: market.quote,
+                    }),
+                );
+            }
             data.markets
                 .retain(|market| market.asset_pair != notif.asset_pair);
+            data.market_prices.remove(&notif.asset_pair);
+            data.markets_updated_at = Some(Instant::now());
+        }
+
+        mkt::Notification::MarketPrice(notif) => {
+            let price = MarketPrice {
+                ind_price: notif.ind_price.map(NormalFloat::value),
+                last_price: notif.last_price.map(NormalFloat::value),
+                updated_at: Instant::now(),
+            };
+            if let Some(price_notif) = convert_market_price(data, &notif.asset_pair, &price) {
+                let price_notif = EncodedNotif::new(api::Notif::MarketPrice(price_notif));
+                let subscribed = data.price_subs.get(&notif.asset_pair);
+                for client_id in subscribed.into_iter().flatten() {
+                    if let Some(client) = data.clients.get(client_id) {
+                        client.notif_sender.send(price_notif.clone());
+                    }
+                }
+            }
+            data.market_prices.insert(notif.asset_pair, price);
+        }
+
+        mkt::Notification::Quote(notif) => {
+            let ws_generation = data.quote_subs.get(&notif.quote_sub_id).copied();
+            if ws_generation != Some(data.ws_generation) {
+                tracing::debug!(
+                    quote_sub_id = notif.quote_sub_id.value(),
+                    "discard stale quote notification"
+                );
+                data.stale_quote_notifs += 1;
+            }
+            data.discarded_quote_notifs += 1;
+        }
+
+        mkt::Notification::OwnOrderCreated(notif) => {
+            data.own_orders.insert(notif.order.order_id, notif.order);
         }
 
-        mkt::Notification::UtxoAdded(_)
-        | mkt::Notification::UtxoRemoved(_)
-        | mkt::Notification::OwnOrderCreated(_)
-        | mkt::Notification::OwnOrderRemoved(_)
-        | mkt::Notification::PublicOrderCreated(_)
+        mkt::Notification::OwnOrderRemoved(notif) => {
+            tracing::debug!(order_id = notif.order_id.value(), "own order removed");
+            remove_own_order(data, notif.order_id).await;
+        }
+
+        mkt::Notification::MakerSign(notif) => {
+            process_maker_sign(data, notif).await;
+        }
+
+        mkt::Notification::UtxoAdded(notif) => {
+            if let Some(market_utxos) = data.market_utxos.as_mut() {
+                market_utxos.insert(notif.utxo);
+            }
+        }
+
+        mkt::Notification::UtxoRemoved(notif) => {
+            if let Some(market_utxos) = data.market_utxos.as_mut() {
+                market_utxos.remove(&notif.utxo);
+            }
+        }
+
+        mkt::Notification::PublicOrderCreated(_)
         | mkt::Notification::PublicOrderRemoved(_)
-        | mkt::Notification::Quote(_)
-        | mkt::Notification::MakerSign(_)
-        | mkt::Notification::MarketPrice(_)
         | mkt::Notification::ChartUpdate(_)
         | mkt::Notification::HistoryUpdated(_)
         | mkt::Notification::NewEvent(_)
@@ -1015,9 +6040,20 @@ fn process_market_notif(data: &mut Data, notif: mkt::Notification) {
 }
 
 async fn process_ws_event(data: &mut Data, event: WrappedResponse) {
+    if let WrappedResponse::Response(ResponseMessage::Response(Some(request_id), res)) = &event {
+        process_broadcast_retry_resp(data, request_id, res);
+        process_peg_status_resp(data, request_id, res);
+    }
+
     match event {
         WrappedResponse::Connected => {
             process_ws_connected(data);
+
+            if data.settings.maker_orders {
+                if let Err(err) = market_login(data).await {
+                    tracing::error!("market login failed: {err}");
+                }
+            }
         }
 
         WrappedResponse::Disconnected => {
@@ -1035,7 +6071,14 @@ async fn process_ws_event(data: &mut Data, event: WrappedResponse) {
             _,
             Ok(sideswap_api::Response::PegStatus(status)),
         )) => {
-            process_peg_status(data, status);
+            process_peg_status(data, status).await;
+        }
+
+        WrappedResponse::Response(ResponseMessage::Response(
+            _,
+            Ok(sideswap_api::Response::Assets(resp)),
+        )) => {
+            process_assets(data, resp.assets);
         }
 
         WrappedResponse::Response(ResponseMessage::Response(_req_id, _res)) => {}
@@ -1043,38 +6086,63 @@ async fn process_ws_event(data: &mut Data, event: WrappedResponse) {
         WrappedResponse::Response(ResponseMessage::Notification(
             sideswap_api::Notification::PegStatus(status),
         )) => {
-            process_peg_status(data, status);
+            process_peg_status(data, status).await;
         }
 
         WrappedResponse::Response(ResponseMessage::Notification(
             sideswap_api::Notification::Market(notif),
         )) => {
-            process_market_notif(data, notif);
+            process_market_notif(data, notif).await;
         }
 
         WrappedResponse::Response(ResponseMessage::Notification(_)) => {}
     }
 }
 
-async fn reload_balances(data: &mut Data) {
-    let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
-    data.wallet_command_sender
-        .send(sideswap_lwk::Command::GetUtxos {
+/// The `Balances` key of an asset that is not whitelisted (the first 8 characters of the asset ID)
+fn unknown_asset_key(asset_id: &elements::AssetId) -> api::Ticker {
+    let asset_id = asset_id.to_string();
+    api::Ticker::from_str(&asset_id[..8]).expect("must not fail")
+}
+
+async fn reload_balances(data: &mut Data, wallet_name: &str) {
+    let res = wallet_request(data, wallet_name, |res_sender| {
+        sideswap_lwk::Command::GetUtxos {
             req: sideswap_lwk::GetUtxosReq {},
-            res_sender: res_sender.into(),
-        })
-        .expect("must not fail");
-    let resp = res_receiver
-        .await
-        .expect("must not fail")
-        .expect("must not fail");
+            res_sender,
+        }
+    })
+    .await;
+    let resp = match res {
+        Ok(resp) => resp,
+        Err(err) => {
+            tracing::error!(wallet = wallet_name, "loading wallet UTXOs failed: {err}");
+            return;
+        }
+    };
+
+    let wallet = data.wallets.get_mut(wallet_name).expect("must be known");
+    let change_used = wallet.quote_change_address.as_ref().is_some_and(|address| {
+        let script_pubkey = address.script_pubkey();
+        resp.utxos
+            .iter()
+            .any(|utxo| utxo.script_pubkey == script_pubkey)
+    });
+    if change_used {
+        wallet.quote_change_address = None;
+    }
 
     type BalancesSat = BTreeMap<elements::AssetId, u64>;
     let mut confirmed = BalancesSat::new();
+    let mut unconfirmed = BalancesSat::new();
     let mut balances = BalancesSat::new();
+    let mut confirmed_utxos = BTreeSet::new();
     for utxo in resp.utxos {
         if utxo.height.is_some() {
             *confirmed.entry(utxo.unblinded.asset).or_default() += utxo.unblinded.value;
+            confirmed_utxos.insert(utxo.outpoint);
+        } else {
+            *unconfirmed.entry(utxo.unblinded.asset).or_default() += utxo.unblinded.value;
         }
         *balances.entry(utxo.unblinded.asset).or_default() += utxo.unblinded.value;
     }
@@ -1082,44 +6150,206 @@ async fn reload_balances(data: &mut Data) {
     let convert_balances = |balances: &BalancesSat| -> api::Balances {
         balances
             .iter()
-            .filter_map(|(asset_id, amount)| {
-                let ticker = data.ticker_loader.ticker(asset_id)?;
-                let precision = data.ticker_loader.precision(ticker);
-                let amount = asset_float_amount_(*amount, precision);
-                Some((ticker, amount))
-            })
+            .map(
+                |(asset_id, amount)| match data.ticker_loader.ticker(asset_id) {
+                    Some(ticker) => {
+                        let precision = data.ticker_loader.precision(ticker);
+                        (ticker, asset_float_amount_(*amount, precision))
+                    }
+                    None => (unknown_asset_key(asset_id), *amount as f64),
+                },
+            )
             .collect()
     };
 
     let new_balances = api::BalancesNotif {
+        wallet: wallet_name.to_owned(),
         balances: convert_balances(&balances),
         confirmed: convert_balances(&confirmed),
+        unconfirmed: convert_balances(&unconfirmed),
     };
 
-    if data.last_balances.as_ref() != Some(&new_balances) {
-        log::debug!("wallet balances updated: {new_balances:?}");
-        send_notifs(data, &api::Notif::Balances(new_balances.clone()));
-        data.last_balances = Some(new_balances);
+    let wallet = data.wallets.get_mut(wallet_name).expect("must be known");
+    wallet.balances = Some(balances);
+    wallet.confirmed_balances = Some(confirmed);
+    wallet.confirmed_utxos = confirmed_utxos;
+
+    if wallet.last_balances.as_ref() != Some(&new_balances) {
+        tracing::debug!("wallet balances updated: {new_balances:?}");
+        // The initial balances are not a change
+        let initial = wallet.last_balances.is_none();
+        wallet.last_balances = Some(new_balances.clone());
+        let notif = api::Notif::Balances(new_balances.clone());
+        if initial {
+            send_client_notifs(data, &notif);
+        } else {
+            send_notifs(data, &notif);
+        }
+        // The metrics report the default wallet only
+        if wallet_name == DEFAULT_WALLET {
+            data.metrics.set_balances(&new_balances.balances);
+        }
     }
 }
 
-async fn process_wallet_event(data: &mut Data, event: sideswap_lwk::Event) {
+async fn process_wallet_event(data: &mut Data, wallet_name: &str, event: sideswap_lwk::Event) {
+    data.metrics.wallet_event(Instant::now());
+
     match event {
         sideswap_lwk::Event::Utxos { utxo_data } => {
-            data.utxo_data = Some(utxo_data);
+            let wallet = data.wallets.get_mut(wallet_name).expect("must be known");
+            wallet.utxo_data = Some(utxo_data);
+
+            // The maker orders are funded from the default wallet
+            if wallet_name == DEFAULT_WALLET {
+                if let Err(err) = sync_market_utxos(data).await {
+                    tracing::error!("market UTXOs sync failed: {err}");
+                }
+            }
         }
 
         sideswap_lwk::Event::Updated => {
-            reload_balances(data).await;
+            reload_balances(data, wallet_name).await;
+
+            update_tx_statuses(data).await;
+
+            if data.wallets[wallet_name].gap_limit_warning.is_some() {
+                match get_new_address(data, wallet_name, false, None).await {
+                    Ok(new_address) => {
+                        update_gap_limit_warning(data, wallet_name, new_address.index);
+                    }
+                    Err(err) => tracing::error!(
+                        wallet = wallet_name,
+                        "getting first unused address failed: {err}"
+                    ),
+                }
+            }
+        }
+    }
+}
+
+async fn process_timer(data: &mut Data) {
+    if data
+        .clock_check_at
+        .is_some_and(|clock_check_at| Instant::now() >= clock_check_at)
+    {
+        start_clock_check(data);
+    }
+
+    let locked = data
+        .signing_lock
+        .as_mut()
+        .map(|signing_lock| signing_lock.check_timeout(Instant::now()))
+        .unwrap_or_default();
+    if locked {
+        audit(data, "signing locked due to inactivity".to_owned()).await;
+        send_lock_status(data);
+    }
+
+    if data
+        .balance_snapshot_at
+        .is_some_and(|balance_snapshot_at| Instant::now() >= balance_snapshot_at)
+    {
+        record_balance_snapshot(data, TimestampMs::now()).await;
+        data.balance_snapshot_at = next_balance_snapshot_at(&data.settings);
+    }
+
+    expire_approvals(data, TimestampMs::now()).await;
+
+    expire_created_txs(data, TimestampMs::now()).await;
+}
+
+fn next_balance_snapshot_at(settings: &Settings) -> Option<Instant> {
+    settings
+        .balance_history
+        .as_ref()
+        .map(|config| Instant::now() + config.interval())
+}
+
+async fn record_balance_snapshot(data: &Data, created_at: TimestampMs) {
+    let Some(config) = &data.settings.balance_history else {
+        return;
+    };
+    let created_at = created_at.millis() as i64;
+    let mut rows = Vec::new();
+    for (wallet_name, wallet) in data.wallets.iter() {
+        // Do not record misleading zero balances
+        let Some(balances) = &wallet.balances else {
+            tracing::debug!(
+                wallet = wallet_name,
+                "wallet is not synced yet, skip balance snapshot"
+            );
+            continue;
+        };
+        rows.extend(
+            balances
+                .iter()
+                .map(|(asset_id, amount)| models::BalanceHistory {
+                    wallet: wallet_name.clone(),
+                    created_at,
+                    asset_id: Text(*asset_id),
+                    amount: *amount as i64,
+                }),
+        );
+        // Every snapshot must have at least one row
+        if !balances.contains_key(&data.policy_asset) {
+            rows.push(models::BalanceHistory {
+                wallet: wallet_name.clone(),
+                created_at,
+                asset_id: Text(data.policy_asset),
+                amount: 0,
+            });
         }
     }
+    let delete_before = config
+        .retention()
+        .map(|retention| created_at - retention.as_millis() as i64);
+
+    data.db.add_balance_snapshot(&rows, delete_before).await;
+}
+
+/// Drops the stored rows with addresses of another network (the DB was used with another env before the env was stored)
+fn skip_foreign_addresses<T>(
+    rows: Vec<T>,
+    network: Network,
+    kind: &str,
+    foreign_addresses: &mut u64,
+    address: impl Fn(&T) -> &elements::Address,
+) -> Vec<T> {
+    rows.into_iter()
+        .filter(|row| {
+            let address = address(row);
+            let own_network = address.params == network.d().elements_params;
+            if !own_network {
+                tracing::error!(
+                    "skip the stored {kind} {address}, not a {} address",
+                    network.d().name
+                );
+                *foreign_addresses += 1;
+            }
+            own_network
+        })
+        .collect()
+}
+
+/// The wallets share the DB, the default wallet uses the key from before several wallets were supported
+fn wallet_id_key(wallet_name: &str) -> String {
+    if wallet_name == DEFAULT_WALLET {
+        "wallet_id".to_owned()
+    } else {
+        format!("wallet_id.{wallet_name}")
+    }
 }
 
-pub async fn check_wallet_id(wallet: &sideswap_lwk::Wallet, db: &Db) {
+pub async fn check_wallet_id(wallet: &sideswap_lwk::Wallet, db: &Db, wallet_name: &str) {
     let expected_wallet_id = wallet.wallet_id();
-    log::debug!("check wallet_id, expected: {expected_wallet_id}");
+    tracing::debug!(
+        wallet = wallet_name,
+        "check wallet_id, expected: {expected_wallet_id}"
+    );
 
-    let wallet_id_key = "wallet_id";
+    let wallet_id_key = wallet_id_key(wallet_name);
+    let wallet_id_key = wallet_id_key.as_str();
 
     let stored_wallet_id = db.get_setting::<String>(wallet_id_key).await;
 
@@ -1127,29 +6357,73 @@ pub async fn check_wallet_id(wallet: &sideswap_lwk::Wallet, db: &Db) {
         Some(stored_wallet_id) => {
             assert!(
                 stored_wallet_id == expected_wallet_id,
-                "The working directory has already been used with a different mnemonic. Please revert to the old mnemonic and script variant or use a new working directory. Old wallet id: {}, new wallet id: {}",
+                "The working directory has already been used with a different mnemonic for wallet {}. Please revert to the old mnemonic and script variant or use a new working directory. Old wallet id: {}, new wallet id: {}",
+                wallet_name,
                 stored_wallet_id,
                 expected_wallet_id,
 
             );
-            log::debug!("valid wallet_id stored in the DB");
+            tracing::debug!("valid wallet_id stored in the DB");
         }
         None => {
-            log::debug!("no wallet_id stored in the DB, saving it");
+            tracing::debug!("no wallet_id stored in the DB, saving it");
             db.set_setting::<String>(wallet_id_key, &expected_wallet_id)
                 .await
         }
     }
 }
 
+async fn load_pegs(db: &Db) -> BTreeMap<OrderId, PegData> {
+    let mut peg_events = BTreeMap::<OrderId, Vec<models::PegEvent>>::new();
+    for event in db.load_peg_events().await {
+        peg_events.entry(event.order_id.0).or_default().push(event);
+    }
+
+    let mut notif_stages = BTreeMap::<OrderId, BTreeMap<_, _>>::new();
+    for stage in db.load_peg_notif_stages().await {
+        notif_stages
+            .entry(stage.order_id.0)
+            .or_default()
+            .insert((stage.tx_hash.0, stage.vout as u32), stage.stage.0);
+    }
+
+    db.load_pegs()
+        .await
+        .iter()
+        .map(|peg| {
+            let timeline = peg_events.remove(&peg.order_id.0).unwrap_or_default();
+            (
+                peg.order_id.0,
+                PegData {
+                    status: None,
+                    timeline,
+                    notif_stages: notif_stages.remove(&peg.order_id.0).unwrap_or_default(),
+                    created_by: peg.created_by.clone(),
+                    completed_at: peg.completed_at,
+                    status_request_id: None,
+                    server_error: None,
+                },
+            )
+        })
+        .collect()
+}
+
 pub async fn run(
     settings: Settings,
+    config_path: String,
     mut command_receiver: UnboundedReceiver<Command>,
-    ticker_loader: Arc<TickerLoader>,
+    gdk_registry: GdkRegistryCache,
     db: Db,
+    drain_sender: watch::Sender<bool>,
+    tor_status: watch::Receiver<tor::Status>,
 ) {
     let server_url = settings.env.base_server_ws_url();
 
+    let metrics = Arc::new(Metrics::default());
+    if let Some(listen_on) = settings.metrics_listen_on {
+        metrics::start(listen_on, Arc::clone(&metrics));
+    }
+
     let (req_sender, req_receiver) = unbounded_channel::<WrappedRequest>();
     let (resp_sender, resp_receiver) = unbounded_channel::<WrappedResponse>();
     tokio::spawn(sideswap_common::ws::auto::run(
@@ -1157,27 +6431,72 @@ pub async fn run(
         req_receiver,
         resp_sender,
     ));
-    let ws = WsReqSender::new(req_sender, resp_receiver);
+    let mut ws = WsReqSender::new(req_sender, resp_receiver);
+    ws.set_size_limits(settings.upstream_size_limits.unwrap_or_default());
 
     let policy_asset = settings.env.nd().policy_asset;
 
     let network = settings.env.d().network;
 
-    let wallet = sideswap_lwk::Wallet::new(sideswap_lwk::Params {
-        network,
-        work_dir: settings.work_dir.clone(),
-        mnemonic: settings.mnemonic.clone(),
-        script_variant: settings.script_variant,
-    });
-    check_wallet_id(&wallet, &db).await;
-    let (wallet_command_sender, mut wallet_event_receiver) = wallet.start();
+    let mut ticker_loader =
+        TickerLoader::from_registry(&gdk_registry, settings.whitelisted_assets.as_ref(), network)
+            .expect("must not fail");
+    if let Some(ticker_aliases) = &settings.ticker_aliases {
+        ticker_loader
+            .add_aliases(ticker_aliases)
+            .expect("invalid ticker_aliases");
+    }
+    let ticker_loader = Arc::new(ticker_loader);
+
+    let mut wallet_params = vec![(
+        DEFAULT_WALLET.to_owned(),
+        sideswap_lwk::Params {
+            network,
+            work_dir: settings.work_dir.clone(),
+            mnemonic: settings.wallet_mnemonic().expect("can't load the mnemonic"),
+            script_variant: settings.script_variant,
+        },
+    )];
+    for config in settings.wallets.iter() {
+        let mnemonic = config
+            .mnemonic()
+            .unwrap_or_else(|err| panic!("can't load the {} wallet mnemonic: {err}", config.name));
+        wallet_params.push((
+            config.name.clone(),
+            sideswap_lwk::Params {
+                network,
+                work_dir: config.work_dir.clone(),
+                mnemonic,
+                script_variant: config.script_variant,
+            },
+        ));
+    }
 
-    let pegs = db
-        .load_pegs()
-        .await
-        .iter()
-        .map(|peg| (peg.order_id.0, PegData { status: None }))
-        .collect();
+    // The events of all wallets are processed in the worker loop, tagged with the wallet name
+    let (wallet_event_sender, mut wallet_event_receiver) =
+        unbounded_channel::<(String, sideswap_lwk::Event)>();
+    let mut wallets = BTreeMap::new();
+    for (wallet_name, params) in wallet_params {
+        let wallet = sideswap_lwk::Wallet::new(params);
+        check_wallet_id(&wallet, &db, &wallet_name).await;
+        let (command_sender, mut event_receiver) = wallet.start();
+        let event_sender = wallet_event_sender.clone();
+        let wallet_name_copy = wallet_name.clone();
+        tokio::spawn(async move {
+            while let Some(event) = event_receiver.recv().await {
+                if event_sender
+                    .send((wallet_name_copy.clone(), event))
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        wallets.insert(wallet_name, WalletData::new(command_sender));
+    }
+    drop(wallet_event_sender);
+
+    let pegs = load_pegs(&db).await;
 
     let monitored_txs = db
         .load_monitored_txs()
@@ -1186,38 +6505,151 @@ pub async fn run(
         .map(|monitored_tx| (monitored_tx.txid.0, monitored_tx))
         .collect::<BTreeMap<_, _>>();
 
-    let addresses = db
-        .load_addresses()
+    let mut foreign_addresses = 0;
+
+    let addresses = skip_foreign_addresses(
+        db.load_addresses().await,
+        network,
+        "wallet address",
+        &mut foreign_addresses,
+        |addr| &addr.address.0,
+    );
+    for addr in addresses {
+        match wallets.get_mut(&addr.wallet) {
+            Some(wallet) => {
+                wallet.addresses.insert(addr.ind as u32, addr);
+            }
+            None => tracing::warn!(
+                wallet = addr.wallet,
+                "skip address {} of a wallet that is not configured",
+                addr.address.0
+            ),
+        }
+    }
+
+    let allowed_addresses = skip_foreign_addresses(
+        db.load_allowed_addresses().await,
+        network,
+        "allowed address",
+        &mut foreign_addresses,
+        |allowed| &allowed.address.0,
+    )
+    .into_iter()
+    .map(|allowed| (allowed.address.0.to_string(), allowed))
+    .collect::<BTreeMap<_, _>>();
+
+    let payment_refs = PaymentRefs::new(db.load_payment_references().await);
+
+    let pending_approvals = db
+        .load_pending_approvals()
+        .await
+        .into_iter()
+        .map(|approval| (approval.approval_id.clone(), approval))
+        .collect::<BTreeMap<_, _>>();
+
+    let created_txs = db
+        .load_created_txs()
         .await
         .into_iter()
-        .map(|addr| (addr.ind as u32, addr))
+        .map(|row| (row.txid.0, CreatedTx::from_row(row)))
         .collect::<BTreeMap<_, _>>();
 
+    let maker_orders = skip_foreign_addresses(
+        db.load_maker_orders().await,
+        network,
+        "maker order address",
+        &mut foreign_addresses,
+        |row| &row.receive_address.0,
+    )
+    .into_iter()
+    .map(|row| (mkt::OrdId::new(row.order_id as u64), row))
+    .collect::<BTreeMap<_, _>>();
+
+    let quote_coalescing = settings.quote_coalescing.as_ref().map(QuoteCoalescing::new);
+
+    let balance_snapshot_at = next_balance_snapshot_at(&settings);
+
+    let signing_lock = settings
+        .auto_lock
+        .as_ref()
+        .map(|config| SigningLock::new(config, Instant::now()));
+
+    let clock_skew = ClockSkew::new(Duration::from_secs(
+        settings
+            .clock_check
+            .as_ref()
+            .map(|config| config.max_skew_seconds)
+            .unwrap_or_default(),
+    ));
+    let clock_check_at = settings.clock_check.as_ref().map(|_| Instant::now());
+    let (clock_sample_sender, mut clock_sample_receiver) = unbounded_channel::<ClockSample>();
+    let (unlock_result_sender, mut unlock_result_receiver) = unbounded_channel::<UnlockResult>();
+
+    let webhooks = Webhooks::new(settings.webhooks.as_ref());
+
     let mut data = Data {
-        _settings: settings,
+        settings,
         policy_asset,
         ticker_loader,
+        gdk_registry: Some(gdk_registry),
         db,
         ws,
-        wallet_command_sender,
+        wallets,
         markets: Vec::new(),
+        markets_updated_at: None,
+        market_prices: BTreeMap::new(),
+        price_subs: BTreeMap::new(),
         clients: BTreeMap::new(),
-        last_balances: None,
-        utxo_data: None,
+        balance_snapshot_at,
         pegs,
         monitored_txs,
+        tx_statuses: BTreeMap::new(),
         quotes: BTreeMap::new(),
-        created_txs: BTreeMap::new(),
-        addresses,
+        created_txs,
+        pending_broadcasts: BTreeMap::new(),
+        signing_lock,
+        clock_skew,
+        clock_check_at,
+        clock_sample_sender: clock_sample_sender.into(),
+        unlock_result_sender: unlock_result_sender.into(),
+        ws_generation: 0,
+        ws_connected: false,
+        quote_subs: BTreeMap::new(),
+        last_quote_sub: None,
+        stale_quote_notifs: 0,
+        discarded_quote_notifs: 0,
+        quote_timeouts: 0,
+        foreign_addresses,
+        asset_flags: BTreeMap::new(),
+        gaid_addresses: HashSet::new(),
+        drain_deadline: None,
+        drain_sender,
+        allowed_addresses,
+        pending_approvals,
+        payment_refs,
+        quote_coalescing,
+        own_orders: BTreeMap::new(),
+        maker_orders,
+        market_utxos: None,
+        config_path: Some(config_path),
+        tor_status,
+        metrics,
+        webhooks,
     };
 
     let term_signal = sideswap_dealer::signals::TermSignal::new();
 
+    let drain_signal = DrainSignal::new();
+
+    let reload_signal = config_reload::ReloadSignal::new();
+
+    let mut timer = tokio::time::interval(Duration::from_secs(1));
+
     loop {
         tokio::select! {
             event = wallet_event_receiver.recv() => {
-                let event = event.expect("must be open");
-                process_wallet_event(&mut data, event).await;
+                let (wallet_name, event) = event.expect("must be open");
+                process_wallet_event(&mut data, &wallet_name, event).await;
             },
 
             command = command_receiver.recv() => {
@@ -1229,14 +6661,59 @@ pub async fn run(
                 process_ws_event(&mut data, event).await;
             },
 
+            sample = clock_sample_receiver.recv() => {
+                let sample = sample.expect("channel must be open");
+                process_clock_sample(&mut data, sample);
+            },
+
+            result = unlock_result_receiver.recv() => {
+                let result = result.expect("channel must be open");
+                finish_unlock(&mut data, result).await;
+            },
+
+            _ = timer.tick() => {
+                process_timer(&mut data).await;
+            },
+
             _ = term_signal.recv() => {
-                log::info!("terminate signal received");
+                tracing::info!("terminate signal received, shutting down");
+                shutdown(&mut data, &mut command_receiver).await;
                 break;
             },
+
+            _ = drain_signal.recv() => {
+                let grace_period = data
+                    .settings
+                    .drain_grace_seconds
+                    .map(Duration::from_secs)
+                    .unwrap_or(drain::DEFAULT_GRACE_PERIOD);
+                start_drain(&mut data, grace_period).await;
+            },
+
+            _ = reload_signal.recv() => {
+                if let Err(err) = reload_config(&mut data).await {
+                    tracing::error!("config reload failed: {err}");
+                }
+            },
         }
 
-        data.quotes.retain(|_quote_id, quote| quote.ttl_valid())
+        data.quotes.retain(|_quote_id, quote| quote.ttl_valid());
+
+        data.metrics
+            .set_counts(data.clients.len(), data.monitored_txs.len());
+
+        if drain_finished(&data, Instant::now()) {
+            tracing::info!("draining finished, exit");
+            data.clients.clear();
+            tokio::time::sleep(drain::FLUSH_PERIOD).await;
+            break;
+        }
     }
 
+    record_balance_snapshot(&data, TimestampMs::now()).await;
+
     data.db.close().await;
 }
+
+#[cfg(test)]
+mod tests;
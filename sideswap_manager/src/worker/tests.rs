@@ -0,0 +1,6826 @@
+use elements::hashes::Hash;
+use sideswap_common::network::Network;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Mutex,
+};
+use tokio::sync::mpsc::UnboundedSender;
+
+use super::*;
+
+const TEST_MNEMONIC: &str =
+    "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+/// The first external address of `TEST_MNEMONIC` (wpkh, Liquid Testnet)
+const TEST_WALLET_ADDRESS: &str = "tlq1qq2xvpcvfup5j8zscjq05u2wxxjcyewk7979f3mmz5l7uw5pqmx6xf5xy50hsn6vhkm5euwt72x878eq6zxx2z58hd7zrsg9qn";
+
+const TEST_MESSAGE: &str = "I control this address";
+
+/// Returned by the fake wallet for the L-BTC send-all recipient
+const TEST_DRAINED_AMOUNT: u64 = 123_456;
+
+/// The wallet tip height reported by the fake wallet
+const TEST_TIP_HEIGHT: u32 = 100;
+
+/// `TEST_MESSAGE` signed with `TEST_WALLET_ADDRESS`
+const TEST_MESSAGE_SIGNATURE: &str =
+    "J9Tg8TTJKVTbEG/lQO/Cjev+G88SpNie51qZ4kVBulW7eQyGcXuTmeB/27Zop0sq974Vj8a/9nMTi4fhKiLUBGs=";
+
+fn amp_ticker() -> DealerTicker {
+    "AMPT".parse().expect("must not fail")
+}
+
+fn amp_asset_id() -> AssetId {
+    AssetId::from_slice(&[7; 32]).expect("must not fail")
+}
+
+/// A test asset with precision 0 (whole units only)
+fn whole_ticker() -> DealerTicker {
+    "WHOLE".parse().expect("must not fail")
+}
+
+/// A test asset with precision 2
+fn cents_ticker() -> DealerTicker {
+    "CENTS".parse().expect("must not fail")
+}
+
+struct TestEnv {
+    data: Data,
+    ws_requests: UnboundedReceiver<WrappedRequest>,
+    ws_responses: UnboundedSender<WrappedResponse>,
+    wallet_commands: Option<mpsc::Receiver<sideswap_lwk::Command>>,
+    /// Returned by the fake wallet for `GetTxs`
+    wallet_txs: Arc<Mutex<Vec<sideswap_lwk::WalletTx>>>,
+    /// Returned by the fake wallet for `GetUtxos`
+    wallet_utxos: Arc<Mutex<Vec<sideswap_lwk::WalletTxOut>>>,
+    /// The number of change addresses issued by the fake wallet
+    change_addresses: Arc<AtomicU32>,
+    unlock_results: UnboundedReceiver<UnlockResult>,
+}
+
+fn test_settings() -> Settings {
+    serde_json::from_value(serde_json::json!({
+        "env": "Testnet",
+        "work_dir": "/var/lib/sideswap_manager",
+        "mnemonic": TEST_MNEMONIC,
+        "script_variant": "wpkh",
+        "ws_server": {
+            "listen_on": "127.0.0.1:3102",
+        },
+    }))
+    .expect("must not fail")
+}
+
+fn test_ticker_loader() -> TickerLoader {
+    let network = Network::LiquidTestnet.d();
+    TickerLoader::from_assets([
+        (
+            network.policy_asset,
+            DealerTicker::LBTC,
+            AssetPrecision::BITCOIN_PRECISION,
+        ),
+        (
+            network.known_assets.USDt,
+            DealerTicker::USDT,
+            AssetPrecision::BITCOIN_PRECISION,
+        ),
+        (amp_asset_id(), amp_ticker(), AssetPrecision::ZERO),
+        (
+            AssetId::from_slice(&[8; 32]).expect("must not fail"),
+            whole_ticker(),
+            AssetPrecision::ZERO,
+        ),
+        (
+            AssetId::from_slice(&[9; 32]).expect("must not fail"),
+            cents_ticker(),
+            AssetPrecision::TWO,
+        ),
+    ])
+}
+
+fn test_address(index: u32) -> elements::Address {
+    let mut secret_key = [0; 32];
+    secret_key[28..].copy_from_slice(&(index + 1).to_be_bytes());
+    let secret_key =
+        elements::secp256k1_zkp::SecretKey::from_slice(&secret_key).expect("must not fail");
+    let public_key = secret_key.public_key(elements::secp256k1_zkp::SECP256K1);
+    let pubkey = elements::bitcoin::PublicKey::new(public_key);
+    elements::Address::p2wpkh(
+        &pubkey,
+        Some(public_key),
+        &elements::AddressParams::LIQUID_TESTNET,
+    )
+}
+
+fn test_utxo_data(asset: AssetId, value: u64) -> UtxoData {
+    test_utxos(asset, value, 1)
+}
+
+/// `count` UTXOs with the same asset and value
+fn test_utxos(asset: AssetId, value: u64, count: u32) -> UtxoData {
+    test_mixed_utxos(&vec![(asset, value); count as usize])
+}
+
+/// One UTXO for every asset and value (the vouts are the indices)
+fn test_mixed_utxos(utxos: &[(AssetId, u64)]) -> UtxoData {
+    let mut utxo_data = UtxoData::new(sideswap_dealer::utxo_data::Params {
+        confifential_only: false,
+    });
+    let secret_key =
+        elements::secp256k1_zkp::SecretKey::from_slice(&[1; 32]).expect("must not fail");
+    utxo_data.reset(
+        (0u32..)
+            .zip(utxos.iter().copied())
+            .map(
+                |(vout, (asset, value))| sideswap_dealer::utxo_data::UtxoWithKey {
+                    utxo: sideswap_api::Utxo {
+                        txid: elements::Txid::from_byte_array([1; 32]),
+                        vout,
+                        asset,
+                        asset_bf: elements::confidential::AssetBlindingFactor::zero(),
+                        value,
+                        value_bf: elements::confidential::ValueBlindingFactor::zero(),
+                        redeem_script: None,
+                    },
+                    priv_key: elements::bitcoin::PrivateKey::new(
+                        secret_key,
+                        elements::bitcoin::Network::Testnet,
+                    ),
+                },
+            )
+            .collect(),
+    );
+    utxo_data
+}
+
+fn usdt_market() -> mkt::MarketInfo {
+    let network = Network::LiquidTestnet.d();
+    mkt::MarketInfo {
+        asset_pair: mkt::AssetPair {
+            base: network.policy_asset,
+            quote: network.known_assets.USDt,
+        },
+        fee_asset: AssetType::Quote,
+        type_: sideswap_api::MarketType::Stablecoin,
+    }
+}
+
+fn market_resp(resp: mkt::Response) -> WrappedResponse {
+    WrappedResponse::Response(ResponseMessage::Response(
+        None,
+        Ok(sideswap_api::Response::Market(resp)),
+    ))
+}
+
+fn market_notif(notif: mkt::Notification) -> WrappedResponse {
+    WrappedResponse::Response(ResponseMessage::Notification(
+        sideswap_api::Notification::Market(notif),
+    ))
+}
+
+impl TestEnv {
+    async fn new() -> TestEnv {
+        let settings = test_settings();
+        let policy_asset = settings.env.nd().policy_asset;
+
+        let (req_sender, ws_requests) = unbounded_channel::<WrappedRequest>();
+        let (ws_responses, resp_receiver) = unbounded_channel::<WrappedResponse>();
+        let ws = WsReqSender::new(req_sender, resp_receiver);
+
+        let (wallet_command_sender, wallet_commands) = mpsc::channel();
+        let (unlock_result_sender, unlock_results) = unbounded_channel();
+
+        let data = Data {
+            settings,
+            policy_asset,
+            ticker_loader: Arc::new(test_ticker_loader()),
+            gdk_registry: None,
+            db: Db::open_memory().await,
+            ws,
+            wallets: BTreeMap::from([(
+                DEFAULT_WALLET.to_owned(),
+                WalletData::new(wallet_command_sender),
+            )]),
+            markets: Vec::new(),
+            markets_updated_at: None,
+            market_prices: BTreeMap::new(),
+            price_subs: BTreeMap::new(),
+            clients: BTreeMap::new(),
+            balance_snapshot_at: None,
+            pegs: BTreeMap::new(),
+            monitored_txs: BTreeMap::new(),
+            tx_statuses: BTreeMap::new(),
+            quotes: BTreeMap::new(),
+            created_txs: BTreeMap::new(),
+            pending_broadcasts: BTreeMap::new(),
+            signing_lock: None,
+            clock_skew: ClockSkew::new(Duration::from_secs(30)),
+            clock_check_at: None,
+            clock_sample_sender: unbounded_channel().0.into(),
+            unlock_result_sender: unlock_result_sender.into(),
+            ws_generation: 0,
+            ws_connected: true,
+            quote_subs: BTreeMap::new(),
+            last_quote_sub: None,
+            stale_quote_notifs: 0,
+            discarded_quote_notifs: 0,
+            quote_timeouts: 0,
+            foreign_addresses: 0,
+            asset_flags: BTreeMap::new(),
+            gaid_addresses: HashSet::new(),
+            drain_deadline: None,
+            drain_sender: watch::channel(false).0,
+            allowed_addresses: BTreeMap::new(),
+            quote_coalescing: None,
+            pending_approvals: BTreeMap::new(),
+            payment_refs: PaymentRefs::new(Vec::new()),
+            own_orders: BTreeMap::new(),
+            maker_orders: BTreeMap::new(),
+            market_utxos: None,
+            config_path: None,
+            tor_status: watch::channel(tor::Status::default()).1,
+            metrics: Arc::new(Metrics::default()),
+            webhooks: Webhooks::new(None),
+        };
+
+        TestEnv {
+            data,
+            ws_requests,
+            ws_responses,
+            wallet_commands: Some(wallet_commands),
+            wallet_txs: Arc::new(Mutex::new(Vec::new())),
+            wallet_utxos: Arc::new(Mutex::new(Vec::new())),
+            change_addresses: Arc::new(AtomicU32::new(0)),
+            unlock_results,
+        }
+    }
+
+    /// Unlocks as the worker loop does (the signing lock must be locked)
+    async fn unlock(&mut self, password: &str) -> Result<api::Resp, Error> {
+        let req = api::Req::Unlock(api::UnlockReq {
+            password: password.to_owned(),
+        });
+        match start_request(&mut self.data, ClientId(1), req).await? {
+            Processed::Done(resp) => Ok(resp),
+            Processed::Pending(resp) => {
+                let result = self.unlock_results.recv().await.expect("must be open");
+                finish_unlock(&mut self.data, result).await;
+                resp.await
+            }
+        }
+    }
+
+    fn wallet(&mut self) -> &mut WalletData {
+        self.data
+            .wallets
+            .get_mut(DEFAULT_WALLET)
+            .expect("must be set")
+    }
+
+    /// Start a fake wallet, the returned value is the first unused address index (can be changed later)
+    fn start_wallet(&mut self, first_unused: u32) -> Arc<AtomicU32> {
+        let wallet_commands = self.wallet_commands.take().expect("must be set");
+        let first_unused = Arc::new(AtomicU32::new(first_unused));
+        let first_unused_copy = Arc::clone(&first_unused);
+        let wallet_txs = Arc::clone(&self.wallet_txs);
+        let wallet_utxos = Arc::clone(&self.wallet_utxos);
+        let change_addresses = Arc::clone(&self.change_addresses);
+        std::thread::spawn(move || {
+            while let Ok(command) = wallet_commands.recv() {
+                match command {
+                    sideswap_lwk::Command::NewAdddress { req, res_sender } if req.change => {
+                        // Every change address is new (as if the previous one was used already)
+                        let index = req
+                            .index
+                            .unwrap_or_else(|| change_addresses.fetch_add(1, Ordering::Relaxed));
+                        res_sender.send(Ok(sideswap_lwk::NewAddrResp {
+                            change: true,
+                            index,
+                            address: test_address(1000 + index),
+                        }));
+                    }
+                    sideswap_lwk::Command::NewAdddress { req, res_sender } => {
+                        let index = req
+                            .index
+                            .unwrap_or_else(|| first_unused_copy.load(Ordering::Relaxed));
+                        res_sender.send(Ok(sideswap_lwk::NewAddrResp {
+                            change: req.change,
+                            index,
+                            address: test_address(index),
+                        }));
+                    }
+                    sideswap_lwk::Command::GetTxs { req: _, res_sender } => {
+                        let txs = wallet_txs.lock().expect("must not fail").clone();
+                        res_sender.send(Ok(sideswap_lwk::GetTxsResp { txs }));
+                    }
+                    sideswap_lwk::Command::GetUtxos { req: _, res_sender } => {
+                        let utxos = wallet_utxos.lock().expect("must not fail").clone();
+                        res_sender.send(Ok(sideswap_lwk::GetUtxosResp {
+                            utxos,
+                            tip_height: TEST_TIP_HEIGHT,
+                        }));
+                    }
+                    sideswap_lwk::Command::BroadcastTx { tx: _, res_sender } => {
+                        if let Some(res_sender) = res_sender {
+                            res_sender.send(Ok(elements::Txid::from_byte_array([2; 32])));
+                        }
+                    }
+                    sideswap_lwk::Command::CreateTx { req, res_sender } => {
+                        // The fee as if the tx was 1000 vbytes long
+                        let output = req
+                            .fee_rate
+                            .map(|fee_rate| {
+                                elements::TxOut::new_fee(
+                                    (fee_rate * 1000.0) as u64,
+                                    test_settings().env.nd().policy_asset,
+                                )
+                            })
+                            .into_iter()
+                            .collect();
+                        // Only the manually selected UTXOs are spent
+                        let input = req
+                            .utxos
+                            .into_iter()
+                            .flatten()
+                            .map(|previous_output| elements::TxIn {
+                                previous_output,
+                                ..Default::default()
+                            })
+                            .collect();
+                        res_sender.send(Ok(sideswap_lwk::CreateTxResp {
+                            tx: elements::Transaction {
+                                version: 2,
+                                lock_time: elements::LockTime::ZERO,
+                                input,
+                                output,
+                            },
+                            drained_amount: req.drain_lbtc_to.map(|_| TEST_DRAINED_AMOUNT),
+                        }));
+                    }
+                    sideswap_lwk::Command::SignMessage { req, res_sender } => {
+                        let wallet = sideswap_lwk::Wallet::new(sideswap_lwk::Params {
+                            network: Network::LiquidTestnet,
+                            work_dir: std::env::temp_dir(),
+                            mnemonic: TEST_MNEMONIC.parse().expect("must not fail"),
+                            script_variant: test_settings().script_variant,
+                        });
+                        res_sender.send(wallet.sign_message(req));
+                    }
+                    _ => panic!("unexpected wallet command"),
+                }
+            }
+        });
+        first_unused
+    }
+
+    /// Simulate the upstream WS connection being established
+    async fn connect_upstream(&mut self) {
+        self.ws_responses
+            .send(WrappedResponse::Connected)
+            .expect("must not fail");
+        let event = self.data.ws.recv().await;
+        process_ws_event(&mut self.data, event).await;
+        while self.ws_requests.try_recv().is_ok() {}
+    }
+
+    async fn connect_client(&mut self, client_id: u64) -> UnboundedReceiver<SharedNotif> {
+        self.connect_named_client(client_id, None).await
+    }
+
+    async fn connect_named_client(
+        &mut self,
+        client_id: u64,
+        client_name: Option<&str>,
+    ) -> UnboundedReceiver<SharedNotif> {
+        self.connect_client_with(client_id, client_name, false)
+            .await
+    }
+
+    /// Connects a client logged in with its own client token (`[ws_server].client_tokens`)
+    async fn connect_token_client(
+        &mut self,
+        client_id: u64,
+        client_name: &str,
+    ) -> UnboundedReceiver<SharedNotif> {
+        self.connect_client_with(client_id, Some(client_name), true)
+            .await
+    }
+
+    async fn connect_client_with(
+        &mut self,
+        client_id: u64,
+        client_name: Option<&str>,
+        client_token: bool,
+    ) -> UnboundedReceiver<SharedNotif> {
+        let (notif_sender, notif_receiver) = unbounded_channel();
+        process_command(
+            &mut self.data,
+            Command::ClientConnected {
+                client_id: ClientId(client_id),
+                client_name: client_name.map(str::to_owned),
+                client_token,
+                notif_sender: notif_sender.into(),
+            },
+        )
+        .await;
+        notif_receiver
+    }
+}
+
+/// Also waits for the pending wallet requests (which `process_command` answers in the background)
+async fn process_request(
+    data: &mut Data,
+    client_id: ClientId,
+    req: api::Req,
+) -> Result<api::Resp, Error> {
+    match start_request(data, client_id, req).await? {
+        Processed::Done(resp) => Ok(resp),
+        Processed::Pending(resp) => resp.await,
+    }
+}
+
+/// Respond to the next StartQuotes request and then send `events`
+async fn reply_start_quotes(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+    quote_sub_id: QuoteSubId,
+    events: Vec<WrappedResponse>,
+) {
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::Market(mkt::Request::StartQuotes(_)),
+        )) = req
+        {
+            ws_responses
+                .send(WrappedResponse::Response(ResponseMessage::Response(
+                    Some(request_id),
+                    Ok(sideswap_api::Response::Market(mkt::Response::StartQuotes(
+                        mkt::StartQuotesResponse {
+                            quote_sub_id,
+                            fee_asset: AssetType::Quote,
+                        },
+                    ))),
+                )))
+                .expect("must not fail");
+            break;
+        }
+    }
+    for event in events {
+        ws_responses.send(event).expect("must not fail");
+    }
+}
+
+/// Respond to the next ResolveGaid request with `address`
+async fn reply_resolve_gaid(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+    address: elements::Address,
+) {
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::Market(mkt::Request::ResolveGaid(_)),
+        )) = req
+        {
+            ws_responses
+                .send(WrappedResponse::Response(ResponseMessage::Response(
+                    Some(request_id),
+                    Ok(sideswap_api::Response::Market(mkt::Response::ResolveGaid(
+                        mkt::ResolveGaidResponse { address },
+                    ))),
+                )))
+                .expect("must not fail");
+            break;
+        }
+    }
+}
+
+/// Respond to the next GetQuote request with an empty PSET
+async fn reply_get_quote(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+) {
+    reply_get_quote_pset(
+        ws_requests,
+        ws_responses,
+        PartiallySignedTransaction::new_v2(),
+    )
+    .await;
+}
+
+/// Respond to the next GetQuote request with the PSET
+async fn reply_get_quote_pset(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+    pset: PartiallySignedTransaction,
+) {
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::Market(mkt::Request::GetQuote(_)),
+        )) = req
+        {
+            ws_responses
+                .send(WrappedResponse::Response(ResponseMessage::Response(
+                    Some(request_id),
+                    Ok(sideswap_api::Response::Market(mkt::Response::GetQuote(
+                        mkt::GetQuoteResponse {
+                            pset: encode_pset(&pset),
+                            ttl: Duration::from_secs(30).into(),
+                        },
+                    ))),
+                )))
+                .expect("must not fail");
+            break;
+        }
+    }
+}
+
+/// Respond to the next ServerStatus request
+async fn reply_server_status(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+) {
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::ServerStatus(_),
+        )) = req
+        {
+            let status = sideswap_api::ServerStatus {
+                min_peg_in_amount: 1_000,
+                min_peg_out_amount: 100_000,
+                server_fee_percent_peg_in: 0.1,
+                server_fee_percent_peg_out: 0.1,
+                min_submit_amount: 0,
+                price_band: 0.0,
+                elements_fee_rate: FeeRateSats::from_raw(0.1),
+                bitcoin_fee_rates: vec![
+                    sideswap_api::FeeRate {
+                        blocks: 2,
+                        value: FeeRateSats::from_raw(10.0),
+                    },
+                    sideswap_api::FeeRate {
+                        blocks: 6,
+                        value: FeeRateSats::from_raw(5.0),
+                    },
+                ],
+                upload_url: String::new(),
+                policy_asset: Network::LiquidTestnet.d().policy_asset,
+                peg_out_bitcoin_tx_vsize: 200,
+            };
+            ws_responses
+                .send(WrappedResponse::Response(ResponseMessage::Response(
+                    Some(request_id),
+                    Ok(sideswap_api::Response::ServerStatus(status)),
+                )))
+                .expect("must not fail");
+            break;
+        }
+    }
+}
+
+fn test_asset(
+    asset_id: AssetId,
+    ticker: DealerTicker,
+    market_type: sideswap_api::MarketType,
+) -> sideswap_api::Asset {
+    sideswap_api::Asset {
+        asset_id,
+        name: ticker.to_string(),
+        ticker: sideswap_api::Ticker(ticker.to_string()),
+        icon: None,
+        precision: AssetPrecision::BITCOIN_PRECISION,
+        icon_url: None,
+        instant_swaps: None,
+        domain: None,
+        domain_agent: None,
+        domain_agent_link: None,
+        always_show: None,
+        issuance_prevout: None,
+        issuer_pubkey: None,
+        contract: None,
+        market_type: Some(market_type),
+        server_fee: None,
+        amp_asset_restrictions: None,
+        payjoin: Some(market_type == sideswap_api::MarketType::Stablecoin),
+    }
+}
+
+fn quote_notif(quote_sub_id: QuoteSubId) -> WrappedResponse {
+    market_notif(mkt::Notification::Quote(mkt::QuoteNotif {
+        quote_sub_id,
+        asset_pair: usdt_market().asset_pair,
+        asset_type: AssetType::Base,
+        amount: 100_000,
+        trade_dir: TradeDir::Sell,
+        status: mkt::QuoteStatus::Success {
+            quote_id: QuoteId::new(quote_sub_id.value()),
+            base_amount: 100_000,
+            quote_amount: 95_000_000,
+            server_fee: 100_000,
+            fixed_fee: 0,
+            ttl: Duration::from_secs(30).into(),
+        },
+    }))
+}
+
+/// Prepare everything needed by `get_quote` except the server responses
+async fn prepare_get_quote(env: &mut TestEnv) -> api::GetQuoteReq {
+    env.connect_upstream().await;
+    env.start_wallet(0);
+    env.data.markets = vec![usdt_market()];
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 1_000_000));
+    api::GetQuoteReq {
+        send_asset: DealerTicker::LBTC,
+        recv_asset: DealerTicker::USDT,
+        send_amount: Some(0.001),
+        recv_amount: None,
+        receive_address: Some(test_address(0)),
+        gaid: None,
+        instant_swap: false,
+        allow_partial: false,
+        // The test PSETs are empty unless `reply_get_quote_pset` is used
+        verify_pset: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        timeout_secs: None,
+        allow_routing: false,
+        wallet: DEFAULT_WALLET.to_owned(),
+    }
+}
+
+fn recv_all(receiver: &mut UnboundedReceiver<SharedNotif>) -> Vec<api::Notif> {
+    let mut notifs = Vec::new();
+    while let Ok(notif) = receiver.try_recv() {
+        notifs.push(notif.notif().clone());
+    }
+    notifs
+}
+
+#[tokio::test]
+async fn market_data_replayed_on_connect() {
+    let mut env = TestEnv::new().await;
+    env.connect_upstream().await;
+
+    let market = usdt_market();
+
+    process_ws_event(
+        &mut env.data,
+        market_resp(mkt::Response::ListMarkets(mkt::ListMarketsResponse {
+            markets: vec![market.clone()],
+            token_quotes: Vec::new(),
+        })),
+    )
+    .await;
+
+    process_ws_event(
+        &mut env.data,
+        market_notif(mkt::Notification::MarketPrice(mkt::MarketPriceNotif {
+            asset_pair: market.asset_pair,
+            ind_price: Some(NormalFloat::new(95000.0).unwrap()),
+            last_price: None,
+        })),
+    )
+    .await;
+
+    // Prices are sent only after SubscribePrice
+    let mut notif_receiver = env.connect_client(1).await;
+    let notifs = recv_all(&mut notif_receiver);
+    assert_eq!(notifs.len(), 1);
+
+    match &notifs[0] {
+        api::Notif::Markets(notif) => {
+            assert!(!notif.stale);
+            assert_eq!(notif.markets.len(), 1);
+            assert_eq!(notif.markets[0].base, DealerTicker::LBTC);
+            assert_eq!(notif.markets[0].quote, DealerTicker::USDT);
+        }
+        _ => panic!("markets notification expected"),
+    }
+
+    subscribe_price(
+        &mut env.data,
+        ClientId(1),
+        api::SubscribePriceReq {
+            base: DealerTicker::LBTC,
+            quote: DealerTicker::USDT,
+        },
+    )
+    .unwrap();
+    let notifs = recv_all(&mut notif_receiver);
+    assert_eq!(notifs.len(), 1);
+
+    match &notifs[0] {
+        api::Notif::MarketPrice(notif) => {
+            assert!(!notif.stale);
+            assert_eq!(notif.base, DealerTicker::LBTC);
+            assert_eq!(notif.quote, DealerTicker::USDT);
+            assert_eq!(notif.ind_price, Some(95000.0));
+            assert_eq!(notif.last_price, None);
+        }
+        _ => panic!("market price notification expected"),
+    }
+}
+
+#[tokio::test]
+async fn market_changes_notified_and_listed() {
+    let mut env = TestEnv::new().await;
+    env.connect_upstream().await;
+    let mut notif_receiver = env.connect_client(1).await;
+    recv_all(&mut notif_receiver);
+
+    let unknown_market = mkt::MarketInfo {
+        asset_pair: mkt::AssetPair {
+            base: env.data.policy_asset,
+            quote: AssetId::from_slice(&[99; 32]).expect("must not fail"),
+        },
+        ..usdt_market()
+    };
+    for market in [usdt_market(), unknown_market.clone()] {
+        process_ws_event(
+            &mut env.data,
+            market_notif(mkt::Notification::MarketAdded(mkt::MarketAddedNotif {
+                market,
+            })),
+        )
+        .await;
+    }
+
+    // Markets with unknown assets are not reported
+    let notifs = recv_all(&mut notif_receiver);
+    assert_eq!(notifs.len(), 1);
+    match &notifs[0] {
+        api::Notif::MarketAdded(notif) => {
+            assert_eq!(notif.market.base, DealerTicker::LBTC);
+            assert_eq!(notif.market.quote, DealerTicker::USDT);
+            assert!(matches!(notif.market.fee_asset, api::AssetType::Quote));
+        }
+        _ => panic!("market added notification expected"),
+    }
+
+    let resp = list_markets(&env.data, api::ListMarketsReq {}).unwrap();
+    assert!(!resp.stale);
+    assert_eq!(resp.markets.len(), 1);
+    assert_eq!(resp.markets[0].quote, DealerTicker::USDT);
+
+    for market in [unknown_market, usdt_market()] {
+        process_ws_event(
+            &mut env.data,
+            market_notif(mkt::Notification::MarketRemoved(mkt::MarketRemovedNotif {
+                asset_pair: market.asset_pair,
+            })),
+        )
+        .await;
+    }
+
+    let notifs = recv_all(&mut notif_receiver);
+    assert_eq!(notifs.len(), 1);
+    match &notifs[0] {
+        api::Notif::MarketRemoved(notif) => {
+            assert_eq!(notif.base, DealerTicker::LBTC);
+            assert_eq!(notif.quote, DealerTicker::USDT);
+        }
+        _ => panic!("market removed notification expected"),
+    }
+    assert!(list_markets(&env.data, api::ListMarketsReq {})
+        .unwrap()
+        .markets
+        .is_empty());
+}
+
+#[tokio::test]
+async fn price_subscriptions_follow_clients() {
+    let mut env = TestEnv::new().await;
+    env.connect_upstream().await;
+    env.data.markets = vec![usdt_market()];
+    let asset_pair = usdt_market().asset_pair;
+
+    let mut alice = env.connect_client(1).await;
+    let mut bob = env.connect_client(2).await;
+    let mut other = env.connect_client(3).await;
+
+    fn upstream_subs(env: &mut TestEnv) -> Vec<(&'static str, mkt::AssetPair)> {
+        let mut requests = Vec::new();
+        while let Ok(req) = env.ws_requests.try_recv() {
+            match req {
+                WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+                    _,
+                    sideswap_api::Request::Market(mkt::Request::Subscribe(req)),
+                )) => requests.push(("subscribe", req.asset_pair)),
+                WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+                    _,
+                    sideswap_api::Request::Market(mkt::Request::Unsubscribe(req)),
+                )) => requests.push(("unsubscribe", req.asset_pair)),
+                _ => {}
+            }
+        }
+        requests
+    }
+
+    let req = || api::SubscribePriceReq {
+        base: DealerTicker::LBTC,
+        quote: DealerTicker::USDT,
+    };
+    for client_id in [ClientId(1), ClientId(2)] {
+        subscribe_price(&mut env.data, client_id, req()).unwrap();
+    }
+    assert_eq!(upstream_subs(&mut env), [("subscribe", asset_pair)]);
+
+    // The pair must be an existing market
+    assert!(matches!(
+        subscribe_price(
+            &mut env.data,
+            ClientId(1),
+            api::SubscribePriceReq {
+                base: DealerTicker::USDT,
+                quote: DealerTicker::LBTC,
+            },
+        ),
+        Err(Error::NoMarket)
+    ));
+
+    process_ws_event(
+        &mut env.data,
+        market_notif(mkt::Notification::MarketPrice(mkt::MarketPriceNotif {
+            asset_pair,
+            ind_price: Some(NormalFloat::new(95000.0).unwrap()),
+            last_price: Some(NormalFloat::new(94900.0).unwrap()),
+        })),
+    )
+    .await;
+    for receiver in [&mut alice, &mut bob] {
+        let notifs = recv_all(receiver);
+        assert!(matches!(
+            &notifs[..],
+            [api::Notif::MarketPrice(api::MarketPriceNotif {
+                last_price: Some(94900.0),
+                ..
+            })]
+        ));
+    }
+    assert!(!recv_all(&mut other)
+        .iter()
+        .any(|notif| matches!(notif, api::Notif::MarketPrice(_))));
+
+    // Resubscribed after reconnects
+    process_ws_event(&mut env.data, WrappedResponse::Connected).await;
+    assert_eq!(upstream_subs(&mut env), [("subscribe", asset_pair)]);
+
+    // Unsubscribed upstream when the last subscriber leaves
+    unsubscribe_price(
+        &mut env.data,
+        ClientId(1),
+        api::UnsubscribePriceReq {
+            base: DealerTicker::LBTC,
+            quote: DealerTicker::USDT,
+        },
+    )
+    .unwrap();
+    assert!(upstream_subs(&mut env).is_empty());
+    process_command(
+        &mut env.data,
+        Command::ClientDisconnected {
+            client_id: ClientId(2),
+        },
+    )
+    .await;
+    assert_eq!(upstream_subs(&mut env), [("unsubscribe", asset_pair)]);
+    assert!(env.data.price_subs.is_empty());
+    assert!(env.data.market_prices.is_empty());
+}
+
+#[tokio::test]
+async fn market_data_stale_when_disconnected() {
+    let mut env = TestEnv::new().await;
+
+    env.data.markets = vec![usdt_market()];
+    env.data.markets_updated_at =
+        Some(Instant::now() - MARKET_DATA_STALE_PERIOD - Duration::from_secs(1));
+
+    let mut notif_receiver = env.connect_client(1).await;
+    let notifs = recv_all(&mut notif_receiver);
+    assert_eq!(notifs.len(), 1);
+
+    match &notifs[0] {
+        api::Notif::Markets(notif) => assert!(notif.stale),
+        _ => panic!("markets notification expected"),
+    }
+}
+
+#[tokio::test]
+async fn signing_refused_while_locked() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    let config = crate::signing_lock::Config {
+        timeout_minutes: 1,
+        password_hash: crate::signing_lock::password_hash("secret"),
+    };
+    env.data.signing_lock = Some(SigningLock::new(&config, Instant::now()));
+
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::AcceptQuote(api::AcceptQuoteReq {
+            quote_id: QuoteId::new(1),
+            user_note: None,
+            idempotency_key: None,
+        }),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::Locked)));
+
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::ListAddresses(api::ListAddressesReq {
+            include_change: false,
+            wallet: DEFAULT_WALLET.to_owned(),
+        }),
+    )
+    .await;
+    assert!(res.is_ok());
+
+    // Other requests are processed while the password is checked
+    let unlock_req = |password: &str| {
+        api::Req::Unlock(api::UnlockReq {
+            password: password.to_owned(),
+        })
+    };
+    let pending = match start_request(&mut env.data, ClientId(1), unlock_req("wrong")).await {
+        Ok(Processed::Pending(resp)) => resp,
+        _ => panic!("pending unlock expected"),
+    };
+    let res = process_request(&mut env.data, ClientId(1), unlock_req("secret")).await;
+    assert!(matches!(res, Err(Error::UnlockBackoff(_))));
+    let result = env.unlock_results.recv().await.unwrap();
+    finish_unlock(&mut env.data, result).await;
+    let res = pending.await;
+    assert!(matches!(res, Err(Error::WrongPassword)));
+
+    let res = env.unlock("secret").await;
+    assert!(matches!(res, Err(Error::UnlockBackoff(_))));
+
+    env.data.signing_lock = Some(SigningLock::new(&config, Instant::now()));
+    let mut notif_receiver = env.connect_client(1).await;
+    recv_all(&mut notif_receiver);
+
+    let res = env.unlock("secret").await;
+    assert!(res.is_ok());
+    assert!(matches!(
+        recv_all(&mut notif_receiver).as_slice(),
+        [api::Notif::LockStatus(api::LockStatusNotif {
+            locked: false
+        })]
+    ));
+
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::AcceptQuote(api::AcceptQuoteReq {
+            quote_id: QuoteId::new(1),
+            user_note: None,
+            idempotency_key: None,
+        }),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NoQuote)));
+
+    let events = env.data.db.load_audit_events().await;
+    assert!(events.iter().all(|event| event.created_at > 0));
+    assert_eq!(
+        events
+            .into_iter()
+            .map(|event| event.event)
+            .collect::<Vec<_>>(),
+        ["unlock failed: wrong password", "signing unlocked"]
+    );
+}
+
+#[tokio::test]
+async fn clock_skew_reported_in_server_info() {
+    let mut env = TestEnv::new().await;
+
+    let server_info = |data: &Data| get_server_info(data, api::GetServerInfoReq {}).unwrap();
+    assert!(!server_info(&env.data).clock_skew_detected);
+    assert_eq!(server_info(&env.data).clock_skew_ms, None);
+
+    let local_time = TimestampMs::from_millis(1_700_000_000_000);
+    process_clock_sample(
+        &mut env.data,
+        ClockSample {
+            local_time,
+            remote_time: TimestampMs::from_millis(local_time.millis() + 120_000),
+        },
+    );
+    assert!(server_info(&env.data).clock_skew_detected);
+    assert_eq!(server_info(&env.data).clock_skew_ms, Some(120_000));
+
+    process_clock_sample(
+        &mut env.data,
+        ClockSample {
+            local_time,
+            remote_time: local_time,
+        },
+    );
+    assert!(!server_info(&env.data).clock_skew_detected);
+    assert_eq!(server_info(&env.data).clock_skew_ms, Some(0));
+}
+
+#[tokio::test]
+async fn new_address_uses_requested_wallet() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    let mut notif_receiver = env.connect_client(1).await;
+
+    let (command_sender, wallet_commands) = mpsc::channel();
+    env.data
+        .wallets
+        .insert("cold".to_owned(), WalletData::new(command_sender));
+    std::thread::spawn(move || {
+        while let Ok(command) = wallet_commands.recv() {
+            match command {
+                sideswap_lwk::Command::NewAdddress { req, res_sender } => {
+                    let index = req.index.unwrap_or_default();
+                    res_sender.send(Ok(sideswap_lwk::NewAddrResp {
+                        change: req.change,
+                        index,
+                        address: test_address(5000 + index),
+                    }));
+                }
+                sideswap_lwk::Command::GetUtxos { req: _, res_sender } => {
+                    res_sender.send(Ok(sideswap_lwk::GetUtxosResp {
+                        utxos: Vec::new(),
+                        tip_height: TEST_TIP_HEIGHT,
+                    }));
+                }
+                sideswap_lwk::Command::GetTxs { req: _, res_sender } => {
+                    res_sender.send(Ok(sideswap_lwk::GetTxsResp { txs: Vec::new() }));
+                }
+                _ => panic!("unexpected wallet command"),
+            }
+        }
+    });
+
+    let new_address_req = |wallet: &str| api::NewAddressReq {
+        user_note: None,
+        wallet: wallet.to_owned(),
+    };
+
+    let resp = new_address(&mut env.data, new_address_req("cold"))
+        .await
+        .unwrap();
+    assert_eq!(resp.index, 0);
+    assert_eq!(resp.address, test_address(5000));
+    assert_eq!(env.data.wallets["cold"].addresses[&0].wallet, "cold");
+    assert!(env.wallet().addresses.is_empty());
+
+    // The default wallet indexes are independent
+    let resp = new_address(&mut env.data, new_address_req(DEFAULT_WALLET))
+        .await
+        .unwrap();
+    assert_eq!(resp.index, 0);
+    assert_eq!(resp.address, test_address(0));
+
+    let resp = new_address_batch(
+        &mut env.data,
+        api::NewAddressBatchReq {
+            count: 2,
+            note_prefix: None,
+            wallet: "cold".to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        resp.addresses
+            .iter()
+            .map(|addr| addr.address.clone())
+            .collect::<Vec<_>>(),
+        [test_address(5001), test_address(5002)]
+    );
+    let list_req = |wallet: &str| api::ListAddressesReq {
+        include_change: false,
+        wallet: wallet.to_owned(),
+    };
+    let resp = list_addresses(&env.data, list_req("cold")).await.unwrap();
+    assert_eq!(resp.addresses.len(), 3);
+    assert!(resp
+        .addresses
+        .iter()
+        .all(|addr| addr.address != test_address(0)));
+    let res = list_addresses(&env.data, list_req("nope")).await;
+    assert!(matches!(res, Err(Error::UnknownWallet { .. })));
+
+    let res = new_address(&mut env.data, new_address_req("nope")).await;
+    assert!(matches!(
+        res,
+        Err(Error::UnknownWallet { wallet, available }) if wallet == "nope" && available == ["cold", "default"]
+    ));
+
+    reload_balances(&mut env.data, "cold").await;
+    assert!(matches!(
+        recv_all(&mut notif_receiver).as_slice(),
+        [api::Notif::Balances(api::BalancesNotif { wallet, .. })] if wallet == "cold"
+    ));
+}
+
+#[tokio::test]
+async fn new_address_batch_stops_at_gap_limit() {
+    let mut env = TestEnv::new().await;
+    env.data.settings.gap_limit = Some(10);
+    env.start_wallet(5);
+
+    let resp = new_address_batch(
+        &mut env.data,
+        api::NewAddressBatchReq {
+            count: 25,
+            note_prefix: Some("invoice-".to_owned()),
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        resp.stopped_reason,
+        Some(api::AddressBatchStopReason::GapLimit)
+    );
+    assert_eq!(
+        resp.addresses
+            .iter()
+            .map(|addr| addr.index)
+            .collect::<Vec<_>>(),
+        (5..15).collect::<Vec<_>>()
+    );
+    assert_eq!(resp.addresses[0].user_note.as_deref(), Some("invoice-1"));
+    assert_eq!(resp.addresses[9].user_note.as_deref(), Some("invoice-10"));
+
+    let stored = env.data.db.load_addresses().await;
+    assert_eq!(stored.len(), 10);
+    assert_eq!(env.wallet().addresses.len(), 10);
+
+    let resp = new_address_batch(
+        &mut env.data,
+        api::NewAddressBatchReq {
+            count: 1,
+            note_prefix: None,
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(resp.addresses.is_empty());
+    assert_eq!(
+        resp.stopped_reason,
+        Some(api::AddressBatchStopReason::GapLimit)
+    );
+}
+
+#[tokio::test]
+async fn stale_quote_ignored_after_reconnect() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let quote_sub_id = QuoteSubId::new(1);
+
+    let (res, ()) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), req),
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![
+                WrappedResponse::Disconnected,
+                WrappedResponse::Connected,
+                quote_notif(quote_sub_id),
+            ],
+        ),
+    );
+    assert!(matches!(
+        res,
+        Err(Error::WsError(ws_req_sender::Error::Disconnected))
+    ));
+    assert!(env.data.quotes.is_empty());
+
+    // Process the reconnect and the delayed quote notification
+    for _ in 0..2 {
+        let event = env.data.ws.recv().await;
+        process_ws_event(&mut env.data, event).await;
+    }
+    assert_eq!(env.data.stale_quote_notifs, 1);
+    assert!(env.data.quotes.is_empty());
+}
+
+#[tokio::test]
+async fn quote_from_previous_connection_not_signed() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let quote_sub_id = QuoteSubId::new(1);
+
+    // The reconnect is reported without a disconnect, the subscription is gone anyway
+    let (res, ()) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), req),
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![WrappedResponse::Connected, quote_notif(quote_sub_id)],
+        ),
+    );
+    assert!(matches!(
+        res,
+        Err(Error::WsError(ws_req_sender::Error::Disconnected))
+    ));
+    assert!(env.data.quotes.is_empty());
+
+    let event = env.data.ws.recv().await;
+    process_ws_event(&mut env.data, event).await;
+    assert_eq!(env.data.stale_quote_notifs, 1);
+}
+
+#[tokio::test]
+async fn quote_timeout_stops_quotes() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let quote_req = |timeout_secs| api::GetQuoteReq {
+        timeout_secs,
+        receive_address: Some(test_address(0)),
+        gaid: None,
+        utxos: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..req
+    };
+    let quote_sub_id = QuoteSubId::new(1);
+
+    for timeout_secs in [0, 61] {
+        let res = get_quote(&mut env.data, ClientId(1), quote_req(Some(timeout_secs))).await;
+        assert!(matches!(res, Err(Error::InvalidQuoteTimeout(secs)) if secs == timeout_secs));
+    }
+
+    let (res, ()) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), quote_req(Some(1))),
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            Vec::new()
+        ),
+    );
+    assert!(matches!(
+        res,
+        Err(Error::WsError(ws_req_sender::Error::Timeout(_)))
+    ));
+    assert_eq!(env.data.last_quote_sub, None);
+
+    // The quotes are stopped without waiting for the response, a late quote is discarded
+    reply_stop_quotes(&mut env.ws_requests, &env.ws_responses).await;
+    env.ws_responses
+        .send(quote_notif(quote_sub_id))
+        .expect("must not fail");
+    for _ in 0..2 {
+        let event = env.data.ws.recv().await;
+        process_ws_event(&mut env.data, event).await;
+    }
+    assert!(env.data.quotes.is_empty());
+
+    let resp = get_diagnostics(&env.data, api::GetDiagnosticsReq {}).unwrap();
+    assert_eq!(resp.quote_timeouts, 1);
+    assert_eq!(resp.discarded_quote_notifs, 1);
+    assert_eq!(resp.stale_quote_notifs, 1);
+}
+
+#[tokio::test]
+async fn oversized_start_quotes_rejected_locally() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    env.wallet().utxo_data = Some(test_utxos(env.data.policy_asset, 1000, 2000));
+    env.data.ws.set_size_limits(ws_req_sender::SizeLimits {
+        soft_bytes: 10_000,
+        hard_bytes: 100_000,
+    });
+
+    let res = get_quote(&mut env.data, ClientId(1), req).await;
+    match res {
+        Err(Error::WsError(ws_req_sender::Error::RequestTooLarge { size, limit })) => {
+            assert!(size > limit);
+            assert_eq!(limit, 100_000);
+        }
+        _ => panic!("RequestTooLarge expected"),
+    }
+    assert!(matches!(
+        Error::WsError(ws_req_sender::Error::RequestTooLarge { size: 1, limit: 0 }).error_code(),
+        api::ErrorCode::RequestTooLarge
+    ));
+    assert!(env.ws_requests.try_recv().is_err());
+    assert!(env.data.quotes.is_empty());
+
+    let resp = get_server_info(&env.data, api::GetServerInfoReq {}).unwrap();
+    let sizes = &resp.upstream_request_sizes["Market.start_quotes"];
+    assert_eq!(sizes.count, 1);
+    assert_eq!(sizes.rejected, 1);
+    assert!(sizes.max_bytes > 100_000);
+    // Between 256 KiB and 1 MiB
+    assert_eq!(sizes.histogram, [0, 0, 0, 1, 0, 0]);
+}
+
+#[tokio::test]
+async fn gap_limit_warning_and_clearance() {
+    let mut env = TestEnv::new().await;
+    env.data.settings.gap_limit = Some(10);
+    env.data.settings.gap_limit_warning = Some(3);
+    let first_unused = env.start_wallet(0);
+    let mut notif_receiver = env.connect_client(1).await;
+
+    for index in 0..7 {
+        let resp = new_address(
+            &mut env.data,
+            api::NewAddressReq {
+                user_note: None,
+                wallet: DEFAULT_WALLET.to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.index, index);
+        assert_eq!(resp.gap_limit_remaining, 9 - index);
+    }
+    assert!(recv_all(&mut notif_receiver).is_empty());
+
+    for remaining in [2, 1] {
+        let resp = new_address(
+            &mut env.data,
+            api::NewAddressReq {
+                user_note: None,
+                wallet: DEFAULT_WALLET.to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(resp.gap_limit_remaining, remaining);
+    }
+    assert!(matches!(
+        recv_all(&mut notif_receiver).as_slice(),
+        [api::Notif::GapLimitWarning(api::GapLimitWarningNotif {
+            wallet: _,
+            remaining: 2,
+            first_unused_index: 0,
+            active: true,
+        })]
+    ));
+
+    // Address 4 received funds
+    first_unused.store(5, Ordering::Relaxed);
+    for _ in 0..2 {
+        process_wallet_event(&mut env.data, DEFAULT_WALLET, sideswap_lwk::Event::Updated).await;
+    }
+    assert!(matches!(
+        recv_all(&mut notif_receiver).as_slice(),
+        [
+            api::Notif::Balances(_),
+            api::Notif::GapLimitWarning(api::GapLimitWarningNotif {
+                wallet: _,
+                remaining: 6,
+                first_unused_index: 5,
+                active: false,
+            })
+        ]
+    ));
+
+    let resp = new_address(
+        &mut env.data,
+        api::NewAddressReq {
+            user_note: None,
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.index, 9);
+    assert_eq!(resp.gap_limit_remaining, 5);
+    assert!(recv_all(&mut notif_receiver).is_empty());
+}
+
+#[tokio::test]
+async fn amp_asset_requires_gaid_address() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.connect_upstream().await;
+
+    let network = Network::LiquidTestnet.d();
+    process_ws_event(
+        &mut env.data,
+        WrappedResponse::Response(ResponseMessage::Response(
+            None,
+            Ok(sideswap_api::Response::Assets(
+                sideswap_api::AssetsResponse {
+                    assets: vec![
+                        test_asset(
+                            network.known_assets.USDt,
+                            DealerTicker::USDT,
+                            sideswap_api::MarketType::Stablecoin,
+                        ),
+                        test_asset(amp_asset_id(), amp_ticker(), sideswap_api::MarketType::Amp),
+                    ],
+                },
+            )),
+        )),
+    )
+    .await;
+
+    let assets = list_assets(&env.data, api::ListAssetsReq {})
+        .unwrap()
+        .assets;
+    let flags = |ticker| {
+        let asset = assets.iter().find(|asset| asset.ticker == ticker).unwrap();
+        (asset.amp_restricted, asset.payjoin)
+    };
+    assert_eq!(flags(amp_ticker()), (Some(true), Some(false)));
+    assert_eq!(flags(DealerTicker::USDT), (Some(false), Some(true)));
+    assert_eq!(flags(DealerTicker::LBTC), (None, None));
+
+    let send = |address: elements::Address, asset| api::CreateTxReq {
+        recipients: vec![api::Recipient {
+            address: Some(address.to_string()),
+            asset: Some(asset),
+            uri: None,
+            amount: 1.0,
+            send_all: false,
+        }],
+        aggregate_duplicates: false,
+        fee_rate: None,
+        allow_unconfidential: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+
+    let res = create_tx(
+        &mut env.data,
+        ClientId(0),
+        send(test_address(5), amp_ticker()),
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(Error::AmpAddressRequired { asset }) if asset == amp_ticker()
+    ));
+
+    create_tx(
+        &mut env.data,
+        ClientId(0),
+        send(test_address(5), DealerTicker::USDT),
+    )
+    .await
+    .unwrap();
+
+    let (resp, ()) = tokio::join!(
+        resolve_gaid(
+            &mut env.data,
+            api::ResolveGaidReq {
+                asset: amp_ticker(),
+                gaid: "GA2zxWdhAYtREeYCVFTGRhHQmYMPAP".to_owned(),
+            },
+        ),
+        reply_resolve_gaid(&mut env.ws_requests, &env.ws_responses, test_address(6)),
+    );
+    assert_eq!(resp.unwrap().address, test_address(6));
+
+    create_tx(
+        &mut env.data,
+        ClientId(0),
+        send(test_address(6), amp_ticker()),
+    )
+    .await
+    .unwrap();
+}
+
+#[tokio::test]
+async fn shutdown_rejects_queued_requests_and_waits_for_broadcasts() {
+    let mut env = TestEnv::new().await;
+    env.connect_upstream().await;
+    let (drain_sender, drain_receiver) = watch::channel(false);
+    env.data.drain_sender = drain_sender;
+    let _notif_receiver = env.connect_client(1).await;
+
+    let pending_broadcast = || PendingBroadcast {
+        tx: elements::Transaction {
+            version: 2,
+            lock_time: elements::LockTime::ZERO,
+            input: Vec::new(),
+            output: Vec::new(),
+        },
+        attempts: 0,
+        request_id: None,
+    };
+    let txid = elements::Txid::from_byte_array([5; 32]);
+    env.data
+        .pending_broadcasts
+        .insert(txid, pending_broadcast());
+    retry_broadcasts(&mut env.data);
+
+    let (command_sender, mut command_receiver) = unbounded_channel();
+    let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
+    command_sender
+        .send(Command::Request {
+            client_id: ClientId(1),
+            req: api::Req::ListAssets(api::ListAssetsReq {}),
+            res_sender: res_sender.into(),
+        })
+        .expect("must not fail");
+
+    let broadcast = mkt::Response::BroadcastTx(mkt::BroadcastTxResponse { txid });
+    tokio::join!(
+        shutdown(&mut env.data, &mut command_receiver),
+        reply_market(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            |req| matches!(req, mkt::Request::BroadcastTx(_)),
+            Ok(broadcast),
+        ),
+    );
+    assert!(*drain_receiver.borrow());
+    assert!(matches!(res_receiver.await, Ok(Err(Error::ShuttingDown))));
+    assert!(env.data.pending_broadcasts.is_empty());
+    assert!(env.data.clients.is_empty());
+
+    // Not answered broadcasts are not awaited after the timeout
+    env.data.settings.shutdown_timeout_seconds = Some(0);
+    env.data
+        .pending_broadcasts
+        .insert(txid, pending_broadcast());
+    retry_broadcasts(&mut env.data);
+    shutdown(&mut env.data, &mut command_receiver).await;
+    assert!(env.data.pending_broadcasts.contains_key(&txid));
+}
+
+#[tokio::test]
+async fn drain_rejects_writes_and_finishes_started_tx() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 1_000_000));
+    let (drain_sender, drain_receiver) = watch::channel(false);
+    env.data.drain_sender = drain_sender;
+    let mut notif_receiver = env.connect_client(1).await;
+
+    let create_tx_req = || {
+        api::Req::CreateTx(api::CreateTxReq {
+            recipients: vec![api::Recipient {
+                address: Some(test_address(5).to_string()),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.001,
+                send_all: false,
+            }],
+            aggregate_duplicates: false,
+            fee_rate: None,
+            allow_unconfidential: false,
+            utxos: None,
+            allow_unconfirmed: true,
+            wallet: DEFAULT_WALLET.to_owned(),
+        })
+    };
+
+    // Created before draining, but not sent yet
+    let txid = match process_request(&mut env.data, ClientId(1), create_tx_req()).await {
+        Ok(api::Resp::CreateTx(resp)) => resp.txid,
+        _ => panic!("CreateTx response expected"),
+    };
+
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::Drain(api::DrainReq { grace_seconds: 60 }),
+    )
+    .await;
+    assert!(matches!(res, Ok(api::Resp::Drain(_))));
+    assert!(*drain_receiver.borrow());
+    assert!(matches!(
+        recv_all(&mut notif_receiver).as_slice(),
+        [api::Notif::Draining(_)]
+    ));
+    assert!(
+        get_server_info(&env.data, api::GetServerInfoReq {})
+            .unwrap()
+            .draining
+    );
+
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::NewAddress(api::NewAddressReq {
+            user_note: None,
+            wallet: DEFAULT_WALLET.to_owned(),
+        }),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::Draining)));
+    let res = process_request(&mut env.data, ClientId(1), create_tx_req()).await;
+    assert!(matches!(res, Err(Error::Draining)));
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::ListAddresses(api::ListAddressesReq {
+            include_change: false,
+            wallet: DEFAULT_WALLET.to_owned(),
+        }),
+    )
+    .await;
+    assert!(matches!(res, Ok(api::Resp::ListAddresses(_))));
+
+    assert!(!drain_finished(&env.data, Instant::now()));
+    assert!(drain_finished(
+        &env.data,
+        Instant::now() + Duration::from_secs(61)
+    ));
+
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::SendTx(api::SendTxReq {
+            txid,
+            user_note: None,
+            wallet_only: true,
+            idempotency_key: None,
+            wallet: DEFAULT_WALLET.to_owned(),
+        }),
+    )
+    .await;
+    assert!(matches!(res, Ok(api::Resp::SendTx(_))));
+
+    assert!(drain_finished(&env.data, Instant::now()));
+}
+
+#[test]
+fn peg_addr_validation() {
+    let secret_key =
+        elements::secp256k1_zkp::SecretKey::from_slice(&[3; 32]).expect("must not fail");
+    let pubkey = elements::bitcoin::PublicKey::new(
+        secret_key.public_key(elements::secp256k1_zkp::SECP256K1),
+    );
+    let blinder = pubkey.inner;
+    let liquid = |params, blinder| elements::Address::p2wpkh(&pubkey, blinder, params).to_string();
+    let bitcoin = |network| {
+        elements::bitcoin::Address::p2wpkh(
+            &elements::bitcoin::CompressedPublicKey(pubkey.inner),
+            network,
+        )
+        .to_string()
+    };
+
+    let lq = liquid(&elements::AddressParams::LIQUID, Some(blinder));
+    let tlq = liquid(&elements::AddressParams::LIQUID_TESTNET, Some(blinder));
+    let tex = liquid(&elements::AddressParams::LIQUID_TESTNET, None);
+    let bc = bitcoin(elements::bitcoin::Network::Bitcoin);
+    let tb = bitcoin(elements::bitcoin::Network::Testnet);
+
+    // (network, peg_in, address, allow_unconfidential, expected chain if rejected)
+    let cases = [
+        (Network::LiquidTestnet, true, &tlq, false, None),
+        (Network::LiquidTestnet, true, &tb, false, Some("Liquid")),
+        (Network::LiquidTestnet, false, &tb, false, None),
+        (Network::LiquidTestnet, false, &tlq, false, Some("Bitcoin")),
+        (Network::LiquidTestnet, true, &lq, false, Some("Liquid")),
+        (Network::LiquidTestnet, false, &bc, false, Some("Bitcoin")),
+        (Network::LiquidTestnet, true, &tex, false, Some("Liquid")),
+        (Network::LiquidTestnet, true, &tex, true, None),
+        (Network::Liquid, true, &lq, false, None),
+        (Network::Liquid, true, &bc, false, Some("Liquid")),
+        (Network::Liquid, false, &bc, false, None),
+        (Network::Liquid, false, &lq, false, Some("Bitcoin")),
+        (Network::Liquid, true, &tlq, false, Some("Liquid")),
+        (Network::Liquid, false, &tb, false, Some("Bitcoin")),
+    ];
+
+    for (network, peg_in, address, allow_unconfidential, expected) in cases {
+        let res = validate_peg_addr(network, peg_in, address, allow_unconfidential);
+        match expected {
+            None => assert_eq!(res.unwrap(), *address),
+            Some(chain) => assert!(
+                matches!(res, Err(Error::InvalidPegAddress { expected_chain, .. }) if expected_chain == chain),
+                "{network:?} peg_in: {peg_in} {address}"
+            ),
+        }
+    }
+
+    let normalized = validate_peg_addr(
+        Network::Liquid,
+        false,
+        &format!(" {} ", bc.to_uppercase()),
+        false,
+    );
+    assert_eq!(normalized.unwrap(), bc);
+}
+
+fn peg_tx(
+    tx_hash: u8,
+    tx_state: sideswap_api::PegTxState,
+    detected_confs: Option<i32>,
+) -> sideswap_api::TxStatus {
+    let done = matches!(tx_state, sideswap_api::PegTxState::Done);
+    sideswap_api::TxStatus {
+        tx_hash: sideswap_api::HashN([tx_hash; 32]),
+        vout: 0,
+        status: format!("{tx_state:?}"),
+        amount: 100_000,
+        payout: Some(99_000),
+        tx_state,
+        tx_state_code: sideswap_api::peg_tx_state_code(tx_state),
+        detected_confs,
+        total_confs: detected_confs.map(|_| 2),
+        created_at: 1_700_000_000_000,
+        payout_txid: done.then_some(sideswap_api::HashN([tx_hash + 100; 32])),
+    }
+}
+
+async fn send_peg_status(env: &mut TestEnv, order_id: OrderId, list: Vec<sideswap_api::TxStatus>) {
+    let status = sideswap_api::PegStatus {
+        order_id,
+        peg_in: true,
+        addr: "tb1qserver".to_owned(),
+        addr_recv: test_address(0).to_string(),
+        list,
+        created_at: 1_700_000_000_000,
+        expires_at: 1_800_000_000_000,
+        return_address: None,
+    };
+    process_ws_event(
+        &mut env.data,
+        WrappedResponse::Response(ResponseMessage::Notification(
+            sideswap_api::Notification::PegStatus(status),
+        )),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn peg_timeline_records_state_changes() {
+    use sideswap_api::PegTxState::*;
+
+    let mut env = TestEnv::new().await;
+    let order_id = sideswap_api::HashN([9; 32]);
+    env.data
+        .db
+        .add_peg(Peg {
+            order_id: Text(order_id),
+            addr_recv: None,
+            created_by: None,
+            completed_at: None,
+        })
+        .await;
+    env.data.pegs.insert(
+        order_id,
+        PegData {
+            status: None,
+            timeline: Vec::new(),
+            notif_stages: BTreeMap::new(),
+            created_by: None,
+            completed_at: None,
+            status_request_id: None,
+            server_error: None,
+        },
+    );
+
+    let statuses = [
+        vec![],
+        vec![peg_tx(1, Detected, Some(0))],
+        vec![peg_tx(1, Detected, Some(1))],
+        vec![peg_tx(1, Detected, Some(1))],
+        vec![peg_tx(1, Processing, None)],
+        vec![peg_tx(1, Done, None), peg_tx(2, InsufficientAmount, None)],
+        vec![peg_tx(1, Done, None), peg_tx(2, InsufficientAmount, None)],
+    ];
+    for list in statuses {
+        send_peg_status(&mut env, order_id, list).await;
+    }
+
+    let timeline = get_peg_timeline(&mut env.data, api::GetPegTimelineReq { order_id })
+        .await
+        .unwrap()
+        .events
+        .into_iter()
+        .map(|event| (event.tx_hash.0[0], event.tx_state, event.payout_txid))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        timeline,
+        [
+            (1, api::PegTxState::Detected, None),
+            (1, api::PegTxState::Processing, None),
+            (
+                1,
+                api::PegTxState::Done,
+                Some(sideswap_api::HashN([101; 32]))
+            ),
+            (2, api::PegTxState::InsufficientAmount, None),
+        ]
+    );
+    assert_eq!(env.data.db.load_peg_events().await.len(), 4);
+
+    let res = get_peg_timeline(
+        &mut env.data,
+        api::GetPegTimelineReq {
+            order_id: sideswap_api::HashN([8; 32]),
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::UnknownPeg)));
+
+    // The number of stored events is limited
+    for index in 0..MAX_PEG_EVENTS {
+        let tx_state = if index % 2 == 0 { Detected } else { Processing };
+        send_peg_status(&mut env, order_id, vec![peg_tx(3, tx_state, None)]).await;
+    }
+    assert_eq!(env.data.pegs[&order_id].timeline.len(), MAX_PEG_EVENTS);
+    assert_eq!(env.data.db.load_peg_events().await.len(), MAX_PEG_EVENTS);
+}
+
+fn peg_semantic_notifs_names(notifs: Vec<api::Notif>) -> Vec<(&'static str, u8)> {
+    notifs
+        .into_iter()
+        .filter_map(|notif| match notif {
+            api::Notif::PegDepositDetected(notif) => Some(("detected", notif.txid.0[0])),
+            api::Notif::PegDepositConfirmed(notif) => Some(("confirmed", notif.txid.0[0])),
+            api::Notif::PegPayoutBroadcast(notif) => Some(("payout", notif.txid.0[0])),
+            api::Notif::PegCompleted(notif) => Some(("completed", notif.txid.0[0])),
+            api::Notif::PegFailed(notif) => Some(("failed", notif.txid.0[0])),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn peg_semantic_notifs_sent_once() {
+    use sideswap_api::PegTxState::*;
+
+    let mut env = TestEnv::new().await;
+    let order_id = sideswap_api::HashN([9; 32]);
+    env.data
+        .db
+        .add_peg(Peg {
+            order_id: Text(order_id),
+            addr_recv: None,
+            created_by: None,
+            completed_at: None,
+        })
+        .await;
+    env.data.pegs = load_pegs(&env.data.db).await;
+    let mut notif_receiver = env.connect_client(1).await;
+
+    let statuses = [
+        vec![],
+        vec![peg_tx(1, Detected, Some(0))],
+        vec![peg_tx(1, Detected, Some(1))],
+        vec![peg_tx(1, Detected, Some(1))],
+        vec![peg_tx(1, Processing, None)],
+        vec![
+            peg_tx(1, Processing, None),
+            peg_tx(2, InsufficientAmount, None),
+        ],
+    ];
+    for list in statuses {
+        send_peg_status(&mut env, order_id, list).await;
+    }
+    let notifs = recv_all(&mut notif_receiver);
+    assert_eq!(
+        notifs
+            .iter()
+            .filter(|notif| matches!(notif, api::Notif::PegStatus(_)))
+            .count(),
+        6
+    );
+    assert_eq!(
+        peg_semantic_notifs_names(notifs),
+        [("detected", 1), ("confirmed", 1), ("failed", 2)]
+    );
+
+    // The stages are restored after a restart, the resent statuses are not reported again
+    env.data.pegs = load_pegs(&env.data.db).await;
+    for _ in 0..2 {
+        send_peg_status(
+            &mut env,
+            order_id,
+            vec![peg_tx(1, Done, None), peg_tx(2, InsufficientAmount, None)],
+        )
+        .await;
+    }
+    let notifs = recv_all(&mut notif_receiver);
+    match &notifs[..] {
+        [api::Notif::PegStatus(_), api::Notif::PegPayoutBroadcast(payout), api::Notif::PegCompleted(completed), api::Notif::PegStatus(_)] =>
+        {
+            assert_eq!(payout.payout_txid, sideswap_api::HashN([101; 32]));
+            assert_eq!(payout.payout_amount, Some(0.00099));
+            assert_eq!(completed.order_id, order_id);
+        }
+        _ => panic!("unexpected notifications"),
+    }
+
+    del_peg(&mut env.data, api::DelPegReq { order_id })
+        .await
+        .unwrap();
+    assert!(env.data.db.load_peg_notif_stages().await.is_empty());
+}
+
+#[tokio::test]
+async fn peg_details_track_latest_tx_state() {
+    use sideswap_api::PegTxState::*;
+
+    let mut env = TestEnv::new().await;
+    let order_id = sideswap_api::HashN([9; 32]);
+    env.data
+        .db
+        .add_peg(Peg {
+            order_id: Text(order_id),
+            addr_recv: None,
+            created_by: None,
+            completed_at: None,
+        })
+        .await;
+    env.data.pegs = load_pegs(&env.data.db).await;
+
+    send_peg_status(&mut env, order_id, vec![peg_tx(1, Detected, Some(0))]).await;
+    let mut refunded = peg_tx(2, InsufficientAmount, None);
+    refunded.status = "Refunded".to_owned();
+    refunded.payout = None;
+    send_peg_status(&mut env, order_id, vec![peg_tx(1, Done, None), refunded]).await;
+
+    let details = get_peg_details(&mut env.data, api::GetPegDetailsReq { order_id })
+        .await
+        .unwrap()
+        .txs
+        .into_iter()
+        .map(|tx| {
+            (
+                tx.txid.0[0],
+                tx.amount,
+                tx.status,
+                tx.payout_txid,
+                tx.payout_amount,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        details,
+        [
+            (
+                1,
+                0.001,
+                api::PegTxProgress::Done,
+                Some(sideswap_api::HashN([101; 32])),
+                Some(0.00099)
+            ),
+            (
+                2,
+                0.001,
+                api::PegTxProgress::Unknown("Refunded".to_owned()),
+                None,
+                None
+            ),
+        ]
+    );
+    assert_eq!(
+        api::PegTxProgress::from_server("Insufficient amount"),
+        api::PegTxProgress::InsufficientAmount
+    );
+
+    let res = get_peg_details(
+        &mut env.data,
+        api::GetPegDetailsReq {
+            order_id: sideswap_api::HashN([8; 32]),
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::UnknownPeg)));
+
+    del_peg(&mut env.data, api::DelPegReq { order_id })
+        .await
+        .unwrap();
+    assert!(env.data.db.load_peg_txs(order_id).await.is_empty());
+}
+
+/// The orders of the `PegStatus` requests sent upstream after a reconnect
+fn queried_pegs(env: &mut TestEnv) -> Vec<OrderId> {
+    process_ws_connected(&mut env.data);
+    let mut order_ids = Vec::new();
+    while let Ok(req) = env.ws_requests.try_recv() {
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            _,
+            sideswap_api::Request::PegStatus(req),
+        )) = req
+        {
+            order_ids.push(req.order_id);
+        }
+    }
+    order_ids
+}
+
+#[tokio::test]
+async fn pegs_unknown_to_server_marked() {
+    let mut env = TestEnv::new().await;
+    for index in [1, 2] {
+        env.data
+            .db
+            .add_peg(Peg {
+                order_id: Text(sideswap_api::HashN([index; 32])),
+                addr_recv: None,
+                created_by: None,
+                completed_at: None,
+            })
+            .await;
+    }
+    env.data.pegs = load_pegs(&env.data.db).await;
+
+    process_ws_connected(&mut env.data);
+    while let Ok(req) = env.ws_requests.try_recv() {
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::PegStatus(req),
+        )) = req
+        {
+            let res = if req.order_id == sideswap_api::HashN([1; 32]) {
+                Err(sideswap_api::Error {
+                    code: sideswap_api::ErrorCode::InvalidRequest,
+                    message: "order not found".to_owned(),
+                })
+            } else {
+                Ok(sideswap_api::Response::PegStatus(sideswap_api::PegStatus {
+                    order_id: req.order_id,
+                    peg_in: true,
+                    addr: "tb1qserver".to_owned(),
+                    addr_recv: test_address(0).to_string(),
+                    list: Vec::new(),
+                    created_at: 1_700_000_000_000,
+                    expires_at: 1_800_000_000_000,
+                    return_address: None,
+                }))
+            };
+            process_ws_event(
+                &mut env.data,
+                WrappedResponse::Response(ResponseMessage::Response(Some(request_id), res)),
+            )
+            .await;
+        }
+    }
+
+    let diagnostics = get_diagnostics(&env.data, api::GetDiagnosticsReq {}).unwrap();
+    assert_eq!(diagnostics.rejected_pegs, [sideswap_api::HashN([1; 32])]);
+    // Still tracked, the server might know the order after the next reconnect
+    assert_eq!(env.data.pegs.len(), 2);
+    assert!(env
+        .data
+        .pegs
+        .values()
+        .all(|peg| peg.status_request_id.is_none()));
+}
+
+#[test]
+fn foreign_addresses_skipped() {
+    let mainnet_address = elements::Address::p2wpkh(
+        &elements::bitcoin::PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap(),
+        None,
+        &elements::AddressParams::LIQUID,
+    );
+    let mut foreign_addresses = 0;
+    let addresses = skip_foreign_addresses(
+        vec![test_address(0), mainnet_address, test_address(1)],
+        Network::LiquidTestnet,
+        "wallet address",
+        &mut foreign_addresses,
+        |address| address,
+    );
+    assert_eq!(addresses, [test_address(0), test_address(1)]);
+    assert_eq!(foreign_addresses, 1);
+}
+
+#[tokio::test]
+async fn peg_complete_notified_once_and_retired() {
+    use sideswap_api::PegTxState::*;
+
+    let mut env = TestEnv::new().await;
+    let order_id = sideswap_api::HashN([9; 32]);
+    env.data
+        .db
+        .add_peg(Peg {
+            order_id: Text(order_id),
+            addr_recv: None,
+            created_by: None,
+            completed_at: None,
+        })
+        .await;
+    env.data.pegs = load_pegs(&env.data.db).await;
+    let mut notif_receiver = env.connect_client(1).await;
+    let complete_notifs = |notifs: Vec<api::Notif>| {
+        notifs
+            .into_iter()
+            .filter_map(|notif| match notif {
+                api::Notif::PegComplete(notif) => Some((notif.order_id, notif.amount_received)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    send_peg_status(
+        &mut env,
+        order_id,
+        vec![peg_tx(1, Done, None), peg_tx(2, Processing, None)],
+    )
+    .await;
+    assert!(complete_notifs(recv_all(&mut notif_receiver)).is_empty());
+
+    for _ in 0..2 {
+        send_peg_status(
+            &mut env,
+            order_id,
+            vec![peg_tx(1, Done, None), peg_tx(2, Done, None)],
+        )
+        .await;
+    }
+    assert_eq!(
+        complete_notifs(recv_all(&mut notif_receiver)),
+        [(order_id, 0.00099 + 0.00099)]
+    );
+    let completed_at = env.data.db.load_pegs().await[0].completed_at;
+    assert!(completed_at.is_some());
+
+    // Not reported again after a restart either
+    env.data.pegs = load_pegs(&env.data.db).await;
+    assert_eq!(env.data.pegs[&order_id].completed_at, completed_at);
+    send_peg_status(
+        &mut env,
+        order_id,
+        vec![peg_tx(1, Done, None), peg_tx(2, Done, None)],
+    )
+    .await;
+    assert!(complete_notifs(recv_all(&mut notif_receiver)).is_empty());
+
+    // Still queried after reconnects until the retention period ends
+    assert_eq!(queried_pegs(&mut env), [order_id]);
+    let peg = env.data.pegs.get_mut(&order_id).unwrap();
+    peg.completed_at = peg
+        .completed_at
+        .map(|completed_at| completed_at - COMPLETED_PEG_RETENTION.as_millis() as i64);
+    assert!(queried_pegs(&mut env).is_empty());
+
+    del_peg(&mut env.data, api::DelPegReq { order_id })
+        .await
+        .unwrap();
+    assert!(env.data.pegs.is_empty());
+    assert!(env.data.db.load_pegs().await.is_empty());
+}
+
+#[tokio::test]
+async fn allowlist_enforcement() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+
+    let send = |address: elements::Address| api::CreateTxReq {
+        recipients: vec![api::Recipient {
+            address: Some(address.to_string()),
+            asset: Some(DealerTicker::LBTC),
+            uri: None,
+            amount: 0.001,
+            send_all: false,
+        }],
+        aggregate_duplicates: false,
+        fee_rate: None,
+        allow_unconfidential: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+    let not_allowed = |err: Option<Error>| match err {
+        Some(Error::AddressNotAllowed(address)) => address == test_address(5),
+        _ => false,
+    };
+
+    // Enforcement is off by default
+    create_tx(&mut env.data, ClientId(0), send(test_address(5)))
+        .await
+        .unwrap();
+
+    env.data.settings.enforce_allowlist = true;
+    assert!(not_allowed(
+        create_tx(&mut env.data, ClientId(0), send(test_address(5)))
+            .await
+            .err()
+    ));
+    assert!(not_allowed(
+        get_quote(
+            &mut env.data,
+            ClientId(1),
+            api::GetQuoteReq {
+                send_asset: DealerTicker::LBTC,
+                recv_asset: DealerTicker::USDT,
+                send_amount: Some(0.001),
+                recv_amount: None,
+                receive_address: Some(test_address(5)),
+                gaid: None,
+                instant_swap: false,
+                allow_partial: false,
+                verify_pset: false,
+                utxos: None,
+                allow_unconfirmed: true,
+                timeout_secs: None,
+                allow_routing: false,
+                wallet: DEFAULT_WALLET.to_owned(),
+            },
+        )
+        .await
+        .err()
+    ));
+
+    add_allowed_address(
+        &mut env.data,
+        api::AddAllowedAddressReq {
+            address: test_address(5),
+            label: Some("exchange".to_owned()),
+            added_by: Some("ops".to_owned()),
+        },
+    )
+    .await
+    .unwrap();
+    create_tx(&mut env.data, ClientId(0), send(test_address(5)))
+        .await
+        .unwrap();
+
+    let list = list_allowed_addresses(&env.data, api::ListAllowedAddressesReq {}).unwrap();
+    assert!(list.enforced);
+    assert_eq!(list.addresses.len(), 1);
+    assert_eq!(list.addresses[0].address, test_address(5));
+    assert_eq!(list.addresses[0].label.as_deref(), Some("exchange"));
+    assert_eq!(env.data.db.load_allowed_addresses().await.len(), 1);
+
+    // Own wallet addresses are always allowed
+    let own = new_address(
+        &mut env.data,
+        api::NewAddressReq {
+            user_note: None,
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap()
+    .address;
+    create_tx(&mut env.data, ClientId(0), send(own))
+        .await
+        .unwrap();
+
+    remove_allowed_address(
+        &mut env.data,
+        api::RemoveAllowedAddressReq {
+            address: test_address(5),
+        },
+    )
+    .await
+    .unwrap();
+    assert!(not_allowed(
+        create_tx(&mut env.data, ClientId(0), send(test_address(5)))
+            .await
+            .err()
+    ));
+    assert!(env.data.db.load_allowed_addresses().await.is_empty());
+
+    let events = env.data.db.load_audit_events().await;
+    assert!(events[0].event.starts_with("allowed address added"));
+    assert!(events[1].event.starts_with("allowed address removed"));
+}
+
+#[tokio::test]
+async fn quote_change_address_reused_until_used() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+
+    async fn quote(env: &mut TestEnv, req: &api::GetQuoteReq, quote_sub_id: QuoteSubId) {
+        let req = api::GetQuoteReq {
+            receive_address: req.receive_address.clone(),
+            gaid: req.gaid.clone(),
+            utxos: req.utxos.clone(),
+            wallet: DEFAULT_WALLET.to_owned(),
+            ..*req
+        };
+        let (res, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), req), async {
+            reply_start_quotes(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                quote_sub_id,
+                vec![quote_notif(quote_sub_id)],
+            )
+            .await;
+            reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+        });
+        res.unwrap();
+    }
+
+    for sub_id in 1..=3 {
+        quote(&mut env, &req, QuoteSubId::new(sub_id)).await;
+    }
+    assert_eq!(env.change_addresses.load(Ordering::Relaxed), 1);
+    assert_eq!(env.wallet().quote_change_address, Some(test_address(1000)));
+
+    // Not used yet
+    process_wallet_event(&mut env.data, DEFAULT_WALLET, sideswap_lwk::Event::Updated).await;
+    quote(&mut env, &req, QuoteSubId::new(4)).await;
+    assert_eq!(env.change_addresses.load(Ordering::Relaxed), 1);
+
+    // Funds received to the change address, a new one is used for the next quote
+    let mut utxo = wallet_tx_to(sideswap_lwk::Chain::Internal, 0).outputs[1]
+        .clone()
+        .unwrap();
+    utxo.script_pubkey = test_address(1000).script_pubkey();
+    *env.wallet_utxos.lock().unwrap() = vec![utxo];
+    process_wallet_event(&mut env.data, DEFAULT_WALLET, sideswap_lwk::Event::Updated).await;
+    assert_eq!(env.wallet().quote_change_address, None);
+    quote(&mut env, &req, QuoteSubId::new(5)).await;
+    assert_eq!(env.change_addresses.load(Ordering::Relaxed), 2);
+    assert_eq!(env.wallet().quote_change_address, Some(test_address(1001)));
+}
+
+#[tokio::test]
+async fn swap_pset_verified_against_quote() {
+    let mut env = TestEnv::new().await;
+    let req = api::GetQuoteReq {
+        verify_pset: true,
+        utxos: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..prepare_get_quote(&mut env).await
+    };
+    let usdt = *env.data.ticker_loader.asset_id(DealerTicker::USDT);
+    let policy_asset = env.data.policy_asset;
+
+    let swap_pset = |recv_amount| {
+        let mut pset = PartiallySignedTransaction::new_v2();
+        pset.add_input(elements::pset::Input::from_prevout(
+            elements::OutPoint::new(elements::Txid::from_byte_array([1; 32]), 0),
+        ));
+        for (address, asset, amount) in [
+            (test_address(0), usdt, recv_amount),
+            (test_address(1000), policy_asset, 900_000),
+        ] {
+            pset.add_output(elements::pset::Output {
+                script_pubkey: address.script_pubkey(),
+                asset: Some(asset),
+                amount: Some(amount),
+                ..Default::default()
+            });
+        }
+        pset
+    };
+
+    async fn quote(
+        env: &mut TestEnv,
+        req: &api::GetQuoteReq,
+        quote_sub_id: QuoteSubId,
+        pset: PartiallySignedTransaction,
+    ) -> Result<api::GetQuoteResp, Error> {
+        let req = api::GetQuoteReq {
+            receive_address: req.receive_address.clone(),
+            gaid: None,
+            utxos: req.utxos.clone(),
+            wallet: DEFAULT_WALLET.to_owned(),
+            ..*req
+        };
+        let (res, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), req), async {
+            reply_start_quotes(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                quote_sub_id,
+                vec![quote_notif(quote_sub_id)],
+            )
+            .await;
+            reply_get_quote_pset(&mut env.ws_requests, &env.ws_responses, pset).await;
+        });
+        res
+    }
+
+    let resp = quote(&mut env, &req, QuoteSubId::new(1), swap_pset(94_900_000))
+        .await
+        .unwrap();
+    let breakdown = resp.pset_breakdown.unwrap();
+    assert_eq!(breakdown.total_in, 0.01);
+    assert_eq!(breakdown.receive_out, resp.recv_amount);
+    assert_eq!(breakdown.change_out, 0.009);
+    assert_eq!(breakdown.network_fee, 0.0);
+
+    let res = quote(&mut env, &req, QuoteSubId::new(2), swap_pset(94_000_000)).await;
+    assert!(matches!(res, Err(Error::PsetMismatch { .. })));
+    assert_eq!(env.data.quotes.len(), 1);
+
+    // Not verified if disabled
+    let req = api::GetQuoteReq {
+        verify_pset: false,
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..req
+    };
+    let resp = quote(&mut env, &req, QuoteSubId::new(3), swap_pset(94_000_000))
+        .await
+        .unwrap();
+    assert!(resp.pset_breakdown.is_none());
+}
+
+#[tokio::test]
+async fn quote_pset_signed_on_accept() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let usdt = *env.data.ticker_loader.asset_id(DealerTicker::USDT);
+
+    let mut pset = PartiallySignedTransaction::new_v2();
+    pset.add_input(elements::pset::Input::from_prevout(
+        elements::OutPoint::new(elements::Txid::from_byte_array([1; 32]), 0),
+    ));
+    pset.add_output(elements::pset::Output {
+        script_pubkey: test_address(0).script_pubkey(),
+        asset: Some(usdt),
+        amount: Some(94_900_000),
+        ..Default::default()
+    });
+
+    async fn quote(
+        env: &mut TestEnv,
+        req: &api::GetQuoteReq,
+        quote_sub_id: QuoteSubId,
+        pset: PartiallySignedTransaction,
+    ) -> api::GetQuoteResp {
+        let req = api::GetQuoteReq {
+            receive_address: req.receive_address.clone(),
+            gaid: None,
+            utxos: None,
+            wallet: req.wallet.clone(),
+            ..*req
+        };
+        let (res, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), req), async {
+            reply_start_quotes(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                quote_sub_id,
+                vec![quote_notif(quote_sub_id)],
+            )
+            .await;
+            reply_get_quote_pset(&mut env.ws_requests, &env.ws_responses, pset).await;
+        });
+        res.unwrap()
+    }
+
+    let resp = quote(&mut env, &req, QuoteSubId::new(1), pset.clone()).await;
+    let stored = &env.data.quotes[&resp.quote_id].pset;
+    assert!(stored.inputs()[0].final_script_witness.is_none());
+
+    let accept_req = |quote_id| api::AcceptQuoteReq {
+        quote_id,
+        user_note: None,
+        idempotency_key: None,
+    };
+    let (res, signed) = tokio::join!(
+        accept_quote(&mut env.data, ClientId(1), accept_req(resp.quote_id)),
+        async {
+            loop {
+                let req = env.ws_requests.recv().await.expect("must be open");
+                if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+                    request_id,
+                    sideswap_api::Request::Market(mkt::Request::TakerSign(req)),
+                )) = req
+                {
+                    env.ws_responses
+                        .send(WrappedResponse::Response(ResponseMessage::Response(
+                            Some(request_id),
+                            Ok(sideswap_api::Response::Market(mkt::Response::TakerSign(
+                                mkt::TakerSignResponse { txid: resp.txid },
+                            ))),
+                        )))
+                        .expect("must not fail");
+                    break pset_check::decode(&req.pset).unwrap();
+                }
+            }
+        }
+    );
+    res.unwrap();
+    assert!(signed.inputs()[0].final_script_witness.is_some());
+
+    // The server reports another swap txid
+    let resp = quote(&mut env, &req, QuoteSubId::new(2), pset.clone()).await;
+    let (res, _) = tokio::join!(
+        accept_quote(&mut env.data, ClientId(1), accept_req(resp.quote_id)),
+        reply_taker_sign(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            Some(elements::Txid::from_byte_array([9; 32]))
+        )
+    );
+    assert!(matches!(res, Err(Error::PsetMismatch { .. })));
+
+    // The quoted wallet UTXO was spent before the quote was accepted
+    let resp = quote(&mut env, &req, QuoteSubId::new(3), pset).await;
+    env.wallet().utxo_data.as_mut().unwrap().reset(Vec::new());
+    let res = accept_quote(&mut env.data, ClientId(1), accept_req(resp.quote_id)).await;
+    assert!(matches!(res, Err(Error::PsetMismatch { .. })));
+    assert!(env.ws_requests.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn quote_received_to_resolved_gaid() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let usdt = *env.data.ticker_loader.asset_id(DealerTicker::USDT);
+    let gaid_req = || api::GetQuoteReq {
+        receive_address: None,
+        gaid: Some("GA2zxWdhAYtREeYCVFTGRhHQmYMPAP".to_owned()),
+        utxos: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..req
+    };
+
+    let quote_sub_id = QuoteSubId::new(1);
+    let (res, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), gaid_req()), async {
+        reply_resolve_gaid(&mut env.ws_requests, &env.ws_responses, test_address(7)).await;
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![quote_notif(quote_sub_id)],
+        )
+        .await;
+        reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+    });
+    assert_eq!(res.unwrap().receive_address, test_address(7));
+    assert!(env.data.gaid_addresses.contains(&(usdt, test_address(7))));
+
+    let error = sideswap_api::Error {
+        code: sideswap_api::ErrorCode::InvalidRequest,
+        message: "unknown GAID".to_owned(),
+    };
+    let (res, ()) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), gaid_req()),
+        reply_market(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            |req| matches!(req, mkt::Request::ResolveGaid(_)),
+            Err(error),
+        ),
+    );
+    assert!(matches!(
+        res,
+        Err(Error::GaidResolveFailed { reason, .. }) if reason == "unknown GAID"
+    ));
+
+    let res = get_quote(
+        &mut env.data,
+        ClientId(1),
+        api::GetQuoteReq {
+            receive_address: Some(test_address(0)),
+            wallet: DEFAULT_WALLET.to_owned(),
+            ..gaid_req()
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::InvalidQuoteReceiver)));
+}
+
+#[tokio::test]
+async fn quote_by_recv_amount() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let usdt = *env.data.ticker_loader.asset_id(DealerTicker::USDT);
+    let recv_req = |recv_amount| api::GetQuoteReq {
+        send_amount: None,
+        recv_amount: Some(recv_amount),
+        receive_address: Some(test_address(0)),
+        gaid: None,
+        utxos: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..req
+    };
+
+    // The fee is paid with the received USDt
+    let quote_sub_id = QuoteSubId::new(1);
+    let mut start_quotes = None;
+    let (res, ()) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), recv_req(0.949)),
+        async {
+            reply_market_request(&mut env.ws_requests, &env.ws_responses, |req| match req {
+                mkt::Request::StartQuotes(req) => {
+                    start_quotes = Some((req.asset_type, req.amount, req.trade_dir));
+                    Some(mkt::Response::StartQuotes(mkt::StartQuotesResponse {
+                        quote_sub_id,
+                        fee_asset: AssetType::Quote,
+                    }))
+                }
+                _ => None,
+            })
+            .await;
+            env.ws_responses
+                .send(quote_notif(quote_sub_id))
+                .expect("must not fail");
+            reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+        }
+    );
+    assert_eq!(
+        start_quotes,
+        Some((AssetType::Quote, 94_900_000, TradeDir::Buy))
+    );
+    let resp = res.unwrap();
+    assert_eq!(resp.send_amount, 0.001);
+    assert_eq!(resp.recv_amount, 0.949);
+
+    // The quote is for another received amount
+    let quote_sub_id = QuoteSubId::new(2);
+    let (res, ()) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), recv_req(0.95)),
+        async {
+            reply_start_quotes(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                quote_sub_id,
+                vec![quote_notif(quote_sub_id)],
+            )
+            .await;
+        }
+    );
+    assert!(matches!(
+        res,
+        Err(Error::NotEnoughAmount { asset_id, required: 95_000_000, available: 94_900_000 })
+            if asset_id == usdt
+    ));
+
+    for (send_amount, recv_amount) in [(None, None), (Some(0.001), Some(0.949))] {
+        let res = get_quote(
+            &mut env.data,
+            ClientId(1),
+            api::GetQuoteReq {
+                send_amount,
+                recv_amount,
+                wallet: DEFAULT_WALLET.to_owned(),
+                ..recv_req(0.949)
+            },
+        )
+        .await;
+        assert!(matches!(res, Err(Error::InvalidQuoteAmount)));
+    }
+}
+
+#[tokio::test]
+async fn quote_metrics_counted() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    // The first connection is not a reconnect
+    env.connect_upstream().await;
+
+    let quote_sub_id = QuoteSubId::new(1);
+    let quote_req = || api::GetQuoteReq {
+        receive_address: Some(test_address(0)),
+        gaid: None,
+        utxos: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..req
+    };
+    let (res, ()) = tokio::join!(
+        process_request(
+            &mut env.data,
+            ClientId(1),
+            api::Req::GetQuote(Box::new(quote_req()))
+        ),
+        async {
+            reply_start_quotes(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                quote_sub_id,
+                vec![quote_notif(quote_sub_id)],
+            )
+            .await;
+            reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+        }
+    );
+    res.unwrap();
+
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::GetQuote(Box::new(api::GetQuoteReq {
+            recv_amount: Some(0.949),
+            wallet: DEFAULT_WALLET.to_owned(),
+            ..quote_req()
+        })),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::InvalidQuoteAmount)));
+
+    let text = env.data.metrics.render(Instant::now());
+    for sample in [
+        "ws_reconnects_total 1",
+        "quotes_requested_total 2",
+        "quotes_failed_total{kind=\"InvalidRequest\"} 1",
+    ] {
+        assert!(text.lines().any(|line| line == sample), "{sample}");
+    }
+}
+
+#[tokio::test]
+async fn identical_quotes_coalesced() {
+    let mut env = TestEnv::new().await;
+    env.data.quote_coalescing = Some(QuoteCoalescing::new(&quote_coalescing::Config {
+        window_ms: 2000,
+        per_client: false,
+    }));
+    let req = prepare_get_quote(&mut env).await;
+    let quote_req = |receive_address| api::GetQuoteReq {
+        receive_address: Some(receive_address),
+        gaid: None,
+        utxos: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..req
+    };
+    let quote_sub_id = QuoteSubId::new(1);
+
+    let (first, ()) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), quote_req(test_address(0))),
+        async {
+            reply_start_quotes(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                quote_sub_id,
+                vec![quote_notif(quote_sub_id)],
+            )
+            .await;
+            reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+        },
+    );
+    let first = first.unwrap();
+
+    // Served by the worker one by one, without new upstream requests
+    for client_id in [ClientId(1), ClientId(2)] {
+        let resp = get_quote(&mut env.data, client_id, quote_req(test_address(0)))
+            .await
+            .unwrap();
+        assert_eq!(resp.quote_id, first.quote_id);
+        assert_eq!(resp.txid, first.txid);
+        assert_eq!(resp.recv_amount, first.recv_amount);
+    }
+    while let Ok(req) = env.ws_requests.try_recv() {
+        assert!(!matches!(
+            req,
+            WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+                _,
+                sideswap_api::Request::Market(mkt::Request::StartQuotes(_)),
+            ))
+        ));
+    }
+
+    // A different receive address needs a new quote
+    let (res, ()) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), quote_req(test_address(1))),
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![WrappedResponse::Disconnected],
+        ),
+    );
+    assert!(matches!(
+        res,
+        Err(Error::WsError(ws_req_sender::Error::Disconnected))
+    ));
+}
+
+#[tokio::test]
+async fn low_balance_quote_reported_or_requoted() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let quote_sub_id = QuoteSubId::new(1);
+    let partial_sub_id = QuoteSubId::new(2);
+
+    let low_balance_notif = || {
+        market_notif(mkt::Notification::Quote(mkt::QuoteNotif {
+            quote_sub_id,
+            asset_pair: usdt_market().asset_pair,
+            asset_type: AssetType::Base,
+            amount: 100_000,
+            trade_dir: TradeDir::Sell,
+            status: mkt::QuoteStatus::LowBalance {
+                base_amount: 100_000,
+                quote_amount: 95_000_000,
+                server_fee: 100_000,
+                fixed_fee: 0,
+                available: 60_000,
+            },
+        }))
+    };
+
+    let (res, ()) = tokio::join!(
+        get_quote(
+            &mut env.data,
+            ClientId(1),
+            api::GetQuoteReq {
+                receive_address: Some(test_address(0)),
+                gaid: None,
+                utxos: None,
+                wallet: DEFAULT_WALLET.to_owned(),
+                ..req
+            }
+        ),
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![low_balance_notif()],
+        ),
+    );
+    let err = api::Error::from(res.err().unwrap());
+    assert!(matches!(err.code, api::ErrorCode::QuoteLowBalance));
+    assert!(matches!(
+        err.details,
+        Some(api::ErrorDetails::QuoteLowBalance {
+            available: 0.0006,
+            required: 0.001,
+        })
+    ));
+    assert_eq!(
+        err.text,
+        "not enough L-BTC for the quote, required: 0.001, available: 0.0006"
+    );
+
+    // The available amount is quoted instead
+    let (res, ()) = tokio::join!(
+        get_quote(
+            &mut env.data,
+            ClientId(1),
+            api::GetQuoteReq {
+                receive_address: Some(test_address(0)),
+                gaid: None,
+                allow_partial: true,
+                utxos: None,
+                wallet: DEFAULT_WALLET.to_owned(),
+                ..req
+            }
+        ),
+        async {
+            reply_start_quotes(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                quote_sub_id,
+                vec![low_balance_notif()],
+            )
+            .await;
+            reply_start_quotes(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                partial_sub_id,
+                vec![market_notif(mkt::Notification::Quote(mkt::QuoteNotif {
+                    quote_sub_id: partial_sub_id,
+                    asset_pair: usdt_market().asset_pair,
+                    asset_type: AssetType::Base,
+                    amount: 60_000,
+                    trade_dir: TradeDir::Sell,
+                    status: mkt::QuoteStatus::Success {
+                        quote_id: QuoteId::new(2),
+                        base_amount: 60_000,
+                        quote_amount: 57_000_000,
+                        server_fee: 60_000,
+                        fixed_fee: 0,
+                        ttl: Duration::from_secs(30).into(),
+                    },
+                }))],
+            )
+            .await;
+            reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+        },
+    );
+    let resp = res.unwrap();
+    assert_eq!(resp.quote_id, QuoteId::new(2));
+    assert_eq!(resp.send_amount, 0.0006);
+    let quote = &env.data.quotes[&resp.quote_id];
+    assert_eq!(quote.quote_sub_id, partial_sub_id);
+    assert!(quote.description.starts_with("swap 0.0006 L-BTC for "));
+}
+
+#[tokio::test]
+async fn peg_fee_estimated_from_server_status() {
+    let mut env = TestEnv::new().await;
+    env.connect_upstream().await;
+
+    async fn estimate(
+        env: &mut TestEnv,
+        peg_in: bool,
+        amount: f64,
+        blocks: Option<i32>,
+    ) -> Result<api::PegFeeEstimateResp, Error> {
+        let (res, ()) = tokio::join!(
+            peg_fee_estimate(
+                &mut env.data,
+                api::PegFeeEstimateReq {
+                    peg_in,
+                    amount,
+                    blocks,
+                },
+            ),
+            reply_server_status(&mut env.ws_requests, &env.ws_responses),
+        );
+        res
+    }
+
+    let resp = estimate(&mut env, true, 0.01, None).await.unwrap();
+    assert_eq!(resp.server_fee_percent, 0.1);
+    assert_eq!(resp.fixed_fee, 0.0);
+    assert_eq!(resp.fee_rate, None);
+    assert_eq!(resp.recv_amount, 0.00999);
+    assert_eq!(resp.min_amount, 0.00001);
+
+    // The fee rate for 2 blocks is used by default
+    let resp = estimate(&mut env, false, 0.01, None).await.unwrap();
+    assert_eq!(resp.fixed_fee, 0.00002);
+    assert_eq!(resp.fee_rate, Some(FeeRateSats::from_raw(10.0)));
+    assert_eq!(resp.recv_amount, 0.00997);
+    assert_eq!(resp.min_amount, 0.001);
+
+    let resp = estimate(&mut env, false, 0.01, Some(6)).await.unwrap();
+    assert_eq!(resp.fixed_fee, 0.00001);
+    assert_eq!(resp.recv_amount, 0.00998);
+
+    let err = estimate(&mut env, false, 0.0005, None).await.err().unwrap();
+    assert!(matches!(
+        api::Error::from(err).details,
+        Some(api::ErrorDetails::PegAmountTooLow { min_amount: 0.001 })
+    ));
+
+    let err = estimate(&mut env, false, 0.01, Some(3))
+        .await
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "no fee rate for 3 blocks, available targets: [2, 6]"
+    );
+}
+
+#[tokio::test]
+async fn send_tx_logs_fields_without_secrets() {
+    let (subscriber, events) = crate::logging::LogSubscriber::capture(crate::logging::Config {
+        format: crate::logging::LogFormat::Json,
+        truncate_addresses: true,
+    });
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 1_000_000));
+
+    let request = |req| {
+        let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
+        let command = Command::Request {
+            client_id: ClientId(3),
+            req,
+            res_sender: res_sender.into(),
+        };
+        (command, res_receiver)
+    };
+
+    let (command, res_receiver) = request(api::Req::CreateTx(api::CreateTxReq {
+        recipients: vec![api::Recipient {
+            address: Some(test_address(5).to_string()),
+            asset: Some(DealerTicker::LBTC),
+            uri: None,
+            amount: 0.001,
+            send_all: false,
+        }],
+        aggregate_duplicates: false,
+        fee_rate: None,
+        allow_unconfidential: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    }));
+    process_command(&mut env.data, command).await;
+    let txid = match res_receiver.await.unwrap() {
+        Ok(api::Resp::CreateTx(resp)) => resp.txid,
+        _ => panic!("CreateTx response expected"),
+    };
+
+    let (command, res_receiver) = request(api::Req::SendTx(api::SendTxReq {
+        txid,
+        user_note: None,
+        wallet_only: true,
+        idempotency_key: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+    }));
+    process_command(&mut env.data, command).await;
+    assert!(matches!(
+        res_receiver.await.unwrap(),
+        Ok(api::Resp::SendTx(_))
+    ));
+
+    let events = events.lock().unwrap().clone();
+    let find = |message: &str| {
+        events
+            .iter()
+            .filter(|event| event.message == message)
+            .cloned()
+            .collect::<Vec<_>>()
+    };
+
+    let processed = find("request processed");
+    assert_eq!(processed.len(), 2);
+    assert_eq!(processed[0].field("request"), Some("CreateTx"));
+    assert_eq!(processed[1].field("request"), Some("SendTx"));
+    for event in processed.iter() {
+        assert_eq!(event.field("client_id"), Some("3"));
+        assert!(event.field("duration_ms").is_some());
+    }
+    let create_trace_id = processed[0].field("trace_id").unwrap();
+    let send_trace_id = processed[1].field("trace_id").unwrap();
+    assert_ne!(create_trace_id, send_trace_id);
+
+    let recipient = find("tx recipient").remove(0);
+    assert_eq!(recipient.field("trace_id"), Some(create_trace_id));
+    let address = recipient.field("address").unwrap();
+    assert!(address.contains("..."));
+    assert!(test_address(5)
+        .to_string()
+        .ends_with(&address[address.len() - 4..]));
+
+    let sent = find("tx sent").remove(0);
+    assert_eq!(sent.field("trace_id"), Some(send_trace_id));
+    assert_eq!(sent.field("txid"), Some(txid.to_string().as_str()));
+    assert_eq!(sent.field("wallet_ok"), Some("true"));
+
+    let full_address = test_address(5).to_string();
+    for event in events.iter() {
+        for (_name, value) in event.fields.iter() {
+            assert!(!value.contains(&full_address));
+            assert!(!value.contains(TEST_MNEMONIC));
+            assert!(!value.contains("cHNldP8"));
+        }
+    }
+}
+
+#[tokio::test]
+async fn balance_history_recorded_and_downsampled() {
+    let mut env = TestEnv::new().await;
+    env.data.settings.balance_history = Some(crate::balance_history::Config {
+        interval_seconds: 3600,
+        retention_days: Some(2),
+    });
+    let policy_asset = env.data.policy_asset;
+    let usdt = *env.data.ticker_loader.asset_id(DealerTicker::USDT);
+    let hour = 3_600_000;
+    let day = 24 * hour;
+    let start = 100 * day;
+
+    // Not synced yet
+    record_balance_snapshot(&env.data, TimestampMs::from_millis(start)).await;
+    assert!(env
+        .data
+        .db
+        .load_balance_history(DEFAULT_WALLET, 0, i64::MAX)
+        .await
+        .is_empty());
+
+    // 12 hourly snapshots, USDt is received after 3 hours and spent after 6 hours
+    for index in 0..12u64 {
+        let mut balances = BTreeMap::from([(policy_asset, 100_000 + index)]);
+        if (3..6).contains(&index) {
+            balances.insert(usdt, 5_000_000);
+        }
+        env.wallet().balances = Some(balances);
+        record_balance_snapshot(&env.data, TimestampMs::from_millis(start + index * hour)).await;
+    }
+
+    let history = |asset, from: Option<u64>, to: Option<u64>, granularity_seconds| {
+        api::GetBalanceHistoryReq {
+            asset,
+            from: from.map(TimestampMs::from_millis),
+            to: to.map(TimestampMs::from_millis),
+            granularity_seconds,
+            wallet: DEFAULT_WALLET.to_owned(),
+        }
+    };
+
+    let resp = get_balance_history(&env.data, history(None, None, None, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.granularity_seconds, None);
+    assert_eq!(resp.points.len(), 12);
+    assert_eq!(resp.points[0].timestamp.millis(), start);
+    assert_eq!(
+        resp.points[0].balances,
+        [(DealerTicker::LBTC, 0.001)].into()
+    );
+    assert_eq!(
+        resp.points[3].balances,
+        [(DealerTicker::LBTC, 0.00100003), (DealerTicker::USDT, 0.05)].into()
+    );
+    // Zero balance is reported after the asset was spent
+    assert_eq!(resp.points[6].balances.get(&DealerTicker::USDT), Some(&0.0));
+
+    let resp = get_balance_history(
+        &env.data,
+        history(
+            Some(DealerTicker::USDT),
+            Some(start + 2 * hour),
+            Some(start + 7 * hour),
+            None,
+        ),
+    )
+    .await
+    .unwrap();
+    let usdt_series = resp
+        .points
+        .iter()
+        .map(|point| point.balances.get(&DealerTicker::USDT).copied())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        usdt_series,
+        [
+            None,
+            Some(0.05),
+            Some(0.05),
+            Some(0.05),
+            Some(0.0),
+            Some(0.0)
+        ]
+    );
+
+    // One point per 4 hours, the last snapshot of every period
+    let resp = get_balance_history(&env.data, history(None, None, None, Some(4 * 3600)))
+        .await
+        .unwrap();
+    assert_eq!(resp.granularity_seconds, Some(4 * 3600));
+    let timestamps = resp
+        .points
+        .iter()
+        .map(|point| (point.timestamp.millis() - start) / hour)
+        .collect::<Vec<_>>();
+    assert_eq!(timestamps, [3, 7, 11]);
+
+    assert!(matches!(
+        get_balance_history(&env.data, history(None, Some(start), Some(start - 1), None)).await,
+        Err(Error::InvalidHistoryRequest(_))
+    ));
+
+    // Older snapshots are deleted with the retention period
+    record_balance_snapshot(
+        &env.data,
+        TimestampMs::from_millis(start + 2 * day + 5 * hour),
+    )
+    .await;
+    let resp = get_balance_history(&env.data, history(None, None, None, None))
+        .await
+        .unwrap();
+    assert_eq!(resp.points.len(), 12 - 5 + 1);
+
+    // Every wallet has its own history
+    let mut cold = WalletData::new(mpsc::channel().0);
+    cold.balances = Some(BTreeMap::from([(usdt, 7_000_000)]));
+    env.data.wallets.insert("cold".to_owned(), cold);
+    record_balance_snapshot(
+        &env.data,
+        TimestampMs::from_millis(start + 2 * day + 6 * hour),
+    )
+    .await;
+    let cold_history = api::GetBalanceHistoryReq {
+        wallet: "cold".to_owned(),
+        ..history(None, None, None, None)
+    };
+    let resp = get_balance_history(&env.data, cold_history).await.unwrap();
+    assert_eq!(resp.points.len(), 1);
+    assert_eq!(
+        resp.points[0].balances,
+        [(DealerTicker::LBTC, 0.0), (DealerTicker::USDT, 0.07)].into()
+    );
+    let resp = get_balance_history(&env.data, history(None, None, None, None))
+        .await
+        .unwrap();
+    // One more old snapshot is deleted
+    assert_eq!(resp.points.len(), 12 - 6 + 2);
+    assert!(resp
+        .points
+        .iter()
+        .all(|point| point.balances.get(&DealerTicker::USDT) != Some(&0.07)));
+
+    let unknown_history = api::GetBalanceHistoryReq {
+        wallet: "nope".to_owned(),
+        ..history(None, None, None, None)
+    };
+    let res = get_balance_history(&env.data, unknown_history).await;
+    assert!(matches!(res, Err(Error::UnknownWallet { .. })));
+}
+
+#[test]
+fn asset_amount_precision() {
+    let precision_0 = AssetPrecision::ZERO;
+    let precision_2 = AssetPrecision::TWO;
+    let precision_8 = AssetPrecision::BITCOIN_PRECISION;
+
+    // (precision, amount, expected integer amount)
+    let cases = [
+        (precision_0, 5.0, Some(5)),
+        (precision_0, 0.0, Some(0)),
+        (
+            precision_0,
+            9_007_199_254_740_991.0,
+            Some(9_007_199_254_740_991),
+        ),
+        (precision_0, 5.1, None),
+        (precision_0, 5.000000001, None),
+        (precision_0, -5.0, None),
+        (precision_2, 5.0, Some(500)),
+        (precision_2, 0.29, Some(29)),
+        (precision_2, 1.005, None),
+        (precision_2, 0.1 + 0.2, None),
+        (precision_8, 0.3, Some(30_000_000)),
+        (precision_8, 0.00000001, Some(1)),
+        (precision_8, 21_000_000.0, Some(2_100_000_000_000_000)),
+        (precision_8, 0.000000001, None),
+        (precision_8, f64::NAN, None),
+        (precision_8, f64::INFINITY, None),
+    ];
+    for (precision, amount, expected) in cases {
+        let res = try_convert_asset_amount(amount, precision);
+        assert_eq!(res.as_ref().ok().copied(), expected, "{amount} {precision}");
+        match res {
+            Ok(int_amount) => {
+                // Displayed amounts must round-trip exactly
+                let displayed = asset_float_amount_(int_amount, precision);
+                assert_eq!(displayed.to_string(), amount.to_string());
+                assert_eq!(
+                    try_convert_asset_amount(displayed, precision).unwrap(),
+                    int_amount
+                );
+            }
+            Err(err) => {
+                assert!(matches!(err, Error::InvalidAssetAmount(_, _)));
+                assert!(err
+                    .to_string()
+                    .contains(&format!("precision is {precision}")));
+            }
+        }
+    }
+
+    assert_eq!(asset_float_amount_(5, precision_0).to_string(), "5");
+    assert_eq!(asset_float_amount_(510, precision_2).to_string(), "5.1");
+    assert_eq!(
+        asset_float_amount_(1, precision_8).to_string(),
+        "0.00000001"
+    );
+}
+
+#[tokio::test]
+async fn precision_0_amounts_accepted_in_any_form() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    let whole_asset_id = *env.data.ticker_loader.asset_id(whole_ticker());
+    env.wallet().utxo_data = Some(test_utxo_data(whole_asset_id, 10));
+
+    let create_tx_req = |amount: serde_json::Value| {
+        serde_json::from_value::<api::Req>(serde_json::json!({
+            "CreateTx": {
+                "recipients": [{
+                    "address": test_address(5).to_string(),
+                    "asset": "WHOLE",
+                    "amount": amount,
+                }],
+            },
+        }))
+    };
+
+    for amount in [
+        serde_json::json!(5),
+        serde_json::json!(5.0),
+        serde_json::json!("5"),
+        serde_json::json!("5.0"),
+    ] {
+        match create_tx_req(amount.clone()).unwrap() {
+            api::Req::CreateTx(req) => {
+                assert_eq!(req.recipients[0].amount, 5.0, "{amount}");
+                assert!(
+                    create_tx(&mut env.data, ClientId(0), req).await.is_ok(),
+                    "{amount}"
+                );
+            }
+            _ => panic!("CreateTx request expected"),
+        }
+    }
+
+    for amount in [serde_json::json!(5.1), serde_json::json!("5.1")] {
+        let req = match create_tx_req(amount).unwrap() {
+            api::Req::CreateTx(req) => req,
+            _ => panic!("CreateTx request expected"),
+        };
+        let err = create_tx(&mut env.data, ClientId(0), req)
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            Error::InvalidAssetAmount(_, AssetPrecision::ZERO)
+        ));
+        assert!(err.to_string().contains("at most 0 decimal places"));
+    }
+
+    for amount in [serde_json::json!("five"), serde_json::json!(null)] {
+        assert!(create_tx_req(amount).is_err());
+    }
+}
+
+#[tokio::test]
+async fn sign_message_with_own_address() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    let address = elements::Address::from_str(TEST_WALLET_ADDRESS).expect("must not fail");
+    env.wallet().addresses.insert(
+        0,
+        models::Address {
+            wallet: DEFAULT_WALLET.to_owned(),
+            ind: 0,
+            address: Text(address.clone()),
+            user_note: None,
+        },
+    );
+
+    for index_or_address in [serde_json::json!(0), serde_json::json!(TEST_WALLET_ADDRESS)] {
+        let req = serde_json::from_value::<api::SignMessageReq>(serde_json::json!({
+            "index_or_address": index_or_address,
+            "message": TEST_MESSAGE,
+        }))
+        .expect("must not fail");
+        let resp = sign_message(&mut env.data, req)
+            .await
+            .expect("must not fail");
+        assert_eq!(resp.index, 0);
+        assert_eq!(resp.address, address);
+        assert_eq!(resp.signature, TEST_MESSAGE_SIGNATURE);
+    }
+
+    let resp = verify_message(api::VerifyMessageReq {
+        address: address.clone(),
+        message: TEST_MESSAGE.to_owned(),
+        signature: TEST_MESSAGE_SIGNATURE.to_owned(),
+    })
+    .expect("must not fail");
+    assert!(resp.valid);
+
+    let events = env.data.db.load_audit_events().await;
+    assert_eq!(events.len(), 2);
+    assert!(events[0].event.contains(TEST_WALLET_ADDRESS));
+    assert!(events[0].event.contains(TEST_MESSAGE));
+
+    let res = sign_message(
+        &mut env.data,
+        api::SignMessageReq {
+            index_or_address: api::IndexOrAddress::Index(1),
+            message: TEST_MESSAGE.to_owned(),
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NotOwnAddress(_))));
+
+    let res = sign_message(
+        &mut env.data,
+        api::SignMessageReq {
+            index_or_address: api::IndexOrAddress::Address(test_address(0)),
+            message: TEST_MESSAGE.to_owned(),
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NotOwnAddress(_))));
+
+    let res = sign_message(
+        &mut env.data,
+        api::SignMessageReq {
+            index_or_address: api::IndexOrAddress::Index(0),
+            message: "x".repeat(MAX_MESSAGE_LEN + 1),
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::MessageTooLong(MAX_MESSAGE_LEN))));
+
+    let res = sign_message(
+        &mut env.data,
+        api::SignMessageReq {
+            index_or_address: api::IndexOrAddress::Index(0),
+            message: TEST_MESSAGE.to_owned(),
+            wallet: "nope".to_owned(),
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::UnknownWallet { .. })));
+
+    let config = crate::signing_lock::Config {
+        timeout_minutes: 1,
+        password_hash: crate::signing_lock::password_hash("secret"),
+    };
+    env.data.signing_lock = Some(SigningLock::new(&config, Instant::now()));
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::SignMessage(api::SignMessageReq {
+            index_or_address: api::IndexOrAddress::Index(0),
+            message: TEST_MESSAGE.to_owned(),
+            wallet: DEFAULT_WALLET.to_owned(),
+        }),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::Locked)));
+    assert_eq!(env.data.db.load_audit_events().await.len(), 2);
+}
+
+#[test]
+fn verify_message_rejects_mismatches() {
+    let address = elements::Address::from_str(TEST_WALLET_ADDRESS).expect("must not fail");
+    let verify = |address: &elements::Address, message: &str, signature: &str| {
+        verify_message(api::VerifyMessageReq {
+            address: address.clone(),
+            message: message.to_owned(),
+            signature: signature.to_owned(),
+        })
+        .map(|resp| resp.valid)
+    };
+
+    assert!(!verify(&address, "I control this address!", TEST_MESSAGE_SIGNATURE).unwrap());
+    assert!(!verify(&test_address(0), TEST_MESSAGE, TEST_MESSAGE_SIGNATURE).unwrap());
+
+    // The same key, but the P2SH-P2WPKH header
+    let mut signature = b64::decode(TEST_MESSAGE_SIGNATURE).unwrap();
+    signature[0] -= 4;
+    assert!(!verify(&address, TEST_MESSAGE, &b64::encode(&signature)).unwrap());
+
+    // Compressed P2PKH headers are accepted for segwit addresses
+    signature[0] -= 4;
+    assert!(verify(&address, TEST_MESSAGE, &b64::encode(&signature)).unwrap());
+
+    assert!(matches!(
+        verify(&address, TEST_MESSAGE, "not base64"),
+        Err(Error::Lwk(sideswap_lwk::Error::InvalidArg(_)))
+    ));
+    assert!(matches!(
+        verify(&address, TEST_MESSAGE, &b64::encode(&[0; 65])),
+        Err(Error::Lwk(sideswap_lwk::Error::InvalidArg(_)))
+    ));
+    assert!(matches!(
+        verify(
+            &address,
+            &"x".repeat(MAX_MESSAGE_LEN + 1),
+            TEST_MESSAGE_SIGNATURE
+        ),
+        Err(Error::MessageTooLong(_))
+    ));
+}
+
+/// Decode a message from a newer server version the same way as `ws::auto::run` does
+fn upstream_msg(value: serde_json::Value) -> Option<WrappedResponse> {
+    ws::auto::parse_response(&value.to_string()).map(WrappedResponse::Response)
+}
+
+#[tokio::test]
+async fn upstream_protocol_drift_tolerated() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let quote_req = || api::GetQuoteReq {
+        receive_address: Some(test_address(0)),
+        gaid: None,
+        utxos: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..req
+    };
+    let skipped_before = ws::auto::skipped_messages();
+
+    // A new notification type is skipped
+    assert!(upstream_msg(serde_json::json!({
+        "Notification": {"Market": {"future_notif": {"value": 1}}},
+    }))
+    .is_none());
+
+    // A new response type fails only the request it was sent for
+    let (res, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), quote_req()), async {
+        loop {
+            let req = env.ws_requests.recv().await.expect("must be open");
+            if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+                request_id,
+                sideswap_api::Request::Market(mkt::Request::StartQuotes(_)),
+            )) = req
+            {
+                let msg = upstream_msg(serde_json::json!({
+                    "Response": [request_id, {"Ok": {"Market": {"future_response": {}}}}],
+                }))
+                .expect("must be replaced");
+                env.ws_responses.send(msg).expect("must not fail");
+                break;
+            }
+        }
+    });
+    assert!(matches!(
+        res,
+        Err(Error::WsError(ws_req_sender::Error::BackendError(_, _)))
+    ));
+
+    // A new quote status (and a new field) fails the quote without waiting for the timeout
+    let quote_sub_id = QuoteSubId::new(1);
+    let mut future_quote = serde_json::to_value(match quote_notif(quote_sub_id) {
+        WrappedResponse::Response(msg) => msg,
+        _ => unreachable!(),
+    })
+    .unwrap();
+    let quote = &mut future_quote["Notification"]["Market"]["quote"];
+    quote["status"] = serde_json::json!({"Throttled": {"retry_in": 5}});
+    quote["future_field"] = serde_json::json!(true);
+    let future_quote = upstream_msg(future_quote).expect("must be replaced");
+
+    let (res, ()) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), quote_req()),
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![future_quote],
+        ),
+    );
+    match res {
+        Err(Error::QuoteError(error_msg)) => {
+            assert_eq!(error_msg, "unsupported quote status: Throttled")
+        }
+        _ => panic!("quote error expected"),
+    }
+
+    // Unknown fields in known messages are ignored
+    let mut known_quote = serde_json::to_value(match quote_notif(quote_sub_id) {
+        WrappedResponse::Response(msg) => msg,
+        _ => unreachable!(),
+    })
+    .unwrap();
+    known_quote["Notification"]["Market"]["quote"]["future_field"] = serde_json::json!(1);
+    let known_quote = upstream_msg(known_quote).expect("must be decoded");
+
+    let (res, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), quote_req()), async {
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![known_quote],
+        )
+        .await;
+        reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+    },);
+    assert!(res.is_ok());
+
+    let resp = get_server_info(&env.data, api::GetServerInfoReq {}).unwrap();
+    assert_eq!(resp.skipped_upstream_msgs - skipped_before, 3);
+}
+
+/// Respond to the next StopQuotes request
+async fn reply_stop_quotes(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+) {
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::Market(mkt::Request::StopQuotes(_)),
+        )) = req
+        {
+            ws_responses
+                .send(WrappedResponse::Response(ResponseMessage::Response(
+                    Some(request_id),
+                    Ok(sideswap_api::Response::Market(mkt::Response::StopQuotes(
+                        mkt::StopQuotesResponse {},
+                    ))),
+                )))
+                .expect("must not fail");
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn cancel_quote_stops_active_session() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+
+    let mut quote_ids = Vec::new();
+    for sub_id in 1..=2 {
+        let quote_sub_id = QuoteSubId::new(sub_id);
+        let (resp, ()) = tokio::join!(
+            get_quote(
+                &mut env.data,
+                ClientId(1),
+                api::GetQuoteReq {
+                    receive_address: req.receive_address.clone(),
+                    gaid: req.gaid.clone(),
+                    utxos: None,
+                    wallet: DEFAULT_WALLET.to_owned(),
+                    ..req
+                }
+            ),
+            async {
+                reply_start_quotes(
+                    &mut env.ws_requests,
+                    &env.ws_responses,
+                    quote_sub_id,
+                    vec![quote_notif(quote_sub_id)],
+                )
+                .await;
+                reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+            }
+        );
+        quote_ids.push(resp.unwrap().quote_id);
+    }
+    assert_eq!(env.data.quotes.len(), 2);
+
+    // The first quoting session was replaced already, nothing is sent upstream
+    cancel_quote(
+        &mut env.data,
+        api::CancelQuoteReq {
+            quote_id: quote_ids[0],
+        },
+    )
+    .await
+    .unwrap();
+    assert!(env.ws_requests.try_recv().is_err());
+    assert!(!env.data.quotes.contains_key(&quote_ids[0]));
+
+    let (res, ()) = tokio::join!(
+        cancel_quote(
+            &mut env.data,
+            api::CancelQuoteReq {
+                quote_id: quote_ids[1],
+            },
+        ),
+        reply_stop_quotes(&mut env.ws_requests, &env.ws_responses),
+    );
+    res.unwrap();
+    assert!(env.data.quotes.is_empty());
+
+    let res = cancel_quote(
+        &mut env.data,
+        api::CancelQuoteReq {
+            quote_id: quote_ids[1],
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NoQuote)));
+    let res = accept_quote(
+        &mut env.data,
+        ClientId(0),
+        api::AcceptQuoteReq {
+            quote_id: quote_ids[1],
+            user_note: None,
+            idempotency_key: None,
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NoQuote)));
+}
+
+#[tokio::test]
+async fn quotes_invalidated_by_reconnect() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let mut client = env.connect_client(1).await;
+
+    let quote_sub_id = QuoteSubId::new(1);
+    let (resp, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), req), async {
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![quote_notif(quote_sub_id)],
+        )
+        .await;
+        reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+    });
+    let quote_id = resp.unwrap().quote_id;
+    recv_all(&mut client);
+
+    process_ws_event(&mut env.data, WrappedResponse::Disconnected).await;
+    assert!(matches!(
+        &recv_all(&mut client)[..],
+        [api::Notif::ServerConnection(api::ServerConnectionNotif {
+            connected: false
+        })]
+    ));
+    let mut late_client = env.connect_client(2).await;
+    assert!(recv_all(&mut late_client).iter().any(|notif| matches!(
+        notif,
+        api::Notif::ServerConnection(api::ServerConnectionNotif { connected: false })
+    )));
+
+    process_ws_event(&mut env.data, WrappedResponse::Connected).await;
+    assert!(recv_all(&mut client).iter().any(|notif| matches!(
+        notif,
+        api::Notif::ServerConnection(api::ServerConnectionNotif { connected: true })
+    )));
+    while env.ws_requests.try_recv().is_ok() {}
+
+    // The new session does not know the quote, nothing is sent upstream
+    let res = accept_quote(
+        &mut env.data,
+        ClientId(1),
+        api::AcceptQuoteReq {
+            quote_id,
+            user_note: None,
+            idempotency_key: None,
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::QuoteInvalidatedByReconnect)));
+    assert!(env.ws_requests.try_recv().is_err());
+    assert!(env.data.monitored_txs.is_empty());
+}
+
+#[tokio::test]
+async fn accepted_quotes_recorded_as_swaps() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let quote_sub_id = QuoteSubId::new(1);
+
+    let (resp, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), req), async {
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![quote_notif(quote_sub_id)],
+        )
+        .await;
+        reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+    });
+    let resp = resp.unwrap();
+
+    let (res, ()) = tokio::join!(
+        accept_quote(
+            &mut env.data,
+            ClientId(1),
+            api::AcceptQuoteReq {
+                quote_id: resp.quote_id,
+                user_note: None,
+                idempotency_key: None,
+            },
+        ),
+        reply_market_request(&mut env.ws_requests, &env.ws_responses, |req| match req {
+            mkt::Request::TakerSign(_) => Some(mkt::Response::TakerSign(mkt::TakerSignResponse {
+                txid: resp.txid,
+            })),
+            _ => None,
+        })
+    );
+    let accepted = res.unwrap();
+
+    async fn get_swaps_resp(
+        data: &Data,
+        from: Option<u64>,
+        to: Option<u64>,
+    ) -> Result<api::GetSwapsResp, Error> {
+        let req = api::GetSwapsReq {
+            from: from.map(TimestampMs::from_millis),
+            to: to.map(TimestampMs::from_millis),
+        };
+        get_swaps(data, req).await?.await
+    }
+
+    let swaps = get_swaps_resp(&env.data, None, None).await.unwrap().swaps;
+    assert_eq!(swaps.len(), 1);
+    let swap = &swaps[0];
+    assert_eq!(swap.quote_id, resp.quote_id);
+    assert_eq!(swap.txid, resp.txid);
+    assert_eq!(swap.send_asset, DealerTicker::LBTC);
+    assert_eq!(swap.send_amount, 0.001);
+    assert_eq!(swap.recv_asset, DealerTicker::USDT);
+    assert_eq!(swap.recv_amount, resp.recv_amount);
+    assert_eq!(swap.price, 950.0);
+    assert_eq!(swap.fee_asset, DealerTicker::USDT);
+    assert_eq!(swap.fee_amount, 0.001);
+    assert_eq!(swap.server_fee, 100_000);
+    assert_eq!(swap.fixed_fee, 0);
+    assert_eq!(swap.status, api::TxStatus::NotFound);
+    assert_eq!(swap.reference.as_deref(), Some(accepted.reference.as_str()));
+
+    let mut wallet_tx = wallet_tx_to(sideswap_lwk::Chain::External, 0);
+    wallet_tx.txid = resp.txid;
+    wallet_tx.height = Some(100);
+    *env.wallet_txs.lock().unwrap() = vec![wallet_tx];
+    let swaps = get_swaps_resp(&env.data, None, None).await.unwrap().swaps;
+    assert_eq!(swaps[0].status, api::TxStatus::Confirmed);
+
+    let timestamp = swaps[0].timestamp.millis();
+    let swaps = get_swaps_resp(&env.data, Some(timestamp), Some(timestamp))
+        .await
+        .unwrap()
+        .swaps;
+    assert_eq!(swaps.len(), 1);
+    let swaps = get_swaps_resp(&env.data, Some(timestamp + 1), None)
+        .await
+        .unwrap()
+        .swaps;
+    assert!(swaps.is_empty());
+    let res = get_swaps_resp(&env.data, Some(timestamp), Some(timestamp - 1)).await;
+    assert!(matches!(res, Err(Error::InvalidSwapsRequest(_))));
+}
+
+fn cents_market() -> mkt::MarketInfo {
+    mkt::MarketInfo {
+        asset_pair: mkt::AssetPair {
+            base: Network::LiquidTestnet.d().policy_asset,
+            quote: AssetId::from_slice(&[9; 32]).expect("must not fail"),
+        },
+        fee_asset: AssetType::Quote,
+        type_: sideswap_api::MarketType::Stablecoin,
+    }
+}
+
+/// Respond with the quote of a routed leg, the PSET spends `input` (so the legs have different txids)
+async fn reply_leg_quote(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+    quote_sub_id: QuoteSubId,
+    (trade_dir, base_amount, quote_amount, server_fee): (TradeDir, u64, u64, u64),
+    input: u8,
+) -> elements::Txid {
+    let notif = market_notif(mkt::Notification::Quote(mkt::QuoteNotif {
+        quote_sub_id,
+        asset_pair: usdt_market().asset_pair,
+        asset_type: AssetType::Base,
+        amount: base_amount,
+        trade_dir,
+        status: mkt::QuoteStatus::Success {
+            quote_id: QuoteId::new(quote_sub_id.value()),
+            base_amount,
+            quote_amount,
+            server_fee,
+            fixed_fee: 0,
+            ttl: Duration::from_secs(30).into(),
+        },
+    }));
+    reply_start_quotes(ws_requests, ws_responses, quote_sub_id, vec![notif]).await;
+    let mut pset = PartiallySignedTransaction::new_v2();
+    pset.add_input(elements::pset::Input::from_prevout(
+        elements::OutPoint::new(elements::Txid::from_byte_array([input; 32]), 0),
+    ));
+    let txid = pset.extract_tx().expect("must not fail").txid();
+    reply_get_quote_pset(ws_requests, ws_responses, pset).await;
+    txid
+}
+
+/// Respond to the next TakerSign request (with an error if `txid` is `None`)
+async fn reply_taker_sign(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+    txid: Option<elements::Txid>,
+) -> QuoteId {
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::Market(mkt::Request::TakerSign(req)),
+        )) = req
+        {
+            let resp = match txid {
+                Some(txid) => Ok(sideswap_api::Response::Market(mkt::Response::TakerSign(
+                    mkt::TakerSignResponse { txid },
+                ))),
+                None => Err(sideswap_api::Error {
+                    code: sideswap_api::ErrorCode::ServerError,
+                    message: "swap failed".to_owned(),
+                }),
+            };
+            ws_responses
+                .send(WrappedResponse::Response(ResponseMessage::Response(
+                    Some(request_id),
+                    resp,
+                )))
+                .expect("must not fail");
+            break req.quote_id;
+        }
+    }
+}
+
+#[tokio::test]
+async fn quote_routed_through_lbtc() {
+    let mut env = TestEnv::new().await;
+    prepare_get_quote(&mut env).await;
+    let usdt = *env.data.ticker_loader.asset_id(DealerTicker::USDT);
+    env.data.markets = vec![usdt_market(), cents_market()];
+    env.wallet().utxo_data = Some(test_mixed_utxos(&[
+        (env.data.policy_asset, 1_000_000),
+        (usdt, 200_000_000),
+    ]));
+    let routed_req = |send_amount, recv_amount, allow_routing| api::GetQuoteReq {
+        send_asset: DealerTicker::USDT,
+        recv_asset: cents_ticker(),
+        send_amount,
+        recv_amount,
+        receive_address: Some(test_address(0)),
+        gaid: None,
+        instant_swap: false,
+        allow_partial: false,
+        verify_pset: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        timeout_secs: None,
+        allow_routing,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+    // 1 USDt -> 0.001 L-BTC (the fee is paid with USDt), 0.001 L-BTC -> 94.9 CENTS
+    let first_leg = (TradeDir::Buy, 100_000, 99_900_000, 100_000);
+    let second_leg = (TradeDir::Sell, 100_000, 9_500, 10);
+
+    let res = get_quote(
+        &mut env.data,
+        ClientId(1),
+        routed_req(Some(1.0), None, false),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NoMarket)));
+
+    let (res, (first_txid, second_txid)) = tokio::join!(
+        get_quote(
+            &mut env.data,
+            ClientId(1),
+            routed_req(Some(1.0), None, true)
+        ),
+        async {
+            let first_txid = reply_leg_quote(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                QuoteSubId::new(1),
+                first_leg,
+                2,
+            )
+            .await;
+            let second_txid = reply_leg_quote(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                QuoteSubId::new(2),
+                second_leg,
+                3,
+            )
+            .await;
+            (first_txid, second_txid)
+        }
+    );
+    let resp = res.unwrap();
+    assert_eq!(resp.send_amount, 1.0);
+    assert_eq!(resp.recv_amount, 94.9);
+    assert_eq!(resp.txid, first_txid);
+    assert_eq!(resp.receive_address, test_address(0));
+    let route = resp.route.unwrap();
+    assert_eq!(route.intermediate_asset, DealerTicker::LBTC);
+    assert_eq!(route.intermediate_amount, 0.001);
+    assert_eq!(route.first_quote_id, resp.quote_id);
+    assert_eq!(route.second_txid, second_txid);
+    // The first leg is received by the wallet
+    let leg_address = env.wallet().quote_change_address.clone().unwrap();
+    assert_eq!(env.data.quotes[&resp.quote_id].receive_address, leg_address);
+
+    // The legs are accepted in order
+    let (res, quote_ids) = tokio::join!(
+        accept_quote(
+            &mut env.data,
+            ClientId(1),
+            api::AcceptQuoteReq {
+                quote_id: resp.quote_id,
+                user_note: None,
+                idempotency_key: None,
+            },
+        ),
+        async {
+            let first =
+                reply_taker_sign(&mut env.ws_requests, &env.ws_responses, Some(first_txid)).await;
+            let second =
+                reply_taker_sign(&mut env.ws_requests, &env.ws_responses, Some(second_txid)).await;
+            [first, second]
+        }
+    );
+    assert_eq!(quote_ids, [route.first_quote_id, route.second_quote_id]);
+    let accepted = res.unwrap();
+    assert_eq!(accepted.txid, first_txid);
+    let accepted_route = accepted.route.unwrap();
+    assert_eq!(accepted_route.second_txid, Some(second_txid));
+    assert!(accepted_route.second_reference.is_some());
+    assert_eq!(accepted_route.second_leg_error, None);
+    assert!(env.data.monitored_txs.contains_key(&first_txid));
+    assert!(env.data.monitored_txs.contains_key(&second_txid));
+
+    // Quoted by the received amount, the second leg is quoted first
+    let (res, ()) = tokio::join!(
+        get_quote(
+            &mut env.data,
+            ClientId(1),
+            routed_req(None, Some(94.9), true)
+        ),
+        async {
+            reply_leg_quote(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                QuoteSubId::new(3),
+                second_leg,
+                4,
+            )
+            .await;
+            reply_leg_quote(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                QuoteSubId::new(4),
+                first_leg,
+                5,
+            )
+            .await;
+        }
+    );
+    let resp = res.unwrap();
+    assert_eq!(resp.quote_id, QuoteId::new(4));
+    assert_eq!((resp.send_amount, resp.recv_amount), (1.0, 94.9));
+
+    // The second leg fails, the first leg is completed
+    let (res, ()) = tokio::join!(
+        accept_quote(
+            &mut env.data,
+            ClientId(1),
+            api::AcceptQuoteReq {
+                quote_id: resp.quote_id,
+                user_note: None,
+                idempotency_key: None,
+            },
+        ),
+        async {
+            reply_taker_sign(&mut env.ws_requests, &env.ws_responses, Some(resp.txid)).await;
+            reply_taker_sign(&mut env.ws_requests, &env.ws_responses, None).await;
+        }
+    );
+    let accepted = res.unwrap();
+    assert_eq!(accepted.txid, resp.txid);
+    let accepted_route = accepted.route.unwrap();
+    assert_eq!(accepted_route.intermediate_amount, 0.001);
+    assert_eq!(accepted_route.second_txid, None);
+    assert!(accepted_route.second_leg_error.is_some());
+
+    // The first leg is forgotten if the second leg can't be quoted
+    let quotes = env.data.quotes.len();
+    let (res, ()) = tokio::join!(
+        get_quote(
+            &mut env.data,
+            ClientId(1),
+            routed_req(Some(1.0), None, true)
+        ),
+        async {
+            reply_leg_quote(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                QuoteSubId::new(5),
+                first_leg,
+                6,
+            )
+            .await;
+            reply_market_request(&mut env.ws_requests, &env.ws_responses, |req| match req {
+                mkt::Request::StartQuotes(_) => {
+                    Some(mkt::Response::StartQuotes(mkt::StartQuotesResponse {
+                        quote_sub_id: QuoteSubId::new(6),
+                        fee_asset: AssetType::Quote,
+                    }))
+                }
+                _ => None,
+            })
+            .await;
+            env.ws_responses
+                .send(market_notif(mkt::Notification::Quote(mkt::QuoteNotif {
+                    quote_sub_id: QuoteSubId::new(6),
+                    asset_pair: cents_market().asset_pair,
+                    asset_type: AssetType::Base,
+                    amount: 100_000,
+                    trade_dir: TradeDir::Sell,
+                    status: mkt::QuoteStatus::Error {
+                        error_msg: "no liquidity".to_owned(),
+                    },
+                })))
+                .expect("must not fail");
+        }
+    );
+    assert!(matches!(res, Err(Error::QuoteError(_))));
+    assert_eq!(env.data.quotes.len(), quotes);
+
+    // The first leg output can't pay the second leg, the wallet must have the intermediate amount
+    env.wallet().utxo_data = Some(test_mixed_utxos(&[
+        (env.data.policy_asset, 50_000),
+        (usdt, 200_000_000),
+    ]));
+    let (res, _) = tokio::join!(
+        get_quote(
+            &mut env.data,
+            ClientId(1),
+            routed_req(Some(1.0), None, true)
+        ),
+        reply_leg_quote(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            QuoteSubId::new(8),
+            first_leg,
+            8,
+        )
+    );
+    assert!(matches!(
+        res,
+        Err(Error::RouteLowBalance {
+            asset: DealerTicker::LBTC,
+            required: 0.001,
+            available: 0.0005
+        })
+    ));
+    assert_eq!(env.data.quotes.len(), quotes);
+    env.wallet().utxo_data = Some(test_mixed_utxos(&[
+        (env.data.policy_asset, 1_000_000),
+        (usdt, 200_000_000),
+    ]));
+
+    // A direct market is quoted directly
+    env.data.markets.push(mkt::MarketInfo {
+        asset_pair: mkt::AssetPair {
+            base: usdt,
+            quote: cents_market().asset_pair.quote,
+        },
+        fee_asset: AssetType::Quote,
+        type_: sideswap_api::MarketType::Stablecoin,
+    });
+    let (res, ()) = tokio::join!(
+        get_quote(
+            &mut env.data,
+            ClientId(1),
+            routed_req(Some(1.0), None, true)
+        ),
+        async {
+            reply_leg_quote(
+                &mut env.ws_requests,
+                &env.ws_responses,
+                QuoteSubId::new(7),
+                (TradeDir::Sell, 100_000_000, 9_500, 10),
+                7,
+            )
+            .await;
+        }
+    );
+    assert!(res.unwrap().route.is_none());
+}
+
+#[tokio::test]
+async fn explain_quote_matches_get_quote() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    let quote_sub_id = QuoteSubId::new(1);
+
+    let (resp, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), req), async {
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![quote_notif(quote_sub_id)],
+        )
+        .await;
+        reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+    });
+    let resp = resp.unwrap();
+
+    let stored = explain_quote(
+        &env.data,
+        api::ExplainQuoteReq {
+            quote_id: Some(resp.quote_id),
+            numbers: None,
+        },
+    )
+    .unwrap();
+    assert_eq!(stored.recv_amount, resp.recv_amount);
+    assert_eq!(stored.send_asset, DealerTicker::LBTC);
+    assert_eq!(stored.send_amount_raw, 100_000);
+    assert_eq!(stored.recv_asset, DealerTicker::USDT);
+    assert_eq!(stored.recv_amount_raw, 94_900_000);
+    assert_eq!(stored.fee_asset, DealerTicker::USDT);
+    assert_eq!(stored.fee_amount_raw, 100_000);
+    assert_eq!(
+        stored.breakdown.last().map(String::as_str),
+        Some("send 0.00100000 base, receive 0.94900000 quote")
+    );
+
+    // The same numbers sent explicitly (tickers are resolved case-insensitively)
+    let numbers = serde_json::from_value::<api::QuoteNumbers>(serde_json::json!({
+        "base": "lbtc",
+        "quote": "USDt",
+        "base_amount": 100_000,
+        "quote_amount": 95_000_000,
+        "server_fee": 100_000,
+        "fixed_fee": 0,
+        "trade_dir": "Sell",
+        "fee_asset": "Quote",
+    }))
+    .unwrap();
+    let raw = explain_quote(
+        &env.data,
+        api::ExplainQuoteReq {
+            quote_id: None,
+            numbers: Some(numbers),
+        },
+    )
+    .unwrap();
+    assert_eq!(
+        serde_json::to_value(&raw).unwrap(),
+        serde_json::to_value(&stored).unwrap()
+    );
+
+    let res = explain_quote(
+        &env.data,
+        api::ExplainQuoteReq {
+            quote_id: Some(QuoteId::new(2)),
+            numbers: None,
+        },
+    );
+    assert!(matches!(res, Err(Error::NoQuote)));
+
+    let res = explain_quote(
+        &env.data,
+        api::ExplainQuoteReq {
+            quote_id: None,
+            numbers: None,
+        },
+    );
+    assert!(matches!(res, Err(Error::InvalidExplainRequest(_))));
+}
+
+/// Respond to the next Peg and PegStatus requests (the new peg has no payments yet)
+async fn reply_new_peg(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+    order_id: OrderId,
+) {
+    let reply = |request_id, resp| {
+        ws_responses
+            .send(WrappedResponse::Response(ResponseMessage::Response(
+                Some(request_id),
+                Ok(resp),
+            )))
+            .expect("must not fail");
+    };
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        match req {
+            WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+                request_id,
+                sideswap_api::Request::Peg(_),
+            )) => reply(
+                request_id,
+                sideswap_api::Response::Peg(sideswap_api::PegResponse {
+                    order_id,
+                    peg_addr: "tb1qserver".to_owned(),
+                    created_at: 1_700_000_000_000,
+                    expires_at: 1_800_000_000_000,
+                    recv_amount: None,
+                }),
+            ),
+            WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+                request_id,
+                sideswap_api::Request::PegStatus(_),
+            )) => {
+                reply(
+                    request_id,
+                    sideswap_api::Response::PegStatus(sideswap_api::PegStatus {
+                        order_id,
+                        peg_in: true,
+                        addr: "tb1qserver".to_owned(),
+                        addr_recv: test_address(0).to_string(),
+                        list: Vec::new(),
+                        created_at: 1_700_000_000_000,
+                        expires_at: 1_800_000_000_000,
+                        return_address: None,
+                    }),
+                );
+                break;
+            }
+            _ => {}
+        }
+    }
+}
+
+#[tokio::test]
+async fn peg_quota_is_per_client() {
+    use sideswap_api::PegTxState::*;
+
+    let mut env = TestEnv::new().await;
+    env.data.settings.client_quotas = Some(crate::quotas::Config {
+        max_pegs: Some(1),
+        max_monitored_txs: None,
+        max_created_txs: None,
+        max_quotes: None,
+    });
+    env.connect_upstream().await;
+    let _alice = env.connect_named_client(1, Some("alice")).await;
+    let _bob = env.connect_named_client(2, Some("bob")).await;
+
+    let peg_req = || api::NewPegReq {
+        addr_recv: test_address(0).to_string(),
+        peg_in: true,
+        fee_rate: None,
+        allow_unconfidential: true,
+    };
+    let alice_order = sideswap_api::HashN([1; 32]);
+    let bob_order = sideswap_api::HashN([2; 32]);
+
+    let (res, ()) = tokio::join!(
+        new_peg(&mut env.data, ClientId(1), peg_req()),
+        reply_new_peg(&mut env.ws_requests, &env.ws_responses, alice_order),
+    );
+    res.unwrap();
+
+    // Rejected locally, nothing is sent to the server
+    let res = new_peg(&mut env.data, ClientId(1), peg_req()).await;
+    assert!(matches!(
+        res,
+        Err(Error::QuotaExceeded {
+            resource: api::QuotaResource::Pegs,
+            limit: 1
+        })
+    ));
+    assert!(env.ws_requests.try_recv().is_err());
+
+    // Other clients are not affected
+    let (res, ()) = tokio::join!(
+        new_peg(&mut env.data, ClientId(2), peg_req()),
+        reply_new_peg(&mut env.ws_requests, &env.ws_responses, bob_order),
+    );
+    res.unwrap();
+
+    let pegs_usage = |resp: api::GetQuotasResp| {
+        let usage = resp
+            .quotas
+            .into_iter()
+            .find(|usage| usage.resource == api::QuotaResource::Pegs)
+            .unwrap();
+        (resp.client_name, usage.used, usage.limit)
+    };
+    let resp = get_quotas(&mut env.data, ClientId(1), api::GetQuotasReq {})
+        .await
+        .unwrap();
+    assert_eq!(pegs_usage(resp), (Some("alice".to_owned()), 1, Some(1)));
+
+    // Finished pegs are not counted
+    send_peg_status(&mut env, alice_order, vec![peg_tx(1, Done, Some(2))]).await;
+    let resp = get_quotas(&mut env.data, ClientId(1), api::GetQuotasReq {})
+        .await
+        .unwrap();
+    assert_eq!(pegs_usage(resp), (Some("alice".to_owned()), 0, Some(1)));
+
+    // Deleted pegs are not counted either
+    del_peg(
+        &mut env.data,
+        api::DelPegReq {
+            order_id: bob_order,
+        },
+    )
+    .await
+    .unwrap();
+    let resp = get_quotas(&mut env.data, ClientId(2), api::GetQuotasReq {})
+        .await
+        .unwrap();
+    assert_eq!(pegs_usage(resp), (Some("bob".to_owned()), 0, Some(1)));
+}
+
+/// A wallet transaction paying to the wallet address
+fn wallet_tx_to(chain: sideswap_lwk::Chain, index: u32) -> sideswap_lwk::WalletTx {
+    let txid = elements::Txid::from_byte_array([3; 32]);
+    let output = sideswap_lwk::WalletTxOut {
+        outpoint: elements::OutPoint::new(txid, 0),
+        script_pubkey: elements::Script::new(),
+        height: None,
+        unblinded: elements::TxOutSecrets::new(
+            Network::LiquidTestnet.d().policy_asset,
+            elements::confidential::AssetBlindingFactor::zero(),
+            1000,
+            elements::confidential::ValueBlindingFactor::zero(),
+        ),
+        wildcard_index: index,
+        ext_int: chain,
+        is_spent: false,
+        address: test_address(100 + index),
+    };
+    sideswap_lwk::WalletTx {
+        tx: elements::Transaction {
+            version: 2,
+            lock_time: elements::LockTime::ZERO,
+            input: Vec::new(),
+            output: Vec::new(),
+        },
+        txid,
+        height: None,
+        balance: BTreeMap::new(),
+        fee: 0,
+        type_: "incoming".to_owned(),
+        timestamp: None,
+        inputs: Vec::new(),
+        outputs: vec![None, Some(output)],
+    }
+}
+
+#[tokio::test]
+async fn list_addresses_reports_usage() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    for index in 0..3 {
+        env.wallet().addresses.insert(
+            index,
+            models::Address {
+                wallet: DEFAULT_WALLET.to_owned(),
+                ind: index.into(),
+                address: Text(test_address(index)),
+                user_note: None,
+            },
+        );
+    }
+    *env.wallet_txs.lock().unwrap() = vec![
+        wallet_tx_to(sideswap_lwk::Chain::External, 1),
+        wallet_tx_to(sideswap_lwk::Chain::Internal, 0),
+    ];
+
+    let summary = |resp: api::ListAddressesResp| {
+        resp.addresses
+            .into_iter()
+            .map(|address| (address.index, address.change, address.used))
+            .collect::<Vec<_>>()
+    };
+
+    let resp = list_addresses(
+        &env.data,
+        api::ListAddressesReq {
+            include_change: false,
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        summary(resp),
+        [(0, false, false), (1, false, true), (2, false, false)]
+    );
+
+    let resp = list_addresses(
+        &env.data,
+        api::ListAddressesReq {
+            include_change: true,
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        summary(resp),
+        [
+            (0, false, false),
+            (1, false, true),
+            (2, false, false),
+            (0, true, true)
+        ]
+    );
+}
+
+#[tokio::test]
+async fn config_reload_applies_reloadable_settings() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 1_000_000));
+    let config_path = std::env::temp_dir().join(format!(
+        "sideswap_manager_reload_{}.toml",
+        std::process::id()
+    ));
+    env.data.config_path = Some(config_path.to_str().unwrap().to_owned());
+    let write_config = |mnemonic: &str, extra: &str| {
+        let config = format!(
+            "env = \"Testnet\"\n\
+             work_dir = \"/var/lib/sideswap_manager\"\n\
+             mnemonic = \"{mnemonic}\"\n\
+             script_variant = \"wpkh\"\n\
+             {extra}\n\
+             [ws_server]\n\
+             listen_on = \"127.0.0.1:3102\"\n"
+        );
+        std::fs::write(&config_path, config).unwrap();
+    };
+    let mut notif_receiver = env.connect_client(1).await;
+
+    let create_tx_req = || {
+        api::Req::CreateTx(api::CreateTxReq {
+            recipients: vec![api::Recipient {
+                address: Some(test_address(5).to_string()),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.001,
+                send_all: false,
+            }],
+            aggregate_duplicates: false,
+            fee_rate: None,
+            allow_unconfidential: false,
+            utxos: None,
+            allow_unconfirmed: true,
+            wallet: DEFAULT_WALLET.to_owned(),
+        })
+    };
+    let reload_req = || api::Req::ReloadConfig(api::ReloadConfigReq {});
+
+    assert!(process_request(&mut env.data, ClientId(1), create_tx_req())
+        .await
+        .is_ok());
+
+    write_config(TEST_MNEMONIC, "[client_quotas]\nmax_created_txs = 1");
+    match process_request(&mut env.data, ClientId(1), reload_req()).await {
+        Ok(api::Resp::ReloadConfig(resp)) => assert_eq!(resp.changed_fields, ["client_quotas"]),
+        _ => panic!("ReloadConfig response expected"),
+    }
+    let res = process_request(&mut env.data, ClientId(1), create_tx_req()).await;
+    assert!(matches!(
+        res,
+        Err(Error::QuotaExceeded {
+            resource: api::QuotaResource::CreatedTxs,
+            limit: 1
+        })
+    ));
+
+    // The connected client is kept and notified
+    assert!(env.data.clients.contains_key(&ClientId(1)));
+    assert!(recv_all(&mut notif_receiver).iter().any(|notif| matches!(
+        notif,
+        api::Notif::ConfigReloaded(notif) if notif.changed_fields == ["client_quotas"]
+    )));
+
+    // Nothing is applied if a boot-only setting is changed
+    write_config(
+        "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        "enforce_allowlist = true",
+    );
+    let res = process_request(&mut env.data, ClientId(1), reload_req()).await;
+    assert!(matches!(res, Err(Error::RestartRequired(ref fields)) if *fields == ["mnemonic"]));
+    assert!(!env.data.settings.enforce_allowlist);
+    assert!(env.data.settings.client_quotas.is_some());
+
+    std::fs::remove_file(&config_path).unwrap();
+}
+
+#[tokio::test]
+async fn duplicate_recipients_rejected_or_aggregated() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+
+    // The same address in another string form
+    let same_address = test_address(5)
+        .to_string()
+        .to_uppercase()
+        .parse::<elements::Address>()
+        .unwrap();
+    let req = |aggregate_duplicates| api::CreateTxReq {
+        recipients: vec![
+            api::Recipient {
+                address: Some(test_address(5).to_string()),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.001,
+                send_all: false,
+            },
+            api::Recipient {
+                address: Some(test_address(6).to_string()),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.002,
+                send_all: false,
+            },
+            api::Recipient {
+                address: Some(same_address.clone().to_string()),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.0005,
+                send_all: false,
+            },
+        ],
+        aggregate_duplicates,
+        fee_rate: None,
+        allow_unconfidential: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+
+    let res = create_tx(&mut env.data, ClientId(0), req(false)).await;
+    assert!(matches!(
+        res,
+        Err(Error::DuplicateRecipient {
+            index_a: 0,
+            index_b: 2
+        })
+    ));
+
+    let resp = create_tx(&mut env.data, ClientId(0), req(true))
+        .await
+        .unwrap();
+    let outputs = resp
+        .recipients
+        .iter()
+        .map(|recipient| (recipient.amount, recipient.recipient_indices.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(outputs, [(0.0015, vec![0, 2]), (0.002, vec![1])]);
+
+    // The same address with different assets is not a duplicate
+    let resp = create_tx(
+        &mut env.data,
+        ClientId(0),
+        api::CreateTxReq {
+            recipients: vec![
+                api::Recipient {
+                    address: Some(test_address(5).to_string()),
+                    asset: Some(DealerTicker::LBTC),
+                    uri: None,
+                    amount: 0.001,
+                    send_all: false,
+                },
+                api::Recipient {
+                    address: Some(test_address(5).to_string()),
+                    asset: Some(DealerTicker::USDT),
+                    uri: None,
+                    amount: 10.0,
+                    send_all: false,
+                },
+            ],
+            aggregate_duplicates: false,
+            fee_rate: None,
+            allow_unconfidential: false,
+            utxos: None,
+            allow_unconfirmed: true,
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.recipients.len(), 2);
+}
+
+#[tokio::test]
+async fn send_all_recipients() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    let usdt_asset = Network::LiquidTestnet.d().known_assets.USDt;
+    env.wallet().balances = Some(BTreeMap::from([
+        (env.data.policy_asset, 1_000_000),
+        (usdt_asset, 2_500_000_000),
+    ]));
+
+    let send_all = |address: elements::Address, asset| api::Recipient {
+        address: Some(address.to_string()),
+        asset: Some(asset),
+        uri: None,
+        amount: 0.0,
+        send_all: true,
+    };
+    let req = |recipients| api::CreateTxReq {
+        recipients,
+        aggregate_duplicates: false,
+        fee_rate: None,
+        allow_unconfidential: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+
+    // The whole asset balance, the fee is paid with L-BTC
+    let resp = create_tx(
+        &mut env.data,
+        ClientId(0),
+        req(vec![send_all(test_address(5), DealerTicker::USDT)]),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.recipients[0].amount, 25.0);
+
+    // L-BTC is drained by the wallet
+    let resp = create_tx(
+        &mut env.data,
+        ClientId(0),
+        req(vec![
+            send_all(test_address(5), DealerTicker::LBTC),
+            send_all(test_address(6), DealerTicker::USDT),
+        ]),
+    )
+    .await
+    .unwrap();
+    let amounts = resp
+        .recipients
+        .iter()
+        .map(|recipient| recipient.amount)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        amounts,
+        [
+            asset_float_amount(
+                TEST_DRAINED_AMOUNT as i64,
+                AssetPrecision::BITCOIN_PRECISION
+            ),
+            25.0
+        ]
+    );
+
+    let res = create_tx(
+        &mut env.data,
+        ClientId(0),
+        req(vec![
+            send_all(test_address(5), DealerTicker::USDT),
+            api::Recipient {
+                address: Some(test_address(6).to_string()),
+                asset: Some(DealerTicker::USDT),
+                uri: None,
+                amount: 1.0,
+                send_all: false,
+            },
+        ]),
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(Error::SendAllNotAlone(DealerTicker::USDT))
+    ));
+
+    env.wallet().balances = Some(BTreeMap::new());
+    let res = create_tx(
+        &mut env.data,
+        ClientId(0),
+        req(vec![send_all(test_address(5), DealerTicker::USDT)]),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NothingToSend(DealerTicker::USDT))));
+}
+
+#[tokio::test]
+async fn payment_uri_recipients() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    let usdt_asset = Network::LiquidTestnet.d().known_assets.USDt;
+
+    let uri_recipient = |uri: String| api::Recipient {
+        address: None,
+        asset: None,
+        uri: Some(uri),
+        amount: 0.0,
+        send_all: false,
+    };
+    let req = |recipients| api::CreateTxReq {
+        recipients,
+        aggregate_duplicates: false,
+        fee_rate: None,
+        allow_unconfidential: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+
+    let resp = create_tx(
+        &mut env.data,
+        ClientId(0),
+        req(vec![
+            uri_recipient(format!(
+                "liquidtestnet:{}?amount=12.5&assetid={usdt_asset}&label=Invoice%2042",
+                test_address(5)
+            )),
+            api::Recipient {
+                address: Some(test_address(6).to_string()),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.001,
+                send_all: false,
+            },
+            // L-BTC without assetid, the amount is set separately
+            api::Recipient {
+                amount: 0.002,
+                ..uri_recipient(format!("liquidtestnet:{}", test_address(7)))
+            },
+        ]),
+    )
+    .await
+    .unwrap();
+    let recipients = resp
+        .recipients
+        .iter()
+        .map(|recipient| (recipient.asset, recipient.amount))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        recipients,
+        [
+            (DealerTicker::USDT, 12.5),
+            (DealerTicker::LBTC, 0.001),
+            (DealerTicker::LBTC, 0.002)
+        ]
+    );
+    let description = &env.data.created_txs[&resp.txid].description;
+    assert!(
+        description.starts_with(&format!(
+            "send 12.5 USDt to {} (Invoice 42), send 0.001 L-BTC",
+            test_address(5)
+        )),
+        "{description}"
+    );
+
+    let mut mainnet_address = test_address(5);
+    mainnet_address.params = &elements::AddressParams::LIQUID;
+    let res = create_tx(
+        &mut env.data,
+        ClientId(0),
+        req(vec![uri_recipient(format!(
+            "liquidnetwork:{mainnet_address}?amount=1"
+        ))]),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::InvalidAddress { index: 0, .. })));
+
+    let res = create_tx(
+        &mut env.data,
+        ClientId(0),
+        req(vec![uri_recipient(format!(
+            "liquidtestnet:{}?amount=0.000000001",
+            test_address(5)
+        ))]),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::InvalidAssetAmount(_, _))));
+
+    let unknown_asset = AssetId::from_slice(&[99; 32]).expect("must not fail");
+    let with_address = api::Recipient {
+        address: Some(test_address(5).to_string()),
+        ..uri_recipient(format!("liquidtestnet:{}", test_address(5)))
+    };
+    let with_amount = api::Recipient {
+        amount: 1.0,
+        ..uri_recipient(format!("liquidtestnet:{}?amount=1", test_address(5)))
+    };
+    for recipient in [
+        uri_recipient(format!(
+            "liquidtestnet:{}?amount=1&assetid={unknown_asset}",
+            test_address(5)
+        )),
+        with_address,
+        with_amount,
+    ] {
+        let res = create_tx(&mut env.data, ClientId(0), req(vec![recipient])).await;
+        assert!(matches!(res, Err(Error::InvalidRecipient { index: 0, .. })));
+    }
+}
+
+#[tokio::test]
+async fn payment_references_assigned_and_found() {
+    let mut env = TestEnv::new().await;
+    env.connect_upstream().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 1_000_000));
+
+    // Already used in the DB (but not loaded), must be skipped
+    let taken = crate::payment_refs::encode(1);
+    assert!(
+        env.data
+            .db
+            .add_payment_reference(&models::PaymentReference {
+                id: 1000,
+                reference: taken.clone(),
+                kind: Json(api::ReferenceKind::Peg),
+                target: "unknown".to_owned(),
+            })
+            .await
+    );
+
+    let find = |data: &Data, reference: &str| {
+        find_by_reference(
+            data,
+            api::FindByReferenceReq {
+                reference: reference.to_owned(),
+            },
+        )
+        .map(|resp| resp.resource)
+    };
+
+    let created = create_tx(
+        &mut env.data,
+        ClientId(0),
+        api::CreateTxReq {
+            recipients: vec![api::Recipient {
+                address: Some(test_address(5).to_string()),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.001,
+                send_all: false,
+            }],
+            aggregate_duplicates: false,
+            fee_rate: None,
+            allow_unconfidential: false,
+            utxos: None,
+            allow_unconfirmed: true,
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(created.reference, crate::payment_refs::encode(2));
+    assert!(matches!(
+        find(&env.data, &created.reference),
+        Ok(api::ReferencedResource::CreatedTx { txid }) if txid == created.txid
+    ));
+
+    // The reference stays the same after the tx is sent
+    send_tx(
+        &mut env.data,
+        ClientId(0),
+        api::SendTxReq {
+            txid: created.txid,
+            user_note: None,
+            wallet_only: true,
+            idempotency_key: None,
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    let lowercase = created.reference.to_lowercase().replace('-', "");
+    assert!(matches!(
+        find(&env.data, &lowercase),
+        Ok(api::ReferencedResource::MonitoredTx { txid }) if txid == created.txid
+    ));
+    let monitored = get_monitored_txs(&env.data, api::GetMonitoredTxsReq {})
+        .await
+        .unwrap();
+    assert_eq!(monitored.txs[0].reference, Some(created.reference.clone()));
+
+    let order_id = sideswap_api::HashN([3; 32]);
+    let mut client = env.connect_client(1).await;
+    let (res, ()) = tokio::join!(
+        new_peg(
+            &mut env.data,
+            ClientId(1),
+            api::NewPegReq {
+                addr_recv: test_address(0).to_string(),
+                peg_in: true,
+                fee_rate: None,
+                allow_unconfidential: true,
+            }
+        ),
+        reply_new_peg(&mut env.ws_requests, &env.ws_responses, order_id),
+    );
+    let peg_reference = res.unwrap().peg.reference.unwrap();
+    assert!(matches!(
+        find(&env.data, &peg_reference),
+        Ok(api::ReferencedResource::Peg { order_id: found }) if found == order_id
+    ));
+    let notif_references = recv_all(&mut client)
+        .into_iter()
+        .filter_map(|notif| match notif {
+            api::Notif::PegStatus(notif) => Some(notif.peg.reference),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(notif_references, [Some(peg_reference.clone())]);
+
+    let references = BTreeSet::from([taken.clone(), created.reference, peg_reference]);
+    assert_eq!(references.len(), 3);
+
+    assert!(matches!(
+        find(&env.data, &taken),
+        Err(Error::UnknownReference)
+    ));
+    let mut typo = crate::payment_refs::encode(2).into_bytes();
+    typo.swap(0, 6);
+    assert!(matches!(
+        find(&env.data, std::str::from_utf8(&typo).unwrap()),
+        Err(Error::InvalidReference(_))
+    ));
+}
+
+#[tokio::test]
+async fn tx_status_changes_notified() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    let mut client = env.connect_client(1).await;
+
+    let mut wallet_tx = wallet_tx_to(sideswap_lwk::Chain::External, 0);
+    let txid = wallet_tx.txid;
+    new_monitored_tx(
+        &env.data.db,
+        &mut env.data.monitored_txs,
+        MonitoredTx {
+            txid: Text(txid),
+            description: None,
+            user_note: None,
+            created_by: None,
+            send_result: None,
+            wallet: None,
+            created_at: 0,
+        },
+    )
+    .await;
+
+    let tx_statuses = |client: &mut UnboundedReceiver<SharedNotif>| {
+        recv_all(client)
+            .into_iter()
+            .filter_map(|notif| match notif {
+                api::Notif::TxStatus(notif) => Some((notif.txid, notif.status)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+
+    process_wallet_event(&mut env.data, DEFAULT_WALLET, sideswap_lwk::Event::Updated).await;
+    assert_eq!(tx_statuses(&mut client), [(txid, api::TxStatus::NotFound)]);
+
+    env.wallet_txs.lock().unwrap().push(wallet_tx.clone());
+    process_wallet_event(&mut env.data, DEFAULT_WALLET, sideswap_lwk::Event::Updated).await;
+    assert_eq!(tx_statuses(&mut client), [(txid, api::TxStatus::Mempool)]);
+
+    // Not sent again if nothing was changed
+    process_wallet_event(&mut env.data, DEFAULT_WALLET, sideswap_lwk::Event::Updated).await;
+    assert_eq!(tx_statuses(&mut client), []);
+
+    wallet_tx.height = Some(100);
+    *env.wallet_txs.lock().unwrap() = vec![wallet_tx];
+    process_wallet_event(&mut env.data, DEFAULT_WALLET, sideswap_lwk::Event::Updated).await;
+    assert_eq!(tx_statuses(&mut client), [(txid, api::TxStatus::Confirmed)]);
+
+    // New clients get the last known status
+    let mut new_client = env.connect_client(2).await;
+    assert_eq!(
+        tx_statuses(&mut new_client),
+        [(txid, api::TxStatus::Confirmed)]
+    );
+
+    del_monitored_tx(&mut env.data, api::DelMonitoredTxReq { txid, force: false })
+        .await
+        .unwrap();
+    let mut new_client = env.connect_client(3).await;
+    assert_eq!(tx_statuses(&mut new_client), []);
+}
+
+#[tokio::test]
+async fn del_monitored_tx_checks_status() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+
+    let mut wallet_tx = wallet_tx_to(sideswap_lwk::Chain::External, 0);
+    let txid = wallet_tx.txid;
+    let del = |txid, force| api::DelMonitoredTxReq { txid, force };
+
+    let res = del_monitored_tx(&mut env.data, del(txid, false)).await;
+    assert!(matches!(res, Err(Error::UnknownMonitoredTx(unknown)) if unknown == txid));
+
+    async fn monitor(env: &mut TestEnv, txid: elements::Txid) {
+        new_monitored_tx(
+            &env.data.db,
+            &mut env.data.monitored_txs,
+            MonitoredTx {
+                txid: Text(txid),
+                description: None,
+                user_note: None,
+                created_by: None,
+                send_result: None,
+                wallet: None,
+                created_at: 0,
+            },
+        )
+        .await;
+    }
+    monitor(&mut env, txid).await;
+    env.wallet_txs.lock().unwrap().push(wallet_tx.clone());
+
+    // Still in the mempool
+    let res = del_monitored_tx(&mut env.data, del(txid, false)).await;
+    assert!(matches!(res, Err(Error::MonitoredTxUnconfirmed(_))));
+    assert!(env.data.monitored_txs.contains_key(&txid));
+
+    del_monitored_tx(&mut env.data, del(txid, true))
+        .await
+        .unwrap();
+    assert!(env.data.monitored_txs.is_empty());
+    assert!(env.data.db.load_monitored_txs().await.is_empty());
+
+    // Confirmed transactions are removed without force
+    monitor(&mut env, txid).await;
+    wallet_tx.height = Some(100);
+    *env.wallet_txs.lock().unwrap() = vec![wallet_tx];
+    del_monitored_tx(&mut env.data, del(txid, false))
+        .await
+        .unwrap();
+    assert!(env.data.monitored_txs.is_empty());
+}
+
+#[tokio::test]
+async fn wallet_txs_filtered_and_annotated() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    let policy_asset = env.data.policy_asset;
+
+    let wallet_tx = |byte: u8, height: Option<u32>, balance: i64| {
+        let mut tx = wallet_tx_to(sideswap_lwk::Chain::External, 0);
+        tx.txid = elements::Txid::from_byte_array([byte; 32]);
+        tx.height = height;
+        tx.balance = BTreeMap::from([(policy_asset, balance)]);
+        tx.fee = 30;
+        tx
+    };
+    *env.wallet_txs.lock().unwrap() = vec![
+        wallet_tx(1, Some(100), 5000),
+        wallet_tx(2, Some(200), -5030),
+        wallet_tx(3, None, 7000),
+    ];
+    let outgoing_txid = elements::Txid::from_byte_array([2; 32]);
+    new_monitored_tx(
+        &env.data.db,
+        &mut env.data.monitored_txs,
+        MonitoredTx {
+            txid: Text(outgoing_txid),
+            description: Some("payout".to_owned()),
+            user_note: Some("invoice 42".to_owned()),
+            created_by: None,
+            send_result: None,
+            wallet: None,
+            created_at: 0,
+        },
+    )
+    .await;
+
+    let resp = get_wallet_txs(
+        &env.data,
+        api::GetWalletTxsReq {
+            after_height: None,
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.txs.len(), 3);
+
+    let resp = get_wallet_txs(
+        &env.data,
+        api::GetWalletTxsReq {
+            after_height: Some(100),
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    let summary = resp
+        .txs
+        .iter()
+        .map(|tx| (tx.height, tx.network_fee, tx.user_note.as_deref()))
+        .collect::<Vec<_>>();
+    // The fee is only reported if it was paid by the wallet
+    assert_eq!(
+        summary,
+        [
+            (Some(200), Some(30), Some("invoice 42")),
+            (None, None, None)
+        ]
+    );
+    assert_eq!(resp.txs[0].description.as_deref(), Some("payout"));
+
+    let res = get_wallet_txs(
+        &env.data,
+        api::GetWalletTxsReq {
+            after_height: None,
+            wallet: "nope".to_owned(),
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::UnknownWallet { wallet, .. }) if wallet == "nope"));
+}
+
+#[tokio::test]
+async fn tx_notes_exported_and_imported() {
+    let mut env = TestEnv::new().await;
+    let txid_a = elements::Txid::from_byte_array([1; 32]);
+    let txid_b = elements::Txid::from_byte_array([2; 32]);
+    for txid in [txid_a, txid_b] {
+        new_monitored_tx(
+            &env.data.db,
+            &mut env.data.monitored_txs,
+            MonitoredTx {
+                txid: Text(txid),
+                description: Some(format!("payout {txid}")),
+                user_note: None,
+                created_by: None,
+                send_result: None,
+                wallet: None,
+                created_at: 0,
+            },
+        )
+        .await;
+    }
+    let mut notif_receiver = env.connect_client(1).await;
+    recv_all(&mut notif_receiver);
+
+    let set_note = |txid, user_note: &str| api::SetTxNoteReq {
+        txid,
+        user_note: Some(user_note.to_owned()),
+    };
+    set_tx_note(&mut env.data, set_note(txid_a, "invoice 42"))
+        .await
+        .unwrap();
+    let notifs = recv_all(&mut notif_receiver);
+    assert!(matches!(
+        notifs.as_slice(),
+        [api::Notif::TxNote(notif)] if notif.txid == txid_a && notif.user_note.as_deref() == Some("invoice 42")
+    ));
+    let unknown = elements::Txid::from_byte_array([3; 32]);
+    let res = set_tx_note(&mut env.data, set_note(unknown, "invoice 43")).await;
+    assert!(matches!(res, Err(Error::UnknownMonitoredTx(txid)) if txid == unknown));
+
+    let exported = export_notes(&env.data, api::ExportNotesReq {})
+        .unwrap()
+        .notes;
+    assert_eq!(exported.len(), 2);
+    assert_eq!(exported[&txid_a].user_note.as_deref(), Some("invoice 42"));
+    let notes = exported
+        .iter()
+        .map(|(txid, notes)| (txid.to_string(), notes.clone()))
+        .collect::<BTreeMap<_, _>>();
+
+    // Restored into a new database
+    let mut env = TestEnv::new().await;
+    let mut notif_receiver = env.connect_client(1).await;
+    recv_all(&mut notif_receiver);
+    let import = |notes, overwrite| api::ImportNotesReq { notes, overwrite };
+
+    let mut invalid = notes.clone();
+    invalid.insert("not a txid".to_owned(), exported[&txid_a].clone());
+    let res = import_notes(&mut env.data, import(invalid, false)).await;
+    assert!(matches!(res, Err(Error::InvalidImportedTxid(txid)) if txid == "not a txid"));
+    assert!(env.data.monitored_txs.is_empty());
+
+    let resp = import_notes(&mut env.data, import(notes.clone(), false))
+        .await
+        .unwrap();
+    assert_eq!(resp.imported, [txid_a, txid_b]);
+    assert!(resp.skipped.is_empty());
+    assert_eq!(recv_all(&mut notif_receiver).len(), 2);
+    let loaded = env.data.db.load_monitored_txs().await;
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(loaded[0].description, exported[&txid_a].description);
+    assert_eq!(loaded[0].user_note.as_deref(), Some("invoice 42"));
+
+    set_tx_note(&mut env.data, set_note(txid_a, "invoice 44"))
+        .await
+        .unwrap();
+    set_tx_note(
+        &mut env.data,
+        api::SetTxNoteReq {
+            txid: txid_b,
+            user_note: None,
+        },
+    )
+    .await
+    .unwrap();
+    recv_all(&mut notif_receiver);
+
+    // A missing note is not a conflict
+    let mut changed = notes.clone();
+    changed.get_mut(&txid_b.to_string()).unwrap().user_note = Some("invoice 45".to_owned());
+    let resp = import_notes(&mut env.data, import(changed.clone(), false))
+        .await
+        .unwrap();
+    assert_eq!(resp.imported, [txid_b]);
+    assert_eq!(resp.skipped, [txid_a]);
+    assert_eq!(
+        env.data.monitored_txs[&txid_a].user_note.as_deref(),
+        Some("invoice 44")
+    );
+    assert_eq!(
+        env.data.monitored_txs[&txid_b].user_note.as_deref(),
+        Some("invoice 45")
+    );
+
+    let resp = import_notes(&mut env.data, import(changed, true))
+        .await
+        .unwrap();
+    assert_eq!(resp.imported, [txid_a, txid_b]);
+    assert_eq!(
+        env.data.db.load_monitored_txs().await[0]
+            .user_note
+            .as_deref(),
+        Some("invoice 42")
+    );
+}
+
+fn approvals_config() -> crate::approvals::Config {
+    crate::approvals::Config {
+        thresholds: BTreeMap::from([(DealerTicker::LBTC, 0.01)]),
+        ttl_seconds: None,
+    }
+}
+
+fn create_tx_req(amount: f64) -> api::CreateTxReq {
+    api::CreateTxReq {
+        recipients: vec![api::Recipient {
+            address: Some(test_address(5).to_string()),
+            asset: Some(DealerTicker::LBTC),
+            uri: None,
+            amount,
+            send_all: false,
+        }],
+        aggregate_duplicates: false,
+        fee_rate: None,
+        allow_unconfidential: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    }
+}
+
+async fn create_lbtc_tx(data: &mut Data, client_id: ClientId, amount: f64) -> elements::Txid {
+    let req = create_tx_req(amount);
+    create_tx(data, client_id, req).await.unwrap().txid
+}
+
+fn send_req(txid: elements::Txid) -> api::SendTxReq {
+    api::SendTxReq {
+        txid,
+        user_note: None,
+        wallet_only: true,
+        idempotency_key: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+    }
+}
+
+fn approvals_resolved(
+    client: &mut UnboundedReceiver<SharedNotif>,
+) -> Vec<(api::ApprovalState, Option<elements::Txid>, Option<String>)> {
+    recv_all(client)
+        .into_iter()
+        .filter_map(|notif| match notif {
+            api::Notif::ApprovalResolved(notif) => Some((notif.state, notif.txid, notif.error)),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn large_send_approved_by_another_client() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.data.settings.approvals = Some(approvals_config());
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 100_000_000));
+    let mut alice = env.connect_named_client(1, Some("alice")).await;
+    let mut bob = env.connect_token_client(2, "bob").await;
+    let _anonymous = env.connect_client(3).await;
+    // Uses the approver name without its client token
+    let _fake_bob = env.connect_named_client(4, Some("bob")).await;
+
+    let txid = create_lbtc_tx(&mut env.data, ClientId(1), 0.5).await;
+    let approval_id = match send_tx(&mut env.data, ClientId(1), send_req(txid)).await {
+        Err(Error::ApprovalRequired(approval_id)) => approval_id,
+        _ => panic!("ApprovalRequired expected"),
+    };
+    assert!(!env.data.monitored_txs.contains_key(&txid));
+
+    // Repeated requests return the same approval
+    let res = send_tx(&mut env.data, ClientId(1), send_req(txid)).await;
+    assert!(matches!(res, Err(Error::ApprovalRequired(id)) if id == approval_id));
+
+    let approvals = list_pending_approvals(&env.data, api::ListPendingApprovalsReq {})
+        .unwrap()
+        .approvals;
+    assert_eq!(approvals.len(), 1);
+    assert_eq!(approvals[0].approval_id, approval_id);
+    assert_eq!(approvals[0].operation, api::ApprovalOperation::SendTx);
+    assert_eq!(approvals[0].txid, txid);
+    assert_eq!(approvals[0].requested_by.as_deref(), Some("alice"));
+    assert_eq!(
+        approvals[0].amounts,
+        BTreeMap::from([(DealerTicker::LBTC, 0.5)])
+    );
+    assert_eq!(env.data.db.load_pending_approvals().await.len(), 1);
+
+    // Neither the requester, an anonymous client nor a client without its own token can approve
+    for client_id in [ClientId(1), ClientId(3), ClientId(4)] {
+        let req = api::ApproveReq {
+            approval_id: approval_id.clone(),
+        };
+        let res = approve(&mut env.data, client_id, req).await;
+        assert!(matches!(res, Err(Error::ApproverNotAllowed)));
+    }
+
+    let req = api::ApproveReq {
+        approval_id: approval_id.clone(),
+    };
+    let resp = approve(&mut env.data, ClientId(2), req).await.unwrap();
+    assert_eq!(resp.txid, txid);
+    assert!(resp.send_tx.is_some());
+    assert_eq!(
+        env.data.monitored_txs[&txid].created_by.as_deref(),
+        Some("alice")
+    );
+    assert_eq!(
+        approvals_resolved(&mut alice),
+        [(api::ApprovalState::Approved, Some(txid), None)]
+    );
+    assert_eq!(approvals_resolved(&mut bob), []);
+    assert!(env.data.pending_approvals.is_empty());
+    assert!(env.data.db.load_pending_approvals().await.is_empty());
+
+    let req = api::ApproveReq { approval_id };
+    let res = approve(&mut env.data, ClientId(2), req).await;
+    assert!(matches!(res, Err(Error::UnknownApproval)));
+}
+
+#[tokio::test]
+async fn approvals_rejected_and_expired() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.data.settings.approvals = Some(approvals_config());
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 100_000_000));
+    let mut alice = env.connect_named_client(1, Some("alice")).await;
+    let _bob = env.connect_token_client(2, "bob").await;
+
+    async fn park(data: &mut Data) -> String {
+        let txid = create_lbtc_tx(data, ClientId(1), 0.5).await;
+        match send_tx(data, ClientId(1), send_req(txid)).await {
+            Err(Error::ApprovalRequired(approval_id)) => approval_id,
+            _ => panic!("ApprovalRequired expected"),
+        }
+    }
+
+    let approval_id = park(&mut env.data).await;
+    let req = api::RejectReq {
+        approval_id: approval_id.clone(),
+        reason: Some("unexpected amount".to_owned()),
+    };
+    reject(&mut env.data, ClientId(2), req).await.unwrap();
+    assert_eq!(
+        approvals_resolved(&mut alice),
+        [(
+            api::ApprovalState::Rejected,
+            None,
+            Some("unexpected amount".to_owned())
+        )]
+    );
+    let req = api::ApproveReq { approval_id };
+    let res = approve(&mut env.data, ClientId(2), req).await;
+    assert!(matches!(res, Err(Error::UnknownApproval)));
+
+    let approval_id = park(&mut env.data).await;
+    expire_approvals(&mut env.data, TimestampMs::now()).await;
+    assert!(env.data.pending_approvals.contains_key(&approval_id));
+    let later = TimestampMs::from_millis(TimestampMs::now().millis() + 2 * 3600 * 1000);
+    expire_approvals(&mut env.data, later).await;
+    assert_eq!(
+        approvals_resolved(&mut alice),
+        [(api::ApprovalState::Expired, None, None)]
+    );
+    assert!(env.data.pending_approvals.is_empty());
+    assert!(env.data.db.load_pending_approvals().await.is_empty());
+
+    // Amounts below the threshold are sent without an approval
+    let txid = create_lbtc_tx(&mut env.data, ClientId(1), 0.001).await;
+    send_tx(&mut env.data, ClientId(1), send_req(txid))
+        .await
+        .unwrap();
+    assert!(env.data.monitored_txs.contains_key(&txid));
+}
+
+#[tokio::test]
+async fn quote_approval_expires_with_quote() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    env.data.settings.approvals = Some(crate::approvals::Config {
+        thresholds: BTreeMap::from([(DealerTicker::LBTC, 0.0001)]),
+        ttl_seconds: None,
+    });
+    let _alice = env.connect_named_client(1, Some("alice")).await;
+
+    let quote_sub_id = QuoteSubId::new(1);
+    let (resp, ()) = tokio::join!(get_quote(&mut env.data, ClientId(1), req), async {
+        reply_start_quotes(
+            &mut env.ws_requests,
+            &env.ws_responses,
+            quote_sub_id,
+            vec![quote_notif(quote_sub_id)],
+        )
+        .await;
+        reply_get_quote(&mut env.ws_requests, &env.ws_responses).await;
+    });
+    let quote_id = resp.unwrap().quote_id;
+
+    let req = api::AcceptQuoteReq {
+        quote_id,
+        user_note: None,
+        idempotency_key: None,
+    };
+    let approval_id = match accept_quote(&mut env.data, ClientId(1), req).await {
+        Err(Error::ApprovalRequired(approval_id)) => approval_id,
+        _ => panic!("ApprovalRequired expected"),
+    };
+
+    // Capped at the quote TTL (30 seconds), not the configured 3600 seconds
+    let approval = &env.data.pending_approvals[&approval_id];
+    let ttl = approval.expires_at - approval.created_at;
+    assert!(ttl > 0 && ttl <= 30_000, "unexpected approval TTL: {ttl}");
+    let later = TimestampMs::from_millis(TimestampMs::now().millis() + 31_000);
+    expire_approvals(&mut env.data, later).await;
+    assert!(env.data.pending_approvals.is_empty());
+}
+
+#[tokio::test]
+async fn created_txs_restored_and_expired() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 100_000_000));
+
+    let restart = |data: &mut Data, rows: Vec<models::CreatedTx>| {
+        data.created_txs = rows
+            .into_iter()
+            .map(|row| (row.txid.0, CreatedTx::from_row(row)))
+            .collect();
+    };
+
+    let txid = create_lbtc_tx(&mut env.data, ClientId(1), 0.001).await;
+    let rows = env.data.db.load_created_txs().await;
+    assert_eq!(rows.len(), 1);
+    assert_eq!(rows[0].txid.0, txid);
+    assert_eq!(
+        rows[0].amounts.0,
+        BTreeMap::from([(DealerTicker::LBTC, 0.001)])
+    );
+
+    restart(&mut env.data, rows);
+    send_tx(&mut env.data, ClientId(1), send_req(txid))
+        .await
+        .unwrap();
+    assert!(env.data.created_txs.is_empty());
+    assert!(env.data.db.load_created_txs().await.is_empty());
+    del_monitored_tx(&mut env.data, api::DelMonitoredTxReq { txid, force: true })
+        .await
+        .unwrap();
+
+    // Restored transactions are still checked against the current wallet UTXOs
+    let txid = create_lbtc_tx(&mut env.data, ClientId(1), 0.001).await;
+    let mut rows = env.data.db.load_created_txs().await;
+    let mut tx = CreatedTx::from_row(rows.remove(0));
+    tx.tx.input.push(elements::TxIn {
+        previous_output: elements::OutPoint::new(elements::Txid::from_byte_array([9; 32]), 0),
+        ..Default::default()
+    });
+    rows.push(tx.to_row(txid));
+    restart(&mut env.data, rows);
+    let res = send_tx(&mut env.data, ClientId(1), send_req(txid)).await;
+    assert!(matches!(res, Err(Error::UtxoCheckFailed(_))));
+    assert!(env.data.monitored_txs.is_empty());
+
+    expire_created_txs(&mut env.data, TimestampMs::now()).await;
+    assert!(env.data.created_txs.contains_key(&txid));
+    let later = TimestampMs::from_millis(TimestampMs::now().millis() + 25 * 3600 * 1000);
+    expire_created_txs(&mut env.data, later).await;
+    assert!(env.data.created_txs.is_empty());
+    assert!(env.data.db.load_created_txs().await.is_empty());
+}
+
+#[tokio::test]
+async fn sent_tx_replayed() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 100_000_000));
+    let keyed_req = |txid, key: &str| api::SendTxReq {
+        idempotency_key: Some(key.to_owned()),
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..send_req(txid)
+    };
+
+    let txid = create_lbtc_tx(&mut env.data, ClientId(1), 0.001).await;
+    let resp = send_tx(&mut env.data, ClientId(1), keyed_req(txid, "payout-1"))
+        .await
+        .unwrap();
+    assert!(matches!(resp.res_wallet, api::BroadcastStatus::Success {}));
+    assert!(env.data.created_txs.is_empty());
+
+    // The sent tx is not created again, the stored result is returned
+    let resp = send_tx(&mut env.data, ClientId(1), send_req(txid))
+        .await
+        .unwrap();
+    assert!(matches!(resp.res_wallet, api::BroadcastStatus::Success {}));
+    let stored = env.data.db.load_monitored_txs().await;
+    assert!(stored[0].send_result.is_some());
+
+    // The key is still known after the monitored tx is removed
+    del_monitored_tx(&mut env.data, api::DelMonitoredTxReq { txid, force: true })
+        .await
+        .unwrap();
+    let res = send_tx(&mut env.data, ClientId(1), send_req(txid)).await;
+    assert!(matches!(res, Err(Error::NoCreatedTx)));
+    let resp = send_tx(&mut env.data, ClientId(1), keyed_req(txid, "payout-1"))
+        .await
+        .unwrap();
+    assert!(matches!(resp.res_wallet, api::BroadcastStatus::Success {}));
+
+    // The test wallet txs only differ by the fee
+    let other_req = api::CreateTxReq {
+        fee_rate: Some(FeeRateSats::from_raw(0.2)),
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..create_tx_req(0.002)
+    };
+    let other_txid = create_tx(&mut env.data, ClientId(1), other_req)
+        .await
+        .unwrap()
+        .txid;
+    let res = send_tx(
+        &mut env.data,
+        ClientId(1),
+        keyed_req(other_txid, "payout-1"),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::IdempotencyKeyReused(key)) if key == "payout-1"));
+    assert!(env.data.created_txs.contains_key(&other_txid));
+
+    // Failed requests release the key
+    let unknown_txid = elements::Txid::from_byte_array([9; 32]);
+    let res = send_tx(
+        &mut env.data,
+        ClientId(1),
+        keyed_req(unknown_txid, "payout-2"),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NoCreatedTx)));
+    let key_id = idempotency::KeyId::new(
+        None,
+        DEFAULT_WALLET,
+        "payout-2".to_owned(),
+        &idempotency::Request::SendTx { txid: unknown_txid },
+    );
+    assert!(env.data.db.load_idempotency_key(&key_id).await.is_none());
+    send_tx(
+        &mut env.data,
+        ClientId(1),
+        keyed_req(other_txid, "payout-2"),
+    )
+    .await
+    .unwrap();
+
+    let res = accept_quote(
+        &mut env.data,
+        ClientId(1),
+        api::AcceptQuoteReq {
+            quote_id: QuoteId::new(1),
+            user_note: None,
+            idempotency_key: Some("payout-2".to_owned()),
+        },
+    )
+    .await;
+    // The keys of other request kinds are independent
+    assert!(matches!(res, Err(Error::NoQuote)));
+
+    let res = send_tx(&mut env.data, ClientId(1), keyed_req(txid, "")).await;
+    assert!(matches!(res, Err(Error::InvalidIdempotencyKey(_))));
+}
+
+#[tokio::test]
+async fn idempotency_keys_scoped() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 100_000_000));
+    let _alice = env.connect_named_client(1, Some("alice")).await;
+    let _bob = env.connect_named_client(2, Some("bob")).await;
+    env.data
+        .wallets
+        .insert("cold".to_owned(), WalletData::new(mpsc::channel().0));
+    let keyed_req = |txid, wallet: &str, key: &str| api::SendTxReq {
+        idempotency_key: Some(key.to_owned()),
+        wallet: wallet.to_owned(),
+        ..send_req(txid)
+    };
+
+    let txid = create_lbtc_tx(&mut env.data, ClientId(1), 0.001).await;
+    send_tx(
+        &mut env.data,
+        ClientId(1),
+        keyed_req(txid, DEFAULT_WALLET, "payout-1"),
+    )
+    .await
+    .unwrap();
+
+    // The sent tx result is not returned to another wallet
+    let res = send_tx(
+        &mut env.data,
+        ClientId(1),
+        keyed_req(txid, "cold", "payout-1"),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NoCreatedTx)));
+    let res = send_tx(
+        &mut env.data,
+        ClientId(1),
+        api::SendTxReq {
+            wallet: "cold".to_owned(),
+            ..send_req(txid)
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::NoCreatedTx)));
+
+    // Another client can use the same key
+    let other_req = api::CreateTxReq {
+        fee_rate: Some(FeeRateSats::from_raw(0.2)),
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..create_tx_req(0.002)
+    };
+    let other_txid = create_tx(&mut env.data, ClientId(2), other_req)
+        .await
+        .unwrap()
+        .txid;
+    let resp = send_tx(
+        &mut env.data,
+        ClientId(2),
+        keyed_req(other_txid, DEFAULT_WALLET, "payout-1"),
+    )
+    .await
+    .unwrap();
+    assert!(matches!(resp.res_wallet, api::BroadcastStatus::Success {}));
+    assert!(!env.data.created_txs.contains_key(&other_txid));
+
+    // Reserved, but not finished (the manager was restarted while sending)
+    let request = idempotency::Request::SendTx { txid: other_txid };
+    let reserved = |key: &str, response: Option<&str>| models::IdempotencyKey {
+        client: "alice".to_owned(),
+        wallet: DEFAULT_WALLET.to_owned(),
+        kind: request.kind().to_owned(),
+        key: key.to_owned(),
+        request: Json(request.clone()),
+        response: response.map(str::to_owned),
+        created_at: TimestampMs::now().millis() as i64,
+    };
+    env.data
+        .db
+        .add_idempotency_key(&reserved("payout-2", None))
+        .await;
+    let res = send_tx(
+        &mut env.data,
+        ClientId(1),
+        keyed_req(other_txid, DEFAULT_WALLET, "payout-2"),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::IdempotencyKeyInProgress(key)) if key == "payout-2"));
+
+    env.data
+        .db
+        .add_idempotency_key(&reserved("payout-3", Some("not json")))
+        .await;
+    let res = send_tx(
+        &mut env.data,
+        ClientId(1),
+        keyed_req(other_txid, DEFAULT_WALLET, "payout-3"),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::InvalidStoredResponse(_))));
+}
+
+/// Respond to the next market request accepted by `expected` with `res`
+async fn reply_market(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+    expected: fn(&mkt::Request) -> bool,
+    res: Result<mkt::Response, sideswap_api::Error>,
+) {
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::Market(req),
+        )) = req
+        {
+            if !expected(&req) {
+                continue;
+            }
+            ws_responses
+                .send(WrappedResponse::Response(ResponseMessage::Response(
+                    Some(request_id),
+                    res.map(sideswap_api::Response::Market),
+                )))
+                .expect("must not fail");
+            return;
+        }
+    }
+}
+
+#[tokio::test]
+async fn failed_server_broadcast_retried_after_reconnect() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.connect_upstream().await;
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 100_000_000));
+
+    // Different fee rates make different transactions
+    let mut txids = Vec::new();
+    for fee_rate in [0.1, 0.2] {
+        let req = api::CreateTxReq {
+            recipients: vec![api::Recipient {
+                address: Some(test_address(5).to_string()),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.001,
+                send_all: false,
+            }],
+            aggregate_duplicates: false,
+            fee_rate: Some(FeeRateSats::from_raw(fee_rate)),
+            allow_unconfidential: false,
+            utxos: None,
+            allow_unconfirmed: true,
+            wallet: DEFAULT_WALLET.to_owned(),
+        };
+        txids.push(
+            create_tx(&mut env.data, ClientId(1), req)
+                .await
+                .unwrap()
+                .txid,
+        );
+    }
+    let txid = txids[0];
+
+    fn is_broadcast(req: &mkt::Request) -> bool {
+        matches!(req, mkt::Request::BroadcastTx(_))
+    }
+    async fn reply_broadcast_failed(
+        ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+        ws_responses: &UnboundedSender<WrappedResponse>,
+    ) {
+        let check_outpoints = mkt::Response::CheckOutpoints(mkt::CheckOutpointsResponse {});
+        reply_market(
+            ws_requests,
+            ws_responses,
+            |req| matches!(req, mkt::Request::CheckOutpoints(_)),
+            Ok(check_outpoints),
+        )
+        .await;
+        let error = sideswap_api::Error {
+            code: sideswap_api::ErrorCode::ServerError,
+            message: "connection lost".to_owned(),
+        };
+        reply_market(ws_requests, ws_responses, is_broadcast, Err(error)).await;
+    }
+    let req = api::SendTxReq {
+        txid,
+        user_note: None,
+        wallet_only: false,
+        idempotency_key: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+    let (res, ()) = tokio::join!(
+        send_tx(&mut env.data, ClientId(1), req),
+        reply_broadcast_failed(&mut env.ws_requests, &env.ws_responses)
+    );
+    let res = res.unwrap();
+    assert!(matches!(res.res_wallet, api::BroadcastStatus::Success {}));
+    assert!(matches!(
+        res.res_server,
+        Some(api::BroadcastStatus::Error { .. })
+    ));
+    // Only the sent transaction is removed
+    assert_eq!(env.data.created_txs.keys().collect::<Vec<_>>(), [&txids[1]]);
+    assert_eq!(env.data.db.load_created_txs().await.len(), 1);
+    assert!(env.data.pending_broadcasts.contains_key(&txid));
+
+    async fn reconnect(env: &mut TestEnv) {
+        env.ws_responses
+            .send(WrappedResponse::Connected)
+            .expect("must not fail");
+        let event = env.data.ws.recv().await;
+        process_ws_event(&mut env.data, event).await;
+    }
+    async fn process_reply(env: &mut TestEnv, res: Result<mkt::Response, sideswap_api::Error>) {
+        reply_market(&mut env.ws_requests, &env.ws_responses, is_broadcast, res).await;
+        let event = env.data.ws.recv().await;
+        process_ws_event(&mut env.data, event).await;
+    }
+
+    // The failed retry is kept
+    reconnect(&mut env).await;
+    let error = sideswap_api::Error {
+        code: sideswap_api::ErrorCode::ServerError,
+        message: "try again".to_owned(),
+    };
+    process_reply(&mut env, Err(error)).await;
+    assert_eq!(env.data.pending_broadcasts[&txid].attempts, 1);
+
+    reconnect(&mut env).await;
+    let broadcast = mkt::Response::BroadcastTx(mkt::BroadcastTxResponse { txid });
+    process_reply(&mut env, Ok(broadcast)).await;
+    assert!(env.data.pending_broadcasts.is_empty());
+
+    // The retries are bounded
+    env.data.pending_broadcasts.insert(
+        txid,
+        PendingBroadcast {
+            tx: elements::Transaction {
+                version: 2,
+                lock_time: elements::LockTime::ZERO,
+                input: Vec::new(),
+                output: Vec::new(),
+            },
+            attempts: MAX_BROADCAST_RETRIES,
+            request_id: None,
+        },
+    );
+    reconnect(&mut env).await;
+    assert!(env.data.pending_broadcasts.is_empty());
+}
+
+#[tokio::test]
+async fn create_tx_fee_rate() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 100_000_000));
+
+    let req = |fee_rate: Option<f64>| api::CreateTxReq {
+        recipients: vec![api::Recipient {
+            address: Some(test_address(5).to_string()),
+            asset: Some(DealerTicker::LBTC),
+            uri: None,
+            amount: 0.001,
+            send_all: false,
+        }],
+        aggregate_duplicates: false,
+        fee_rate: fee_rate.map(FeeRateSats::from_raw),
+        allow_unconfidential: false,
+        utxos: None,
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+
+    let low = create_tx(&mut env.data, ClientId(1), req(Some(0.1)))
+        .await
+        .unwrap();
+    let high = create_tx(&mut env.data, ClientId(1), req(Some(1.0)))
+        .await
+        .unwrap();
+    assert!(low.network_fee < high.network_fee);
+    for resp in [&low, &high] {
+        assert!(resp.vsize > 0);
+        assert_eq!(
+            resp.fee_rate,
+            FeeRateSats::from_fee(resp.network_fee, resp.vsize)
+        );
+    }
+
+    for fee_rate in [0.0, -1.0, 0.05, 5.5, f64::NAN] {
+        let res = create_tx(&mut env.data, ClientId(1), req(Some(fee_rate))).await;
+        assert!(matches!(res, Err(Error::InvalidFeeRate(_))));
+    }
+}
+
+#[tokio::test]
+async fn create_tx_validates_recipient_addresses() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 100_000_000));
+
+    let req = |address: String, allow_unconfidential| api::CreateTxReq {
+        recipients: vec![
+            api::Recipient {
+                address: Some(test_address(5).to_string()),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.001,
+                send_all: false,
+            },
+            api::Recipient {
+                address: Some(address),
+                asset: Some(DealerTicker::LBTC),
+                uri: None,
+                amount: 0.001,
+                send_all: false,
+            },
+        ],
+        aggregate_duplicates: false,
+        fee_rate: None,
+        allow_unconfidential,
+        utxos: None,
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+
+    let unconfidential = test_address(6).to_unconfidential();
+    let mainnet = elements::Address {
+        params: &elements::AddressParams::LIQUID,
+        ..test_address(6)
+    };
+    for (address, reason) in [
+        (
+            "tex1qtypo".to_owned(),
+            "bech32 error: parsing unchecked hrpstring failed",
+        ),
+        (
+            "bc1qjq4gy9tf8ss9s65m3a5z447xz92u0v7lutxkd6qg3hjgqgah84dqxckyfq".to_owned(),
+            "Bitcoin address used, use NewPeg for peg-outs",
+        ),
+        (mainnet.to_string(), "not a LiquidTestnet address"),
+        (
+            unconfidential.to_string(),
+            "the address is not confidential",
+        ),
+    ] {
+        let res = create_tx(&mut env.data, ClientId(1), req(address, false)).await;
+        match res {
+            Err(Error::InvalidAddress {
+                index: 1,
+                reason: actual,
+            }) => assert_eq!(actual, reason),
+            _ => panic!("InvalidAddress for recipient 1 expected"),
+        }
+    }
+
+    let resp = create_tx(
+        &mut env.data,
+        ClientId(1),
+        req(format!(" {unconfidential} "), true),
+    )
+    .await
+    .unwrap();
+    assert_eq!(resp.recipients[1].address, unconfidential);
+}
+
+#[tokio::test]
+async fn utxos_listed_and_selected_for_tx() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.wallet().utxo_data = Some(test_utxos(env.data.policy_asset, 100_000, 2));
+    let outpoint = |vout| elements::OutPoint::new(elements::Txid::from_byte_array([1; 32]), vout);
+
+    // The first UTXO is confirmed in the block before the tip
+    let mut utxo = wallet_tx_to(sideswap_lwk::Chain::External, 0).outputs[1]
+        .clone()
+        .unwrap();
+    utxo.outpoint = outpoint(0);
+    utxo.height = Some(TEST_TIP_HEIGHT - 1);
+    *env.wallet_utxos.lock().unwrap() = vec![utxo];
+
+    let resp = list_utxos(
+        &env.data,
+        api::ListUtxosReq {
+            wallet: DEFAULT_WALLET.to_owned(),
+        },
+    )
+    .await
+    .unwrap();
+    let utxos = resp
+        .utxos
+        .iter()
+        .map(|utxo| (utxo.outpoint, utxo.ticker, utxo.amount, utxo.confirmations))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        utxos,
+        [
+            (outpoint(0), DealerTicker::LBTC, 0.001, 2),
+            (outpoint(1), DealerTicker::LBTC, 0.001, 0),
+        ]
+    );
+
+    let res = list_utxos(
+        &env.data,
+        api::ListUtxosReq {
+            wallet: "nope".to_owned(),
+        },
+    )
+    .await;
+    assert!(matches!(res, Err(Error::UnknownWallet { wallet, .. }) if wallet == "nope"));
+
+    // Unknown outpoints are ignored
+    let req = |asset, amount| api::CreateTxReq {
+        recipients: vec![api::Recipient {
+            address: Some(test_address(5).to_string()),
+            asset: Some(asset),
+            uri: None,
+            amount,
+            send_all: false,
+        }],
+        aggregate_duplicates: false,
+        fee_rate: None,
+        allow_unconfidential: false,
+        utxos: Some(vec![outpoint(1), outpoint(7)]),
+        allow_unconfirmed: true,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+    let res = create_tx(&mut env.data, ClientId(0), req(DealerTicker::LBTC, 0.0015)).await;
+    assert!(matches!(
+        res,
+        Err(Error::NotEnoughAmount {
+            required: 150_000,
+            available: 100_000,
+            ..
+        })
+    ));
+
+    let resp = create_tx(&mut env.data, ClientId(0), req(DealerTicker::LBTC, 0.0008))
+        .await
+        .unwrap();
+    let inputs = env.data.created_txs[&resp.txid]
+        .tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<_>>();
+    assert_eq!(inputs, [outpoint(1)]);
+
+    let res = create_tx(&mut env.data, ClientId(0), req(DealerTicker::USDT, 1.0)).await;
+    assert!(matches!(
+        res,
+        Err(Error::UtxoSelectionNotSupported(DealerTicker::USDT))
+    ));
+}
+
+#[tokio::test]
+async fn quote_utxos_restricted() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    env.wallet().utxo_data = Some(test_utxos(env.data.policy_asset, 1_000_000, 2));
+    let outpoint = elements::OutPoint::new(elements::Txid::from_byte_array([1; 32]), 1);
+
+    let quote_req = |utxos| api::GetQuoteReq {
+        receive_address: Some(test_address(0)),
+        gaid: None,
+        utxos: Some(utxos),
+        wallet: DEFAULT_WALLET.to_owned(),
+        ..req
+    };
+    let (res, utxos) = tokio::join!(
+        get_quote(&mut env.data, ClientId(1), quote_req(vec![outpoint])),
+        async {
+            loop {
+                let req = env.ws_requests.recv().await.unwrap();
+                if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+                    request_id,
+                    sideswap_api::Request::Market(mkt::Request::StartQuotes(req)),
+                )) = req
+                {
+                    let error = sideswap_api::Error {
+                        code: sideswap_api::ErrorCode::ServerError,
+                        message: "stop".to_owned(),
+                    };
+                    env.ws_responses
+                        .send(WrappedResponse::Response(ResponseMessage::Response(
+                            Some(request_id),
+                            Err(error),
+                        )))
+                        .unwrap();
+                    break req.utxos;
+                }
+            }
+        }
+    );
+    assert!(res.is_err());
+    let utxos = utxos.iter().map(UtxoExt::outpoint).collect::<Vec<_>>();
+    assert_eq!(utxos, [outpoint]);
+
+    let res = get_quote(&mut env.data, ClientId(1), quote_req(Vec::new())).await;
+    assert!(matches!(
+        res,
+        Err(Error::NotEnoughAmount { available: 0, .. })
+    ));
+}
+
+#[tokio::test]
+async fn refreshed_tickers_added_to_balances() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    let mut notif_receiver = env.connect_client(1).await;
+    let new_asset = AssetId::from_slice(&[10; 32]).unwrap();
+
+    let mut utxo = wallet_tx_to(sideswap_lwk::Chain::External, 0).outputs[1]
+        .clone()
+        .unwrap();
+    utxo.unblinded.asset = new_asset;
+    utxo.unblinded.value = 1234;
+    *env.wallet_utxos.lock().unwrap() = vec![utxo];
+
+    let mut balances = async |env: &mut TestEnv| {
+        process_wallet_event(&mut env.data, DEFAULT_WALLET, sideswap_lwk::Event::Updated).await;
+        recv_all(&mut notif_receiver)
+            .into_iter()
+            .find_map(|notif| match notif {
+                api::Notif::Balances(notif) => Some(notif.balances),
+                _ => None,
+            })
+            .unwrap()
+    };
+    // Reported in the base units until whitelisted
+    assert_eq!(
+        balances(&mut env).await,
+        BTreeMap::from([(DealerTicker::from_str("0a0a0a0a").unwrap(), 1234.0)])
+    );
+
+    // The same ticker with another asset is not replaced
+    let refreshed = TickerLoader::from_assets([
+        (
+            env.data.policy_asset,
+            DealerTicker::LBTC,
+            AssetPrecision::BITCOIN_PRECISION,
+        ),
+        (new_asset, DealerTicker::DEPIX, AssetPrecision::TWO),
+        (
+            AssetId::from_slice(&[11; 32]).unwrap(),
+            DealerTicker::USDT,
+            AssetPrecision::BITCOIN_PRECISION,
+        ),
+    ]);
+    while env.ws_requests.try_recv().is_ok() {}
+    assert_eq!(
+        merge_tickers(&mut env.data, &refreshed),
+        [DealerTicker::DEPIX]
+    );
+    assert_eq!(
+        *env.data.ticker_loader.asset_id(DealerTicker::USDT),
+        Network::LiquidTestnet.d().known_assets.USDt
+    );
+
+    // The asset flags are requested again for the new asset
+    let mut assets_requested = false;
+    while let Ok(req) = env.ws_requests.try_recv() {
+        assets_requested |= matches!(
+            req,
+            WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+                _,
+                sideswap_api::Request::Assets(_),
+            ))
+        );
+    }
+    assert!(assets_requested);
+
+    assert_eq!(
+        balances(&mut env).await,
+        BTreeMap::from([(DealerTicker::DEPIX, 12.34)])
+    );
+
+    assert!(merge_tickers(&mut env.data, &refreshed).is_empty());
+    assert!(env.ws_requests.try_recv().is_err());
+
+    // The asset registry is not loaded in tests
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::RefreshTickers(api::RefreshTickersReq {}),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::InvalidConfig(_))));
+}
+
+/// Respond to the next AssetDetails request
+async fn reply_asset_details(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+    res: Result<sideswap_api::AssetDetailsResponse, sideswap_api::Error>,
+) {
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::AssetDetails(_),
+        )) = req
+        {
+            ws_responses
+                .send(WrappedResponse::Response(ResponseMessage::Response(
+                    Some(request_id),
+                    res.map(sideswap_api::Response::AssetDetails),
+                )))
+                .expect("must not fail");
+            return;
+        }
+    }
+}
+
+#[tokio::test]
+async fn unknown_asset_info_requested() {
+    let mut env = TestEnv::new().await;
+    env.connect_upstream().await;
+    let asset_id = AssetId::from_slice(&[10; 32]).unwrap();
+
+    let details = sideswap_api::AssetDetailsResponse {
+        asset_id,
+        name: "Test Token".to_owned(),
+        ticker: sideswap_api::Ticker("TESTTOKEN".to_owned()),
+        precision: AssetPrecision::TWO,
+        icon_url: String::new(),
+        domain: "example.com".to_owned(),
+        domain_agent: None,
+        chain_stats: None,
+        chart_url: None,
+        chart_stats: None,
+    };
+    let (res, ()) = tokio::join!(
+        get_asset_info(&mut env.data, api::GetAssetInfoReq { asset_id }),
+        reply_asset_details(&mut env.ws_requests, &env.ws_responses, Ok(details)),
+    );
+    let resp = res.unwrap();
+    assert_eq!(
+        resp.balance_key,
+        DealerTicker::from_str("0a0a0a0a").unwrap()
+    );
+    assert!(!resp.whitelisted);
+    assert!(resp.registered);
+    assert_eq!(resp.ticker.as_deref(), Some("TESTTOKEN"));
+    assert_eq!(resp.precision, Some(2));
+
+    // Not registered
+    let error = sideswap_api::Error {
+        code: sideswap_api::ErrorCode::InvalidRequest,
+        message: "unknown asset".to_owned(),
+    };
+    let asset_id = env.data.policy_asset;
+    let (res, ()) = tokio::join!(
+        get_asset_info(&mut env.data, api::GetAssetInfoReq { asset_id }),
+        reply_asset_details(&mut env.ws_requests, &env.ws_responses, Err(error)),
+    );
+    let resp = res.unwrap();
+    assert_eq!(resp.balance_key, DealerTicker::LBTC);
+    assert!(resp.whitelisted);
+    assert!(!resp.registered);
+    assert_eq!(resp.name, None);
+}
+
+#[tokio::test]
+async fn unconfirmed_utxos_reported_and_excluded() {
+    let mut env = TestEnv::new().await;
+    let req = prepare_get_quote(&mut env).await;
+    env.wallet().utxo_data = Some(test_utxos(env.data.policy_asset, 100_000, 2));
+    let mut notif_receiver = env.connect_client(1).await;
+    let outpoint = |vout| elements::OutPoint::new(elements::Txid::from_byte_array([1; 32]), vout);
+    let policy_asset = env.data.policy_asset;
+    let wallet_utxo = |vout, height| {
+        let mut utxo = wallet_tx_to(sideswap_lwk::Chain::External, 0).outputs[1]
+            .clone()
+            .unwrap();
+        utxo.outpoint = outpoint(vout);
+        utxo.height = height;
+        utxo.unblinded.asset = policy_asset;
+        utxo.unblinded.value = 100_000;
+        utxo
+    };
+
+    let mut balances = async |env: &mut TestEnv| {
+        process_wallet_event(&mut env.data, DEFAULT_WALLET, sideswap_lwk::Event::Updated).await;
+        recv_all(&mut notif_receiver)
+            .into_iter()
+            .filter_map(|notif| match notif {
+                api::Notif::Balances(notif) => Some((notif.confirmed, notif.unconfirmed)),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    let lbtc = |amount| BTreeMap::from([(DealerTicker::LBTC, amount)]);
+
+    *env.wallet_utxos.lock().unwrap() =
+        vec![wallet_utxo(0, Some(TEST_TIP_HEIGHT)), wallet_utxo(1, None)];
+    assert_eq!(balances(&mut env).await, [(lbtc(0.001), lbtc(0.001))]);
+
+    let res = get_quote(
+        &mut env.data,
+        ClientId(1),
+        api::GetQuoteReq {
+            send_amount: Some(0.0015),
+            receive_address: Some(test_address(0)),
+            gaid: None,
+            allow_unconfirmed: false,
+            wallet: DEFAULT_WALLET.to_owned(),
+            ..req
+        },
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(Error::NotEnoughAmount {
+            required: 150_000,
+            available: 100_000,
+            ..
+        })
+    ));
+
+    // Only the confirmed L-BTC UTXOs are given to the wallet
+    let create_req = |amount| api::CreateTxReq {
+        recipients: vec![api::Recipient {
+            address: Some(test_address(5).to_string()),
+            asset: Some(DealerTicker::LBTC),
+            uri: None,
+            amount,
+            send_all: false,
+        }],
+        aggregate_duplicates: false,
+        fee_rate: None,
+        allow_unconfidential: false,
+        utxos: None,
+        allow_unconfirmed: false,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+    let res = create_tx(&mut env.data, ClientId(1), create_req(0.0015)).await;
+    assert!(matches!(
+        res,
+        Err(Error::NotEnoughAmount {
+            available: 100_000,
+            ..
+        })
+    ));
+    let resp = create_tx(&mut env.data, ClientId(1), create_req(0.0008))
+        .await
+        .unwrap();
+    let inputs = env.data.created_txs[&resp.txid]
+        .tx
+        .input
+        .iter()
+        .map(|input| input.previous_output)
+        .collect::<Vec<_>>();
+    assert_eq!(inputs, [outpoint(0)]);
+
+    // The same total, but another split
+    *env.wallet_utxos.lock().unwrap() = vec![
+        wallet_utxo(0, Some(TEST_TIP_HEIGHT)),
+        wallet_utxo(1, Some(TEST_TIP_HEIGHT)),
+    ];
+    assert_eq!(balances(&mut env).await, [(lbtc(0.002), BTreeMap::new())]);
+    assert!(balances(&mut env).await.is_empty());
+}
+
+#[tokio::test]
+async fn wallet_timeout_does_not_stall_worker() {
+    let mut env = TestEnv::new().await;
+    env.data.settings.wallet_timeout_seconds = Some(1);
+    // The fake wallet is not started, so the wallet commands are never answered
+
+    let request = |req| {
+        let (res_sender, res_receiver) = tokio::sync::oneshot::channel();
+        let command = Command::Request {
+            client_id: ClientId(1),
+            req,
+            res_sender: res_sender.into(),
+        };
+        (command, res_receiver)
+    };
+
+    let (command, mut wallet_txs_res) = request(api::Req::GetWalletTxs(api::GetWalletTxsReq {
+        after_height: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+    }));
+    process_command(&mut env.data, command).await;
+
+    // Answered while the wallet request is still pending
+    let (command, res_receiver) = request(api::Req::GetDiagnostics(api::GetDiagnosticsReq {}));
+    process_command(&mut env.data, command).await;
+    assert!(matches!(
+        res_receiver.await.unwrap(),
+        Ok(api::Resp::GetDiagnostics(_))
+    ));
+    assert!(wallet_txs_res.try_recv().is_err());
+
+    assert!(matches!(
+        wallet_txs_res.await.unwrap(),
+        Err(Error::WalletTimeout(1))
+    ));
+
+    // The other wallet commands time out without blocking forever too
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::NewAddress(api::NewAddressReq {
+            user_note: None,
+            wallet: DEFAULT_WALLET.to_owned(),
+        }),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::WalletTimeout(1))));
+}
+
+/// Respond to the next market request for which `resp` returns a response (other requests are skipped)
+async fn reply_market_request(
+    ws_requests: &mut UnboundedReceiver<WrappedRequest>,
+    ws_responses: &UnboundedSender<WrappedResponse>,
+    mut resp: impl FnMut(&mkt::Request) -> Option<mkt::Response>,
+) {
+    loop {
+        let req = ws_requests.recv().await.expect("must be open");
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            request_id,
+            sideswap_api::Request::Market(req),
+        )) = req
+        {
+            if let Some(resp) = resp(&req) {
+                ws_responses
+                    .send(WrappedResponse::Response(ResponseMessage::Response(
+                        Some(request_id),
+                        Ok(sideswap_api::Response::Market(resp)),
+                    )))
+                    .expect("must not fail");
+                break;
+            }
+        }
+    }
+}
+
+fn sent_maker_signs(ws_requests: &mut UnboundedReceiver<WrappedRequest>) -> Vec<mkt::QuoteId> {
+    let mut quote_ids = Vec::new();
+    while let Ok(req) = ws_requests.try_recv() {
+        if let WrappedRequest::Request(sideswap_api::RequestMessage::Request(
+            _,
+            sideswap_api::Request::Market(mkt::Request::MakerSign(req)),
+        )) = req
+        {
+            quote_ids.push(req.quote_id);
+        }
+    }
+    quote_ids
+}
+
+#[tokio::test]
+async fn maker_orders_submitted_and_signed() {
+    let mut env = TestEnv::new().await;
+    env.start_wallet(0);
+    env.data.markets = vec![usdt_market()];
+    env.wallet().utxo_data = Some(test_utxo_data(env.data.policy_asset, 1_000_000));
+    let usdt = *env.data.ticker_loader.asset_id(DealerTicker::USDT);
+    let policy_asset = env.data.policy_asset;
+
+    let submit_req = || api::SubmitOrderReq {
+        base: DealerTicker::LBTC,
+        quote: DealerTicker::USDT,
+        trade_dir: api::TradeDir::Sell,
+        amount: 0.01,
+        price: 100_000.0,
+        ttl_seconds: None,
+        private: false,
+        client_order_id: None,
+        wallet: DEFAULT_WALLET.to_owned(),
+    };
+
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::SubmitOrder(submit_req()),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::MakerOrdersDisabled)));
+    env.data.settings.maker_orders = true;
+
+    // The market session is funded from the default wallet only
+    env.data
+        .wallets
+        .insert("cold".to_owned(), WalletData::new(mpsc::channel().0));
+    let cold_req = api::SubmitOrderReq {
+        wallet: "cold".to_owned(),
+        ..submit_req()
+    };
+    let res = submit_order(&mut env.data, ClientId(1), cold_req).await;
+    assert!(matches!(res, Err(Error::InvalidOrderRequest(_))));
+    env.data.wallets.remove("cold");
+
+    // Registered on the first login, the wallet UTXOs are added after that
+    env.ws_responses
+        .send(WrappedResponse::Connected)
+        .expect("must not fail");
+    let event = env.data.ws.recv().await;
+    tokio::join!(process_ws_event(&mut env.data, event), async {
+        reply_market_request(&mut env.ws_requests, &env.ws_responses, |req| {
+            matches!(req, mkt::Request::Register(_)).then(|| {
+                mkt::Response::Register(mkt::RegisterResponse {
+                    token: "market-token".to_owned(),
+                })
+            })
+        })
+        .await;
+        reply_market_request(&mut env.ws_requests, &env.ws_responses, |req| match req {
+            mkt::Request::Login(req) => {
+                assert_eq!(req.token, "market-token");
+                Some(mkt::Response::Login(mkt::LoginResponse {
+                    orders: Vec::new(),
+                    utxos: Vec::new(),
+                    new_events: Vec::new(),
+                    min_order_amounts: None,
+                }))
+            }
+            _ => None,
+        })
+        .await;
+        reply_market_request(&mut env.ws_requests, &env.ws_responses, |req| match req {
+            mkt::Request::AddUtxos(req) => Some(mkt::Response::AddUtxos(mkt::AddUtxosResponse {
+                utxos: req.utxos.iter().map(UtxoExt::outpoint).collect(),
+            })),
+            _ => None,
+        })
+        .await;
+    });
+    assert_eq!(
+        env.data.db.get_setting::<String>(MARKET_TOKEN_KEY).await,
+        Some("market-token".to_owned())
+    );
+    assert_eq!(env.data.market_utxos.as_ref().map(BTreeSet::len), Some(1));
+
+    // New wallet UTXOs are registered too
+    let (event_res, ()) = tokio::join!(
+        process_wallet_event(
+            &mut env.data,
+            DEFAULT_WALLET,
+            sideswap_lwk::Event::Utxos {
+                utxo_data: test_utxos(policy_asset, 1_000_000, 2),
+            },
+        ),
+        reply_market_request(&mut env.ws_requests, &env.ws_responses, |req| match req {
+            mkt::Request::AddUtxos(req) => {
+                assert_eq!(req.utxos.len(), 1);
+                Some(mkt::Response::AddUtxos(mkt::AddUtxosResponse {
+                    utxos: req.utxos.iter().map(UtxoExt::outpoint).collect(),
+                }))
+            }
+            _ => None,
+        })
+    );
+    let () = event_res;
+    assert_eq!(env.data.market_utxos.as_ref().map(BTreeSet::len), Some(2));
+
+    let order_id = mkt::OrdId::new(1);
+    let own_order = |base_amount, price| mkt::OwnOrder {
+        order_id,
+        created_at: TimestampMs::from_millis(0),
+        client_order_id: None,
+        asset_pair: usdt_market().asset_pair,
+        price: NormalFloat::new(price).expect("must be valid"),
+        price_tracking: None,
+        orig_amount: base_amount,
+        active_amount: base_amount,
+        trade_dir: TradeDir::Sell,
+        ttl: None,
+        private_id: None,
+        online: true,
+    };
+
+    let (res, ()) = tokio::join!(
+        process_request(
+            &mut env.data,
+            ClientId(1),
+            api::Req::SubmitOrder(submit_req())
+        ),
+        reply_market_request(&mut env.ws_requests, &env.ws_responses, |req| match req {
+            mkt::Request::AddOrder(req) => {
+                assert_eq!(req.base_amount, 1_000_000);
+                assert_eq!(req.receive_address, test_address(1000));
+                assert_eq!(req.change_address, test_address(1000));
+                Some(mkt::Response::AddOrder(mkt::AddOrderResponse {
+                    order: own_order(req.base_amount, req.price.expect("must be set").value()),
+                }))
+            }
+            _ => None,
+        })
+    );
+    let Ok(api::Resp::SubmitOrder(resp)) = res else {
+        panic!("unexpected response");
+    };
+    assert_eq!(resp.order.order_id, order_id);
+    assert_eq!(resp.order.orig_amount, 0.01);
+    assert_eq!(env.data.db.load_maker_orders().await.len(), 1);
+
+    let (res, ()) = tokio::join!(
+        process_request(
+            &mut env.data,
+            ClientId(1),
+            api::Req::EditOrder(api::EditOrderReq {
+                order_id,
+                amount: Some(0.005),
+                price: None,
+            })
+        ),
+        reply_market_request(&mut env.ws_requests, &env.ws_responses, |req| match req {
+            mkt::Request::EditOrder(req) => {
+                assert_eq!(req.base_amount, Some(500_000));
+                assert_eq!(req.price, None);
+                Some(mkt::Response::EditOrder(mkt::EditOrderResponse {
+                    order: own_order(500_000, 100_000.0),
+                }))
+            }
+            _ => None,
+        })
+    );
+    assert!(matches!(res, Ok(api::Resp::EditOrder(_))));
+
+    let Ok(api::Resp::ListOwnOrders(resp)) = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::ListOwnOrders(api::ListOwnOrdersReq {}),
+    )
+    .await
+    else {
+        panic!("unexpected response");
+    };
+    assert_eq!(resp.orders.len(), 1);
+    assert_eq!(resp.orders[0].active_amount, 0.005);
+
+    // 0.001 L-BTC sold for 100 USDt
+    let maker_sign = |quote_id, recv_amount| {
+        let mut pset = PartiallySignedTransaction::new_v2();
+        pset.add_input(elements::pset::Input::from_prevout(
+            elements::OutPoint::new(elements::Txid::from_byte_array([1; 32]), 0),
+        ));
+        for (asset, amount) in [(usdt, recv_amount), (policy_asset, 900_000)] {
+            pset.add_output(elements::pset::Output {
+                script_pubkey: test_address(1000).script_pubkey(),
+                asset: Some(asset),
+                amount: Some(amount),
+                ..Default::default()
+            });
+        }
+        market_notif(mkt::Notification::MakerSign(mkt::MakerSignNotif {
+            quote_id: mkt::QuoteId::new(quote_id),
+            orders: vec![mkt::MakerSwapInfo {
+                order_id,
+                price: NormalFloat::new(100_000.0).expect("must be valid"),
+                base_amount: 100_000,
+                quote_amount: 10_000_000_000,
+            }],
+            pset: encode_pset(&pset),
+        }))
+    };
+
+    process_ws_event(&mut env.data, maker_sign(1, 10_000_000_000)).await;
+    assert_eq!(
+        sent_maker_signs(&mut env.ws_requests),
+        vec![mkt::QuoteId::new(1)]
+    );
+    assert_eq!(env.data.monitored_txs.len(), 1);
+    let events = env.data.db.load_audit_events().await;
+    assert!(events
+        .last()
+        .unwrap()
+        .event
+        .starts_with("maker swap 0.001 L-BTC for 100 USDt, orders: 1, txid: "));
+
+    // Underpaid
+    process_ws_event(&mut env.data, maker_sign(2, 9_000_000_000)).await;
+    assert!(sent_maker_signs(&mut env.ws_requests).is_empty());
+
+    // Not signed while locked
+    let config = crate::signing_lock::Config {
+        timeout_minutes: 15,
+        password_hash: crate::signing_lock::password_hash("secret"),
+    };
+    env.data.signing_lock = Some(SigningLock::new(&config, Instant::now()));
+    process_ws_event(&mut env.data, maker_sign(3, 10_000_000_000)).await;
+    assert!(sent_maker_signs(&mut env.ws_requests).is_empty());
+    env.data.signing_lock = None;
+
+    // Matched swaps are signed without an approval, so the orders above the thresholds are refused
+    env.data.settings.approvals = Some(crate::approvals::Config {
+        thresholds: BTreeMap::from([(DealerTicker::LBTC, 0.0005)]),
+        ttl_seconds: None,
+    });
+    process_ws_event(&mut env.data, maker_sign(4, 10_000_000_000)).await;
+    assert!(sent_maker_signs(&mut env.ws_requests).is_empty());
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::SubmitOrder(submit_req()),
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(Error::OrderAboveApprovalThreshold {
+            asset: DealerTicker::LBTC,
+            threshold: 0.0005,
+        })
+    ));
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::EditOrder(api::EditOrderReq {
+            order_id,
+            amount: None,
+            price: Some(110_000.0),
+        }),
+    )
+    .await;
+    assert!(matches!(
+        res,
+        Err(Error::OrderAboveApprovalThreshold { .. })
+    ));
+    // A buy order sends the quote asset (USDt has no threshold)
+    let (send_asset, send_amount) = order_send_amount(
+        TradeDir::Buy,
+        (DealerTicker::LBTC, DealerTicker::USDT),
+        0.01,
+        100_000.0,
+    );
+    assert_eq!((send_asset, send_amount), (DealerTicker::USDT, 1000.0));
+    check_order_threshold(&env.data, send_asset, send_amount).unwrap();
+    env.data.settings.approvals = None;
+
+    // The orders are offline until the next login
+    process_ws_event(&mut env.data, WrappedResponse::Disconnected).await;
+    assert!(env.data.market_utxos.is_none());
+    assert!(!env.data.own_orders[&order_id].online);
+
+    process_ws_event(
+        &mut env.data,
+        market_notif(mkt::Notification::OwnOrderRemoved(
+            mkt::OwnOrderRemovedNotif { order_id },
+        )),
+    )
+    .await;
+    assert!(env.data.own_orders.is_empty());
+    assert!(env.data.db.load_maker_orders().await.is_empty());
+
+    let res = process_request(
+        &mut env.data,
+        ClientId(1),
+        api::Req::CancelOrder(api::CancelOrderReq { order_id }),
+    )
+    .await;
+    assert!(matches!(res, Err(Error::UnknownOrder(_))));
+}
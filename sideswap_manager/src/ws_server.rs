@@ -1,37 +1,187 @@
-use std::net::SocketAddr;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
-use futures::{SinkExt, StreamExt};
+use futures::{future::BoxFuture, stream::FuturesUnordered, FutureExt, SinkExt, StreamExt};
 use serde::Deserialize;
 use tokio::{
     net::{TcpListener, TcpStream},
     sync::{
         mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
-        oneshot,
+        oneshot, watch,
+    },
+    time::Instant,
+};
+use tokio_tungstenite::{
+    tungstenite::{
+        handshake::server::{ErrorResponse, Request, Response},
+        http::StatusCode,
+        Message,
     },
+    WebSocketStream,
 };
-use tokio_tungstenite::{tungstenite::Message, WebSocketStream};
+use tracing::Instrument;
 
-use crate::{error::Error, worker::Command};
+use crate::{
+    error::Error,
+    notif_encoding::{NotifEncoding, SharedNotif},
+    quotas,
+    worker::Command,
+};
 
 use super::api;
 
-#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct ClientId(u64);
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ClientId(pub(crate) u64);
 
-#[derive(Debug, Clone, Deserialize)]
+static LAST_CLIENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Shared by the WS connections and the HTTP requests, so the IDs are never reused
+pub fn next_client_id() -> ClientId {
+    ClientId(LAST_CLIENT_ID.fetch_add(1, Ordering::Relaxed) + 1)
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Config {
     listen_on: SocketAddr,
+    /// If set, new connections must send `To::Login` with this token first
+    auth_token: Option<String>,
+    /// Per-client login tokens (client name -> token), accepted besides `auth_token`.
+    /// A client logged in with its own token is identified by the name (`client_name` is ignored),
+    /// only such clients can approve (see `approvals`).
+    #[serde(default)]
+    client_tokens: BTreeMap<String, String>,
+    /// How often the clients are pinged (in seconds), 30 by default
+    ping_interval_seconds: Option<u64>,
+    /// The connection is closed if nothing is received for this long after a ping (in seconds), 10 by default.
+    /// New connections must log in within the same time if `auth_token` is set.
+    ping_timeout_seconds: Option<u64>,
+}
+
+/// The connection is closed after this many failed login attempts
+const MAX_LOGIN_FAILURES: usize = 3;
+
+/// The requests of one connection waiting for the worker, the next ones are rejected
+const MAX_PENDING_REQS: usize = 100;
+
+/// The number of messages in one batch
+const MAX_BATCH_LEN: usize = 100;
+
+const DEFAULT_PING_INTERVAL: Duration = Duration::from_secs(30);
+const DEFAULT_PING_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Detects the clients that went away without closing the connection
+#[derive(Debug, Copy, Clone)]
+struct Keepalive {
+    interval: Duration,
+    timeout: Duration,
+}
+
+impl Config {
+    pub fn listen_on(&self) -> SocketAddr {
+        self.listen_on
+    }
+
+    pub fn auth(&self) -> Auth {
+        Auth {
+            auth_token: self.auth_token.clone(),
+            client_tokens: self.client_tokens.clone(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), anyhow::Error> {
+        for (name, token) in self.client_tokens.iter() {
+            quotas::validate_client_name(name)?;
+            anyhow::ensure!(!token.is_empty(), "the client token of {name} is empty");
+            anyhow::ensure!(
+                self.auth_token.as_ref() != Some(token),
+                "the client token of {name} is the same as auth_token"
+            );
+        }
+        let tokens = self.client_tokens.values().collect::<BTreeSet<_>>();
+        anyhow::ensure!(
+            tokens.len() == self.client_tokens.len(),
+            "the client tokens must be different"
+        );
+        Ok(())
+    }
+
+    fn keepalive(&self) -> Keepalive {
+        Keepalive {
+            interval: self
+                .ping_interval_seconds
+                .map_or(DEFAULT_PING_INTERVAL, Duration::from_secs),
+            timeout: self
+                .ping_timeout_seconds
+                .map_or(DEFAULT_PING_TIMEOUT, Duration::from_secs),
+        }
+    }
+}
+
+/// The accepted login tokens (shared by the WS and HTTP servers)
+#[derive(Debug, Clone, Default)]
+pub struct Auth {
+    pub auth_token: Option<String>,
+    /// Client name -> token
+    pub client_tokens: BTreeMap<String, String>,
+}
+
+/// A successful login
+#[derive(Debug, Clone, PartialEq)]
+pub enum Login {
+    /// With `auth_token` (or no login is required), the client name is self-asserted (`client_name`)
+    Shared,
+    /// With one of `client_tokens`, the client is identified by the name
+    Client(String),
+}
+
+impl Auth {
+    pub fn required(&self) -> bool {
+        self.auth_token.is_some() || !self.client_tokens.is_empty()
+    }
+
+    /// All tokens are compared, so the response time does not depend on which one matched
+    pub fn check(&self, token: &str) -> Option<Login> {
+        if !self.required() {
+            return Some(Login::Shared);
+        }
+        let shared = self
+            .auth_token
+            .as_deref()
+            .is_some_and(|auth_token| token_matches(Some(auth_token), token));
+        let client = self
+            .client_tokens
+            .iter()
+            .fold(None, |found, (name, client_token)| {
+                let matched = token_matches(Some(client_token), token);
+                found.or(matched.then(|| name.clone()))
+            });
+        match (client, shared) {
+            (Some(name), _) => Some(Login::Client(name)),
+            (None, true) => Some(Login::Shared),
+            (None, false) => None,
+        }
+    }
 }
 
 struct Data {
+    client_id: ClientId,
     command_sender: UnboundedSender<Command>,
     ws_stream: WebSocketStream<TcpStream>,
+    notif_encoding: NotifEncoding,
+    auth: Auth,
+    keepalive: Keepalive,
+    /// Requests waiting for the worker, the responses are sent in the completion order
+    pending_reqs: FuturesUnordered<BoxFuture<'static, api::From>>,
 }
 
 async fn send_msg(data: &mut Data, msg: Message) {
     let res = data.ws_stream.send(msg).await;
     if let Err(err) = res {
-        log::debug!("ws message sending failed: {err}");
+        tracing::debug!("ws message sending failed: {err}");
     }
 }
 
@@ -40,13 +190,22 @@ async fn send_from(data: &mut Data, from: api::From) {
     send_msg(data, Message::text(msg)).await;
 }
 
-async fn send_notif(data: &mut Data, notif: api::Notif) {
-    send_from(data, api::From::Notif { notif }).await;
+async fn send_notif(data: &mut Data, notif: SharedNotif) {
+    let msg = match data.notif_encoding {
+        NotifEncoding::Json => Message::text(notif.json()),
+        NotifEncoding::Protobuf => Message::binary(notif.protobuf().to_vec()),
+    };
+    send_msg(data, msg).await;
 }
 
-async fn process_ws_req(data: &mut Data, req: api::Req) -> Result<api::Resp, Error> {
+pub async fn process_req(
+    command_sender: UnboundedSender<Command>,
+    client_id: ClientId,
+    req: api::Req,
+) -> Result<api::Resp, Error> {
     let (res_sender, res_receiver) = oneshot::channel();
-    data.command_sender.send(Command::Request {
+    command_sender.send(Command::Request {
+        client_id,
         req,
         res_sender: res_sender.into(),
     })?;
@@ -54,39 +213,163 @@ async fn process_ws_req(data: &mut Data, req: api::Req) -> Result<api::Resp, Err
     Ok(resp)
 }
 
+/// The response is sent once ready, the next messages are read meanwhile
 async fn process_to_msg(data: &mut Data, to: api::To) {
     match to {
+        api::To::Req { id, req: _ } if data.pending_reqs.len() >= MAX_PENDING_REQS => {
+            let text = format!("too many requests in progress (max {MAX_PENDING_REQS})");
+            send_from(data, too_many_requests(id, text)).await;
+        }
         api::To::Req { id, req } => {
-            let res = process_ws_req(data, req).await;
-            match res {
-                Ok(resp) => send_from(data, api::From::Resp { id, resp }).await,
-                Err(err) => {
-                    send_from(
-                        data,
-                        api::From::Error {
+            let res = process_req(data.command_sender.clone(), data.client_id, req);
+            data.pending_reqs.push(
+                async move {
+                    match res.await {
+                        Ok(resp) => api::From::Resp { id, resp },
+                        Err(err) => api::From::Error {
                             id,
                             err: err.into(),
                         },
-                    )
-                    .await
+                    }
                 }
-            }
+                .boxed(),
+            );
+        }
+        api::To::Login { token } => {
+            // Already logged in (or no login is required)
+            let from = if token_valid(data, &token) {
+                api::From::LoggedIn {}
+            } else {
+                unauthorized(0, "wrong token")
+            };
+            data.pending_reqs.push(async move { from }.boxed());
         }
     }
 }
 
-fn get_req_id(msg: &str) -> api::ReqId {
-    #[derive(serde::Deserialize)]
-    pub enum ToIdOnly {
-        Req { id: api::ReqId },
+fn token_valid(data: &Data, token: &str) -> bool {
+    data.auth.check(token).is_some()
+}
+
+/// Compares all bytes, so the response time does not depend on the matched prefix length.
+/// Any token is accepted if `auth_token` is not set.
+pub fn token_matches(auth_token: Option<&str>, token: &str) -> bool {
+    match auth_token {
+        Some(auth_token) => {
+            auth_token.len() == token.len()
+                && auth_token
+                    .bytes()
+                    .zip(token.bytes())
+                    .fold(0, |acc, (a, b)| acc | (a ^ b))
+                    == 0
+        }
+        None => true,
+    }
+}
+
+fn unauthorized(id: api::ReqId, text: &str) -> api::From {
+    api::From::Error {
+        id,
+        err: api::Error {
+            code: api::ErrorCode::Unauthorized,
+            text: text.to_owned(),
+            details: None,
+        },
+    }
+}
+
+/// Waits for a valid `To::Login`, returns `None` if the connection is closed or there are too many failures
+async fn login(data: &mut Data) -> Option<Login> {
+    let mut failures = 0;
+    while failures < MAX_LOGIN_FAILURES {
+        let msg = match data.ws_stream.next().await {
+            Some(Ok(Message::Text(msg))) => msg,
+            Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return None,
+            Some(Ok(_)) => continue,
+        };
+        let from = match serde_json::from_str::<api::To>(&msg) {
+            Ok(api::To::Login { token }) => match data.auth.check(&token) {
+                Some(login) => {
+                    send_from(data, api::From::LoggedIn {}).await;
+                    return Some(login);
+                }
+                None => unauthorized(0, "wrong token"),
+            },
+            Ok(api::To::Req { id, req: _ }) => unauthorized(id, "login required"),
+            Err(_) => unauthorized(get_req_id(&msg), "login required"),
+        };
+        send_from(data, from).await;
+        failures += 1;
     }
+    tracing::debug!("too many failed login attempts, close connection");
+    None
+}
+
+#[derive(serde::Deserialize)]
+enum ToIdOnly {
+    Req { id: api::ReqId },
+}
+
+fn get_req_id(msg: &str) -> api::ReqId {
     serde_json::from_str::<ToIdOnly>(msg)
         .map(|ToIdOnly::Req { id }| id)
         .unwrap_or_default()
 }
 
+fn too_many_requests(id: api::ReqId, text: String) -> api::From {
+    api::From::Error {
+        id,
+        err: api::Error {
+            code: api::ErrorCode::TooManyRequests,
+            text,
+            details: None,
+        },
+    }
+}
+
+fn invalid_json(id: api::ReqId, err: serde_json::Error) -> api::From {
+    api::From::Error {
+        id,
+        err: api::Error {
+            code: api::ErrorCode::InvalidRequest,
+            text: format!("invalid JSON: {err}"),
+            details: None,
+        },
+    }
+}
+
+/// A JSON array of `To` messages, every request gets its own response
+async fn process_batch(data: &mut Data, msg: &str) {
+    let values = match serde_json::from_str::<Vec<serde_json::Value>>(msg) {
+        Ok(values) => values,
+        Err(err) => {
+            send_from(data, invalid_json(0, err)).await;
+            return;
+        }
+    };
+    if values.len() > MAX_BATCH_LEN {
+        let text = format!("the batch is too long (max {MAX_BATCH_LEN} messages)");
+        send_from(data, too_many_requests(0, text)).await;
+        return;
+    }
+    for value in values {
+        match api::To::deserialize(&value) {
+            Ok(to) => process_to_msg(data, to).await,
+            Err(err) => {
+                let id = ToIdOnly::deserialize(&value)
+                    .map(|ToIdOnly::Req { id }| id)
+                    .unwrap_or_default();
+                send_from(data, invalid_json(id, err)).await;
+            }
+        }
+    }
+}
+
 async fn process_ws_msg(data: &mut Data, msg: Message) {
     match msg {
+        Message::Text(msg) if msg.trim_start().starts_with('[') => {
+            process_batch(data, &msg).await;
+        }
         Message::Text(msg) => {
             let res = serde_json::from_str::<api::To>(&msg);
             match res {
@@ -94,68 +377,80 @@ async fn process_ws_msg(data: &mut Data, msg: Message) {
                     process_to_msg(data, to).await;
                 }
                 Err(err) => {
-                    send_from(
-                        data,
-                        api::From::Error {
-                            id: get_req_id(&msg),
-                            err: api::Error {
-                                code: api::ErrorCode::InvalidRequest,
-                                text: format!("invalid JSON: {err}"),
-                                details: None,
-                            },
-                        },
-                    )
-                    .await;
+                    send_from(data, invalid_json(get_req_id(&msg), err)).await;
                 }
             }
         }
         Message::Binary(_) => {
-            log::debug!("binary message ignored");
+            tracing::debug!("binary message ignored");
+        }
+        Message::Ping(payload) => {
+            send_msg(data, Message::Pong(payload)).await;
         }
-        Message::Ping(_) => {}
         Message::Pong(_) => {}
         Message::Close(msg) => {
-            log::debug!("close message received: {msg:?}");
+            tracing::debug!("close message received: {msg:?}");
         }
         Message::Frame(_) => {
-            log::debug!("frame message ignored");
+            tracing::debug!("frame message ignored");
         }
     }
 }
 
 async fn client_loop(
     data: &mut Data,
-    mut notif_receiver: UnboundedReceiver<api::Notif>,
+    mut notif_receiver: UnboundedReceiver<SharedNotif>,
 ) -> Result<(), anyhow::Error> {
+    let keepalive = data.keepalive;
+    let mut ping_timer =
+        tokio::time::interval_at(Instant::now() + keepalive.interval, keepalive.interval);
+    // Set after a ping, cleared once anything is received
+    let mut pong_deadline = None;
+
     loop {
         tokio::select! {
             msg = data.ws_stream.next() => {
+                pong_deadline = None;
                 match msg {
                     Some(Ok(msg)) => {
                         process_ws_msg(data, msg).await;
                     },
                     Some(Err(err)) => {
-                        log::debug!("ws connection closed: {err}");
+                        tracing::debug!("ws connection closed: {err}");
                         break;
                     },
                     None => {
-                        log::debug!("ws connection closed");
+                        tracing::debug!("ws connection closed");
                         break;
                     },
                 }
             },
 
+            Some(from) = data.pending_reqs.next(), if !data.pending_reqs.is_empty() => {
+                send_from(data, from).await;
+            },
+
             notif = notif_receiver.recv() => {
                 match notif {
                     Some(notif) => {
                         send_notif(data, notif).await;
                     },
                     None => {
-                        log::debug!("disconnect client");
+                        tracing::debug!("disconnect client");
                         break;
                     },
                 }
             },
+
+            _ = ping_timer.tick() => {
+                send_msg(data, Message::Ping(Default::default())).await;
+                pong_deadline.get_or_insert(Instant::now() + keepalive.timeout);
+            },
+
+            _ = tokio::time::sleep_until(pong_deadline.unwrap_or_else(Instant::now)), if pong_deadline.is_some() => {
+                tracing::debug!("no pong received, close dead ws connection");
+                break;
+            },
         }
     }
 
@@ -166,31 +461,83 @@ async fn client_run(
     command_sender: UnboundedSender<Command>,
     client_id: ClientId,
     tcp_stream: TcpStream,
+    auth: Auth,
+    keepalive: Keepalive,
 ) {
-    let ws_stream = match tokio_tungstenite::accept_async(tcp_stream).await {
+    let mut notif_encoding = NotifEncoding::default();
+    let mut client_name = None;
+    let callback = |req: &Request, resp: Response| -> Result<Response, ErrorResponse> {
+        let query = req.uri().query();
+        match NotifEncoding::from_query(query)
+            .and_then(|encoding| Ok((encoding, quotas::client_name_from_query(query)?)))
+        {
+            Ok((encoding, name)) => {
+                notif_encoding = encoding;
+                client_name = name;
+                Ok(resp)
+            }
+            Err(err) => {
+                let mut resp = ErrorResponse::new(Some(err.to_string()));
+                *resp.status_mut() = StatusCode::BAD_REQUEST;
+                Err(resp)
+            }
+        }
+    };
+
+    let ws_stream = match tokio_tungstenite::accept_hdr_async(tcp_stream, callback).await {
         Ok(ws_stream) => ws_stream,
         Err(err) => {
-            log::error!("ws handshake failed: {err}");
+            tracing::error!("ws handshake failed: {err}");
             return;
         }
     };
 
     let mut data = Data {
+        client_id,
         command_sender,
         ws_stream,
+        notif_encoding,
+        auth,
+        keepalive,
+        pending_reqs: FuturesUnordered::new(),
     };
 
+    // Unauthenticated connections are not reported to the worker and get no notifications
+    let mut client_token = false;
+    if data.auth.required() {
+        let login = match tokio::time::timeout(keepalive.timeout, login(&mut data)).await {
+            Ok(login) => login,
+            Err(_) => {
+                tracing::debug!("no login received in time, close connection");
+                None
+            }
+        };
+        match login {
+            Some(Login::Shared) => {}
+            Some(Login::Client(name)) => {
+                client_name = Some(name);
+                client_token = true;
+            }
+            None => {
+                let _ = data.ws_stream.close(None).await;
+                return;
+            }
+        }
+    }
+
     let (event_sender, event_receiver) = unbounded_channel();
 
     let _ = data.command_sender.send(Command::ClientConnected {
         client_id,
+        client_name,
+        client_token,
         notif_sender: event_sender.into(),
     });
 
     let result = client_loop(&mut data, event_receiver).await;
 
     if let Err(err) = result {
-        log::debug!("ws connection stopped: {err}");
+        tracing::debug!("ws connection stopped: {err}");
     }
 
     let _ = data
@@ -198,23 +545,52 @@ async fn client_run(
         .send(Command::ClientDisconnected { client_id });
 }
 
-async fn run(config: Config, command_sender: UnboundedSender<Command>) {
-    log::info!("start WS server on {}...", config.listen_on);
+async fn run(
+    config: Config,
+    command_sender: UnboundedSender<Command>,
+    mut drain_receiver: watch::Receiver<bool>,
+) {
+    tracing::info!("start WS server on {}...", config.listen_on);
     let listener = TcpListener::bind(&config.listen_on)
         .await
         .expect("port must be open");
-    let mut last_id = 0;
 
     loop {
-        let (tcp_stream, _socket) = listener.accept().await.expect("should not fail");
+        tokio::select! {
+            res = listener.accept() => {
+                let (tcp_stream, _socket) = res.expect("should not fail");
+
+                let client_id = next_client_id();
 
-        last_id += 1;
-        let client_id = ClientId(last_id);
+                let span = tracing::debug_span!("ws_client", client_id = client_id.0);
+                tokio::spawn(
+                    client_run(
+                        command_sender.clone(),
+                        client_id,
+                        tcp_stream,
+                        config.auth(),
+                        config.keepalive(),
+                    )
+                    .instrument(span),
+                );
+            },
 
-        tokio::spawn(client_run(command_sender.clone(), client_id, tcp_stream));
+            _ = drain_receiver.wait_for(|draining| *draining) => {
+                tracing::info!("draining, stop accepting new WS connections");
+                break;
+            },
+        }
     }
 }
 
-pub fn start(config: Config, command_sender: UnboundedSender<Command>) {
-    tokio::task::spawn(run(config, command_sender));
+/// The listening socket is closed once `drain_receiver` is set to true
+pub fn start(
+    config: Config,
+    command_sender: UnboundedSender<Command>,
+    drain_receiver: watch::Receiver<bool>,
+) {
+    tokio::task::spawn(run(config, command_sender, drain_receiver));
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,320 @@
+use sideswap_common::channel_helpers::UncheckedUnboundedSender;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use super::*;
+
+type ClientStream = WebSocketStream<tokio_tungstenite::MaybeTlsStream<TcpStream>>;
+
+/// Starts a client connection handler and connects to it
+async fn start_client(auth_token: Option<&str>) -> (ClientStream, UnboundedReceiver<Command>) {
+    let keepalive = Keepalive {
+        interval: DEFAULT_PING_INTERVAL,
+        timeout: DEFAULT_PING_TIMEOUT,
+    };
+    let auth = Auth {
+        auth_token: auth_token.map(str::to_owned),
+        client_tokens: BTreeMap::new(),
+    };
+    start_client_with_keepalive(auth, keepalive).await
+}
+
+async fn start_client_with_keepalive(
+    auth: Auth,
+    keepalive: Keepalive,
+) -> (ClientStream, UnboundedReceiver<Command>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let (command_sender, command_receiver) = unbounded_channel();
+    tokio::spawn(async move {
+        let (tcp_stream, _socket) = listener.accept().await.unwrap();
+        client_run(command_sender, ClientId(1), tcp_stream, auth, keepalive).await;
+    });
+    let (ws_stream, _resp) = tokio_tungstenite::connect_async(format!("ws://{address}"))
+        .await
+        .unwrap();
+    (ws_stream, command_receiver)
+}
+
+/// The returned notification sender keeps the connection open
+async fn client_connected(
+    command_receiver: &mut UnboundedReceiver<Command>,
+) -> UncheckedUnboundedSender<SharedNotif> {
+    match command_receiver.recv().await {
+        Some(Command::ClientConnected { notif_sender, .. }) => notif_sender,
+        _ => panic!("unexpected command"),
+    }
+}
+
+async fn connect() -> (
+    ClientStream,
+    UnboundedReceiver<Command>,
+    UncheckedUnboundedSender<SharedNotif>,
+) {
+    let (ws_stream, mut command_receiver) = start_client(None).await;
+    let notif_sender = client_connected(&mut command_receiver).await;
+    (ws_stream, command_receiver, notif_sender)
+}
+
+async fn recv_json(ws_stream: &mut ClientStream) -> serde_json::Value {
+    let msg = ws_stream.next().await.unwrap().unwrap();
+    serde_json::from_str(msg.to_text().unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn batched_requests_answered_in_completion_order() {
+    let (mut ws_stream, mut command_receiver, _notif_sender) = connect().await;
+
+    let batch = r#"[
+        {"Req":{"id":1,"req":{"ListAssets":{}}}},
+        {"Req":{"id":2,"req":{"Unknown":{}}}},
+        {"Req":{"id":3,"req":{"GetMonitoredTxs":{}}}}
+    ]"#;
+    ws_stream.send(Message::text(batch)).await.unwrap();
+
+    let error = recv_json(&mut ws_stream).await;
+    assert_eq!(error["Error"]["id"], 2);
+    assert_eq!(error["Error"]["err"]["code"], "InvalidRequest");
+
+    let mut res_senders = Vec::new();
+    for _ in 0..2 {
+        match command_receiver.recv().await.unwrap() {
+            Command::Request {
+                req, res_sender, ..
+            } => res_senders.push((req, res_sender)),
+            _ => panic!("unexpected command"),
+        }
+    }
+    let (get_monitored_txs, list_assets) = (res_senders.pop().unwrap(), res_senders.pop().unwrap());
+    assert!(matches!(list_assets.0, api::Req::ListAssets(_)));
+    assert!(matches!(get_monitored_txs.0, api::Req::GetMonitoredTxs(_)));
+
+    // The slow request does not block the later ones
+    get_monitored_txs
+        .1
+        .send(Ok(api::Resp::GetMonitoredTxs(api::GetMonitoredTxsResp {
+            txs: Vec::new(),
+        })));
+    let resp = recv_json(&mut ws_stream).await;
+    assert_eq!(resp["Resp"]["id"], 3);
+    assert!(resp["Resp"]["resp"]["GetMonitoredTxs"].is_object());
+
+    list_assets
+        .1
+        .send(Ok(api::Resp::ListAssets(api::ListAssetsResp {
+            assets: Vec::new(),
+        })));
+    let resp = recv_json(&mut ws_stream).await;
+    assert_eq!(resp["Resp"]["id"], 1);
+    assert!(resp["Resp"]["resp"]["ListAssets"].is_object());
+
+    // Single messages still work
+    ws_stream
+        .send(Message::text(r#"{"Req":{"id":4,"req":{"ListAssets":{}}}}"#))
+        .await
+        .unwrap();
+    match command_receiver.recv().await.unwrap() {
+        Command::Request { res_sender, .. } => res_sender.send(Err(Error::NoMarket)),
+        _ => panic!("unexpected command"),
+    }
+    let error = recv_json(&mut ws_stream).await;
+    assert_eq!(error["Error"]["id"], 4);
+}
+
+#[tokio::test]
+async fn pending_requests_limited() {
+    let (mut ws_stream, mut command_receiver, _notif_sender) = connect().await;
+    let batch = |ids: std::ops::RangeInclusive<usize>| {
+        let msgs = ids
+            .map(|id| format!(r#"{{"Req":{{"id":{id},"req":{{"ListAssets":{{}}}}}}}}"#))
+            .collect::<Vec<_>>();
+        Message::text(format!("[{}]", msgs.join(",")))
+    };
+
+    ws_stream.send(batch(1..=MAX_BATCH_LEN + 1)).await.unwrap();
+    let error = recv_json(&mut ws_stream).await;
+    assert_eq!(error["Error"]["id"], 0);
+    assert_eq!(error["Error"]["err"]["code"], "TooManyRequests");
+
+    ws_stream.send(batch(1..=MAX_PENDING_REQS)).await.unwrap();
+    let mut res_senders = Vec::new();
+    for _ in 0..MAX_PENDING_REQS {
+        match command_receiver.recv().await.unwrap() {
+            Command::Request { res_sender, .. } => res_senders.push(res_sender),
+            _ => panic!("unexpected command"),
+        }
+    }
+
+    let next_req = |id| {
+        Message::text(format!(
+            r#"{{"Req":{{"id":{id},"req":{{"ListAssets":{{}}}}}}}}"#
+        ))
+    };
+    ws_stream.send(next_req(1000)).await.unwrap();
+    let error = recv_json(&mut ws_stream).await;
+    assert_eq!(error["Error"]["id"], 1000);
+    assert_eq!(error["Error"]["err"]["code"], "TooManyRequests");
+    assert!(command_receiver.try_recv().is_err());
+
+    // Accepted again once a response is sent
+    res_senders.pop().unwrap().send(Err(Error::NoMarket));
+    let error = recv_json(&mut ws_stream).await;
+    assert_eq!(error["Error"]["err"]["code"], "NoMarket");
+    ws_stream.send(next_req(1001)).await.unwrap();
+    assert!(matches!(
+        command_receiver.recv().await.unwrap(),
+        Command::Request { .. }
+    ));
+}
+
+#[tokio::test]
+async fn login_required_if_token_configured() {
+    let (mut ws_stream, mut command_receiver) = start_client(Some("secret")).await;
+
+    ws_stream
+        .send(Message::text(r#"{"Req":{"id":5,"req":{"ListAssets":{}}}}"#))
+        .await
+        .unwrap();
+    let error = recv_json(&mut ws_stream).await;
+    assert_eq!(error["Error"]["id"], 5);
+    assert_eq!(error["Error"]["err"]["code"], "Unauthorized");
+
+    ws_stream
+        .send(Message::text(r#"{"Login":{"token":"wrong"}}"#))
+        .await
+        .unwrap();
+    let error = recv_json(&mut ws_stream).await;
+    assert_eq!(error["Error"]["err"]["code"], "Unauthorized");
+    // Not reported to the worker yet
+    assert!(command_receiver.try_recv().is_err());
+
+    ws_stream
+        .send(Message::text(r#"{"Login":{"token":"secret"}}"#))
+        .await
+        .unwrap();
+    let resp = recv_json(&mut ws_stream).await;
+    assert!(resp["LoggedIn"].is_object());
+    let _notif_sender = client_connected(&mut command_receiver).await;
+
+    // The connection is closed after too many failures
+    let (mut ws_stream, mut command_receiver) = start_client(Some("secret")).await;
+    for _ in 0..MAX_LOGIN_FAILURES {
+        ws_stream
+            .send(Message::text(r#"{"Login":{"token":"wrong"}}"#))
+            .await
+            .unwrap();
+        let error = recv_json(&mut ws_stream).await;
+        assert_eq!(error["Error"]["err"]["code"], "Unauthorized");
+    }
+    assert!(matches!(
+        ws_stream.next().await,
+        Some(Ok(Message::Close(_))) | Some(Err(_)) | None
+    ));
+    assert!(command_receiver.recv().await.is_none());
+
+    // The connection is closed if the login is not sent in time
+    let keepalive = Keepalive {
+        interval: DEFAULT_PING_INTERVAL,
+        timeout: Duration::from_millis(200),
+    };
+    let auth = Auth {
+        auth_token: Some("secret".to_owned()),
+        client_tokens: BTreeMap::new(),
+    };
+    let (mut ws_stream, mut command_receiver) = start_client_with_keepalive(auth, keepalive).await;
+    let msg = tokio::time::timeout(Duration::from_secs(5), ws_stream.next())
+        .await
+        .expect("the connection must be closed");
+    assert!(matches!(
+        msg,
+        Some(Ok(Message::Close(_))) | Some(Err(_)) | None
+    ));
+    assert!(command_receiver.recv().await.is_none());
+}
+
+#[tokio::test]
+async fn client_tokens_identify_clients() {
+    let keepalive = Keepalive {
+        interval: DEFAULT_PING_INTERVAL,
+        timeout: DEFAULT_PING_TIMEOUT,
+    };
+    let auth = Auth {
+        auth_token: Some("secret".to_owned()),
+        client_tokens: BTreeMap::from([("bob".to_owned(), "bob-secret".to_owned())]),
+    };
+
+    for (token, expected) in [("secret", None), ("bob-secret", Some("bob"))] {
+        let (mut ws_stream, mut command_receiver) =
+            start_client_with_keepalive(auth.clone(), keepalive).await;
+        ws_stream
+            .send(Message::text(format!(
+                r#"{{"Login":{{"token":"{token}"}}}}"#
+            )))
+            .await
+            .unwrap();
+        let resp = recv_json(&mut ws_stream).await;
+        assert!(resp["LoggedIn"].is_object());
+        match command_receiver.recv().await {
+            Some(Command::ClientConnected {
+                client_name,
+                client_token,
+                ..
+            }) => {
+                assert_eq!(client_name.as_deref(), expected);
+                assert_eq!(client_token, expected.is_some());
+            }
+            _ => panic!("unexpected command"),
+        }
+    }
+
+    // Only the client tokens are configured
+    let auth = Auth {
+        auth_token: None,
+        client_tokens: auth.client_tokens,
+    };
+    let (mut ws_stream, mut command_receiver) = start_client_with_keepalive(auth, keepalive).await;
+    ws_stream
+        .send(Message::text(r#"{"Login":{"token":"secret"}}"#))
+        .await
+        .unwrap();
+    let error = recv_json(&mut ws_stream).await;
+    assert_eq!(error["Error"]["err"]["code"], "Unauthorized");
+    assert!(command_receiver.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn silent_clients_disconnected() {
+    let keepalive = Keepalive {
+        interval: Duration::from_millis(100),
+        timeout: Duration::from_millis(200),
+    };
+    let (mut ws_stream, mut command_receiver) =
+        start_client_with_keepalive(Auth::default(), keepalive).await;
+    let _notif_sender = client_connected(&mut command_receiver).await;
+
+    // Client pings are answered
+    ws_stream
+        .send(Message::Ping(b"abc".to_vec().into()))
+        .await
+        .unwrap();
+    match ws_stream.next().await {
+        Some(Ok(Message::Pong(payload))) => assert_eq!(payload.as_ref(), b"abc"),
+        msg => panic!("unexpected message: {msg:?}"),
+    }
+
+    // Server pings are answered by the client library while the stream is polled
+    for _ in 0..3 {
+        assert!(matches!(ws_stream.next().await, Some(Ok(Message::Ping(_)))));
+    }
+    assert!(command_receiver.try_recv().is_err());
+
+    // Not polled anymore, so the pings stay unanswered
+    let command = tokio::time::timeout(Duration::from_secs(5), command_receiver.recv())
+        .await
+        .expect("the connection must be closed");
+    assert!(matches!(
+        command,
+        Some(Command::ClientDisconnected {
+            client_id: ClientId(1)
+        })
+    ));
+}